@@ -0,0 +1,159 @@
+use std::hash::Hasher;
+
+use glam::Vec2;
+
+use crate::libs::ecs::World;
+use crate::libs::ecs::game_loop::GameLoop;
+use crate::libs::ecs::hash::StateHasher;
+use crate::libs::ecs::schedule::Schedule;
+use crate::libs::input::Input;
+use crate::libs::input::bindings::ActionId;
+use crate::libs::input::snapshot::InputFrame;
+use crate::libs::rendering::camera::Camera;
+use crate::libs::rendering::canvas::WebGlCanvas;
+use crate::libs::rendering::colour::Colour;
+use crate::libs::rendering::graph::{PassContext, RenderGraph};
+use crate::libs::rendering::shapes::tessellate::StrokeStyle;
+use crate::libs::rendering::shapes::{ShapeBatch, ShapeRenderer};
+use crate::libs::replay::Recorder;
+use crate::libs::types::errors::ErrorStr;
+use crate::libs::types::rng::Rng;
+use crate::libs::types::shared::{SharedRefCell, shared_ref_cell};
+use crate::log_info;
+
+const LOG_TARGET: &str = "app::demo::game";
+pub const TICK_HZ: u16 = 60;
+const SEED: u64 = 0xdead_beef;
+// share of the distance to the pointer covered each tick
+const FOLLOW: f32 = 0.15;
+const MARKER_RADIUS: f32 = 12.0;
+const MARKER_COLOURS: [Colour; 4] = [Colour::CYAN, Colour::MAGENTA, Colour::YELLOW, Colour::GREEN];
+
+#[derive(Debug, Clone, Copy)]
+pub struct DemoActions {
+    pub select: ActionId,
+    pub save_replay: ActionId,
+}
+
+// in canvas pixels like the pointer, so the simulation doesn't depend on the camera
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Marker {
+    position: Vec2,
+    colour: usize,
+}
+
+// follows the pointer while select is held, a new click picks a random colour
+fn follow_pointer(world: &mut World, select: ActionId) -> Result<(), ErrorStr> {
+    let frame = if let Some(frame) = world.resource::<InputFrame>() {
+        *frame
+    } else {
+        return Err(ErrorStr::new("No input frame"));
+    };
+    let colour = match (frame.just_pressed(select), world.resource_mut::<Rng>()) {
+        (true, Some(rng)) => Some(rng.next_u32() as usize % MARKER_COLOURS.len()),
+        _ => None,
+    };
+    let target = match (frame.pressed(select), frame.current.pointer) {
+        (true, Some([x, y])) => Some(Vec2::new(x as f32, y as f32)),
+        _ => None,
+    };
+    world.each::<&mut Marker, _>(|_, marker| {
+        if let Some(colour) = colour {
+            marker.colour = colour;
+        }
+        if let Some(target) = target {
+            marker.position += (target - marker.position) * FOLLOW;
+        }
+    })
+}
+
+// what every peer has to agree on, for replays and rollback checksums
+pub fn hash(world: &World, hasher: &mut StateHasher) -> Result<(), ErrorStr> {
+    world.hash_component_with::<Marker, _>(hasher, |marker, hasher| {
+        hasher.write_f32(marker.position.x);
+        hasher.write_f32(marker.position.y);
+        hasher.write_u32(marker.colour as u32);
+    })?;
+    if let Some(rng) = world.resource::<Rng>() {
+        hasher.write_u64(rng.state());
+    }
+    Ok(())
+}
+
+// the whole match so far, from a fresh world
+fn save_replay(world: &World, recorder: &Recorder) -> Result<(), ErrorStr> {
+    let replay = recorder.clone().finish(world, hash)?;
+    log_info!(LOG_TARGET, "Saving a replay of {} ticks", replay.len());
+    replay.download()
+}
+
+// the demo's simulation, run by the canvas' game loop. every tick's input is recorded, the save
+// replay action downloads the recording. returns the world so other parts can read its input
+pub fn add_tasks(
+    web_gl_canvas: &WebGlCanvas<'static>,
+    graph: &mut RenderGraph,
+    camera: SharedRefCell<Camera>,
+    input: SharedRefCell<Input>,
+    actions: DemoActions,
+) -> Result<SharedRefCell<World>, ErrorStr> {
+    let mut world = World::new();
+    let marker = world.spawn()?;
+    world.insert(
+        marker,
+        Marker {
+            position: Vec2::ZERO,
+            colour: 0,
+        },
+    )?;
+    let recorder = shared_ref_cell(Recorder::start(&mut world, SEED, 1, TICK_HZ)?);
+    let world = shared_ref_cell(world);
+
+    let mut schedule = Schedule::new();
+    schedule.add_system(Input::into_system(input, "Input"));
+    schedule.add_system(Recorder::into_system(recorder.clone(), "Record"));
+    let schedule = schedule
+        .with_system(
+            move |world: &mut World| follow_pointer(world, actions.select),
+            "Follow pointer",
+        )
+        .with_system(
+            move |world: &mut World| {
+                let save = world
+                    .resource::<InputFrame>()
+                    .is_some_and(|frame| frame.just_pressed(actions.save_replay));
+                if save {
+                    save_replay(world, &recorder.borrow())?;
+                }
+                Ok(())
+            },
+            "Save replay",
+        );
+    web_gl_canvas.set_game_loop(GameLoop::new(world.clone(), schedule, TICK_HZ as f64));
+
+    let draw_world = world.clone();
+    let mut renderer: Option<ShapeRenderer> = None;
+    let mut batch = ShapeBatch::new();
+    let backbuffer = graph.backbuffer();
+    graph.add_pass(
+        "Marker",
+        &[],
+        &[backbuffer],
+        move |pass: &mut PassContext| {
+            if renderer.is_none() {
+                renderer = Some(ShapeRenderer::new(pass.gl())?);
+            }
+            let camera = camera.borrow();
+            batch.clear();
+            draw_world.borrow().each::<&Marker, _>(|_, marker| {
+                let center = camera.screen_to_world(marker.position);
+                let style = StrokeStyle::new(3.0, MARKER_COLOURS[marker.colour]);
+                batch.stroke_circle(center, MARKER_RADIUS, &style);
+            })?;
+            if let Some(renderer) = renderer.as_mut() {
+                renderer.draw(pass.gl(), camera.view_projection(), &batch);
+            }
+            Ok(())
+        },
+    );
+    Ok(world)
+}
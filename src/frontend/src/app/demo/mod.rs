@@ -0,0 +1,95 @@
+pub mod game;
+pub mod particles;
+pub mod scene;
+pub mod session;
+pub mod shapes;
+pub mod tilemap;
+
+use glam::Vec2;
+use web_sys::ResizeObserverEntry;
+
+use crate::app::demo::game::DemoActions;
+use crate::libs::input::Input;
+use crate::libs::input::bindings::{Binding, InputMap};
+use crate::libs::net::conditioned::ConditionsHandle;
+use crate::libs::rendering::camera::Camera;
+use crate::libs::rendering::canvas::{RafTask, RafTime, ResizeTask, WebGlCanvas};
+use crate::libs::rendering::graph::RenderGraph;
+use crate::libs::types::errors::ErrorStr;
+use crate::libs::types::shared::{SharedRefCell, shared_ref_cell};
+use crate::log_error;
+
+const LOG_TARGET: &str = "app::demo";
+const SELECT: &str = "Select";
+const SAVE_REPLAY: &str = "Save replay";
+
+// the engine's subsystems running together on one canvas, drawn over the triangle. world units
+// are canvas pixels with the origin in the middle, y up. each part updates in its own raf task
+// and draws in a render graph pass, the graph runs after all of them
+pub fn add_tasks(web_gl_canvas: &WebGlCanvas<'static>, conditions: ConditionsHandle) {
+    let camera = shared_ref_cell(Camera::orthographic(Vec2::ZERO, 1.0, Vec2::ONE));
+    let mut graph = RenderGraph::new();
+    web_gl_canvas.add_resize_task(camera_resize_task(camera.clone()));
+    web_gl_canvas.add_raf_task(debug_draw_task(camera.clone()));
+    tilemap::add_tasks(web_gl_canvas, &mut graph, camera.clone());
+    if let Err(error) = scene::add_tasks(web_gl_canvas, &mut graph, camera.clone()) {
+        log_error!(LOG_TARGET, "{}", error);
+    }
+    if let Err(error) = add_game_tasks(web_gl_canvas, &mut graph, camera.clone()) {
+        log_error!(LOG_TARGET, "{}", error);
+    }
+    particles::add_tasks(web_gl_canvas, &mut graph, camera);
+    session::add_tasks(web_gl_canvas, conditions);
+    if let Err(error) = graph.compile() {
+        log_error!(LOG_TARGET, "{}", error);
+    }
+    web_gl_canvas.add_raf_task(graph.into_raf_task("Demo render graph"));
+}
+
+// the simulation, plus the shapes that are picked with its input
+fn add_game_tasks(
+    web_gl_canvas: &WebGlCanvas<'static>,
+    graph: &mut RenderGraph,
+    camera: SharedRefCell<Camera>,
+) -> Result<(), ErrorStr> {
+    let map = InputMap::new()
+        .with_action(SELECT, vec![Binding::Pointer { button: 0 }])?
+        .with_action(SAVE_REPLAY, vec![Binding::key("KeyR")])?;
+    let actions = match (map.action(SELECT), map.action(SAVE_REPLAY)) {
+        (Some(select), Some(save_replay)) => DemoActions {
+            select,
+            save_replay,
+        },
+        _ => return Err(ErrorStr::new("Demo input map is missing an action")),
+    };
+    let input = shared_ref_cell(Input::new(map));
+    web_gl_canvas.add_init_task(Input::into_init_task(input.clone(), "Demo input"));
+    let world = game::add_tasks(web_gl_canvas, graph, camera.clone(), input, actions)?;
+    shapes::add_tasks(web_gl_canvas, graph, camera, world, actions.select);
+    Ok(())
+}
+
+// debug primitives pushed by the demo are in world space
+fn debug_draw_task(camera: SharedRefCell<Camera>) -> RafTask<'static> {
+    RafTask::new(
+        move |canvas: &WebGlCanvas, _time: RafTime| {
+            let view_projection = camera.borrow().view_projection();
+            canvas.debug_draw().set_view_projection(view_projection);
+            Ok(())
+        },
+        "Demo debug draw",
+    )
+}
+
+fn camera_resize_task(camera: SharedRefCell<Camera>) -> ResizeTask<'static> {
+    ResizeTask::new(
+        move |_canvas: &WebGlCanvas, resize: &ResizeObserverEntry| -> Result<(), ErrorStr> {
+            let rect = resize.content_rect();
+            camera
+                .borrow_mut()
+                .set_viewport(rect.width() as f32, rect.height() as f32);
+            Ok(())
+        },
+        "Demo camera resize",
+    )
+}
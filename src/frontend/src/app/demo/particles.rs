@@ -0,0 +1,158 @@
+use std::collections::BTreeMap;
+
+use glam::{Vec2, Vec3};
+use wasm_bindgen_futures::spawn_local;
+use web_sys::WebGl2RenderingContext;
+
+use crate::libs::rendering::camera::Camera;
+use crate::libs::rendering::canvas::{InitTask, RafTask, RafTime, WebGlCanvas};
+use crate::libs::rendering::graph::{PassContext, RenderGraph};
+use crate::libs::rendering::particles::effect::{ParticleEffect, load_effect};
+use crate::libs::rendering::particles::{EffectHandle, ParticleBackend, ParticleSystem};
+use crate::libs::types::errors::ErrorStr;
+use crate::libs::types::shared::{SharedRefCell, shared_ref_cell};
+use crate::{log_error, log_info};
+
+const LOG_TARGET: &str = "app::demo::particles";
+// copied next to the wasm by trunk, see index.html
+const DUST: &str = "effects/dust.json";
+const EXPLOSION: &str = "effects/explosion.json";
+const TRAIL: &str = "effects/trail.json";
+const TRAIL_RADIUS: f32 = 150.0;
+// radians per second
+const TRAIL_SPEED: f32 = 1.5;
+const EXPLOSION_INTERVAL: f32 = 3.0;
+
+// the canvas' particle system and the effects it plays, filled in as the effect files arrive
+#[derive(Debug, Default)]
+pub struct Effects {
+    system: Option<ParticleSystem>,
+    loaded: BTreeMap<&'static str, ParticleEffect>,
+    dust: Option<EffectHandle>,
+    trail: Option<EffectHandle>,
+    time: f32,
+    next_explosion: f32,
+}
+
+impl Effects {
+    // a one off burst, does nothing until the system and the effect are ready
+    pub fn explode(&mut self, gl: &WebGl2RenderingContext, position: Vec2) -> Result<(), ErrorStr> {
+        let (system, effect) = match (self.system.as_mut(), self.loaded.get(EXPLOSION)) {
+            (Some(system), Some(effect)) => (system, effect),
+            _ => return Ok(()),
+        };
+        system.spawn(gl, effect, position.extend(0.0))?;
+        Ok(())
+    }
+
+    #[inline]
+    pub fn trail_position(&self) -> Vec2 {
+        Vec2::from_angle(self.time * TRAIL_SPEED) * TRAIL_RADIUS
+    }
+
+    // delta in seconds
+    fn update(&mut self, gl: &WebGl2RenderingContext, delta: f32) -> Result<(), ErrorStr> {
+        self.time += delta;
+        let trail_position = self.trail_position();
+        let system = if let Some(system) = self.system.as_mut() {
+            system
+        } else {
+            return Ok(());
+        };
+        if let (None, Some(effect)) = (self.dust, self.loaded.get(DUST)) {
+            self.dust = Some(system.spawn(gl, effect, Vec3::ZERO)?);
+        }
+        if let (None, Some(effect)) = (self.trail, self.loaded.get(TRAIL)) {
+            self.trail = Some(system.spawn(gl, effect, trail_position.extend(0.0))?);
+        }
+        if let Some(trail) = self.trail {
+            system.set_origin(trail, trail_position.extend(0.0));
+        }
+        system.update(gl, delta)?;
+        if self.time >= self.next_explosion {
+            self.next_explosion = self.time + EXPLOSION_INTERVAL;
+            self.explode(gl, trail_position)?;
+        }
+        Ok(())
+    }
+
+    fn draw(&self, gl: &WebGl2RenderingContext, camera: &Camera) {
+        if let Some(system) = self.system.as_ref() {
+            system.draw(gl, camera);
+        }
+    }
+}
+
+// starts fetching the effects, then builds the system once there's a context, updates it every
+// frame and draws it in its own pass
+pub fn add_tasks(
+    web_gl_canvas: &WebGlCanvas<'static>,
+    graph: &mut RenderGraph,
+    camera: SharedRefCell<Camera>,
+) -> SharedRefCell<Effects> {
+    let effects = shared_ref_cell(Effects::default());
+    for url in [DUST, EXPLOSION, TRAIL] {
+        let effects = effects.clone();
+        spawn_local(async move {
+            match load_effect(url).await {
+                Ok(effect) => {
+                    effects.borrow_mut().loaded.insert(url, effect);
+                }
+                Err(error) => log_error!(LOG_TARGET, "{}", error),
+            }
+        });
+    }
+
+    let init_effects = effects.clone();
+    web_gl_canvas.add_init_task(InitTask::new(
+        move |canvas: &WebGlCanvas| {
+            let context = canvas.get_context();
+            let gl = if let Some(gl) = context.as_ref() {
+                gl
+            } else {
+                return false;
+            };
+            match ParticleSystem::new(gl, ParticleBackend::TransformFeedback) {
+                Ok(system) => {
+                    log_info!(LOG_TARGET, "Particles running on {:?}", system.backend());
+                    init_effects.borrow_mut().system = Some(system);
+                    true
+                }
+                Err(error) => {
+                    log_error!(LOG_TARGET, "{}", error);
+                    false
+                }
+            }
+        },
+        "Particles init",
+    ));
+
+    let raf_effects = effects.clone();
+    web_gl_canvas.add_raf_task(RafTask::new(
+        move |canvas: &WebGlCanvas, time: RafTime| {
+            let context = canvas.get_context();
+            let gl = if let Some(gl) = context.as_ref() {
+                gl
+            } else {
+                return Err(ErrorStr::new("Particles have no GL context"));
+            };
+            raf_effects
+                .borrow_mut()
+                .update(gl, (time.delta() / 1000.0) as f32)
+        },
+        "Particles",
+    ));
+
+    let backbuffer = graph.backbuffer();
+    let draw_effects = effects.clone();
+    graph.add_pass(
+        "Particles",
+        &[],
+        &[backbuffer],
+        move |pass: &mut PassContext| {
+            draw_effects.borrow().draw(pass.gl(), &camera.borrow());
+            Ok(())
+        },
+    );
+    effects
+}
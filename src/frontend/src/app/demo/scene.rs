@@ -0,0 +1,206 @@
+use glam::{Quat, Vec2, Vec3};
+use web_sys::WebGl2RenderingContext;
+
+use crate::libs::rendering::camera::Camera;
+use crate::libs::rendering::canvas::{RafTask, RafTime, WebGlCanvas};
+use crate::libs::rendering::colour::Colour;
+use crate::libs::rendering::graph::{PassContext, RenderGraph};
+use crate::libs::rendering::shapes::{ShapeBatch, ShapeRenderer};
+use crate::libs::scene::bounds::Aabb;
+use crate::libs::scene::queue::{MaterialId, MeshId, RenderItem, Renderable, SceneDrawer};
+use crate::libs::scene::transform::Transform;
+use crate::libs::scene::{NodeId, Scene};
+use crate::libs::types::errors::ErrorStr;
+use crate::libs::types::shared::{SharedRefCell, shared_ref_cell};
+
+// unit outlines, scaled by their node
+const SQUARE: MeshId = MeshId(0);
+const CIRCLE: MeshId = MeshId(1);
+const CIRCLE_POINTS: usize = 24;
+const MATERIALS: [Colour; 3] = [
+    Colour::rgb(1.0, 0.6, 0.1),
+    Colour::rgb(0.3, 0.6, 1.0),
+    Colour::rgba(0.8, 0.8, 0.8, 0.6),
+];
+const ORIGIN: Vec2 = Vec2::new(-250.0, -120.0);
+// radians per second
+const SUN_SPEED: f32 = 0.4;
+const PLANET_SPEED: f32 = 1.2;
+
+// fills the outline of each mesh item into a shape batch, drawn in one go at the end
+#[derive(Debug)]
+struct ShapeSceneDrawer {
+    renderer: Option<ShapeRenderer>,
+    batch: ShapeBatch,
+    meshes: [Vec<Vec2>; 2],
+    points: Vec<Vec2>,
+}
+
+impl ShapeSceneDrawer {
+    fn new() -> Self {
+        let circle = (0..CIRCLE_POINTS)
+            .map(|i| {
+                Vec2::from_angle(std::f32::consts::TAU * i as f32 / CIRCLE_POINTS as f32) * 0.5
+            })
+            .collect();
+        let square = vec![
+            Vec2::new(-0.5, -0.5),
+            Vec2::new(0.5, -0.5),
+            Vec2::new(0.5, 0.5),
+            Vec2::new(-0.5, 0.5),
+        ];
+        Self {
+            renderer: None,
+            batch: ShapeBatch::new(),
+            meshes: [square, circle],
+            points: Vec::new(),
+        }
+    }
+}
+
+impl SceneDrawer for ShapeSceneDrawer {
+    fn begin(&mut self, gl: &WebGl2RenderingContext, _camera: &Camera) -> Result<(), ErrorStr> {
+        if self.renderer.is_none() {
+            self.renderer = Some(ShapeRenderer::new(gl)?);
+        }
+        self.batch.clear();
+        Ok(())
+    }
+
+    fn draw(
+        &mut self,
+        _gl: &WebGl2RenderingContext,
+        _camera: &Camera,
+        item: &RenderItem,
+    ) -> Result<(), ErrorStr> {
+        let (mesh, material) = match item.renderable {
+            Renderable::Mesh { mesh, material, .. } => (mesh, material),
+            _ => return Ok(()),
+        };
+        let (outline, colour) = match (
+            self.meshes.get(mesh.0 as usize),
+            MATERIALS.get(material.0 as usize),
+        ) {
+            (Some(outline), Some(colour)) => (outline, *colour),
+            _ => {
+                return Err(ErrorStr::new(format!(
+                    "Unknown {:?} or {:?}",
+                    mesh, material
+                )));
+            }
+        };
+        self.points.clear();
+        self.points.extend(
+            outline
+                .iter()
+                .map(|point| item.world.transform_point3(point.extend(0.0)).truncate()),
+        );
+        self.batch.fill_polygon(&self.points, colour);
+        Ok(())
+    }
+
+    fn end(&mut self, gl: &WebGl2RenderingContext, camera: &Camera) -> Result<(), ErrorStr> {
+        if let Some(renderer) = self.renderer.as_mut() {
+            renderer.draw(gl, camera.view_projection(), &self.batch);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct Orbits {
+    sun: NodeId,
+    planet: NodeId,
+    time: f32,
+}
+
+fn mesh(mesh: MeshId, material: usize) -> Option<Renderable> {
+    Some(Renderable::Mesh {
+        mesh,
+        material: MaterialId(material as u32),
+        transparent: MATERIALS[material].a < 1.0,
+    })
+}
+
+// a sun with a planet and its moon. only the sun and the planet spin, the moon is carried round
+// by the hierarchy
+fn build(scene: &mut Scene) -> Result<Orbits, ErrorStr> {
+    let unit = Some(Aabb::from_center(Vec3::ZERO, Vec3::new(0.5, 0.5, 0.0)));
+    let sun = scene.create_node("Sun");
+    scene.set_local(sun, Transform::from_2d(ORIGIN, 0.0, Vec2::ONE));
+    let sun_body = scene.create_child(sun, "Sun body")?;
+    scene.set_local(
+        sun_body,
+        Transform::from_2d(Vec2::ZERO, 0.0, Vec2::splat(48.0)),
+    );
+    scene.set_renderable(sun_body, mesh(SQUARE, 0));
+    scene.set_bounds(sun_body, unit);
+
+    let planet = scene.create_child(sun, "Planet")?;
+    scene.set_local(
+        planet,
+        Transform::from_2d(Vec2::new(90.0, 0.0), 0.0, Vec2::ONE),
+    );
+    let planet_body = scene.create_child(planet, "Planet body")?;
+    scene.set_local(
+        planet_body,
+        Transform::from_2d(Vec2::ZERO, 0.0, Vec2::splat(24.0)),
+    );
+    scene.set_renderable(planet_body, mesh(CIRCLE, 1));
+    scene.set_bounds(planet_body, unit);
+
+    let moon = scene.create_child(planet, "Moon")?;
+    scene.set_local(
+        moon,
+        Transform::from_2d(Vec2::new(28.0, 0.0), 0.0, Vec2::splat(10.0)),
+    );
+    scene.set_renderable(moon, mesh(CIRCLE, 2));
+    scene.set_bounds(moon, unit);
+    Ok(Orbits {
+        sun,
+        planet,
+        time: 0.0,
+    })
+}
+
+// spins the scene every frame and draws it in its own pass
+pub fn add_tasks(
+    web_gl_canvas: &WebGlCanvas<'static>,
+    graph: &mut RenderGraph,
+    camera: SharedRefCell<Camera>,
+) -> Result<(), ErrorStr> {
+    let mut scene = Scene::new();
+    let mut orbits = build(&mut scene)?;
+    let scene = shared_ref_cell(scene);
+
+    let update_scene = scene.clone();
+    web_gl_canvas.add_raf_task(RafTask::new(
+        move |_canvas: &WebGlCanvas, time: RafTime| {
+            orbits.time += (time.delta() / 1000.0) as f32;
+            let mut scene = update_scene.borrow_mut();
+            let (sun_angle, planet_angle) = (orbits.time * SUN_SPEED, orbits.time * PLANET_SPEED);
+            scene.update_local(orbits.sun, |local| {
+                local.rotation = Quat::from_rotation_z(sun_angle);
+            });
+            scene.update_local(orbits.planet, |local| {
+                local.rotation = Quat::from_rotation_z(planet_angle);
+            });
+            Ok(())
+        },
+        "Scene",
+    ));
+
+    let backbuffer = graph.backbuffer();
+    let mut drawer = ShapeSceneDrawer::new();
+    graph.add_pass(
+        "Scene",
+        &[],
+        &[backbuffer],
+        move |pass: &mut PassContext| {
+            scene
+                .borrow_mut()
+                .draw(pass.gl(), &camera.borrow(), &mut drawer)
+        },
+    );
+    Ok(())
+}
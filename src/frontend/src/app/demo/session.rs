@@ -0,0 +1,375 @@
+use web_sys::UrlSearchParams;
+
+use crate::app::demo::game::{self, TICK_HZ};
+use crate::libs::input::snapshot::InputSnapshot;
+use crate::libs::net::PeerId;
+use crate::libs::net::SyncMode;
+use crate::libs::net::authority::client::AuthorityClient;
+use crate::libs::net::authority::host::AuthorityHost;
+use crate::libs::net::authority::{AuthorityConfig, Replicated};
+use crate::libs::net::clock::{ClockConfig, ClockSync};
+use crate::libs::net::conditioned::{ConditionedTransport, ConditionsHandle};
+use crate::libs::net::lobby::{Lobby, LobbyEvent};
+use crate::libs::net::protocol::handshake::{Handshake, PeerEvent};
+use crate::libs::net::protocol::{Message, RoomSettings};
+use crate::libs::net::rollback::{RollbackConfig, RollbackSession};
+use crate::libs::net::signaling::protocol::PeerInfo;
+use crate::libs::net::signaling::websocket::WebSocketSignaling;
+use crate::libs::net::signaling::{
+    SignalingClient, SignalingConfig, SignalingEvent, SignalingState, apply_event,
+};
+use crate::libs::net::webrtc::{DEFAULT_STUN_SERVER, IceServer, WebRtcTransport};
+use crate::libs::rendering::canvas::{RafTask, RafTime, WebGlCanvas};
+use crate::libs::types::errors::ErrorStr;
+use crate::{log_debug, log_info, log_warn};
+
+const LOG_TARGET: &str = "app::demo::session";
+// query parameters, e.g. ?room=test&name=Ada&signal=ws://127.0.0.1:8787/ws
+const ROOM_KEY: &str = "room";
+const NAME_KEY: &str = "name";
+const SIGNAL_KEY: &str = "signal";
+const DEFAULT_NAME: &str = "Player";
+// on the page's own host when no signal parameter is given
+const SIGNAL_PATH: &str = "/ws";
+
+type Predict = fn(&mut Replicated, &InputSnapshot);
+
+// nothing in the demo's world is player controlled over the network yet
+fn predict(_: &mut Replicated, _: &InputSnapshot) {}
+
+// how a started match stays in sync. it follows the room, who is in it and who hosts, the
+// demo's game loop still runs its own world
+enum MatchSync {
+    Rollback(RollbackSession),
+    Host(AuthorityHost),
+    Client(AuthorityClient<Predict>),
+}
+
+impl MatchSync {
+    fn start(lobby: &Lobby, tick: u64) -> Result<Self, ErrorStr> {
+        let local_player = if let Some(local_player) = lobby.local_player() {
+            local_player
+        } else {
+            return Err(ErrorStr::new("Started without a player slot"));
+        };
+        let config = AuthorityConfig {
+            tick_hz: TICK_HZ as f64,
+            ..AuthorityConfig::default()
+        };
+        let sync = match (lobby.settings().mode, lobby.host()) {
+            (SyncMode::Rollback, _) => {
+                let config = RollbackConfig {
+                    topology: lobby.topology(),
+                    ..RollbackConfig::default()
+                };
+                let peers = lobby.players();
+                let session = RollbackSession::new(config, tick, local_player, peers, game::hash)?;
+                MatchSync::Rollback(session)
+            }
+            (SyncMode::Authoritative, _) if lobby.is_host() => {
+                let mut host = AuthorityHost::new(config, local_player);
+                for (peer, slot) in lobby.players() {
+                    host.add_peer(peer, slot)?;
+                }
+                MatchSync::Host(host)
+            }
+            (SyncMode::Authoritative, Some(host)) => {
+                let predict = predict as Predict;
+                MatchSync::Client(AuthorityClient::new(config, host, local_player, predict))
+            }
+            (SyncMode::Authoritative, None) => {
+                return Err(ErrorStr::new("Started without a host"));
+            }
+        };
+        Ok(sync)
+    }
+
+    fn handle_message(
+        &mut self,
+        peer: PeerId,
+        message: &Message,
+        now_ms: f64,
+    ) -> Result<bool, ErrorStr> {
+        match self {
+            MatchSync::Rollback(session) => session.handle_message(peer, message),
+            MatchSync::Host(host) => host.handle_message(peer, message),
+            MatchSync::Client(client) => client.handle_message(peer, message, now_ms),
+        }
+    }
+
+    // so nothing waits on their input any more
+    fn remove_peer(&mut self, peer: PeerId) {
+        match self {
+            MatchSync::Rollback(session) => {
+                session.remove_peer(peer);
+            }
+            MatchSync::Host(host) => {
+                host.remove_peer(peer);
+            }
+            MatchSync::Client(_) => {}
+        }
+    }
+
+    // the new host takes over from the newest snapshot, everyone else follows it. a rollback
+    // match takes the lobby's topology, a mesh once the old host is gone
+    fn change_host(self, lobby: &Lobby, canvas: &WebGlCanvas) -> Result<Self, ErrorStr> {
+        let host = if let Some(host) = lobby.host() {
+            host
+        } else {
+            return Ok(self);
+        };
+        let sync = match self {
+            MatchSync::Client(client) if lobby.is_host() => {
+                let game_loop = canvas.game_loop();
+                let world = if let Some(game_loop) = game_loop.as_ref() {
+                    game_loop.world().clone()
+                } else {
+                    return Err(ErrorStr::new("No game loop to take over"));
+                };
+                let mut promoted = client.promote(&mut world.borrow_mut())?;
+                for (peer, slot) in lobby.players() {
+                    promoted.add_peer(peer, slot)?;
+                }
+                MatchSync::Host(promoted)
+            }
+            MatchSync::Client(mut client) => {
+                client.set_host(host);
+                MatchSync::Client(client)
+            }
+            MatchSync::Rollback(mut session) => {
+                session.set_topology(lobby.topology())?;
+                MatchSync::Rollback(session)
+            }
+            sync => sync,
+        };
+        Ok(sync)
+    }
+}
+
+// from the signaling server through to the lobby. the transport is made on every join, the
+// room hands out a new id after each reconnect. everything the lobby and clock send or receive
+// goes through the network conditions menu's settings
+struct Session {
+    signaling: SignalingClient<WebSocketSignaling>,
+    name: String,
+    conditions: ConditionsHandle,
+    transport: Option<ConditionedTransport<WebRtcTransport>>,
+    handshake: Handshake,
+    clock: ClockSync,
+    lobby: Option<Lobby>,
+    sync: Option<MatchSync>,
+}
+
+impl Session {
+    fn new(config: SignalingConfig, url: String, conditions: ConditionsHandle) -> Self {
+        let seed = js_sys::Date::now() as u64;
+        Self {
+            name: config.name.clone(),
+            conditions,
+            handshake: Handshake::new(config.name.clone()),
+            clock: clock_sync(),
+            signaling: SignalingClient::new(WebSocketSignaling::new(url), config, seed),
+            transport: None,
+            lobby: None,
+            sync: None,
+        }
+    }
+
+    // call every frame, after the game loop advanced
+    fn update(&mut self, canvas: &WebGlCanvas, time: RafTime) -> Result<(), ErrorStr> {
+        let now_ms = time.timestamp();
+        if self.signaling.state() == SignalingState::Idle {
+            self.signaling.start(now_ms);
+        }
+        self.signaling.update(now_ms);
+        while let Some(event) = self.signaling.recv() {
+            if let SignalingEvent::Joined { id, mode, peers } = &event {
+                self.joined(*id, *mode, peers);
+            }
+            // one bad signal shouldn't hold up the rest
+            let result = match self.transport.as_mut() {
+                Some(transport) => apply_event(transport.inner_mut(), &event),
+                None => Ok(()),
+            };
+            if let Err(error) = result {
+                log_warn!(LOG_TARGET, "{}", error);
+            }
+        }
+        let transport = if let Some(transport) = self.transport.as_mut() {
+            transport
+        } else {
+            return Ok(());
+        };
+        self.signaling.flush_signals(transport.inner_mut())?;
+        transport.update(now_ms)?;
+        self.clock.update(transport, now_ms)?;
+        // fractional, the peer's tick is too
+        let tick = canvas.game_loop().as_ref().map_or(0.0, |game_loop| {
+            game_loop.world().borrow().tick() as f64 + time.alpha()
+        });
+        for event in self.handshake.poll(transport) {
+            match &event {
+                PeerEvent::Error { peer, error } => log_warn!(LOG_TARGET, "{:?}: {}", peer, error),
+                PeerEvent::Message { peer, message, .. } => {
+                    match self
+                        .clock
+                        .handle_message(transport, *peer, message, now_ms, tick as u64)
+                    {
+                        Ok(true) => continue,
+                        Ok(false) => {}
+                        Err(error) => log_warn!(LOG_TARGET, "{}", error),
+                    }
+                    let handled = match self.sync.as_mut() {
+                        Some(sync) => sync.handle_message(*peer, message, now_ms),
+                        None => Ok(false),
+                    };
+                    match handled {
+                        Ok(true) => continue,
+                        Ok(false) => {}
+                        Err(error) => log_warn!(LOG_TARGET, "{}", error),
+                    }
+                }
+                _ => {}
+            }
+            let result = match self.lobby.as_mut() {
+                Some(lobby) => lobby.handle_event(transport, &event),
+                None => Ok(false),
+            };
+            if let Err(error) = result {
+                log_warn!(LOG_TARGET, "{}", error);
+            }
+        }
+        let events: Vec<LobbyEvent> = match self.lobby.as_mut() {
+            Some(lobby) => std::iter::from_fn(|| lobby.recv()).collect(),
+            None => Vec::new(),
+        };
+        for event in events {
+            log_info!(LOG_TARGET, "{:?}", event);
+            if let Err(error) = self.follow_room(canvas, &event) {
+                log_warn!(LOG_TARGET, "{}", error);
+            }
+        }
+        self.follow_host(canvas, tick, now_ms);
+        Ok(())
+    }
+
+    // keeps the match's sync in step with the room
+    fn follow_room(&mut self, canvas: &WebGlCanvas, event: &LobbyEvent) -> Result<(), ErrorStr> {
+        let lobby = if let Some(lobby) = self.lobby.as_ref() {
+            lobby
+        } else {
+            return Ok(());
+        };
+        match event {
+            LobbyEvent::Started { tick } => self.sync = Some(MatchSync::start(lobby, *tick)?),
+            LobbyEvent::MemberLeft(member) => {
+                if let Some(sync) = self.sync.as_mut() {
+                    sync.remove_peer(member.peer);
+                }
+            }
+            LobbyEvent::HostChanged { .. } => {
+                if let Some(sync) = self.sync.take() {
+                    self.sync = Some(sync.change_host(lobby, canvas)?);
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    // everyone but the host runs its simulation a little faster or slower until it's on the
+    // host's tick, and stays there
+    fn follow_host(&self, canvas: &WebGlCanvas, tick: f64, now_ms: f64) {
+        let host = match self.lobby.as_ref() {
+            Some(lobby) if !lobby.is_host() => lobby.host(),
+            _ => None,
+        };
+        let time_scale = host.map_or(1.0, |host| self.clock.time_scale(host, tick, now_ms, 0.0));
+        if let Some(game_loop) = canvas.game_loop().as_mut() {
+            game_loop.set_time_scale(time_scale);
+        }
+    }
+
+    // whoever finds the room empty hosts it
+    fn joined(&mut self, id: PeerId, mode: SyncMode, peers: &[PeerInfo]) {
+        log_debug!(LOG_TARGET, "Joined as {} with {} peers", id, peers.len());
+        let ice_servers = vec![IceServer::stun(DEFAULT_STUN_SERVER)];
+        let inner = WebRtcTransport::new(id, ice_servers);
+        let seed = js_sys::Date::now() as u64;
+        self.transport = Some(ConditionedTransport::new(
+            inner,
+            self.conditions.clone(),
+            seed,
+        ));
+        self.handshake = Handshake::new(self.name.clone());
+        self.clock = clock_sync();
+        self.sync = None;
+        self.lobby = Some(if peers.is_empty() {
+            let settings = RoomSettings {
+                mode,
+                ..RoomSettings::default()
+            };
+            Lobby::create(id, self.name.clone(), settings)
+        } else {
+            Lobby::join(id, self.name.clone())
+        });
+    }
+}
+
+fn clock_sync() -> ClockSync {
+    ClockSync::new(ClockConfig {
+        tick_hz: TICK_HZ as f64,
+        ..ClockConfig::default()
+    })
+}
+
+fn query() -> Option<UrlSearchParams> {
+    let search = web_sys::window()?.location().search().ok()?;
+    UrlSearchParams::new_with_str(&search).ok()
+}
+
+// the page's host, over wss when the page is on https
+fn default_signal_url() -> Option<String> {
+    let location = web_sys::window()?.location();
+    let scheme = match location.protocol().ok()?.as_str() {
+        "https:" => "wss",
+        _ => "ws",
+    };
+    Some(format!(
+        "{}://{}{}",
+        scheme,
+        location.host().ok()?,
+        SIGNAL_PATH
+    ))
+}
+
+// joins the room named in the room query parameter, nothing happens without one
+pub fn add_tasks(web_gl_canvas: &WebGlCanvas<'static>, conditions: ConditionsHandle) {
+    let params = if let Some(params) = query() {
+        params
+    } else {
+        return;
+    };
+    let room = if let Some(room) = params.get(ROOM_KEY) {
+        room
+    } else {
+        log_debug!(LOG_TARGET, "No room given, playing alone");
+        return;
+    };
+    let url = if let Some(url) = params.get(SIGNAL_KEY).or_else(default_signal_url) {
+        url
+    } else {
+        log_warn!(LOG_TARGET, "No signaling server to join {} through", room);
+        return;
+    };
+    let name = params
+        .get(NAME_KEY)
+        .unwrap_or_else(|| DEFAULT_NAME.to_string());
+    log_info!(LOG_TARGET, "Joining room {} through {}", room, url);
+    let config = SignalingConfig::new(room, name);
+    let mut session = Session::new(config, url, conditions);
+    web_gl_canvas.add_raf_task(RafTask::new(
+        move |canvas: &WebGlCanvas, time: RafTime| session.update(canvas, time),
+        "Network session",
+    ));
+}
@@ -0,0 +1,206 @@
+use glam::{UVec2, Vec2};
+use web_sys::WebGl2RenderingContext;
+
+use crate::libs::ecs::World;
+use crate::libs::input::bindings::ActionId;
+use crate::libs::input::snapshot::InputFrame;
+use crate::libs::rendering::camera::Camera;
+use crate::libs::rendering::canvas::{RafTask, RafTime, WebGlCanvas};
+use crate::libs::rendering::colour::Colour;
+use crate::libs::rendering::graph::{PassContext, RenderGraph};
+use crate::libs::rendering::picking::{PickBatch, Picker};
+use crate::libs::rendering::shapes::tessellate::{LineCap, LineJoin, StrokeStyle};
+use crate::libs::rendering::shapes::{ShapeBatch, ShapeRenderer};
+use crate::libs::types::entity::EntityId;
+use crate::libs::types::errors::ErrorStr;
+use crate::libs::types::shared::{SharedRefCell, shared_ref_cell};
+use crate::log_info;
+
+const LOG_TARGET: &str = "app::demo::shapes";
+const STAR_POINTS: usize = 5;
+const STAR_RADIUS: f32 = 60.0;
+const STAR_CENTER: Vec2 = Vec2::new(-250.0, 120.0);
+// radians per second
+const STAR_SPEED: f32 = 0.5;
+const PANEL_MIN: Vec2 = Vec2::new(170.0, -170.0);
+const PANEL_MAX: Vec2 = Vec2::new(310.0, -90.0);
+// a few pixels of slack around the pointer for thin outlines
+const PICK_SIZE: UVec2 = UVec2::splat(5);
+
+// the shapes that can be clicked on
+const STAR: EntityId = EntityId::from_parts(0, 0);
+const PANEL: EntityId = EntityId::from_parts(1, 0);
+
+#[derive(Debug, Default)]
+struct Shapes {
+    renderer: Option<ShapeRenderer>,
+    picker: Option<Picker>,
+    batch: ShapeBatch,
+    pick_batch: PickBatch,
+    selected: Option<EntityId>,
+    // the game loop may run no tick or several between frames
+    last_tick: Option<u64>,
+    time: f32,
+}
+
+impl Shapes {
+    #[inline]
+    fn outline(&self, id: EntityId) -> Colour {
+        if self.selected == Some(id) {
+            Colour::YELLOW
+        } else {
+            Colour::WHITE
+        }
+    }
+
+    // rebuilt every frame, the batch is immediate mode. the pick batch gets the same outlines
+    fn fill(&mut self) {
+        let angle = self.time * STAR_SPEED;
+        let star: Vec<Vec2> = (0..STAR_POINTS * 2)
+            .map(|i| {
+                let radius = if i % 2 == 0 {
+                    STAR_RADIUS
+                } else {
+                    STAR_RADIUS * 0.4
+                };
+                let point = std::f32::consts::TAU * i as f32 / (STAR_POINTS * 2) as f32;
+                STAR_CENTER + Vec2::from_angle(angle + point) * radius
+            })
+            .collect();
+
+        self.pick_batch.clear();
+        self.pick_batch.add_polygon(&star, STAR);
+        self.pick_batch.add_rect(PANEL_MIN, PANEL_MAX, PANEL);
+
+        let (star_outline, panel_outline) = (self.outline(STAR), self.outline(PANEL));
+        let batch = &mut self.batch;
+        batch.clear();
+        batch.fill_polygon(&star, Colour::rgba(1.0, 0.8, 0.2, 0.8));
+        batch.draw_polyline(
+            &star,
+            true,
+            &StrokeStyle::new(3.0, star_outline).with_join(LineJoin::Round),
+        );
+        // the path the particle trail follows
+        batch.stroke_circle(
+            Vec2::ZERO,
+            150.0,
+            &StrokeStyle::new(1.5, Colour::rgba(1.0, 1.0, 1.0, 0.3)),
+        );
+        batch.fill_rounded_rect(PANEL_MIN, PANEL_MAX, 12.0, Colour::rgba(0.2, 0.4, 0.9, 0.7));
+        batch.stroke_rounded_rect(
+            PANEL_MIN,
+            PANEL_MAX,
+            12.0,
+            &StrokeStyle::new(2.0, panel_outline),
+        );
+        let wave: Vec<Vec2> = (0..=16)
+            .map(|i| {
+                let x = i as f32 * 20.0 - 160.0;
+                Vec2::new(x, -200.0 + (x * 0.05 + self.time * 2.0).sin() * 15.0)
+            })
+            .collect();
+        batch.draw_polyline(
+            &wave,
+            false,
+            &StrokeStyle::new(6.0, Colour::GREEN).with_cap(LineCap::Round),
+        );
+    }
+}
+
+// clicking starts a readback, its result turns up a frame or two later
+fn pick(
+    shapes: &mut Shapes,
+    gl: &WebGl2RenderingContext,
+    camera: &Camera,
+    world: &World,
+    select: ActionId,
+) -> Result<(), ErrorStr> {
+    if shapes.picker.is_none() {
+        shapes.picker = Some(Picker::new(gl)?);
+    }
+    let picker = if let Some(picker) = shapes.picker.as_mut() {
+        picker
+    } else {
+        return Ok(());
+    };
+    if let Some(result) = picker.poll(gl) {
+        // the shape under the pointer, else any close by
+        shapes.selected = result
+            .entity()
+            .or_else(|| result.entities().first().copied());
+        log_info!(LOG_TARGET, "Selected {:?}", shapes.selected);
+    }
+    // the input of the latest tick, once
+    let tick = world.tick();
+    if shapes.last_tick == Some(tick) {
+        return Ok(());
+    }
+    shapes.last_tick = Some(tick);
+    let frame = world.resource::<InputFrame>().copied().unwrap_or_default();
+    if let (true, Some([x, y])) = (frame.just_pressed(select), frame.current.pointer) {
+        let screen = Vec2::new(x as f32, y as f32);
+        picker.request_pick(gl, &shapes.pick_batch, camera, screen, PICK_SIZE)?;
+    }
+    Ok(())
+}
+
+// vector shapes over the map, in one draw call a frame. select picks them
+pub fn add_tasks(
+    web_gl_canvas: &WebGlCanvas<'static>,
+    graph: &mut RenderGraph,
+    camera: SharedRefCell<Camera>,
+    world: SharedRefCell<World>,
+    select: ActionId,
+) {
+    let shapes = shared_ref_cell(Shapes::default());
+    let update_shapes = shapes.clone();
+    let update_camera = camera.clone();
+    web_gl_canvas.add_raf_task(RafTask::new(
+        move |canvas: &WebGlCanvas, time: RafTime| {
+            let context = canvas.get_context();
+            let gl = if let Some(gl) = context.as_ref() {
+                gl
+            } else {
+                return Err(ErrorStr::new("Shapes have no GL context"));
+            };
+            let mut shapes = update_shapes.borrow_mut();
+            if shapes.renderer.is_none() {
+                shapes.renderer = Some(ShapeRenderer::new(gl)?);
+            }
+            shapes.time += (time.delta() / 1000.0) as f32;
+            shapes.fill();
+            let camera = update_camera.borrow();
+            pick(&mut shapes, gl, &camera, &world.borrow(), select)?;
+            let label = match shapes.selected {
+                Some(STAR) => Some((STAR_CENTER, "Star selected")),
+                Some(PANEL) => Some((PANEL_MAX, "Panel selected")),
+                _ => None,
+            };
+            if let Some((position, label)) = label {
+                canvas
+                    .debug_draw()
+                    .text(position.extend(0.0), label, Colour::YELLOW);
+            }
+            Ok(())
+        },
+        "Shapes",
+    ));
+
+    let backbuffer = graph.backbuffer();
+    graph.add_pass(
+        "Shapes",
+        &[],
+        &[backbuffer],
+        move |pass: &mut PassContext| {
+            let mut shapes = shapes.borrow_mut();
+            let Shapes {
+                renderer, batch, ..
+            } = &mut *shapes;
+            if let Some(renderer) = renderer.as_mut() {
+                renderer.draw(pass.gl(), camera.borrow().view_projection(), batch);
+            }
+            Ok(())
+        },
+    );
+}
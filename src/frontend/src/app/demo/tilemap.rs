@@ -0,0 +1,124 @@
+use glam::Vec2;
+use wasm_bindgen_futures::spawn_local;
+
+use crate::libs::rendering::camera::Camera;
+use crate::libs::rendering::canvas::{RafTask, RafTime, WebGlCanvas};
+use crate::libs::rendering::colour::Colour;
+use crate::libs::rendering::graph::{PassContext, RenderGraph};
+use crate::libs::rendering::tilemap::tiled::{Layer, TileMap};
+use crate::libs::rendering::tilemap::{LoadedTileMap, TileMapRenderer, load_tile_map};
+use crate::libs::types::errors::ErrorStr;
+use crate::libs::types::shared::{SharedRefCell, shared_ref_cell};
+use crate::{log_error, log_info};
+
+const LOG_TARGET: &str = "app::demo::tilemap";
+// copied next to the wasm by trunk, see index.html
+const MAP: &str = "maps/demo.tmj";
+// object layer whose points get a debug label from their label property
+const MARKERS: &str = "Markers";
+
+#[derive(Debug, Clone, PartialEq)]
+struct Marker {
+    position: Vec2,
+    label: String,
+}
+
+#[derive(Debug, Default)]
+struct DemoMap {
+    // until the renderer has been built from it
+    loaded: Option<LoadedTileMap>,
+    renderer: Option<TileMapRenderer>,
+    markers: Vec<Marker>,
+}
+
+fn markers(map: &TileMap) -> Vec<Marker> {
+    let layer = match map.layer(MARKERS) {
+        Some(Layer::Objects(layer)) => layer,
+        _ => return Vec::new(),
+    };
+    layer
+        .objects
+        .iter()
+        .filter(|object| object.visible)
+        .map(|object| Marker {
+            position: TileMap::tiled_to_world(object.position + layer.offset),
+            label: object
+                .properties
+                .get("label")
+                .and_then(|label| label.as_str())
+                .unwrap_or(&object.name)
+                .to_string(),
+        })
+        .collect()
+}
+
+// fetches the map, then draws it under everything else with its markers as debug labels
+pub fn add_tasks(
+    web_gl_canvas: &WebGlCanvas<'static>,
+    graph: &mut RenderGraph,
+    camera: SharedRefCell<Camera>,
+) {
+    let map = shared_ref_cell(DemoMap::default());
+    let load_map = map.clone();
+    spawn_local(async move {
+        match load_tile_map(MAP).await {
+            Ok(loaded) => {
+                let mut map = load_map.borrow_mut();
+                map.markers = markers(&loaded.map);
+                map.loaded = Some(loaded);
+            }
+            Err(error) => log_error!(LOG_TARGET, "{}", error),
+        }
+    });
+
+    let update_map = map.clone();
+    web_gl_canvas.add_raf_task(RafTask::new(
+        move |canvas: &WebGlCanvas, time: RafTime| {
+            let context = canvas.get_context();
+            let gl = if let Some(gl) = context.as_ref() {
+                gl
+            } else {
+                return Err(ErrorStr::new("Tilemap has no GL context"));
+            };
+            let mut map = update_map.borrow_mut();
+            if let Some(loaded) = map.loaded.take() {
+                let renderer = TileMapRenderer::new(gl, &loaded)?;
+                log_info!(
+                    LOG_TARGET,
+                    "Loaded {}, {}x{} tiles",
+                    MAP,
+                    loaded.map.width,
+                    loaded.map.height
+                );
+                map.renderer = Some(renderer);
+            }
+            if let Some(renderer) = map.renderer.as_mut() {
+                renderer.update(time.delta());
+            }
+            let mut debug_draw = canvas.debug_draw();
+            for marker in map.markers.iter() {
+                debug_draw.cross(marker.position.extend(0.0), 8.0, Colour::WHITE);
+                debug_draw.text(
+                    marker.position.extend(0.0),
+                    marker.label.clone(),
+                    Colour::WHITE,
+                );
+            }
+            Ok(())
+        },
+        "Tilemap",
+    ));
+
+    let backbuffer = graph.backbuffer();
+    graph.add_pass(
+        "Tilemap",
+        &[],
+        &[backbuffer],
+        move |pass: &mut PassContext| {
+            if let Some(renderer) = map.borrow_mut().renderer.as_mut() {
+                renderer.draw(pass.gl(), &camera.borrow());
+            }
+            Ok(())
+        },
+    );
+}
@@ -1,3 +1,4 @@
 pub mod body;
 pub mod button;
+pub mod demo;
 pub mod triangle;
@@ -1,19 +1,28 @@
+use crate::app::demo;
+use crate::libs::net::conditioned::ConditionsHandle;
+use crate::libs::net::conditioned::menu::NetworkConditionsMenu;
+use crate::libs::profiler::overlay::ProfilerOverlay;
 use crate::libs::rendering::canvas::*;
 use crate::libs::types::errors::ErrorStr;
+use crate::libs::types::shared::{SharedRefCell, shared_ref_cell};
+use crate::{log_error, log_info};
 
 use leptos::html::Canvas;
-use leptos::logging;
 use leptos::prelude::*;
 use leptos_use::{
     use_raf_fn, use_resize_observer, use_window_size, UseRafFnCallbackArgs, UseWindowSizeReturn,
 };
 
 use wasm_bindgen::JsCast;
-use web_sys::{ResizeObserverEntry, WebGl2RenderingContext, WebGlProgram, WebGlShader};
+use web_sys::{
+    ResizeObserverEntry, WebGl2RenderingContext, WebGlBuffer, WebGlProgram, WebGlShader,
+};
 
 use std::fmt;
 use std::rc::Rc;
 
+const LOG_TARGET: &str = "app::triangle";
+
 const VERTEX_SHADER_SOURCE: &str = r#"
 attribute vec4 position;
 void main() {
@@ -28,7 +37,18 @@ void main() {
 }
 "#;
 
-fn triangle_init(web_gl_canvas: &WebGlCanvas) -> bool {
+// what triangle_init built, so the triangle can be drawn again every frame under the demo
+#[derive(Debug)]
+struct TriangleState {
+    program: WebGlProgram,
+    buffer: WebGlBuffer,
+    position: u32,
+}
+
+fn triangle_init(
+    web_gl_canvas: &WebGlCanvas,
+    state: &SharedRefCell<Option<TriangleState>>,
+) -> bool {
     let mut gl_opt = web_gl_canvas.get_context_mut();
     let canvas = web_gl_canvas
         .get_canvas_mut()
@@ -38,7 +58,8 @@ fn triangle_init(web_gl_canvas: &WebGlCanvas) -> bool {
         .unwrap();
     if let Some(gl) = gl_opt.as_mut() {
         // do stuff
-        logging::log!(
+        log_info!(
+            LOG_TARGET,
             "Init canvas width: {}, height: {}",
             canvas.width(),
             canvas.height()
@@ -51,7 +72,8 @@ fn triangle_init(web_gl_canvas: &WebGlCanvas) -> bool {
         ) {
             Ok(vert_s) => vert_s,
             Err(error) => {
-                logging::error!(
+                log_error!(
+                    LOG_TARGET,
                     "Vertex shader compilation failed in triangle_init. Error: {}",
                     error
                 );
@@ -65,7 +87,8 @@ fn triangle_init(web_gl_canvas: &WebGlCanvas) -> bool {
         ) {
             Ok(vert_s) => vert_s,
             Err(error) => {
-                logging::error!(
+                log_error!(
+                    LOG_TARGET,
                     "Fragment shader compilation failed in triangle_init. Error: {}",
                     error
                 );
@@ -75,7 +98,11 @@ fn triangle_init(web_gl_canvas: &WebGlCanvas) -> bool {
         let program = match link_program(&gl, &frag_s, &vert_s) {
             Ok(program) => program,
             Err(error) => {
-                logging::error!("Program linking failed in triangle_init. Error: {}", error);
+                log_error!(
+                    LOG_TARGET,
+                    "Program linking failed in triangle_init. Error: {}",
+                    error
+                );
                 return false;
             }
         };
@@ -84,7 +111,8 @@ fn triangle_init(web_gl_canvas: &WebGlCanvas) -> bool {
         let buffer = if let Some(buffer) = gl.create_buffer() {
             buffer
         } else {
-            logging::error!(
+            log_error!(
+                LOG_TARGET,
                 "Could not create buffer in triangle_init, using {:?}",
                 web_gl_canvas
             );
@@ -106,9 +134,15 @@ fn triangle_init(web_gl_canvas: &WebGlCanvas) -> bool {
         gl.clear_color(0.0, 0.0, 0.0, 1.0);
         gl.clear(WebGl2RenderingContext::COLOR_BUFFER_BIT);
         gl.draw_arrays(WebGl2RenderingContext::TRIANGLES, 0, 3);
+        *state.borrow_mut() = Some(TriangleState {
+            program,
+            buffer,
+            position,
+        });
         true
     } else {
-        logging::error!(
+        log_error!(
+            LOG_TARGET,
             "GL context is None in triangle_init, using {:?}",
             web_gl_canvas
         );
@@ -120,7 +154,7 @@ fn triangle_resize(
     web_gl_canvas: &WebGlCanvas,
     resize_entry: &ResizeObserverEntry,
 ) -> Result<(), ErrorStr> {
-    logging::log!("triangle_resize called");
+    log_info!(LOG_TARGET, "triangle_resize called");
     let canvas = web_gl_canvas
         .get_canvas_mut()
         .as_mut()
@@ -131,7 +165,8 @@ fn triangle_resize(
     let mut gl = if let Some(gl) = gl_opt.as_mut() {
         gl
     } else {
-        logging::error!(
+        log_error!(
+            LOG_TARGET,
             "GL context is none in triangle_resize, using {:?}",
             web_gl_canvas
         );
@@ -141,7 +176,8 @@ fn triangle_resize(
         )));
     };
     let rect = resize_entry.content_rect();
-    logging::log!(
+    log_info!(
+        LOG_TARGET,
         "Resize observer width: {}, height: {}",
         rect.width(),
         rect.height()
@@ -155,14 +191,68 @@ fn triangle_resize(
     Ok(())
 }
 
+fn triangle_draw(
+    web_gl_canvas: &WebGlCanvas,
+    state: &SharedRefCell<Option<TriangleState>>,
+) -> Result<(), ErrorStr> {
+    let context = web_gl_canvas.get_context();
+    let gl = if let Some(gl) = context.as_ref() {
+        gl
+    } else {
+        return Err(ErrorStr::new("GL context is none in triangle_draw"));
+    };
+    let state = state.borrow();
+    let state = if let Some(state) = state.as_ref() {
+        state
+    } else {
+        return Ok(());
+    };
+    gl.clear_color(0.0, 0.0, 0.0, 1.0);
+    gl.clear(WebGl2RenderingContext::COLOR_BUFFER_BIT);
+    gl.use_program(Some(&state.program));
+    gl.bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, Some(&state.buffer));
+    gl.vertex_attrib_pointer_with_i32(
+        state.position,
+        2,
+        WebGl2RenderingContext::FLOAT,
+        false,
+        0,
+        0,
+    );
+    gl.enable_vertex_attrib_array(state.position);
+    gl.draw_arrays(WebGl2RenderingContext::TRIANGLES, 0, 3);
+    gl.bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, None);
+    Ok(())
+}
+
 #[component]
 pub fn Triangle2() -> impl IntoView {
     let web_gl_canvas = WebGlCanvas::new("Triangle");
-    let init_task = InitTask::new(triangle_init, "Triangle init");
+    let state = shared_ref_cell(None);
+    let init_state = state.clone();
+    let init_task = InitTask::new(
+        move |canvas: &WebGlCanvas| triangle_init(canvas, &init_state),
+        "Triangle init",
+    );
     let resize_task = ResizeTask::new(triangle_resize, "Triangle resize");
+    let raf_task = RafTask::new(
+        move |canvas: &WebGlCanvas, _time: RafTime| triangle_draw(canvas, &state),
+        "Triangle draw",
+    );
     web_gl_canvas.add_init_task(init_task);
     web_gl_canvas.add_resize_task(resize_task);
-    view! { <WebGlCanvasComponent web_gl_canvas=web_gl_canvas /> }
+    web_gl_canvas.add_raf_task(raf_task);
+    // what the demo's network session goes through, picked in the menu
+    let conditions = ConditionsHandle::default();
+    demo::add_tasks(&web_gl_canvas, conditions.clone());
+    let profiler = web_gl_canvas.profiler();
+    view! {
+        <div>
+            <WebGlCanvasComponent web_gl_canvas=web_gl_canvas />
+            <ProfilerOverlay profiler=profiler />
+            <NetworkConditionsMenu conditions=conditions />
+        </div>
+    }
 }
 
 #[component]
@@ -170,9 +260,9 @@ pub fn Triangle() -> impl IntoView {
     let canvas_ref = NodeRef::<Canvas>::new();
     let UseWindowSizeReturn { width, height } = use_window_size();
 
-    leptos::logging::log!("triangle func called");
+    log_info!(LOG_TARGET, "triangle func called");
     Effect::new(move |_| {
-        leptos::logging::log!("event called");
+        log_info!(LOG_TARGET, "event called");
         if let Some(canvas) = canvas_ref.get() {
             canvas.set_width(width.get() as u32);
             canvas.set_height(height.get() as u32);
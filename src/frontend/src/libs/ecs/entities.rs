@@ -0,0 +1,118 @@
+use crate::libs::types::entity::{EntityId, MAX_ENTITIES};
+use crate::libs::types::errors::ErrorStr;
+
+// hands out generational ids. freed slots are reused lowest index first, so the ids a peer gets
+// depend only on the sequence of spawns and despawns, never on timing
+#[derive(Debug, Clone, Default)]
+pub struct Entities {
+    generations: Vec<u32>,
+    alive: Vec<bool>,
+    free: Vec<u32>,
+    len: usize,
+}
+
+impl Entities {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn allocate(&mut self) -> Result<EntityId, ErrorStr> {
+        let index = match self.free.pop() {
+            Some(index) => index,
+            None => {
+                let index = self.generations.len() as u32;
+                if index >= MAX_ENTITIES {
+                    return Err(ErrorStr::new("Entity limit reached"));
+                }
+                self.generations.push(0);
+                self.alive.push(false);
+                index
+            }
+        };
+        self.alive[index as usize] = true;
+        self.len += 1;
+        Ok(EntityId::from_parts(
+            index,
+            self.generations[index as usize],
+        ))
+    }
+
+    pub fn free(&mut self, entity: EntityId) -> bool {
+        if !self.is_alive(entity) {
+            return false;
+        }
+        let index = entity.index();
+        self.alive[index as usize] = false;
+        self.generations[index as usize] = self.generations[index as usize].wrapping_add(1);
+        // kept sorted high to low so pop() returns the lowest free index
+        let position = self.free.partition_point(|free| *free > index);
+        self.free.insert(position, index);
+        self.len -= 1;
+        true
+    }
+
+    pub fn is_alive(&self, entity: EntityId) -> bool {
+        let index = entity.index() as usize;
+        match (self.alive.get(index), self.generations.get(index)) {
+            (Some(true), Some(generation)) => {
+                EntityId::from_parts(entity.index(), *generation) == entity
+            }
+            _ => false,
+        }
+    }
+
+    // ascending index order
+    pub fn iter(&self) -> impl Iterator<Item = EntityId> + '_ {
+        self.alive
+            .iter()
+            .enumerate()
+            .filter(|(_, alive)| **alive)
+            .map(|(index, _)| EntityId::from_parts(index as u32, self.generations[index]))
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reuses_the_lowest_free_index_first() {
+        let mut entities = Entities::new();
+        let ids: Vec<EntityId> = (0..4).map(|_| entities.allocate().unwrap()).collect();
+        assert_eq!(
+            ids.iter().map(EntityId::index).collect::<Vec<_>>(),
+            vec![0, 1, 2, 3]
+        );
+        assert!(entities.free(ids[2]));
+        assert!(entities.free(ids[0]));
+        let reused = entities.allocate().unwrap();
+        assert_eq!((reused.index(), reused.generation()), (0, 1));
+        assert_eq!(entities.allocate().unwrap().index(), 2);
+        assert_eq!(entities.allocate().unwrap().index(), 4);
+        assert_eq!(entities.len(), 5);
+    }
+
+    #[test]
+    fn rejects_stale_ids() {
+        let mut entities = Entities::new();
+        let stale = entities.allocate().unwrap();
+        assert!(entities.free(stale));
+        assert!(!entities.free(stale));
+        let current = entities.allocate().unwrap();
+        assert_eq!(current.index(), stale.index());
+        assert!(!entities.is_alive(stale));
+        assert!(entities.is_alive(current));
+        assert!(!entities.free(stale));
+        assert_eq!(entities.iter().collect::<Vec<_>>(), vec![current]);
+    }
+}
@@ -0,0 +1,329 @@
+use crate::libs::ecs::World;
+use crate::libs::ecs::schedule::Schedule;
+use crate::libs::ecs::storage::Component;
+use crate::libs::types::errors::ErrorStr;
+use crate::libs::types::shared::SharedRefCell;
+use crate::{log_debug, log_info};
+
+const LOG_TARGET: &str = "ecs::game_loop";
+
+pub const DEFAULT_TICK_HZ: f64 = 60.0;
+// after a long stall only this many ticks are caught up, the rest of the time is dropped.
+// otherwise a slow tick makes the next frame slower still (spiral of death)
+pub const DEFAULT_MAX_STEPS: u32 = 5;
+// a single frame never counts for more than this, e.g. after a breakpoint
+const MAX_FRAME_MS: f64 = 250.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FixedSteps {
+    pub steps: u32,
+    // how far between the previous and the current tick the frame is, in [0, 1)
+    pub alpha: f64,
+    // simulation time thrown away because of the catch up cap
+    pub dropped_ms: f64,
+}
+
+// turns variable frame deltas into a whole number of fixed ticks
+#[derive(Debug, Clone, Copy)]
+pub struct FixedTimestep {
+    step_ms: f64,
+    max_steps: u32,
+    accumulator: f64,
+}
+
+impl FixedTimestep {
+    pub fn new(tick_hz: f64, max_steps: u32) -> Self {
+        Self {
+            step_ms: 1000.0 / tick_hz,
+            max_steps: max_steps.max(1),
+            accumulator: 0.0,
+        }
+    }
+
+    pub fn advance(&mut self, delta_ms: f64) -> FixedSteps {
+        self.accumulator += delta_ms.clamp(0.0, MAX_FRAME_MS);
+        let mut steps = (self.accumulator / self.step_ms).floor() as u32;
+        let mut dropped_ms = 0.0;
+        if steps > self.max_steps {
+            dropped_ms = (steps - self.max_steps) as f64 * self.step_ms;
+            steps = self.max_steps;
+        }
+        self.accumulator -= steps as f64 * self.step_ms + dropped_ms;
+        FixedSteps {
+            steps,
+            alpha: self.alpha(),
+            dropped_ms,
+        }
+    }
+
+    #[inline]
+    pub fn alpha(&self) -> f64 {
+        (self.accumulator / self.step_ms).clamp(0.0, 1.0)
+    }
+
+    pub fn reset(&mut self) {
+        self.accumulator = 0.0;
+    }
+
+    #[inline]
+    pub fn step_ms(&self) -> f64 {
+        self.step_ms
+    }
+}
+
+// copy of a component from the previous tick, so rendering can interpolate towards the current
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Previous<T>(pub T);
+
+type SnapshotFn = Box<dyn FnMut(&mut World) -> Result<(), ErrorStr>>;
+
+// runs a schedule at a fixed tick rate, driven by the canvas' raf loop
+pub struct GameLoop<'a> {
+    world: SharedRefCell<World>,
+    schedule: Schedule<'a>,
+    timestep: FixedTimestep,
+    snapshots: Vec<SnapshotFn>,
+    paused: bool,
+    // simulated ms per real ms, nudged by network clock sync
+    time_scale: f64,
+    // set while the tab is hidden, so the first frame back doesn't try to catch up
+    hidden: bool,
+}
+
+impl<'a> std::fmt::Debug for GameLoop<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("GameLoop")
+            .field("schedule", &self.schedule)
+            .field("timestep", &self.timestep)
+            .field("paused", &self.paused)
+            .field("time_scale", &self.time_scale)
+            .field("hidden", &self.hidden)
+            .finish()
+    }
+}
+
+impl<'a> GameLoop<'a> {
+    pub fn new(world: SharedRefCell<World>, schedule: Schedule<'a>, tick_hz: f64) -> Self {
+        Self {
+            world,
+            schedule,
+            timestep: FixedTimestep::new(tick_hz, DEFAULT_MAX_STEPS),
+            snapshots: Vec::new(),
+            paused: false,
+            time_scale: 1.0,
+            hidden: false,
+        }
+    }
+
+    pub fn with_max_steps(mut self, max_steps: u32) -> Self {
+        self.timestep = FixedTimestep::new(1000.0 / self.timestep.step_ms(), max_steps);
+        self
+    }
+
+    // before every tick each T is copied into a Previous<T> on the same entity. a Previous<T>
+    // whose T was removed is removed as well
+    pub fn track_previous<T>(mut self) -> Self
+    where
+        T: Component,
+    {
+        self.snapshots.push(Box::new(|world: &mut World| {
+            let stale: Vec<_> = match world.storage::<Previous<T>>()? {
+                Some(storage) => storage
+                    .iter()
+                    .map(|(entity, _)| entity)
+                    .filter(|entity| !world.has::<T>(*entity))
+                    .collect(),
+                None => Vec::new(),
+            };
+            for entity in stale {
+                world.remove::<Previous<T>>(entity);
+            }
+            let current: Vec<_> = match world.storage::<T>()? {
+                Some(storage) => storage
+                    .iter()
+                    .map(|(entity, component)| (entity, component.clone()))
+                    .collect(),
+                None => return Ok(()),
+            };
+            for (entity, component) in current {
+                world.insert(entity, Previous(component))?;
+            }
+            Ok(())
+        }));
+        self
+    }
+
+    // called once per animation frame. returns the interpolation alpha for rendering
+    pub fn advance(&mut self, delta_ms: f64) -> Result<f64, ErrorStr> {
+        if document_hidden() {
+            if !self.hidden {
+                log_info!(LOG_TARGET, "Tab hidden, pausing simulation");
+            }
+            self.hidden = true;
+            return Ok(self.timestep.alpha());
+        }
+        if self.hidden {
+            self.hidden = false;
+            self.timestep.reset();
+            log_info!(LOG_TARGET, "Tab visible, resuming simulation");
+            return Ok(0.0);
+        }
+        if self.paused {
+            return Ok(self.timestep.alpha());
+        }
+        let steps = self.timestep.advance(delta_ms * self.time_scale);
+        if steps.dropped_ms > 0.0 {
+            log_debug!(
+                LOG_TARGET,
+                "Simulation behind, dropped {:.1} ms",
+                steps.dropped_ms
+            );
+        }
+        for _ in 0..steps.steps {
+            self.step()?;
+        }
+        Ok(steps.alpha)
+    }
+
+    // a single tick, regardless of time. also for stepping while paused
+    pub fn step(&mut self) -> Result<(), ErrorStr> {
+        let mut world = self.world.borrow_mut();
+        for snapshot in self.snapshots.iter_mut() {
+            snapshot(&mut world)?;
+        }
+        self.schedule.run(&mut world)
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+        self.timestep.reset();
+    }
+
+    // runs the simulation slightly faster or slower than real time, so a peer that drifted
+    // catches up without skipping ticks
+    pub fn set_time_scale(&mut self, time_scale: f64) {
+        self.time_scale = time_scale.max(0.0);
+    }
+
+    #[inline]
+    pub fn time_scale(&self) -> f64 {
+        self.time_scale
+    }
+
+    #[inline]
+    pub fn paused(&self) -> bool {
+        self.paused || self.hidden
+    }
+
+    #[inline]
+    pub fn world(&self) -> &SharedRefCell<World> {
+        &self.world
+    }
+
+    #[inline]
+    pub fn step_ms(&self) -> f64 {
+        self.timestep.step_ms()
+    }
+}
+
+fn document_hidden() -> bool {
+    web_sys::window()
+        .and_then(|window| window.document())
+        .is_some_and(|document| document.hidden())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::libs::types::shared::shared_ref_cell;
+
+    const EPSILON: f64 = 1e-9;
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct Position(i32);
+
+    #[test]
+    fn a_partial_step_only_accumulates() {
+        let mut timestep = FixedTimestep::new(100.0, DEFAULT_MAX_STEPS);
+        let steps = timestep.advance(4.0);
+        assert_eq!(steps.steps, 0);
+        assert_eq!(steps.dropped_ms, 0.0);
+        assert!((steps.alpha - 0.4).abs() < EPSILON);
+        let steps = timestep.advance(7.0);
+        assert_eq!(steps.steps, 1);
+        assert!((steps.alpha - 0.1).abs() < EPSILON);
+    }
+
+    #[test]
+    fn runs_multiple_steps_per_frame() {
+        let mut timestep = FixedTimestep::new(100.0, DEFAULT_MAX_STEPS);
+        let steps = timestep.advance(35.0);
+        assert_eq!(steps.steps, 3);
+        assert_eq!(steps.dropped_ms, 0.0);
+        assert!((steps.alpha - 0.5).abs() < EPSILON);
+        assert!((timestep.alpha() - 0.5).abs() < EPSILON);
+    }
+
+    #[test]
+    fn caps_the_catch_up_and_drops_the_rest() {
+        let mut timestep = FixedTimestep::new(100.0, 3);
+        let steps = timestep.advance(72.0);
+        assert_eq!(steps.steps, 3);
+        assert!((steps.dropped_ms - 40.0).abs() < EPSILON);
+        assert!((steps.alpha - 0.2).abs() < EPSILON);
+        // a stall longer than a frame may count for is clamped before the cap applies
+        let mut timestep = FixedTimestep::new(100.0, 100);
+        let steps = timestep.advance(10_000.0);
+        assert_eq!(steps.steps, 25);
+        assert_eq!(steps.dropped_ms, 0.0);
+        // the next frame starts from the left over fraction, not from the dropped time
+        let steps = timestep.advance(5.0);
+        assert_eq!(steps.steps, 0);
+        assert!((steps.alpha - 0.5).abs() < EPSILON);
+    }
+
+    #[test]
+    fn alpha_ignores_negative_deltas_and_resets() {
+        let mut timestep = FixedTimestep::new(100.0, DEFAULT_MAX_STEPS);
+        timestep.advance(2.5);
+        let steps = timestep.advance(-50.0);
+        assert_eq!(steps.steps, 0);
+        assert!((steps.alpha - 0.25).abs() < EPSILON);
+        timestep.reset();
+        assert_eq!(timestep.alpha(), 0.0);
+        assert!((timestep.step_ms() - 10.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn tracks_and_clears_previous_components() {
+        let mut world = World::new();
+        let a = world.spawn().unwrap();
+        let b = world.spawn().unwrap();
+        world.insert(a, Position(1)).unwrap();
+        world.insert(b, Position(2)).unwrap();
+        let world = shared_ref_cell(world);
+        let schedule = Schedule::new().with_system(
+            |world: &mut World| world.each::<&mut Position, _>(|_, position| position.0 += 10),
+            "Move",
+        );
+        let mut game_loop =
+            GameLoop::new(world.clone(), schedule, 60.0).track_previous::<Position>();
+        game_loop.step().unwrap();
+        {
+            let world = world.borrow();
+            assert_eq!(
+                *world.get::<Previous<Position>>(a).unwrap(),
+                Previous(Position(1))
+            );
+            assert_eq!(*world.get::<Position>(a).unwrap(), Position(11));
+        }
+        world.borrow_mut().remove::<Position>(b);
+        game_loop.step().unwrap();
+        let world = world.borrow();
+        assert_eq!(
+            *world.get::<Previous<Position>>(a).unwrap(),
+            Previous(Position(11))
+        );
+        assert!(!world.has::<Previous<Position>>(b));
+    }
+}
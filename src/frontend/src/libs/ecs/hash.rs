@@ -0,0 +1,115 @@
+use std::hash::{Hash, Hasher};
+
+use crate::libs::ecs::World;
+use crate::libs::ecs::storage::Component;
+use crate::libs::types::errors::ErrorStr;
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+// FNV-1a, so the same state hashes the same in every browser and every build. the std hasher
+// makes no such promise
+#[derive(Debug, Clone, Copy)]
+pub struct StateHasher {
+    state: u64,
+}
+
+impl Default for StateHasher {
+    fn default() -> Self {
+        Self { state: FNV_OFFSET }
+    }
+}
+
+impl StateHasher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // floats by bit pattern, so -0.0 and 0.0 differ, as they may in later arithmetic
+    #[inline]
+    pub fn write_f32(&mut self, value: f32) {
+        self.write_u32(value.to_bits());
+    }
+
+    #[inline]
+    pub fn write_f64(&mut self, value: f64) {
+        self.write_u64(value.to_bits());
+    }
+}
+
+impl Hasher for StateHasher {
+    fn finish(&self) -> u64 {
+        self.state
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.state ^= *byte as u64;
+            self.state = self.state.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    // fixed endianness rather than the platform's
+    fn write_u32(&mut self, value: u32) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_usize(&mut self, value: usize) {
+        self.write_u64(value as u64);
+    }
+}
+
+// hashes whatever parts of the world make up the match state
+pub trait StateHashFnTrait = Fn(&World, &mut StateHasher) -> Result<(), ErrorStr>;
+
+impl World {
+    // every live entity id, combine with hash_component for the data. the tick is left out so
+    // a recording started mid session hashes the same as its replay from tick 0
+    pub fn hash_entities(&self, hasher: &mut StateHasher) {
+        hasher.write_usize(self.entities().len());
+        for entity in self.entities().iter() {
+            hasher.write_u32(entity.raw());
+        }
+    }
+
+    pub fn hash_component<T>(&self, hasher: &mut StateHasher) -> Result<(), ErrorStr>
+    where
+        T: Component + Hash,
+    {
+        self.hash_component_with::<T, _>(hasher, |component, hasher| component.hash(hasher))
+    }
+
+    // for components that can't derive Hash, e.g. because of floats
+    pub fn hash_component_with<T, F>(
+        &self,
+        hasher: &mut StateHasher,
+        mut hash: F,
+    ) -> Result<(), ErrorStr>
+    where
+        T: Component,
+        F: FnMut(&T, &mut StateHasher),
+    {
+        hasher.write(std::any::type_name::<T>().as_bytes());
+        if let Some(storage) = self.storage::<T>()? {
+            for (entity, component) in storage.iter() {
+                hasher.write_u32(entity.raw());
+                hash(component, hasher);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn state_hash<F>(&self, hash: F) -> Result<u64, ErrorStr>
+    where
+        F: StateHashFnTrait,
+    {
+        let mut hasher = StateHasher::new();
+        self.hash_entities(&mut hasher);
+        hash(self, &mut hasher)?;
+        Ok(hasher.finish())
+    }
+}
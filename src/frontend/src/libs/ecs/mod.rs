@@ -0,0 +1,422 @@
+pub mod entities;
+pub mod game_loop;
+pub mod hash;
+pub mod query;
+pub mod schedule;
+pub mod storage;
+
+use std::any::{Any, TypeId};
+use std::cell::{Ref, RefCell, RefMut};
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::libs::ecs::entities::Entities;
+use crate::libs::ecs::query::Query;
+use crate::libs::ecs::storage::{Component, ErasedStorage, Storage};
+use crate::libs::types::entity::EntityId;
+use crate::libs::types::errors::ErrorStr;
+
+// singletons shared by systems, e.g. the simulation rng or the input for the current tick
+trait ErasedResource {
+    fn clone_boxed(&self) -> Box<dyn ErasedResource>;
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+// wrapped so the blanket impl doesn't also cover references and boxes
+struct Resource<T>(T);
+
+impl<T> ErasedResource for Resource<T>
+where
+    T: Component,
+{
+    fn clone_boxed(&self) -> Box<dyn ErasedResource> {
+        Box::new(Resource(self.0.clone()))
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+// all game state. entities are plain ids, their data lives in one storage per component type.
+// everything is iterated in entity index order so peers applying the same inputs stay in sync
+#[derive(Default)]
+pub struct World {
+    entities: Entities,
+    storages: BTreeMap<TypeId, Box<dyn ErasedStorage>>,
+    resources: BTreeMap<TypeId, Box<dyn ErasedResource>>,
+    tick: u64,
+}
+
+impl Clone for World {
+    fn clone(&self) -> Self {
+        Self {
+            entities: self.entities.clone(),
+            storages: self
+                .storages
+                .iter()
+                .map(|(id, storage)| (*id, storage.clone_boxed()))
+                .collect(),
+            resources: self
+                .resources
+                .iter()
+                .map(|(id, resource)| (*id, resource.clone_boxed()))
+                .collect(),
+            tick: self.tick,
+        }
+    }
+}
+
+impl fmt::Debug for World {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let storages: Vec<(&str, usize)> = self
+            .storages
+            .values()
+            .map(|storage| (storage.type_name(), storage.len()))
+            .collect();
+        f.debug_struct("World")
+            .field("entities", &self.entities.len())
+            .field("storages", &storages)
+            .field("resources", &self.resources.len())
+            .field("tick", &self.tick)
+            .finish()
+    }
+}
+
+impl World {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn spawn(&mut self) -> Result<EntityId, ErrorStr> {
+        self.entities.allocate()
+    }
+
+    // removes the entity and all of its components
+    pub fn despawn(&mut self, entity: EntityId) -> bool {
+        if !self.entities.free(entity) {
+            return false;
+        }
+        for storage in self.storages.values() {
+            storage.remove_entity(entity);
+        }
+        true
+    }
+
+    #[inline]
+    pub fn is_alive(&self, entity: EntityId) -> bool {
+        self.entities.is_alive(entity)
+    }
+
+    #[inline]
+    pub fn entities(&self) -> &Entities {
+        &self.entities
+    }
+
+    // returns the component the entity had before
+    pub fn insert<T>(&mut self, entity: EntityId, component: T) -> Result<Option<T>, ErrorStr>
+    where
+        T: Component,
+    {
+        if !self.entities.is_alive(entity) {
+            let error = format!("Can't insert component into dead entity {}", entity);
+            return Err(ErrorStr::new(error));
+        }
+        let storage = self
+            .storages
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(RefCell::new(Storage::<T>::default())));
+        let storage = if let Some(storage) = downcast::<T>(storage.as_ref()) {
+            storage
+        } else {
+            return Err(ErrorStr::new("Component storage has the wrong type"));
+        };
+        Ok(storage.borrow_mut().insert(entity, component))
+    }
+
+    pub fn remove<T>(&mut self, entity: EntityId) -> Option<T>
+    where
+        T: Component,
+    {
+        let storage = self.storages.get(&TypeId::of::<T>())?;
+        downcast::<T>(storage.as_ref())?.borrow_mut().remove(entity)
+    }
+
+    // None if the entity doesn't have the component or the storage is mutably borrowed
+    pub fn get<T>(&self, entity: EntityId) -> Option<Ref<'_, T>>
+    where
+        T: Component,
+    {
+        let storage = self.storage::<T>().ok()??;
+        Ref::filter_map(storage, |storage| storage.get(entity)).ok()
+    }
+
+    pub fn get_mut<T>(&self, entity: EntityId) -> Option<RefMut<'_, T>>
+    where
+        T: Component,
+    {
+        let storage = self.storage_mut::<T>().ok()??;
+        RefMut::filter_map(storage, |storage| storage.get_mut(entity)).ok()
+    }
+
+    #[inline]
+    pub fn has<T>(&self, entity: EntityId) -> bool
+    where
+        T: Component,
+    {
+        self.get::<T>(entity).is_some()
+    }
+
+    // Ok(None) when no entity ever had a T
+    pub fn storage<T>(&self) -> Result<Option<Ref<'_, Storage<T>>>, ErrorStr>
+    where
+        T: Component,
+    {
+        let storage = if let Some(storage) = self.typed_storage::<T>() {
+            storage
+        } else {
+            return Ok(None);
+        };
+        match storage.try_borrow() {
+            Ok(storage) => Ok(Some(storage)),
+            Err(_) => Err(borrow_error::<T>()),
+        }
+    }
+
+    pub fn storage_mut<T>(&self) -> Result<Option<RefMut<'_, Storage<T>>>, ErrorStr>
+    where
+        T: Component,
+    {
+        let storage = if let Some(storage) = self.typed_storage::<T>() {
+            storage
+        } else {
+            return Ok(None);
+        };
+        match storage.try_borrow_mut() {
+            Ok(storage) => Ok(Some(storage)),
+            Err(_) => Err(borrow_error::<T>()),
+        }
+    }
+
+    fn typed_storage<T>(&self) -> Option<&RefCell<Storage<T>>>
+    where
+        T: Component,
+    {
+        downcast::<T>(self.storages.get(&TypeId::of::<T>())?.as_ref())
+    }
+
+    // calls f for every entity matching the query, in ascending entity index order. fails if the
+    // query borrows a component both mutably and immutably, e.g. (&mut A, &A)
+    pub fn each<Q, F>(&self, mut f: F) -> Result<(), ErrorStr>
+    where
+        Q: Query,
+        F: FnMut(EntityId, Q::Item<'_>),
+    {
+        let mut fetch = Q::fetch(self)?;
+        for entity in self.entities.iter() {
+            if let Some(item) = Q::get(&mut fetch, entity) {
+                f(entity, item);
+            }
+        }
+        Ok(())
+    }
+
+    // the matching entities, for systems that need to spawn or despawn while iterating
+    pub fn matching<Q>(&self) -> Result<Vec<EntityId>, ErrorStr>
+    where
+        Q: Query,
+    {
+        let mut entities = Vec::new();
+        self.each::<Q, _>(|entity, _| entities.push(entity))?;
+        Ok(entities)
+    }
+
+    // replaces any resource of the same type
+    pub fn insert_resource<T>(&mut self, resource: T)
+    where
+        T: Component,
+    {
+        self.resources
+            .insert(TypeId::of::<T>(), Box::new(Resource(resource)));
+    }
+
+    pub fn resource<T>(&self) -> Option<&T>
+    where
+        T: Component,
+    {
+        self.resources
+            .get(&TypeId::of::<T>())
+            .and_then(|resource| resource.as_any().downcast_ref::<Resource<T>>())
+            .map(|resource| &resource.0)
+    }
+
+    pub fn resource_mut<T>(&mut self) -> Option<&mut T>
+    where
+        T: Component,
+    {
+        self.resources
+            .get_mut(&TypeId::of::<T>())
+            .and_then(|resource| resource.as_any_mut().downcast_mut::<Resource<T>>())
+            .map(|resource| &mut resource.0)
+    }
+
+    pub fn remove_resource<T>(&mut self) -> Option<T>
+    where
+        T: Component,
+    {
+        let resource = self.resources.remove(&TypeId::of::<T>())?;
+        resource
+            .as_any()
+            .downcast_ref::<Resource<T>>()
+            .map(|resource| resource.0.clone())
+    }
+
+    // number of completed schedule runs
+    #[inline]
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    // for carrying on a simulation that was running somewhere else, e.g. a migrated host
+    pub fn set_tick(&mut self, tick: u64) {
+        self.tick = tick;
+    }
+
+    pub(crate) fn advance_tick(&mut self) {
+        self.tick += 1;
+    }
+}
+
+fn downcast<T>(storage: &dyn ErasedStorage) -> Option<&RefCell<Storage<T>>>
+where
+    T: Component,
+{
+    storage.as_any().downcast_ref::<RefCell<Storage<T>>>()
+}
+
+fn borrow_error<T>() -> ErrorStr {
+    ErrorStr::new(format!(
+        "Component {} is already borrowed",
+        std::any::type_name::<T>()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    struct Position(i32);
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    struct Velocity(i32);
+
+    fn hash(world: &World, hasher: &mut hash::StateHasher) -> Result<(), ErrorStr> {
+        world.hash_component::<Position>(hasher)?;
+        world.hash_component::<Velocity>(hasher)
+    }
+
+    // a moving, b standing, c moving without a position
+    fn world() -> (World, [EntityId; 3]) {
+        let mut world = World::new();
+        let (a, b, c) = (
+            world.spawn().unwrap(),
+            world.spawn().unwrap(),
+            world.spawn().unwrap(),
+        );
+        world.insert(a, Position(1)).unwrap();
+        world.insert(a, Velocity(2)).unwrap();
+        world.insert(b, Position(10)).unwrap();
+        world.insert(c, Velocity(-1)).unwrap();
+        (world, [a, b, c])
+    }
+
+    #[test]
+    fn iterates_in_index_order_whatever_the_insert_order() {
+        let mut world = World::new();
+        let entities: Vec<EntityId> = (0..4).map(|_| world.spawn().unwrap()).collect();
+        for (value, entity) in entities.iter().enumerate().rev() {
+            world.insert(*entity, Position(value as i32)).unwrap();
+        }
+        let mut seen = Vec::new();
+        world
+            .each::<&Position, _>(|entity, position| seen.push((entity, position.0)))
+            .unwrap();
+        let expected: Vec<(EntityId, i32)> = entities
+            .iter()
+            .enumerate()
+            .map(|(value, entity)| (*entity, value as i32))
+            .collect();
+        assert_eq!(seen, expected);
+    }
+
+    #[test]
+    fn queries_tuples_and_options() {
+        let (mut world, [a, b, c]) = world();
+        let mut moving = Vec::new();
+        world
+            .each::<(&Position, &Velocity), _>(|entity, (position, velocity)| {
+                moving.push((entity, position.0, velocity.0))
+            })
+            .unwrap();
+        assert_eq!(moving, vec![(a, 1, 2)]);
+
+        let mut positions = Vec::new();
+        world
+            .each::<(&Position, Option<&Velocity>), _>(|entity, (position, velocity)| {
+                positions.push((entity, position.0, velocity.map(|velocity| velocity.0)))
+            })
+            .unwrap();
+        assert_eq!(positions, vec![(a, 1, Some(2)), (b, 10, None)]);
+
+        world
+            .each::<(&mut Position, &Velocity), _>(|_, (position, velocity)| {
+                position.0 += velocity.0
+            })
+            .unwrap();
+        assert_eq!(*world.get::<Position>(a).unwrap(), Position(3));
+        assert_eq!(world.matching::<&Velocity>().unwrap(), vec![a, c]);
+
+        assert!(world.despawn(a));
+        let reused = world.spawn().unwrap();
+        assert_eq!(reused.index(), a.index());
+        assert!(world.get::<Position>(reused).is_none());
+        assert!(world.insert(a, Position(0)).is_err());
+    }
+
+    #[test]
+    fn rejects_aliased_queries() {
+        let (world, _) = world();
+        let result = world.each::<(&mut Position, &Position), _>(|_, _| {});
+        assert!(result.is_err());
+        let result = world.each::<(&Position, &Position), _>(|_, _| {});
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn clones_compare_equal() {
+        let (mut world, [a, _, _]) = world();
+        world.insert_resource(7u64);
+        world.set_tick(12);
+        let mut clone = world.clone();
+        assert_eq!(
+            clone.state_hash(hash).unwrap(),
+            world.state_hash(hash).unwrap()
+        );
+        assert_eq!(clone.resource::<u64>(), Some(&7));
+        assert_eq!(clone.tick(), 12);
+
+        // copies, not shared
+        clone.insert(a, Position(99)).unwrap();
+        assert_ne!(
+            clone.state_hash(hash).unwrap(),
+            world.state_hash(hash).unwrap()
+        );
+        assert_eq!(*world.get::<Position>(a).unwrap(), Position(1));
+    }
+}
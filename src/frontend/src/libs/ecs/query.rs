@@ -0,0 +1,95 @@
+use std::cell::{Ref, RefMut};
+
+use crate::libs::ecs::World;
+use crate::libs::ecs::storage::{Component, Storage};
+use crate::libs::types::entity::EntityId;
+use crate::libs::types::errors::ErrorStr;
+
+// something World::each can iterate: &T, &mut T, Option<&T> or a tuple of those.
+// fetch borrows the storages once per query, get then looks up a single entity
+pub trait Query {
+    type Fetch<'w>;
+    type Item<'f>;
+
+    fn fetch(world: &World) -> Result<Self::Fetch<'_>, ErrorStr>;
+    fn get<'f>(fetch: &'f mut Self::Fetch<'_>, entity: EntityId) -> Option<Self::Item<'f>>;
+}
+
+impl<T> Query for &T
+where
+    T: Component,
+{
+    // None when no entity ever had the component
+    type Fetch<'w> = Option<Ref<'w, Storage<T>>>;
+    type Item<'f> = &'f T;
+
+    fn fetch(world: &World) -> Result<Self::Fetch<'_>, ErrorStr> {
+        world.storage::<T>()
+    }
+
+    fn get<'f>(fetch: &'f mut Self::Fetch<'_>, entity: EntityId) -> Option<Self::Item<'f>> {
+        fetch.as_ref()?.get(entity)
+    }
+}
+
+impl<T> Query for &mut T
+where
+    T: Component,
+{
+    type Fetch<'w> = Option<RefMut<'w, Storage<T>>>;
+    type Item<'f> = &'f mut T;
+
+    fn fetch(world: &World) -> Result<Self::Fetch<'_>, ErrorStr> {
+        world.storage_mut::<T>()
+    }
+
+    fn get<'f>(fetch: &'f mut Self::Fetch<'_>, entity: EntityId) -> Option<Self::Item<'f>> {
+        fetch.as_mut()?.get_mut(entity)
+    }
+}
+
+// matches every entity, with or without the component
+impl<Q> Query for Option<Q>
+where
+    Q: Query,
+{
+    type Fetch<'w> = Q::Fetch<'w>;
+    type Item<'f> = Option<Q::Item<'f>>;
+
+    fn fetch(world: &World) -> Result<Self::Fetch<'_>, ErrorStr> {
+        Q::fetch(world)
+    }
+
+    fn get<'f>(fetch: &'f mut Self::Fetch<'_>, entity: EntityId) -> Option<Self::Item<'f>> {
+        Some(Q::get(fetch, entity))
+    }
+}
+
+macro_rules! impl_query_tuple {
+    ($($name:ident),+) => {
+        impl<$($name),+> Query for ($($name,)+)
+        where
+            $($name: Query,)+
+        {
+            type Fetch<'w> = ($($name::Fetch<'w>,)+);
+            type Item<'f> = ($($name::Item<'f>,)+);
+
+            fn fetch(world: &World) -> Result<Self::Fetch<'_>, ErrorStr> {
+                Ok(($($name::fetch(world)?,)+))
+            }
+
+            #[allow(non_snake_case)]
+            fn get<'f>(fetch: &'f mut Self::Fetch<'_>, entity: EntityId) -> Option<Self::Item<'f>> {
+                let ($($name,)+) = fetch;
+                Some(($($name::get($name, entity)?,)+))
+            }
+        }
+    };
+}
+
+impl_query_tuple!(A);
+impl_query_tuple!(A, B);
+impl_query_tuple!(A, B, C);
+impl_query_tuple!(A, B, C, D);
+impl_query_tuple!(A, B, C, D, E);
+impl_query_tuple!(A, B, C, D, E, F);
@@ -0,0 +1,122 @@
+use crate::define_task;
+use crate::libs::ecs::World;
+use crate::libs::rendering::canvas::{RafTask, RafTime, WebGlCanvas};
+use crate::libs::types::errors::ErrorStr;
+use crate::libs::types::shared::SharedRefCell;
+
+pub trait SystemFnTrait = FnMut(&mut World) -> Result<(), ErrorStr> + 'static;
+define_task!(System, SystemFn, SystemFnTrait, (world: &mut World), Result<(), ErrorStr>);
+
+// systems run one after another in the order they were added, never concurrently, so a
+// simulation step is a pure function of the world and the order is part of it
+#[derive(Debug, Default)]
+pub struct Schedule<'a> {
+    systems: Vec<System<'a>>,
+}
+
+impl<'a> Schedule<'a> {
+    pub fn new() -> Self {
+        Self {
+            systems: Vec::new(),
+        }
+    }
+
+    pub fn add_system(&mut self, system: System<'a>) {
+        self.systems.push(system);
+    }
+
+    pub fn with_system<F>(mut self, system: F, name: &'a str) -> Self
+    where
+        F: SystemFnTrait,
+    {
+        self.add_system(System::new(system, name));
+        self
+    }
+
+    // stops at the first failing system, the rest of the step is skipped
+    pub fn run(&mut self, world: &mut World) -> Result<(), ErrorStr> {
+        for system in self.systems.iter_mut() {
+            if let Err(error) = system.execute(world) {
+                let error = format!("System '{}' failed: {}", system.name(), error);
+                return Err(ErrorStr::new(error));
+            }
+        }
+        world.advance_tick();
+        Ok(())
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &'a str> + '_ {
+        self.systems.iter().map(|system| system.name())
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.systems.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.systems.is_empty()
+    }
+}
+
+impl Schedule<'static> {
+    // one simulation step per frame
+    pub fn into_raf_task(
+        mut self,
+        world: SharedRefCell<World>,
+        name: &'static str,
+    ) -> RafTask<'static> {
+        RafTask::new(
+            move |_canvas: &WebGlCanvas, _time: RafTime| self.run(&mut world.borrow_mut()),
+            name,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, Default)]
+    struct Ran(Vec<&'static str>);
+
+    fn system(name: &'static str) -> impl SystemFnTrait {
+        move |world: &mut World| {
+            if let Some(ran) = world.resource_mut::<Ran>() {
+                ran.0.push(name);
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn runs_systems_in_the_order_added() {
+        let mut world = World::new();
+        world.insert_resource(Ran::default());
+        let mut schedule = Schedule::new()
+            .with_system(system("c"), "C")
+            .with_system(system("a"), "A");
+        schedule.add_system(System::new(system("b"), "B"));
+        assert_eq!(schedule.names().collect::<Vec<_>>(), vec!["C", "A", "B"]);
+        schedule.run(&mut world).unwrap();
+        schedule.run(&mut world).unwrap();
+        let ran = world.resource::<Ran>().unwrap();
+        assert_eq!(ran.0, vec!["c", "a", "b", "c", "a", "b"]);
+        assert_eq!(world.tick(), 2);
+    }
+
+    #[test]
+    fn stops_at_the_first_failure() {
+        let mut world = World::new();
+        world.insert_resource(Ran::default());
+        let mut schedule = Schedule::new()
+            .with_system(system("a"), "A")
+            .with_system(|_: &mut World| Err(ErrorStr::new("broken")), "Broken")
+            .with_system(system("c"), "C");
+        let error = schedule.run(&mut world).unwrap_err();
+        assert!(error.to_string().contains("Broken"));
+        assert_eq!(world.resource::<Ran>().unwrap().0, vec!["a"]);
+        assert_eq!(world.tick(), 0);
+    }
+}
@@ -0,0 +1,162 @@
+use std::any::Any;
+use std::cell::RefCell;
+
+use crate::libs::types::entity::EntityId;
+
+// Clone so whole worlds can be copied for rollback and snapshots
+pub trait Component = Clone + 'static;
+
+// components indexed by entity index. iteration always runs in index order, independent of the
+// order components were inserted in
+#[derive(Debug, Clone)]
+pub struct Storage<T> {
+    slots: Vec<Option<(EntityId, T)>>,
+    len: usize,
+}
+
+impl<T> Default for Storage<T> {
+    fn default() -> Self {
+        Self {
+            slots: Vec::new(),
+            len: 0,
+        }
+    }
+}
+
+impl<T> Storage<T> {
+    // returns the previous component of the entity
+    pub fn insert(&mut self, entity: EntityId, component: T) -> Option<T> {
+        let index = entity.index() as usize;
+        if index >= self.slots.len() {
+            self.slots.resize_with(index + 1, || None);
+        }
+        let previous = self.slots[index].replace((entity, component));
+        match previous {
+            Some((owner, previous)) if owner == entity => Some(previous),
+            Some(_) => None,
+            None => {
+                self.len += 1;
+                None
+            }
+        }
+    }
+
+    pub fn remove(&mut self, entity: EntityId) -> Option<T> {
+        let slot = self.slots.get_mut(entity.index() as usize)?;
+        match slot {
+            Some((owner, _)) if *owner == entity => {
+                self.len -= 1;
+                slot.take().map(|(_, component)| component)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn get(&self, entity: EntityId) -> Option<&T> {
+        match self.slots.get(entity.index() as usize) {
+            Some(Some((owner, component))) if *owner == entity => Some(component),
+            _ => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, entity: EntityId) -> Option<&mut T> {
+        match self.slots.get_mut(entity.index() as usize) {
+            Some(Some((owner, component))) if *owner == entity => Some(component),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    pub fn contains(&self, entity: EntityId) -> bool {
+        self.get(entity).is_some()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (EntityId, &T)> {
+        self.slots.iter().filter_map(|slot| {
+            slot.as_ref()
+                .map(|(entity, component)| (*entity, component))
+        })
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (EntityId, &mut T)> {
+        self.slots.iter_mut().filter_map(|slot| {
+            slot.as_mut()
+                .map(|(entity, component)| (*entity, component))
+        })
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+// lets the world keep storages of different component types in one map
+pub(crate) trait ErasedStorage {
+    fn remove_entity(&self, entity: EntityId);
+    fn clone_boxed(&self) -> Box<dyn ErasedStorage>;
+    fn len(&self) -> usize;
+    fn type_name(&self) -> &'static str;
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<T> ErasedStorage for RefCell<Storage<T>>
+where
+    T: Component,
+{
+    fn remove_entity(&self, entity: EntityId) {
+        self.borrow_mut().remove(entity);
+    }
+
+    fn clone_boxed(&self) -> Box<dyn ErasedStorage> {
+        Box::new(RefCell::new(self.borrow().clone()))
+    }
+
+    fn len(&self) -> usize {
+        self.borrow().len()
+    }
+
+    fn type_name(&self) -> &'static str {
+        std::any::type_name::<T>()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn iterates_in_index_order() {
+        let mut storage = Storage::default();
+        for index in [5, 1, 3] {
+            storage.insert(EntityId::from_parts(index, 0), index);
+        }
+        let indices: Vec<u32> = storage.iter().map(|(entity, _)| entity.index()).collect();
+        assert_eq!(indices, vec![1, 3, 5]);
+        assert_eq!(storage.len(), 3);
+    }
+
+    #[test]
+    fn ignores_stale_ids() {
+        let mut storage = Storage::default();
+        let stale = EntityId::from_parts(2, 0);
+        let current = EntityId::from_parts(2, 1);
+        assert_eq!(storage.insert(stale, "old"), None);
+        assert_eq!(storage.insert(current, "new"), None);
+        assert_eq!(storage.len(), 1);
+        assert_eq!(storage.get(stale), None);
+        assert_eq!(storage.remove(stale), None);
+        assert_eq!(storage.insert(current, "newer"), Some("new"));
+        assert_eq!(storage.remove(current), Some("newer"));
+        assert!(storage.is_empty());
+    }
+}
@@ -0,0 +1,338 @@
+use serde::{Deserialize, Serialize};
+
+use crate::libs::types::errors::ErrorStr;
+
+// actions are packed into a u64 per tick, axes into a fixed array
+pub const MAX_ACTIONS: usize = 64;
+pub const MAX_AXES: usize = 8;
+
+// a physical input that can trigger an action
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Binding {
+    // KeyboardEvent.code, so bindings follow the key position rather than the layout
+    Key { code: String },
+    // MouseEvent.button: 0 main, 1 middle, 2 secondary. touch and pen contact count as 0
+    Pointer { button: i16 },
+    GamepadButton { button: u32 },
+    // a stick pushed past the dead zone in one direction
+    GamepadAxis { axis: u32, positive: bool },
+}
+
+impl Binding {
+    pub fn key<S>(code: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self::Key { code: code.into() }
+    }
+}
+
+// a source for an analogue value in [-1, 1]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AxisBinding {
+    Buttons {
+        negative: Binding,
+        positive: Binding,
+    },
+    Gamepad {
+        axis: u32,
+        inverted: bool,
+    },
+    // pointer movement in canvas pixels per tick, divided by scale. mostly for pointer lock
+    PointerDelta {
+        vertical: bool,
+        scale: f32,
+    },
+    Wheel {
+        scale: f32,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ActionId(pub(crate) u8);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct AxisId(pub(crate) u8);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Action {
+    name: String,
+    bindings: Vec<Binding>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Axis {
+    name: String,
+    bindings: Vec<AxisBinding>,
+}
+
+// named actions and axes and what triggers them. ids are positions in declaration order, so
+// every peer declaring the same map agrees on them
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct InputMap {
+    actions: Vec<Action>,
+    axes: Vec<Axis>,
+}
+
+impl InputMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_action<S>(mut self, name: S, bindings: Vec<Binding>) -> Result<Self, ErrorStr>
+    where
+        S: Into<String>,
+    {
+        let name = name.into();
+        if self.action(&name).is_some() {
+            return Err(ErrorStr::new(format!("Action '{}' already exists", name)));
+        }
+        if self.actions.len() == MAX_ACTIONS {
+            let error = format!("Input maps are limited to {} actions", MAX_ACTIONS);
+            return Err(ErrorStr::new(error));
+        }
+        self.actions.push(Action { name, bindings });
+        Ok(self)
+    }
+
+    pub fn with_axis<S>(mut self, name: S, bindings: Vec<AxisBinding>) -> Result<Self, ErrorStr>
+    where
+        S: Into<String>,
+    {
+        let name = name.into();
+        if self.axis(&name).is_some() {
+            return Err(ErrorStr::new(format!("Axis '{}' already exists", name)));
+        }
+        if self.axes.len() == MAX_AXES {
+            let error = format!("Input maps are limited to {} axes", MAX_AXES);
+            return Err(ErrorStr::new(error));
+        }
+        self.axes.push(Axis { name, bindings });
+        Ok(self)
+    }
+
+    pub fn action(&self, name: &str) -> Option<ActionId> {
+        self.actions
+            .iter()
+            .position(|action| action.name == name)
+            .map(|index| ActionId(index as u8))
+    }
+
+    pub fn axis(&self, name: &str) -> Option<AxisId> {
+        self.axes
+            .iter()
+            .position(|axis| axis.name == name)
+            .map(|index| AxisId(index as u8))
+    }
+
+    pub fn action_name(&self, id: ActionId) -> Option<&str> {
+        self.actions
+            .get(id.0 as usize)
+            .map(|action| action.name.as_str())
+    }
+
+    pub fn axis_name(&self, id: AxisId) -> Option<&str> {
+        self.axes.get(id.0 as usize).map(|axis| axis.name.as_str())
+    }
+
+    pub(crate) fn actions(&self) -> impl Iterator<Item = (ActionId, &[Binding])> {
+        self.actions
+            .iter()
+            .enumerate()
+            .map(|(index, action)| (ActionId(index as u8), action.bindings.as_slice()))
+    }
+
+    pub(crate) fn axes(&self) -> impl Iterator<Item = (AxisId, &[AxisBinding])> {
+        self.axes
+            .iter()
+            .enumerate()
+            .map(|(index, axis)| (AxisId(index as u8), axis.bindings.as_slice()))
+    }
+
+    pub fn bindings(&self, id: ActionId) -> &[Binding] {
+        self.actions
+            .get(id.0 as usize)
+            .map(|action| action.bindings.as_slice())
+            .unwrap_or(&[])
+    }
+
+    pub fn rebind(&mut self, id: ActionId, bindings: Vec<Binding>) {
+        if let Some(action) = self.actions.get_mut(id.0 as usize) {
+            action.bindings = bindings;
+        }
+    }
+
+    pub fn rebind_axis(&mut self, id: AxisId, bindings: Vec<AxisBinding>) {
+        if let Some(axis) = self.axes.get_mut(id.0 as usize) {
+            axis.bindings = bindings;
+        }
+    }
+
+    // keys bound to anything, their browser defaults (scrolling, quick find) get suppressed
+    pub fn is_bound_key(&self, code: &str) -> bool {
+        let is_key =
+            |binding: &Binding| matches!(binding, Binding::Key { code: bound } if bound == code);
+        self.actions
+            .iter()
+            .any(|action| action.bindings.iter().any(is_key))
+            || self.axes.iter().any(|axis| {
+                axis.bindings.iter().any(|binding| match binding {
+                    AxisBinding::Buttons { negative, positive } => {
+                        is_key(negative) || is_key(positive)
+                    }
+                    _ => false,
+                })
+            })
+    }
+
+    // the defaults with any bindings saved under key applied on top. saved actions that no
+    // longer exist are ignored and new ones keep their defaults, so the map layout can change
+    pub fn load(defaults: Self, key: &str) -> Self {
+        let saved = web_sys::window()
+            .and_then(|window| window.local_storage().ok())
+            .flatten()
+            .and_then(|storage| storage.get_item(key).ok())
+            .flatten();
+        let saved = if let Some(saved) = saved {
+            saved
+        } else {
+            return defaults;
+        };
+        let saved: Self = match serde_json::from_str(&saved) {
+            Ok(saved) => saved,
+            Err(error) => {
+                crate::log_warn!("input", "Ignoring saved bindings '{}': {}", key, error);
+                return defaults;
+            }
+        };
+        let mut map = defaults;
+        for action in saved.actions {
+            if let Some(id) = map.action(&action.name) {
+                map.rebind(id, action.bindings);
+            }
+        }
+        for axis in saved.axes {
+            if let Some(id) = map.axis(&axis.name) {
+                map.rebind_axis(id, axis.bindings);
+            }
+        }
+        map
+    }
+
+    pub fn save(&self, key: &str) -> Result<(), ErrorStr> {
+        let storage = web_sys::window()
+            .and_then(|window| window.local_storage().ok())
+            .flatten();
+        let storage = if let Some(storage) = storage {
+            storage
+        } else {
+            return Err(ErrorStr::new("localStorage is unavailable"));
+        };
+        let json = match serde_json::to_string(self) {
+            Ok(json) => json,
+            Err(error) => {
+                let error = format!("Unable to serialise bindings: {}", error);
+                return Err(ErrorStr::new(error));
+            }
+        };
+        if let Err(error) = storage.set_item(key, &json) {
+            let error = format!("Unable to save bindings: {:?}", error);
+            return Err(ErrorStr::new(error));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map() -> InputMap {
+        InputMap::new()
+            .with_action("Jump", vec![Binding::key("Space")])
+            .unwrap()
+            .with_action("Fire", vec![Binding::Pointer { button: 0 }])
+            .unwrap()
+            .with_axis(
+                "Horizontal",
+                vec![AxisBinding::Buttons {
+                    negative: Binding::key("KeyA"),
+                    positive: Binding::key("KeyD"),
+                }],
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn ids_follow_declaration_order() {
+        let map = map();
+        assert_eq!(map.action("Jump"), Some(ActionId(0)));
+        assert_eq!(map.action("Fire"), Some(ActionId(1)));
+        assert_eq!(map.action("Crouch"), None);
+        assert_eq!(map.axis("Horizontal"), Some(AxisId(0)));
+        assert_eq!(map.action_name(ActionId(1)), Some("Fire"));
+        assert_eq!(map.axis_name(AxisId(0)), Some("Horizontal"));
+        assert_eq!(map.axis_name(AxisId(1)), None);
+    }
+
+    #[test]
+    fn rejects_duplicates_and_too_many_entries() {
+        assert!(map().with_action("Jump", Vec::new()).is_err());
+        assert!(map().with_axis("Horizontal", Vec::new()).is_err());
+        let mut full = InputMap::new();
+        for index in 0..MAX_ACTIONS {
+            full = full
+                .with_action(format!("Action {}", index), Vec::new())
+                .unwrap();
+        }
+        assert!(full.with_action("One more", Vec::new()).is_err());
+        let mut full = InputMap::new();
+        for index in 0..MAX_AXES {
+            full = full
+                .with_axis(format!("Axis {}", index), Vec::new())
+                .unwrap();
+        }
+        assert!(full.with_axis("One more", Vec::new()).is_err());
+    }
+
+    #[test]
+    fn rebinds_actions_and_axes() {
+        let mut map = map();
+        let jump = map.action("Jump").unwrap();
+        assert!(map.is_bound_key("Space"));
+        map.rebind(
+            jump,
+            vec![Binding::key("KeyJ"), Binding::GamepadButton { button: 0 }],
+        );
+        assert_eq!(map.bindings(jump).len(), 2);
+        assert!(!map.is_bound_key("Space"));
+        assert!(map.is_bound_key("KeyJ"));
+
+        let horizontal = map.axis("Horizontal").unwrap();
+        assert!(map.is_bound_key("KeyA"));
+        map.rebind_axis(
+            horizontal,
+            vec![AxisBinding::Gamepad {
+                axis: 0,
+                inverted: false,
+            }],
+        );
+        assert!(!map.is_bound_key("KeyA"));
+
+        // unknown ids are ignored
+        map.rebind(ActionId(9), vec![Binding::key("KeyX")]);
+        assert!(map.bindings(ActionId(9)).is_empty());
+        assert!(!map.is_bound_key("KeyX"));
+    }
+
+    #[test]
+    fn serialises_bindings() {
+        let map = map();
+        let json = serde_json::to_string(&map).unwrap();
+        assert!(json.contains(r#"{"type":"key","code":"Space"}"#));
+        let parsed: InputMap = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, map);
+    }
+}
@@ -0,0 +1,324 @@
+pub mod bindings;
+pub mod raw;
+pub mod snapshot;
+
+use glam::Vec2;
+use leptos::prelude::GetUntracked;
+use wasm_bindgen::JsCast;
+use wasm_bindgen::closure::Closure;
+use web_sys::{
+    AddEventListenerOptions, Event, EventTarget, HtmlCanvasElement, HtmlElement, KeyboardEvent,
+    PointerEvent, WheelEvent,
+};
+
+use crate::libs::ecs::World;
+use crate::libs::ecs::schedule::System;
+use crate::libs::input::bindings::InputMap;
+use crate::libs::input::raw::RawInput;
+use crate::libs::input::snapshot::{InputFrame, InputSnapshot};
+use crate::libs::rendering::canvas::{InitTask, WebGlCanvas};
+use crate::libs::types::errors::ErrorStr;
+use crate::libs::types::shared::{SharedRefCell, shared_ref_cell};
+use crate::{log_debug, log_error};
+
+const LOG_TARGET: &str = "input";
+// localStorage key for rebound controls
+pub const BINDINGS_KEY: &str = "input_bindings";
+
+struct Listener {
+    target: EventTarget,
+    event: &'static str,
+    callback: Closure<dyn FnMut(Event)>,
+}
+
+// collects keyboard, pointer and gamepad input for one canvas and turns it into per tick
+// snapshots through an InputMap
+pub struct Input {
+    map: SharedRefCell<InputMap>,
+    raw: SharedRefCell<RawInput>,
+    canvas: Option<HtmlCanvasElement>,
+    listeners: Vec<Listener>,
+    previous: InputSnapshot,
+}
+
+impl std::fmt::Debug for Input {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Input")
+            .field("map", &self.map)
+            .field("raw", &self.raw)
+            .field("attached", &self.canvas.is_some())
+            .field("previous", &self.previous)
+            .finish()
+    }
+}
+
+impl Drop for Input {
+    fn drop(&mut self) {
+        self.detach();
+    }
+}
+
+impl Input {
+    pub fn new(map: InputMap) -> Self {
+        Self {
+            map: shared_ref_cell(map),
+            raw: shared_ref_cell(RawInput::default()),
+            canvas: None,
+            listeners: Vec::new(),
+            previous: InputSnapshot::default(),
+        }
+    }
+
+    // the defaults with the player's saved bindings on top
+    pub fn with_saved_bindings(defaults: InputMap) -> Self {
+        Self::new(InputMap::load(defaults, BINDINGS_KEY))
+    }
+
+    // keys are read from the whole window, pointers only over the canvas
+    pub fn attach(&mut self, canvas: &HtmlCanvasElement) -> Result<(), ErrorStr> {
+        self.detach();
+        // otherwise touch drags scroll and zoom the page instead of reaching us
+        if let Err(error) = canvas.style().set_property("touch-action", "none") {
+            log_error!(LOG_TARGET, "Unable to disable touch actions: {:?}", error);
+        }
+        let window: EventTarget = if let Some(window) = web_sys::window() {
+            window.into()
+        } else {
+            return Err(ErrorStr::new("No window to read input from"));
+        };
+        let target: &EventTarget = canvas.as_ref();
+
+        let (raw, map) = (self.raw.clone(), self.map.clone());
+        self.listen(&window, "keydown", move |event| {
+            let event = if let Some(event) = keyboard_event(&event) {
+                event
+            } else {
+                return;
+            };
+            let code = event.code();
+            let shortcut = event.ctrl_key() || event.meta_key() || event.alt_key();
+            if !shortcut && map.borrow().is_bound_key(&code) {
+                event.prevent_default();
+            }
+            raw.borrow_mut().key_down(code);
+        })?;
+        let raw = self.raw.clone();
+        self.listen(&window, "keyup", move |event| {
+            // released keys always count, even over text inputs
+            if let Some(event) = event.dyn_ref::<KeyboardEvent>() {
+                raw.borrow_mut().key_up(&event.code());
+            }
+        })?;
+        let raw = self.raw.clone();
+        self.listen(&window, "blur", move |_| raw.borrow_mut().release_all())?;
+
+        let raw = self.raw.clone();
+        self.listen(target, "pointerdown", move |event| {
+            if let Some(event) = event.dyn_ref::<PointerEvent>() {
+                event.prevent_default();
+                // keep getting moves and the release when dragged off the canvas
+                if let Some(canvas) = event
+                    .target()
+                    .and_then(|target| target.dyn_into::<HtmlElement>().ok())
+                {
+                    let _ = canvas.set_pointer_capture(event.pointer_id());
+                }
+                update_pointer(&mut raw.borrow_mut(), event);
+            }
+        })?;
+        for name in ["pointermove", "pointerup"] {
+            let raw = self.raw.clone();
+            self.listen(target, name, move |event| {
+                if let Some(event) = event.dyn_ref::<PointerEvent>() {
+                    let mut raw = raw.borrow_mut();
+                    update_pointer(&mut raw, event);
+                    let delta = Vec2::new(event.movement_x() as f32, event.movement_y() as f32);
+                    raw.pointer_moved(delta);
+                }
+            })?;
+        }
+        for name in ["pointercancel", "pointerleave"] {
+            let raw = self.raw.clone();
+            self.listen(target, name, move |event| {
+                if let Some(event) = event.dyn_ref::<PointerEvent>() {
+                    // mice stay around after leaving, only forget the position
+                    let keep = event.pointer_type() == "mouse" && event.buttons() != 0;
+                    if !keep {
+                        raw.borrow_mut()
+                            .pointer_left(event.pointer_id(), event.is_primary());
+                    }
+                }
+            })?;
+        }
+        let raw = self.raw.clone();
+        self.listen(target, "wheel", move |event| {
+            if let Some(event) = event.dyn_ref::<WheelEvent>() {
+                event.prevent_default();
+                raw.borrow_mut().wheel(event.delta_y() as f32);
+            }
+        })?;
+        self.listen(target, "contextmenu", |event| event.prevent_default())?;
+
+        self.canvas = Some(canvas.clone());
+        log_debug!(LOG_TARGET, "Input attached to canvas");
+        Ok(())
+    }
+
+    pub fn detach(&mut self) {
+        for listener in self.listeners.drain(..) {
+            let _ = listener.target.remove_event_listener_with_callback(
+                listener.event,
+                listener.callback.as_ref().unchecked_ref(),
+            );
+        }
+        self.canvas = None;
+        self.raw.borrow_mut().release_all();
+    }
+
+    fn listen<F>(
+        &mut self,
+        target: &EventTarget,
+        event: &'static str,
+        callback: F,
+    ) -> Result<(), ErrorStr>
+    where
+        F: FnMut(Event) + 'static,
+    {
+        let callback = Closure::<dyn FnMut(Event)>::new(callback);
+        // not passive, prevent_default has to work for wheel and touch
+        let options = AddEventListenerOptions::new();
+        options.set_passive(false);
+        let result = target.add_event_listener_with_callback_and_add_event_listener_options(
+            event,
+            callback.as_ref().unchecked_ref(),
+            &options,
+        );
+        if let Err(error) = result {
+            let error = format!("Unable to listen for {}: {:?}", event, error);
+            return Err(ErrorStr::new(error));
+        }
+        self.listeners.push(Listener {
+            target: target.clone(),
+            event,
+            callback,
+        });
+        Ok(())
+    }
+
+    // has to be called from a user gesture, e.g. a click handler
+    pub fn request_pointer_lock(&self) {
+        if let Some(canvas) = self.canvas.as_ref() {
+            canvas.request_pointer_lock();
+        }
+    }
+
+    pub fn exit_pointer_lock(&self) {
+        if let Some(document) = web_sys::window().and_then(|window| window.document()) {
+            document.exit_pointer_lock();
+        }
+    }
+
+    pub fn pointer_locked(&self) -> bool {
+        let locked = web_sys::window()
+            .and_then(|window| window.document())
+            .and_then(|document| document.pointer_lock_element());
+        match (locked, self.canvas.as_ref()) {
+            (Some(locked), Some(canvas)) => locked == ***canvas,
+            _ => false,
+        }
+    }
+
+    // polls gamepads and maps the raw state. call exactly once per simulation tick, pointer
+    // movement and wheel are accumulated until then
+    pub fn snapshot(&mut self, tick: u64) -> InputSnapshot {
+        let mut raw = self.raw.borrow_mut();
+        raw.poll_gamepad();
+        let snapshot = InputSnapshot::from_raw(tick, &raw, &self.map.borrow());
+        raw.end_tick();
+        snapshot
+    }
+
+    // a snapshot together with the one from the previous tick
+    pub fn frame(&mut self, tick: u64) -> InputFrame {
+        let current = self.snapshot(tick);
+        let previous = std::mem::replace(&mut self.previous, current);
+        InputFrame { current, previous }
+    }
+
+    #[inline]
+    pub fn map(&self) -> std::cell::Ref<'_, InputMap> {
+        self.map.borrow()
+    }
+
+    // changes apply from the next snapshot, save_bindings keeps them
+    #[inline]
+    pub fn map_mut(&self) -> std::cell::RefMut<'_, InputMap> {
+        self.map.borrow_mut()
+    }
+
+    pub fn save_bindings(&self) -> Result<(), ErrorStr> {
+        self.map.borrow().save(BINDINGS_KEY)
+    }
+
+    // attaches to the canvas element once it exists
+    pub fn into_init_task<'a>(input: SharedRefCell<Input>, name: &'a str) -> InitTask<'a> {
+        InitTask::new(
+            move |canvas: &WebGlCanvas| {
+                let element = canvas
+                    .get_canvas()
+                    .and_then(|canvas_ref| canvas_ref.get_untracked());
+                let element = if let Some(element) = element {
+                    element
+                } else {
+                    return false;
+                };
+                match input.borrow_mut().attach(&element) {
+                    Ok(_) => true,
+                    Err(error) => {
+                        log_error!(LOG_TARGET, "{}", error);
+                        false
+                    }
+                }
+            },
+            name,
+        )
+    }
+
+    // takes this tick's snapshot into the world as an InputFrame resource. add it first in the
+    // schedule so every system sees the same input
+    pub fn into_system<'a>(input: SharedRefCell<Input>, name: &'a str) -> System<'a> {
+        System::new(
+            move |world: &mut World| {
+                let frame = input.borrow_mut().frame(world.tick());
+                world.insert_resource(frame);
+                Ok(())
+            },
+            name,
+        )
+    }
+}
+
+// keys typed into text fields belong to them
+fn keyboard_event(event: &Event) -> Option<&KeyboardEvent> {
+    let editable = event
+        .target()
+        .and_then(|target| target.dyn_into::<HtmlElement>().ok())
+        .is_some_and(|element| {
+            let tag = element.tag_name();
+            tag == "INPUT" || tag == "TEXTAREA" || tag == "SELECT" || element.is_content_editable()
+        });
+    if editable {
+        return None;
+    }
+    event.dyn_ref::<KeyboardEvent>()
+}
+
+fn update_pointer(raw: &mut RawInput, event: &PointerEvent) {
+    let position = Vec2::new(event.offset_x() as f32, event.offset_y() as f32);
+    raw.pointer(
+        event.pointer_id(),
+        event.is_primary(),
+        position,
+        event.buttons(),
+    );
+}
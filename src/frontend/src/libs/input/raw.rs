@@ -0,0 +1,182 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use glam::Vec2;
+use wasm_bindgen::JsCast;
+use web_sys::{Gamepad, GamepadButton};
+
+use crate::libs::input::bindings::{AxisBinding, Binding};
+
+// sticks rest slightly off centre, anything below this reads as 0
+const GAMEPAD_DEAD_ZONE: f32 = 0.15;
+
+#[derive(Debug, Clone, Copy, Default)]
+struct PointerState {
+    position: Vec2,
+    // MouseEvent.buttons bit mask
+    buttons: u16,
+}
+
+#[derive(Debug, Clone, Default)]
+struct GamepadState {
+    buttons: Vec<bool>,
+    axes: Vec<f32>,
+}
+
+// device state as the browser events left it, before any mapping
+#[derive(Debug, Clone, Default)]
+pub struct RawInput {
+    keys: BTreeSet<String>,
+    // by pointer id, touch gives one per finger
+    pointers: BTreeMap<i32, PointerState>,
+    primary: Option<Vec2>,
+    // accumulated between ticks
+    pointer_delta: Vec2,
+    wheel: f32,
+    gamepad: GamepadState,
+}
+
+impl RawInput {
+    pub(crate) fn key_down(&mut self, code: String) {
+        self.keys.insert(code);
+    }
+
+    pub(crate) fn key_up(&mut self, code: &str) {
+        self.keys.remove(code);
+    }
+
+    pub(crate) fn pointer(&mut self, id: i32, primary: bool, position: Vec2, buttons: u16) {
+        self.pointers.insert(id, PointerState { position, buttons });
+        if primary {
+            self.primary = Some(position);
+        }
+    }
+
+    pub(crate) fn pointer_moved(&mut self, delta: Vec2) {
+        self.pointer_delta += delta;
+    }
+
+    pub(crate) fn pointer_left(&mut self, id: i32, primary: bool) {
+        self.pointers.remove(&id);
+        if primary {
+            self.primary = None;
+        }
+    }
+
+    pub(crate) fn wheel(&mut self, delta: f32) {
+        self.wheel += delta;
+    }
+
+    // focus lost, key and button up events will never arrive
+    pub(crate) fn release_all(&mut self) {
+        self.keys.clear();
+        for pointer in self.pointers.values_mut() {
+            pointer.buttons = 0;
+        }
+    }
+
+    pub(crate) fn end_tick(&mut self) {
+        self.pointer_delta = Vec2::ZERO;
+        self.wheel = 0.0;
+    }
+
+    // first connected gamepad only
+    pub(crate) fn poll_gamepad(&mut self) {
+        let gamepads = web_sys::window().and_then(|window| window.navigator().get_gamepads().ok());
+        let gamepad = gamepads.and_then(|gamepads| {
+            gamepads
+                .iter()
+                .filter_map(|gamepad| gamepad.dyn_into::<Gamepad>().ok())
+                .find(|gamepad| gamepad.connected())
+        });
+        let gamepad = if let Some(gamepad) = gamepad {
+            gamepad
+        } else {
+            self.gamepad = GamepadState::default();
+            return;
+        };
+        self.gamepad.buttons = gamepad
+            .buttons()
+            .iter()
+            .map(|button| {
+                button
+                    .dyn_into::<GamepadButton>()
+                    .is_ok_and(|button| button.pressed())
+            })
+            .collect();
+        self.gamepad.axes = gamepad
+            .axes()
+            .iter()
+            .map(|axis| {
+                let value = axis.as_f64().unwrap_or(0.0) as f32;
+                if value.abs() < GAMEPAD_DEAD_ZONE {
+                    0.0
+                } else {
+                    value
+                }
+            })
+            .collect();
+    }
+
+    #[inline]
+    pub fn primary_pointer(&self) -> Option<Vec2> {
+        self.primary
+    }
+
+    pub fn pressed(&self, binding: &Binding) -> bool {
+        match binding {
+            Binding::Key { code } => self.keys.contains(code),
+            Binding::Pointer { button } => {
+                let mask = pointer_button_mask(*button);
+                self.pointers
+                    .values()
+                    .any(|pointer| pointer.buttons & mask != 0)
+            }
+            Binding::GamepadButton { button } => self
+                .gamepad
+                .buttons
+                .get(*button as usize)
+                .copied()
+                .unwrap_or(false),
+            Binding::GamepadAxis { axis, positive } => {
+                let value = self.gamepad_axis(*axis);
+                if *positive { value > 0.0 } else { value < 0.0 }
+            }
+        }
+    }
+
+    pub fn axis(&self, binding: &AxisBinding) -> f32 {
+        match binding {
+            AxisBinding::Buttons { negative, positive } => {
+                self.pressed(positive) as i32 as f32 - self.pressed(negative) as i32 as f32
+            }
+            AxisBinding::Gamepad { axis, inverted } => {
+                let value = self.gamepad_axis(*axis);
+                if *inverted { -value } else { value }
+            }
+            AxisBinding::PointerDelta { vertical, scale } => {
+                let delta = if *vertical {
+                    self.pointer_delta.y
+                } else {
+                    self.pointer_delta.x
+                };
+                delta / scale
+            }
+            AxisBinding::Wheel { scale } => self.wheel / scale,
+        }
+    }
+
+    fn gamepad_axis(&self, axis: u32) -> f32 {
+        self.gamepad.axes.get(axis as usize).copied().unwrap_or(0.0)
+    }
+}
+
+// MouseEvent.button numbers to MouseEvent.buttons bits, which swap middle and secondary
+fn pointer_button_mask(button: i16) -> u16 {
+    match button {
+        0 => 1,
+        1 => 4,
+        2 => 2,
+        button if (0..16).contains(&button) => 1 << button,
+        _ => 0,
+    }
+}
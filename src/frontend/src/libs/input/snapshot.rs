@@ -0,0 +1,255 @@
+use serde::{Deserialize, Serialize};
+
+use crate::libs::input::bindings::{ActionId, AxisId, InputMap, MAX_AXES};
+use crate::libs::input::raw::RawInput;
+
+// everything the simulation may read about input for one tick. values are quantised when the
+// snapshot is taken, so the local simulation sees exactly what gets sent to peers
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct InputSnapshot {
+    pub tick: u64,
+    // bit n set while action n is held
+    pub actions: u64,
+    // [-1, 1] scaled to i16
+    pub axes: [i16; MAX_AXES],
+    // canvas pixels of the primary pointer, None when it left the canvas
+    pub pointer: Option<[i16; 2]>,
+}
+
+impl InputSnapshot {
+    // maps the raw device state through the bindings
+    pub(crate) fn from_raw(tick: u64, raw: &RawInput, map: &InputMap) -> Self {
+        let mut snapshot = Self {
+            tick,
+            ..Self::default()
+        };
+        for (id, bindings) in map.actions() {
+            let pressed = bindings.iter().any(|binding| raw.pressed(binding));
+            snapshot.set_action(id, pressed);
+        }
+        for (id, bindings) in map.axes() {
+            let value: f32 = bindings.iter().map(|binding| raw.axis(binding)).sum();
+            snapshot.set_axis(id, value);
+        }
+        snapshot.pointer = raw
+            .primary_pointer()
+            .map(|pointer| [pointer.x as i16, pointer.y as i16]);
+        snapshot
+    }
+
+    #[inline]
+    pub fn pressed(&self, action: ActionId) -> bool {
+        self.actions & (1 << action.0) != 0
+    }
+
+    #[inline]
+    pub fn axis(&self, axis: AxisId) -> f32 {
+        self.axes[axis.0 as usize] as f32 / i16::MAX as f32
+    }
+
+    pub(crate) fn set_action(&mut self, action: ActionId, pressed: bool) {
+        if pressed {
+            self.actions |= 1 << action.0;
+        } else {
+            self.actions &= !(1 << action.0);
+        }
+    }
+
+    pub(crate) fn set_axis(&mut self, axis: AxisId, value: f32) {
+        self.axes[axis.0 as usize] = (value.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16;
+    }
+}
+
+// the current tick's input next to the previous one, for edge detection. inserted into the
+// world as a resource before the simulation systems run
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct InputFrame {
+    pub current: InputSnapshot,
+    pub previous: InputSnapshot,
+}
+
+impl InputFrame {
+    #[inline]
+    pub fn pressed(&self, action: ActionId) -> bool {
+        self.current.pressed(action)
+    }
+
+    #[inline]
+    pub fn just_pressed(&self, action: ActionId) -> bool {
+        self.current.pressed(action) && !self.previous.pressed(action)
+    }
+
+    #[inline]
+    pub fn just_released(&self, action: ActionId) -> bool {
+        !self.current.pressed(action) && self.previous.pressed(action)
+    }
+
+    #[inline]
+    pub fn axis(&self, axis: AxisId) -> f32 {
+        self.current.axis(axis)
+    }
+}
+
+// every player's input for one tick, indexed by player slot. the local player's frame is also
+// available on its own as the InputFrame resource
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PlayerInputs {
+    pub frames: Vec<InputFrame>,
+}
+
+impl PlayerInputs {
+    #[inline]
+    pub fn player(&self, slot: usize) -> Option<&InputFrame> {
+        self.frames.get(slot)
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec2;
+
+    use super::*;
+    use crate::libs::input::bindings::{AxisBinding, Binding};
+
+    fn map() -> InputMap {
+        InputMap::new()
+            .with_action("Jump", vec![Binding::key("Space"), Binding::key("KeyW")])
+            .unwrap()
+            .with_action("Fire", vec![Binding::Pointer { button: 0 }])
+            .unwrap()
+            .with_axis(
+                "Horizontal",
+                vec![AxisBinding::Buttons {
+                    negative: Binding::key("KeyA"),
+                    positive: Binding::key("KeyD"),
+                }],
+            )
+            .unwrap()
+            .with_axis("Zoom", vec![AxisBinding::Wheel { scale: 100.0 }])
+            .unwrap()
+    }
+
+    // what Input::frame does once per tick
+    fn frame(
+        tick: u64,
+        raw: &mut RawInput,
+        map: &InputMap,
+        previous: &mut InputSnapshot,
+    ) -> InputFrame {
+        let current = InputSnapshot::from_raw(tick, raw, map);
+        raw.end_tick();
+        let previous = std::mem::replace(previous, current);
+        InputFrame { current, previous }
+    }
+
+    #[test]
+    fn maps_actions_and_axes() {
+        let map = map();
+        let jump = map.action("Jump").unwrap();
+        let fire = map.action("Fire").unwrap();
+        let horizontal = map.axis("Horizontal").unwrap();
+        let zoom = map.axis("Zoom").unwrap();
+        let mut raw = RawInput::default();
+        raw.key_down("KeyW".to_string());
+        raw.key_down("KeyA".to_string());
+        raw.pointer(1, true, Vec2::new(12.0, 34.0), 1);
+        raw.wheel(50.0);
+        let snapshot = InputSnapshot::from_raw(7, &raw, &map);
+        assert_eq!(snapshot.tick, 7);
+        assert!(snapshot.pressed(jump));
+        assert!(snapshot.pressed(fire));
+        assert_eq!(snapshot.axis(horizontal), -1.0);
+        assert!((snapshot.axis(zoom) - 0.5).abs() < 1e-4);
+        assert_eq!(snapshot.pointer, Some([12, 34]));
+
+        // opposite buttons cancel out, the wheel only counts for the tick it happened in
+        raw.key_down("KeyD".to_string());
+        raw.end_tick();
+        raw.pointer_left(1, true);
+        let snapshot = InputSnapshot::from_raw(8, &raw, &map);
+        assert_eq!(snapshot.axis(horizontal), 0.0);
+        assert_eq!(snapshot.axis(zoom), 0.0);
+        assert!(!snapshot.pressed(fire));
+        assert_eq!(snapshot.pointer, None);
+    }
+
+    #[test]
+    fn rebinding_applies_to_the_next_snapshot() {
+        let mut map = map();
+        let jump = map.action("Jump").unwrap();
+        let horizontal = map.axis("Horizontal").unwrap();
+        let mut raw = RawInput::default();
+        raw.key_down("Space".to_string());
+        raw.key_down("ArrowRight".to_string());
+        assert!(InputSnapshot::from_raw(0, &raw, &map).pressed(jump));
+        map.rebind(jump, vec![Binding::key("KeyJ")]);
+        map.rebind_axis(
+            horizontal,
+            vec![AxisBinding::Buttons {
+                negative: Binding::key("ArrowLeft"),
+                positive: Binding::key("ArrowRight"),
+            }],
+        );
+        let snapshot = InputSnapshot::from_raw(1, &raw, &map);
+        assert!(!snapshot.pressed(jump));
+        assert_eq!(snapshot.axis(horizontal), 1.0);
+        raw.key_down("KeyJ".to_string());
+        assert!(InputSnapshot::from_raw(2, &raw, &map).pressed(jump));
+    }
+
+    #[test]
+    fn frames_report_pressed_held_and_released_edges() {
+        let map = map();
+        let jump = map.action("Jump").unwrap();
+        let mut raw = RawInput::default();
+        let mut previous = InputSnapshot::default();
+
+        let idle = frame(0, &mut raw, &map, &mut previous);
+        assert!(!idle.pressed(jump) && !idle.just_pressed(jump) && !idle.just_released(jump));
+
+        raw.key_down("Space".to_string());
+        let pressed = frame(1, &mut raw, &map, &mut previous);
+        assert!(pressed.pressed(jump) && pressed.just_pressed(jump));
+        assert!(!pressed.just_released(jump));
+
+        let held = frame(2, &mut raw, &map, &mut previous);
+        assert!(held.pressed(jump));
+        assert!(!held.just_pressed(jump) && !held.just_released(jump));
+        assert_eq!(held.previous.tick, 1);
+
+        raw.key_up("Space");
+        let released = frame(3, &mut raw, &map, &mut previous);
+        assert!(!released.pressed(jump) && !released.just_pressed(jump));
+        assert!(released.just_released(jump));
+
+        // losing focus releases everything, the release shows up as an edge
+        raw.key_down("KeyW".to_string());
+        assert!(frame(4, &mut raw, &map, &mut previous).just_pressed(jump));
+        raw.release_all();
+        assert!(frame(5, &mut raw, &map, &mut previous).just_released(jump));
+    }
+
+    #[test]
+    fn quantises_axes() {
+        let mut snapshot = InputSnapshot::default();
+        let axis = AxisId(0);
+        snapshot.set_axis(axis, 2.0);
+        assert_eq!(snapshot.axes[0], i16::MAX);
+        snapshot.set_axis(axis, -0.5);
+        assert_eq!(snapshot.axes[0], -16384);
+        snapshot.set_action(ActionId(63), true);
+        assert_eq!(snapshot.actions, 1 << 63);
+        snapshot.set_action(ActionId(63), false);
+        assert_eq!(snapshot.actions, 0);
+    }
+}
@@ -0,0 +1,157 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::libs::types::errors::ErrorStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Level {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+    // only valid in filters, silences a target
+    Off,
+}
+
+impl Level {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Level::Trace => "TRACE",
+            Level::Debug => "DEBUG",
+            Level::Info => "INFO",
+            Level::Warn => "WARN",
+            Level::Error => "ERROR",
+            Level::Off => "OFF",
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for Level {
+    type Err = ErrorStr;
+
+    fn from_str(level: &str) -> Result<Self, Self::Err> {
+        match level.trim().to_ascii_lowercase().as_str() {
+            "trace" => Ok(Level::Trace),
+            "debug" => Ok(Level::Debug),
+            "info" => Ok(Level::Info),
+            "warn" | "warning" => Ok(Level::Warn),
+            "error" => Ok(Level::Error),
+            "off" | "none" => Ok(Level::Off),
+            _ => Err(ErrorStr::new(format!("Unknown log level '{}'", level))),
+        }
+    }
+}
+
+// what's logged with no filter set, and under targets a filter doesn't mention
+pub fn default_level() -> Level {
+    if cfg!(debug_assertions) {
+        Level::Info
+    } else {
+        Level::Warn
+    }
+}
+
+// env_logger style spec: "warn,rendering=debug,net::webrtc=trace". a bare level sets the default,
+// target=level overrides everything under that module path
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Filter {
+    default: Level,
+    targets: Vec<(String, Level)>,
+}
+
+impl Filter {
+    pub fn new(default: Level) -> Self {
+        Self {
+            default,
+            targets: Vec::new(),
+        }
+    }
+
+    pub fn parse(spec: &str) -> Result<Self, ErrorStr> {
+        let mut filter = Self::new(default_level());
+        for directive in spec.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            match directive.split_once('=') {
+                Some((target, level)) => {
+                    let level = level.parse::<Level>()?;
+                    filter.set_target(target.trim(), level);
+                }
+                None => filter.default = directive.parse::<Level>()?,
+            }
+        }
+        Ok(filter)
+    }
+
+    pub fn set_target(&mut self, target: &str, level: Level) {
+        self.targets.retain(|(existing, _)| existing != target);
+        self.targets.push((target.to_string(), level));
+        // longest first, so the most specific override is found first
+        self.targets
+            .sort_by_key(|(target, _)| std::cmp::Reverse(target.len()));
+    }
+
+    pub fn level_for(&self, target: &str) -> Level {
+        self.targets
+            .iter()
+            .find(|(prefix, _)| {
+                target == prefix
+                    || (target.starts_with(prefix.as_str())
+                        && target[prefix.len()..].starts_with("::"))
+            })
+            .map(|(_, level)| *level)
+            .unwrap_or(self.default)
+    }
+
+    #[inline]
+    pub fn enabled(&self, level: Level, target: &str) -> bool {
+        level != Level::Off && level >= self.level_for(target)
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.default.as_str().to_ascii_lowercase())?;
+        for (target, level) in self.targets.iter() {
+            write!(f, ",{}={}", target, level.as_str().to_ascii_lowercase())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_levels_and_targets() {
+        let filter = Filter::parse("warn, rendering=debug,net::webrtc=trace").unwrap();
+        assert_eq!(filter.level_for("app"), Level::Warn);
+        assert_eq!(filter.level_for("rendering"), Level::Debug);
+        assert_eq!(filter.level_for("rendering::canvas"), Level::Debug);
+        assert_eq!(filter.level_for("net::webrtc::channel"), Level::Trace);
+        assert_eq!(filter.level_for("net::loopback"), Level::Warn);
+        // a prefix only matches whole path segments
+        assert_eq!(filter.level_for("renderingx"), Level::Warn);
+        assert_eq!(filter.to_string(), "warn,net::webrtc=trace,rendering=debug");
+    }
+
+    #[test]
+    fn targets_alone_keep_the_default_level() {
+        let filter = Filter::parse("rendering=off").unwrap();
+        assert_eq!(filter.level_for("app"), default_level());
+        assert!(!filter.enabled(Level::Error, "rendering::canvas"));
+        assert_eq!(Filter::parse("").unwrap(), Filter::new(default_level()));
+    }
+
+    #[test]
+    fn rejects_unknown_levels() {
+        assert!(Filter::parse("loud").is_err());
+        assert!(Filter::parse("rendering=loud").is_err());
+    }
+}
@@ -0,0 +1,346 @@
+pub mod filter;
+
+use std::cell::RefCell;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+use web_sys::UrlSearchParams;
+
+use crate::libs::logging::filter::{Filter, Level, default_level};
+use crate::libs::types::download::download_bytes;
+use crate::libs::types::errors::ErrorStr;
+
+// entries kept for bug reports
+pub const LOG_HISTORY: usize = 512;
+// the same message is printed at most RATE_BURST times per window, the rest are counted
+const RATE_WINDOW_MS: f64 = 1000.0;
+const RATE_BURST: u32 = 5;
+const MAX_RATE_KEYS: usize = 256;
+// ?log=warn,rendering=debug in the url, or the same spec saved under this localStorage key
+const FILTER_KEY: &str = "log";
+
+/// Logs at the given level under a module style target, e.g.
+/// `log_at!(Level::Info, "rendering::canvas", "Initialising {}", name)`.
+/// The message is only formatted when the target's filter lets it through
+#[macro_export]
+macro_rules! log_at {
+    ($level:expr, $target:expr, $($arg:tt)+) => {
+        if $crate::libs::logging::enabled($level, $target) {
+            $crate::libs::logging::log($level, $target, format_args!($($arg)+));
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! log_error {
+    ($target:expr, $($arg:tt)+) => {
+        $crate::log_at!($crate::libs::logging::filter::Level::Error, $target, $($arg)+)
+    };
+}
+
+#[macro_export]
+macro_rules! log_warn {
+    ($target:expr, $($arg:tt)+) => {
+        $crate::log_at!($crate::libs::logging::filter::Level::Warn, $target, $($arg)+)
+    };
+}
+
+#[macro_export]
+macro_rules! log_info {
+    ($target:expr, $($arg:tt)+) => {
+        $crate::log_at!($crate::libs::logging::filter::Level::Info, $target, $($arg)+)
+    };
+}
+
+#[macro_export]
+macro_rules! log_debug {
+    ($target:expr, $($arg:tt)+) => {
+        $crate::log_at!($crate::libs::logging::filter::Level::Debug, $target, $($arg)+)
+    };
+}
+
+#[macro_export]
+macro_rules! log_trace {
+    ($target:expr, $($arg:tt)+) => {
+        $crate::log_at!($crate::libs::logging::filter::Level::Trace, $target, $($arg)+)
+    };
+}
+
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    // ms since the unix epoch
+    pub timestamp: f64,
+    pub level: Level,
+    pub target: &'static str,
+    pub message: String,
+}
+
+impl fmt::Display for LogEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {:5} {}: {}",
+            format_timestamp(self.timestamp),
+            self.level.as_str(),
+            self.target,
+            self.message
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct RateState {
+    window_start: f64,
+    count: u32,
+    suppressed: u32,
+}
+
+#[derive(Debug)]
+struct Logger {
+    filter: Filter,
+    history: VecDeque<LogEntry>,
+    rates: HashMap<u64, RateState>,
+}
+
+thread_local! {
+    static LOGGER: RefCell<Logger> = RefCell::new(Logger {
+        filter: Filter::new(default_level()),
+        history: VecDeque::with_capacity(LOG_HISTORY),
+        rates: HashMap::new(),
+    });
+}
+
+// picks up the filter from the query string, falling back to localStorage. call once at startup
+pub fn init() {
+    let spec = if let Some(spec) = query_filter().or_else(stored_filter) {
+        spec
+    } else {
+        return;
+    };
+    if let Err(error) = set_filter(&spec) {
+        crate::log_warn!("logging", "Ignoring log filter '{}': {}", spec, error);
+    }
+}
+
+fn query_filter() -> Option<String> {
+    let search = web_sys::window()?.location().search().ok()?;
+    UrlSearchParams::new_with_str(&search).ok()?.get(FILTER_KEY)
+}
+
+fn stored_filter() -> Option<String> {
+    let storage = web_sys::window()?.local_storage().ok()??;
+    storage.get_item(FILTER_KEY).ok()?
+}
+
+pub fn set_filter(spec: &str) -> Result<(), ErrorStr> {
+    let filter = Filter::parse(spec)?;
+    LOGGER.with(|logger| logger.borrow_mut().filter = filter);
+    Ok(())
+}
+
+// applies the filter and keeps it for the next page load
+pub fn save_filter(spec: &str) -> Result<(), ErrorStr> {
+    set_filter(spec)?;
+    let storage = web_sys::window()
+        .and_then(|window| window.local_storage().ok())
+        .flatten();
+    let storage = if let Some(storage) = storage {
+        storage
+    } else {
+        return Err(ErrorStr::new("localStorage is unavailable"));
+    };
+    if let Err(error) = storage.set_item(FILTER_KEY, spec) {
+        let error = format!("Unable to save log filter: {:?}", error);
+        return Err(ErrorStr::new(error));
+    }
+    Ok(())
+}
+
+pub fn filter() -> Filter {
+    LOGGER.with(|logger| logger.borrow().filter.clone())
+}
+
+#[inline]
+pub fn enabled(level: Level, target: &str) -> bool {
+    LOGGER.with(|logger| logger.borrow().filter.enabled(level, target))
+}
+
+// use the log_* macros instead, they skip formatting for filtered messages
+pub fn log(level: Level, target: &'static str, args: fmt::Arguments) {
+    let message = args.to_string();
+    let now = now();
+    let summary = LOGGER.with(|logger| {
+        let mut logger = logger.borrow_mut();
+        let key = message_key(target, &message);
+        if logger.rates.len() >= MAX_RATE_KEYS {
+            logger
+                .rates
+                .retain(|_, rate| now - rate.window_start <= RATE_WINDOW_MS);
+        }
+        let rate = logger.rates.entry(key).or_insert(RateState {
+            window_start: now,
+            count: 0,
+            suppressed: 0,
+        });
+        let mut summary = None;
+        if now - rate.window_start > RATE_WINDOW_MS {
+            if rate.suppressed > 0 {
+                summary = Some(rate.suppressed);
+            }
+            *rate = RateState {
+                window_start: now,
+                count: 0,
+                suppressed: 0,
+            };
+        }
+        rate.count += 1;
+        if rate.count > RATE_BURST {
+            rate.suppressed += 1;
+            return Err(());
+        }
+        Ok(summary)
+    });
+    let summary = if let Ok(summary) = summary {
+        summary
+    } else {
+        return;
+    };
+    if let Some(suppressed) = summary {
+        let repeated = format!("previous message repeated {} more times", suppressed);
+        emit(LogEntry {
+            timestamp: now,
+            level,
+            target,
+            message: repeated,
+        });
+    }
+    emit(LogEntry {
+        timestamp: now,
+        level,
+        target,
+        message,
+    });
+}
+
+fn message_key(target: &str, message: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    target.hash(&mut hasher);
+    message.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn emit(entry: LogEntry) {
+    let line = format!("[{} {}] {}", entry.level, entry.target, entry.message);
+    write_line(entry.level, &line);
+    LOGGER.with(|logger| {
+        let mut logger = logger.borrow_mut();
+        if logger.history.len() == LOG_HISTORY {
+            logger.history.pop_front();
+        }
+        logger.history.push_back(entry);
+    });
+}
+
+// the browser's clock and console in wasm, std elsewhere so native tests can log too. ms since
+// the unix epoch
+#[cfg(target_arch = "wasm32")]
+fn now() -> f64 {
+    js_sys::Date::now()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn now() -> f64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|since| since.as_secs_f64() * 1000.0)
+        .unwrap_or(0.0)
+}
+
+#[cfg(target_arch = "wasm32")]
+fn format_timestamp(timestamp: f64) -> String {
+    let date = js_sys::Date::new(&wasm_bindgen::JsValue::from_f64(timestamp));
+    String::from(date.to_iso_string())
+}
+
+// seconds since the epoch, there's no calendar outside the browser
+#[cfg(not(target_arch = "wasm32"))]
+fn format_timestamp(timestamp: f64) -> String {
+    format!("{:.3}", timestamp / 1000.0)
+}
+
+#[cfg(target_arch = "wasm32")]
+fn write_line(level: Level, line: &str) {
+    let line = wasm_bindgen::JsValue::from_str(line);
+    match level {
+        Level::Trace | Level::Debug => web_sys::console::debug_1(&line),
+        Level::Info => web_sys::console::info_1(&line),
+        Level::Warn => web_sys::console::warn_1(&line),
+        Level::Error | Level::Off => web_sys::console::error_1(&line),
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write_line(_level: Level, line: &str) {
+    eprintln!("{}", line);
+}
+
+#[cfg(target_arch = "wasm32")]
+fn user_agent() -> Option<String> {
+    web_sys::window()?.navigator().user_agent().ok()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn user_agent() -> Option<String> {
+    None
+}
+
+pub fn recent() -> Vec<LogEntry> {
+    LOGGER.with(|logger| logger.borrow().history.iter().cloned().collect())
+}
+
+// plain text dump of the recent entries, to attach to bug reports
+pub fn export_logs() -> String {
+    let mut export = format!("filter: {}\n", filter());
+    if let Some(agent) = user_agent() {
+        export.push_str(&format!("user agent: {}\n", agent));
+    }
+    for entry in recent() {
+        export.push_str(&entry.to_string());
+        export.push('\n');
+    }
+    export
+}
+
+// saves export_logs() as a text file
+pub fn download_logs() -> Result<(), ErrorStr> {
+    let file_name = format!("logs-{}.txt", now() as u64);
+    download_bytes(export_logs().as_bytes(), "text/plain", &file_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TARGET: &str = "logging::tests";
+
+    #[test]
+    fn keeps_history_and_limits_repeats() {
+        set_filter("info").unwrap();
+        crate::log_debug!(TARGET, "filtered out");
+        for _ in 0..RATE_BURST + 3 {
+            crate::log_warn!(TARGET, "repeated");
+        }
+        crate::log_info!(TARGET, "kept {}", 1);
+        let messages: Vec<String> = recent()
+            .into_iter()
+            .filter(|entry| entry.target == TARGET)
+            .map(|entry| entry.message)
+            .collect();
+        let mut expected = vec!["repeated".to_string(); RATE_BURST as usize];
+        expected.push("kept 1".to_string());
+        assert_eq!(messages, expected);
+        assert!(export_logs().contains("WARN  logging::tests: repeated"));
+    }
+}
@@ -1,3 +1,11 @@
+pub mod ecs;
+pub mod input;
+#[macro_use]
+pub mod logging;
+pub mod net;
+pub mod profiler;
 pub mod rendering;
+pub mod replay;
+pub mod scene;
 pub mod tasks;
 pub mod types;
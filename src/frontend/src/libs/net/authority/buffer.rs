@@ -0,0 +1,229 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::f32::consts::{PI, TAU};
+
+use crate::libs::net::protocol::EntityState;
+use crate::libs::types::entity::EntityId;
+
+// older snapshots than this many are dropped even if nothing newer was sampled
+const MAX_SNAPSHOTS: usize = 64;
+// how quickly the clock estimate follows arrival times, per snapshot
+const OFFSET_RATE: f64 = 0.1;
+const JITTER_RATE: f64 = 0.1;
+// the delay covers this many times the measured jitter on top of the minimum
+const JITTER_MARGIN: f64 = 2.0;
+
+#[derive(Debug, Clone)]
+struct Snapshot {
+    tick: u64,
+    entities: BTreeMap<EntityId, EntityState>,
+}
+
+// jitter buffer for the host's snapshots. remote entities are shown a little in the past,
+// between the two snapshots around that time, so late or uneven arrivals don't make them jump
+#[derive(Debug, Clone)]
+pub struct SnapshotBuffer {
+    step_ms: f64,
+    min_delay_ms: f64,
+    snapshots: VecDeque<Snapshot>,
+    // host tick time minus local time, smoothed
+    offset_ms: Option<f64>,
+    jitter_ms: f64,
+}
+
+impl SnapshotBuffer {
+    pub fn new(step_ms: f64, min_delay_ms: f64) -> Self {
+        Self {
+            step_ms,
+            min_delay_ms,
+            snapshots: VecDeque::new(),
+            offset_ms: None,
+            jitter_ms: 0.0,
+        }
+    }
+
+    // out of order snapshots are slotted in, ones older than everything buffered are dropped
+    pub fn push(&mut self, tick: u64, entities: &[EntityState], now_ms: f64) {
+        if self
+            .snapshots
+            .front()
+            .is_some_and(|oldest| tick < oldest.tick)
+        {
+            return;
+        }
+        let index = self
+            .snapshots
+            .partition_point(|snapshot| snapshot.tick < tick);
+        if self
+            .snapshots
+            .get(index)
+            .is_some_and(|snapshot| snapshot.tick == tick)
+        {
+            return;
+        }
+        let entities = entities.iter().map(|entity| (entity.id, *entity)).collect();
+        self.snapshots.insert(index, Snapshot { tick, entities });
+        if self.snapshots.len() > MAX_SNAPSHOTS {
+            self.snapshots.pop_front();
+        }
+
+        let sample = tick as f64 * self.step_ms - now_ms;
+        match self.offset_ms {
+            Some(offset) => {
+                let deviation = sample - offset;
+                self.jitter_ms += (deviation.abs() - self.jitter_ms) * JITTER_RATE;
+                self.offset_ms = Some(offset + deviation * OFFSET_RATE);
+            }
+            None => self.offset_ms = Some(sample),
+        }
+    }
+
+    #[inline]
+    pub fn latest_tick(&self) -> Option<u64> {
+        self.snapshots.back().map(|snapshot| snapshot.tick)
+    }
+
+    #[inline]
+    pub fn jitter_ms(&self) -> f64 {
+        self.jitter_ms
+    }
+
+    // how far behind the host remote entities are shown
+    #[inline]
+    pub fn delay_ms(&self) -> f64 {
+        self.min_delay_ms + self.jitter_ms * JITTER_MARGIN
+    }
+
+    // the host tick to show at now_ms, fractional. None before the first snapshot
+    pub fn render_tick(&self, now_ms: f64) -> Option<f64> {
+        let offset = self.offset_ms?;
+        Some(((now_ms + offset - self.delay_ms()) / self.step_ms).max(0.0))
+    }
+
+    // every entity as of render_tick. entities appear and disappear with the older of the two
+    // snapshots, outside the buffered range the nearest snapshot is held rather than guessed
+    pub fn sample(&mut self, render_tick: f64) -> Vec<EntityState> {
+        // the older snapshot of the pair stays, anything before it is done with
+        while self.snapshots.len() > 2 && self.snapshots[1].tick as f64 <= render_tick {
+            self.snapshots.pop_front();
+        }
+        let (from, to) = match (self.snapshots.front(), self.snapshots.get(1)) {
+            (Some(from), Some(to)) => (from, to),
+            (Some(only), None) => return only.entities.values().copied().collect(),
+            _ => return Vec::new(),
+        };
+        let span = (to.tick - from.tick) as f64;
+        let t = ((render_tick - from.tick as f64) / span).clamp(0.0, 1.0) as f32;
+        from.entities
+            .values()
+            .map(|entity| match to.entities.get(&entity.id) {
+                Some(next) => interpolate(entity, next, t),
+                None => *entity,
+            })
+            .collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+        self.offset_ms = None;
+        self.jitter_ms = 0.0;
+    }
+}
+
+fn interpolate(from: &EntityState, to: &EntityState, t: f32) -> EntityState {
+    // the short way round
+    let turn = (to.rotation - from.rotation + PI).rem_euclid(TAU) - PI;
+    EntityState {
+        position: from.position.lerp(to.position, t),
+        velocity: from.velocity.lerp(to.velocity, t),
+        rotation: from.rotation + turn * t,
+        ..*from
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec2;
+
+    use super::*;
+
+    const STEP_MS: f64 = 10.0;
+
+    fn entity(raw: u32, x: f32, rotation: f32) -> EntityState {
+        EntityState {
+            id: EntityId::new(raw).unwrap(),
+            position: Vec2::new(x, -x),
+            velocity: Vec2::ZERO,
+            rotation,
+            visible: true,
+            owner: None,
+        }
+    }
+
+    fn by_id(states: &[EntityState], raw: u32) -> Option<EntityState> {
+        states.iter().find(|state| state.id.raw() == raw).copied()
+    }
+
+    #[test]
+    fn samples_between_two_snapshots() {
+        let mut buffer = SnapshotBuffer::new(STEP_MS, 0.0);
+        buffer.push(3, &[entity(1, 0.0, PI - 0.1), entity(2, 5.0, 0.0)], 0.0);
+        buffer.push(6, &[entity(1, 3.0, -PI + 0.1), entity(3, 9.0, 0.0)], 30.0);
+        let states = buffer.sample(4.5);
+        let first = by_id(&states, 1).unwrap();
+        assert_eq!(first.position, Vec2::new(1.5, -1.5));
+        // halfway the short way round is a half turn, not zero
+        assert!((first.rotation.abs() - PI).abs() < 1e-5);
+        // gone in the newer snapshot, held until the older one is done with
+        assert_eq!(by_id(&states, 2).unwrap().position, Vec2::new(5.0, -5.0));
+        // new in the newer snapshot, not shown before it
+        assert_eq!(by_id(&states, 3), None);
+    }
+
+    #[test]
+    fn holds_outside_the_buffered_range() {
+        let mut buffer = SnapshotBuffer::new(STEP_MS, 0.0);
+        assert!(buffer.sample(0.0).is_empty());
+        buffer.push(3, &[entity(1, 0.0, 0.0)], 0.0);
+        assert_eq!(buffer.sample(100.0)[0].position.x, 0.0);
+        buffer.push(6, &[entity(1, 3.0, 0.0)], 30.0);
+        assert_eq!(buffer.sample(1.0)[0].position.x, 0.0);
+        assert_eq!(buffer.sample(8.0)[0].position.x, 3.0);
+        // older than everything buffered
+        buffer.push(0, &[entity(1, -3.0, 0.0)], 30.0);
+        assert_eq!(buffer.sample(1.0)[0].position.x, 0.0);
+    }
+
+    #[test]
+    fn slots_in_late_snapshots_and_drops_used_ones() {
+        let mut buffer = SnapshotBuffer::new(STEP_MS, 0.0);
+        buffer.push(3, &[entity(1, 0.0, 0.0)], 0.0);
+        buffer.push(9, &[entity(1, 6.0, 0.0)], 60.0);
+        buffer.push(6, &[entity(1, 4.0, 0.0)], 60.0);
+        assert_eq!(buffer.latest_tick(), Some(9));
+        assert_eq!(buffer.sample(7.5)[0].position.x, 5.0);
+        // the snapshot at 3 is done with
+        assert_eq!(buffer.sample(3.0)[0].position.x, 4.0);
+    }
+
+    #[test]
+    fn delays_by_the_measured_jitter() {
+        let mut buffer = SnapshotBuffer::new(STEP_MS, 50.0);
+        assert_eq!(buffer.render_tick(0.0), None);
+        // arriving exactly on time, the render tick trails now by the minimum delay
+        for tick in 0..10 {
+            buffer.push(tick, &[], tick as f64 * STEP_MS);
+        }
+        assert_eq!(buffer.jitter_ms(), 0.0);
+        assert!((buffer.render_tick(200.0).unwrap() - 15.0).abs() < 1e-9);
+        // uneven arrivals grow the delay
+        for tick in 10..20 {
+            let late = if tick % 2 == 0 { 20.0 } else { 0.0 };
+            buffer.push(tick, &[], tick as f64 * STEP_MS + late);
+        }
+        assert!(buffer.jitter_ms() > 0.0);
+        assert!(buffer.delay_ms() > 50.0);
+        buffer.clear();
+        assert_eq!(buffer.render_tick(0.0), None);
+        assert_eq!(buffer.latest_tick(), None);
+    }
+}
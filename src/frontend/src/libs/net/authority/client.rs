@@ -0,0 +1,638 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use glam::Vec2;
+
+use crate::libs::ecs::World;
+use crate::libs::input::snapshot::InputSnapshot;
+use crate::libs::net::authority::buffer::SnapshotBuffer;
+use crate::libs::net::authority::host::AuthorityHost;
+use crate::libs::net::authority::{AuthorityConfig, Replicated};
+use crate::libs::net::protocol::delta;
+use crate::libs::net::protocol::{EntityState, MAX_INPUTS, Message};
+use crate::libs::net::{Channel, PeerId, Transport};
+use crate::libs::types::entity::EntityId;
+use crate::libs::types::errors::ErrorStr;
+use crate::log_debug;
+
+const LOG_TARGET: &str = "net::authority";
+// share of the remaining correction removed per tick
+const CORRECTION_RATE: f32 = 0.2;
+
+// moves the local player's entity one tick ahead, the same way the host's systems do
+pub trait PredictFnTrait = FnMut(&mut Replicated, &InputSnapshot) + 'static;
+
+// the client side of host authoritative sync. the local player's entity is predicted from
+// local input and corrected when snapshots show the host disagreed, everything else is
+// interpolated between the host's snapshots
+pub struct AuthorityClient<P>
+where
+    P: PredictFnTrait,
+{
+    config: AuthorityConfig,
+    host: PeerId,
+    local_player: u8,
+    predict: P,
+    buffer: SnapshotBuffer,
+    // recent snapshots in full, oldest first, for applying deltas. the newest is acknowledged
+    baselines: VecDeque<(u64, Vec<EntityState>)>,
+    // inputs the host hasn't applied yet, consecutive from the oldest
+    pending: VecDeque<InputSnapshot>,
+    next_input: u64,
+    // newest snapshot reconciled against
+    reconciled: Option<u64>,
+    // the host's id of the local player's entity and where it's predicted to be
+    predicted: Option<(EntityId, Replicated)>,
+    // what's drawn minus what's predicted, eased to zero so small corrections don't jump
+    correction: Vec2,
+    // host entity -> local entity
+    entities: BTreeMap<EntityId, EntityId>,
+}
+
+impl<P> AuthorityClient<P>
+where
+    P: PredictFnTrait,
+{
+    pub fn new(config: AuthorityConfig, host: PeerId, local_player: u8, predict: P) -> Self {
+        Self {
+            config,
+            host,
+            local_player,
+            predict,
+            buffer: SnapshotBuffer::new(config.step_ms(), config.interpolation_delay_ms),
+            baselines: VecDeque::new(),
+            pending: VecDeque::new(),
+            next_input: 0,
+            reconciled: None,
+            predicted: None,
+            correction: Vec2::ZERO,
+            entities: BTreeMap::new(),
+        }
+    }
+
+    #[inline]
+    pub fn host(&self) -> PeerId {
+        self.host
+    }
+
+    // follows a new host after the old one left. its ticks carry on from the old host's, but
+    // its entity ids and baselines are its own, so everything buffered is dropped and remote
+    // entities hold still until its first snapshot
+    pub fn set_host(&mut self, host: PeerId) {
+        self.host = host;
+        self.buffer.clear();
+        self.baselines.clear();
+        self.reconciled = None;
+    }
+
+    // takes over as host after the old one left, from the newest snapshot it sent. the local
+    // copies become the real entities, the local player's where it's predicted to be, and the
+    // world carries on from that snapshot's tick
+    pub fn promote(self, world: &mut World) -> Result<AuthorityHost, ErrorStr> {
+        let (tick, entities) = if let Some(newest) = self.baselines.back() {
+            newest
+        } else {
+            return Err(ErrorStr::new(
+                "No snapshot from the old host to carry on from",
+            ));
+        };
+        let mut kept = BTreeSet::new();
+        for state in entities.iter() {
+            let replicated = match self.predicted {
+                Some((id, predicted)) if id == state.id => predicted,
+                _ => Replicated::from(state),
+            };
+            let entity = match self.entities.get(&state.id) {
+                Some(entity) if world.is_alive(*entity) => *entity,
+                _ => world.spawn()?,
+            };
+            world.insert(entity, replicated)?;
+            kept.insert(entity);
+        }
+        for entity in self.entities.values() {
+            if !kept.contains(entity) {
+                world.despawn(*entity);
+            }
+        }
+        world.set_tick(*tick);
+        Ok(AuthorityHost::new(self.config, self.local_player))
+    }
+
+    #[inline]
+    pub fn buffer(&self) -> &SnapshotBuffer {
+        &self.buffer
+    }
+
+    // the local copy of a host entity, once interpolate has spawned it
+    #[inline]
+    pub fn local_entity(&self, host_entity: EntityId) -> Option<EntityId> {
+        self.entities.get(&host_entity).copied()
+    }
+
+    // takes Snapshot and Delta messages from the host. returns false for anything else
+    pub fn handle_message(
+        &mut self,
+        peer: PeerId,
+        message: &Message,
+        now_ms: f64,
+    ) -> Result<bool, ErrorStr> {
+        let (tick, last_input, entities) = match message {
+            Message::Snapshot {
+                tick,
+                last_input,
+                entities,
+            } => (*tick, *last_input, entities.clone()),
+            Message::Delta {
+                tick,
+                baseline,
+                last_input,
+                changed,
+                removed,
+            } => {
+                let before = self
+                    .baselines
+                    .iter()
+                    .find(|(buffered, _)| buffered == baseline);
+                let before = if let Some((_, before)) = before {
+                    before
+                } else {
+                    // the host falls back to a full snapshot once our acks move on
+                    log_debug!(LOG_TARGET, "Delta against {} which is gone", baseline);
+                    return Ok(true);
+                };
+                (*tick, *last_input, delta::apply(before, changed, removed)?)
+            }
+            _ => return Ok(false),
+        };
+        if peer != self.host {
+            return Err(ErrorStr::new(format!(
+                "Snapshot from {}, not the host",
+                peer
+            )));
+        }
+        self.buffer.push(tick, &entities, now_ms);
+        if self.reconciled.is_none_or(|reconciled| tick > reconciled) {
+            self.reconciled = Some(tick);
+            self.reconcile(last_input, &entities);
+        }
+        self.keep_baseline(tick, entities);
+        Ok(true)
+    }
+
+    fn keep_baseline(&mut self, tick: u64, entities: Vec<EntityState>) {
+        let index = self
+            .baselines
+            .partition_point(|(buffered, _)| *buffered < tick);
+        if self
+            .baselines
+            .get(index)
+            .is_some_and(|(buffered, _)| *buffered == tick)
+        {
+            return;
+        }
+        self.baselines.insert(index, (tick, entities));
+        let newest = self.baselines.back().map_or(tick, |(newest, _)| *newest);
+        let max_age = self.config.max_baseline_age;
+        while self
+            .baselines
+            .front()
+            .is_some_and(|(oldest, _)| oldest + max_age < newest)
+        {
+            self.baselines.pop_front();
+        }
+    }
+
+    // restarts prediction from the host's state and replays whatever it hasn't applied yet
+    fn reconcile(&mut self, last_input: Option<u64>, entities: &[EntityState]) {
+        if let Some(last) = last_input {
+            while self.pending.front().is_some_and(|input| input.tick <= last) {
+                self.pending.pop_front();
+            }
+        }
+        let owned = entities
+            .iter()
+            .find(|entity| entity.owner == Some(self.local_player));
+        let state = if let Some(state) = owned {
+            state
+        } else {
+            self.predicted = None;
+            self.correction = Vec2::ZERO;
+            return;
+        };
+        let mut replicated = Replicated::from(state);
+        for input in self.pending.iter() {
+            (self.predict)(&mut replicated, input);
+        }
+        match self.predicted {
+            Some((id, before)) if id == state.id => {
+                let shown = before.position + self.correction;
+                self.correction = shown - replicated.position;
+                if self.correction.length() > self.config.snap_distance {
+                    log_debug!(LOG_TARGET, "Snapped prediction by {}", self.correction);
+                    self.correction = Vec2::ZERO;
+                }
+            }
+            _ => self.correction = Vec2::ZERO,
+        }
+        self.predicted = Some((state.id, replicated));
+    }
+
+    // sends this tick's input to the host and predicts its effect. call once per fixed tick
+    pub fn advance<T>(
+        &mut self,
+        transport: &mut T,
+        local_input: InputSnapshot,
+    ) -> Result<(), ErrorStr>
+    where
+        T: Transport + ?Sized,
+    {
+        let input = InputSnapshot {
+            tick: self.next_input,
+            ..local_input
+        };
+        self.next_input += 1;
+        self.pending.push_back(input);
+        // the host skips whatever it never got, so there's no point keeping more than one send
+        while self.pending.len() > MAX_INPUTS {
+            self.pending.pop_front();
+        }
+        if let Some((_, predicted)) = self.predicted.as_mut() {
+            (self.predict)(predicted, &input);
+        }
+        self.correction *= 1.0 - CORRECTION_RATE;
+
+        if !transport.is_connected(self.host) {
+            return Ok(());
+        }
+        let start_tick = self.pending.front().map_or(input.tick, |first| first.tick);
+        let message = Message::Inputs {
+            player: self.local_player,
+            start_tick,
+            ack_tick: self.baselines.back().map(|(tick, _)| *tick),
+            inputs: self.pending.iter().copied().collect(),
+        };
+        transport.send(self.host, Channel::Unreliable, &message.encode()?)
+    }
+
+    // writes Replicated for every host entity as of now_ms, spawning and despawning local
+    // copies to match. call once per rendered frame
+    pub fn interpolate(&mut self, world: &mut World, now_ms: f64) -> Result<(), ErrorStr> {
+        let render_tick = if let Some(render_tick) = self.buffer.render_tick(now_ms) {
+            render_tick
+        } else {
+            return Ok(());
+        };
+        let mut states = self.buffer.sample(render_tick);
+        // the predicted entity is shown where it's predicted, not where it was
+        if let Some((id, predicted)) = self.predicted {
+            let shown = Replicated {
+                position: predicted.position + self.correction,
+                ..predicted
+            };
+            states.retain(|state| state.id != id);
+            states.push(shown.state(id));
+        }
+
+        let mut seen = BTreeSet::new();
+        for state in states.iter() {
+            seen.insert(state.id);
+            let entity = match self.entities.get(&state.id) {
+                Some(entity) if world.is_alive(*entity) => *entity,
+                _ => {
+                    let entity = world.spawn()?;
+                    self.entities.insert(state.id, entity);
+                    entity
+                }
+            };
+            world.insert(entity, Replicated::from(state))?;
+        }
+        let gone: Vec<EntityId> = self
+            .entities
+            .keys()
+            .filter(|id| !seen.contains(*id))
+            .copied()
+            .collect();
+        for id in gone {
+            if let Some(entity) = self.entities.remove(&id) {
+                world.despawn(entity);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::libs::ecs::schedule::Schedule;
+    use crate::libs::input::snapshot::PlayerInputs;
+    use crate::libs::net::TransportEvent;
+    use crate::libs::net::loopback::{LoopbackNetwork, LoopbackTransport};
+
+    const LATENCY: u64 = 2;
+    const CLIENT: u8 = 1;
+
+    type Predict = fn(&mut Replicated, &InputSnapshot);
+
+    fn predict(replicated: &mut Replicated, input: &InputSnapshot) {
+        if input.actions & 1 != 0 {
+            replicated.position.x += 1.0;
+        }
+    }
+
+    // owned entities move with their player's input, everything else drifts right
+    fn movement(world: &mut World) -> Result<(), ErrorStr> {
+        let frames = world
+            .resource::<PlayerInputs>()
+            .map(|inputs| inputs.frames.clone())
+            .unwrap_or_default();
+        world.each::<&mut Replicated, _>(|_, replicated| match replicated.owner {
+            Some(owner) => {
+                if let Some(frame) = frames.get(owner as usize) {
+                    predict(replicated, &frame.current);
+                }
+            }
+            None => replicated.position.x += 1.0,
+        })
+    }
+
+    fn input(pressed: bool) -> InputSnapshot {
+        InputSnapshot {
+            actions: pressed as u64,
+            ..InputSnapshot::default()
+        }
+    }
+
+    fn now_ms(tick: u64) -> f64 {
+        tick as f64 * AuthorityConfig::default().step_ms()
+    }
+
+    struct Host {
+        transport: LoopbackTransport,
+        host: AuthorityHost,
+        world: World,
+        schedule: Schedule<'static>,
+    }
+
+    impl Host {
+        fn update(&mut self) {
+            while let Some(event) = self.transport.recv() {
+                if let TransportEvent::Message { peer, data, .. } = event {
+                    let message = Message::decode(&data).unwrap();
+                    assert!(self.host.handle_message(peer, &message).unwrap());
+                }
+            }
+            self.host
+                .advance(
+                    &mut self.world,
+                    &mut self.schedule,
+                    &mut self.transport,
+                    input(false),
+                )
+                .unwrap();
+        }
+    }
+
+    struct Client {
+        transport: LoopbackTransport,
+        client: AuthorityClient<Predict>,
+        world: World,
+    }
+
+    impl Client {
+        fn new(transport: LoopbackTransport, host: PeerId, player: u8) -> Self {
+            let config = AuthorityConfig::default();
+            Self {
+                transport,
+                client: AuthorityClient::new(config, host, player, predict as Predict),
+                world: World::new(),
+            }
+        }
+
+        fn receive(&mut self, tick: u64) {
+            while let Some(event) = self.transport.recv() {
+                if let TransportEvent::Message { peer, data, .. } = event {
+                    let message = Message::decode(&data).unwrap();
+                    let handled = self.client.handle_message(peer, &message, now_ms(tick));
+                    assert!(handled.unwrap());
+                }
+            }
+        }
+
+        fn send(&mut self, tick: u64, pressed: bool) {
+            self.client
+                .advance(&mut self.transport, input(pressed))
+                .unwrap();
+            self.client
+                .interpolate(&mut self.world, now_ms(tick))
+                .unwrap();
+        }
+
+        fn replicated(&self, host_entity: EntityId) -> Replicated {
+            let entity = self.client.local_entity(host_entity).unwrap();
+            *self.world.get::<Replicated>(entity).unwrap()
+        }
+    }
+
+    // a host with the client's entity and a drifting one, and a client per slot from 1
+    fn start(clients: u8) -> (LoopbackNetwork, Host, Vec<Client>, [EntityId; 2]) {
+        let network = LoopbackNetwork::new();
+        let transport = network.add_peer();
+        let host_id = transport.local_id();
+        let mut host = Host {
+            transport,
+            host: AuthorityHost::new(AuthorityConfig::default(), 0),
+            world: World::new(),
+            schedule: Schedule::new().with_system(movement, "Move"),
+        };
+        let mut entities = [host.world.spawn().unwrap(), host.world.spawn().unwrap()];
+        entities.sort();
+        for (entity, owner) in entities.iter().zip([Some(CLIENT), None]) {
+            let replicated = Replicated {
+                visible: true,
+                owner,
+                ..Replicated::default()
+            };
+            host.world.insert(*entity, replicated).unwrap();
+        }
+        let mut peers = vec![host_id];
+        let clients: Vec<Client> = (0..clients)
+            .map(|index| {
+                let transport = network.add_peer();
+                let slot = CLIENT + index;
+                peers.push(transport.local_id());
+                host.host.add_peer(transport.local_id(), slot).unwrap();
+                Client::new(transport, host_id, slot)
+            })
+            .collect();
+        network.connect_all(&peers).unwrap();
+        network.set_latency(LATENCY);
+        (network, host, clients, entities)
+    }
+
+    fn host_position(host: &Host, entity: EntityId) -> Vec2 {
+        host.world.get::<Replicated>(entity).unwrap().position
+    }
+
+    #[test]
+    fn corrects_a_misprediction() {
+        let (network, mut host, mut clients, [owned, _]) = start(1);
+        let client = &mut clients[0];
+        let mut tick = 0;
+        let mut run = |host: &mut Host, client: &mut Client, tick: &mut u64, pressed: bool| {
+            client.receive(*tick);
+            client.send(*tick, pressed);
+            host.update();
+            network.step();
+            *tick += 1;
+        };
+        for index in 0..60 {
+            run(&mut host, client, &mut tick, index < 30);
+        }
+        let (_, predicted) = client.client.predicted.unwrap();
+        assert_eq!(predicted.position, host_position(&host, owned));
+        assert!(host_position(&host, owned).x > 20.0);
+        assert!(client.client.correction.length() < 0.01);
+
+        // something only the host knows about pushes the entity a little
+        host.world.get_mut::<Replicated>(owned).unwrap().position.x += 2.0;
+        let before = client.replicated(owned).position;
+        let mut eased = false;
+        for _ in 0..10 {
+            client.receive(tick);
+            let (_, predicted) = client.client.predicted.unwrap();
+            if predicted.position == host_position(&host, owned) {
+                // the prediction moved, what's shown hasn't yet
+                let shown = predicted.position + client.client.correction;
+                assert!((shown - before).length() < 0.01);
+                eased = true;
+                break;
+            }
+            run(&mut host, client, &mut tick, false);
+        }
+        assert!(eased);
+        for _ in 0..30 {
+            run(&mut host, client, &mut tick, false);
+        }
+        let shown = client.replicated(owned).position;
+        assert!((shown - host_position(&host, owned)).length() < 0.01);
+
+        // too far to ease, the prediction jumps
+        host.world.get_mut::<Replicated>(owned).unwrap().position.x += 50.0;
+        for _ in 0..10 {
+            run(&mut host, client, &mut tick, false);
+        }
+        assert_eq!(client.client.correction, Vec2::ZERO);
+        assert_eq!(
+            client.replicated(owned).position,
+            host_position(&host, owned)
+        );
+    }
+
+    #[test]
+    fn interpolates_remote_entities_between_snapshots() {
+        let (network, mut host, mut clients, [_, drifting]) = start(1);
+        let client = &mut clients[0];
+        let interval = AuthorityConfig::default().snapshot_interval as f32;
+        let mut between = 0;
+        for tick in 0..60 {
+            client.receive(tick);
+            let render_tick = client.client.buffer().render_tick(now_ms(tick));
+            client.send(tick, false);
+            host.update();
+            network.step();
+            let render_tick = match render_tick {
+                Some(render_tick) if tick > 20 => render_tick as f32,
+                _ => continue,
+            };
+            // drifting moves a unit a tick, so it's shown at the render tick
+            let x = client.replicated(drifting).position.x;
+            assert!((x - render_tick).abs() < 1e-3, "tick {}", tick);
+            if x % interval != 0.0 {
+                between += 1;
+            }
+        }
+        assert!(between > 10);
+    }
+
+    #[test]
+    fn promotes_a_client_to_host() {
+        let (network, mut host, clients, [owned, drifting]) = start(2);
+        let [mut a, mut b] = <[Client; 2]>::try_from(clients).ok().unwrap();
+        let old_host = host.transport.local_id();
+        for tick in 0..30 {
+            for client in [&mut a, &mut b] {
+                client.receive(tick);
+                client.send(tick, true);
+            }
+            host.update();
+            network.step();
+        }
+        drop(host);
+        a.receive(30);
+        b.receive(30);
+
+        let (_, predicted) = a.client.predicted.unwrap();
+        let (newest, states) = a.client.baselines.back().cloned().unwrap();
+        let drifted = states.iter().find(|state| state.id == drifting).unwrap();
+        let (owned, drifting) = (
+            a.client.local_entity(owned).unwrap(),
+            a.client.local_entity(drifting).unwrap(),
+        );
+        let Client {
+            transport,
+            client,
+            mut world,
+        } = a;
+        let a_id = transport.local_id();
+        let promoted = client.promote(&mut world).unwrap();
+        assert_eq!(world.tick(), newest);
+        // the local player's entity where it was predicted, the rest as last sent
+        assert_eq!(
+            world.get::<Replicated>(owned).unwrap().position,
+            predicted.position
+        );
+        assert_eq!(
+            world.get::<Replicated>(drifting).unwrap().position,
+            drifted.position
+        );
+
+        let mut host = Host {
+            transport,
+            host: promoted,
+            world,
+            schedule: Schedule::new().with_system(movement, "Move"),
+        };
+        host.host
+            .add_peer(b.transport.local_id(), CLIENT + 1)
+            .unwrap();
+        b.client.set_host(a_id);
+        let stale = Message::Snapshot {
+            tick: newest + 1,
+            last_input: None,
+            entities: Vec::new(),
+        };
+        assert!(
+            b.client
+                .handle_message(old_host, &stale, now_ms(31))
+                .is_err()
+        );
+        for tick in 31..60 {
+            b.receive(tick);
+            b.send(tick, false);
+            host.update();
+            network.step();
+        }
+        let b_id = b.transport.local_id();
+        assert!(host.host.stats(b_id).full >= 1);
+        assert!(host.host.stats(b_id).deltas >= 1);
+        // b follows the new host's entities
+        for entity in [owned, drifting] {
+            let shown = b.replicated(entity);
+            assert_eq!(
+                shown.owner,
+                host.world.get::<Replicated>(entity).unwrap().owner
+            );
+        }
+        assert!(b.replicated(drifting).position.x > drifted.position.x);
+    }
+}
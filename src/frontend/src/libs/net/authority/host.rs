@@ -0,0 +1,339 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::libs::ecs::World;
+use crate::libs::ecs::schedule::Schedule;
+use crate::libs::input::snapshot::{InputFrame, InputSnapshot, PlayerInputs};
+use crate::libs::net::authority::{AuthorityConfig, replicated_states};
+use crate::libs::net::protocol::delta;
+use crate::libs::net::protocol::{EntityState, Message};
+use crate::libs::net::{Channel, PeerId, Transport};
+use crate::libs::profiler::network::{forget_snapshots, record_snapshots};
+use crate::libs::types::errors::ErrorStr;
+use crate::{log_debug, log_info};
+
+const LOG_TARGET: &str = "net::authority";
+// inputs a client may be ahead of the host before the oldest are dropped, each one queued is
+// a tick of extra latency for that player
+const MAX_BACKLOG: usize = 4;
+
+// a client's inputs, applied one per host tick in the client's order
+#[derive(Debug, Clone, Default)]
+struct RemoteInputs {
+    pending: BTreeMap<u64, InputSnapshot>,
+    // the client's tick of the input in current
+    last_applied: Option<u64>,
+    frame: InputFrame,
+}
+
+impl RemoteInputs {
+    fn receive(&mut self, inputs: &[InputSnapshot]) {
+        for input in inputs.iter() {
+            if self.last_applied.is_none_or(|last| input.tick > last) {
+                self.pending.insert(input.tick, *input);
+            }
+        }
+    }
+
+    // the next input in order, skipping lost ones. without one the last is held
+    fn next(&mut self) -> InputFrame {
+        while self.pending.len() > MAX_BACKLOG {
+            self.pending.pop_first();
+        }
+        self.frame.previous = self.frame.current;
+        if let Some((tick, input)) = self.pending.pop_first() {
+            self.last_applied = Some(tick);
+            self.frame.current = input;
+        }
+        self.frame
+    }
+}
+
+// what was sent to one client, for the stats overlay and tuning. see profiler::network
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SnapshotStats {
+    pub full: u64,
+    pub deltas: u64,
+    pub bytes: u64,
+    pub last_bytes: usize,
+}
+
+// the host side of host authoritative sync: runs the real simulation with everyone's latest
+// input and sends each client a snapshot of every Replicated entity at a fixed rate
+#[derive(Debug, Clone)]
+pub struct AuthorityHost {
+    config: AuthorityConfig,
+    local_player: u8,
+    // remote peers and their player slots
+    peers: BTreeMap<PeerId, u8>,
+    inputs: BTreeMap<u8, RemoteInputs>,
+    local: InputFrame,
+    // the newest snapshot tick each client has
+    acks: BTreeMap<PeerId, u64>,
+    // snapshots still young enough to be a baseline, oldest first
+    sent: VecDeque<(u64, Vec<EntityState>)>,
+    stats: BTreeMap<PeerId, SnapshotStats>,
+}
+
+impl AuthorityHost {
+    pub fn new(config: AuthorityConfig, local_player: u8) -> Self {
+        Self {
+            config,
+            local_player,
+            peers: BTreeMap::new(),
+            inputs: BTreeMap::new(),
+            local: InputFrame::default(),
+            acks: BTreeMap::new(),
+            sent: VecDeque::new(),
+            stats: BTreeMap::new(),
+        }
+    }
+
+    #[inline]
+    pub fn config(&self) -> AuthorityConfig {
+        self.config
+    }
+
+    #[inline]
+    pub fn stats(&self, peer: PeerId) -> SnapshotStats {
+        self.stats.get(&peer).copied().unwrap_or_default()
+    }
+
+    // unlike rollback, players can join a running match
+    pub fn add_peer(&mut self, peer: PeerId, slot: u8) -> Result<(), ErrorStr> {
+        let taken = slot == self.local_player || self.peers.values().any(|other| *other == slot);
+        if taken {
+            return Err(ErrorStr::new(format!("Player slot {} is taken", slot)));
+        }
+        log_info!(LOG_TARGET, "{} joined as player {}", peer, slot);
+        self.peers.insert(peer, slot);
+        self.inputs.insert(slot, RemoteInputs::default());
+        Ok(())
+    }
+
+    pub fn remove_peer(&mut self, peer: PeerId) -> Option<u8> {
+        let slot = self.peers.remove(&peer)?;
+        self.inputs.remove(&slot);
+        self.acks.remove(&peer);
+        self.stats.remove(&peer);
+        forget_snapshots(peer);
+        Some(slot)
+    }
+
+    // takes Inputs messages from clients. returns false for anything else
+    pub fn handle_message(&mut self, peer: PeerId, message: &Message) -> Result<bool, ErrorStr> {
+        let (player, ack_tick, inputs) = match message {
+            Message::Inputs {
+                player,
+                ack_tick,
+                inputs,
+                ..
+            } => (*player, *ack_tick, inputs),
+            _ => return Ok(false),
+        };
+        let slot = match self.peers.get(&peer) {
+            Some(slot) => *slot,
+            None => return Err(ErrorStr::new(format!("{} isn't in this match", peer))),
+        };
+        if player != slot {
+            let error = format!("{} sent inputs for player {}", peer, player);
+            return Err(ErrorStr::new(error));
+        }
+        if let Some(queue) = self.inputs.get_mut(&slot) {
+            queue.receive(inputs);
+        }
+        if let Some(ack) = ack_tick {
+            let acked = self.acks.entry(peer).or_insert(ack);
+            *acked = ack.max(*acked);
+        }
+        Ok(true)
+    }
+
+    // one tick with the newest input of every player, then snapshots if one is due
+    pub fn advance<T>(
+        &mut self,
+        world: &mut World,
+        schedule: &mut Schedule,
+        transport: &mut T,
+        local_input: InputSnapshot,
+    ) -> Result<(), ErrorStr>
+    where
+        T: Transport + ?Sized,
+    {
+        let tick = world.tick();
+        self.local = InputFrame {
+            current: InputSnapshot {
+                tick,
+                ..local_input
+            },
+            previous: self.local.current,
+        };
+        let players = self
+            .peers
+            .values()
+            .copied()
+            .chain(std::iter::once(self.local_player))
+            .max()
+            .map_or(0, |slot| slot as usize + 1);
+        let mut frames = vec![InputFrame::default(); players];
+        frames[self.local_player as usize] = self.local;
+        for (slot, queue) in self.inputs.iter_mut() {
+            frames[*slot as usize] = queue.next();
+        }
+        world.insert_resource(self.local);
+        world.insert_resource(PlayerInputs { frames });
+        schedule.run(world)?;
+
+        let interval = self.config.snapshot_interval.max(1) as u64;
+        if world.tick().is_multiple_of(interval) {
+            self.send_snapshots(world, transport)?;
+        }
+        Ok(())
+    }
+
+    // a delta against what each client last acknowledged, or everything when there's no
+    // usable baseline
+    fn send_snapshots<T>(&mut self, world: &World, transport: &mut T) -> Result<(), ErrorStr>
+    where
+        T: Transport + ?Sized,
+    {
+        let tick = world.tick();
+        let entities = replicated_states(world)?;
+        let max_age = self.config.max_baseline_age;
+        while self
+            .sent
+            .front()
+            .is_some_and(|(sent, _)| sent + max_age < tick)
+        {
+            self.sent.pop_front();
+        }
+        for (peer, slot) in self.peers.iter() {
+            if !transport.is_connected(*peer) {
+                continue;
+            }
+            let last_input = self.inputs.get(slot).and_then(|queue| queue.last_applied);
+            let baseline = self
+                .acks
+                .get(peer)
+                .and_then(|ack| self.sent.iter().find(|(sent, _)| sent == ack));
+            let message = match baseline {
+                Some((baseline, before)) => {
+                    let (changed, removed) = delta::diff(before, &entities);
+                    Message::Delta {
+                        tick,
+                        baseline: *baseline,
+                        last_input,
+                        changed,
+                        removed,
+                    }
+                }
+                None => Message::Snapshot {
+                    tick,
+                    last_input,
+                    entities: entities.clone(),
+                },
+            };
+            let data = message.encode()?;
+            let stats = self.stats.entry(*peer).or_default();
+            if baseline.is_some() {
+                stats.deltas += 1;
+            } else {
+                stats.full += 1;
+            }
+            stats.bytes += data.len() as u64;
+            stats.last_bytes = data.len();
+            record_snapshots(*peer, *stats);
+            if let Err(error) = transport.send(*peer, Channel::Unreliable, &data) {
+                log_debug!(LOG_TARGET, "Snapshot to {} failed: {}", peer, error);
+            }
+        }
+        self.sent.push_back((tick, entities));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::libs::net::TransportEvent;
+    use crate::libs::net::authority::Replicated;
+    use crate::libs::net::loopback::LoopbackNetwork;
+
+    // (tick, baseline) of every snapshot that arrived, None for a full one
+    fn received<T>(transport: &mut T) -> Vec<(u64, Option<u64>)>
+    where
+        T: Transport,
+    {
+        let mut snapshots = Vec::new();
+        while let Some(event) = transport.recv() {
+            let data = match event {
+                TransportEvent::Message { data, .. } => data,
+                _ => continue,
+            };
+            match Message::decode(&data).unwrap() {
+                Message::Snapshot { tick, .. } => snapshots.push((tick, None)),
+                Message::Delta { tick, baseline, .. } => snapshots.push((tick, Some(baseline))),
+                message => panic!("Unexpected {:?}", message.kind()),
+            }
+        }
+        snapshots
+    }
+
+    #[test]
+    fn falls_back_to_a_full_snapshot_once_the_baseline_ages_out() {
+        let network = LoopbackNetwork::new();
+        let (mut transport, mut client) = (network.add_peer(), network.add_peer());
+        let client_id = client.local_id();
+        network.connect(transport.local_id(), client_id).unwrap();
+        let config = AuthorityConfig {
+            snapshot_interval: 3,
+            max_baseline_age: 9,
+            ..AuthorityConfig::default()
+        };
+        let mut host = AuthorityHost::new(config, 0);
+        host.add_peer(client_id, 1).unwrap();
+        let mut world = World::new();
+        let entity = world.spawn().unwrap();
+        world.insert(entity, Replicated::default()).unwrap();
+        let mut schedule = Schedule::new().with_system(
+            move |world: &mut World| {
+                if let Some(mut replicated) = world.get_mut::<Replicated>(entity) {
+                    replicated.position.x += 1.0;
+                }
+                Ok(())
+            },
+            "Move",
+        );
+        let mut tick = |host: &mut AuthorityHost, transport: &mut _| {
+            host.advance(
+                &mut world,
+                &mut schedule,
+                transport,
+                InputSnapshot::default(),
+            )
+            .unwrap();
+            network.step();
+        };
+
+        for _ in 0..3 {
+            tick(&mut host, &mut transport);
+        }
+        assert_eq!(received(&mut client), vec![(3, None)]);
+        // the client acknowledges tick 3 and then goes quiet
+        let ack = Message::Inputs {
+            player: 1,
+            start_tick: 0,
+            ack_tick: Some(3),
+            inputs: Vec::new(),
+        };
+        assert!(host.handle_message(client_id, &ack).unwrap());
+        for _ in 0..12 {
+            tick(&mut host, &mut transport);
+        }
+        let expected = vec![(6, Some(3)), (9, Some(3)), (12, Some(3)), (15, None)];
+        assert_eq!(received(&mut client), expected);
+        let stats = host.stats(client_id);
+        assert_eq!((stats.full, stats.deltas), (2, 3));
+        assert!(stats.last_bytes > 0);
+        assert!(stats.bytes >= stats.last_bytes as u64 * 5);
+    }
+}
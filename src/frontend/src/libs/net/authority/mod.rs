@@ -0,0 +1,101 @@
+pub mod buffer;
+pub mod client;
+pub mod host;
+
+use glam::Vec2;
+
+use crate::libs::ecs::World;
+use crate::libs::ecs::game_loop::DEFAULT_TICK_HZ;
+use crate::libs::net::protocol::EntityState;
+use crate::libs::types::entity::EntityId;
+use crate::libs::types::errors::ErrorStr;
+
+pub const DEFAULT_SNAPSHOT_INTERVAL: u32 = 3;
+pub const DEFAULT_INTERPOLATION_DELAY_MS: f64 = 100.0;
+pub const DEFAULT_SNAP_DISTANCE: f32 = 8.0;
+pub const DEFAULT_MAX_BASELINE_AGE: u64 = 60;
+
+// the part of an entity the host shares with everyone. systems on the host write it, clients
+// get it from snapshots
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Replicated {
+    pub position: Vec2,
+    pub velocity: Vec2,
+    // radians
+    pub rotation: f32,
+    pub visible: bool,
+    // the player slot controlling it, that client predicts it instead of interpolating
+    pub owner: Option<u8>,
+}
+
+impl Replicated {
+    pub fn state(&self, id: EntityId) -> EntityState {
+        EntityState {
+            id,
+            position: self.position,
+            velocity: self.velocity,
+            rotation: self.rotation,
+            visible: self.visible,
+            owner: self.owner,
+        }
+    }
+}
+
+impl From<&EntityState> for Replicated {
+    fn from(state: &EntityState) -> Self {
+        Self {
+            position: state.position,
+            velocity: state.velocity,
+            rotation: state.rotation,
+            visible: state.visible,
+            owner: state.owner,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AuthorityConfig {
+    pub tick_hz: f64,
+    // the host sends a snapshot every this many ticks
+    pub snapshot_interval: u32,
+    // remote entities are shown at least this far in the past, more when arrivals are uneven
+    pub interpolation_delay_ms: f64,
+    // a predicted entity further than this from the host's state jumps there, closer ones are
+    // eased over a few ticks
+    pub snap_distance: f32,
+    // snapshots are sent as deltas against the newest one the client acknowledged, unless that
+    // is more than this many ticks old
+    pub max_baseline_age: u64,
+}
+
+impl Default for AuthorityConfig {
+    fn default() -> Self {
+        Self {
+            tick_hz: DEFAULT_TICK_HZ,
+            snapshot_interval: DEFAULT_SNAPSHOT_INTERVAL,
+            interpolation_delay_ms: DEFAULT_INTERPOLATION_DELAY_MS,
+            snap_distance: DEFAULT_SNAP_DISTANCE,
+            max_baseline_age: DEFAULT_MAX_BASELINE_AGE,
+        }
+    }
+}
+
+impl AuthorityConfig {
+    #[inline]
+    pub fn step_ms(&self) -> f64 {
+        1000.0 / self.tick_hz
+    }
+}
+
+// every Replicated entity, at wire precision
+pub fn replicated_states(world: &World) -> Result<Vec<EntityState>, ErrorStr> {
+    let storage = if let Some(storage) = world.storage::<Replicated>()? {
+        storage
+    } else {
+        return Ok(Vec::new());
+    };
+    Ok(storage
+        .iter()
+        .map(|(entity, replicated)| replicated.state(entity).quantized())
+        .collect())
+}
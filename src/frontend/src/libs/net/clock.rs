@@ -0,0 +1,447 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::libs::ecs::game_loop::DEFAULT_TICK_HZ;
+use crate::libs::net::protocol::Message;
+use crate::libs::net::{Channel, PeerId, Transport};
+use crate::libs::types::errors::ErrorStr;
+use crate::log_debug;
+
+const LOG_TARGET: &str = "net::clock";
+
+pub const DEFAULT_PING_INTERVAL_MS: f64 = 500.0;
+pub const DEFAULT_SAMPLE_WINDOW: usize = 16;
+pub const DEFAULT_MAX_SPEED_ADJUST: f64 = 0.05;
+// unanswered pings are given up on after this many intervals
+const PING_TIMEOUT_INTERVALS: f64 = 8.0;
+// how quickly the published timeline follows the window's estimate, per sample
+const EPOCH_RATE: f64 = 0.2;
+// speed change per tick of error, before the cap
+const SPEED_GAIN: f64 = 0.01;
+// closer than this counts as in step, so the speed doesn't hunt around rounding
+const DEADBAND_TICKS: f64 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClockConfig {
+    pub tick_hz: f64,
+    pub ping_interval_ms: f64,
+    // recent samples per peer the estimates are taken from
+    pub sample_window: usize,
+    // time_scale stays within 1 plus or minus this
+    pub max_speed_adjust: f64,
+}
+
+impl Default for ClockConfig {
+    fn default() -> Self {
+        Self {
+            tick_hz: DEFAULT_TICK_HZ,
+            ping_interval_ms: DEFAULT_PING_INTERVAL_MS,
+            sample_window: DEFAULT_SAMPLE_WINDOW,
+            max_speed_adjust: DEFAULT_MAX_SPEED_ADJUST,
+        }
+    }
+}
+
+// one ping's worth of measurement, all in local ms
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Sample {
+    rtt_ms: f64,
+    // remote clock minus local clock
+    offset_ms: f64,
+    // local time the remote's tick 0 was at
+    epoch_ms: f64,
+}
+
+// what is known about one peer's clock
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PeerClock {
+    // median of the window
+    pub rtt_ms: f64,
+    // mean distance of the window's rtts from the median
+    pub jitter_ms: f64,
+    pub offset_ms: f64,
+    pub epoch_ms: f64,
+    pub samples: usize,
+}
+
+impl PeerClock {
+    // the peer's tick at local time now_ms, fractional
+    #[inline]
+    pub fn tick_at(&self, now_ms: f64, step_ms: f64) -> f64 {
+        (now_ms - self.epoch_ms) / step_ms
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct PeerState {
+    // sequence -> local send time
+    pending: BTreeMap<u32, f64>,
+    samples: VecDeque<Sample>,
+    clock: Option<PeerClock>,
+}
+
+// pings every connected peer and estimates rtt, jitter and clock offset from the answers. the
+// slower half of each peer's recent samples is ignored for the offset, queueing delays are
+// rarely the same both ways
+#[derive(Debug, Clone)]
+pub struct ClockSync {
+    config: ClockConfig,
+    peers: BTreeMap<PeerId, PeerState>,
+    next_sequence: u32,
+    next_ping_ms: f64,
+}
+
+impl ClockSync {
+    pub fn new(config: ClockConfig) -> Self {
+        Self {
+            config,
+            peers: BTreeMap::new(),
+            next_sequence: 0,
+            next_ping_ms: 0.0,
+        }
+    }
+
+    #[inline]
+    pub fn config(&self) -> ClockConfig {
+        self.config
+    }
+
+    #[inline]
+    pub fn step_ms(&self) -> f64 {
+        1000.0 / self.config.tick_hz
+    }
+
+    // None until the peer answered a ping
+    #[inline]
+    pub fn clock(&self, peer: PeerId) -> Option<PeerClock> {
+        self.peers.get(&peer).and_then(|state| state.clock)
+    }
+
+    // the peer's current tick as far as can be told, fractional
+    pub fn peer_tick(&self, peer: PeerId, now_ms: f64) -> Option<f64> {
+        self.clock(peer)
+            .map(|clock| clock.tick_at(now_ms, self.step_ms()))
+    }
+
+    // pings everyone when it's time and forgets peers that left. call once per frame
+    pub fn update<T>(&mut self, transport: &mut T, now_ms: f64) -> Result<(), ErrorStr>
+    where
+        T: Transport + ?Sized,
+    {
+        let connected = transport.peers();
+        self.peers.retain(|peer, _| connected.contains(peer));
+        if now_ms < self.next_ping_ms {
+            return Ok(());
+        }
+        self.next_ping_ms = now_ms + self.config.ping_interval_ms;
+        let sequence = self.next_sequence;
+        self.next_sequence = self.next_sequence.wrapping_add(1);
+        let message = Message::Ping {
+            sequence,
+            sent_ms: now_ms as u32,
+        };
+        let data = message.encode()?;
+        let timeout = self.config.ping_interval_ms * PING_TIMEOUT_INTERVALS;
+        for peer in connected {
+            let state = self.peers.entry(peer).or_default();
+            state.pending.retain(|_, sent| now_ms - *sent < timeout);
+            state.pending.insert(sequence, now_ms);
+            if let Err(error) = transport.send(peer, Channel::Unreliable, &data) {
+                log_debug!(LOG_TARGET, "Ping to {} failed: {}", peer, error);
+            }
+        }
+        Ok(())
+    }
+
+    // answers pings and takes samples from pongs. returns false for other messages. tick is
+    // the local simulation's, for the other side's timeline
+    pub fn handle_message<T>(
+        &mut self,
+        transport: &mut T,
+        peer: PeerId,
+        message: &Message,
+        now_ms: f64,
+        tick: u64,
+    ) -> Result<bool, ErrorStr>
+    where
+        T: Transport + ?Sized,
+    {
+        match message {
+            Message::Ping { sequence, sent_ms } => {
+                let pong = Message::Pong {
+                    sequence: *sequence,
+                    sent_ms: *sent_ms,
+                    received_ms: now_ms as u32,
+                    tick,
+                };
+                transport.send(peer, Channel::Unreliable, &pong.encode()?)?;
+                Ok(true)
+            }
+            Message::Pong {
+                sequence,
+                received_ms,
+                tick,
+                ..
+            } => {
+                self.sample(peer, *sequence, *received_ms, *tick, now_ms);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    fn sample(&mut self, peer: PeerId, sequence: u32, received_ms: u32, tick: u64, now_ms: f64) {
+        let step_ms = self.step_ms();
+        let window = self.config.sample_window.max(1);
+        let state = if let Some(state) = self.peers.get_mut(&peer) {
+            state
+        } else {
+            return;
+        };
+        // late, duplicated or never sent
+        let sent = if let Some(sent) = state.pending.remove(&sequence) {
+            sent
+        } else {
+            return;
+        };
+        let rtt_ms = now_ms - sent;
+        // assumes the answer took as long to come back as the ping took to get there
+        let midpoint = sent + rtt_ms / 2.0;
+        state.samples.push_back(Sample {
+            rtt_ms,
+            offset_ms: received_ms as f64 - midpoint,
+            epoch_ms: midpoint - tick as f64 * step_ms,
+        });
+        while state.samples.len() > window {
+            state.samples.pop_front();
+        }
+        let estimate = estimate(&state.samples);
+        state.clock = Some(match state.clock {
+            Some(clock) => PeerClock {
+                epoch_ms: clock.epoch_ms + (estimate.epoch_ms - clock.epoch_ms) * EPOCH_RATE,
+                ..estimate
+            },
+            None => estimate,
+        });
+    }
+
+    // how fast to run the local simulation so that local_tick ends up lead_ticks ahead of the
+    // peer's, e.g. for GameLoop::set_time_scale. 1 without a clock for the peer
+    pub fn time_scale(&self, peer: PeerId, local_tick: f64, now_ms: f64, lead_ticks: f64) -> f64 {
+        let target = if let Some(tick) = self.peer_tick(peer, now_ms) {
+            tick + lead_ticks
+        } else {
+            return 1.0;
+        };
+        // positive when behind
+        let error = target - local_tick;
+        if error.abs() < DEADBAND_TICKS {
+            return 1.0;
+        }
+        let max = self.config.max_speed_adjust;
+        1.0 + (error * SPEED_GAIN).clamp(-max, max)
+    }
+}
+
+fn estimate(samples: &VecDeque<Sample>) -> PeerClock {
+    let mut sorted: Vec<Sample> = samples.iter().copied().collect();
+    sorted.sort_by(|a, b| a.rtt_ms.total_cmp(&b.rtt_ms));
+    let rtt_ms = sorted[sorted.len() / 2].rtt_ms;
+    let jitter_ms = sorted
+        .iter()
+        .map(|sample| (sample.rtt_ms - rtt_ms).abs())
+        .sum::<f64>()
+        / sorted.len() as f64;
+    let fastest = &sorted[..sorted.len().div_ceil(2)];
+    let count = fastest.len() as f64;
+    PeerClock {
+        rtt_ms,
+        jitter_ms,
+        offset_ms: fastest.iter().map(|sample| sample.offset_ms).sum::<f64>() / count,
+        epoch_ms: fastest.iter().map(|sample| sample.epoch_ms).sum::<f64>() / count,
+        samples: sorted.len(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::libs::net::loopback::{LoopbackNetwork, LoopbackTransport};
+    use crate::libs::net::{Transport, TransportEvent};
+
+    // the remote clock runs this far ahead of ours
+    const OFFSET_MS: f64 = 1000.0;
+    // local time of the remote's tick 0
+    const EPOCH_MS: f64 = -250.0;
+
+    fn pair() -> (LoopbackNetwork, LoopbackTransport, LoopbackTransport) {
+        let network = LoopbackNetwork::new();
+        let (a, b) = (network.add_peer(), network.add_peer());
+        network.connect(a.local_id(), b.local_id()).unwrap();
+        (network, a, b)
+    }
+
+    // pings once per interval, each answered after (there, back) ms by a peer on OFFSET_MS and
+    // EPOCH_MS
+    fn sample(delays: &[(f64, f64)]) -> (ClockSync, PeerId, f64) {
+        let (_network, mut a, b) = pair();
+        let mut clock = ClockSync::new(ClockConfig::default());
+        let interval = clock.config().ping_interval_ms;
+        let mut now = 0.0;
+        for (sequence, (there, back)) in delays.iter().enumerate() {
+            now = sequence as f64 * interval;
+            clock.update(&mut a, now).unwrap();
+            let received = now + there;
+            let pong = Message::Pong {
+                sequence: sequence as u32,
+                sent_ms: now as u32,
+                received_ms: (received + OFFSET_MS) as u32,
+                tick: ((received - EPOCH_MS) / clock.step_ms()) as u64,
+            };
+            now = received + back;
+            let handled = clock
+                .handle_message(&mut a, b.local_id(), &pong, now, 0)
+                .unwrap();
+            assert!(handled);
+        }
+        (clock, b.local_id(), now)
+    }
+
+    #[test]
+    fn estimates_rtt_and_jitter() {
+        let (clock, peer, _) = sample(&[
+            (20.0, 20.0),
+            (25.0, 25.0),
+            (30.0, 30.0),
+            (25.0, 25.0),
+            (100.0, 100.0),
+        ]);
+        let estimate = clock.clock(peer).unwrap();
+        assert_eq!(estimate.samples, 5);
+        // the median of 40, 50, 50, 60 and 200
+        assert_eq!(estimate.rtt_ms, 50.0);
+        assert_eq!(estimate.jitter_ms, (10.0 + 0.0 + 0.0 + 10.0 + 150.0) / 5.0);
+    }
+
+    #[test]
+    fn keeps_a_window_of_samples() {
+        let delays = vec![(10.0, 10.0); DEFAULT_SAMPLE_WINDOW + 4];
+        let (clock, peer, _) = sample(&delays);
+        let estimate = clock.clock(peer).unwrap();
+        assert_eq!(estimate.samples, DEFAULT_SAMPLE_WINDOW);
+        assert_eq!(estimate.rtt_ms, 20.0);
+        assert_eq!(estimate.jitter_ms, 0.0);
+    }
+
+    #[test]
+    fn estimates_offset_and_tick_from_the_fastest_samples() {
+        // the slow ones queued on the way back only, taking them at face value would skew
+        // the offset
+        let (clock, peer, now) = sample(&[
+            (15.0, 15.0),
+            (15.0, 185.0),
+            (15.0, 15.0),
+            (15.0, 285.0),
+            (15.0, 15.0),
+        ]);
+        let estimate = clock.clock(peer).unwrap();
+        assert!((estimate.offset_ms - OFFSET_MS).abs() < 1.0);
+        let expected = (now - EPOCH_MS) / clock.step_ms();
+        let tick = clock.peer_tick(peer, now).unwrap();
+        assert!((tick - expected).abs() < 1.0, "{} for {}", tick, expected);
+    }
+
+    #[test]
+    fn ignores_unexpected_pongs() {
+        let (_network, mut a, b) = pair();
+        let mut clock = ClockSync::new(ClockConfig::default());
+        clock.update(&mut a, 0.0).unwrap();
+        let pong = |sequence| Message::Pong {
+            sequence,
+            sent_ms: 0,
+            received_ms: 0,
+            tick: 0,
+        };
+        clock
+            .handle_message(&mut a, b.local_id(), &pong(7), 20.0, 0)
+            .unwrap();
+        assert_eq!(clock.clock(b.local_id()), None);
+        for _ in 0..2 {
+            clock
+                .handle_message(&mut a, b.local_id(), &pong(0), 20.0, 0)
+                .unwrap();
+        }
+        assert_eq!(clock.clock(b.local_id()).unwrap().samples, 1);
+    }
+
+    #[test]
+    fn answers_pings_with_the_local_tick() {
+        let (_network, mut a, mut b) = pair();
+        let mut clock = ClockSync::new(ClockConfig::default());
+        let ping = Message::Ping {
+            sequence: 3,
+            sent_ms: 100,
+        };
+        assert!(
+            clock
+                .handle_message(&mut a, b.local_id(), &ping, 250.0, 42)
+                .unwrap()
+        );
+        let other = Message::Ready {
+            player: 0,
+            ready: true,
+        };
+        assert!(
+            !clock
+                .handle_message(&mut a, b.local_id(), &other, 250.0, 42)
+                .unwrap()
+        );
+        assert_eq!(b.recv(), Some(TransportEvent::Connected(a.local_id())));
+        let data = match b.recv() {
+            Some(TransportEvent::Message { data, .. }) => data,
+            event => panic!("Expected a pong, got {:?}", event),
+        };
+        let pong = Message::Pong {
+            sequence: 3,
+            sent_ms: 100,
+            received_ms: 250,
+            tick: 42,
+        };
+        assert_eq!(Message::decode(&data).unwrap(), pong);
+    }
+
+    #[test]
+    fn scales_time_towards_the_peer() {
+        let (clock, peer, now) = sample(&[(10.0, 10.0); 4]);
+        let tick = clock.peer_tick(peer, now).unwrap();
+        assert_eq!(clock.time_scale(peer, tick, now, 0.0), 1.0);
+        assert_eq!(clock.time_scale(PeerId(99), tick - 10.0, now, 0.0), 1.0);
+        let behind = clock.time_scale(peer, tick - 2.0, now, 0.0);
+        let ahead = clock.time_scale(peer, tick + 2.0, now, 0.0);
+        assert!(behind > 1.0 && ahead < 1.0);
+        // a lead counts as being behind by as much
+        assert_eq!(clock.time_scale(peer, tick, now, 2.0), behind);
+        let max = DEFAULT_MAX_SPEED_ADJUST;
+        assert_eq!(clock.time_scale(peer, tick - 1000.0, now, 0.0), 1.0 + max);
+        assert_eq!(clock.time_scale(peer, tick + 1000.0, now, 0.0), 1.0 - max);
+    }
+
+    #[test]
+    fn forgets_disconnected_peers() {
+        let (network, mut a, b) = pair();
+        let mut clock = ClockSync::new(ClockConfig::default());
+        clock.update(&mut a, 0.0).unwrap();
+        let pong = Message::Pong {
+            sequence: 0,
+            sent_ms: 0,
+            received_ms: 0,
+            tick: 0,
+        };
+        clock
+            .handle_message(&mut a, b.local_id(), &pong, 20.0, 0)
+            .unwrap();
+        assert!(clock.clock(b.local_id()).is_some());
+        network.disconnect(a.local_id(), b.local_id());
+        clock.update(&mut a, 30.0).unwrap();
+        assert_eq!(clock.clock(b.local_id()), None);
+    }
+}
@@ -0,0 +1,85 @@
+use leptos::ev;
+use leptos::prelude::*;
+
+use crate::libs::net::conditioned::{ConditionPreset, ConditionsHandle, NetworkConditions};
+use crate::log_warn;
+
+const LOG_TARGET: &str = "net::conditioned::menu";
+const TOGGLE_KEY: &str = "F4";
+
+fn describe(conditions: &NetworkConditions) -> String {
+    let bandwidth = conditions
+        .bandwidth_kbps
+        .map(|kbps| format!("{:.0} kbps", kbps))
+        .unwrap_or_else(|| "unlimited".to_string());
+    format!(
+        "{:.0}±{:.0} ms  loss {:.1}%  dup {:.1}%  reorder {:.1}%  {}",
+        conditions.latency_ms,
+        conditions.jitter_ms,
+        conditions.faults.loss * 100.0,
+        conditions.faults.duplicate * 100.0,
+        conditions.faults.reorder * 100.0,
+        bandwidth,
+    )
+}
+
+// dev menu for picking a network preset, toggled with F4 or the button. the choice is kept in
+// localStorage, ?net=<name> overrides it
+#[component]
+pub fn NetworkConditionsMenu(conditions: ConditionsHandle) -> impl IntoView {
+    if let Some(preset) = ConditionPreset::initial() {
+        conditions.set(preset.conditions());
+    }
+    let visible = RwSignal::new(false);
+    let selected = RwSignal::new(conditions.preset());
+    let conditions = StoredValue::new_local(conditions);
+
+    let handle = window_event_listener(ev::keydown, move |event| {
+        if event.key() == TOGGLE_KEY {
+            event.prevent_default();
+            visible.update(|visible| *visible = !*visible);
+        }
+    });
+    on_cleanup(move || handle.remove());
+
+    let select = move |preset: ConditionPreset| {
+        conditions.with_value(|conditions| conditions.set(preset.conditions()));
+        selected.set(Some(preset));
+        if let Err(error) = preset.save() {
+            log_warn!(LOG_TARGET, "{}", error);
+        }
+    };
+
+    let presets = ConditionPreset::ALL
+        .into_iter()
+        .map(|preset| {
+            let class = move || {
+                if selected.get() == Some(preset) {
+                    "block w-full text-left px-1 rounded bg-blue-600"
+                } else {
+                    "block w-full text-left px-1 rounded hover:bg-gray-700"
+                }
+            };
+            view! {
+                <button class=class on:click=move |_| select(preset)>
+                    <p>{preset.label()}</p>
+                    <p class="text-gray-400">{describe(&preset.conditions())}</p>
+                </button>
+            }
+        })
+        .collect_view();
+
+    view! {
+        <button
+            on:click=move |_| visible.update(|visible| *visible = !*visible)
+            class="fixed top-2 right-16 z-50 bg-gray-800/80 hover:bg-gray-700 text-white text-xs font-mono py-1 px-2 rounded"
+        >
+            "Net"
+        </button>
+        <Show when=move || visible.get()>
+            <div class="fixed top-10 right-16 z-50 w-72 p-2 rounded bg-gray-900/85 text-white text-xs font-mono space-y-1">
+                {presets.clone()}
+            </div>
+        </Show>
+    }
+}
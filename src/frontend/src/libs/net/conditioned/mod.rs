@@ -0,0 +1,609 @@
+pub mod menu;
+
+use std::collections::{BTreeMap, VecDeque};
+
+use web_sys::UrlSearchParams;
+
+use crate::libs::net::{Channel, PeerId, Transport, TransportEvent, check_message_size};
+use crate::libs::types::errors::ErrorStr;
+use crate::libs::types::rng::Rng;
+use crate::libs::types::shared::{SharedRefCell, shared_ref_cell};
+use crate::log_debug;
+
+const LOG_TARGET: &str = "net::conditioned";
+// query parameter and localStorage key for the preset
+const PRESET_KEY: &str = "net";
+// a message queued behind the bandwidth cap for longer than this is dropped, like a full
+// router buffer. only unreliable ones, reliable channels never drop
+const MAX_QUEUE_MS: f64 = 500.0;
+// how long a reordered message is held back on top of its delay
+const REORDER_HOLD_MS: f64 = 40.0;
+
+// what goes wrong on the unreliable channel, as chances per message in [0, 1]. the reliable
+// channel stays perfect, like a real data channel. ConditionedTransport and the loopback
+// network both decide a message's fate with apply
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LinkFaults {
+    pub loss: f32,
+    pub duplicate: f32,
+    // held back so later messages overtake it
+    pub reorder: f32,
+}
+
+impl LinkFaults {
+    // an entry per copy of the message that goes out, true for one held back. empty when it
+    // was lost
+    pub fn apply(&self, rng: &mut Rng) -> Vec<bool> {
+        if chance(rng, self.loss) {
+            return Vec::new();
+        }
+        let copies = if chance(rng, self.duplicate) { 2 } else { 1 };
+        (0..copies).map(|_| chance(rng, self.reorder)).collect()
+    }
+}
+
+fn chance(rng: &mut Rng, chance: f32) -> bool {
+    chance > 0.0 && rng.next_f32() < chance
+}
+
+// applied to each direction separately, so the round trip gets twice the latency
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct NetworkConditions {
+    pub latency_ms: f64,
+    // each message gets up to this much more or less than latency_ms
+    pub jitter_ms: f64,
+    pub faults: LinkFaults,
+    // kilobits per second, None for unlimited
+    pub bandwidth_kbps: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ConditionPreset {
+    Perfect,
+    GoodWifi,
+    Mobile,
+    Terrible,
+}
+
+impl ConditionPreset {
+    pub const ALL: [ConditionPreset; 4] = [
+        ConditionPreset::Perfect,
+        ConditionPreset::GoodWifi,
+        ConditionPreset::Mobile,
+        ConditionPreset::Terrible,
+    ];
+
+    pub fn conditions(&self) -> NetworkConditions {
+        match self {
+            ConditionPreset::Perfect => NetworkConditions::default(),
+            ConditionPreset::GoodWifi => NetworkConditions {
+                latency_ms: 15.0,
+                jitter_ms: 5.0,
+                faults: LinkFaults {
+                    loss: 0.005,
+                    duplicate: 0.0,
+                    reorder: 0.001,
+                },
+                bandwidth_kbps: Some(20_000.0),
+            },
+            ConditionPreset::Mobile => NetworkConditions {
+                latency_ms: 60.0,
+                jitter_ms: 25.0,
+                faults: LinkFaults {
+                    loss: 0.02,
+                    duplicate: 0.002,
+                    reorder: 0.01,
+                },
+                bandwidth_kbps: Some(2_000.0),
+            },
+            ConditionPreset::Terrible => NetworkConditions {
+                latency_ms: 200.0,
+                jitter_ms: 100.0,
+                faults: LinkFaults {
+                    loss: 0.1,
+                    duplicate: 0.02,
+                    reorder: 0.05,
+                },
+                bandwidth_kbps: Some(256.0),
+            },
+        }
+    }
+
+    #[inline]
+    pub fn name(&self) -> &'static str {
+        match self {
+            ConditionPreset::Perfect => "perfect",
+            ConditionPreset::GoodWifi => "good_wifi",
+            ConditionPreset::Mobile => "mobile",
+            ConditionPreset::Terrible => "terrible",
+        }
+    }
+
+    #[inline]
+    pub fn label(&self) -> &'static str {
+        match self {
+            ConditionPreset::Perfect => "Perfect",
+            ConditionPreset::GoodWifi => "Good wifi",
+            ConditionPreset::Mobile => "Mobile",
+            ConditionPreset::Terrible => "Terrible",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|preset| preset.name() == name)
+    }
+
+    // the one whose conditions these are exactly, if any
+    pub fn matching(conditions: &NetworkConditions) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|preset| preset.conditions() == *conditions)
+    }
+
+    // from the net query parameter, falling back to localStorage, e.g. ?net=mobile
+    pub fn initial() -> Option<Self> {
+        let name = query_preset().or_else(stored_preset)?;
+        Self::from_name(&name)
+    }
+
+    // keeps the choice for the next page load
+    pub fn save(&self) -> Result<(), ErrorStr> {
+        let storage = web_sys::window()
+            .and_then(|window| window.local_storage().ok())
+            .flatten();
+        let storage = if let Some(storage) = storage {
+            storage
+        } else {
+            return Err(ErrorStr::new("localStorage is unavailable"));
+        };
+        if let Err(error) = storage.set_item(PRESET_KEY, self.name()) {
+            let error = format!("Unable to save network preset: {:?}", error);
+            return Err(ErrorStr::new(error));
+        }
+        Ok(())
+    }
+}
+
+fn query_preset() -> Option<String> {
+    let search = web_sys::window()?.location().search().ok()?;
+    UrlSearchParams::new_with_str(&search).ok()?.get(PRESET_KEY)
+}
+
+fn stored_preset() -> Option<String> {
+    let storage = web_sys::window()?.local_storage().ok()??;
+    storage.get_item(PRESET_KEY).ok()?
+}
+
+// shared with the transport, so a menu can change conditions while it runs. cheap to clone
+#[derive(Debug, Clone, Default)]
+pub struct ConditionsHandle {
+    conditions: SharedRefCell<NetworkConditions>,
+}
+
+impl ConditionsHandle {
+    pub fn new(conditions: NetworkConditions) -> Self {
+        Self {
+            conditions: shared_ref_cell(conditions),
+        }
+    }
+
+    #[inline]
+    pub fn get(&self) -> NetworkConditions {
+        *self.conditions.borrow()
+    }
+
+    pub fn set(&self, conditions: NetworkConditions) {
+        *self.conditions.borrow_mut() = conditions;
+    }
+
+    #[inline]
+    pub fn preset(&self) -> Option<ConditionPreset> {
+        ConditionPreset::matching(&self.get())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConditionStats {
+    pub delayed: u64,
+    pub dropped: u64,
+    pub duplicated: u64,
+    pub reordered: u64,
+}
+
+#[derive(Debug, Clone)]
+struct Delayed {
+    peer: PeerId,
+    channel: Channel,
+    data: Vec<u8>,
+}
+
+// one way's queue of messages waiting out their delay
+#[derive(Debug, Clone, Default)]
+struct Direction {
+    // (due in microseconds, arrival order)
+    queue: BTreeMap<(u64, u64), Delayed>,
+    // when the bandwidth cap lets the next message start
+    free_at_ms: f64,
+    // reliable messages keep their order, each is due no earlier than the one before
+    last_reliable_ms: BTreeMap<PeerId, f64>,
+}
+
+impl Direction {
+    fn due(&mut self, now_ms: f64) -> Vec<Delayed> {
+        let now_us = (now_ms * 1000.0) as u64;
+        let mut due = Vec::new();
+        while let Some(entry) = self.queue.first_entry() {
+            if entry.key().0 > now_us {
+                break;
+            }
+            due.push(entry.remove());
+        }
+        due
+    }
+
+    fn forget(&mut self, peer: PeerId) {
+        self.queue.retain(|_, delayed| delayed.peer != peer);
+        self.last_reliable_ms.remove(&peer);
+    }
+}
+
+// makes any transport behave like a worse network: delay, jitter, loss, duplication,
+// reordering and a bandwidth cap, both on the way out and on the way in. time only moves with
+// update, so tests can run it on a simulated clock
+pub struct ConditionedTransport<T>
+where
+    T: Transport,
+{
+    inner: T,
+    conditions: ConditionsHandle,
+    rng: Rng,
+    now_ms: f64,
+    order: u64,
+    outgoing: Direction,
+    incoming: Direction,
+    events: VecDeque<TransportEvent>,
+    stats: ConditionStats,
+}
+
+impl<T> std::fmt::Debug for ConditionedTransport<T>
+where
+    T: Transport,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("ConditionedTransport")
+            .field("local_id", &self.inner.local_id())
+            .field("conditions", &self.conditions.get())
+            .field("outgoing", &self.outgoing.queue.len())
+            .field("incoming", &self.incoming.queue.len())
+            .field("stats", &self.stats)
+            .finish()
+    }
+}
+
+impl<T> ConditionedTransport<T>
+where
+    T: Transport,
+{
+    // seeded, so a bad run can be repeated exactly on a simulated clock
+    pub fn new(inner: T, conditions: ConditionsHandle, seed: u64) -> Self {
+        Self {
+            inner,
+            conditions,
+            rng: Rng::new(seed),
+            now_ms: 0.0,
+            order: 0,
+            outgoing: Direction::default(),
+            incoming: Direction::default(),
+            events: VecDeque::new(),
+            stats: ConditionStats::default(),
+        }
+    }
+
+    #[inline]
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    #[inline]
+    pub fn inner_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    #[inline]
+    pub fn conditions(&self) -> ConditionsHandle {
+        self.conditions.clone()
+    }
+
+    #[inline]
+    pub fn stats(&self) -> ConditionStats {
+        self.stats
+    }
+
+    // messages still waiting, both ways
+    #[inline]
+    pub fn queued(&self) -> usize {
+        self.outgoing.queue.len() + self.incoming.queue.len()
+    }
+
+    // sends and receives whatever is due by now_ms. call once per frame, before recv
+    pub fn update(&mut self, now_ms: f64) -> Result<(), ErrorStr> {
+        self.now_ms = now_ms;
+        self.pull();
+        for delayed in self.outgoing.due(now_ms) {
+            if !self.inner.is_connected(delayed.peer) {
+                continue;
+            }
+            if let Err(error) = self
+                .inner
+                .send(delayed.peer, delayed.channel, &delayed.data)
+            {
+                log_debug!(
+                    LOG_TARGET,
+                    "Delayed send to {} failed: {}",
+                    delayed.peer,
+                    error
+                );
+            }
+        }
+        self.release();
+        Ok(())
+    }
+
+    // takes everything the inner transport has, holding messages back
+    fn pull(&mut self) {
+        while let Some(event) = self.inner.recv() {
+            match event {
+                TransportEvent::Message {
+                    peer,
+                    channel,
+                    data,
+                } => self.delay(false, peer, channel, data),
+                TransportEvent::Disconnected(peer) => {
+                    // whatever was still on the wire is lost with the connection
+                    self.outgoing.forget(peer);
+                    self.incoming.forget(peer);
+                    self.events.push_back(TransportEvent::Disconnected(peer));
+                }
+                event => self.events.push_back(event),
+            }
+        }
+    }
+
+    fn release(&mut self) {
+        for delayed in self.incoming.due(self.now_ms) {
+            self.events.push_back(TransportEvent::Message {
+                peer: delayed.peer,
+                channel: delayed.channel,
+                data: delayed.data,
+            });
+        }
+    }
+
+    fn delay(&mut self, outgoing: bool, peer: PeerId, channel: Channel, data: Vec<u8>) {
+        let conditions = self.conditions.get();
+        let now_ms = self.now_ms;
+        let unreliable = channel == Channel::Unreliable;
+        let copies = if unreliable {
+            conditions.faults.apply(&mut self.rng)
+        } else {
+            vec![false]
+        };
+        if copies.is_empty() {
+            self.stats.dropped += 1;
+            return;
+        }
+        // the cap is shared by every peer in the same direction, like one uplink
+        let mut sent_ms = now_ms;
+        if let Some(kbps) = conditions.bandwidth_kbps.filter(|kbps| *kbps > 0.0) {
+            let direction = if outgoing {
+                &mut self.outgoing
+            } else {
+                &mut self.incoming
+            };
+            let start_ms = direction.free_at_ms.max(now_ms);
+            if unreliable && start_ms - now_ms > MAX_QUEUE_MS {
+                self.stats.dropped += 1;
+                return;
+            }
+            // kilobits per second is bits per millisecond
+            sent_ms = start_ms + (data.len() * 8) as f64 / kbps;
+            direction.free_at_ms = sent_ms;
+        }
+        if copies.len() > 1 {
+            self.stats.duplicated += 1;
+        }
+        for held in copies {
+            let jitter = conditions.jitter_ms * (self.rng.next_f32() as f64 * 2.0 - 1.0);
+            let mut due_ms = sent_ms + (conditions.latency_ms + jitter).max(0.0);
+            if held {
+                self.stats.reordered += 1;
+                due_ms += REORDER_HOLD_MS;
+            }
+            let direction = if outgoing {
+                &mut self.outgoing
+            } else {
+                &mut self.incoming
+            };
+            if !unreliable {
+                let last = direction.last_reliable_ms.entry(peer).or_insert(due_ms);
+                due_ms = due_ms.max(*last);
+                *last = due_ms;
+            }
+            self.order += 1;
+            let key = ((due_ms * 1000.0) as u64, self.order);
+            direction.queue.insert(
+                key,
+                Delayed {
+                    peer,
+                    channel,
+                    data: data.clone(),
+                },
+            );
+            self.stats.delayed += 1;
+        }
+    }
+}
+
+impl<T> Transport for ConditionedTransport<T>
+where
+    T: Transport,
+{
+    #[inline]
+    fn local_id(&self) -> PeerId {
+        self.inner.local_id()
+    }
+
+    fn peers(&self) -> Vec<PeerId> {
+        self.inner.peers()
+    }
+
+    fn is_connected(&self, peer: PeerId) -> bool {
+        self.inner.is_connected(peer)
+    }
+
+    // queued, errors from the inner transport only show up in the log once it's due
+    fn send(&mut self, peer: PeerId, channel: Channel, data: &[u8]) -> Result<(), ErrorStr> {
+        if !self.inner.is_connected(peer) {
+            return Err(ErrorStr::new(format!("Not connected to {}", peer)));
+        }
+        check_message_size(data)?;
+        self.delay(true, peer, channel, data.to_vec());
+        Ok(())
+    }
+
+    fn recv(&mut self) -> Option<TransportEvent> {
+        if self.events.is_empty() {
+            self.pull();
+            self.release();
+        }
+        self.events.pop_front()
+    }
+
+    fn disconnect(&mut self, peer: PeerId) {
+        self.outgoing.forget(peer);
+        self.incoming.forget(peer);
+        self.inner.disconnect(peer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::libs::net::loopback::{LoopbackNetwork, LoopbackTransport};
+
+    const FRAME_MS: f64 = 10.0;
+    const UNRELIABLE: usize = 1000;
+    const RELIABLE: usize = 50;
+
+    #[test]
+    fn names_every_preset() {
+        for preset in ConditionPreset::ALL {
+            assert_eq!(ConditionPreset::from_name(preset.name()), Some(preset));
+            assert_eq!(
+                ConditionPreset::matching(&preset.conditions()),
+                Some(preset)
+            );
+        }
+        assert_eq!(ConditionPreset::from_name("dial_up"), None);
+        assert_eq!(
+            ConditionPreset::Perfect.conditions(),
+            NetworkConditions::default()
+        );
+        let handle = ConditionsHandle::new(ConditionPreset::Mobile.conditions());
+        assert_eq!(handle.preset(), Some(ConditionPreset::Mobile));
+        handle.set(NetworkConditions {
+            latency_ms: 1.0,
+            ..NetworkConditions::default()
+        });
+        assert_eq!(handle.preset(), None);
+    }
+
+    #[test]
+    fn presets_get_worse_in_order() {
+        for pair in ConditionPreset::ALL.windows(2) {
+            let (better, worse) = (pair[0].conditions(), pair[1].conditions());
+            assert!(better.latency_ms < worse.latency_ms);
+            assert!(better.jitter_ms < worse.jitter_ms);
+            assert!(better.faults.loss < worse.faults.loss);
+            let bandwidth =
+                |conditions: &NetworkConditions| conditions.bandwidth_kbps.unwrap_or(f64::INFINITY);
+            assert!(bandwidth(&better) > bandwidth(&worse));
+        }
+    }
+
+    #[test]
+    fn applies_link_faults() {
+        let mut rng = Rng::new(7);
+        assert_eq!(LinkFaults::default().apply(&mut rng), vec![false]);
+        let lost = LinkFaults {
+            loss: 1.0,
+            ..LinkFaults::default()
+        };
+        assert!(lost.apply(&mut rng).is_empty());
+        let doubled = LinkFaults {
+            duplicate: 1.0,
+            reorder: 1.0,
+            ..LinkFaults::default()
+        };
+        assert_eq!(doubled.apply(&mut rng), vec![true, true]);
+    }
+
+    // a sends through the preset to b, which answers nothing. returns what b got on each
+    // channel, in order, and a's stats
+    fn send_through(preset: ConditionPreset) -> (Vec<u16>, Vec<u16>, ConditionStats) {
+        let network = LoopbackNetwork::new();
+        let (a, mut b) = (network.add_peer(), network.add_peer());
+        let (a_id, b_id) = (a.local_id(), b.local_id());
+        network.connect(a_id, b_id).unwrap();
+        let conditions = ConditionsHandle::new(preset.conditions());
+        let mut a: ConditionedTransport<LoopbackTransport> =
+            ConditionedTransport::new(a, conditions, 0x5eed);
+        for index in 0..UNRELIABLE.max(RELIABLE) as u16 {
+            if (index as usize) < UNRELIABLE {
+                a.send(b_id, Channel::Unreliable, &index.to_le_bytes())
+                    .unwrap();
+            }
+            if (index as usize) < RELIABLE {
+                a.send(b_id, Channel::Reliable, &index.to_le_bytes())
+                    .unwrap();
+            }
+        }
+        let (mut unreliable, mut reliable) = (Vec::new(), Vec::new());
+        let latency = preset.conditions().latency_ms - preset.conditions().jitter_ms;
+        let mut now = 0.0;
+        while a.queued() > 0 {
+            now += FRAME_MS;
+            a.update(now).unwrap();
+            while let Some(event) = b.recv() {
+                if let TransportEvent::Message { channel, data, .. } = event {
+                    assert!(now >= latency, "{:?} arrived after {} ms", preset, now);
+                    let index = u16::from_le_bytes([data[0], data[1]]);
+                    match channel {
+                        Channel::Unreliable => unreliable.push(index),
+                        Channel::Reliable => reliable.push(index),
+                    }
+                }
+            }
+        }
+        (unreliable, reliable, a.stats())
+    }
+
+    #[test]
+    fn delivers_everything_on_a_perfect_network() {
+        let (unreliable, reliable, stats) = send_through(ConditionPreset::Perfect);
+        assert_eq!(unreliable, (0..UNRELIABLE as u16).collect::<Vec<_>>());
+        assert_eq!(reliable, (0..RELIABLE as u16).collect::<Vec<_>>());
+        assert_eq!(stats.dropped + stats.duplicated + stats.reordered, 0);
+    }
+
+    #[test]
+    fn keeps_reliable_messages_whole_on_a_terrible_network() {
+        let (unreliable, reliable, stats) = send_through(ConditionPreset::Terrible);
+        assert_eq!(reliable, (0..RELIABLE as u16).collect::<Vec<_>>());
+        assert!(stats.dropped > 0 && stats.duplicated > 0 && stats.reordered > 0);
+        let expected = UNRELIABLE as u64 - stats.dropped + stats.duplicated;
+        assert_eq!(unreliable.len() as u64, expected);
+        let in_order = unreliable.windows(2).all(|pair| pair[0] <= pair[1]);
+        assert!(!in_order);
+    }
+}
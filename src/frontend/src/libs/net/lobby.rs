@@ -0,0 +1,828 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use crate::libs::net::clock::ClockSync;
+use crate::libs::net::protocol::handshake::PeerEvent;
+use crate::libs::net::protocol::{
+    LeaveReason, Message, PROTOCOL_VERSION, RoomMember, RoomSettings,
+};
+use crate::libs::net::topology::{self, Topology, TopologyConfig, TopologyDecision};
+use crate::libs::net::{Channel, PeerId, SyncMode, Transport};
+use crate::libs::profiler::network::record_topology;
+use crate::libs::types::errors::ErrorStr;
+use crate::{log_debug, log_info, log_warn};
+
+const LOG_TARGET: &str = "net::lobby";
+// lines kept for the chat log, older ones are dropped
+const CHAT_HISTORY: usize = 100;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChatLine {
+    pub peer: PeerId,
+    pub player: u8,
+    pub name: String,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LobbyEvent {
+    // the host gave us a slot
+    Entered { player: u8 },
+    MemberJoined(RoomMember),
+    MemberLeft(RoomMember),
+    // settings or someone's ready state
+    Changed,
+    Chat(ChatLine),
+    Started { tick: u64 },
+    // the host left and the lowest remaining peer took over, a running match goes on as a
+    // mesh. in authoritative mode the new host promotes its AuthorityClient, everyone else
+    // points theirs at the new host
+    HostChanged { previous: PeerId, host: PeerId },
+}
+
+// the room before and around a match: who is in it with which player slot, who is ready, the
+// settings and chat. the host is always the member with the lowest peer id, ids only grow so a
+// newcomer never takes over, and when the host leaves everyone picks the same successor without
+// having to talk about it. the host's view is the truth and is sent whole on every change
+#[derive(Debug, Clone)]
+pub struct Lobby {
+    local_id: PeerId,
+    name: String,
+    host: Option<PeerId>,
+    settings: RoomSettings,
+    members: BTreeMap<PeerId, RoomMember>,
+    started: Option<u64>,
+    topology: Topology,
+    // accepted peers, members or not
+    connected: BTreeSet<PeerId>,
+    chat: VecDeque<ChatLine>,
+    events: VecDeque<LobbyEvent>,
+}
+
+impl Lobby {
+    // a new room with us as its host in slot 0
+    pub fn create<S>(local_id: PeerId, name: S, settings: RoomSettings) -> Self
+    where
+        S: Into<String>,
+    {
+        let name = name.into();
+        let mut lobby = Self::join(local_id, name.clone());
+        lobby.host = Some(local_id);
+        lobby.settings = settings;
+        lobby.members.insert(
+            local_id,
+            RoomMember {
+                peer: local_id,
+                player: 0,
+                name,
+                ready: false,
+            },
+        );
+        lobby
+    }
+
+    // someone else's room, we're a member once its host has sent the room
+    pub fn join<S>(local_id: PeerId, name: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self {
+            local_id,
+            name: name.into(),
+            host: None,
+            settings: RoomSettings::default(),
+            members: BTreeMap::new(),
+            started: None,
+            topology: Topology::Mesh,
+            connected: BTreeSet::new(),
+            chat: VecDeque::new(),
+            events: VecDeque::new(),
+        }
+    }
+
+    #[inline]
+    pub fn local_id(&self) -> PeerId {
+        self.local_id
+    }
+
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    // None until the host's room arrived
+    #[inline]
+    pub fn host(&self) -> Option<PeerId> {
+        self.host
+    }
+
+    #[inline]
+    pub fn is_host(&self) -> bool {
+        self.host == Some(self.local_id)
+    }
+
+    #[inline]
+    pub fn settings(&self) -> &RoomSettings {
+        &self.settings
+    }
+
+    // in peer id order
+    pub fn members(&self) -> impl Iterator<Item = &RoomMember> {
+        self.members.values()
+    }
+
+    #[inline]
+    pub fn member(&self, peer: PeerId) -> Option<&RoomMember> {
+        self.members.get(&peer)
+    }
+
+    #[inline]
+    pub fn local_player(&self) -> Option<u8> {
+        self.member(self.local_id).map(|member| member.player)
+    }
+
+    // every remote member and its slot, e.g. for RollbackSession::new
+    pub fn players(&self) -> BTreeMap<PeerId, u8> {
+        self.members
+            .values()
+            .filter(|member| member.peer != self.local_id)
+            .map(|member| (member.peer, member.player))
+            .collect()
+    }
+
+    // the match's first tick, once started
+    #[inline]
+    pub fn started(&self) -> Option<u64> {
+        self.started
+    }
+
+    // what the host picked when it started the match
+    #[inline]
+    pub fn topology(&self) -> Topology {
+        self.topology
+    }
+
+    // host only, mesh or star with us as the hub, from the player count and our rtts to
+    // everyone. pass the result's topology to start. shown in the profiler overlay
+    pub fn choose_topology(&self, config: &TopologyConfig, clock: &ClockSync) -> TopologyDecision {
+        let rtts: Vec<f64> = self
+            .members
+            .keys()
+            .filter(|peer| **peer != self.local_id)
+            .filter_map(|peer| clock.clock(*peer))
+            .map(|clock| clock.rtt_ms)
+            .collect();
+        let decision = topology::choose(config, self.local_id, self.members.len(), &rtts);
+        record_topology(decision);
+        decision
+    }
+
+    pub fn all_ready(&self) -> bool {
+        !self.members.is_empty() && self.members.values().all(|member| member.ready)
+    }
+
+    // oldest first
+    pub fn chat(&self) -> impl Iterator<Item = &ChatLine> {
+        self.chat.iter()
+    }
+
+    pub fn recv(&mut self) -> Option<LobbyEvent> {
+        self.events.pop_front()
+    }
+
+    // takes the handshake's events. returns true for lobby messages, connection events are
+    // noted but left for whoever else needs them too
+    pub fn handle_event<T>(
+        &mut self,
+        transport: &mut T,
+        event: &PeerEvent,
+    ) -> Result<bool, ErrorStr>
+    where
+        T: Transport + ?Sized,
+    {
+        match event {
+            PeerEvent::Accepted { peer, name } => {
+                self.connected.insert(*peer);
+                if self.is_host() {
+                    self.admit(transport, *peer, name)?;
+                }
+                Ok(false)
+            }
+            PeerEvent::Disconnected(peer) => {
+                self.connected.remove(peer);
+                self.depart(transport, *peer)?;
+                Ok(false)
+            }
+            PeerEvent::Message { peer, message, .. } => {
+                self.handle_message(transport, *peer, message)
+            }
+            PeerEvent::Error { .. } => Ok(false),
+        }
+    }
+
+    fn handle_message<T>(
+        &mut self,
+        transport: &mut T,
+        peer: PeerId,
+        message: &Message,
+    ) -> Result<bool, ErrorStr>
+    where
+        T: Transport + ?Sized,
+    {
+        match message {
+            Message::Room {
+                settings,
+                members,
+                started,
+                topology,
+            } => {
+                // only ever from the host, or whoever we elected when it left. a room from
+                // anyone else would quietly make them host
+                if self.host.is_some_and(|host| peer != host) {
+                    log_warn!(
+                        LOG_TARGET,
+                        "Ignored room from {}, the host is elsewhere",
+                        peer
+                    );
+                    return Ok(true);
+                }
+                self.host = Some(peer);
+                self.apply_room(settings, members, *started, *topology);
+            }
+            Message::Ready { player, ready } => {
+                if !self.is_host() {
+                    return Ok(true);
+                }
+                let member = self
+                    .members
+                    .get_mut(&peer)
+                    .filter(|member| member.player == *player);
+                let member = if let Some(member) = member {
+                    member
+                } else {
+                    let error = format!("{} sent ready for player {}", peer, player);
+                    return Err(ErrorStr::new(error));
+                };
+                if member.ready != *ready {
+                    member.ready = *ready;
+                    self.events.push_back(LobbyEvent::Changed);
+                    self.send_room(transport)?;
+                }
+            }
+            Message::Chat { player, text } => {
+                let member = self
+                    .members
+                    .get(&peer)
+                    .filter(|member| member.player == *player);
+                let member = if let Some(member) = member {
+                    member
+                } else {
+                    let error = format!("{} sent chat as player {}", peer, player);
+                    return Err(ErrorStr::new(error));
+                };
+                let line = ChatLine {
+                    peer,
+                    player: member.player,
+                    name: member.name.clone(),
+                    text: text.clone(),
+                };
+                self.push_chat(line);
+            }
+            Message::Leave { reason, .. } => {
+                log_info!(LOG_TARGET, "{} left: {:?}", peer, reason);
+                self.depart(transport, peer)?;
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    // the host's answer to a new peer: a free slot, or turned away
+    fn admit<T>(&mut self, transport: &mut T, peer: PeerId, name: &str) -> Result<(), ErrorStr>
+    where
+        T: Transport + ?Sized,
+    {
+        if self.members.contains_key(&peer) {
+            return Ok(());
+        }
+        let refusal = if self.members.len() >= self.settings.max_players as usize {
+            Some("Room is full")
+        } else if self.started.is_some() && self.settings.mode == SyncMode::Rollback {
+            // every rollback peer has to start from the same world
+            Some("Match already started")
+        } else {
+            None
+        };
+        if let Some(reason) = refusal {
+            log_info!(LOG_TARGET, "Turned away {}: {}", peer, reason);
+            let reject = Message::Reject {
+                version: PROTOCOL_VERSION,
+                reason: reason.to_string(),
+            };
+            let _ = transport.send(peer, Channel::Reliable, &reject.encode()?);
+            transport.disconnect(peer);
+            self.connected.remove(&peer);
+            return Ok(());
+        }
+        let player = (0..self.settings.max_players)
+            .find(|slot| self.members.values().all(|member| member.player != *slot))
+            .unwrap_or_default();
+        let member = RoomMember {
+            peer,
+            player,
+            name: name.to_string(),
+            ready: false,
+        };
+        log_info!(
+            LOG_TARGET,
+            "{} ({}) joined as player {}",
+            peer,
+            name,
+            player
+        );
+        self.members.insert(peer, member.clone());
+        self.events.push_back(LobbyEvent::MemberJoined(member));
+        self.send_room(transport)
+    }
+
+    // a member left or dropped. everyone works out the new host the same way
+    fn depart<T>(&mut self, transport: &mut T, peer: PeerId) -> Result<(), ErrorStr>
+    where
+        T: Transport + ?Sized,
+    {
+        let member = if let Some(member) = self.members.remove(&peer) {
+            member
+        } else {
+            return Ok(());
+        };
+        self.events.push_back(LobbyEvent::MemberLeft(member));
+        if self.host == Some(peer) {
+            self.host = self.members.keys().next().copied();
+            // a star through the old host has no hub any more, everyone falls back to a mesh
+            // the same way and the new host's room confirms it
+            self.topology = Topology::Mesh;
+            if let Some(host) = self.host {
+                log_info!(LOG_TARGET, "Host {} left, {} takes over", peer, host);
+                self.events.push_back(LobbyEvent::HostChanged {
+                    previous: peer,
+                    host,
+                });
+            }
+        }
+        if self.is_host() {
+            self.send_room(transport)?;
+        }
+        Ok(())
+    }
+
+    fn apply_room(
+        &mut self,
+        settings: &RoomSettings,
+        members: &[RoomMember],
+        started: Option<u64>,
+        topology: Topology,
+    ) {
+        let before = std::mem::take(&mut self.members);
+        self.members = members
+            .iter()
+            .map(|member| (member.peer, member.clone()))
+            .collect();
+        for (peer, member) in before.iter() {
+            if !self.members.contains_key(peer) {
+                self.events
+                    .push_back(LobbyEvent::MemberLeft(member.clone()));
+            }
+        }
+        for (peer, member) in self.members.iter() {
+            if before.contains_key(peer) {
+                continue;
+            }
+            if *peer == self.local_id {
+                self.events.push_back(LobbyEvent::Entered {
+                    player: member.player,
+                });
+            } else {
+                self.events
+                    .push_back(LobbyEvent::MemberJoined(member.clone()));
+            }
+        }
+        let changed = self.settings != *settings
+            || before
+                .iter()
+                .any(|(peer, member)| self.members.get(peer).is_some_and(|now| now != member));
+        self.settings = settings.clone();
+        if changed {
+            self.events.push_back(LobbyEvent::Changed);
+        }
+        self.topology = topology;
+        if let (None, Some(tick)) = (self.started, started) {
+            record_topology(TopologyDecision::from_host(topology, self.members.len()));
+            self.events.push_back(LobbyEvent::Started { tick });
+        }
+        self.started = started;
+    }
+
+    // the whole room to every member
+    fn send_room<T>(&self, transport: &mut T) -> Result<(), ErrorStr>
+    where
+        T: Transport + ?Sized,
+    {
+        let message = Message::Room {
+            settings: self.settings.clone(),
+            members: self.members.values().cloned().collect(),
+            started: self.started,
+            topology: self.topology,
+        };
+        self.send_members(transport, &message)
+    }
+
+    fn send_members<T>(&self, transport: &mut T, message: &Message) -> Result<(), ErrorStr>
+    where
+        T: Transport + ?Sized,
+    {
+        let data = message.encode()?;
+        for peer in self.members.keys() {
+            if *peer == self.local_id || !self.connected.contains(peer) {
+                continue;
+            }
+            if let Err(error) = transport.send(*peer, Channel::Reliable, &data) {
+                log_debug!(
+                    LOG_TARGET,
+                    "{:?} to {} failed: {}",
+                    message.kind(),
+                    peer,
+                    error
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn push_chat(&mut self, line: ChatLine) {
+        self.chat.push_back(line.clone());
+        while self.chat.len() > CHAT_HISTORY {
+            self.chat.pop_front();
+        }
+        self.events.push_back(LobbyEvent::Chat(line));
+    }
+
+    fn local_member(&self) -> Result<&RoomMember, ErrorStr> {
+        match self.members.get(&self.local_id) {
+            Some(member) => Ok(member),
+            None => Err(ErrorStr::new("Not in a room yet")),
+        }
+    }
+
+    fn check_host(&self) -> Result<(), ErrorStr> {
+        if !self.is_host() {
+            return Err(ErrorStr::new("Only the host can do that"));
+        }
+        Ok(())
+    }
+
+    pub fn set_ready<T>(&mut self, transport: &mut T, ready: bool) -> Result<(), ErrorStr>
+    where
+        T: Transport + ?Sized,
+    {
+        let player = self.local_member()?.player;
+        if let Some(member) = self.members.get_mut(&self.local_id) {
+            member.ready = ready;
+        }
+        self.events.push_back(LobbyEvent::Changed);
+        if self.is_host() {
+            return self.send_room(transport);
+        }
+        let host = if let Some(host) = self.host {
+            host
+        } else {
+            return Ok(());
+        };
+        let message = Message::Ready { player, ready };
+        transport.send(host, Channel::Reliable, &message.encode()?)
+    }
+
+    // host only, before the match starts
+    pub fn set_settings<T>(
+        &mut self,
+        transport: &mut T,
+        settings: RoomSettings,
+    ) -> Result<(), ErrorStr>
+    where
+        T: Transport + ?Sized,
+    {
+        self.check_host()?;
+        if self.started.is_some() {
+            return Err(ErrorStr::new("Match already started"));
+        }
+        if (settings.max_players as usize) < self.members.len() {
+            let error = format!(
+                "{} players are already in the room, more than {}",
+                self.members.len(),
+                settings.max_players
+            );
+            return Err(ErrorStr::new(error));
+        }
+        self.settings = settings;
+        self.events.push_back(LobbyEvent::Changed);
+        self.send_room(transport)
+    }
+
+    // to every member, and into our own log
+    pub fn send_chat<T, S>(&mut self, transport: &mut T, text: S) -> Result<(), ErrorStr>
+    where
+        T: Transport + ?Sized,
+        S: Into<String>,
+    {
+        let member = self.local_member()?.clone();
+        let text = text.into();
+        let message = Message::Chat {
+            player: member.player,
+            text: text.clone(),
+        };
+        self.send_members(transport, &message)?;
+        self.push_chat(ChatLine {
+            peer: self.local_id,
+            player: member.player,
+            name: member.name,
+            text,
+        });
+        Ok(())
+    }
+
+    // host only, once everyone is ready. tick is the match's first, the same world tick on
+    // every peer, see choose_topology for the topology
+    pub fn start<T>(
+        &mut self,
+        transport: &mut T,
+        tick: u64,
+        topology: Topology,
+    ) -> Result<(), ErrorStr>
+    where
+        T: Transport + ?Sized,
+    {
+        self.check_host()?;
+        if self.started.is_some() {
+            return Err(ErrorStr::new("Match already started"));
+        }
+        if !self.all_ready() {
+            return Err(ErrorStr::new("Not everyone is ready"));
+        }
+        if let Some(hub) = topology.hub().filter(|hub| !self.members.contains_key(hub)) {
+            return Err(ErrorStr::new(format!("Hub {} isn't in the room", hub)));
+        }
+        self.started = Some(tick);
+        self.topology = topology;
+        self.events.push_back(LobbyEvent::Started { tick });
+        self.send_room(transport)
+    }
+
+    // tells everyone and drops every connection. the lobby is empty afterwards
+    pub fn leave<T>(&mut self, transport: &mut T)
+    where
+        T: Transport + ?Sized,
+    {
+        if let Some(player) = self.local_player() {
+            let message = Message::Leave {
+                player,
+                reason: LeaveReason::Quit,
+            };
+            let _ = self.send_members(transport, &message);
+        }
+        for peer in std::mem::take(&mut self.connected) {
+            transport.disconnect(peer);
+        }
+        self.host = None;
+        self.members.clear();
+        self.started = None;
+        self.topology = Topology::Mesh;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::libs::net::loopback::{LoopbackNetwork, LoopbackTransport};
+    use crate::libs::net::protocol::handshake::Handshake;
+
+    // enough for a message, its answer and the room that follows
+    const ROUNDS: usize = 4;
+
+    struct Peer {
+        transport: LoopbackTransport,
+        handshake: Handshake,
+        lobby: Lobby,
+        errors: Vec<String>,
+    }
+
+    impl Peer {
+        fn new(network: &LoopbackNetwork, name: &str, settings: Option<RoomSettings>) -> Self {
+            let transport = network.add_peer();
+            let id = transport.local_id();
+            let lobby = match settings {
+                Some(settings) => Lobby::create(id, name, settings),
+                None => Lobby::join(id, name),
+            };
+            Self {
+                transport,
+                handshake: Handshake::new(name),
+                lobby,
+                errors: Vec::new(),
+            }
+        }
+
+        fn id(&self) -> PeerId {
+            self.lobby.local_id()
+        }
+
+        fn events(&mut self) -> Vec<LobbyEvent> {
+            std::iter::from_fn(|| self.lobby.recv()).collect()
+        }
+    }
+
+    fn pump(network: &LoopbackNetwork, peers: &mut [&mut Peer]) {
+        for _ in 0..ROUNDS {
+            for peer in peers.iter_mut() {
+                for event in peer.handshake.poll(&mut peer.transport) {
+                    if let PeerEvent::Error { error, .. } = &event {
+                        peer.errors.push(error.clone());
+                    }
+                    peer.lobby
+                        .handle_event(&mut peer.transport, &event)
+                        .unwrap();
+                }
+            }
+            network.step();
+        }
+    }
+
+    fn settings(max_players: u8) -> RoomSettings {
+        RoomSettings {
+            map: "arena".to_string(),
+            max_players,
+            ..RoomSettings::default()
+        }
+    }
+
+    // a host and two joiners, everyone connected to everyone
+    fn room(max_players: u8) -> (LoopbackNetwork, Peer, Peer, Peer) {
+        let network = LoopbackNetwork::new();
+        let mut host = Peer::new(&network, "Ada", Some(settings(max_players)));
+        let mut b = Peer::new(&network, "Grace", None);
+        let mut c = Peer::new(&network, "Linus", None);
+        network.connect_all(&[host.id(), b.id()]).unwrap();
+        pump(&network, &mut [&mut host, &mut b, &mut c]);
+        network.connect_all(&[host.id(), b.id(), c.id()]).unwrap();
+        pump(&network, &mut [&mut host, &mut b, &mut c]);
+        (network, host, b, c)
+    }
+
+    #[test]
+    fn runs_a_room_up_to_the_start() {
+        let (network, mut host, mut b, mut c) = room(4);
+        assert!(host.lobby.is_host());
+        assert_eq!(host.lobby.local_player(), Some(0));
+        assert_eq!(b.lobby.local_player(), Some(1));
+        assert_eq!(c.lobby.local_player(), Some(2));
+        assert!(b.events().contains(&LobbyEvent::Entered { player: 1 }));
+        for peer in [&b, &c] {
+            assert_eq!(peer.lobby.host(), Some(host.id()));
+            assert_eq!(
+                peer.lobby.members().collect::<Vec<_>>(),
+                host.lobby.members().collect::<Vec<_>>()
+            );
+        }
+        assert_eq!(
+            host.lobby.players(),
+            BTreeMap::from([(b.id(), 1), (c.id(), 2)])
+        );
+
+        // only the host changes the settings, everyone sees them
+        assert!(b.lobby.set_settings(&mut b.transport, settings(3)).is_err());
+        let changed = RoomSettings {
+            map: "docks".to_string(),
+            ..settings(3)
+        };
+        host.lobby
+            .set_settings(&mut host.transport, changed.clone())
+            .unwrap();
+        assert!(
+            host.lobby
+                .set_settings(&mut host.transport, settings(2))
+                .is_err()
+        );
+        b.lobby.set_ready(&mut b.transport, true).unwrap();
+        c.lobby.send_chat(&mut c.transport, "hello").unwrap();
+        let b_id = b.id();
+        pump(&network, &mut [&mut host, &mut b, &mut c]);
+        for peer in [&mut host, &mut b, &mut c] {
+            assert_eq!(peer.lobby.settings(), &changed);
+            assert!(peer.lobby.member(b_id).unwrap().ready);
+            let chat: Vec<(&str, &str)> = peer
+                .lobby
+                .chat()
+                .map(|line| (line.name.as_str(), line.text.as_str()))
+                .collect();
+            assert_eq!(chat, vec![("Linus", "hello")]);
+            assert!(peer.events().contains(&LobbyEvent::Changed));
+        }
+
+        // everyone has to be ready, and only the host starts
+        let tick = 120;
+        let result = host.lobby.start(&mut host.transport, tick, Topology::Mesh);
+        assert!(result.is_err());
+        host.lobby.set_ready(&mut host.transport, true).unwrap();
+        c.lobby.set_ready(&mut c.transport, true).unwrap();
+        pump(&network, &mut [&mut host, &mut b, &mut c]);
+        assert!(host.lobby.all_ready());
+        assert!(
+            c.lobby
+                .start(&mut c.transport, tick, Topology::Mesh)
+                .is_err()
+        );
+        let stray = Topology::Star { hub: PeerId(99) };
+        assert!(host.lobby.start(&mut host.transport, tick, stray).is_err());
+        let star = Topology::Star { hub: host.id() };
+        host.lobby.start(&mut host.transport, tick, star).unwrap();
+        pump(&network, &mut [&mut host, &mut b, &mut c]);
+        for peer in [&mut host, &mut b, &mut c] {
+            assert_eq!(peer.lobby.started(), Some(tick));
+            assert_eq!(peer.lobby.topology(), star);
+            assert!(peer.events().contains(&LobbyEvent::Started { tick }));
+        }
+        assert!(host.lobby.start(&mut host.transport, tick, star).is_err());
+    }
+
+    #[test]
+    fn turns_away_peers_when_full() {
+        let (_network, host, b, c) = room(2);
+        assert_eq!(host.lobby.members().count(), 2);
+        assert_eq!(b.lobby.local_player(), Some(1));
+        assert_eq!(c.lobby.local_player(), None);
+        assert_eq!(c.lobby.host(), None);
+        assert!(!c.transport.is_connected(host.id()));
+        assert!(c.errors.iter().any(|error| error.contains("Room is full")));
+        assert_eq!(b.lobby.members().count(), 2);
+    }
+
+    #[test]
+    fn hands_over_when_the_host_leaves_mid_match() {
+        let (network, mut host, mut b, mut c) = room(4);
+        for peer in [&mut host, &mut b, &mut c] {
+            peer.lobby.set_ready(&mut peer.transport, true).unwrap();
+        }
+        pump(&network, &mut [&mut host, &mut b, &mut c]);
+        let star = Topology::Star { hub: host.id() };
+        host.lobby.start(&mut host.transport, 0, star).unwrap();
+        pump(&network, &mut [&mut host, &mut b, &mut c]);
+        b.events();
+        c.events();
+
+        let (old_host, b_id) = (host.id(), b.id());
+        host.lobby.leave(&mut host.transport);
+        drop(host);
+        pump(&network, &mut [&mut b, &mut c]);
+        for peer in [&mut b, &mut c] {
+            assert_eq!(peer.lobby.host(), Some(b_id));
+            assert_eq!(peer.lobby.started(), Some(0));
+            assert_eq!(peer.lobby.topology(), Topology::Mesh);
+            assert_eq!(peer.lobby.members().count(), 2);
+            let events = peer.events();
+            assert!(events.contains(&LobbyEvent::HostChanged {
+                previous: old_host,
+                host: b_id,
+            }));
+            assert!(events.iter().any(
+                |event| matches!(event, LobbyEvent::MemberLeft(member) if member.peer == old_host)
+            ));
+        }
+        assert!(b.lobby.is_host());
+        // the new host runs the room from here
+        c.lobby.set_ready(&mut c.transport, false).unwrap();
+        pump(&network, &mut [&mut b, &mut c]);
+        assert!(!b.lobby.member(c.id()).unwrap().ready);
+    }
+
+    #[test]
+    fn ignores_rooms_from_anyone_but_the_host() {
+        let network = LoopbackNetwork::new();
+        // a lower id than the host's, which used to be enough to take over
+        let mut low = Peer::new(&network, "Low", None);
+        let mut host = Peer::new(&network, "Ada", Some(settings(4)));
+        let mut c = Peer::new(&network, "Linus", None);
+        network.connect_all(&[low.id(), host.id(), c.id()]).unwrap();
+        pump(&network, &mut [&mut host, &mut low, &mut c]);
+        assert_eq!(c.lobby.host(), Some(host.id()));
+        let forged = Message::Room {
+            settings: settings(4),
+            members: Vec::new(),
+            started: Some(0),
+            topology: Topology::Mesh,
+        };
+        low.transport
+            .send(c.id(), Channel::Reliable, &forged.encode().unwrap())
+            .unwrap();
+        pump(&network, &mut [&mut c]);
+        assert_eq!(c.lobby.host(), Some(host.id()));
+        assert_eq!(c.lobby.members().count(), 3);
+        assert_eq!(c.lobby.started(), None);
+    }
+}
@@ -0,0 +1,370 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use crate::libs::net::conditioned::LinkFaults;
+use crate::libs::net::{Channel, PeerId, Transport, TransportEvent, check_message_size};
+use crate::libs::types::errors::ErrorStr;
+use crate::libs::types::rng::Rng;
+use crate::libs::types::shared::{SharedRefCell, shared_ref_cell};
+
+// a reordered message is held back up to this many extra steps
+const MAX_REORDER_STEPS: u32 = 4;
+
+#[derive(Debug, Default)]
+struct Hub {
+    next_id: u32,
+    inboxes: BTreeMap<PeerId, VecDeque<TransportEvent>>,
+    // (lower, higher)
+    links: BTreeSet<(PeerId, PeerId)>,
+    // in steps, see LoopbackNetwork::step
+    latency: u64,
+    now: u64,
+    // (due step, send order) -> (from, to, event)
+    in_flight: BTreeMap<(u64, u64), (PeerId, PeerId, TransportEvent)>,
+    sent: u64,
+    faults: LinkFaults,
+    rng: Option<Rng>,
+}
+
+impl Hub {
+    fn push(&mut self, peer: PeerId, event: TransportEvent) {
+        if let Some(inbox) = self.inboxes.get_mut(&peer) {
+            inbox.push_back(event);
+        }
+    }
+
+    fn send(&mut self, from: PeerId, to: PeerId, event: TransportEvent) {
+        let unreliable = matches!(
+            event,
+            TransportEvent::Message {
+                channel: Channel::Unreliable,
+                ..
+            }
+        );
+        if !unreliable {
+            self.deliver(from, to, event, 0);
+            return;
+        }
+        let faults = self.faults;
+        let copies = match self.rng.as_mut() {
+            Some(rng) => faults.apply(rng),
+            None => vec![false],
+        };
+        for held in copies {
+            // a reordered message waits some extra steps
+            let held = if held {
+                1 + self
+                    .rng
+                    .as_mut()
+                    .map_or(0, |rng| rng.next_u32() % MAX_REORDER_STEPS)
+            } else {
+                0
+            };
+            self.deliver(from, to, event.clone(), held as u64);
+        }
+    }
+
+    fn deliver(&mut self, from: PeerId, to: PeerId, event: TransportEvent, held: u64) {
+        let delay = self.latency + held;
+        if delay == 0 {
+            self.push(to, event);
+            return;
+        }
+        self.sent += 1;
+        let key = (self.now + delay, self.sent);
+        self.in_flight.insert(key, (from, to, event));
+    }
+
+    fn unlink(&mut self, a: PeerId, b: PeerId) {
+        if self.links.remove(&link(a, b)) {
+            self.push(a, TransportEvent::Disconnected(b));
+            self.push(b, TransportEvent::Disconnected(a));
+        }
+    }
+}
+
+fn link(a: PeerId, b: PeerId) -> (PeerId, PeerId) {
+    (a.min(b), a.max(b))
+}
+
+// in memory network, no browser needed. messages arrive in order and without loss on both
+// channels, on the receiver's next recv or after a fixed number of steps with set_latency.
+// set_faults makes the unreliable channel live up to its name, the way ConditionedTransport does
+#[derive(Debug, Clone, Default)]
+pub struct LoopbackNetwork {
+    hub: SharedRefCell<Hub>,
+}
+
+impl LoopbackNetwork {
+    pub fn new() -> Self {
+        Self {
+            hub: shared_ref_cell(Hub::default()),
+        }
+    }
+
+    // ids count up from 1
+    pub fn add_peer(&self) -> LoopbackTransport {
+        let mut hub = self.hub.borrow_mut();
+        hub.next_id += 1;
+        let id = PeerId(hub.next_id);
+        hub.inboxes.insert(id, VecDeque::new());
+        LoopbackTransport {
+            id,
+            hub: self.hub.clone(),
+        }
+    }
+
+    pub fn connect(&self, a: PeerId, b: PeerId) -> Result<(), ErrorStr> {
+        let mut hub = self.hub.borrow_mut();
+        if a == b || !hub.inboxes.contains_key(&a) || !hub.inboxes.contains_key(&b) {
+            let error = format!("Can't connect {} to {}", a, b);
+            return Err(ErrorStr::new(error));
+        }
+        if hub.links.insert(link(a, b)) {
+            hub.push(a, TransportEvent::Connected(b));
+            hub.push(b, TransportEvent::Connected(a));
+        }
+        Ok(())
+    }
+
+    // connects every pair of the given peers
+    pub fn connect_all(&self, peers: &[PeerId]) -> Result<(), ErrorStr> {
+        for (i, a) in peers.iter().enumerate() {
+            for b in peers[i + 1..].iter() {
+                self.connect(*a, *b)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn disconnect(&self, a: PeerId, b: PeerId) {
+        self.hub.borrow_mut().unlink(a, b);
+    }
+
+    // messages sent from now on wait this many steps before they can be received
+    pub fn set_latency(&self, steps: u64) {
+        self.hub.borrow_mut().latency = steps;
+    }
+
+    // seeded, so a failing run can be repeated exactly
+    pub fn set_faults(&self, faults: LinkFaults, seed: u64) {
+        let mut hub = self.hub.borrow_mut();
+        hub.faults = faults;
+        hub.rng = Some(Rng::new(seed));
+    }
+
+    // moves the network clock on by one, e.g. once per simulated tick, delivering whatever is
+    // due. messages between peers that disconnected in the meantime are lost
+    pub fn step(&self) {
+        let mut hub = self.hub.borrow_mut();
+        hub.now += 1;
+        let now = hub.now;
+        while let Some(entry) = hub.in_flight.first_entry() {
+            if entry.key().0 > now {
+                break;
+            }
+            let (from, to, event) = entry.remove();
+            if hub.links.contains(&link(from, to)) {
+                hub.push(to, event);
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct LoopbackTransport {
+    id: PeerId,
+    hub: SharedRefCell<Hub>,
+}
+
+impl Drop for LoopbackTransport {
+    // like closing the tab, everyone connected sees a disconnect
+    fn drop(&mut self) {
+        let mut hub = self.hub.borrow_mut();
+        for peer in peers_of(&hub, self.id) {
+            hub.unlink(self.id, peer);
+        }
+        hub.inboxes.remove(&self.id);
+    }
+}
+
+fn peers_of(hub: &Hub, id: PeerId) -> Vec<PeerId> {
+    hub.links
+        .iter()
+        .filter_map(|(a, b)| match (*a == id, *b == id) {
+            (true, _) => Some(*b),
+            (_, true) => Some(*a),
+            _ => None,
+        })
+        .collect()
+}
+
+impl Transport for LoopbackTransport {
+    #[inline]
+    fn local_id(&self) -> PeerId {
+        self.id
+    }
+
+    fn peers(&self) -> Vec<PeerId> {
+        let mut peers = peers_of(&self.hub.borrow(), self.id);
+        peers.sort();
+        peers
+    }
+
+    fn is_connected(&self, peer: PeerId) -> bool {
+        self.hub.borrow().links.contains(&link(self.id, peer))
+    }
+
+    fn send(&mut self, peer: PeerId, channel: Channel, data: &[u8]) -> Result<(), ErrorStr> {
+        check_message_size(data)?;
+        let mut hub = self.hub.borrow_mut();
+        if !hub.links.contains(&link(self.id, peer)) {
+            return Err(ErrorStr::new(format!("Not connected to {}", peer)));
+        }
+        let event = TransportEvent::Message {
+            peer: self.id,
+            channel,
+            data: data.to_vec(),
+        };
+        hub.send(self.id, peer, event);
+        Ok(())
+    }
+
+    fn recv(&mut self) -> Option<TransportEvent> {
+        self.hub
+            .borrow_mut()
+            .inboxes
+            .get_mut(&self.id)
+            .and_then(|inbox| inbox.pop_front())
+    }
+
+    fn disconnect(&mut self, peer: PeerId) {
+        self.hub.borrow_mut().unlink(self.id, peer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::libs::net::MAX_MESSAGE_SIZE;
+
+    fn message(peer: PeerId, channel: Channel, data: &[u8]) -> Option<TransportEvent> {
+        Some(TransportEvent::Message {
+            peer,
+            channel,
+            data: data.to_vec(),
+        })
+    }
+
+    fn pair(network: &LoopbackNetwork) -> (LoopbackTransport, LoopbackTransport) {
+        let (mut a, mut b) = (network.add_peer(), network.add_peer());
+        network.connect(a.local_id(), b.local_id()).unwrap();
+        assert_eq!(a.recv(), Some(TransportEvent::Connected(b.local_id())));
+        assert_eq!(b.recv(), Some(TransportEvent::Connected(a.local_id())));
+        (a, b)
+    }
+
+    #[test]
+    fn connects_peers() {
+        let network = LoopbackNetwork::new();
+        let (a, b, c) = (network.add_peer(), network.add_peer(), network.add_peer());
+        assert_eq!(
+            (a.local_id(), b.local_id(), c.local_id()),
+            (PeerId(1), PeerId(2), PeerId(3))
+        );
+        assert!(network.connect(a.local_id(), a.local_id()).is_err());
+        assert!(network.connect(a.local_id(), PeerId(9)).is_err());
+        network
+            .connect_all(&[a.local_id(), b.local_id(), c.local_id()])
+            .unwrap();
+        // connecting twice is a no op
+        network.connect(b.local_id(), a.local_id()).unwrap();
+        assert_eq!(a.peers(), vec![b.local_id(), c.local_id()]);
+        assert_eq!(c.peers(), vec![a.local_id(), b.local_id()]);
+        assert!(b.is_connected(c.local_id()));
+        let mut a = a;
+        assert_eq!(a.recv(), Some(TransportEvent::Connected(b.local_id())));
+        assert_eq!(a.recv(), Some(TransportEvent::Connected(c.local_id())));
+        assert_eq!(a.recv(), None);
+    }
+
+    #[test]
+    fn sends_in_order_on_both_channels() {
+        let network = LoopbackNetwork::new();
+        let (mut a, mut b) = pair(&network);
+        let (a_id, b_id) = (a.local_id(), b.local_id());
+        a.send(b_id, Channel::Reliable, b"one").unwrap();
+        a.send(b_id, Channel::Unreliable, b"two").unwrap();
+        b.send(a_id, Channel::Reliable, b"three").unwrap();
+        assert_eq!(b.recv(), message(a_id, Channel::Reliable, b"one"));
+        assert_eq!(b.recv(), message(a_id, Channel::Unreliable, b"two"));
+        assert_eq!(b.recv(), None);
+        assert_eq!(a.recv(), message(b_id, Channel::Reliable, b"three"));
+    }
+
+    #[test]
+    fn rejects_unconnected_and_oversized_sends() {
+        let network = LoopbackNetwork::new();
+        let (mut a, b) = pair(&network);
+        let mut c = network.add_peer();
+        assert!(c.send(a.local_id(), Channel::Reliable, b"hi").is_err());
+        let big = vec![0; MAX_MESSAGE_SIZE + 1];
+        assert!(a.send(b.local_id(), Channel::Reliable, &big).is_err());
+    }
+
+    #[test]
+    fn disconnects_on_drop() {
+        let network = LoopbackNetwork::new();
+        let (mut a, b) = pair(&network);
+        let b_id = b.local_id();
+        drop(b);
+        assert_eq!(a.recv(), Some(TransportEvent::Disconnected(b_id)));
+        assert!(!a.is_connected(b_id));
+        assert!(a.peers().is_empty());
+        assert!(a.send(b_id, Channel::Reliable, b"gone").is_err());
+        // and can't be connected to again
+        assert!(network.connect(a.local_id(), b_id).is_err());
+    }
+
+    #[test]
+    fn disconnects_both_sides() {
+        let network = LoopbackNetwork::new();
+        let (mut a, mut b) = pair(&network);
+        a.disconnect(b.local_id());
+        assert_eq!(a.recv(), Some(TransportEvent::Disconnected(b.local_id())));
+        assert_eq!(b.recv(), Some(TransportEvent::Disconnected(a.local_id())));
+        // only once
+        network.disconnect(a.local_id(), b.local_id());
+        assert_eq!(a.recv(), None);
+    }
+
+    #[test]
+    fn delivers_after_latency_steps() {
+        let network = LoopbackNetwork::new();
+        let (mut a, mut b) = pair(&network);
+        let a_id = a.local_id();
+        network.set_latency(2);
+        a.send(b.local_id(), Channel::Reliable, b"first").unwrap();
+        network.step();
+        a.send(b.local_id(), Channel::Unreliable, b"second")
+            .unwrap();
+        assert_eq!(b.recv(), None);
+        network.step();
+        assert_eq!(b.recv(), message(a_id, Channel::Reliable, b"first"));
+        assert_eq!(b.recv(), None);
+        network.step();
+        assert_eq!(b.recv(), message(a_id, Channel::Unreliable, b"second"));
+        assert_eq!(b.recv(), None);
+    }
+
+    #[test]
+    fn drops_in_flight_messages_on_disconnect() {
+        let network = LoopbackNetwork::new();
+        let (mut a, mut b) = pair(&network);
+        network.set_latency(1);
+        a.send(b.local_id(), Channel::Reliable, b"late").unwrap();
+        network.disconnect(a.local_id(), b.local_id());
+        network.step();
+        assert_eq!(b.recv(), Some(TransportEvent::Disconnected(a.local_id())));
+        assert_eq!(b.recv(), None);
+    }
+}
@@ -0,0 +1,120 @@
+pub mod authority;
+pub mod clock;
+pub mod conditioned;
+pub mod lobby;
+pub mod loopback;
+pub mod protocol;
+pub mod reliable;
+pub mod rollback;
+pub mod signaling;
+pub mod topology;
+pub mod webrtc;
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::libs::types::errors::ErrorStr;
+
+// larger messages are rejected by send, browsers disagree above this. bigger payloads have to
+// be split by the caller
+pub const MAX_MESSAGE_SIZE: usize = 64 * 1024;
+
+// assigned by the signaling server, unique within a room. ordered so every peer agrees on
+// anything decided by lowest id
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct PeerId(pub u32);
+
+impl fmt::Display for PeerId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "peer {}", self.0)
+    }
+}
+
+// how a room keeps its peers' simulations in step. chosen by whoever creates the room
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncMode {
+    // every peer simulates everything, see rollback
+    #[default]
+    Rollback,
+    // the host simulates, the rest interpolate its snapshots, see authority
+    Authoritative,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Channel {
+    // arrives once, in send order. for chat, lobby and join/leave
+    Reliable,
+    // may be lost or reordered, never delayed by retransmits. for inputs and snapshots
+    Unreliable,
+}
+
+impl Channel {
+    pub const ALL: [Channel; 2] = [Channel::Reliable, Channel::Unreliable];
+
+    #[inline]
+    pub fn index(&self) -> usize {
+        match self {
+            Channel::Reliable => 0,
+            Channel::Unreliable => 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransportEvent {
+    // every channel to the peer is open
+    Connected(PeerId),
+    Disconnected(PeerId),
+    Message {
+        peer: PeerId,
+        channel: Channel,
+        data: Vec<u8>,
+    },
+    Error {
+        peer: Option<PeerId>,
+        error: String,
+    },
+}
+
+// a set of connections to other peers. polled rather than callback driven, so the game loop
+// can drain it at a fixed point each tick
+pub trait Transport {
+    fn local_id(&self) -> PeerId;
+
+    // currently connected peers, in id order
+    fn peers(&self) -> Vec<PeerId>;
+
+    fn is_connected(&self, peer: PeerId) -> bool {
+        self.peers().contains(&peer)
+    }
+
+    fn send(&mut self, peer: PeerId, channel: Channel, data: &[u8]) -> Result<(), ErrorStr>;
+
+    // stops at the first failure
+    fn broadcast(&mut self, channel: Channel, data: &[u8]) -> Result<(), ErrorStr> {
+        for peer in self.peers() {
+            self.send(peer, channel, data)?;
+        }
+        Ok(())
+    }
+
+    // the next pending event, None once drained
+    fn recv(&mut self) -> Option<TransportEvent>;
+
+    fn disconnect(&mut self, peer: PeerId);
+}
+
+pub(crate) fn check_message_size(data: &[u8]) -> Result<(), ErrorStr> {
+    if data.len() > MAX_MESSAGE_SIZE {
+        let error = format!(
+            "Message of {} bytes is over the {} byte limit",
+            data.len(),
+            MAX_MESSAGE_SIZE
+        );
+        return Err(ErrorStr::new(error));
+    }
+    Ok(())
+}
@@ -0,0 +1,283 @@
+use std::f32::consts::TAU;
+
+use glam::Vec2;
+
+use crate::libs::input::bindings::MAX_AXES;
+use crate::libs::input::snapshot::InputSnapshot;
+use crate::libs::net::protocol::EntityState;
+use crate::libs::net::protocol::delta::EntityDelta;
+use crate::libs::types::bytes::{ByteReader, ByteWriter};
+use crate::libs::types::entity::EntityId;
+use crate::libs::types::errors::ErrorStr;
+
+// positions and velocities travel as multiples of this, in world units
+pub const POSITION_STEP: f32 = 1.0 / 64.0;
+// rotations travel as a u16 fraction of a full turn
+const ANGLE_STEPS: f32 = 65536.0;
+
+// rounds to the wire precision. the sender applies this to its own state too when it has to
+// match the receiver exactly
+#[inline]
+pub fn quantize_position(value: Vec2) -> Vec2 {
+    (value / POSITION_STEP).round() * POSITION_STEP
+}
+
+#[inline]
+pub fn quantize_angle(radians: f32) -> f32 {
+    angle_to_u16(radians) as f32 / ANGLE_STEPS * TAU
+}
+
+fn angle_to_u16(radians: f32) -> u16 {
+    let turns = (radians / TAU).rem_euclid(1.0);
+    ((turns * ANGLE_STEPS).round() as u32 % ANGLE_STEPS as u32) as u16
+}
+
+pub fn write_vec2(writer: &mut ByteWriter, value: Vec2) {
+    let steps = (value / POSITION_STEP).round();
+    writer.write_zigzag(steps.x as i64);
+    writer.write_zigzag(steps.y as i64);
+}
+
+pub fn read_vec2(reader: &mut ByteReader) -> Result<Vec2, ErrorStr> {
+    let x = reader.read_zigzag()? as f32 * POSITION_STEP;
+    let y = reader.read_zigzag()? as f32 * POSITION_STEP;
+    Ok(Vec2::new(x, y))
+}
+
+#[inline]
+pub fn write_angle(writer: &mut ByteWriter, radians: f32) {
+    writer.write_u16(angle_to_u16(radians));
+}
+
+#[inline]
+pub fn read_angle(reader: &mut ByteReader) -> Result<f32, ErrorStr> {
+    Ok(reader.read_u16()? as f32 / ANGLE_STEPS * TAU)
+}
+
+// counts are capped by what's left, every element takes at least a byte
+pub fn read_count(reader: &mut ByteReader, max: usize) -> Result<usize, ErrorStr> {
+    let count = reader.read_varint()? as usize;
+    if count > max || count > reader.remaining() {
+        let error = format!("Count {} is over the limit of {}", count, max);
+        return Err(ErrorStr::new(error));
+    }
+    Ok(count)
+}
+
+// the ticks are implied by the message, each input is stored against the one before it:
+//   flags u8: same as previous, actions non zero, any axis non zero, pointer present
+//   then actions varint, u8 mask of non zero axes with each such axis zigzag, pointer zigzags
+pub fn write_inputs(writer: &mut ByteWriter, inputs: &[InputSnapshot]) {
+    writer.write_varint(inputs.len() as u64);
+    let mut previous: Option<&InputSnapshot> = None;
+    for input in inputs.iter() {
+        let same = previous.is_some_and(|previous| {
+            previous.actions == input.actions
+                && previous.axes == input.axes
+                && previous.pointer == input.pointer
+        });
+        previous = Some(input);
+        if same {
+            writer.write_flags(&[true]);
+            continue;
+        }
+        let axes = input.axes.iter().any(|axis| *axis != 0);
+        writer.write_flags(&[false, input.actions != 0, axes, input.pointer.is_some()]);
+        if input.actions != 0 {
+            writer.write_varint(input.actions);
+        }
+        if axes {
+            let mask = input
+                .axes
+                .iter()
+                .enumerate()
+                .fold(0u8, |mask, (index, axis)| {
+                    mask | (((*axis != 0) as u8) << index)
+                });
+            writer.write_u8(mask);
+            for axis in input.axes.iter().filter(|axis| **axis != 0) {
+                writer.write_zigzag(*axis as i64);
+            }
+        }
+        if let Some([x, y]) = input.pointer {
+            writer.write_zigzag(x as i64);
+            writer.write_zigzag(y as i64);
+        }
+    }
+}
+
+pub fn read_inputs(
+    reader: &mut ByteReader,
+    start_tick: u64,
+    max: usize,
+) -> Result<Vec<InputSnapshot>, ErrorStr> {
+    let count = read_count(reader, max)?;
+    let mut inputs: Vec<InputSnapshot> = Vec::with_capacity(count);
+    for index in 0..count {
+        let tick = if let Some(tick) = start_tick.checked_add(index as u64) {
+            tick
+        } else {
+            return Err(ErrorStr::new("Input tick out of range"));
+        };
+        let [same, actions, axes, pointer] = reader.read_flags::<4>()?;
+        if same {
+            let previous = if let Some(previous) = inputs.last() {
+                *previous
+            } else {
+                return Err(ErrorStr::new("First input repeats nothing"));
+            };
+            inputs.push(InputSnapshot { tick, ..previous });
+            continue;
+        }
+        let mut input = InputSnapshot {
+            tick,
+            ..InputSnapshot::default()
+        };
+        if actions {
+            input.actions = reader.read_varint()?;
+        }
+        if axes {
+            let mask = reader.read_u8()?;
+            for index in 0..MAX_AXES {
+                if mask & (1 << index) != 0 {
+                    input.axes[index] = read_i16(reader)?;
+                }
+            }
+        }
+        if pointer {
+            input.pointer = Some([read_i16(reader)?, read_i16(reader)?]);
+        }
+        inputs.push(input);
+    }
+    Ok(inputs)
+}
+
+// id varint, flags u8: moving, rotated, visible, owned, then position, velocity if moving,
+// rotation if rotated and the owner u8 if owned
+pub fn write_entity(writer: &mut ByteWriter, entity: &EntityState) {
+    writer.write_varint(entity.id.raw() as u64);
+    let moving = entity.velocity != Vec2::ZERO;
+    let rotated = angle_to_u16(entity.rotation) != 0;
+    let owned = entity.owner.is_some();
+    writer.write_flags(&[moving, rotated, entity.visible, owned]);
+    write_vec2(writer, entity.position);
+    if moving {
+        write_vec2(writer, entity.velocity);
+    }
+    if rotated {
+        write_angle(writer, entity.rotation);
+    }
+    if let Some(owner) = entity.owner {
+        writer.write_u8(owner);
+    }
+}
+
+pub fn read_entity(reader: &mut ByteReader) -> Result<EntityState, ErrorStr> {
+    let id = read_entity_id(reader)?;
+    let [moving, rotated, visible, owned] = reader.read_flags::<4>()?;
+    let position = read_vec2(reader)?;
+    let velocity = if moving {
+        read_vec2(reader)?
+    } else {
+        Vec2::ZERO
+    };
+    let rotation = if rotated { read_angle(reader)? } else { 0.0 };
+    let owner = if owned { Some(reader.read_u8()?) } else { None };
+    Ok(EntityState {
+        id,
+        position,
+        velocity,
+        rotation,
+        visible,
+        owner,
+    })
+}
+
+// id varint, flags u8: position, velocity, rotation, visibility changed, visible, owner changed,
+// owned, then the changed fields in that order, the owner u8 if changed and owned
+pub fn write_entity_delta(writer: &mut ByteWriter, delta: &EntityDelta) {
+    writer.write_varint(delta.id.raw() as u64);
+    let owner = delta.owner.flatten();
+    writer.write_flags(&[
+        delta.position.is_some(),
+        delta.velocity.is_some(),
+        delta.rotation.is_some(),
+        delta.visible.is_some(),
+        delta.visible.unwrap_or(false),
+        delta.owner.is_some(),
+        owner.is_some(),
+    ]);
+    if let Some(position) = delta.position {
+        write_vec2(writer, position);
+    }
+    if let Some(velocity) = delta.velocity {
+        write_vec2(writer, velocity);
+    }
+    if let Some(rotation) = delta.rotation {
+        write_angle(writer, rotation);
+    }
+    if let Some(owner) = owner {
+        writer.write_u8(owner);
+    }
+}
+
+pub fn read_entity_delta(reader: &mut ByteReader) -> Result<EntityDelta, ErrorStr> {
+    let id = read_entity_id(reader)?;
+    let [
+        position,
+        velocity,
+        rotation,
+        visibility,
+        visible,
+        owner_changed,
+        owned,
+    ] = reader.read_flags::<7>()?;
+    let position = if position {
+        Some(read_vec2(reader)?)
+    } else {
+        None
+    };
+    let velocity = if velocity {
+        Some(read_vec2(reader)?)
+    } else {
+        None
+    };
+    let rotation = if rotation {
+        Some(read_angle(reader)?)
+    } else {
+        None
+    };
+    let owner = match (owner_changed, owned) {
+        (true, true) => Some(Some(reader.read_u8()?)),
+        (true, false) => Some(None),
+        (false, false) => None,
+        (false, true) => return Err(ErrorStr::new("Owner set without a change")),
+    };
+    if visible && !visibility {
+        return Err(ErrorStr::new("Visibility set without a change"));
+    }
+    Ok(EntityDelta {
+        id,
+        position,
+        velocity,
+        rotation,
+        visible: visibility.then_some(visible),
+        owner,
+    })
+}
+
+pub fn read_entity_id(reader: &mut ByteReader) -> Result<EntityId, ErrorStr> {
+    let raw = reader.read_varint()?;
+    let id = u32::try_from(raw).ok().and_then(EntityId::new);
+    match id {
+        Some(id) => Ok(id),
+        None => Err(ErrorStr::new(format!("Bad entity id {}", raw))),
+    }
+}
+
+fn read_i16(reader: &mut ByteReader) -> Result<i16, ErrorStr> {
+    match i16::try_from(reader.read_zigzag()?) {
+        Ok(value) => Ok(value),
+        Err(_) => Err(ErrorStr::new("Input value out of range")),
+    }
+}
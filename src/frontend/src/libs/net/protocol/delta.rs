@@ -0,0 +1,180 @@
+use std::collections::BTreeMap;
+
+use glam::Vec2;
+
+use crate::libs::net::protocol::EntityState;
+use crate::libs::types::entity::EntityId;
+use crate::libs::types::errors::ErrorStr;
+
+// the fields of one entity that differ from the baseline, None for unchanged. an entity the
+// baseline doesn't have is diffed against a blank one, see EntityDelta::blank
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EntityDelta {
+    pub id: EntityId,
+    pub position: Option<Vec2>,
+    pub velocity: Option<Vec2>,
+    pub rotation: Option<f32>,
+    pub visible: Option<bool>,
+    // Some(None) when the entity lost its owner
+    pub owner: Option<Option<u8>>,
+}
+
+impl EntityDelta {
+    // what an entity missing from the baseline is diffed against
+    pub fn blank(id: EntityId) -> EntityState {
+        EntityState {
+            id,
+            position: Vec2::ZERO,
+            velocity: Vec2::ZERO,
+            rotation: 0.0,
+            visible: false,
+            owner: None,
+        }
+    }
+
+    // both sides should be quantized, or rounding noise counts as a change
+    pub fn between(from: &EntityState, to: &EntityState) -> Self {
+        Self {
+            id: to.id,
+            position: (from.position != to.position).then_some(to.position),
+            velocity: (from.velocity != to.velocity).then_some(to.velocity),
+            rotation: (from.rotation != to.rotation).then_some(to.rotation),
+            visible: (from.visible != to.visible).then_some(to.visible),
+            owner: (from.owner != to.owner).then_some(to.owner),
+        }
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.position.is_none()
+            && self.velocity.is_none()
+            && self.rotation.is_none()
+            && self.visible.is_none()
+            && self.owner.is_none()
+    }
+
+    pub fn apply(&self, base: &EntityState) -> EntityState {
+        EntityState {
+            id: self.id,
+            position: self.position.unwrap_or(base.position),
+            velocity: self.velocity.unwrap_or(base.velocity),
+            rotation: self.rotation.unwrap_or(base.rotation),
+            visible: self.visible.unwrap_or(base.visible),
+            owner: self.owner.unwrap_or(base.owner),
+        }
+    }
+}
+
+// the changed entities and the ids of removed ones, going from baseline to current
+pub fn diff(
+    baseline: &[EntityState],
+    current: &[EntityState],
+) -> (Vec<EntityDelta>, Vec<EntityId>) {
+    let before: BTreeMap<EntityId, &EntityState> =
+        baseline.iter().map(|entity| (entity.id, entity)).collect();
+    let changed = current
+        .iter()
+        .map(|entity| match before.get(&entity.id) {
+            Some(base) => EntityDelta::between(base, entity),
+            None => EntityDelta::between(&EntityDelta::blank(entity.id), entity),
+        })
+        .filter(|delta| !delta.is_empty() || !before.contains_key(&delta.id))
+        .collect();
+    let after: BTreeMap<EntityId, &EntityState> =
+        current.iter().map(|entity| (entity.id, entity)).collect();
+    let removed = baseline
+        .iter()
+        .map(|entity| entity.id)
+        .filter(|id| !after.contains_key(id))
+        .collect();
+    (changed, removed)
+}
+
+// the inverse of diff, in id order. removing an entity the baseline doesn't have means the two
+// sides disagree about the baseline
+pub fn apply(
+    baseline: &[EntityState],
+    changed: &[EntityDelta],
+    removed: &[EntityId],
+) -> Result<Vec<EntityState>, ErrorStr> {
+    let mut entities: BTreeMap<EntityId, EntityState> =
+        baseline.iter().map(|entity| (entity.id, *entity)).collect();
+    for id in removed.iter() {
+        if entities.remove(id).is_none() {
+            let error = format!("Delta removes {} which the baseline doesn't have", id);
+            return Err(ErrorStr::new(error));
+        }
+    }
+    for delta in changed.iter() {
+        let base = entities
+            .get(&delta.id)
+            .copied()
+            .unwrap_or_else(|| EntityDelta::blank(delta.id));
+        entities.insert(delta.id, delta.apply(&base));
+    }
+    Ok(entities.into_values().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entity(raw: u32, x: f32) -> EntityState {
+        EntityState {
+            id: EntityId::new(raw).unwrap(),
+            position: Vec2::new(x, 0.0),
+            velocity: Vec2::ZERO,
+            rotation: 0.0,
+            visible: true,
+            owner: None,
+        }
+    }
+
+    #[test]
+    fn apply_undoes_diff() {
+        let baseline = vec![entity(1, 0.0), entity(2, 1.0), entity(3, 2.0)];
+        let mut moved = entity(2, 4.0);
+        moved.owner = Some(1);
+        moved.visible = false;
+        let current = vec![entity(1, 0.0), moved, entity(4, 0.0)];
+        let (changed, removed) = diff(&baseline, &current);
+        // the unchanged entity is left out, the new one is sent even where it matches blank
+        let ids: Vec<u32> = changed.iter().map(|delta| delta.id.raw()).collect();
+        assert_eq!(ids, vec![2, 4]);
+        assert_eq!(changed[0].position, Some(Vec2::new(4.0, 0.0)));
+        assert_eq!(changed[0].velocity, None);
+        assert_eq!(changed[0].owner, Some(Some(1)));
+        assert_eq!(changed[0].visible, Some(false));
+        assert_eq!(removed, vec![EntityId::new(3).unwrap()]);
+        assert_eq!(apply(&baseline, &changed, &removed).unwrap(), current);
+    }
+
+    #[test]
+    fn diffs_nothing_between_equal_snapshots() {
+        let snapshot = vec![entity(1, 0.0), entity(2, 1.0)];
+        let (changed, removed) = diff(&snapshot, &snapshot);
+        assert!(changed.is_empty() && removed.is_empty());
+        assert_eq!(apply(&snapshot, &changed, &removed).unwrap(), snapshot);
+        let (changed, removed) = diff(&snapshot, &[]);
+        assert!(changed.is_empty());
+        assert_eq!(removed.len(), 2);
+        assert!(apply(&snapshot, &changed, &removed).unwrap().is_empty());
+    }
+
+    #[test]
+    fn rejects_removing_what_the_baseline_lacks() {
+        let baseline = vec![entity(1, 0.0)];
+        let removed = vec![EntityId::new(2).unwrap()];
+        assert!(apply(&baseline, &[], &removed).is_err());
+    }
+
+    #[test]
+    fn clears_the_owner() {
+        let mut owned = entity(1, 0.0);
+        owned.owner = Some(3);
+        let delta = EntityDelta::between(&owned, &entity(1, 0.0));
+        assert_eq!(delta.owner, Some(None));
+        assert!(!delta.is_empty());
+        assert_eq!(delta.apply(&owned).owner, None);
+    }
+}
@@ -0,0 +1,219 @@
+use std::collections::BTreeMap;
+
+use crate::libs::net::protocol::{Message, PROTOCOL_VERSION};
+use crate::libs::net::{Channel, PeerId, Transport, TransportEvent};
+use crate::libs::types::errors::ErrorStr;
+use crate::{log_debug, log_info, log_warn};
+
+const LOG_TARGET: &str = "net::protocol";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PeerEvent {
+    // said hello with our protocol version, game messages flow from here on
+    Accepted {
+        peer: PeerId,
+        name: String,
+    },
+    // only for peers that had been accepted
+    Disconnected(PeerId),
+    Message {
+        peer: PeerId,
+        channel: Channel,
+        message: Message,
+    },
+    Error {
+        peer: Option<PeerId>,
+        error: String,
+    },
+}
+
+// says hello on every new connection and turns away peers on another protocol version, so
+// nothing past the handshake ever has to decode a message it doesn't understand
+#[derive(Debug, Clone)]
+pub struct Handshake {
+    name: String,
+    accepted: BTreeMap<PeerId, String>,
+}
+
+impl Handshake {
+    pub fn new<S>(name: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self {
+            name: name.into(),
+            accepted: BTreeMap::new(),
+        }
+    }
+
+    #[inline]
+    pub fn is_accepted(&self, peer: PeerId) -> bool {
+        self.accepted.contains_key(&peer)
+    }
+
+    // the name the peer said hello with
+    #[inline]
+    pub fn name(&self, peer: PeerId) -> Option<&str> {
+        self.accepted.get(&peer).map(|name| name.as_str())
+    }
+
+    // in id order
+    pub fn accepted(&self) -> impl Iterator<Item = PeerId> + '_ {
+        self.accepted.keys().copied()
+    }
+
+    // drains the transport
+    pub fn poll<T>(&mut self, transport: &mut T) -> Vec<PeerEvent>
+    where
+        T: Transport + ?Sized,
+    {
+        let mut events = Vec::new();
+        while let Some(event) = transport.recv() {
+            match event {
+                TransportEvent::Connected(peer) => {
+                    let hello = Message::hello(self.name.clone());
+                    if let Err(error) = send(transport, peer, Channel::Reliable, &hello) {
+                        events.push(PeerEvent::Error {
+                            peer: Some(peer),
+                            error: error.to_string(),
+                        });
+                    }
+                }
+                TransportEvent::Disconnected(peer) => {
+                    if self.accepted.remove(&peer).is_some() {
+                        events.push(PeerEvent::Disconnected(peer));
+                    }
+                }
+                TransportEvent::Message {
+                    peer,
+                    channel,
+                    data,
+                } => self.receive(transport, peer, channel, &data, &mut events),
+                TransportEvent::Error { peer, error } => {
+                    events.push(PeerEvent::Error { peer, error });
+                }
+            }
+        }
+        events
+    }
+
+    fn receive<T>(
+        &mut self,
+        transport: &mut T,
+        peer: PeerId,
+        channel: Channel,
+        data: &[u8],
+        events: &mut Vec<PeerEvent>,
+    ) where
+        T: Transport + ?Sized,
+    {
+        let message = match Message::decode(data) {
+            Ok(message) => message,
+            Err(error) => {
+                let error = format!("Undecodable message from {}: {}", peer, error);
+                events.push(PeerEvent::Error {
+                    peer: Some(peer),
+                    error,
+                });
+                return;
+            }
+        };
+        match message {
+            Message::Hello { version, name } => {
+                if version != PROTOCOL_VERSION {
+                    let reason = format!(
+                        "Protocol version {} is not supported, expected {}",
+                        version, PROTOCOL_VERSION
+                    );
+                    let reject = Message::Reject {
+                        version: PROTOCOL_VERSION,
+                        reason: reason.clone(),
+                    };
+                    let _ = send(transport, peer, Channel::Reliable, &reject);
+                    transport.disconnect(peer);
+                    log_warn!(LOG_TARGET, "Rejected {}: {}", peer, reason);
+                    events.push(PeerEvent::Error {
+                        peer: Some(peer),
+                        error: reason,
+                    });
+                    return;
+                }
+                log_info!(LOG_TARGET, "{} ({}) accepted", peer, name);
+                self.accepted.insert(peer, name.clone());
+                events.push(PeerEvent::Accepted { peer, name });
+            }
+            Message::Reject { version, reason } => {
+                self.accepted.remove(&peer);
+                transport.disconnect(peer);
+                let error = format!("{} on version {} rejected us: {}", peer, version, reason);
+                events.push(PeerEvent::Error {
+                    peer: Some(peer),
+                    error,
+                });
+            }
+            message => {
+                if !self.is_accepted(peer) {
+                    // unreliable messages can overtake the hello
+                    log_debug!(
+                        LOG_TARGET,
+                        "Dropped {:?} from {} before its hello",
+                        message.kind(),
+                        peer
+                    );
+                    return;
+                }
+                events.push(PeerEvent::Message {
+                    peer,
+                    channel,
+                    message,
+                });
+            }
+        }
+    }
+
+    pub fn send<T>(
+        &self,
+        transport: &mut T,
+        peer: PeerId,
+        channel: Channel,
+        message: &Message,
+    ) -> Result<(), ErrorStr>
+    where
+        T: Transport + ?Sized,
+    {
+        if !self.is_accepted(peer) {
+            let error = format!("{} hasn't completed the handshake", peer);
+            return Err(ErrorStr::new(error));
+        }
+        send(transport, peer, channel, message)
+    }
+
+    // to every accepted peer, encoded once. stops at the first failure
+    pub fn broadcast<T>(
+        &self,
+        transport: &mut T,
+        channel: Channel,
+        message: &Message,
+    ) -> Result<(), ErrorStr>
+    where
+        T: Transport + ?Sized,
+    {
+        let data = message.encode()?;
+        for peer in self.accepted.keys() {
+            transport.send(*peer, channel, &data)?;
+        }
+        Ok(())
+    }
+}
+
+fn send<T>(
+    transport: &mut T,
+    peer: PeerId,
+    channel: Channel,
+    message: &Message,
+) -> Result<(), ErrorStr>
+where
+    T: Transport + ?Sized,
+{
+    transport.send(peer, channel, &message.encode()?)
+}
@@ -0,0 +1,1077 @@
+pub mod codec;
+pub mod delta;
+pub mod handshake;
+
+use glam::Vec2;
+
+use crate::libs::input::snapshot::InputSnapshot;
+use crate::libs::net::protocol::delta::EntityDelta;
+use crate::libs::net::topology::Topology;
+use crate::libs::net::{MAX_MESSAGE_SIZE, PeerId, SyncMode};
+use crate::libs::types::bytes::{ByteReader, ByteWriter};
+use crate::libs::types::entity::EntityId;
+use crate::libs::types::errors::ErrorStr;
+
+// bumped on any change to the layout of a message other than Hello and Reject, whose layout is
+// fixed so that peers on different versions can still tell each other apart
+pub const PROTOCOL_VERSION: u16 = 5;
+// inputs per message, enough redundancy to ride out a burst of lost packets
+pub const MAX_INPUTS: usize = 64;
+pub const MAX_CHAT_LEN: usize = 280;
+pub const MAX_NAME_LEN: usize = 32;
+pub const MAX_REASON_LEN: usize = 128;
+// slots are u8, but nobody needs a room this big
+pub const MAX_PLAYERS: u8 = 16;
+
+// one message per transport packet, the first byte says which
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(u8)]
+pub enum MessageKind {
+    Hello = 0,
+    Reject = 1,
+    Join = 2,
+    Leave = 3,
+    Chat = 4,
+    Ping = 5,
+    Pong = 6,
+    Inputs = 7,
+    Snapshot = 8,
+    Checksum = 9,
+    Delta = 10,
+    Room = 11,
+    Ready = 12,
+    InputAcks = 13,
+}
+
+impl MessageKind {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        let kind = match value {
+            0 => MessageKind::Hello,
+            1 => MessageKind::Reject,
+            2 => MessageKind::Join,
+            3 => MessageKind::Leave,
+            4 => MessageKind::Chat,
+            5 => MessageKind::Ping,
+            6 => MessageKind::Pong,
+            7 => MessageKind::Inputs,
+            8 => MessageKind::Snapshot,
+            9 => MessageKind::Checksum,
+            10 => MessageKind::Delta,
+            11 => MessageKind::Room,
+            12 => MessageKind::Ready,
+            13 => MessageKind::InputAcks,
+            _ => return None,
+        };
+        Some(kind)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum LeaveReason {
+    Quit = 0,
+    TimedOut = 1,
+    Kicked = 2,
+    Desynced = 3,
+}
+
+impl LeaveReason {
+    fn from_u8(value: u8) -> Result<Self, ErrorStr> {
+        let reason = match value {
+            0 => LeaveReason::Quit,
+            1 => LeaveReason::TimedOut,
+            2 => LeaveReason::Kicked,
+            3 => LeaveReason::Desynced,
+            _ => return Err(ErrorStr::new(format!("Unknown leave reason {}", value))),
+        };
+        Ok(reason)
+    }
+}
+
+// what a snapshot says about one entity, at wire precision
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EntityState {
+    pub id: EntityId,
+    pub position: Vec2,
+    pub velocity: Vec2,
+    // radians
+    pub rotation: f32,
+    pub visible: bool,
+    // the player slot controlling it, if any
+    pub owner: Option<u8>,
+}
+
+impl EntityState {
+    // rounded the way it will arrive
+    pub fn quantized(&self) -> Self {
+        Self {
+            position: codec::quantize_position(self.position),
+            velocity: codec::quantize_position(self.velocity),
+            rotation: codec::quantize_angle(self.rotation),
+            ..*self
+        }
+    }
+}
+
+// what the host decided for the room, chosen before the match starts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoomSettings {
+    pub map: String,
+    pub mode: SyncMode,
+    pub max_players: u8,
+}
+
+impl Default for RoomSettings {
+    fn default() -> Self {
+        Self {
+            map: String::new(),
+            mode: SyncMode::default(),
+            max_players: MAX_PLAYERS,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoomMember {
+    pub peer: PeerId,
+    pub player: u8,
+    pub name: String,
+    pub ready: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message {
+    // sent first on every connection
+    Hello {
+        version: u16,
+        name: String,
+    },
+    // the other side's Hello was unacceptable, the connection is closed after this
+    Reject {
+        version: u16,
+        reason: String,
+    },
+    // a player took a slot
+    Join {
+        player: u8,
+        name: String,
+    },
+    Leave {
+        player: u8,
+        reason: LeaveReason,
+    },
+    Chat {
+        player: u8,
+        text: String,
+    },
+    // times are milliseconds on the sender's clock, wrapping
+    Ping {
+        sequence: u32,
+        sent_ms: u32,
+    },
+    Pong {
+        sequence: u32,
+        // the ping's sent_ms, echoed
+        sent_ms: u32,
+        received_ms: u32,
+        // the sender's simulation tick when it answered, for lining up timelines
+        tick: u64,
+    },
+    // a player's inputs for consecutive ticks from start_tick, everything the receiver hasn't
+    // acknowledged yet so a lost packet is covered by the next one
+    Inputs {
+        player: u8,
+        start_tick: u64,
+        // the newest tick of the receiver's own inputs the sender has without gaps
+        ack_tick: Option<u64>,
+        inputs: Vec<InputSnapshot>,
+    },
+    // the host's world. last_input is the newest of the receiver's inputs it includes
+    Snapshot {
+        tick: u64,
+        last_input: Option<u64>,
+        entities: Vec<EntityState>,
+    },
+    // state hash at the start of a tick every player's input before it is known for
+    Checksum {
+        tick: u64,
+        hash: u64,
+    },
+    // a Snapshot told as the changes since baseline, an earlier snapshot the receiver
+    // acknowledged. see protocol::delta
+    Delta {
+        tick: u64,
+        baseline: u64,
+        last_input: Option<u64>,
+        changed: Vec<EntityDelta>,
+        removed: Vec<EntityId>,
+    },
+    // the whole lobby as the host sees it, sent whenever any of it changes. started is the
+    // match's first tick once it's running, with the topology the host picked for it
+    Room {
+        settings: RoomSettings,
+        members: Vec<RoomMember>,
+        started: Option<u64>,
+        topology: Topology,
+    },
+    // a player's ready state, to the host
+    Ready {
+        player: u8,
+        ready: bool,
+    },
+    // the newest tick of each player's inputs the sender has without gaps, to a hub relaying
+    // them. see topology
+    InputAcks {
+        acks: Vec<(u8, u64)>,
+    },
+}
+
+impl Message {
+    pub fn kind(&self) -> MessageKind {
+        match self {
+            Message::Hello { .. } => MessageKind::Hello,
+            Message::Reject { .. } => MessageKind::Reject,
+            Message::Join { .. } => MessageKind::Join,
+            Message::Leave { .. } => MessageKind::Leave,
+            Message::Chat { .. } => MessageKind::Chat,
+            Message::Ping { .. } => MessageKind::Ping,
+            Message::Pong { .. } => MessageKind::Pong,
+            Message::Inputs { .. } => MessageKind::Inputs,
+            Message::Snapshot { .. } => MessageKind::Snapshot,
+            Message::Checksum { .. } => MessageKind::Checksum,
+            Message::Delta { .. } => MessageKind::Delta,
+            Message::Room { .. } => MessageKind::Room,
+            Message::Ready { .. } => MessageKind::Ready,
+            Message::InputAcks { .. } => MessageKind::InputAcks,
+        }
+    }
+
+    pub fn hello<S>(name: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Message::Hello {
+            version: PROTOCOL_VERSION,
+            name: name.into(),
+        }
+    }
+
+    // fails rather than sending something the other side would reject
+    pub fn encode(&self) -> Result<Vec<u8>, ErrorStr> {
+        let mut writer = ByteWriter::with_capacity(16);
+        writer.write_u8(self.kind() as u8);
+        match self {
+            Message::Hello { version, name } => {
+                writer.write_u16(*version);
+                write_text(&mut writer, name, MAX_NAME_LEN)?;
+            }
+            Message::Reject { version, reason } => {
+                writer.write_u16(*version);
+                write_text(&mut writer, reason, MAX_REASON_LEN)?;
+            }
+            Message::Join { player, name } => {
+                writer.write_u8(*player);
+                write_text(&mut writer, name, MAX_NAME_LEN)?;
+            }
+            Message::Leave { player, reason } => {
+                writer.write_u8(*player);
+                writer.write_u8(*reason as u8);
+            }
+            Message::Chat { player, text } => {
+                writer.write_u8(*player);
+                write_text(&mut writer, text, MAX_CHAT_LEN)?;
+            }
+            Message::Ping { sequence, sent_ms } => {
+                writer.write_varint(*sequence as u64);
+                writer.write_u32(*sent_ms);
+            }
+            Message::Pong {
+                sequence,
+                sent_ms,
+                received_ms,
+                tick,
+            } => {
+                writer.write_varint(*sequence as u64);
+                writer.write_u32(*sent_ms);
+                writer.write_u32(*received_ms);
+                writer.write_varint(*tick);
+            }
+            Message::Inputs {
+                player,
+                start_tick,
+                ack_tick,
+                inputs,
+            } => {
+                if inputs.len() > MAX_INPUTS {
+                    let error = format!("{} inputs is over the {} limit", inputs.len(), MAX_INPUTS);
+                    return Err(ErrorStr::new(error));
+                }
+                writer.write_u8(*player);
+                writer.write_varint(*start_tick);
+                // 0 for none, otherwise the tick plus one
+                writer.write_varint(ack_tick.map_or(0, |tick| tick + 1));
+                codec::write_inputs(&mut writer, inputs);
+            }
+            Message::Snapshot {
+                tick,
+                last_input,
+                entities,
+            } => {
+                writer.write_varint(*tick);
+                writer.write_varint(last_input.map_or(0, |tick| tick + 1));
+                writer.write_varint(entities.len() as u64);
+                for entity in entities.iter() {
+                    codec::write_entity(&mut writer, entity);
+                }
+            }
+            Message::Checksum { tick, hash } => {
+                writer.write_varint(*tick);
+                writer.write_u64(*hash);
+            }
+            Message::Delta {
+                tick,
+                baseline,
+                last_input,
+                changed,
+                removed,
+            } => {
+                if baseline >= tick {
+                    let error = format!("Baseline {} isn't before tick {}", baseline, tick);
+                    return Err(ErrorStr::new(error));
+                }
+                writer.write_varint(*tick);
+                // as a distance back, usually a handful of ticks
+                writer.write_varint(tick - baseline);
+                writer.write_varint(last_input.map_or(0, |tick| tick + 1));
+                writer.write_varint(changed.len() as u64);
+                for delta in changed.iter() {
+                    codec::write_entity_delta(&mut writer, delta);
+                }
+                writer.write_varint(removed.len() as u64);
+                for id in removed.iter() {
+                    writer.write_varint(id.raw() as u64);
+                }
+            }
+            Message::Room {
+                settings,
+                members,
+                started,
+                topology,
+            } => {
+                if members.len() > MAX_PLAYERS as usize {
+                    let error = format!(
+                        "{} members is over the {} limit",
+                        members.len(),
+                        MAX_PLAYERS
+                    );
+                    return Err(ErrorStr::new(error));
+                }
+                write_text(&mut writer, &settings.map, MAX_NAME_LEN)?;
+                writer.write_u8(sync_mode_to_u8(settings.mode));
+                writer.write_u8(settings.max_players);
+                writer.write_varint(started.map_or(0, |tick| tick + 1));
+                // 0 for a mesh, otherwise the hub's id plus one
+                writer.write_varint(topology.hub().map_or(0, |hub| hub.0 as u64 + 1));
+                writer.write_varint(members.len() as u64);
+                for member in members.iter() {
+                    writer.write_varint(member.peer.0 as u64);
+                    writer.write_u8(member.player);
+                    writer.write_flags(&[member.ready]);
+                    write_text(&mut writer, &member.name, MAX_NAME_LEN)?;
+                }
+            }
+            Message::Ready { player, ready } => {
+                writer.write_u8(*player);
+                writer.write_flags(&[*ready]);
+            }
+            Message::InputAcks { acks } => {
+                if acks.len() > MAX_PLAYERS as usize {
+                    let error = format!("{} acks is over the {} limit", acks.len(), MAX_PLAYERS);
+                    return Err(ErrorStr::new(error));
+                }
+                writer.write_varint(acks.len() as u64);
+                for (player, tick) in acks.iter() {
+                    writer.write_u8(*player);
+                    writer.write_varint(*tick);
+                }
+            }
+        }
+        if writer.len() > MAX_MESSAGE_SIZE {
+            let error = format!(
+                "{:?} message of {} bytes is too big",
+                self.kind(),
+                writer.len()
+            );
+            return Err(ErrorStr::new(error));
+        }
+        Ok(writer.into_bytes())
+    }
+
+    // trailing bytes are an error, they'd mean the peers disagree about the layout
+    pub fn decode(bytes: &[u8]) -> Result<Self, ErrorStr> {
+        let mut reader = ByteReader::new(bytes);
+        let kind = reader.read_u8()?;
+        let kind = if let Some(kind) = MessageKind::from_u8(kind) {
+            kind
+        } else {
+            return Err(ErrorStr::new(format!("Unknown message kind {}", kind)));
+        };
+        let message = match kind {
+            MessageKind::Hello => Message::Hello {
+                version: reader.read_u16()?,
+                name: read_text(&mut reader, MAX_NAME_LEN)?,
+            },
+            MessageKind::Reject => Message::Reject {
+                version: reader.read_u16()?,
+                reason: read_text(&mut reader, MAX_REASON_LEN)?,
+            },
+            MessageKind::Join => Message::Join {
+                player: reader.read_u8()?,
+                name: read_text(&mut reader, MAX_NAME_LEN)?,
+            },
+            MessageKind::Leave => Message::Leave {
+                player: reader.read_u8()?,
+                reason: LeaveReason::from_u8(reader.read_u8()?)?,
+            },
+            MessageKind::Chat => Message::Chat {
+                player: reader.read_u8()?,
+                text: read_text(&mut reader, MAX_CHAT_LEN)?,
+            },
+            MessageKind::Ping => Message::Ping {
+                sequence: reader.read_varint_as::<u32>()?,
+                sent_ms: reader.read_u32()?,
+            },
+            MessageKind::Pong => Message::Pong {
+                sequence: reader.read_varint_as::<u32>()?,
+                sent_ms: reader.read_u32()?,
+                received_ms: reader.read_u32()?,
+                tick: reader.read_varint()?,
+            },
+            MessageKind::Inputs => {
+                let player = reader.read_u8()?;
+                let start_tick = reader.read_varint()?;
+                let ack_tick = reader.read_varint()?.checked_sub(1);
+                let inputs = codec::read_inputs(&mut reader, start_tick, MAX_INPUTS)?;
+                Message::Inputs {
+                    player,
+                    start_tick,
+                    ack_tick,
+                    inputs,
+                }
+            }
+            MessageKind::Snapshot => {
+                let tick = reader.read_varint()?;
+                let last_input = reader.read_varint()?.checked_sub(1);
+                let count = codec::read_count(&mut reader, usize::MAX)?;
+                let mut entities = Vec::with_capacity(count);
+                for _ in 0..count {
+                    entities.push(codec::read_entity(&mut reader)?);
+                }
+                Message::Snapshot {
+                    tick,
+                    last_input,
+                    entities,
+                }
+            }
+            MessageKind::Checksum => Message::Checksum {
+                tick: reader.read_varint()?,
+                hash: reader.read_u64()?,
+            },
+            MessageKind::Delta => {
+                let tick = reader.read_varint()?;
+                let baseline = match tick.checked_sub(reader.read_varint()?) {
+                    Some(baseline) if baseline < tick => baseline,
+                    _ => return Err(ErrorStr::new("Bad delta baseline")),
+                };
+                let last_input = reader.read_varint()?.checked_sub(1);
+                let count = codec::read_count(&mut reader, usize::MAX)?;
+                let mut changed = Vec::with_capacity(count);
+                for _ in 0..count {
+                    changed.push(codec::read_entity_delta(&mut reader)?);
+                }
+                let count = codec::read_count(&mut reader, usize::MAX)?;
+                let mut removed = Vec::with_capacity(count);
+                for _ in 0..count {
+                    removed.push(codec::read_entity_id(&mut reader)?);
+                }
+                Message::Delta {
+                    tick,
+                    baseline,
+                    last_input,
+                    changed,
+                    removed,
+                }
+            }
+            MessageKind::Room => {
+                let settings = RoomSettings {
+                    map: read_text(&mut reader, MAX_NAME_LEN)?,
+                    mode: sync_mode_from_u8(reader.read_u8()?)?,
+                    max_players: reader.read_u8()?,
+                };
+                let started = reader.read_varint()?.checked_sub(1);
+                let topology = match reader.read_varint_as::<u32>()?.checked_sub(1) {
+                    Some(hub) => Topology::Star { hub: PeerId(hub) },
+                    None => Topology::Mesh,
+                };
+                let count = codec::read_count(&mut reader, MAX_PLAYERS as usize)?;
+                let mut members = Vec::with_capacity(count);
+                for _ in 0..count {
+                    let peer = PeerId(reader.read_varint_as::<u32>()?);
+                    let player = reader.read_u8()?;
+                    let [ready] = reader.read_flags::<1>()?;
+                    members.push(RoomMember {
+                        peer,
+                        player,
+                        name: read_text(&mut reader, MAX_NAME_LEN)?,
+                        ready,
+                    });
+                }
+                Message::Room {
+                    settings,
+                    members,
+                    started,
+                    topology,
+                }
+            }
+            MessageKind::Ready => {
+                let player = reader.read_u8()?;
+                let [ready] = reader.read_flags::<1>()?;
+                Message::Ready { player, ready }
+            }
+            MessageKind::InputAcks => {
+                let count = codec::read_count(&mut reader, MAX_PLAYERS as usize)?;
+                let mut acks = Vec::with_capacity(count);
+                for _ in 0..count {
+                    acks.push((reader.read_u8()?, reader.read_varint()?));
+                }
+                Message::InputAcks { acks }
+            }
+        };
+        if !reader.is_empty() {
+            let error = format!(
+                "{:?} message has {} trailing bytes",
+                kind,
+                reader.remaining()
+            );
+            return Err(ErrorStr::new(error));
+        }
+        Ok(message)
+    }
+}
+
+// limits are in characters, names and chat are shown to other players
+fn write_text(writer: &mut ByteWriter, text: &str, max: usize) -> Result<(), ErrorStr> {
+    if text.chars().count() > max {
+        let error = format!("Text is over the {} character limit", max);
+        return Err(ErrorStr::new(error));
+    }
+    writer.write_str(text);
+    Ok(())
+}
+
+fn read_text(reader: &mut ByteReader, max: usize) -> Result<String, ErrorStr> {
+    let text = reader.read_str()?;
+    if text.chars().count() > max {
+        let error = format!("Text is over the {} character limit", max);
+        return Err(ErrorStr::new(error));
+    }
+    Ok(text.to_string())
+}
+
+fn sync_mode_to_u8(mode: SyncMode) -> u8 {
+    match mode {
+        SyncMode::Rollback => 0,
+        SyncMode::Authoritative => 1,
+    }
+}
+
+fn sync_mode_from_u8(value: u8) -> Result<SyncMode, ErrorStr> {
+    match value {
+        0 => Ok(SyncMode::Rollback),
+        1 => Ok(SyncMode::Authoritative),
+        _ => Err(ErrorStr::new(format!("Unknown sync mode {}", value))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::libs::input::bindings::MAX_AXES;
+    use crate::libs::types::rng::Rng;
+
+    // one of each kind, in kind order, with values that survive quantizing
+    fn samples() -> Vec<Message> {
+        let id = |raw| EntityId::new(raw).unwrap();
+        let mut axes = [0; MAX_AXES];
+        axes[0] = -32767;
+        axes[2] = 12;
+        let input = InputSnapshot {
+            tick: 40,
+            actions: 0b101,
+            axes,
+            pointer: Some([320, -4]),
+        };
+        let entity = EntityState {
+            id: id(3),
+            position: Vec2::new(1.5, -2.25),
+            velocity: Vec2::new(0.0, 0.5),
+            rotation: codec::quantize_angle(1.0),
+            visible: true,
+            owner: Some(1),
+        };
+        vec![
+            Message::hello("Ada"),
+            Message::Reject {
+                version: PROTOCOL_VERSION,
+                reason: "Room is full".to_string(),
+            },
+            Message::Join {
+                player: 2,
+                name: "Grace".to_string(),
+            },
+            Message::Leave {
+                player: 2,
+                reason: LeaveReason::TimedOut,
+            },
+            Message::Chat {
+                player: 1,
+                text: "gg \u{1f600}".to_string(),
+            },
+            Message::Ping {
+                sequence: 300,
+                sent_ms: 0xdead_beef,
+            },
+            Message::Pong {
+                sequence: 300,
+                sent_ms: 0xdead_beef,
+                received_ms: 12,
+                tick: 1000,
+            },
+            Message::Inputs {
+                player: 1,
+                start_tick: 40,
+                ack_tick: Some(38),
+                inputs: vec![
+                    input,
+                    InputSnapshot { tick: 41, ..input },
+                    InputSnapshot {
+                        tick: 42,
+                        ..InputSnapshot::default()
+                    },
+                ],
+            },
+            Message::Snapshot {
+                tick: 500,
+                last_input: None,
+                entities: vec![
+                    entity,
+                    EntityState {
+                        id: id(4),
+                        velocity: Vec2::ZERO,
+                        rotation: 0.0,
+                        owner: None,
+                        ..entity
+                    },
+                ],
+            },
+            Message::Checksum {
+                tick: 500,
+                hash: 0x0123_4567_89ab_cdef,
+            },
+            Message::Delta {
+                tick: 505,
+                baseline: 500,
+                last_input: Some(503),
+                changed: vec![
+                    EntityDelta::between(&EntityDelta::blank(id(5)), &entity),
+                    EntityDelta {
+                        id: id(3),
+                        position: Some(Vec2::new(2.0, -2.25)),
+                        velocity: None,
+                        rotation: None,
+                        visible: Some(false),
+                        owner: Some(None),
+                    },
+                ],
+                removed: vec![id(4)],
+            },
+            Message::Room {
+                settings: RoomSettings {
+                    map: "arena".to_string(),
+                    mode: SyncMode::Authoritative,
+                    max_players: 4,
+                },
+                members: vec![
+                    RoomMember {
+                        peer: PeerId(1),
+                        player: 0,
+                        name: "Ada".to_string(),
+                        ready: true,
+                    },
+                    RoomMember {
+                        peer: PeerId(7),
+                        player: 1,
+                        name: "Grace".to_string(),
+                        ready: false,
+                    },
+                ],
+                started: Some(0),
+                topology: Topology::Star { hub: PeerId(1) },
+            },
+            Message::Ready {
+                player: 1,
+                ready: true,
+            },
+            Message::InputAcks {
+                acks: vec![(0, 41), (1, 0)],
+            },
+        ]
+    }
+
+    // the layout at PROTOCOL_VERSION, in kind order. a change here needs a version bump
+    const GOLDEN_VERSION: u16 = 5;
+    const GOLDEN: [&[u8]; 14] = [
+        &[0x00, 0x05, 0x00, 0x03, 0x41, 0x64, 0x61],
+        &[
+            0x01, 0x05, 0x00, 0x0c, 0x52, 0x6f, 0x6f, 0x6d, 0x20, 0x69, 0x73, 0x20, 0x66, 0x75,
+            0x6c, 0x6c,
+        ],
+        &[0x02, 0x02, 0x05, 0x47, 0x72, 0x61, 0x63, 0x65],
+        &[0x03, 0x02, 0x01],
+        &[0x04, 0x01, 0x07, 0x67, 0x67, 0x20, 0xf0, 0x9f, 0x98, 0x80],
+        &[0x05, 0xac, 0x02, 0xef, 0xbe, 0xad, 0xde],
+        &[
+            0x06, 0xac, 0x02, 0xef, 0xbe, 0xad, 0xde, 0x0c, 0x00, 0x00, 0x00, 0xe8, 0x07,
+        ],
+        &[
+            0x07, 0x01, 0x28, 0x27, 0x03, 0x0e, 0x05, 0x05, 0xfd, 0xff, 0x03, 0x18, 0x80, 0x05,
+            0x07, 0x01, 0x00,
+        ],
+        &[
+            0x08, 0xf4, 0x03, 0x00, 0x02, 0x03, 0x0f, 0xc0, 0x01, 0x9f, 0x02, 0x00, 0x40, 0xbe,
+            0x28, 0x01, 0x04, 0x04, 0xc0, 0x01, 0x9f, 0x02,
+        ],
+        &[
+            0x09, 0xf4, 0x03, 0xef, 0xcd, 0xab, 0x89, 0x67, 0x45, 0x23, 0x01,
+        ],
+        &[
+            0x0a, 0xf9, 0x03, 0x05, 0xf8, 0x03, 0x02, 0x03, 0x7f, 0xc0, 0x01, 0x9f, 0x02, 0x00,
+            0x40, 0xbe, 0x28, 0x01, 0x03, 0x29, 0x80, 0x02, 0x9f, 0x02, 0x01, 0x04,
+        ],
+        &[
+            0x0b, 0x05, 0x61, 0x72, 0x65, 0x6e, 0x61, 0x01, 0x04, 0x01, 0x02, 0x02, 0x01, 0x00,
+            0x01, 0x03, 0x41, 0x64, 0x61, 0x07, 0x01, 0x00, 0x05, 0x47, 0x72, 0x61, 0x63, 0x65,
+        ],
+        &[0x0c, 0x01, 0x01],
+        &[0x0d, 0x02, 0x00, 0x29, 0x01, 0x00],
+    ];
+
+    #[test]
+    fn samples_cover_every_kind() {
+        let kinds: Vec<MessageKind> = samples().iter().map(Message::kind).collect();
+        let expected: Vec<MessageKind> = (0..=u8::MAX).map_while(MessageKind::from_u8).collect();
+        assert_eq!(kinds, expected);
+    }
+
+    #[test]
+    fn round_trips_every_kind() {
+        for message in samples() {
+            let bytes = message.encode().unwrap();
+            assert_eq!(Message::decode(&bytes).unwrap(), message);
+        }
+    }
+
+    #[test]
+    fn matches_golden_bytes() {
+        assert_eq!(
+            PROTOCOL_VERSION, GOLDEN_VERSION,
+            "update GOLDEN for the new version"
+        );
+        for (message, golden) in samples().iter().zip(GOLDEN) {
+            assert_eq!(message.encode().unwrap(), golden, "{:?}", message.kind());
+            assert_eq!(&Message::decode(golden).unwrap(), message);
+        }
+    }
+
+    #[test]
+    fn rejects_trailing_and_missing_bytes() {
+        for message in samples() {
+            let mut bytes = message.encode().unwrap();
+            bytes.push(0);
+            assert!(Message::decode(&bytes).is_err(), "{:?}", message.kind());
+            bytes.truncate(bytes.len() - 2);
+            assert!(Message::decode(&bytes).is_err(), "{:?}", message.kind());
+        }
+        assert!(Message::decode(&[]).is_err());
+        assert!(Message::decode(&[u8::MAX]).is_err());
+    }
+
+    #[test]
+    fn bounds_reject_reasons() {
+        let reason = "x".repeat(MAX_REASON_LEN + 1);
+        let reject = Message::Reject {
+            version: PROTOCOL_VERSION,
+            reason: reason.clone(),
+        };
+        assert!(reject.encode().is_err());
+
+        let mut writer = ByteWriter::new();
+        writer.write_u8(MessageKind::Reject as u8);
+        writer.write_u16(PROTOCOL_VERSION);
+        writer.write_str(&reason);
+        assert!(Message::decode(&writer.into_bytes()).is_err());
+    }
+
+    const RANDOM_ROUNDS: u64 = 200;
+
+    fn random_bool(rng: &mut Rng) -> bool {
+        rng.next_u32() & 1 == 1
+    }
+
+    // below u64::MAX, optional ticks travel as the tick plus one
+    fn random_tick(rng: &mut Rng) -> u64 {
+        match rng.next_u32() % 3 {
+            0 => rng.next_u32() as u64 % 100,
+            1 => rng.next_u64() >> 1,
+            _ => u64::MAX - 1,
+        }
+    }
+
+    fn random_option<T, F>(rng: &mut Rng, f: F) -> Option<T>
+    where
+        F: FnOnce(&mut Rng) -> T,
+    {
+        if random_bool(rng) { Some(f(rng)) } else { None }
+    }
+
+    fn random_text(rng: &mut Rng, max: usize) -> String {
+        const CHARS: [char; 6] = ['a', 'Z', ' ', '\u{e9}', '\u{4e16}', '\u{1f600}'];
+        let len = rng.next_u32() as usize % (max + 1);
+        (0..len)
+            .map(|_| CHARS[rng.next_u32() as usize % CHARS.len()])
+            .collect()
+    }
+
+    fn random_id(rng: &mut Rng) -> EntityId {
+        EntityId::new(rng.next_u32() | 1).unwrap()
+    }
+
+    // whole steps, so the value survives quantizing
+    fn random_vec2(rng: &mut Rng) -> Vec2 {
+        let mut step = || (rng.next_u32() % (1 << 21)) as f32 - (1 << 20) as f32;
+        Vec2::new(step(), step()) * codec::POSITION_STEP
+    }
+
+    fn random_angle(rng: &mut Rng) -> f32 {
+        codec::quantize_angle(rng.range_f32(-10.0, 10.0))
+    }
+
+    fn random_entity(rng: &mut Rng) -> EntityState {
+        EntityState {
+            id: random_id(rng),
+            position: random_vec2(rng),
+            velocity: if random_bool(rng) {
+                random_vec2(rng)
+            } else {
+                Vec2::ZERO
+            },
+            rotation: if random_bool(rng) {
+                random_angle(rng)
+            } else {
+                0.0
+            },
+            visible: random_bool(rng),
+            owner: random_option(rng, |rng| rng.next_u32() as u8),
+        }
+    }
+
+    fn random_delta(rng: &mut Rng) -> EntityDelta {
+        EntityDelta {
+            id: random_id(rng),
+            position: random_option(rng, random_vec2),
+            velocity: random_option(rng, random_vec2),
+            rotation: random_option(rng, random_angle),
+            visible: random_option(rng, random_bool),
+            owner: random_option(rng, |rng| random_option(rng, |rng| rng.next_u32() as u8)),
+        }
+    }
+
+    fn random_inputs(rng: &mut Rng, start_tick: u64) -> Vec<InputSnapshot> {
+        let count = rng.next_u32() as usize % (MAX_INPUTS + 1);
+        let mut inputs: Vec<InputSnapshot> = Vec::with_capacity(count);
+        for index in 0..count {
+            let tick = start_tick + index as u64;
+            // repeats are what the encoding special cases
+            if let (true, Some(previous)) = (random_bool(rng), inputs.last()) {
+                inputs.push(InputSnapshot { tick, ..*previous });
+                continue;
+            }
+            let mut axes = [0; MAX_AXES];
+            for axis in axes.iter_mut() {
+                if random_bool(rng) {
+                    *axis = rng.next_u32() as i16;
+                }
+            }
+            inputs.push(InputSnapshot {
+                tick,
+                actions: if random_bool(rng) { rng.next_u64() } else { 0 },
+                axes,
+                pointer: random_option(rng, |rng| [rng.next_u32() as i16, rng.next_u32() as i16]),
+            });
+        }
+        inputs
+    }
+
+    fn random_message(rng: &mut Rng, kind: MessageKind) -> Message {
+        let player = rng.next_u32() as u8;
+        match kind {
+            MessageKind::Hello => Message::Hello {
+                version: rng.next_u32() as u16,
+                name: random_text(rng, MAX_NAME_LEN),
+            },
+            MessageKind::Reject => Message::Reject {
+                version: rng.next_u32() as u16,
+                reason: random_text(rng, MAX_REASON_LEN),
+            },
+            MessageKind::Join => Message::Join {
+                player,
+                name: random_text(rng, MAX_NAME_LEN),
+            },
+            MessageKind::Leave => Message::Leave {
+                player,
+                reason: LeaveReason::from_u8((rng.next_u32() % 4) as u8).unwrap(),
+            },
+            MessageKind::Chat => Message::Chat {
+                player,
+                text: random_text(rng, MAX_CHAT_LEN),
+            },
+            MessageKind::Ping => Message::Ping {
+                sequence: rng.next_u32(),
+                sent_ms: rng.next_u32(),
+            },
+            MessageKind::Pong => Message::Pong {
+                sequence: rng.next_u32(),
+                sent_ms: rng.next_u32(),
+                received_ms: rng.next_u32(),
+                tick: rng.next_u64(),
+            },
+            MessageKind::Inputs => {
+                let start_tick = rng.next_u64() >> 1;
+                Message::Inputs {
+                    player,
+                    start_tick,
+                    ack_tick: random_option(rng, random_tick),
+                    inputs: random_inputs(rng, start_tick),
+                }
+            }
+            MessageKind::Snapshot => Message::Snapshot {
+                tick: rng.next_u64(),
+                last_input: random_option(rng, random_tick),
+                entities: (0..rng.next_u32() % 32)
+                    .map(|_| random_entity(rng))
+                    .collect(),
+            },
+            MessageKind::Checksum => Message::Checksum {
+                tick: rng.next_u64(),
+                hash: rng.next_u64(),
+            },
+            MessageKind::Delta => {
+                let tick = random_tick(rng).max(1);
+                Message::Delta {
+                    tick,
+                    baseline: rng.next_u64() % tick,
+                    last_input: random_option(rng, random_tick),
+                    changed: (0..rng.next_u32() % 32)
+                        .map(|_| random_delta(rng))
+                        .collect(),
+                    removed: (0..rng.next_u32() % 32).map(|_| random_id(rng)).collect(),
+                }
+            }
+            MessageKind::Room => Message::Room {
+                settings: RoomSettings {
+                    map: random_text(rng, MAX_NAME_LEN),
+                    mode: sync_mode_from_u8((rng.next_u32() % 2) as u8).unwrap(),
+                    max_players: rng.next_u32() as u8,
+                },
+                members: (0..rng.next_u32() % (MAX_PLAYERS as u32 + 1))
+                    .map(|_| RoomMember {
+                        peer: PeerId(rng.next_u32()),
+                        player: rng.next_u32() as u8,
+                        name: random_text(rng, MAX_NAME_LEN),
+                        ready: random_bool(rng),
+                    })
+                    .collect(),
+                started: random_option(rng, random_tick),
+                topology: if random_bool(rng) {
+                    Topology::Star {
+                        hub: PeerId(rng.next_u32() % u32::MAX),
+                    }
+                } else {
+                    Topology::Mesh
+                },
+            },
+            MessageKind::Ready => Message::Ready {
+                player,
+                ready: random_bool(rng),
+            },
+            MessageKind::InputAcks => Message::InputAcks {
+                acks: (0..rng.next_u32() % (MAX_PLAYERS as u32 + 1))
+                    .map(|_| (rng.next_u32() as u8, rng.next_u64()))
+                    .collect(),
+            },
+        }
+    }
+
+    #[test]
+    fn round_trips_random_messages() {
+        let kinds: Vec<MessageKind> = (0..=u8::MAX).map_while(MessageKind::from_u8).collect();
+        let mut rng = Rng::new(0x5eed);
+        for round in 0..RANDOM_ROUNDS {
+            for kind in kinds.iter() {
+                let message = random_message(&mut rng, *kind);
+                let bytes = message.encode().unwrap();
+                let decoded = Message::decode(&bytes);
+                assert_eq!(decoded.unwrap(), message, "round {} {:?}", round, kind);
+            }
+        }
+    }
+
+    #[test]
+    fn random_and_truncated_bytes_never_panic() {
+        let kinds: Vec<MessageKind> = (0..=u8::MAX).map_while(MessageKind::from_u8).collect();
+        let mut rng = Rng::new(0xbad);
+        for _ in 0..RANDOM_ROUNDS {
+            for kind in kinds.iter() {
+                let bytes = random_message(&mut rng, *kind).encode().unwrap();
+                for len in 0..bytes.len() {
+                    assert!(Message::decode(&bytes[..len]).is_err(), "{:?}", kind);
+                }
+                // a valid kind followed by noise, most of it has to be rejected but none of it
+                // may panic
+                let len = rng.next_u32() as usize % 64;
+                let mut noise = vec![*kind as u8];
+                noise.extend((0..len).map(|_| rng.next_u32() as u8));
+                let _ = Message::decode(&noise);
+            }
+            let len = rng.next_u32() as usize % 256;
+            let noise: Vec<u8> = (0..len).map(|_| rng.next_u32() as u8).collect();
+            let _ = Message::decode(&noise);
+        }
+    }
+
+    #[test]
+    fn rejects_input_ticks_past_the_end() {
+        let inputs = Message::Inputs {
+            player: 0,
+            start_tick: u64::MAX - 1,
+            ack_tick: None,
+            inputs: vec![
+                InputSnapshot::default(),
+                InputSnapshot::default(),
+                InputSnapshot::default(),
+            ],
+        };
+        let bytes = inputs.encode().unwrap();
+        assert!(Message::decode(&bytes).is_err());
+    }
+}
@@ -0,0 +1,785 @@
+pub mod packet;
+
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::libs::net::reliable::packet::{
+    CHUNK_OVERHEAD, Chunk, Fragment, HEADER_SIZE, Packet, sequence_newer,
+};
+use crate::libs::net::{Channel, MAX_MESSAGE_SIZE, PeerId, Transport, TransportEvent};
+use crate::libs::types::errors::ErrorStr;
+use crate::log_debug;
+
+const LOG_TARGET: &str = "net::reliable";
+
+// what Channel::Reliable sends on
+pub const DEFAULT_STREAM: u8 = 0;
+pub const FRAGMENT_SIZE: usize = 1024;
+pub const MAX_FRAGMENTS: usize = 1024;
+pub const MAX_RELIABLE_SIZE: usize = FRAGMENT_SIZE * MAX_FRAGMENTS;
+// unreliable messages travel whole, in one packet
+pub const MAX_UNRELIABLE_SIZE: usize = MAX_MESSAGE_SIZE - HEADER_SIZE - CHUNK_OVERHEAD;
+// fragments are batched into packets up to about this size, below the usual path mtu
+const PACKET_SIZE: usize = 1200;
+// fragments sent and not yet acknowledged, per peer. later ones wait their turn
+const SEND_WINDOW: usize = 256;
+// messages this far past the next one in order are dropped, the sender tries again later
+const RECEIVE_WINDOW: u32 = 1024;
+// packets remembered for matching acks against
+const SENT_HISTORY: usize = 256;
+// memory held for messages still being put together, per peer. fragments over it are dropped
+// unacknowledged, so the sender tries again once some got delivered
+pub const MAX_PENDING_BYTES: usize = 4 * MAX_RELIABLE_SIZE;
+// what one slot for a fragment costs before it arrived
+const SLOT_SIZE: usize = std::mem::size_of::<Option<Vec<u8>>>();
+const INITIAL_RTO_MS: f64 = 200.0;
+const MIN_RTO_MS: f64 = 50.0;
+const MAX_RTO_MS: f64 = 2000.0;
+const RTT_RATE: f64 = 0.125;
+const RTT_VARIANCE_RATE: f64 = 0.25;
+
+#[derive(Debug, Clone)]
+struct Outgoing {
+    stream: u8,
+    message: u32,
+    index: u16,
+    count: u16,
+    data: Vec<u8>,
+    last_sent_ms: Option<f64>,
+}
+
+#[derive(Debug, Clone)]
+struct SentPacket {
+    sequence: u16,
+    sent_ms: f64,
+    // keys into Link::outgoing
+    fragments: Vec<u64>,
+    // carried a fragment sent before, so its ack says nothing certain about rtt
+    resent: bool,
+}
+
+#[derive(Debug, Clone)]
+struct Incoming {
+    fragments: Vec<Option<Vec<u8>>>,
+    received: usize,
+    // slots and data, counted in Link::pending_bytes
+    bytes: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+struct StreamIn {
+    // the next message to deliver
+    next: u32,
+    messages: BTreeMap<u32, Incoming>,
+}
+
+#[derive(Debug, Clone)]
+struct Link {
+    next_sequence: u16,
+    next_message: BTreeMap<u8, u32>,
+    // unacknowledged fragments in send order
+    outgoing: BTreeMap<u64, Outgoing>,
+    next_outgoing: u64,
+    sent: VecDeque<SentPacket>,
+    srtt_ms: Option<f64>,
+    rtt_variance_ms: f64,
+    rto_ms: f64,
+
+    // newest packet received and bit n for the one n + 1 before it
+    received: Option<(u16, u32)>,
+    // something reliable arrived since the last packet we sent
+    ack_owed: bool,
+    streams: BTreeMap<u8, StreamIn>,
+    // held by every stream's incomplete messages, see MAX_PENDING_BYTES
+    pending_bytes: usize,
+}
+
+impl Default for Link {
+    fn default() -> Self {
+        Self {
+            next_sequence: 0,
+            next_message: BTreeMap::new(),
+            outgoing: BTreeMap::new(),
+            next_outgoing: 0,
+            sent: VecDeque::new(),
+            srtt_ms: None,
+            rtt_variance_ms: 0.0,
+            rto_ms: INITIAL_RTO_MS,
+            received: None,
+            ack_owed: false,
+            streams: BTreeMap::new(),
+            pending_bytes: 0,
+        }
+    }
+}
+
+impl Link {
+    fn packet(&mut self, chunks: Vec<Chunk>) -> Packet {
+        let sequence = self.next_sequence;
+        self.next_sequence = self.next_sequence.wrapping_add(1);
+        self.ack_owed = false;
+        Packet {
+            sequence,
+            ack: self.received,
+            chunks,
+        }
+    }
+
+    // whether a packet was seen before. only the ack window can be told, older ones count as seen
+    fn seen(&self, sequence: u16) -> bool {
+        let (newest, bits) = if let Some(received) = self.received {
+            received
+        } else {
+            return false;
+        };
+        if sequence == newest {
+            return true;
+        }
+        if sequence_newer(sequence, newest) {
+            return false;
+        }
+        let back = newest.wrapping_sub(sequence) as u32;
+        back > 32 || bits & (1 << (back - 1)) != 0
+    }
+
+    fn mark_received(&mut self, sequence: u16) {
+        self.received = Some(match self.received {
+            None => (sequence, 0),
+            Some((newest, bits)) if sequence_newer(sequence, newest) => {
+                let shift = sequence.wrapping_sub(newest) as u64;
+                let bits = if shift > 32 {
+                    0
+                } else {
+                    (((bits as u64) << shift) | (1 << (shift - 1))) as u32
+                };
+                (sequence, bits)
+            }
+            Some((newest, bits)) => {
+                let back = newest.wrapping_sub(sequence) as u32;
+                (newest, bits | (1 << (back - 1)))
+            }
+        });
+    }
+
+    fn acknowledged(&mut self, ack: u16, bits: u32, now_ms: f64) {
+        let acked = |sequence: u16| {
+            if sequence == ack {
+                return true;
+            }
+            let back = ack.wrapping_sub(sequence) as u32;
+            !sequence_newer(sequence, ack) && back <= 32 && bits & (1 << (back - 1)) != 0
+        };
+        let (done, waiting): (Vec<SentPacket>, Vec<SentPacket>) = self
+            .sent
+            .drain(..)
+            .partition(|packet| acked(packet.sequence));
+        self.sent = waiting.into();
+        for packet in done {
+            for fragment in packet.fragments.iter() {
+                self.outgoing.remove(fragment);
+            }
+            if !packet.resent {
+                self.rtt_sample(now_ms - packet.sent_ms);
+            }
+        }
+    }
+
+    fn rtt_sample(&mut self, rtt_ms: f64) {
+        let srtt = match self.srtt_ms {
+            Some(srtt) => {
+                let deviation = (rtt_ms - srtt).abs();
+                self.rtt_variance_ms += (deviation - self.rtt_variance_ms) * RTT_VARIANCE_RATE;
+                srtt + (rtt_ms - srtt) * RTT_RATE
+            }
+            None => {
+                self.rtt_variance_ms = rtt_ms / 2.0;
+                rtt_ms
+            }
+        };
+        self.srtt_ms = Some(srtt);
+        self.rto_ms = (srtt + 4.0 * self.rtt_variance_ms).clamp(MIN_RTO_MS, MAX_RTO_MS);
+    }
+
+    // false if the fragment can't be taken now, the packet then isn't acknowledged
+    fn accepts(&self, fragment: &Fragment) -> bool {
+        let next = self
+            .streams
+            .get(&fragment.stream)
+            .map_or(0, |stream| stream.next);
+        if fragment.message >= next.saturating_add(RECEIVE_WINDOW) {
+            return false;
+        }
+        if fragment.count as usize > MAX_FRAGMENTS {
+            return false;
+        }
+        let stream = self.streams.get(&fragment.stream);
+        let known = stream.and_then(|stream| stream.messages.get(&fragment.message));
+        known.is_none_or(|incoming| incoming.fragments.len() == fragment.count as usize)
+    }
+
+    // what taking the fragment would add to pending_bytes
+    fn cost(&self, fragment: &Fragment) -> usize {
+        let stream = self.streams.get(&fragment.stream);
+        let next = stream.map_or(0, |stream| stream.next);
+        let known = stream.and_then(|stream| stream.messages.get(&fragment.message));
+        match known {
+            Some(incoming) => match incoming.fragments.get(fragment.index as usize) {
+                Some(None) => fragment.data.len(),
+                _ => 0,
+            },
+            None if fragment.message < next => 0,
+            None => fragment.count as usize * SLOT_SIZE + fragment.data.len(),
+        }
+    }
+
+    // whether the fragments fit what's left of MAX_PENDING_BYTES. the next message in order on
+    // its stream may go over by a message, so later ones filling it can't hold the stream up
+    fn fits(&self, fragments: &[&Fragment]) -> bool {
+        let mut cost = 0;
+        let mut in_order = true;
+        for fragment in fragments.iter() {
+            cost += self.cost(fragment);
+            in_order &= self
+                .streams
+                .get(&fragment.stream)
+                .map_or(0, |stream| stream.next)
+                == fragment.message;
+        }
+        let max = if in_order {
+            MAX_PENDING_BYTES + MAX_RELIABLE_SIZE + MAX_FRAGMENTS * SLOT_SIZE
+        } else {
+            MAX_PENDING_BYTES
+        };
+        self.pending_bytes + cost <= max
+    }
+
+    // the messages of the stream that are now complete and next in order
+    fn receive(&mut self, fragment: Fragment) -> Vec<Vec<u8>> {
+        let stream = self.streams.entry(fragment.stream).or_default();
+        if fragment.message < stream.next {
+            return Vec::new();
+        }
+        let incoming = stream.messages.entry(fragment.message).or_insert_with(|| {
+            let bytes = fragment.count as usize * SLOT_SIZE;
+            self.pending_bytes += bytes;
+            Incoming {
+                fragments: vec![None; fragment.count as usize],
+                received: 0,
+                bytes,
+            }
+        });
+        let slot = &mut incoming.fragments[fragment.index as usize];
+        if slot.is_none() {
+            incoming.bytes += fragment.data.len();
+            self.pending_bytes += fragment.data.len();
+            *slot = Some(fragment.data);
+            incoming.received += 1;
+        }
+        let mut delivered = Vec::new();
+        while let Some(entry) = stream.messages.first_entry() {
+            let complete =
+                *entry.key() == stream.next && entry.get().received == entry.get().fragments.len();
+            if !complete {
+                break;
+            }
+            let incoming = entry.remove();
+            self.pending_bytes -= incoming.bytes;
+            delivered.push(incoming.fragments.into_iter().flatten().flatten().collect());
+            stream.next += 1;
+        }
+        delivered
+    }
+}
+
+// reliable, ordered messages and plain unreliable ones over the inner transport's unreliable
+// channel only. lost fragments are resent on a timer until a packet carrying them is
+// acknowledged, each stream is delivered in order without waiting on the others. both sides
+// need this layer, messages arriving on the inner reliable channel are passed through as is
+pub struct ReliableTransport<T>
+where
+    T: Transport,
+{
+    inner: T,
+    links: BTreeMap<PeerId, Link>,
+    events: VecDeque<TransportEvent>,
+    now_ms: f64,
+}
+
+impl<T> std::fmt::Debug for ReliableTransport<T>
+where
+    T: Transport,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("ReliableTransport")
+            .field("local_id", &self.inner.local_id())
+            .field("links", &self.links.len())
+            .field("events", &self.events.len())
+            .field("now_ms", &self.now_ms)
+            .finish()
+    }
+}
+
+impl<T> ReliableTransport<T>
+where
+    T: Transport,
+{
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            links: BTreeMap::new(),
+            events: VecDeque::new(),
+            now_ms: 0.0,
+        }
+    }
+
+    #[inline]
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    #[inline]
+    pub fn inner_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    // reliable fragments not yet acknowledged by the peer
+    #[inline]
+    pub fn in_flight(&self, peer: PeerId) -> usize {
+        self.links.get(&peer).map_or(0, |link| link.outgoing.len())
+    }
+
+    // smoothed, None before the first acknowledgement
+    #[inline]
+    pub fn rtt_ms(&self, peer: PeerId) -> Option<f64> {
+        self.links.get(&peer).and_then(|link| link.srtt_ms)
+    }
+
+    // resends what timed out and acknowledges what arrived. call once per frame
+    pub fn update(&mut self, now_ms: f64) -> Result<(), ErrorStr> {
+        self.now_ms = now_ms;
+        let peers: Vec<PeerId> = self.links.keys().copied().collect();
+        for peer in peers {
+            if self.inner.is_connected(peer) {
+                self.flush(peer, true)?;
+            }
+        }
+        Ok(())
+    }
+
+    // in order with everything else on the same stream, independent of other streams
+    pub fn send_stream(&mut self, peer: PeerId, stream: u8, data: &[u8]) -> Result<(), ErrorStr> {
+        if data.len() > MAX_RELIABLE_SIZE {
+            let error = format!(
+                "Message of {} bytes is over the {} byte limit",
+                data.len(),
+                MAX_RELIABLE_SIZE
+            );
+            return Err(ErrorStr::new(error));
+        }
+        if !self.inner.is_connected(peer) {
+            return Err(ErrorStr::new(format!("Not connected to {}", peer)));
+        }
+        let link = self.links.entry(peer).or_default();
+        let next = link.next_message.entry(stream).or_insert(0);
+        let message = *next;
+        *next += 1;
+        let pieces: Vec<&[u8]> = if data.is_empty() {
+            vec![data]
+        } else {
+            data.chunks(FRAGMENT_SIZE).collect()
+        };
+        let count = pieces.len() as u16;
+        for (index, piece) in pieces.into_iter().enumerate() {
+            link.outgoing.insert(
+                link.next_outgoing,
+                Outgoing {
+                    stream,
+                    message,
+                    index: index as u16,
+                    count,
+                    data: piece.to_vec(),
+                    last_sent_ms: None,
+                },
+            );
+            link.next_outgoing += 1;
+        }
+        self.flush(peer, false)
+    }
+
+    // sends whatever is due in as few packets as fit, and an empty one if only an ack is
+    fn flush(&mut self, peer: PeerId, ack_only: bool) -> Result<(), ErrorStr> {
+        let now_ms = self.now_ms;
+        let link = if let Some(link) = self.links.get_mut(&peer) {
+            link
+        } else {
+            return Ok(());
+        };
+        let rto_ms = link.rto_ms;
+        let due: Vec<u64> = link
+            .outgoing
+            .iter()
+            .take(SEND_WINDOW)
+            .filter(|(_, outgoing)| {
+                outgoing
+                    .last_sent_ms
+                    .is_none_or(|sent| now_ms - sent >= rto_ms)
+            })
+            .map(|(key, _)| *key)
+            .collect();
+        let mut packets = Vec::new();
+        let mut current: Vec<u64> = Vec::new();
+        let mut size = HEADER_SIZE;
+        for key in due {
+            let len = link.outgoing[&key].data.len() + CHUNK_OVERHEAD;
+            if !current.is_empty() && size + len > PACKET_SIZE {
+                packets.push(std::mem::take(&mut current));
+                size = HEADER_SIZE;
+            }
+            current.push(key);
+            size += len;
+        }
+        if !current.is_empty() {
+            packets.push(current);
+        }
+
+        let mut resends = false;
+        let mut datas = Vec::with_capacity(packets.len());
+        for keys in packets {
+            let mut resent = false;
+            let mut chunks = Vec::with_capacity(keys.len());
+            for key in keys.iter() {
+                if let Some(outgoing) = link.outgoing.get_mut(key) {
+                    resent |= outgoing.last_sent_ms.is_some();
+                    outgoing.last_sent_ms = Some(now_ms);
+                    chunks.push(Chunk::Reliable(Fragment {
+                        stream: outgoing.stream,
+                        message: outgoing.message,
+                        index: outgoing.index,
+                        count: outgoing.count,
+                        data: outgoing.data.clone(),
+                    }));
+                }
+            }
+            let packet = link.packet(chunks);
+            link.sent.push_back(SentPacket {
+                sequence: packet.sequence,
+                sent_ms: now_ms,
+                fragments: keys,
+                resent,
+            });
+            while link.sent.len() > SENT_HISTORY {
+                link.sent.pop_front();
+            }
+            resends |= resent;
+            datas.push(packet.encode());
+        }
+        if datas.is_empty() && ack_only && link.ack_owed {
+            datas.push(link.packet(Vec::new()).encode());
+        }
+        // back off while nothing gets through
+        if resends {
+            link.rto_ms = (link.rto_ms * 2.0).min(MAX_RTO_MS);
+            log_debug!(
+                LOG_TARGET,
+                "Resending to {}, rto {:.0} ms",
+                peer,
+                link.rto_ms
+            );
+        }
+        for data in datas {
+            self.inner.send(peer, Channel::Unreliable, &data)?;
+        }
+        Ok(())
+    }
+
+    fn send_unreliable(&mut self, peer: PeerId, data: &[u8]) -> Result<(), ErrorStr> {
+        if data.len() > MAX_UNRELIABLE_SIZE {
+            let error = format!(
+                "Message of {} bytes is over the {} byte limit",
+                data.len(),
+                MAX_UNRELIABLE_SIZE
+            );
+            return Err(ErrorStr::new(error));
+        }
+        let link = self.links.entry(peer).or_default();
+        let packet = link.packet(vec![Chunk::Unreliable(data.to_vec())]);
+        self.inner.send(peer, Channel::Unreliable, &packet.encode())
+    }
+
+    fn receive(&mut self, peer: PeerId, data: &[u8]) -> Result<(), ErrorStr> {
+        let packet = Packet::decode(data)?;
+        let now_ms = self.now_ms;
+        let link = self.links.entry(peer).or_default();
+        if let Some((ack, bits)) = packet.ack {
+            link.acknowledged(ack, bits, now_ms);
+        }
+        if link.seen(packet.sequence) {
+            return Ok(());
+        }
+        // all or nothing, the ack covers the whole packet
+        let accepted = packet.chunks.iter().all(|chunk| match chunk {
+            Chunk::Reliable(fragment) => link.accepts(fragment),
+            Chunk::Unreliable(_) => true,
+        });
+        if !accepted {
+            log_debug!(LOG_TARGET, "Packet from {} is too far ahead", peer);
+            return Ok(());
+        }
+        let fragments: Vec<&Fragment> = packet
+            .chunks
+            .iter()
+            .filter_map(|chunk| match chunk {
+                Chunk::Reliable(fragment) => Some(fragment),
+                Chunk::Unreliable(_) => None,
+            })
+            .collect();
+        if !link.fits(&fragments) {
+            log_debug!(LOG_TARGET, "Packet from {} is over the pending limit", peer);
+            return Ok(());
+        }
+        link.mark_received(packet.sequence);
+        for chunk in packet.chunks {
+            match chunk {
+                Chunk::Unreliable(data) => self.events.push_back(TransportEvent::Message {
+                    peer,
+                    channel: Channel::Unreliable,
+                    data,
+                }),
+                Chunk::Reliable(fragment) => {
+                    link.ack_owed = true;
+                    for data in link.receive(fragment) {
+                        self.events.push_back(TransportEvent::Message {
+                            peer,
+                            channel: Channel::Reliable,
+                            data,
+                        });
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<T> Transport for ReliableTransport<T>
+where
+    T: Transport,
+{
+    #[inline]
+    fn local_id(&self) -> PeerId {
+        self.inner.local_id()
+    }
+
+    fn peers(&self) -> Vec<PeerId> {
+        self.inner.peers()
+    }
+
+    fn is_connected(&self, peer: PeerId) -> bool {
+        self.inner.is_connected(peer)
+    }
+
+    fn send(&mut self, peer: PeerId, channel: Channel, data: &[u8]) -> Result<(), ErrorStr> {
+        match channel {
+            Channel::Reliable => self.send_stream(peer, DEFAULT_STREAM, data),
+            Channel::Unreliable => self.send_unreliable(peer, data),
+        }
+    }
+
+    fn recv(&mut self) -> Option<TransportEvent> {
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Some(event);
+            }
+            match self.inner.recv()? {
+                TransportEvent::Message {
+                    peer,
+                    channel: Channel::Unreliable,
+                    data,
+                } => {
+                    if let Err(error) = self.receive(peer, &data) {
+                        let error = format!("Bad packet from {}: {}", peer, error);
+                        return Some(TransportEvent::Error {
+                            peer: Some(peer),
+                            error,
+                        });
+                    }
+                }
+                // a new connection starts from scratch on both sides. anything sent before the
+                // event was read already belongs to it
+                TransportEvent::Connected(peer) => {
+                    self.links.entry(peer).or_default();
+                    return Some(TransportEvent::Connected(peer));
+                }
+                TransportEvent::Disconnected(peer) => {
+                    self.links.remove(&peer);
+                    return Some(TransportEvent::Disconnected(peer));
+                }
+                event => return Some(event),
+            }
+        }
+    }
+
+    fn disconnect(&mut self, peer: PeerId) {
+        self.links.remove(&peer);
+        self.inner.disconnect(peer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::libs::net::conditioned::LinkFaults;
+    use crate::libs::net::loopback::{LoopbackNetwork, LoopbackTransport};
+
+    const FRAME_MS: f64 = 16.0;
+    const STREAMS: u8 = 3;
+    const MESSAGES: usize = 60;
+
+    fn pair(
+        network: &LoopbackNetwork,
+    ) -> (
+        ReliableTransport<LoopbackTransport>,
+        ReliableTransport<LoopbackTransport>,
+    ) {
+        let (a, b) = (network.add_peer(), network.add_peer());
+        network.connect(a.local_id(), b.local_id()).unwrap();
+        let (mut a, mut b) = (ReliableTransport::new(a), ReliableTransport::new(b));
+        assert!(matches!(a.recv(), Some(TransportEvent::Connected(_))));
+        assert!(matches!(b.recv(), Some(TransportEvent::Connected(_))));
+        (a, b)
+    }
+
+    fn faulty() -> LoopbackNetwork {
+        let network = LoopbackNetwork::new();
+        network.set_latency(2);
+        let faults = LinkFaults {
+            loss: 0.2,
+            duplicate: 0.1,
+            reorder: 0.2,
+        };
+        network.set_faults(faults, 0x5eed);
+        network
+    }
+
+    // steps both sides until nothing is in flight, returning what b got on the reliable channel
+    // by stream. each message starts with its stream byte
+    fn run(
+        network: &LoopbackNetwork,
+        a: &mut ReliableTransport<LoopbackTransport>,
+        b: &mut ReliableTransport<LoopbackTransport>,
+    ) -> BTreeMap<u8, Vec<Vec<u8>>> {
+        let peer = b.local_id();
+        let mut received: BTreeMap<u8, Vec<Vec<u8>>> = BTreeMap::new();
+        let mut now = 0.0;
+        for _ in 0..2000 {
+            now += FRAME_MS;
+            a.update(now).unwrap();
+            b.update(now).unwrap();
+            network.step();
+            while let Some(event) = a.recv() {
+                assert!(
+                    !matches!(event, TransportEvent::Error { .. }),
+                    "{:?}",
+                    event
+                );
+            }
+            while let Some(event) = b.recv() {
+                if let TransportEvent::Message {
+                    channel: Channel::Reliable,
+                    data,
+                    ..
+                } = event
+                {
+                    received.entry(data[0]).or_default().push(data);
+                }
+            }
+            if a.in_flight(peer) == 0 {
+                break;
+            }
+        }
+        assert_eq!(a.in_flight(peer), 0);
+        received
+    }
+
+    #[test]
+    fn delivers_each_stream_once_and_in_order() {
+        let network = faulty();
+        let (mut a, mut b) = pair(&network);
+        let peer = b.local_id();
+        let mut sent: BTreeMap<u8, Vec<Vec<u8>>> = BTreeMap::new();
+        for index in 0..MESSAGES {
+            let stream = index as u8 % STREAMS;
+            let data = vec![stream, index as u8, (index >> 8) as u8];
+            a.send_stream(peer, stream, &data).unwrap();
+            sent.entry(stream).or_default().push(data);
+        }
+        assert_eq!(run(&network, &mut a, &mut b), sent);
+        assert_eq!(b.links[&a.local_id()].pending_bytes, 0);
+    }
+
+    #[test]
+    fn reassembles_a_message_of_many_fragments() {
+        let network = faulty();
+        let (mut a, mut b) = pair(&network);
+        let peer = b.local_id();
+        let len = FRAGMENT_SIZE * 40 + 17;
+        let data: Vec<u8> = (0..len).map(|index| (index % 251) as u8).collect();
+        a.send_stream(peer, 0, &data).unwrap();
+        a.send_stream(peer, 0, &[0, 1]).unwrap();
+        let received = run(&network, &mut a, &mut b);
+        assert_eq!(received[&0], vec![data, vec![0, 1]]);
+    }
+
+    #[test]
+    fn drops_fragments_over_the_pending_limit() {
+        let network = LoopbackNetwork::new();
+        let (mut raw, b) = (network.add_peer(), network.add_peer());
+        network.connect(raw.local_id(), b.local_id()).unwrap();
+        let mut b = ReliableTransport::new(b);
+        let peer = raw.local_id();
+        let packet = |sequence: u16, stream: u8, message: u32| {
+            let fragment = Fragment {
+                stream,
+                message,
+                index: 0,
+                count: MAX_FRAGMENTS as u16,
+                data: vec![0; 8],
+            };
+            Packet {
+                sequence,
+                ack: None,
+                chunks: vec![Chunk::Reliable(fragment)],
+            }
+            .encode()
+        };
+        // a fragment each of messages that never complete, on every stream
+        for sequence in 0..2048 {
+            let data = packet(sequence, sequence as u8, 1 + sequence as u32 / 256);
+            raw.send(b.local_id(), Channel::Unreliable, &data).unwrap();
+        }
+        while b.recv().is_some() {}
+        let pending = b.links[&peer].pending_bytes;
+        assert!(pending <= MAX_PENDING_BYTES);
+        assert!(pending > MAX_PENDING_BYTES / 2);
+        // the next message in order still gets through
+        let fragment = Fragment {
+            stream: 0,
+            message: 0,
+            index: 0,
+            count: 1,
+            data: vec![7],
+        };
+        let data = Packet {
+            sequence: 2048,
+            ack: None,
+            chunks: vec![Chunk::Reliable(fragment)],
+        }
+        .encode();
+        raw.send(b.local_id(), Channel::Unreliable, &data).unwrap();
+        let event = TransportEvent::Message {
+            peer,
+            channel: Channel::Reliable,
+            data: vec![7],
+        };
+        assert_eq!(b.recv(), Some(event));
+    }
+}
@@ -0,0 +1,140 @@
+use crate::libs::types::bytes::{ByteReader, ByteWriter};
+use crate::libs::types::errors::ErrorStr;
+
+// flags u8: has ack, then sequence u16, ack u16 and ack bits u32
+pub const HEADER_SIZE: usize = 9;
+// the most a chunk adds to its data: flags, stream, message, index, count and length
+pub const CHUNK_OVERHEAD: usize = 1 + 1 + 5 + 3 + 3 + 5;
+
+// one piece of a reliable message, the whole message when count is 1
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fragment {
+    pub stream: u8,
+    pub message: u32,
+    pub index: u16,
+    pub count: u16,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Chunk {
+    Unreliable(Vec<u8>),
+    Reliable(Fragment),
+}
+
+// what actually goes over the unreliable channel. every packet acknowledges the newest packet
+// received from the other side and, bit n of ack_bits, the one n + 1 before it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Packet {
+    pub sequence: u16,
+    // None until anything was received
+    pub ack: Option<(u16, u32)>,
+    pub chunks: Vec<Chunk>,
+}
+
+impl Packet {
+    // chunks follow the header to the end of the packet:
+    //   flags u8: reliable, fragmented
+    //   reliable: stream u8, message varint, index and count varints if fragmented
+    //   length varint and the data
+    pub fn encode(&self) -> Vec<u8> {
+        let size = self.chunks.iter().map(chunk_len).sum::<usize>();
+        let mut writer = ByteWriter::with_capacity(HEADER_SIZE + size);
+        let (ack, ack_bits) = self.ack.unwrap_or((0, 0));
+        writer.write_flags(&[self.ack.is_some()]);
+        writer.write_u16(self.sequence);
+        writer.write_u16(ack);
+        writer.write_u32(ack_bits);
+        for chunk in self.chunks.iter() {
+            match chunk {
+                Chunk::Unreliable(data) => {
+                    writer.write_flags(&[false, false]);
+                    writer.write_varint(data.len() as u64);
+                    writer.write_bytes(data);
+                }
+                Chunk::Reliable(fragment) => {
+                    let fragmented = fragment.count > 1;
+                    writer.write_flags(&[true, fragmented]);
+                    writer.write_u8(fragment.stream);
+                    writer.write_varint(fragment.message as u64);
+                    if fragmented {
+                        writer.write_varint(fragment.index as u64);
+                        writer.write_varint(fragment.count as u64);
+                    }
+                    writer.write_varint(fragment.data.len() as u64);
+                    writer.write_bytes(&fragment.data);
+                }
+            }
+        }
+        writer.into_bytes()
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, ErrorStr> {
+        let mut reader = ByteReader::new(bytes);
+        let [has_ack] = reader.read_flags::<1>()?;
+        let sequence = reader.read_u16()?;
+        let ack = reader.read_u16()?;
+        let ack_bits = reader.read_u32()?;
+        let mut chunks = Vec::new();
+        while !reader.is_empty() {
+            let [reliable, fragmented] = reader.read_flags::<2>()?;
+            if !reliable {
+                if fragmented {
+                    return Err(ErrorStr::new("Fragmented unreliable chunk"));
+                }
+                chunks.push(Chunk::Unreliable(read_data(&mut reader)?));
+                continue;
+            }
+            let stream = reader.read_u8()?;
+            let message = reader.read_varint_as::<u32>()?;
+            let (index, count) = if fragmented {
+                (
+                    reader.read_varint_as::<u16>()?,
+                    reader.read_varint_as::<u16>()?,
+                )
+            } else {
+                (0, 1)
+            };
+            if index >= count {
+                let error = format!("Fragment {} of {}", index, count);
+                return Err(ErrorStr::new(error));
+            }
+            chunks.push(Chunk::Reliable(Fragment {
+                stream,
+                message,
+                index,
+                count,
+                data: read_data(&mut reader)?,
+            }));
+        }
+        Ok(Self {
+            sequence,
+            ack: has_ack.then_some((ack, ack_bits)),
+            chunks,
+        })
+    }
+}
+
+// at most, see CHUNK_OVERHEAD
+pub fn chunk_len(chunk: &Chunk) -> usize {
+    let data = match chunk {
+        Chunk::Unreliable(data) => data,
+        Chunk::Reliable(fragment) => &fragment.data,
+    };
+    data.len() + CHUNK_OVERHEAD
+}
+
+// true if a was sent after b, allowing for wrap around
+#[inline]
+pub fn sequence_newer(a: u16, b: u16) -> bool {
+    a != b && a.wrapping_sub(b) < 0x8000
+}
+
+fn read_data(reader: &mut ByteReader) -> Result<Vec<u8>, ErrorStr> {
+    let len = reader.read_varint()? as usize;
+    if len > reader.remaining() {
+        let error = format!("Chunk of {} bytes with {} left", len, reader.remaining());
+        return Err(ErrorStr::new(error));
+    }
+    Ok(reader.read_bytes(len)?.to_vec())
+}
@@ -0,0 +1,931 @@
+pub mod queue;
+
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::libs::ecs::World;
+use crate::libs::ecs::hash::StateHasher;
+use crate::libs::ecs::schedule::Schedule;
+use crate::libs::input::snapshot::{InputFrame, InputSnapshot, PlayerInputs};
+use crate::libs::net::protocol::{MAX_INPUTS, Message};
+use crate::libs::net::rollback::queue::InputQueue;
+use crate::libs::net::topology::Topology;
+use crate::libs::net::{Channel, PeerId, Transport};
+use crate::libs::types::errors::ErrorStr;
+use crate::{log_debug, log_error};
+
+const LOG_TARGET: &str = "net::rollback";
+
+pub const DEFAULT_INPUT_DELAY: u32 = 2;
+pub const DEFAULT_MAX_ROLLBACK: u32 = 8;
+pub const DEFAULT_CHECKSUM_INTERVAL: u32 = 30;
+// desyncs are reported for this many checksums back
+const CHECKSUM_HISTORY: u64 = 8;
+
+type HashFn = Box<dyn Fn(&World, &mut StateHasher) -> Result<(), ErrorStr>>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RollbackConfig {
+    // local input is applied this many ticks after it's taken. each tick of delay hides a tick
+    // of latency without any rollback
+    pub input_delay: u32,
+    // how many ticks may run on predicted input. further ahead the simulation stalls
+    pub max_rollback: u32,
+    // ticks between state checksums, 0 turns them off
+    pub checksum_interval: u32,
+    // the same on every peer, see topology::choose
+    pub topology: Topology,
+}
+
+impl Default for RollbackConfig {
+    fn default() -> Self {
+        Self {
+            input_delay: DEFAULT_INPUT_DELAY,
+            max_rollback: DEFAULT_MAX_ROLLBACK,
+            checksum_interval: DEFAULT_CHECKSUM_INTERVAL,
+            topology: Topology::Mesh,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Advance {
+    // ran the tick, after resimulating this many ticks for late input
+    Simulated { resimulated: u32 },
+    // too far ahead of someone's input, nothing ran
+    Stalled,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RollbackEvent {
+    // a peer's state hash differs from ours for the same, fully confirmed tick
+    Desync {
+        tick: u64,
+        peer: PeerId,
+        local: u64,
+        remote: u64,
+    },
+    // late input for a tick whose state is gone, so the world can't be corrected. the match
+    // has desynced and the session won't advance any more
+    Unrecoverable {
+        tick: u64,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RollbackStats {
+    pub rollbacks: u64,
+    pub resimulated: u64,
+    pub deepest: u32,
+    pub stalls: u64,
+}
+
+// GGPO style rollback: runs ahead on predicted remote input, keeps the world as it was at the
+// start of each recent tick, and when real input contradicts a prediction rewinds to that tick
+// and resimulates. every peer runs the same schedule from the same world and tick
+pub struct RollbackSession {
+    config: RollbackConfig,
+    start_tick: u64,
+    local_player: u8,
+    // remote peers and their player slots
+    peers: BTreeMap<PeerId, u8>,
+    // peers that left mid match, nothing waits for their input any more
+    departed: BTreeMap<PeerId, u8>,
+    queues: Vec<InputQueue>,
+    // the world at the start of each tick that can still be rolled back to
+    states: VecDeque<(u64, World)>,
+    // the earliest tick a late input proved wrong
+    rollback_to: Option<u64>,
+    // the newest tick of each player's input each peer has without gaps, for the inputs we
+    // send it. only our own player's in a mesh, everyone's the hub relays in a star
+    acks: BTreeMap<(PeerId, u8), u64>,
+    hash: HashFn,
+    next_checksum: u64,
+    checksums: BTreeMap<u64, u64>,
+    remote_checksums: BTreeMap<(u64, PeerId), u64>,
+    events: VecDeque<RollbackEvent>,
+    stats: RollbackStats,
+    // the tick we couldn't roll back to, see RollbackEvent::Unrecoverable
+    failed: Option<u64>,
+}
+
+impl std::fmt::Debug for RollbackSession {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("RollbackSession")
+            .field("config", &self.config)
+            .field("start_tick", &self.start_tick)
+            .field("local_player", &self.local_player)
+            .field("peers", &self.peers)
+            .field("departed", &self.departed)
+            .field("states", &self.states.len())
+            .field("rollback_to", &self.rollback_to)
+            .field("failed", &self.failed)
+            .field("stats", &self.stats)
+            .finish()
+    }
+}
+
+impl RollbackSession {
+    // start_tick is the world's tick when the match starts, the same on every peer. peers maps
+    // every remote peer to its player slot, local_player is ours
+    pub fn new<F>(
+        config: RollbackConfig,
+        start_tick: u64,
+        local_player: u8,
+        peers: BTreeMap<PeerId, u8>,
+        hash: F,
+    ) -> Result<Self, ErrorStr>
+    where
+        F: Fn(&World, &mut StateHasher) -> Result<(), ErrorStr> + 'static,
+    {
+        let players = peers.len() + 1;
+        let mut slots: Vec<u8> = peers.values().copied().collect();
+        slots.push(local_player);
+        slots.sort();
+        slots.dedup();
+        if slots.len() != players || slots.iter().any(|slot| *slot as usize >= players) {
+            let error = format!("Player slots {:?} have to be 0 to {}", slots, players - 1);
+            return Err(ErrorStr::new(error));
+        }
+        if config.max_rollback == 0 {
+            return Err(ErrorStr::new("Rollback needs a window of at least a tick"));
+        }
+        let mut queues = vec![InputQueue::new(start_tick); players];
+        // nothing is pressed during the delay at the start, for all to agree on
+        for tick in start_tick..start_tick + config.input_delay as u64 {
+            let input = InputSnapshot {
+                tick,
+                ..InputSnapshot::default()
+            };
+            queues[local_player as usize].confirm(input);
+        }
+        Ok(Self {
+            config,
+            start_tick,
+            local_player,
+            peers,
+            departed: BTreeMap::new(),
+            queues,
+            states: VecDeque::new(),
+            rollback_to: None,
+            acks: BTreeMap::new(),
+            hash: Box::new(hash),
+            next_checksum: start_tick + config.checksum_interval as u64,
+            checksums: BTreeMap::new(),
+            remote_checksums: BTreeMap::new(),
+            events: VecDeque::new(),
+            stats: RollbackStats::default(),
+            failed: None,
+        })
+    }
+
+    #[inline]
+    pub fn config(&self) -> RollbackConfig {
+        self.config
+    }
+
+    #[inline]
+    pub fn local_player(&self) -> u8 {
+        self.local_player
+    }
+
+    #[inline]
+    pub fn players(&self) -> usize {
+        self.queues.len()
+    }
+
+    #[inline]
+    pub fn stats(&self) -> RollbackStats {
+        self.stats
+    }
+
+    // the next pending event, None once drained
+    #[inline]
+    pub fn recv(&mut self) -> Option<RollbackEvent> {
+        self.events.pop_front()
+    }
+
+    // every tick before this one is simulated with confirmed input only
+    pub fn confirmed_tick(&self) -> u64 {
+        self.present()
+            .map(|queue| first_unconfirmed(queue, self.start_tick))
+            .min()
+            .unwrap_or(self.start_tick)
+    }
+
+    // takes a peer that left out of the match and returns its player slot. its player holds
+    // the last input that was confirmed, peers that got different amounts of it drift apart
+    // and the checksums tell. a star whose hub left falls back to a mesh, as the lobby does
+    pub fn remove_peer(&mut self, peer: PeerId) -> Option<u8> {
+        let slot = self.peers.remove(&peer)?;
+        self.departed.insert(peer, slot);
+        self.acks.retain(|(acked, _), _| *acked != peer);
+        self.remote_checksums.retain(|(_, from), _| *from != peer);
+        if self.config.topology.hub() == Some(peer) {
+            self.config.topology = Topology::Mesh;
+        }
+        Some(slot)
+    }
+
+    // how inputs travel from here on, e.g. what the lobby settled on after the host changed.
+    // the same on every peer, and the hub can't be someone who left
+    pub fn set_topology(&mut self, topology: Topology) -> Result<(), ErrorStr> {
+        if let Some(hub) = topology.hub().filter(|hub| self.departed.contains_key(hub)) {
+            let error = format!("{} left the match, it can't be the hub", hub);
+            return Err(ErrorStr::new(error));
+        }
+        self.config.topology = topology;
+        Ok(())
+    }
+
+    // takes Inputs and Checksum messages from remote peers. returns false for anything else,
+    // so the caller can pass every message through here first
+    pub fn handle_message(&mut self, peer: PeerId, message: &Message) -> Result<bool, ErrorStr> {
+        match message {
+            Message::Inputs {
+                player,
+                ack_tick,
+                inputs,
+                ..
+            } => {
+                let slot = self.slot(peer)?;
+                // the hub passes on everyone's but ours
+                let relayed = self.config.topology.hub() == Some(peer)
+                    && *player != self.local_player
+                    && (*player as usize) < self.queues.len();
+                if *player != slot && !relayed {
+                    let error = format!("{} sent inputs for player {}", peer, player);
+                    return Err(ErrorStr::new(error));
+                }
+                if let Some(ack) = ack_tick {
+                    self.ack(peer, self.local_player, *ack);
+                }
+                for input in inputs.iter() {
+                    if self.queues[*player as usize].confirm(*input) {
+                        let tick = self.rollback_to.map_or(input.tick, |t| t.min(input.tick));
+                        self.rollback_to = Some(tick);
+                    }
+                }
+                Ok(true)
+            }
+            Message::InputAcks { acks } => {
+                self.slot(peer)?;
+                for (player, tick) in acks.iter() {
+                    self.ack(peer, *player, *tick);
+                }
+                Ok(true)
+            }
+            Message::Checksum { tick, hash } => {
+                self.slot(peer)?;
+                match self.checksums.get(tick) {
+                    Some(local) => self.compare(*tick, peer, *local, *hash),
+                    None => {
+                        self.remote_checksums.insert((*tick, peer), *hash);
+                    }
+                }
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    // one tick of the match. local_input is this tick's input, it's applied input_delay ticks
+    // later. call at the fixed tick rate, a stall leaves the world untouched
+    pub fn advance<T>(
+        &mut self,
+        world: &mut World,
+        schedule: &mut Schedule,
+        transport: &mut T,
+        local_input: InputSnapshot,
+    ) -> Result<Advance, ErrorStr>
+    where
+        T: Transport + ?Sized,
+    {
+        if let Some(tick) = self.failed {
+            let error = format!("Desynced, couldn't roll back to tick {}", tick);
+            return Err(ErrorStr::new(error));
+        }
+        let resimulated = self.rollback(world, schedule)?;
+        let tick = world.tick();
+        let waiting = self.present().any(|queue| {
+            tick >= first_unconfirmed(queue, self.start_tick) + self.config.max_rollback as u64
+        });
+        if waiting {
+            self.stats.stalls += 1;
+            // ours may be what they're waiting for in turn
+            self.send_inputs(transport)?;
+            return Ok(Advance::Stalled);
+        }
+        let input = InputSnapshot {
+            tick: tick + self.config.input_delay as u64,
+            ..local_input
+        };
+        self.queues[self.local_player as usize].confirm(input);
+        self.send_inputs(transport)?;
+        self.simulate(world, schedule, tick)?;
+        self.checksum(transport)?;
+        self.prune();
+        Ok(Advance::Simulated { resimulated })
+    }
+
+    fn ack(&mut self, peer: PeerId, player: u8, tick: u64) {
+        let acked = self.acks.entry((peer, player)).or_insert(tick);
+        *acked = (*acked).max(tick);
+    }
+
+    // whether we're the hub of a star, the hub isn't among the remote peers then
+    fn is_hub(&self) -> bool {
+        self.config
+            .topology
+            .hub()
+            .is_some_and(|hub| !self.peers.contains_key(&hub))
+    }
+
+    // the peers we exchange messages with directly
+    fn links(&self) -> Vec<PeerId> {
+        match self.config.topology.hub() {
+            Some(hub) if !self.is_hub() => vec![hub],
+            _ => self.peers.keys().copied().collect(),
+        }
+    }
+
+    // every (peer, player) whose inputs we send to that peer: ours to everyone in a mesh or to
+    // the hub, and as the hub everyone's to everyone else
+    fn routes(&self) -> Vec<(PeerId, u8)> {
+        match self.config.topology.hub() {
+            Some(_) if self.is_hub() => self
+                .peers
+                .iter()
+                .flat_map(|(peer, slot)| {
+                    (0..self.queues.len() as u8)
+                        .filter(move |player| player != slot)
+                        .map(move |player| (*peer, player))
+                })
+                .collect(),
+            Some(hub) => vec![(hub, self.local_player)],
+            None => self
+                .peers
+                .keys()
+                .map(|peer| (*peer, self.local_player))
+                .collect(),
+        }
+    }
+
+    // the queues of the players still in the match
+    fn present(&self) -> impl Iterator<Item = &InputQueue> {
+        self.queues
+            .iter()
+            .enumerate()
+            .filter(|(slot, _)| !self.departed.values().any(|left| *left as usize == *slot))
+            .map(|(_, queue)| queue)
+    }
+
+    fn slot(&self, peer: PeerId) -> Result<u8, ErrorStr> {
+        match self.peers.get(&peer) {
+            Some(slot) => Ok(*slot),
+            None => Err(ErrorStr::new(format!("{} isn't in this match", peer))),
+        }
+    }
+
+    fn rollback(&mut self, world: &mut World, schedule: &mut Schedule) -> Result<u32, ErrorStr> {
+        let from = if let Some(from) = self.rollback_to.take() {
+            from
+        } else {
+            return Ok(0);
+        };
+        let now = world.tick();
+        if from >= now {
+            return Ok(0);
+        }
+        let state = self.states.iter().find(|(tick, _)| *tick == from);
+        *world = if let Some((_, state)) = state {
+            state.clone()
+        } else {
+            // carrying on would leave the world on a wrong prediction for good
+            log_error!(LOG_TARGET, "No state to roll back to tick {}", from);
+            self.failed = Some(from);
+            self.events
+                .push_back(RollbackEvent::Unrecoverable { tick: from });
+            let error = format!("No state to roll back to tick {}", from);
+            return Err(ErrorStr::new(error));
+        };
+        for tick in from..now {
+            self.simulate(world, schedule, tick)?;
+        }
+        let depth = (now - from) as u32;
+        self.stats.rollbacks += 1;
+        self.stats.resimulated += depth as u64;
+        self.stats.deepest = self.stats.deepest.max(depth);
+        log_debug!(LOG_TARGET, "Rolled back {} ticks to {}", depth, from);
+        Ok(depth)
+    }
+
+    fn simulate(
+        &mut self,
+        world: &mut World,
+        schedule: &mut Schedule,
+        tick: u64,
+    ) -> Result<(), ErrorStr> {
+        self.states.retain(|(saved, _)| *saved < tick);
+        self.states.push_back((tick, world.clone()));
+        let start_tick = self.start_tick;
+        let frames: Vec<InputFrame> = self
+            .queues
+            .iter_mut()
+            .map(|queue| {
+                let current = queue.input(tick);
+                let previous = match tick.checked_sub(1) {
+                    Some(previous) if previous >= start_tick => queue.peek(previous),
+                    _ => InputSnapshot::default(),
+                };
+                InputFrame { current, previous }
+            })
+            .collect();
+        world.insert_resource(frames[self.local_player as usize]);
+        world.insert_resource(PlayerInputs { frames });
+        schedule.run(world)
+    }
+
+    // everything each peer hasn't acknowledged, with our ack of theirs. a star's other peers
+    // also ack what the hub relayed
+    fn send_inputs<T>(&mut self, transport: &mut T) -> Result<(), ErrorStr>
+    where
+        T: Transport + ?Sized,
+    {
+        for (peer, player) in self.routes() {
+            if !transport.is_connected(peer) {
+                continue;
+            }
+            let queue = &self.queues[player as usize];
+            let latest = if let Some(latest) = queue.confirmed_until() {
+                latest
+            } else {
+                continue;
+            };
+            let from = self
+                .acks
+                .get(&(peer, player))
+                .map_or(self.start_tick, |ack| ack + 1);
+            // relayed players with nothing new are skipped, ours goes anyway for the ack
+            if from > latest && player != self.local_player {
+                continue;
+            }
+            let inputs: Vec<InputSnapshot> = if from > latest {
+                Vec::new()
+            } else {
+                queue
+                    .confirmed_range(from, latest)
+                    .take(MAX_INPUTS)
+                    .copied()
+                    .collect()
+            };
+            let message = Message::Inputs {
+                player,
+                start_tick: from,
+                ack_tick: self.queues[self.slot(peer)? as usize].confirmed_until(),
+                inputs,
+            };
+            transport.send(peer, Channel::Unreliable, &message.encode()?)?;
+        }
+        let hub = self.config.topology.hub().filter(|_| !self.is_hub());
+        if let Some(hub) = hub.filter(|hub| transport.is_connected(*hub)) {
+            let hub_slot = self.slot(hub)?;
+            let acks = self
+                .queues
+                .iter()
+                .enumerate()
+                .filter(|(player, _)| *player != self.local_player as usize)
+                .filter(|(player, _)| *player != hub_slot as usize)
+                .filter_map(|(player, queue)| Some((player as u8, queue.confirmed_until()?)))
+                .collect();
+            let message = Message::InputAcks { acks };
+            transport.send(hub, Channel::Unreliable, &message.encode()?)?;
+        }
+        Ok(())
+    }
+
+    // hashes every checksum tick whose state is final, i.e. every input before it is confirmed
+    fn checksum<T>(&mut self, transport: &mut T) -> Result<(), ErrorStr>
+    where
+        T: Transport + ?Sized,
+    {
+        if self.config.checksum_interval == 0 {
+            return Ok(());
+        }
+        let confirmed = self.confirmed_tick();
+        while self.next_checksum <= confirmed {
+            let tick = self.next_checksum;
+            self.next_checksum += self.config.checksum_interval as u64;
+            let state = self.states.iter().find(|(saved, _)| *saved == tick);
+            let hash = match state {
+                Some((_, state)) => state.state_hash(&*self.hash)?,
+                // not simulated yet
+                None => {
+                    self.next_checksum = tick;
+                    break;
+                }
+            };
+            self.checksums.insert(tick, hash);
+            let message = Message::Checksum { tick, hash }.encode()?;
+            for peer in self.links() {
+                if transport.is_connected(peer) {
+                    transport.send(peer, Channel::Reliable, &message)?;
+                }
+            }
+            let remote: Vec<(PeerId, u64)> = self
+                .remote_checksums
+                .range((tick, PeerId(0))..=(tick, PeerId(u32::MAX)))
+                .map(|((_, peer), remote)| (*peer, *remote))
+                .collect();
+            for (peer, remote) in remote {
+                self.compare(tick, peer, hash, remote);
+            }
+        }
+        Ok(())
+    }
+
+    fn compare(&mut self, tick: u64, peer: PeerId, local: u64, remote: u64) {
+        if local == remote {
+            return;
+        }
+        log_error!(
+            LOG_TARGET,
+            "Desync with {} at tick {}: {:016x} here, {:016x} there",
+            peer,
+            tick,
+            local,
+            remote
+        );
+        self.events.push_back(RollbackEvent::Desync {
+            tick,
+            peer,
+            local,
+            remote,
+        });
+    }
+
+    // forgets states, inputs and checksums that nothing can refer to any more. states are kept
+    // from the oldest tick someone's input is unconfirmed for, the furthest back late input
+    // can send a rollback, and from the next checksum tick that still needs hashing
+    fn prune(&mut self) {
+        let checksum = (self.config.checksum_interval > 0).then_some(self.next_checksum);
+        let earliest = self
+            .present()
+            .map(|queue| first_unconfirmed(queue, self.start_tick))
+            .chain(self.rollback_to)
+            .chain(checksum)
+            .min()
+            .unwrap_or(self.start_tick);
+        while self
+            .states
+            .front()
+            .is_some_and(|(tick, _)| *tick < earliest)
+        {
+            self.states.pop_front();
+        }
+        // previous inputs are read one tick back, and what we send is resent until acknowledged
+        let mut before = vec![earliest.saturating_sub(1); self.queues.len()];
+        for (peer, player) in self.routes() {
+            let acked = self
+                .acks
+                .get(&(peer, player))
+                .map_or(self.start_tick, |ack| ack + 1);
+            before[player as usize] = before[player as usize].min(acked);
+        }
+        for (queue, before) in self.queues.iter_mut().zip(before) {
+            queue.prune(before);
+        }
+        let history = CHECKSUM_HISTORY * self.config.checksum_interval as u64;
+        let oldest = self.next_checksum.saturating_sub(history);
+        self.checksums = self.checksums.split_off(&oldest);
+        self.remote_checksums = self.remote_checksums.split_off(&(oldest, PeerId(0)));
+    }
+}
+
+fn first_unconfirmed(queue: &InputQueue, start_tick: u64) -> u64 {
+    queue
+        .confirmed_until()
+        .map_or(start_tick, |until| until + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::hash::Hasher;
+
+    use super::*;
+    use crate::libs::net::TransportEvent;
+    use crate::libs::net::loopback::{LoopbackNetwork, LoopbackTransport};
+
+    const TICKS: u64 = 300;
+    const LATENCY: u64 = 4;
+
+    // every player's position, moved by their actions each tick
+    #[derive(Debug, Clone, Default)]
+    struct Positions(Vec<i64>);
+
+    fn movement(world: &mut World) -> Result<(), ErrorStr> {
+        let steps: Vec<i64> = if let Some(inputs) = world.resource::<PlayerInputs>() {
+            inputs
+                .frames
+                .iter()
+                .map(|frame| frame.current.actions as i64 - 1)
+                .collect()
+        } else {
+            return Err(ErrorStr::new("No player inputs"));
+        };
+        if let Some(positions) = world.resource_mut::<Positions>() {
+            for (position, step) in positions.0.iter_mut().zip(steps) {
+                // depends on the order, so a wrong prediction can't cancel out
+                *position = position.wrapping_mul(3).wrapping_add(step);
+            }
+        }
+        Ok(())
+    }
+
+    fn hash(world: &World, hasher: &mut StateHasher) -> Result<(), ErrorStr> {
+        if let Some(positions) = world.resource::<Positions>() {
+            for position in positions.0.iter() {
+                hasher.write_i64(*position);
+            }
+        }
+        Ok(())
+    }
+
+    // changes every few ticks, so holding the last input mispredicts
+    fn scripted(player: u8, tick: u64) -> InputSnapshot {
+        InputSnapshot {
+            tick,
+            actions: (tick / 7 + player as u64) % 3,
+            ..InputSnapshot::default()
+        }
+    }
+
+    struct Peer {
+        transport: LoopbackTransport,
+        session: RollbackSession,
+        world: World,
+        schedule: Schedule<'static>,
+    }
+
+    impl Peer {
+        fn new(
+            transport: LoopbackTransport,
+            player: u8,
+            peers: BTreeMap<PeerId, u8>,
+            topology: Topology,
+        ) -> Self {
+            let mut world = World::new();
+            world.insert_resource(Positions(vec![0; peers.len() + 1]));
+            let config = RollbackConfig {
+                checksum_interval: 10,
+                topology,
+                ..RollbackConfig::default()
+            };
+            Self {
+                transport,
+                session: RollbackSession::new(config, 0, player, peers, hash).unwrap(),
+                world,
+                schedule: Schedule::new().with_system(movement, "Move"),
+            }
+        }
+
+        fn receive(&mut self) {
+            while let Some(event) = self.transport.recv() {
+                if let TransportEvent::Message { peer, data, .. } = event {
+                    let message = Message::decode(&data).unwrap();
+                    assert!(self.session.handle_message(peer, &message).unwrap());
+                }
+            }
+        }
+
+        fn update(&mut self, tick: u64) -> Advance {
+            self.receive();
+            let input = scripted(self.session.local_player(), tick);
+            self.session
+                .advance(
+                    &mut self.world,
+                    &mut self.schedule,
+                    &mut self.transport,
+                    input,
+                )
+                .unwrap()
+        }
+    }
+
+    fn pair() -> (LoopbackNetwork, Peer, Peer) {
+        let network = LoopbackNetwork::new();
+        let (a, b) = (network.add_peer(), network.add_peer());
+        let (a_id, b_id) = (a.local_id(), b.local_id());
+        network.connect(a_id, b_id).unwrap();
+        network.set_latency(LATENCY);
+        let a = Peer::new(a, 0, BTreeMap::from([(b_id, 1)]), Topology::Mesh);
+        let b = Peer::new(b, 1, BTreeMap::from([(a_id, 0)]), Topology::Mesh);
+        (network, a, b)
+    }
+
+    // three players, a as the hub of a star unless it's a mesh. b and c are only connected
+    // through a in a star
+    fn trio(star: bool) -> (LoopbackNetwork, Peer, Peer, Peer) {
+        let network = LoopbackNetwork::new();
+        let (a, b, c) = (network.add_peer(), network.add_peer(), network.add_peer());
+        let (a_id, b_id, c_id) = (a.local_id(), b.local_id(), c.local_id());
+        network.connect(a_id, b_id).unwrap();
+        network.connect(a_id, c_id).unwrap();
+        let topology = if star {
+            Topology::Star { hub: a_id }
+        } else {
+            network.connect(b_id, c_id).unwrap();
+            Topology::Mesh
+        };
+        network.set_latency(LATENCY);
+        let a = Peer::new(a, 0, BTreeMap::from([(b_id, 1), (c_id, 2)]), topology);
+        let b = Peer::new(b, 1, BTreeMap::from([(a_id, 0), (c_id, 2)]), topology);
+        let c = Peer::new(c, 2, BTreeMap::from([(a_id, 0), (b_id, 1)]), topology);
+        (network, a, b, c)
+    }
+
+    // every checksum both have for the same tick matches, and there are a few
+    fn assert_agree(a: &Peer, b: &Peer) {
+        let common: Vec<(&u64, &u64)> = a
+            .session
+            .checksums
+            .iter()
+            .filter(|(tick, _)| b.session.checksums.contains_key(tick))
+            .collect();
+        assert!(common.len() > 2);
+        for (tick, hash) in common {
+            assert_eq!(b.session.checksums[tick], *hash, "tick {}", tick);
+        }
+    }
+
+    #[test]
+    fn agrees_on_checksums_after_rollbacks() {
+        let (network, mut a, mut b) = pair();
+        for tick in 0..TICKS {
+            a.update(tick);
+            b.update(tick);
+            network.step();
+        }
+        for peer in [&mut a, &mut b] {
+            assert!(peer.session.stats().rollbacks > 0);
+            assert_eq!(peer.session.recv(), None);
+        }
+        assert_agree(&a, &b);
+    }
+
+    #[test]
+    fn relays_through_the_hub_of_a_star() {
+        let (network, mut a, mut b, mut c) = trio(true);
+        let (a_id, b_id, c_id) = (
+            a.transport.local_id(),
+            b.transport.local_id(),
+            c.transport.local_id(),
+        );
+        assert!(a.session.is_hub());
+        assert!(!b.session.is_hub() && !c.session.is_hub());
+        assert_eq!(a.session.links(), vec![b_id, c_id]);
+        assert_eq!(b.session.links(), vec![a_id]);
+        assert_eq!(
+            a.session.routes(),
+            vec![(b_id, 0), (b_id, 2), (c_id, 0), (c_id, 1)]
+        );
+        assert_eq!(c.session.routes(), vec![(a_id, 2)]);
+        for tick in 0..TICKS {
+            a.update(tick);
+            b.update(tick);
+            c.update(tick);
+            network.step();
+        }
+        for peer in [&mut a, &mut b, &mut c] {
+            assert!(peer.session.stats().rollbacks > 0);
+            assert_eq!(peer.session.recv(), None);
+        }
+        assert_agree(&a, &b);
+        assert_agree(&a, &c);
+        // b and c ack each other's relayed inputs to the hub, two hops there and one back
+        let end = TICKS - 3 * LATENCY;
+        assert!(a.session.acks[&(b_id, 2)] > end);
+        assert!(a.session.acks[&(c_id, 1)] > end);
+        // only the hub may send someone else's inputs, and never our own back
+        let inputs = |player| Message::Inputs {
+            player,
+            start_tick: TICKS,
+            ack_tick: None,
+            inputs: Vec::new(),
+        };
+        assert!(b.session.handle_message(c_id, &inputs(0)).is_err());
+        assert!(b.session.handle_message(a_id, &inputs(1)).is_err());
+        assert!(b.session.handle_message(a_id, &inputs(2)).unwrap());
+    }
+
+    #[test]
+    fn carries_on_without_a_departed_peer() {
+        let (network, mut a, mut b, mut c) = trio(false);
+        let c_id = c.transport.local_id();
+        for tick in 0..TICKS / 2 {
+            a.update(tick);
+            b.update(tick);
+            c.update(tick);
+            network.step();
+        }
+        // c's last inputs reach both before it goes, so they hold the same one
+        for _ in 0..=LATENCY {
+            network.step();
+            a.receive();
+            b.receive();
+        }
+        drop(c);
+        network.step();
+        for peer in [&mut a, &mut b] {
+            assert_eq!(peer.session.remove_peer(c_id), Some(2));
+            assert_eq!(peer.session.remove_peer(c_id), None);
+            assert!(
+                peer.session
+                    .set_topology(Topology::Star { hub: c_id })
+                    .is_err()
+            );
+        }
+        let stalls = a.session.stats().stalls;
+        for tick in TICKS / 2..TICKS {
+            a.update(tick);
+            b.update(tick);
+            network.step();
+        }
+        // no more than waiting on each other
+        assert!(a.session.stats().stalls - stalls < LATENCY);
+        assert!(a.session.confirmed_tick() > TICKS - 2 * LATENCY);
+        for peer in [&mut a, &mut b] {
+            assert_eq!(peer.session.recv(), None);
+        }
+        assert_agree(&a, &b);
+    }
+
+    #[test]
+    fn falls_back_to_a_mesh_when_the_hub_leaves() {
+        let (network, mut a, mut b, mut c) = trio(true);
+        let a_id = a.transport.local_id();
+        for tick in 0..TICKS / 2 {
+            a.update(tick);
+            b.update(tick);
+            c.update(tick);
+            network.step();
+        }
+        for _ in 0..=LATENCY {
+            network.step();
+            b.receive();
+            c.receive();
+        }
+        drop(a);
+        network
+            .connect(b.transport.local_id(), c.transport.local_id())
+            .unwrap();
+        for peer in [&mut b, &mut c] {
+            assert_eq!(peer.session.remove_peer(a_id), Some(0));
+            assert_eq!(peer.session.config().topology, Topology::Mesh);
+        }
+        for tick in TICKS / 2..TICKS {
+            b.update(tick);
+            c.update(tick);
+            network.step();
+        }
+        assert!(b.session.confirmed_tick() > TICKS - 2 * LATENCY);
+        assert_agree(&b, &c);
+    }
+
+    #[test]
+    fn reports_a_lost_state_as_unrecoverable() {
+        let (network, mut a, mut b) = pair();
+        for tick in 0..60 {
+            a.update(tick);
+            b.update(tick);
+            network.step();
+        }
+        // as if late input had arrived for a tick long since pruned
+        a.session.rollback_to = Some(1);
+        let input = scripted(0, 60);
+        let result = a
+            .session
+            .advance(&mut a.world, &mut a.schedule, &mut a.transport, input);
+        assert!(result.is_err());
+        assert_eq!(
+            a.session.recv(),
+            Some(RollbackEvent::Unrecoverable { tick: 1 })
+        );
+        let result = a
+            .session
+            .advance(&mut a.world, &mut a.schedule, &mut a.transport, input);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn prunes_states_without_checksums() {
+        let (network, mut a, mut b) = pair();
+        a.session.config.checksum_interval = 0;
+        for tick in 0..TICKS {
+            a.update(tick);
+            b.update(tick);
+            network.step();
+        }
+        let bound = (LATENCY + a.session.config.max_rollback as u64) * 2;
+        assert!((a.session.states.len() as u64) < bound);
+    }
+}
@@ -0,0 +1,103 @@
+use std::collections::BTreeMap;
+
+use crate::libs::input::snapshot::InputSnapshot;
+
+// one player's inputs by tick: the confirmed ones, and what was guessed for ticks simulated
+// before their input arrived
+#[derive(Debug, Clone, Default)]
+pub struct InputQueue {
+    confirmed: BTreeMap<u64, InputSnapshot>,
+    predicted: BTreeMap<u64, InputSnapshot>,
+    // every tick from the start up to this one is confirmed
+    confirmed_until: Option<u64>,
+    start_tick: u64,
+}
+
+impl InputQueue {
+    pub fn new(start_tick: u64) -> Self {
+        Self {
+            start_tick,
+            ..Self::default()
+        }
+    }
+
+    #[inline]
+    pub fn confirmed_until(&self) -> Option<u64> {
+        self.confirmed_until
+    }
+
+    #[inline]
+    pub fn confirmed(&self, tick: u64) -> Option<&InputSnapshot> {
+        self.confirmed.get(&tick)
+    }
+
+    // confirmed inputs in [from, to], in tick order
+    pub fn confirmed_range(&self, from: u64, to: u64) -> impl Iterator<Item = &InputSnapshot> {
+        self.confirmed.range(from..=to).map(|(_, input)| input)
+    }
+
+    // returns true when the input contradicts what was predicted for its tick, which means
+    // everything simulated from that tick on is wrong
+    pub fn confirm(&mut self, input: InputSnapshot) -> bool {
+        let tick = input.tick;
+        if tick < self.start_tick || self.confirmed.contains_key(&tick) {
+            return false;
+        }
+        self.confirmed.insert(tick, input);
+        let mut next = self
+            .confirmed_until
+            .map_or(self.start_tick, |until| until + 1);
+        while self.confirmed.contains_key(&next) {
+            self.confirmed_until = Some(next);
+            next += 1;
+        }
+        match self.predicted.remove(&tick) {
+            Some(predicted) => !same_input(&predicted, &input),
+            None => false,
+        }
+    }
+
+    // the input to simulate tick with. without a confirmed one the last known input is held,
+    // players mostly keep doing what they were doing
+    pub fn input(&mut self, tick: u64) -> InputSnapshot {
+        if let Some(input) = self.confirmed.get(&tick) {
+            return *input;
+        }
+        let predicted = self.peek(tick);
+        self.predicted.insert(tick, predicted);
+        predicted
+    }
+
+    // like input, without remembering the guess
+    pub fn peek(&self, tick: u64) -> InputSnapshot {
+        if let Some(input) = self.confirmed.get(&tick) {
+            return *input;
+        }
+        let last = self
+            .confirmed
+            .range(..tick)
+            .next_back()
+            .map(|(_, input)| *input)
+            .unwrap_or_default();
+        InputSnapshot { tick, ..last }
+    }
+
+    // drops what no rollback can reach any more, keeping the newest confirmed input before
+    // the cut for predictions
+    pub fn prune(&mut self, before: u64) {
+        let before = before.min(self.confirmed_until.map_or(self.start_tick, |until| until));
+        let kept = self.confirmed.split_off(&before);
+        if let Some((tick, input)) = self.confirmed.pop_last() {
+            self.confirmed = BTreeMap::from([(tick, input)]);
+        } else {
+            self.confirmed.clear();
+        }
+        self.confirmed.extend(kept);
+        self.predicted = self.predicted.split_off(&before);
+    }
+}
+
+// ticks differ between every snapshot, only the contents count
+pub fn same_input(a: &InputSnapshot, b: &InputSnapshot) -> bool {
+    a.actions == b.actions && a.axes == b.axes && a.pointer == b.pointer
+}
@@ -0,0 +1,331 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::libs::net::{PeerId, SyncMode};
+use crate::libs::net::signaling::protocol::{
+    self, ClientMessage, ErrorCode, PROTOCOL_VERSION, PeerInfo, ServerMessage,
+};
+use crate::libs::net::signaling::{SignalingSocket, SocketEvent};
+use crate::libs::net::webrtc::Signal;
+use crate::libs::types::errors::ErrorStr;
+use crate::libs::types::shared::{SharedRefCell, shared_ref_cell};
+
+#[derive(Debug, Default)]
+struct Client {
+    open: bool,
+    inbox: VecDeque<SocketEvent>,
+    // room and who this client is in it, once joined
+    member: Option<Member>,
+}
+
+#[derive(Debug, Clone)]
+struct Member {
+    room: String,
+    info: PeerInfo,
+    // the room's, copied to everyone in it
+    mode: SyncMode,
+}
+
+#[derive(Debug)]
+struct Hub {
+    online: bool,
+    room_size: usize,
+    next_client: u32,
+    next_peer: u32,
+    clients: BTreeMap<u32, Client>,
+}
+
+impl Hub {
+    fn push(&mut self, client: u32, message: &ServerMessage) {
+        let text = match protocol::encode(message) {
+            Ok(text) => text,
+            Err(_) => return,
+        };
+        let client = self.clients.get_mut(&client).filter(|client| client.open);
+        if let Some(client) = client {
+            client.inbox.push_back(SocketEvent::Message(text));
+        }
+    }
+
+    fn error<S>(&mut self, client: u32, code: ErrorCode, message: S)
+    where
+        S: Into<String>,
+    {
+        let message = ServerMessage::Error {
+            code,
+            message: message.into(),
+        };
+        self.push(client, &message);
+    }
+
+    // clients in the room other than `except`
+    fn members(&self, room: &str, except: u32) -> Vec<(u32, Member)> {
+        self.clients
+            .iter()
+            .filter(|(id, _)| **id != except)
+            .filter_map(|(id, client)| match client.member.as_ref() {
+                Some(member) if member.room == room => Some((*id, member.clone())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn handle(&mut self, client: u32, text: &str) {
+        if protocol::version(text).is_some_and(|version| version != PROTOCOL_VERSION) {
+            let message = format!("Server speaks protocol version {}", PROTOCOL_VERSION);
+            self.error(client, ErrorCode::Version, message);
+            return;
+        }
+        let message = match protocol::decode::<ClientMessage>(text) {
+            Ok(message) => message,
+            Err(error) => {
+                self.error(client, ErrorCode::BadMessage, error.to_string());
+                return;
+            }
+        };
+        match message {
+            ClientMessage::Join { room, name, mode } => self.join(client, room, name, mode),
+            ClientMessage::Offer { to, sdp } => self.relay(client, to, Signal::Offer { sdp }),
+            ClientMessage::Answer { to, sdp } => self.relay(client, to, Signal::Answer { sdp }),
+            ClientMessage::Candidate {
+                to,
+                candidate,
+                sdp_mid,
+                sdp_m_line_index,
+            } => {
+                let signal = Signal::Candidate {
+                    candidate,
+                    sdp_mid,
+                    sdp_m_line_index,
+                };
+                self.relay(client, to, signal)
+            }
+            ClientMessage::Leave => self.leave(client),
+        }
+    }
+
+    fn join(&mut self, client: u32, room: String, name: String, mode: SyncMode) {
+        if self
+            .clients
+            .get(&client)
+            .is_some_and(|c| c.member.is_some())
+        {
+            self.error(client, ErrorCode::BadMessage, "Already in a room");
+            return;
+        }
+        if let Err(error) = protocol::check_join(&room, &name) {
+            self.error(client, ErrorCode::BadMessage, error.to_string());
+            return;
+        }
+        let others = self.members(&room, client);
+        if others.len() >= self.room_size {
+            let message = format!("Room {} is full", room);
+            self.error(client, ErrorCode::RoomFull, message);
+            return;
+        }
+        self.next_peer += 1;
+        let info = PeerInfo {
+            id: PeerId(self.next_peer),
+            name,
+        };
+        // whoever created the room decided
+        let mode = others.first().map_or(mode, |(_, other)| other.mode);
+        for (other, _) in others.iter() {
+            let message = ServerMessage::PeerJoined { peer: info.clone() };
+            self.push(*other, &message);
+        }
+        if let Some(joined) = self.clients.get_mut(&client) {
+            joined.member = Some(Member {
+                room: room.clone(),
+                info: info.clone(),
+                mode,
+            });
+        }
+        let message = ServerMessage::Joined {
+            room,
+            id: info.id,
+            mode,
+            peers: others.into_iter().map(|(_, other)| other.info).collect(),
+        };
+        self.push(client, &message);
+    }
+
+    fn relay(&mut self, client: u32, to: PeerId, signal: Signal) {
+        let (room, from) = match self.clients.get(&client).and_then(|c| c.member.as_ref()) {
+            Some(member) => (member.room.clone(), member.info.id),
+            None => {
+                self.error(client, ErrorCode::BadMessage, "Join a room first");
+                return;
+            }
+        };
+        let target = self
+            .members(&room, client)
+            .into_iter()
+            .find(|(_, other)| other.info.id == to);
+        match target {
+            Some((target, _)) => self.push(target, &ServerMessage::signal(from, signal)),
+            None => {
+                let message = format!("No {} in room {}", to, room);
+                self.error(client, ErrorCode::UnknownPeer, message);
+            }
+        }
+    }
+
+    fn leave(&mut self, client: u32) {
+        let member = self
+            .clients
+            .get_mut(&client)
+            .and_then(|client| client.member.take());
+        if let Some(member) = member {
+            for (other, _) in self.members(&member.room, client) {
+                let message = ServerMessage::PeerLeft { id: member.info.id };
+                self.push(other, &message);
+            }
+        }
+    }
+
+    fn disconnect<S>(&mut self, client: u32, reason: S)
+    where
+        S: Into<String>,
+    {
+        self.leave(client);
+        let client = self.clients.get_mut(&client).filter(|client| client.open);
+        if let Some(client) = client {
+            client.open = false;
+            client.inbox.push_back(SocketEvent::Closed(reason.into()));
+        }
+    }
+}
+
+// an in process stand in for the signaling server, for tests and offline play. speaks the
+// same protocol and enforces the same rules as the real one
+#[derive(Debug, Clone)]
+pub struct LocalSignalingServer {
+    hub: SharedRefCell<Hub>,
+}
+
+impl Default for LocalSignalingServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LocalSignalingServer {
+    pub fn new() -> Self {
+        Self::with_room_size(usize::MAX)
+    }
+
+    pub fn with_room_size(room_size: usize) -> Self {
+        Self {
+            hub: shared_ref_cell(Hub {
+                online: true,
+                room_size,
+                next_client: 0,
+                next_peer: 0,
+                clients: BTreeMap::new(),
+            }),
+        }
+    }
+
+    // an unconnected socket to this server
+    pub fn socket(&self) -> LocalSocket {
+        let mut hub = self.hub.borrow_mut();
+        hub.next_client += 1;
+        let id = hub.next_client;
+        hub.clients.insert(id, Client::default());
+        LocalSocket {
+            hub: self.hub.clone(),
+            id,
+        }
+    }
+
+    // going offline drops every connection and refuses new ones, for exercising reconnects
+    pub fn set_online(&self, online: bool) {
+        let mut hub = self.hub.borrow_mut();
+        hub.online = online;
+        if !online {
+            let clients: Vec<u32> = hub.clients.keys().copied().collect();
+            for client in clients {
+                hub.disconnect(client, "server went offline");
+            }
+        }
+    }
+
+    #[inline]
+    pub fn online(&self) -> bool {
+        self.hub.borrow().online
+    }
+
+    // who is in the room, in id order
+    pub fn room(&self, room: &str) -> Vec<PeerInfo> {
+        // client ids start at 1, so 0 excludes nobody
+        let mut peers: Vec<PeerInfo> = self
+            .hub
+            .borrow()
+            .members(room, 0)
+            .into_iter()
+            .map(|(_, member)| member.info)
+            .collect();
+        peers.sort_by_key(|info| info.id);
+        peers
+    }
+}
+
+#[derive(Debug)]
+pub struct LocalSocket {
+    hub: SharedRefCell<Hub>,
+    id: u32,
+}
+
+impl Drop for LocalSocket {
+    fn drop(&mut self) {
+        let mut hub = self.hub.borrow_mut();
+        hub.leave(self.id);
+        hub.clients.remove(&self.id);
+    }
+}
+
+impl SignalingSocket for LocalSocket {
+    fn connect(&mut self) -> Result<(), ErrorStr> {
+        let mut hub = self.hub.borrow_mut();
+        hub.disconnect(self.id, "reconnecting");
+        let online = hub.online;
+        if let Some(client) = hub.clients.get_mut(&self.id) {
+            client.inbox.clear();
+            if online {
+                client.open = true;
+                client.inbox.push_back(SocketEvent::Open);
+            } else {
+                client
+                    .inbox
+                    .push_back(SocketEvent::Closed("server is offline".to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    fn send(&mut self, text: &str) -> Result<(), ErrorStr> {
+        let mut hub = self.hub.borrow_mut();
+        if !hub.clients.get(&self.id).is_some_and(|client| client.open) {
+            return Err(ErrorStr::new("Signaling socket is not open"));
+        }
+        hub.handle(self.id, text);
+        Ok(())
+    }
+
+    fn recv(&mut self) -> Option<SocketEvent> {
+        self.hub
+            .borrow_mut()
+            .clients
+            .get_mut(&self.id)
+            .and_then(|client| client.inbox.pop_front())
+    }
+
+    fn close(&mut self) {
+        let mut hub = self.hub.borrow_mut();
+        hub.leave(self.id);
+        if let Some(client) = hub.clients.get_mut(&self.id) {
+            client.open = false;
+            client.inbox.clear();
+        }
+    }
+}
@@ -0,0 +1,428 @@
+pub mod local;
+pub mod protocol;
+pub mod websocket;
+
+use std::collections::VecDeque;
+
+use crate::libs::net::{PeerId, SyncMode, Transport};
+use crate::libs::net::signaling::protocol::{
+    ClientMessage, ErrorCode, PROTOCOL_VERSION, PeerInfo, ServerMessage,
+};
+use crate::libs::net::webrtc::{Signal, WebRtcTransport};
+use crate::libs::types::errors::ErrorStr;
+use crate::libs::types::rng::Rng;
+use crate::{log_debug, log_info, log_warn};
+
+const LOG_TARGET: &str = "net::signaling";
+pub const DEFAULT_MIN_BACKOFF_MS: f64 = 500.0;
+pub const DEFAULT_MAX_BACKOFF_MS: f64 = 15_000.0;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SocketEvent {
+    Open,
+    Message(String),
+    // also sent when a connect attempt fails
+    Closed(String),
+}
+
+// a text connection to the signaling server. implemented over a WebSocket in the browser and
+// in process by LocalSignalingServer, so the client can be exercised without a server
+pub trait SignalingSocket {
+    // starts a new connection, dropping any current one. Open or Closed follows through recv
+    fn connect(&mut self) -> Result<(), ErrorStr>;
+
+    fn send(&mut self, text: &str) -> Result<(), ErrorStr>;
+
+    // the next pending event, None once drained
+    fn recv(&mut self) -> Option<SocketEvent>;
+
+    // no Closed event follows
+    fn close(&mut self);
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SignalingConfig {
+    pub room: String,
+    // shown to the other peers
+    pub name: String,
+    // asked for when creating the room, joining an existing one gets its mode
+    pub mode: SyncMode,
+    pub min_backoff_ms: f64,
+    pub max_backoff_ms: f64,
+    // None retries forever
+    pub max_attempts: Option<u32>,
+}
+
+impl SignalingConfig {
+    pub fn new<S, N>(room: S, name: N) -> Self
+    where
+        S: Into<String>,
+        N: Into<String>,
+    {
+        Self {
+            room: room.into(),
+            name: name.into(),
+            mode: SyncMode::default(),
+            min_backoff_ms: DEFAULT_MIN_BACKOFF_MS,
+            max_backoff_ms: DEFAULT_MAX_BACKOFF_MS,
+            max_attempts: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SignalingEvent {
+    // joined or rejoined the room. the id is new after every reconnect
+    Joined {
+        id: PeerId,
+        mode: SyncMode,
+        peers: Vec<PeerInfo>,
+    },
+    PeerJoined(PeerInfo),
+    PeerLeft(PeerId),
+    Signal {
+        from: PeerId,
+        signal: Signal,
+    },
+    // from the server, or a local failure when code is None
+    Error {
+        code: Option<ErrorCode>,
+        message: String,
+    },
+    // lost the server. retry_in_ms is None when the client gave up
+    Disconnected {
+        retry_in_ms: Option<f64>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SignalingState {
+    Idle,
+    Connecting,
+    // connected, waiting for the reply to join
+    Joining,
+    Joined(PeerId),
+    // reconnecting once the time passes
+    Waiting { until_ms: f64 },
+    // a fatal error or out of attempts, start has to be called again
+    Failed,
+}
+
+// joins a room on the signaling server and relays WebRTC signals through it. polled: call
+// update every frame with the current time, then drain recv
+pub struct SignalingClient<S>
+where
+    S: SignalingSocket,
+{
+    socket: S,
+    config: SignalingConfig,
+    state: SignalingState,
+    // failed connects since the last successful join
+    attempts: u32,
+    peers: Vec<PeerInfo>,
+    // the room's, known once joined
+    mode: Option<SyncMode>,
+    events: VecDeque<SignalingEvent>,
+    // jitter for the backoff, so a server restart isn't hit by every client at once
+    rng: Rng,
+}
+
+impl<S> std::fmt::Debug for SignalingClient<S>
+where
+    S: SignalingSocket,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("SignalingClient")
+            .field("config", &self.config)
+            .field("state", &self.state)
+            .field("attempts", &self.attempts)
+            .field("peers", &self.peers)
+            .field("events", &self.events.len())
+            .finish()
+    }
+}
+
+impl<S> SignalingClient<S>
+where
+    S: SignalingSocket,
+{
+    pub fn new(socket: S, config: SignalingConfig, seed: u64) -> Self {
+        Self {
+            socket,
+            config,
+            state: SignalingState::Idle,
+            attempts: 0,
+            peers: Vec::new(),
+            mode: None,
+            events: VecDeque::new(),
+            rng: Rng::new(seed),
+        }
+    }
+
+    // connects and joins the configured room. also restarts after Failed
+    pub fn start(&mut self, now_ms: f64) {
+        // the server would turn the join away on every attempt
+        if let Err(error) = protocol::check_join(&self.config.room, &self.config.name) {
+            self.error(None, error.to_string());
+            self.fail();
+            return;
+        }
+        self.attempts = 0;
+        self.connect(now_ms);
+    }
+
+    // tells the server we're going and stops reconnecting
+    pub fn leave(&mut self) {
+        if let SignalingState::Joined(_) = self.state {
+            let _ = self.send(&ClientMessage::Leave);
+        }
+        self.socket.close();
+        self.peers.clear();
+        self.state = SignalingState::Idle;
+    }
+
+    #[inline]
+    pub fn state(&self) -> SignalingState {
+        self.state
+    }
+
+    #[inline]
+    pub fn local_id(&self) -> Option<PeerId> {
+        match self.state {
+            SignalingState::Joined(id) => Some(id),
+            _ => None,
+        }
+    }
+
+    // how the room syncs, once joined
+    #[inline]
+    pub fn mode(&self) -> Option<SyncMode> {
+        self.mode
+    }
+
+    // the other peers in the room, in id order
+    #[inline]
+    pub fn peers(&self) -> &[PeerInfo] {
+        &self.peers
+    }
+
+    #[inline]
+    pub fn config(&self) -> &SignalingConfig {
+        &self.config
+    }
+
+    // only while joined, signals for a previous connection are meaningless after a reconnect
+    pub fn send_signal(&mut self, to: PeerId, signal: Signal) -> Result<(), ErrorStr> {
+        if self.local_id().is_none() {
+            return Err(ErrorStr::new("Not in a signaling room"));
+        }
+        self.send(&ClientMessage::signal(to, signal))
+    }
+
+    // the next pending event, None once drained
+    #[inline]
+    pub fn recv(&mut self) -> Option<SignalingEvent> {
+        self.events.pop_front()
+    }
+
+    // handles socket events and reconnects when the backoff has passed. now_ms is on any
+    // steadily increasing clock, e.g. performance.now()
+    pub fn update(&mut self, now_ms: f64) {
+        while let Some(event) = self.socket.recv() {
+            match event {
+                SocketEvent::Open => self.opened(),
+                SocketEvent::Message(text) => self.message(&text),
+                SocketEvent::Closed(reason) => self.closed(&reason, now_ms),
+            }
+        }
+        match self.state {
+            SignalingState::Waiting { until_ms } if now_ms >= until_ms => self.connect(now_ms),
+            _ => {}
+        }
+    }
+
+    // passes the transport's outgoing offers, answers and candidates to the server
+    pub fn flush_signals(&mut self, transport: &mut WebRtcTransport) -> Result<(), ErrorStr> {
+        for (to, signal) in transport.take_signals() {
+            self.send_signal(to, signal)?;
+        }
+        Ok(())
+    }
+
+    fn connect(&mut self, now_ms: f64) {
+        self.peers.clear();
+        self.state = SignalingState::Connecting;
+        if let Err(error) = self.socket.connect() {
+            // retried like any other failed attempt
+            self.closed(&error.to_string(), now_ms);
+        }
+    }
+
+    fn send(&mut self, message: &ClientMessage) -> Result<(), ErrorStr> {
+        let text = protocol::encode(message)?;
+        self.socket.send(&text)
+    }
+
+    fn error(&mut self, code: Option<ErrorCode>, message: String) {
+        log_warn!(LOG_TARGET, "{}", message);
+        self.events
+            .push_back(SignalingEvent::Error { code, message });
+    }
+
+    fn opened(&mut self) {
+        if self.state != SignalingState::Connecting {
+            return;
+        }
+        log_debug!(LOG_TARGET, "Connected, joining room {}", self.config.room);
+        let join = ClientMessage::Join {
+            room: self.config.room.clone(),
+            name: self.config.name.clone(),
+            mode: self.config.mode,
+        };
+        match self.send(&join) {
+            Ok(_) => self.state = SignalingState::Joining,
+            Err(error) => self.error(None, error.to_string()),
+        }
+    }
+
+    fn message(&mut self, text: &str) {
+        let message = match protocol::decode::<ServerMessage>(text) {
+            Ok(message) => message,
+            Err(error) => {
+                let mismatch = protocol::version(text) != Some(PROTOCOL_VERSION);
+                self.error(None, error.to_string());
+                if mismatch {
+                    // every later message would be the same
+                    self.fail();
+                }
+                return;
+            }
+        };
+        let message = match message.into_signal() {
+            Ok((from, signal)) => {
+                self.events
+                    .push_back(SignalingEvent::Signal { from, signal });
+                return;
+            }
+            Err(message) => message,
+        };
+        match message {
+            ServerMessage::Joined {
+                room,
+                id,
+                mode,
+                mut peers,
+            } => {
+                log_info!(LOG_TARGET, "Joined {:?} room {} as {}", mode, room, id);
+                peers.sort_by_key(|peer| peer.id);
+                self.attempts = 0;
+                self.state = SignalingState::Joined(id);
+                self.mode = Some(mode);
+                self.peers = peers.clone();
+                let event = SignalingEvent::Joined { id, mode, peers };
+                self.events.push_back(event);
+            }
+            ServerMessage::PeerJoined { peer } => {
+                log_debug!(LOG_TARGET, "{} ({}) joined", peer.id, peer.name);
+                let index = self.peers.partition_point(|other| other.id < peer.id);
+                self.peers.insert(index, peer.clone());
+                self.events.push_back(SignalingEvent::PeerJoined(peer));
+            }
+            ServerMessage::PeerLeft { id } => {
+                log_debug!(LOG_TARGET, "{} left", id);
+                self.peers.retain(|peer| peer.id != id);
+                self.events.push_back(SignalingEvent::PeerLeft(id));
+            }
+            ServerMessage::Error { code, message } => {
+                self.error(Some(code), message);
+                if code.is_fatal() {
+                    self.fail();
+                }
+            }
+            // taken by into_signal above
+            ServerMessage::Offer { .. }
+            | ServerMessage::Answer { .. }
+            | ServerMessage::Candidate { .. } => {}
+        }
+    }
+
+    fn closed(&mut self, reason: &str, now_ms: f64) {
+        match self.state {
+            SignalingState::Idle | SignalingState::Failed => return,
+            _ => {}
+        }
+        self.peers.clear();
+        self.attempts += 1;
+        let exhausted = self
+            .config
+            .max_attempts
+            .is_some_and(|max| self.attempts > max);
+        if exhausted {
+            log_warn!(LOG_TARGET, "Signaling {}, giving up", reason);
+            self.state = SignalingState::Failed;
+            let event = SignalingEvent::Disconnected { retry_in_ms: None };
+            self.events.push_back(event);
+            return;
+        }
+        let delay = self.backoff();
+        log_info!(
+            LOG_TARGET,
+            "Signaling {}, reconnecting in {:.0}ms",
+            reason,
+            delay
+        );
+        self.state = SignalingState::Waiting {
+            until_ms: now_ms + delay,
+        };
+        let event = SignalingEvent::Disconnected {
+            retry_in_ms: Some(delay),
+        };
+        self.events.push_back(event);
+    }
+
+    fn fail(&mut self) {
+        self.socket.close();
+        self.peers.clear();
+        self.state = SignalingState::Failed;
+        let event = SignalingEvent::Disconnected { retry_in_ms: None };
+        self.events.push_back(event);
+    }
+
+    // doubles per attempt up to the cap, then a random half to full of that
+    fn backoff(&mut self) -> f64 {
+        let exponent = self.attempts.saturating_sub(1).min(16) as i32;
+        let delay =
+            (self.config.min_backoff_ms * 2f64.powi(exponent)).min(self.config.max_backoff_ms);
+        delay * self.rng.range_f32(0.5, 1.0) as f64
+    }
+}
+
+// feeds a signaling event to the transport. the newcomer offers to everyone already in the
+// room, offers from others get answered, and peers the server saw leave are dropped
+pub fn apply_event(
+    transport: &mut WebRtcTransport,
+    event: &SignalingEvent,
+) -> Result<(), ErrorStr> {
+    match event {
+        SignalingEvent::Joined { id, peers, .. } => {
+            if *id != transport.local_id() {
+                let error = format!(
+                    "Transport belongs to {}, the room assigned {}",
+                    transport.local_id(),
+                    id
+                );
+                return Err(ErrorStr::new(error));
+            }
+            for peer in peers.iter() {
+                transport.connect(peer.id)?;
+            }
+            Ok(())
+        }
+        SignalingEvent::Signal { from, signal } => transport.handle_signal(*from, signal.clone()),
+        SignalingEvent::PeerLeft(id) => {
+            transport.disconnect(*id);
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,304 @@
+use serde::{Deserialize, Serialize};
+
+use crate::libs::net::{PeerId, SyncMode};
+use crate::libs::net::webrtc::Signal;
+use crate::libs::types::errors::ErrorStr;
+
+// bumped on any incompatible change to the messages below. the server rejects other versions
+pub const PROTOCOL_VERSION: u32 = 1;
+// in bytes, the server turns longer ones away
+pub const MAX_ROOM_LEN: usize = 64;
+pub const MAX_NAME_LEN: usize = 32;
+
+// every message is a JSON object {"v": PROTOCOL_VERSION, "type": ..., ...fields}
+#[derive(Debug, Serialize, Deserialize)]
+struct Envelope<T> {
+    v: u32,
+    #[serde(flatten)]
+    message: T,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    Join {
+        room: String,
+        name: String,
+        // only used when the join creates the room
+        #[serde(default)]
+        mode: SyncMode,
+    },
+    Offer {
+        to: PeerId,
+        sdp: String,
+    },
+    Answer {
+        to: PeerId,
+        sdp: String,
+    },
+    Candidate {
+        to: PeerId,
+        candidate: String,
+        sdp_mid: Option<String>,
+        sdp_m_line_index: Option<u16>,
+    },
+    Leave,
+}
+
+impl ClientMessage {
+    pub fn signal(to: PeerId, signal: Signal) -> Self {
+        match signal {
+            Signal::Offer { sdp } => Self::Offer { to, sdp },
+            Signal::Answer { sdp } => Self::Answer { to, sdp },
+            Signal::Candidate {
+                candidate,
+                sdp_mid,
+                sdp_m_line_index,
+            } => Self::Candidate {
+                to,
+                candidate,
+                sdp_mid,
+                sdp_m_line_index,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PeerInfo {
+    pub id: PeerId,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    // the client speaks another protocol version, reconnecting won't help
+    Version,
+    RoomFull,
+    // to names a peer that isn't in the room
+    UnknownPeer,
+    // unparseable or out of order, e.g. an offer before joining
+    BadMessage,
+}
+
+impl ErrorCode {
+    #[inline]
+    pub fn is_fatal(&self) -> bool {
+        matches!(self, ErrorCode::Version | ErrorCode::RoomFull)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    // reply to join. peers are everyone already in the room
+    Joined {
+        room: String,
+        id: PeerId,
+        #[serde(default)]
+        mode: SyncMode,
+        peers: Vec<PeerInfo>,
+    },
+    PeerJoined {
+        peer: PeerInfo,
+    },
+    PeerLeft {
+        id: PeerId,
+    },
+    Offer {
+        from: PeerId,
+        sdp: String,
+    },
+    Answer {
+        from: PeerId,
+        sdp: String,
+    },
+    Candidate {
+        from: PeerId,
+        candidate: String,
+        sdp_mid: Option<String>,
+        sdp_m_line_index: Option<u16>,
+    },
+    Error {
+        code: ErrorCode,
+        message: String,
+    },
+}
+
+impl ServerMessage {
+    pub fn signal(from: PeerId, signal: Signal) -> Self {
+        match signal {
+            Signal::Offer { sdp } => Self::Offer { from, sdp },
+            Signal::Answer { sdp } => Self::Answer { from, sdp },
+            Signal::Candidate {
+                candidate,
+                sdp_mid,
+                sdp_m_line_index,
+            } => Self::Candidate {
+                from,
+                candidate,
+                sdp_mid,
+                sdp_m_line_index,
+            },
+        }
+    }
+
+    // the relayed signal, if this is one
+    pub fn into_signal(self) -> Result<(PeerId, Signal), Self> {
+        match self {
+            Self::Offer { from, sdp } => Ok((from, Signal::Offer { sdp })),
+            Self::Answer { from, sdp } => Ok((from, Signal::Answer { sdp })),
+            Self::Candidate {
+                from,
+                candidate,
+                sdp_mid,
+                sdp_m_line_index,
+            } => Ok((
+                from,
+                Signal::Candidate {
+                    candidate,
+                    sdp_mid,
+                    sdp_m_line_index,
+                },
+            )),
+            message => Err(message),
+        }
+    }
+}
+
+// what the server accepts in a join
+pub fn check_join(room: &str, name: &str) -> Result<(), ErrorStr> {
+    if room.is_empty() {
+        return Err(ErrorStr::new("Room names can't be empty"));
+    }
+    if room.len() > MAX_ROOM_LEN || name.len() > MAX_NAME_LEN {
+        let error = format!(
+            "Room names are limited to {} bytes and peer names to {}",
+            MAX_ROOM_LEN, MAX_NAME_LEN
+        );
+        return Err(ErrorStr::new(error));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+struct Version {
+    v: u32,
+}
+
+// the version a message claims, None if it isn't a versioned JSON object
+pub fn version(text: &str) -> Option<u32> {
+    serde_json::from_str::<Version>(text)
+        .ok()
+        .map(|version| version.v)
+}
+
+pub fn encode<T>(message: &T) -> Result<String, ErrorStr>
+where
+    T: Serialize,
+{
+    let envelope = Envelope {
+        v: PROTOCOL_VERSION,
+        message,
+    };
+    match serde_json::to_string(&envelope) {
+        Ok(text) => Ok(text),
+        Err(error) => Err(ErrorStr::new(format!(
+            "Unable to encode message: {}",
+            error
+        ))),
+    }
+}
+
+pub fn decode<T>(text: &str) -> Result<T, ErrorStr>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    match version(text) {
+        Some(PROTOCOL_VERSION) => {}
+        Some(version) => {
+            let error = format!(
+                "Protocol version {} is not supported, expected {}",
+                version, PROTOCOL_VERSION
+            );
+            return Err(ErrorStr::new(error));
+        }
+        None => return Err(ErrorStr::new("Message has no protocol version")),
+    }
+    match serde_json::from_str::<Envelope<T>>(text) {
+        Ok(envelope) => Ok(envelope.message),
+        Err(error) => Err(ErrorStr::new(format!("Bad message: {}", error))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use serde_json::Value;
+
+    use super::*;
+
+    // shared with the signal server, which checks the same files against its own types
+    const CLIENT_FIXTURES: &str = include_str!("../../../../../fixtures/signaling/client.json");
+    const SERVER_FIXTURES: &str = include_str!("../../../../../fixtures/signaling/server.json");
+    const CLIENT_TYPES: [&str; 5] = ["join", "offer", "answer", "candidate", "leave"];
+    const SERVER_TYPES: [&str; 7] = [
+        "joined",
+        "peer_joined",
+        "peer_left",
+        "offer",
+        "answer",
+        "candidate",
+        "error",
+    ];
+
+    fn fixtures(text: &str) -> Vec<Value> {
+        serde_json::from_str(text).unwrap()
+    }
+
+    fn types(fixtures: &[Value]) -> BTreeSet<&str> {
+        fixtures
+            .iter()
+            .map(|fixture| fixture["type"].as_str().unwrap())
+            .collect()
+    }
+
+    // decoded and encoded again, it has to come back out exactly as it went in
+    fn round_trip<T>(fixture: &Value)
+    where
+        T: Serialize + for<'de> Deserialize<'de>,
+    {
+        let message: T = decode(&fixture.to_string()).unwrap();
+        let encoded: Value = serde_json::from_str(&encode(&message).unwrap()).unwrap();
+        assert_eq!(&encoded, fixture);
+    }
+
+    #[test]
+    fn matches_client_fixtures() {
+        let fixtures = fixtures(CLIENT_FIXTURES);
+        assert_eq!(types(&fixtures), BTreeSet::from(CLIENT_TYPES));
+        for fixture in fixtures.iter() {
+            round_trip::<ClientMessage>(fixture);
+        }
+    }
+
+    #[test]
+    fn matches_server_fixtures() {
+        let fixtures = fixtures(SERVER_FIXTURES);
+        assert_eq!(types(&fixtures), BTreeSet::from(SERVER_TYPES));
+        for fixture in fixtures.iter() {
+            round_trip::<ServerMessage>(fixture);
+        }
+    }
+
+    #[test]
+    fn checks_join_lengths() {
+        assert!(check_join("lobby", "Ada").is_ok());
+        assert!(check_join("", "Ada").is_err());
+        assert!(check_join(&"r".repeat(MAX_ROOM_LEN + 1), "Ada").is_err());
+        assert!(check_join("lobby", &"n".repeat(MAX_NAME_LEN + 1)).is_err());
+        assert!(check_join(&"r".repeat(MAX_ROOM_LEN), &"n".repeat(MAX_NAME_LEN)).is_ok());
+    }
+}
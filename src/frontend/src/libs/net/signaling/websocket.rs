@@ -0,0 +1,136 @@
+use std::collections::VecDeque;
+
+use wasm_bindgen::JsCast;
+use wasm_bindgen::closure::Closure;
+use web_sys::{CloseEvent, Event, MessageEvent, WebSocket};
+
+use crate::libs::net::signaling::{SignalingSocket, SocketEvent};
+use crate::libs::types::errors::ErrorStr;
+use crate::libs::types::shared::{SharedRefCell, shared_ref_cell};
+
+struct Handlers {
+    _open: Closure<dyn FnMut(Event)>,
+    _message: Closure<dyn FnMut(MessageEvent)>,
+    _close: Closure<dyn FnMut(CloseEvent)>,
+}
+
+// the signaling server over a browser WebSocket, text frames only
+pub struct WebSocketSignaling {
+    url: String,
+    socket: Option<WebSocket>,
+    events: SharedRefCell<VecDeque<SocketEvent>>,
+    handlers: Option<Handlers>,
+}
+
+impl std::fmt::Debug for WebSocketSignaling {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("WebSocketSignaling")
+            .field("url", &self.url)
+            .field(
+                "ready_state",
+                &self.socket.as_ref().map(|s| s.ready_state()),
+            )
+            .field("events", &self.events.borrow().len())
+            .finish()
+    }
+}
+
+impl Drop for WebSocketSignaling {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+impl WebSocketSignaling {
+    // ws:// or wss:// url of the signaling endpoint
+    pub fn new<S>(url: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self {
+            url: url.into(),
+            socket: None,
+            events: shared_ref_cell(VecDeque::new()),
+            handlers: None,
+        }
+    }
+
+    #[inline]
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+}
+
+impl SignalingSocket for WebSocketSignaling {
+    fn connect(&mut self) -> Result<(), ErrorStr> {
+        self.close();
+        let socket = match WebSocket::new(&self.url) {
+            Ok(socket) => socket,
+            Err(error) => {
+                let error = format!("Unable to open {}: {:?}", self.url, error);
+                return Err(ErrorStr::new(error));
+            }
+        };
+
+        let events = self.events.clone();
+        let open = Closure::<dyn FnMut(Event)>::new(move |_: Event| {
+            events.borrow_mut().push_back(SocketEvent::Open);
+        });
+        let events = self.events.clone();
+        let message = Closure::<dyn FnMut(MessageEvent)>::new(move |event: MessageEvent| {
+            // the protocol is JSON, binary frames are ignored
+            if let Some(text) = event.data().as_string() {
+                events.borrow_mut().push_back(SocketEvent::Message(text));
+            }
+        });
+        // errors are always followed by close, which carries more detail
+        let events = self.events.clone();
+        let close = Closure::<dyn FnMut(CloseEvent)>::new(move |event: CloseEvent| {
+            let reason = if event.reason().is_empty() {
+                format!("closed with code {}", event.code())
+            } else {
+                format!("closed with code {}: {}", event.code(), event.reason())
+            };
+            events.borrow_mut().push_back(SocketEvent::Closed(reason));
+        });
+        socket.set_onopen(Some(open.as_ref().unchecked_ref()));
+        socket.set_onmessage(Some(message.as_ref().unchecked_ref()));
+        socket.set_onclose(Some(close.as_ref().unchecked_ref()));
+
+        self.socket = Some(socket);
+        self.handlers = Some(Handlers {
+            _open: open,
+            _message: message,
+            _close: close,
+        });
+        Ok(())
+    }
+
+    fn send(&mut self, text: &str) -> Result<(), ErrorStr> {
+        let socket = match self.socket.as_ref() {
+            Some(socket) if socket.ready_state() == WebSocket::OPEN => socket,
+            _ => return Err(ErrorStr::new("Signaling socket is not open")),
+        };
+        if let Err(error) = socket.send_with_str(text) {
+            let error = format!("Unable to send to {}: {:?}", self.url, error);
+            return Err(ErrorStr::new(error));
+        }
+        Ok(())
+    }
+
+    fn recv(&mut self) -> Option<SocketEvent> {
+        self.events.borrow_mut().pop_front()
+    }
+
+    fn close(&mut self) {
+        if let Some(socket) = self.socket.take() {
+            // detach first so the old socket can't report into the next connection
+            socket.set_onopen(None);
+            socket.set_onmessage(None);
+            socket.set_onclose(None);
+            let _ = socket.close();
+        }
+        self.handlers = None;
+        self.events.borrow_mut().clear();
+    }
+}
@@ -0,0 +1,219 @@
+use crate::libs::net::PeerId;
+use crate::log_info;
+
+const LOG_TARGET: &str = "net::topology";
+
+pub const DEFAULT_MESH_PLAYERS: usize = 4;
+pub const DEFAULT_MAX_MESH_PLAYERS: usize = 8;
+pub const DEFAULT_MAX_RELAY_MS: f64 = 120.0;
+
+// how a rollback match's inputs get from every peer to every other
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Topology {
+    // every peer sends to every other. lowest latency, but everyone's upload grows with the
+    // player count
+    #[default]
+    Mesh,
+    // everyone sends to the hub, which relays to the rest. a hop more for everyone else, and
+    // the hub carries the whole match's upload
+    Star {
+        hub: PeerId,
+    },
+}
+
+impl Topology {
+    #[inline]
+    pub fn hub(&self) -> Option<PeerId> {
+        match self {
+            Topology::Mesh => None,
+            Topology::Star { hub } => Some(*hub),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TopologyReason {
+    // few enough that everyone can send to everyone
+    FewPlayers,
+    // too many for a mesh, the hub relays
+    ManyPlayers,
+    // a mesh would do, relaying through the hub would add too much latency
+    SlowRelay,
+    // over the most a mesh is allowed, whatever the latency
+    TooManyPlayers,
+    // told by the host, the metrics are what can be worked out locally
+    Host,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TopologyConfig {
+    // up to this many players is always a mesh
+    pub mesh_players: usize,
+    // more than this many players is always a star
+    pub max_mesh_players: usize,
+    // in between it's a star, unless the slowest trip through the hub takes longer than this
+    pub max_relay_ms: f64,
+}
+
+impl Default for TopologyConfig {
+    fn default() -> Self {
+        Self {
+            mesh_players: DEFAULT_MESH_PLAYERS,
+            max_mesh_players: DEFAULT_MAX_MESH_PLAYERS,
+            max_relay_ms: DEFAULT_MAX_RELAY_MS,
+        }
+    }
+}
+
+// what was picked and why, with the numbers it was picked from. shown in the profiler overlay
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TopologyDecision {
+    pub topology: Topology,
+    pub reason: TopologyReason,
+    pub players: usize,
+    // the slowest one way trip between two players through the hub, from the hub's rtts. None
+    // without a measurement to everyone
+    pub relay_ms: Option<f64>,
+    // the hub's highest rtt to a player
+    pub max_rtt_ms: Option<f64>,
+    // input messages sent per tick, by each peer in a mesh and by the hub in a star
+    pub mesh_sends: usize,
+    pub hub_sends: usize,
+}
+
+impl TopologyDecision {
+    // a topology the host picked, as seen by everyone else
+    pub fn from_host(topology: Topology, players: usize) -> Self {
+        Self {
+            topology,
+            reason: TopologyReason::Host,
+            players,
+            relay_ms: None,
+            max_rtt_ms: None,
+            mesh_sends: mesh_sends(players),
+            hub_sends: hub_sends(players),
+        }
+    }
+}
+
+#[inline]
+fn mesh_sends(players: usize) -> usize {
+    players.saturating_sub(1)
+}
+
+// every other player's inputs to each of the others
+#[inline]
+fn hub_sends(players: usize) -> usize {
+    players.saturating_sub(1).pow(2)
+}
+
+// picks for a match of players with hub as the would be hub. rtts are the hub's to every other
+// player, in ms
+pub fn choose(
+    config: &TopologyConfig,
+    hub: PeerId,
+    players: usize,
+    rtts: &[f64],
+) -> TopologyDecision {
+    let mut sorted = rtts.to_vec();
+    sorted.sort_by(|a, b| b.total_cmp(a));
+    let measured = sorted.len() + 1 >= players;
+    let max_rtt_ms = sorted.first().copied().filter(|_| measured);
+    // there and on again to the other slowest player, each half a round trip
+    let relay_ms = match sorted.as_slice() {
+        [first, second, ..] if measured => Some((first + second) / 2.0),
+        _ => None,
+    };
+    let (topology, reason) = if players <= config.mesh_players {
+        (Topology::Mesh, TopologyReason::FewPlayers)
+    } else if players > config.max_mesh_players {
+        (Topology::Star { hub }, TopologyReason::TooManyPlayers)
+    } else if relay_ms.is_some_and(|relay_ms| relay_ms > config.max_relay_ms) {
+        (Topology::Mesh, TopologyReason::SlowRelay)
+    } else {
+        (Topology::Star { hub }, TopologyReason::ManyPlayers)
+    };
+    log_info!(
+        LOG_TARGET,
+        "{:?} for {} players ({:?}, relay {:?} ms)",
+        topology,
+        players,
+        reason,
+        relay_ms
+    );
+    TopologyDecision {
+        topology,
+        reason,
+        players,
+        relay_ms,
+        max_rtt_ms,
+        mesh_sends: mesh_sends(players),
+        hub_sends: hub_sends(players),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HUB: PeerId = PeerId(1);
+
+    fn config() -> TopologyConfig {
+        TopologyConfig {
+            mesh_players: 4,
+            max_mesh_players: 6,
+            max_relay_ms: 100.0,
+        }
+    }
+
+    // the hub's rtts to everyone else, the slowest two averaging to relay_ms
+    fn rtts(players: usize, relay_ms: f64) -> Vec<f64> {
+        let mut rtts = vec![20.0; players - 1];
+        rtts[0] = relay_ms;
+        rtts[1] = relay_ms;
+        rtts
+    }
+
+    fn picks(players: usize, relay_ms: f64) -> (Topology, TopologyReason) {
+        let decision = choose(&config(), HUB, players, &rtts(players, relay_ms));
+        (decision.topology, decision.reason)
+    }
+
+    #[test]
+    fn meshes_up_to_mesh_players() {
+        let star = Topology::Star { hub: HUB };
+        assert_eq!(picks(4, 10.0), (Topology::Mesh, TopologyReason::FewPlayers));
+        assert_eq!(
+            picks(4, 500.0),
+            (Topology::Mesh, TopologyReason::FewPlayers)
+        );
+        assert_eq!(picks(5, 10.0), (star, TopologyReason::ManyPlayers));
+    }
+
+    #[test]
+    fn stars_past_max_mesh_players() {
+        let star = Topology::Star { hub: HUB };
+        assert_eq!(picks(6, 500.0), (Topology::Mesh, TopologyReason::SlowRelay));
+        assert_eq!(picks(7, 500.0), (star, TopologyReason::TooManyPlayers));
+        assert_eq!(picks(7, 10.0), (star, TopologyReason::TooManyPlayers));
+    }
+
+    #[test]
+    fn meshes_only_over_max_relay_ms() {
+        let star = Topology::Star { hub: HUB };
+        assert_eq!(picks(5, 100.0), (star, TopologyReason::ManyPlayers));
+        assert_eq!(picks(5, 100.5), (Topology::Mesh, TopologyReason::SlowRelay));
+        let decision = choose(&config(), HUB, 5, &rtts(5, 100.5));
+        assert_eq!(decision.relay_ms, Some(100.5));
+        assert_eq!(decision.max_rtt_ms, Some(100.5));
+        assert_eq!((decision.mesh_sends, decision.hub_sends), (4, 16));
+    }
+
+    #[test]
+    fn stars_without_an_rtt_to_everyone() {
+        let decision = choose(&config(), HUB, 5, &[500.0, 500.0]);
+        assert_eq!(decision.topology, Topology::Star { hub: HUB });
+        assert_eq!(decision.reason, TopologyReason::ManyPlayers);
+        assert_eq!((decision.relay_ms, decision.max_rtt_ms), (None, None));
+    }
+}
@@ -0,0 +1,489 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use js_sys::{Array, Reflect, Uint8Array};
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::{JsFuture, spawn_local};
+use web_sys::{
+    MessageEvent, RtcConfiguration, RtcDataChannel, RtcDataChannelInit, RtcDataChannelState,
+    RtcDataChannelType, RtcIceCandidateInit, RtcIceServer, RtcPeerConnection,
+    RtcPeerConnectionIceEvent, RtcPeerConnectionState, RtcSdpType, RtcSessionDescriptionInit,
+};
+
+use crate::libs::net::{Channel, PeerId, Transport, TransportEvent, check_message_size};
+use crate::libs::profiler::network::{record_received, record_sent};
+use crate::libs::types::errors::ErrorStr;
+use crate::libs::types::shared::{SharedRefCell, shared_ref_cell};
+use crate::{log_debug, log_info, log_warn};
+
+const LOG_TARGET: &str = "net::webrtc";
+pub const DEFAULT_STUN_SERVER: &str = "stun:stun.l.google.com:19302";
+
+// what the two sides of a connection have to swap through the signaling server
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Signal {
+    Offer {
+        sdp: String,
+    },
+    Answer {
+        sdp: String,
+    },
+    Candidate {
+        candidate: String,
+        sdp_mid: Option<String>,
+        sdp_m_line_index: Option<u16>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IceServer {
+    pub urls: Vec<String>,
+    pub username: Option<String>,
+    pub credential: Option<String>,
+}
+
+impl IceServer {
+    pub fn stun<S>(url: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self {
+            urls: vec![url.into()],
+            username: None,
+            credential: None,
+        }
+    }
+}
+
+// written to by the browser callbacks, drained by the transport
+#[derive(Debug, Default)]
+struct Shared {
+    events: VecDeque<TransportEvent>,
+    signals: VecDeque<(PeerId, Signal)>,
+    open_channels: BTreeMap<PeerId, usize>,
+    connected: BTreeSet<PeerId>,
+    // candidates can arrive before the remote description is set, they wait here until it is
+    remote_set: BTreeSet<PeerId>,
+    pending_candidates: BTreeMap<PeerId, Vec<RtcIceCandidateInit>>,
+}
+
+impl Shared {
+    fn error(&mut self, peer: PeerId, error: String) {
+        log_warn!(LOG_TARGET, "{}: {}", peer, error);
+        self.events.push_back(TransportEvent::Error {
+            peer: Some(peer),
+            error,
+        });
+    }
+
+    fn disconnected(&mut self, peer: PeerId) {
+        self.open_channels.remove(&peer);
+        self.remote_set.remove(&peer);
+        self.pending_candidates.remove(&peer);
+        if self.connected.remove(&peer) {
+            log_info!(LOG_TARGET, "Disconnected from {}", peer);
+            self.events.push_back(TransportEvent::Disconnected(peer));
+        }
+    }
+}
+
+type Callback = Closure<dyn FnMut(JsValue)>;
+
+struct PeerLink {
+    connection: RtcPeerConnection,
+    // indexed by Channel::index
+    channels: Vec<RtcDataChannel>,
+    // the browser only holds weak references, dropping these silences the events
+    _callbacks: Vec<Callback>,
+}
+
+impl PeerLink {
+    fn close(&self) {
+        for channel in self.channels.iter() {
+            channel.set_onclose(None);
+            channel.close();
+        }
+        self.connection.set_onconnectionstatechange(None);
+        self.connection.close();
+    }
+}
+
+// one RTCPeerConnection per peer, each carrying a reliable ordered and an unreliable unordered
+// data channel. both channels are pre-negotiated with fixed ids, so there's no ondatachannel
+// dance and either side can send as soon as they open
+pub struct WebRtcTransport {
+    local_id: PeerId,
+    ice_servers: Vec<IceServer>,
+    links: BTreeMap<PeerId, PeerLink>,
+    shared: SharedRefCell<Shared>,
+}
+
+impl std::fmt::Debug for WebRtcTransport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("WebRtcTransport")
+            .field("local_id", &self.local_id)
+            .field("ice_servers", &self.ice_servers)
+            .field("links", &self.links.keys().collect::<Vec<_>>())
+            .field("shared", &self.shared)
+            .finish()
+    }
+}
+
+impl Drop for WebRtcTransport {
+    fn drop(&mut self) {
+        for link in self.links.values() {
+            link.close();
+        }
+    }
+}
+
+impl WebRtcTransport {
+    pub fn new(local_id: PeerId, ice_servers: Vec<IceServer>) -> Self {
+        Self {
+            local_id,
+            ice_servers,
+            links: BTreeMap::new(),
+            shared: shared_ref_cell(Shared::default()),
+        }
+    }
+
+    // starts a connection as the offering side. the offer comes out of take_signals
+    pub fn connect(&mut self, peer: PeerId) -> Result<(), ErrorStr> {
+        if self.links.contains_key(&peer) {
+            return Err(ErrorStr::new(format!("Already connecting to {}", peer)));
+        }
+        let connection = self.create_link(peer)?;
+        let shared = self.shared.clone();
+        spawn_local(async move {
+            let result = async {
+                let offer = JsFuture::from(connection.create_offer()).await?;
+                let sdp = description_sdp(&offer)?;
+                let description = RtcSessionDescriptionInit::new(RtcSdpType::Offer);
+                description.set_sdp(&sdp);
+                JsFuture::from(connection.set_local_description(&description)).await?;
+                Ok::<String, JsValue>(sdp)
+            }
+            .await;
+            let mut shared = shared.borrow_mut();
+            match result {
+                Ok(sdp) => shared.signals.push_back((peer, Signal::Offer { sdp })),
+                Err(error) => shared.error(peer, format!("Unable to create offer: {:?}", error)),
+            }
+        });
+        Ok(())
+    }
+
+    // feeds in a signal the peer sent us through the signaling server
+    pub fn handle_signal(&mut self, peer: PeerId, signal: Signal) -> Result<(), ErrorStr> {
+        match signal {
+            Signal::Offer { sdp } => {
+                // a fresh offer from a peer we knew means it reconnected
+                if let Some(link) = self.links.remove(&peer) {
+                    link.close();
+                    self.shared.borrow_mut().disconnected(peer);
+                }
+                let connection = self.create_link(peer)?;
+                let shared = self.shared.clone();
+                spawn_local(async move {
+                    let result = async {
+                        let offer = RtcSessionDescriptionInit::new(RtcSdpType::Offer);
+                        offer.set_sdp(&sdp);
+                        JsFuture::from(connection.set_remote_description(&offer)).await?;
+                        flush_candidates(&connection, &shared, peer);
+                        let answer = JsFuture::from(connection.create_answer()).await?;
+                        let sdp = description_sdp(&answer)?;
+                        let description = RtcSessionDescriptionInit::new(RtcSdpType::Answer);
+                        description.set_sdp(&sdp);
+                        JsFuture::from(connection.set_local_description(&description)).await?;
+                        Ok::<String, JsValue>(sdp)
+                    }
+                    .await;
+                    let mut shared = shared.borrow_mut();
+                    match result {
+                        Ok(sdp) => shared.signals.push_back((peer, Signal::Answer { sdp })),
+                        Err(error) => {
+                            shared.error(peer, format!("Unable to answer offer: {:?}", error))
+                        }
+                    }
+                });
+            }
+            Signal::Answer { sdp } => {
+                let connection = self.connection(peer)?;
+                let shared = self.shared.clone();
+                spawn_local(async move {
+                    let answer = RtcSessionDescriptionInit::new(RtcSdpType::Answer);
+                    answer.set_sdp(&sdp);
+                    match JsFuture::from(connection.set_remote_description(&answer)).await {
+                        Ok(_) => flush_candidates(&connection, &shared, peer),
+                        Err(error) => shared
+                            .borrow_mut()
+                            .error(peer, format!("Unable to accept answer: {:?}", error)),
+                    }
+                });
+            }
+            Signal::Candidate {
+                candidate,
+                sdp_mid,
+                sdp_m_line_index,
+            } => {
+                let connection = self.connection(peer)?;
+                let init = RtcIceCandidateInit::new(&candidate);
+                init.set_sdp_mid(sdp_mid.as_deref());
+                init.set_sdp_m_line_index(sdp_m_line_index);
+                let mut shared = self.shared.borrow_mut();
+                if shared.remote_set.contains(&peer) {
+                    add_candidate(&connection, &self.shared, peer, init);
+                } else {
+                    shared
+                        .pending_candidates
+                        .entry(peer)
+                        .or_default()
+                        .push(init);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // signals to forward to peers through the signaling server
+    pub fn take_signals(&mut self) -> Vec<(PeerId, Signal)> {
+        self.shared.borrow_mut().signals.drain(..).collect()
+    }
+
+    fn connection(&self, peer: PeerId) -> Result<RtcPeerConnection, ErrorStr> {
+        match self.links.get(&peer) {
+            Some(link) => Ok(link.connection.clone()),
+            None => Err(ErrorStr::new(format!("No connection to {}", peer))),
+        }
+    }
+
+    fn configuration(&self) -> RtcConfiguration {
+        let servers = Array::new();
+        for server in self.ice_servers.iter() {
+            let ice_server = RtcIceServer::new();
+            let urls: Array = server
+                .urls
+                .iter()
+                .map(|url| JsValue::from_str(url))
+                .collect();
+            ice_server.set_urls(&urls);
+            if let Some(username) = server.username.as_deref() {
+                ice_server.set_username(username);
+            }
+            if let Some(credential) = server.credential.as_deref() {
+                ice_server.set_credential(credential);
+            }
+            servers.push(&ice_server);
+        }
+        let configuration = RtcConfiguration::new();
+        configuration.set_ice_servers(&servers);
+        configuration
+    }
+
+    fn create_link(&mut self, peer: PeerId) -> Result<RtcPeerConnection, ErrorStr> {
+        let connection = match RtcPeerConnection::new_with_configuration(&self.configuration()) {
+            Ok(connection) => connection,
+            Err(error) => {
+                let error = format!("Unable to create peer connection: {:?}", error);
+                return Err(ErrorStr::new(error));
+            }
+        };
+        let mut callbacks = Vec::new();
+
+        let shared = self.shared.clone();
+        let on_candidate = Callback::new(move |event: JsValue| {
+            let candidate = event
+                .dyn_into::<RtcPeerConnectionIceEvent>()
+                .ok()
+                .and_then(|event| event.candidate());
+            // None marks the end of gathering
+            if let Some(candidate) = candidate {
+                let signal = Signal::Candidate {
+                    candidate: candidate.candidate(),
+                    sdp_mid: candidate.sdp_mid(),
+                    sdp_m_line_index: candidate.sdp_m_line_index(),
+                };
+                shared.borrow_mut().signals.push_back((peer, signal));
+            }
+        });
+        connection.set_onicecandidate(Some(on_candidate.as_ref().unchecked_ref()));
+        callbacks.push(on_candidate);
+
+        let shared = self.shared.clone();
+        let state_connection = connection.clone();
+        let on_state = Callback::new(move |_| {
+            let state = state_connection.connection_state();
+            log_debug!(LOG_TARGET, "{} connection state {:?}", peer, state);
+            match state {
+                RtcPeerConnectionState::Failed | RtcPeerConnectionState::Closed => {
+                    let mut shared = shared.borrow_mut();
+                    if !shared.connected.contains(&peer) {
+                        shared.error(peer, format!("Connection {:?}", state));
+                    }
+                    shared.disconnected(peer);
+                }
+                _ => {}
+            }
+        });
+        connection.set_onconnectionstatechange(Some(on_state.as_ref().unchecked_ref()));
+        callbacks.push(on_state);
+
+        let mut channels = Vec::new();
+        for channel in Channel::ALL {
+            let init = RtcDataChannelInit::new();
+            init.set_negotiated(true);
+            init.set_id(channel.index() as u16);
+            if channel == Channel::Unreliable {
+                init.set_ordered(false);
+                init.set_max_retransmits(0);
+            }
+            let label = format!("{:?}", channel).to_lowercase();
+            let data_channel = connection.create_data_channel_with_data_channel_dict(&label, &init);
+            data_channel.set_binary_type(RtcDataChannelType::Arraybuffer);
+            callbacks.extend(self.channel_callbacks(peer, channel, &data_channel));
+            channels.push(data_channel);
+        }
+
+        self.links.insert(
+            peer,
+            PeerLink {
+                connection: connection.clone(),
+                channels,
+                _callbacks: callbacks,
+            },
+        );
+        Ok(connection)
+    }
+
+    fn channel_callbacks(
+        &self,
+        peer: PeerId,
+        channel: Channel,
+        data_channel: &RtcDataChannel,
+    ) -> Vec<Callback> {
+        let shared = self.shared.clone();
+        let on_open = Callback::new(move |_| {
+            let mut shared = shared.borrow_mut();
+            let open = shared.open_channels.entry(peer).or_insert(0);
+            *open += 1;
+            if *open == Channel::ALL.len() && shared.connected.insert(peer) {
+                log_info!(LOG_TARGET, "Connected to {}", peer);
+                shared.events.push_back(TransportEvent::Connected(peer));
+            }
+        });
+        data_channel.set_onopen(Some(on_open.as_ref().unchecked_ref()));
+
+        let shared = self.shared.clone();
+        let on_message = Callback::new(move |event: JsValue| {
+            let data = event
+                .dyn_into::<MessageEvent>()
+                .ok()
+                .map(|event| Uint8Array::new(&event.data()).to_vec());
+            if let Some(data) = data {
+                record_received(peer, data.len());
+                let message = TransportEvent::Message {
+                    peer,
+                    channel,
+                    data,
+                };
+                shared.borrow_mut().events.push_back(message);
+            }
+        });
+        data_channel.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+
+        let shared = self.shared.clone();
+        let on_close = Callback::new(move |_| shared.borrow_mut().disconnected(peer));
+        data_channel.set_onclose(Some(on_close.as_ref().unchecked_ref()));
+
+        vec![on_open, on_message, on_close]
+    }
+
+    pub fn buffered_amount(&self, peer: PeerId, channel: Channel) -> u32 {
+        self.links
+            .get(&peer)
+            .map(|link| link.channels[channel.index()].buffered_amount())
+            .unwrap_or(0)
+    }
+}
+
+impl Transport for WebRtcTransport {
+    #[inline]
+    fn local_id(&self) -> PeerId {
+        self.local_id
+    }
+
+    fn peers(&self) -> Vec<PeerId> {
+        self.shared.borrow().connected.iter().copied().collect()
+    }
+
+    fn is_connected(&self, peer: PeerId) -> bool {
+        self.shared.borrow().connected.contains(&peer)
+    }
+
+    fn send(&mut self, peer: PeerId, channel: Channel, data: &[u8]) -> Result<(), ErrorStr> {
+        check_message_size(data)?;
+        let data_channel = if let Some(link) = self.links.get(&peer) {
+            &link.channels[channel.index()]
+        } else {
+            return Err(ErrorStr::new(format!("No connection to {}", peer)));
+        };
+        if data_channel.ready_state() != RtcDataChannelState::Open {
+            let error = format!("{:?} channel to {} is not open", channel, peer);
+            return Err(ErrorStr::new(error));
+        }
+        if let Err(error) = data_channel.send_with_u8_array(data) {
+            let error = format!("Unable to send to {}: {:?}", peer, error);
+            return Err(ErrorStr::new(error));
+        }
+        record_sent(peer, data.len());
+        Ok(())
+    }
+
+    fn recv(&mut self) -> Option<TransportEvent> {
+        self.shared.borrow_mut().events.pop_front()
+    }
+
+    fn disconnect(&mut self, peer: PeerId) {
+        if let Some(link) = self.links.remove(&peer) {
+            link.close();
+        }
+        self.shared.borrow_mut().disconnected(peer);
+    }
+}
+
+fn description_sdp(description: &JsValue) -> Result<String, JsValue> {
+    match Reflect::get(description, &JsValue::from_str("sdp"))?.as_string() {
+        Some(sdp) => Ok(sdp),
+        None => Err(JsValue::from_str("Session description has no sdp")),
+    }
+}
+
+fn flush_candidates(connection: &RtcPeerConnection, shared: &SharedRefCell<Shared>, peer: PeerId) {
+    let pending = {
+        let mut shared = shared.borrow_mut();
+        shared.remote_set.insert(peer);
+        shared.pending_candidates.remove(&peer).unwrap_or_default()
+    };
+    for candidate in pending {
+        add_candidate(connection, shared, peer, candidate);
+    }
+}
+
+fn add_candidate(
+    connection: &RtcPeerConnection,
+    shared: &SharedRefCell<Shared>,
+    peer: PeerId,
+    candidate: RtcIceCandidateInit,
+) {
+    let promise = connection.add_ice_candidate_with_opt_rtc_ice_candidate_init(Some(&candidate));
+    let shared = shared.clone();
+    spawn_local(async move {
+        if let Err(error) = JsFuture::from(promise).await {
+            shared
+                .borrow_mut()
+                .error(peer, format!("Unable to add ICE candidate: {:?}", error));
+        }
+    });
+}
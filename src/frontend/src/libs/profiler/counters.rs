@@ -0,0 +1,73 @@
+use std::cell::Cell;
+use std::ops::Sub;
+
+use web_sys::WebGl2RenderingContext;
+
+// renderers only ever get a bare gl context, so the per frame counters live here instead of
+// being threaded through every draw call. wasm is single threaded
+thread_local! {
+    static COUNTERS: Cell<FrameCounters> = const { Cell::new(FrameCounters::ZERO) };
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FrameCounters {
+    pub draw_calls: u32,
+    pub triangles: u32,
+    // program, vertex array, texture, framebuffer and blend changes
+    pub state_changes: u32,
+}
+
+impl FrameCounters {
+    pub const ZERO: Self = Self {
+        draw_calls: 0,
+        triangles: 0,
+        state_changes: 0,
+    };
+}
+
+impl Sub for FrameCounters {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self {
+            draw_calls: self.draw_calls.saturating_sub(rhs.draw_calls),
+            triangles: self.triangles.saturating_sub(rhs.triangles),
+            state_changes: self.state_changes.saturating_sub(rhs.state_changes),
+        }
+    }
+}
+
+// call next to every draw_arrays / draw_elements, with the vertex or index count
+pub fn record_draw(mode: u32, count: i32, instances: i32) {
+    let count = count.max(0) as u32;
+    let triangles = match mode {
+        WebGl2RenderingContext::TRIANGLES => count / 3,
+        WebGl2RenderingContext::TRIANGLE_STRIP | WebGl2RenderingContext::TRIANGLE_FAN => {
+            count.saturating_sub(2)
+        }
+        _ => 0,
+    };
+    COUNTERS.with(|counters| {
+        let mut current = counters.get();
+        current.draw_calls += 1;
+        current.triangles += triangles * instances.max(1) as u32;
+        counters.set(current);
+    });
+}
+
+pub fn record_state_change() {
+    COUNTERS.with(|counters| {
+        let mut current = counters.get();
+        current.state_changes += 1;
+        counters.set(current);
+    });
+}
+
+// running totals since the last reset
+pub fn counters() -> FrameCounters {
+    COUNTERS.with(|counters| counters.get())
+}
+
+pub fn reset_counters() {
+    COUNTERS.with(|counters| counters.set(FrameCounters::ZERO));
+}
@@ -0,0 +1,116 @@
+use std::collections::VecDeque;
+
+use web_sys::{WebGl2RenderingContext, WebGlQuery};
+
+// from EXT_disjoint_timer_query_webgl2, web-sys doesn't expose the extension's constants
+const TIME_ELAPSED_EXT: u32 = 0x88BF;
+const GPU_DISJOINT_EXT: u32 = 0x8FBB;
+const EXTENSION: &str = "EXT_disjoint_timer_query_webgl2";
+// results usually arrive 2-3 frames late, past this the driver isn't answering
+const MAX_PENDING_QUERIES: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GpuTiming {
+    pub frame: u64,
+    // which task in the frame the query wrapped
+    pub slot: usize,
+    pub ms: f64,
+}
+
+#[derive(Debug)]
+struct PendingQuery {
+    query: WebGlQuery,
+    frame: u64,
+    slot: usize,
+}
+
+// TIME_ELAPSED queries around each task. only one can be active at a time, which is fine since
+// tasks run one after the other
+#[derive(Debug)]
+pub struct GpuTimer {
+    free: Vec<WebGlQuery>,
+    pending: VecDeque<PendingQuery>,
+    active: Option<PendingQuery>,
+}
+
+impl GpuTimer {
+    // None when the extension isn't available (firefox, most mobile browsers)
+    pub fn new(gl: &WebGl2RenderingContext) -> Option<Self> {
+        match gl.get_extension(EXTENSION) {
+            Ok(Some(_)) => Some(Self {
+                free: Vec::new(),
+                pending: VecDeque::new(),
+                active: None,
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn begin(&mut self, gl: &WebGl2RenderingContext, frame: u64, slot: usize) {
+        if self.active.is_some() || self.pending.len() >= MAX_PENDING_QUERIES {
+            return;
+        }
+        let query = if let Some(query) = self.free.pop().or_else(|| gl.create_query()) {
+            query
+        } else {
+            return;
+        };
+        gl.begin_query(TIME_ELAPSED_EXT, &query);
+        self.active = Some(PendingQuery { query, frame, slot });
+    }
+
+    pub fn end(&mut self, gl: &WebGl2RenderingContext) {
+        if let Some(active) = self.active.take() {
+            gl.end_query(TIME_ELAPSED_EXT);
+            self.pending.push_back(active);
+        }
+    }
+
+    // finished queries, oldest first. results from a disjoint period (gpu reset, power state
+    // change) are meaningless and dropped
+    pub fn collect(&mut self, gl: &WebGl2RenderingContext) -> Vec<GpuTiming> {
+        let mut timings = Vec::new();
+        while let Some(pending) = self.pending.front() {
+            let available = gl
+                .get_query_parameter(&pending.query, WebGl2RenderingContext::QUERY_RESULT_AVAILABLE)
+                .as_bool()
+                .unwrap_or(false);
+            if !available {
+                break;
+            }
+            let nanoseconds = gl
+                .get_query_parameter(&pending.query, WebGl2RenderingContext::QUERY_RESULT)
+                .as_f64()
+                .unwrap_or(0.0);
+            timings.push(GpuTiming {
+                frame: pending.frame,
+                slot: pending.slot,
+                ms: nanoseconds / 1_000_000.0,
+            });
+            if let Some(pending) = self.pending.pop_front() {
+                self.free.push(pending.query);
+            }
+        }
+        let disjoint = gl
+            .get_parameter(GPU_DISJOINT_EXT)
+            .ok()
+            .and_then(|value| value.as_bool())
+            .unwrap_or(false);
+        if disjoint {
+            timings.clear();
+        }
+        timings
+    }
+
+    pub fn delete(&mut self, gl: &WebGl2RenderingContext) {
+        if self.active.is_some() {
+            self.end(gl);
+        }
+        for query in self.free.drain(..) {
+            gl.delete_query(Some(&query));
+        }
+        for pending in self.pending.drain(..) {
+            gl.delete_query(Some(&pending.query));
+        }
+    }
+}
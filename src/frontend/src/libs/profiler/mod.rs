@@ -0,0 +1,360 @@
+pub mod counters;
+pub mod gpu_timer;
+pub mod network;
+pub mod overlay;
+
+use std::collections::{BTreeMap, VecDeque};
+
+use leptos::prelude::*;
+use web_sys::{Performance, WebGl2RenderingContext};
+
+use crate::libs::net::PeerId;
+use crate::libs::net::authority::host::SnapshotStats;
+use crate::libs::net::topology::TopologyDecision;
+use crate::libs::profiler::counters::{FrameCounters, counters, reset_counters};
+use crate::libs::profiler::gpu_timer::{GpuTimer, GpuTiming};
+use crate::libs::profiler::network::{
+    PeerBandwidth, SnapshotBandwidth, Traffic, bandwidth, snapshot_bandwidth, snapshots, topology,
+    traffic,
+};
+
+// two seconds at 60hz
+pub const FRAME_HISTORY: usize = 120;
+// the overlay re-renders at this rate, not every frame
+const PUBLISH_INTERVAL_MS: f64 = 250.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaskStats {
+    pub name: String,
+    pub cpu_ms: f64,
+    // None without the timer extension, or before the first results arrive
+    pub gpu_ms: Option<f64>,
+    pub counters: FrameCounters,
+}
+
+// averaged over FRAME_HISTORY, published to the overlay
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProfilerReport {
+    pub fps: f64,
+    pub cpu_ms: f64,
+    pub cpu_max_ms: f64,
+    pub gpu_ms: Option<f64>,
+    // time between frames, oldest first
+    pub frame_times: Vec<f64>,
+    pub counters: FrameCounters,
+    pub tasks: Vec<TaskStats>,
+    pub gpu_timer: bool,
+    // over the last PUBLISH_INTERVAL_MS, one per peer that ever exchanged a message
+    pub bandwidth: Vec<PeerBandwidth>,
+    // likewise, one per client while hosting an authoritative match
+    pub snapshots: Vec<SnapshotBandwidth>,
+    // how the current match's inputs get around, once one started
+    pub topology: Option<TopologyDecision>,
+}
+
+// reactive side of the profiler, for components. cheap to copy
+#[derive(Debug, Clone, Copy)]
+pub struct ProfilerHandle {
+    report: ReadSignal<ProfilerReport>,
+    visible: RwSignal<bool>,
+}
+
+impl ProfilerHandle {
+    #[inline]
+    pub fn report(&self) -> ProfilerReport {
+        self.report.get()
+    }
+
+    #[inline]
+    pub fn visible(&self) -> bool {
+        self.visible.get()
+    }
+
+    pub fn set_visible(&self, visible: bool) {
+        self.visible.set(visible);
+    }
+
+    pub fn toggle(&self) {
+        self.visible.update(|visible| *visible = !*visible);
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TaskSample<'a> {
+    name: &'a str,
+    cpu_ms: f64,
+    gpu_ms: Option<f64>,
+    counters: FrameCounters,
+}
+
+#[derive(Debug, Clone, Default)]
+struct FrameRecord<'a> {
+    frame: u64,
+    interval_ms: f64,
+    cpu_ms: f64,
+    counters: FrameCounters,
+    tasks: Vec<TaskSample<'a>>,
+}
+
+// times every raf task on the cpu, and on the gpu when EXT_disjoint_timer_query_webgl2 exists.
+// gpu queries are only issued while the overlay is visible
+#[derive(Debug)]
+pub struct FrameProfiler<'a> {
+    handle: ProfilerHandle,
+    set_report: WriteSignal<ProfilerReport>,
+    performance: Option<Performance>,
+    gpu: Option<GpuTimer>,
+    gpu_checked: bool,
+
+    frame: u64,
+    frame_start: f64,
+    last_frame_start: Option<f64>,
+    task_start: f64,
+    task_counters: FrameCounters,
+    current: FrameRecord<'a>,
+    history: VecDeque<FrameRecord<'a>>,
+    last_publish: f64,
+    // the last reading of the network totals, and the rates up to it
+    traffic: BTreeMap<PeerId, Traffic>,
+    traffic_at: f64,
+    bandwidth: Vec<PeerBandwidth>,
+    snapshots: BTreeMap<PeerId, SnapshotStats>,
+    snapshot_bandwidth: Vec<SnapshotBandwidth>,
+}
+
+impl<'a> FrameProfiler<'a> {
+    pub fn new() -> Self {
+        let (report, set_report) = signal(ProfilerReport::default());
+        Self {
+            handle: ProfilerHandle {
+                report,
+                visible: RwSignal::new(false),
+            },
+            set_report,
+            performance: web_sys::window().and_then(|window| window.performance()),
+            gpu: None,
+            gpu_checked: false,
+
+            frame: 0,
+            frame_start: 0.0,
+            last_frame_start: None,
+            task_start: 0.0,
+            task_counters: FrameCounters::ZERO,
+            current: FrameRecord::default(),
+            history: VecDeque::with_capacity(FRAME_HISTORY),
+            last_publish: 0.0,
+            traffic: BTreeMap::new(),
+            traffic_at: 0.0,
+            bandwidth: Vec::new(),
+            snapshots: BTreeMap::new(),
+            snapshot_bandwidth: Vec::new(),
+        }
+    }
+
+    #[inline]
+    pub fn handle(&self) -> ProfilerHandle {
+        self.handle
+    }
+
+    fn now(&self) -> f64 {
+        self.performance
+            .as_ref()
+            .map(|performance| performance.now())
+            .unwrap_or(0.0)
+    }
+
+    fn gpu_enabled(&self) -> bool {
+        self.gpu.is_some() && self.handle.visible.get_untracked()
+    }
+
+    pub fn begin_frame(&mut self, gl: Option<&WebGl2RenderingContext>) {
+        let now = self.now();
+        self.frame += 1;
+        self.frame_start = now;
+        let interval_ms = self.last_frame_start.map(|last| now - last).unwrap_or(0.0);
+        self.last_frame_start = Some(now);
+        self.current = FrameRecord {
+            frame: self.frame,
+            interval_ms,
+            ..Default::default()
+        };
+        reset_counters();
+        let gl = if let Some(gl) = gl {
+            gl
+        } else {
+            return;
+        };
+        if !self.gpu_checked {
+            self.gpu_checked = true;
+            self.gpu = GpuTimer::new(gl);
+        }
+        if let Some(gpu) = self.gpu.as_mut() {
+            let timings = gpu.collect(gl);
+            self.apply_gpu_timings(&timings);
+        }
+    }
+
+    pub fn begin_task(&mut self, gl: Option<&WebGl2RenderingContext>, name: &'a str) {
+        let slot = self.current.tasks.len();
+        self.current.tasks.push(TaskSample {
+            name,
+            cpu_ms: 0.0,
+            gpu_ms: None,
+            counters: FrameCounters::ZERO,
+        });
+        self.task_counters = counters();
+        let gpu_enabled = self.gpu_enabled();
+        if let (true, Some(gl), Some(gpu)) = (gpu_enabled, gl, self.gpu.as_mut()) {
+            gpu.begin(gl, self.frame, slot);
+        }
+        self.task_start = self.now();
+    }
+
+    pub fn end_task(&mut self, gl: Option<&WebGl2RenderingContext>) {
+        let cpu_ms = self.now() - self.task_start;
+        if let (Some(gl), Some(gpu)) = (gl, self.gpu.as_mut()) {
+            gpu.end(gl);
+        }
+        let task_counters = counters() - self.task_counters;
+        if let Some(sample) = self.current.tasks.last_mut() {
+            sample.cpu_ms = cpu_ms;
+            sample.counters = task_counters;
+        }
+    }
+
+    pub fn end_frame(&mut self) {
+        let now = self.now();
+        self.current.cpu_ms = now - self.frame_start;
+        self.current.counters = counters();
+        if self.history.len() == FRAME_HISTORY {
+            self.history.pop_front();
+        }
+        self.history.push_back(std::mem::take(&mut self.current));
+        // read even while hidden, so the first rates shown cover a normal interval
+        if now - self.traffic_at >= PUBLISH_INTERVAL_MS {
+            let traffic = traffic();
+            let snapshots = snapshots();
+            self.bandwidth = bandwidth(&self.traffic, &traffic, now - self.traffic_at);
+            self.snapshot_bandwidth =
+                snapshot_bandwidth(&self.snapshots, &snapshots, now - self.traffic_at);
+            self.traffic = traffic;
+            self.snapshots = snapshots;
+            self.traffic_at = now;
+        }
+        if self.handle.visible.get_untracked() && now - self.last_publish >= PUBLISH_INTERVAL_MS {
+            self.last_publish = now;
+            self.set_report.set(self.report());
+        }
+    }
+
+    // gpu results for frames that have already been pushed to history
+    fn apply_gpu_timings(&mut self, timings: &[GpuTiming]) {
+        for timing in timings {
+            let record = self
+                .history
+                .iter_mut()
+                .rev()
+                .find(|record| record.frame == timing.frame);
+            if let Some(sample) = record.and_then(|record| record.tasks.get_mut(timing.slot)) {
+                sample.gpu_ms = Some(timing.ms);
+            }
+        }
+    }
+
+    fn report(&self) -> ProfilerReport {
+        let frames = self.history.len().max(1) as f64;
+        let cpu_ms = self.history.iter().map(|record| record.cpu_ms).sum::<f64>() / frames;
+        let cpu_max_ms = self
+            .history
+            .iter()
+            .map(|record| record.cpu_ms)
+            .fold(0.0, f64::max);
+        let frame_times: Vec<f64> = self
+            .history
+            .iter()
+            .skip(1)
+            .map(|record| record.interval_ms)
+            .collect();
+        let average_interval = frame_times.iter().sum::<f64>() / frame_times.len().max(1) as f64;
+        let fps = if average_interval > 0.0 {
+            1000.0 / average_interval
+        } else {
+            0.0
+        };
+        let tasks = self.task_report();
+        let gpu_samples: Vec<f64> = tasks.iter().filter_map(|task| task.gpu_ms).collect();
+        let gpu_ms = if gpu_samples.is_empty() {
+            None
+        } else {
+            Some(gpu_samples.iter().sum())
+        };
+        ProfilerReport {
+            fps,
+            cpu_ms,
+            cpu_max_ms,
+            gpu_ms,
+            frame_times,
+            counters: self
+                .history
+                .back()
+                .map(|record| record.counters)
+                .unwrap_or_default(),
+            tasks,
+            gpu_timer: self.gpu.is_some(),
+            bandwidth: self.bandwidth.clone(),
+            snapshots: self.snapshot_bandwidth.clone(),
+            topology: topology(),
+        }
+    }
+
+    // per task averages, keyed by the tasks of the newest frame
+    fn task_report(&self) -> Vec<TaskStats> {
+        let newest = if let Some(newest) = self.history.back() {
+            newest
+        } else {
+            return Vec::new();
+        };
+        newest
+            .tasks
+            .iter()
+            .enumerate()
+            .map(|(slot, task)| {
+                let samples = self
+                    .history
+                    .iter()
+                    .filter_map(|record| record.tasks.get(slot))
+                    .filter(|sample| sample.name == task.name);
+                let mut count = 0.0;
+                let mut cpu_ms = 0.0;
+                let mut gpu_count = 0.0;
+                let mut gpu_ms = 0.0;
+                for sample in samples {
+                    count += 1.0;
+                    cpu_ms += sample.cpu_ms;
+                    if let Some(ms) = sample.gpu_ms {
+                        gpu_count += 1.0;
+                        gpu_ms += ms;
+                    }
+                }
+                TaskStats {
+                    name: task.name.to_string(),
+                    cpu_ms: cpu_ms / f64::max(count, 1.0),
+                    gpu_ms: (gpu_count > 0.0).then(|| gpu_ms / gpu_count),
+                    counters: task.counters,
+                }
+            })
+            .collect()
+    }
+
+    pub fn delete(&mut self, gl: &WebGl2RenderingContext) {
+        if let Some(mut gpu) = self.gpu.take() {
+            gpu.delete(gl);
+        }
+    }
+}
+
+impl Default for FrameProfiler<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
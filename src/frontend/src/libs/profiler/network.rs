@@ -0,0 +1,146 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+use crate::libs::net::PeerId;
+use crate::libs::net::authority::host::SnapshotStats;
+use crate::libs::net::topology::TopologyDecision;
+
+// transports record what goes over the wire here, so the overlay can show it without every
+// session handing its transport to the profiler. wasm is single threaded
+thread_local! {
+    static TRAFFIC: RefCell<BTreeMap<PeerId, Traffic>> = const { RefCell::new(BTreeMap::new()) };
+    static TOPOLOGY: RefCell<Option<TopologyDecision>> = const { RefCell::new(None) };
+    static SNAPSHOTS: RefCell<BTreeMap<PeerId, SnapshotStats>> =
+        const { RefCell::new(BTreeMap::new()) };
+}
+
+// bytes and messages to and from one peer
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Traffic {
+    pub sent_bytes: u64,
+    pub sent_messages: u64,
+    pub received_bytes: u64,
+    pub received_messages: u64,
+}
+
+// per second rates for one peer, published to the overlay
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PeerBandwidth {
+    pub peer: PeerId,
+    pub sent_bytes_per_s: f64,
+    pub received_bytes_per_s: f64,
+    pub sent_per_s: f64,
+    pub received_per_s: f64,
+}
+
+// snapshot rate to one client of an authoritative host, published to the overlay
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SnapshotBandwidth {
+    pub peer: PeerId,
+    pub bytes_per_s: f64,
+    pub full_per_s: f64,
+    pub deltas_per_s: f64,
+    // size of the newest snapshot
+    pub last_bytes: usize,
+}
+
+pub fn record_sent(peer: PeerId, bytes: usize) {
+    TRAFFIC.with(|traffic| {
+        let mut traffic = traffic.borrow_mut();
+        let peer = traffic.entry(peer).or_default();
+        peer.sent_bytes += bytes as u64;
+        peer.sent_messages += 1;
+    });
+}
+
+pub fn record_received(peer: PeerId, bytes: usize) {
+    TRAFFIC.with(|traffic| {
+        let mut traffic = traffic.borrow_mut();
+        let peer = traffic.entry(peer).or_default();
+        peer.received_bytes += bytes as u64;
+        peer.received_messages += 1;
+    });
+}
+
+// the newest match's, replacing any before
+pub fn record_topology(decision: TopologyDecision) {
+    TOPOLOGY.with(|topology| *topology.borrow_mut() = Some(decision));
+}
+
+// the host's totals for one client, replacing any before
+pub fn record_snapshots(peer: PeerId, stats: SnapshotStats) {
+    SNAPSHOTS.with(|snapshots| snapshots.borrow_mut().insert(peer, stats));
+}
+
+// the client left, or this peer stopped hosting
+pub fn forget_snapshots(peer: PeerId) {
+    SNAPSHOTS.with(|snapshots| snapshots.borrow_mut().remove(&peer));
+}
+
+#[inline]
+pub fn topology() -> Option<TopologyDecision> {
+    TOPOLOGY.with(|topology| *topology.borrow())
+}
+
+// running totals per peer, never reset
+pub fn traffic() -> BTreeMap<PeerId, Traffic> {
+    TRAFFIC.with(|traffic| traffic.borrow().clone())
+}
+
+pub fn snapshots() -> BTreeMap<PeerId, SnapshotStats> {
+    SNAPSHOTS.with(|snapshots| snapshots.borrow().clone())
+}
+
+// rates between two readings of traffic taken elapsed_ms apart. peers seen only in the earlier
+// reading are gone and left out
+pub fn bandwidth(
+    before: &BTreeMap<PeerId, Traffic>,
+    after: &BTreeMap<PeerId, Traffic>,
+    elapsed_ms: f64,
+) -> Vec<PeerBandwidth> {
+    if elapsed_ms <= 0.0 {
+        return Vec::new();
+    }
+    let per_s = |count: u64| count as f64 * 1000.0 / elapsed_ms;
+    after
+        .iter()
+        .map(|(peer, now)| {
+            let then = before.get(peer).copied().unwrap_or_default();
+            PeerBandwidth {
+                peer: *peer,
+                sent_bytes_per_s: per_s(now.sent_bytes - then.sent_bytes),
+                received_bytes_per_s: per_s(now.received_bytes - then.received_bytes),
+                sent_per_s: per_s(now.sent_messages - then.sent_messages),
+                received_per_s: per_s(now.received_messages - then.received_messages),
+            }
+        })
+        .collect()
+}
+
+// rates between two readings of snapshots, like bandwidth. peers nothing was sent to in
+// between are left out, so a client of a host that stopped disappears. a new host starts its
+// totals from zero, a drop counts as nothing sent
+pub fn snapshot_bandwidth(
+    before: &BTreeMap<PeerId, SnapshotStats>,
+    after: &BTreeMap<PeerId, SnapshotStats>,
+    elapsed_ms: f64,
+) -> Vec<SnapshotBandwidth> {
+    if elapsed_ms <= 0.0 {
+        return Vec::new();
+    }
+    let per_s = |count: u64| count as f64 * 1000.0 / elapsed_ms;
+    after
+        .iter()
+        .filter(|(peer, now)| before.get(peer) != Some(now))
+        .map(|(peer, now)| {
+            let then = before.get(peer).copied().unwrap_or_default();
+            SnapshotBandwidth {
+                peer: *peer,
+                bytes_per_s: per_s(now.bytes.saturating_sub(then.bytes)),
+                full_per_s: per_s(now.full.saturating_sub(then.full)),
+                deltas_per_s: per_s(now.deltas.saturating_sub(then.deltas)),
+                last_bytes: now.last_bytes,
+            }
+        })
+        .collect()
+}
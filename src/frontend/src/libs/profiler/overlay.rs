@@ -0,0 +1,239 @@
+use leptos::ev;
+use leptos::prelude::*;
+
+use crate::libs::net::topology::{Topology, TopologyDecision, TopologyReason};
+use crate::libs::profiler::ProfilerHandle;
+
+const GRAPH_WIDTH: f64 = 240.0;
+const GRAPH_HEIGHT: f64 = 60.0;
+// the graph's y axis always fits at least a 30hz frame
+const GRAPH_MIN_MS: f64 = 33.4;
+const TARGET_FRAME_MS: f64 = 1000.0 / 60.0;
+const TOGGLE_KEY: &str = "F3";
+
+fn graph_points(frame_times: &[f64], scale_ms: f64) -> String {
+    let step = GRAPH_WIDTH / (frame_times.len().max(2) - 1) as f64;
+    frame_times
+        .iter()
+        .enumerate()
+        .map(|(i, ms)| {
+            let y = GRAPH_HEIGHT - (ms / scale_ms).min(1.0) * GRAPH_HEIGHT;
+            format!("{:.1},{:.1}", i as f64 * step, y)
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn format_kb(bytes_per_s: f64) -> String {
+    format!("{:.1}", bytes_per_s / 1024.0)
+}
+
+fn format_gpu(gpu_ms: Option<f64>) -> String {
+    gpu_ms
+        .map(|ms| format!("{:.2}", ms))
+        .unwrap_or_else(|| "-".to_string())
+}
+
+fn format_topology(decision: &TopologyDecision) -> String {
+    let topology = match decision.topology {
+        Topology::Mesh => "mesh".to_string(),
+        Topology::Star { hub } => format!("star via {}", hub),
+    };
+    let reason = match decision.reason {
+        TopologyReason::FewPlayers => "few players",
+        TopologyReason::ManyPlayers => "many players",
+        TopologyReason::SlowRelay => "relay too slow",
+        TopologyReason::TooManyPlayers => "too many for a mesh",
+        TopologyReason::Host => "host's choice",
+    };
+    format!("{}  {} players, {}", topology, decision.players, reason)
+}
+
+fn format_rtt(ms: Option<f64>) -> String {
+    ms.map(|ms| format!("{:.0}", ms))
+        .unwrap_or_else(|| "-".to_string())
+}
+
+// frame stats for one canvas, toggled with F3 or the button
+#[component]
+pub fn ProfilerOverlay(profiler: ProfilerHandle) -> impl IntoView {
+    let handle = window_event_listener(ev::keydown, move |event| {
+        if event.key() == TOGGLE_KEY {
+            event.prevent_default();
+            profiler.toggle();
+        }
+    });
+    on_cleanup(move || handle.remove());
+
+    let stats = move || {
+        let report = profiler.report();
+        let scale_ms = report
+            .frame_times
+            .iter()
+            .copied()
+            .fold(GRAPH_MIN_MS, f64::max);
+        let target_y = GRAPH_HEIGHT - TARGET_FRAME_MS / scale_ms * GRAPH_HEIGHT;
+        let points = graph_points(&report.frame_times, scale_ms);
+        let gpu_note = if report.gpu_timer {
+            ""
+        } else {
+            " (no timer ext)"
+        };
+        let tasks = report
+            .tasks
+            .into_iter()
+            .map(|task| {
+                view! {
+                    <tr>
+                        <td class="pr-2 truncate max-w-28">{task.name}</td>
+                        <td class="pr-2 text-right">{format!("{:.2}", task.cpu_ms)}</td>
+                        <td class="pr-2 text-right">{format_gpu(task.gpu_ms)}</td>
+                        <td class="text-right">{task.counters.draw_calls}</td>
+                    </tr>
+                }
+            })
+            .collect_view();
+        let topology = report.topology.map(|decision| {
+            view! {
+                <p class="mt-1">{format_topology(&decision)}</p>
+                <p class="text-gray-400">
+                    {format!(
+                        "relay {} ms  rtt {} ms  sends/tick mesh {} hub {}",
+                        format_rtt(decision.relay_ms),
+                        format_rtt(decision.max_rtt_ms),
+                        decision.mesh_sends,
+                        decision.hub_sends,
+                    )}
+                </p>
+            }
+        });
+        let has_peers = !report.bandwidth.is_empty();
+        let peers = report
+            .bandwidth
+            .into_iter()
+            .map(|peer| {
+                view! {
+                    <tr>
+                        <td class="pr-2">{peer.peer.to_string()}</td>
+                        <td class="pr-2 text-right">{format_kb(peer.sent_bytes_per_s)}</td>
+                        <td class="pr-2 text-right">{format_kb(peer.received_bytes_per_s)}</td>
+                        <td class="text-right">
+                            {format!("{:.0}/{:.0}", peer.sent_per_s, peer.received_per_s)}
+                        </td>
+                    </tr>
+                }
+            })
+            .collect_view();
+        let has_snapshots = !report.snapshots.is_empty();
+        let snapshots = report
+            .snapshots
+            .into_iter()
+            .map(|peer| {
+                view! {
+                    <tr>
+                        <td class="pr-2">{peer.peer.to_string()}</td>
+                        <td class="pr-2 text-right">{format_kb(peer.bytes_per_s)}</td>
+                        <td class="pr-2 text-right">
+                            {format!("{:.0}/{:.0}", peer.full_per_s, peer.deltas_per_s)}
+                        </td>
+                        <td class="text-right">{peer.last_bytes}</td>
+                    </tr>
+                }
+            })
+            .collect_view();
+        view! {
+            <p>
+                {format!(
+                    "{:.0} fps  cpu {:.2} ms (max {:.2})",
+                    report.fps,
+                    report.cpu_ms,
+                    report.cpu_max_ms,
+                )}
+            </p>
+            <p>{format!("gpu {} ms{}", format_gpu(report.gpu_ms), gpu_note)}</p>
+            <p>
+                {format!(
+                    "draws {}  tris {}  state {}",
+                    report.counters.draw_calls,
+                    report.counters.triangles,
+                    report.counters.state_changes,
+                )}
+            </p>
+            <svg
+                class="my-1 bg-black/40"
+                width=GRAPH_WIDTH
+                height=GRAPH_HEIGHT
+                viewBox=format!("0 0 {} {}", GRAPH_WIDTH, GRAPH_HEIGHT)
+            >
+                <line
+                    x1="0"
+                    x2=GRAPH_WIDTH
+                    y1=target_y
+                    y2=target_y
+                    stroke="#4ade80"
+                    stroke-dasharray="4 2"
+                    stroke-width="1"
+                />
+                <polyline points=points fill="none" stroke="#facc15" stroke-width="1" />
+            </svg>
+            <table class="w-full">
+                <thead>
+                    <tr class="text-gray-400">
+                        <th class="text-left">"task"</th>
+                        <th class="text-right">"cpu"</th>
+                        <th class="text-right">"gpu"</th>
+                        <th class="text-right">"draws"</th>
+                    </tr>
+                </thead>
+                <tbody>{tasks}</tbody>
+            </table>
+            {has_peers
+                .then(|| {
+                    view! {
+                <table class="w-full mt-1">
+                    <thead>
+                        <tr class="text-gray-400">
+                            <th class="text-left">"peer"</th>
+                            <th class="text-right">"up kB/s"</th>
+                            <th class="text-right">"down kB/s"</th>
+                            <th class="text-right">"msg/s"</th>
+                        </tr>
+                    </thead>
+                    <tbody>{peers}</tbody>
+                </table>
+                    }
+                })}
+            {has_snapshots
+                .then(|| {
+                    view! {
+                <table class="w-full mt-1">
+                    <thead>
+                        <tr class="text-gray-400">
+                            <th class="text-left">"client"</th>
+                            <th class="text-right">"snap kB/s"</th>
+                            <th class="text-right">"full/delta"</th>
+                            <th class="text-right">"last B"</th>
+                        </tr>
+                    </thead>
+                    <tbody>{snapshots}</tbody>
+                </table>
+                    }
+                })}
+            {topology}
+        }
+    };
+
+    view! {
+        <button
+            on:click=move |_| profiler.toggle()
+            class="fixed top-2 right-2 z-50 bg-gray-800/80 hover:bg-gray-700 text-white text-xs font-mono py-1 px-2 rounded"
+        >
+            "Stats"
+        </button>
+        <Show when=move || profiler.visible()>
+            <div class="fixed top-10 right-2 z-50 w-64 p-2 rounded bg-gray-900/85 text-white text-xs font-mono pointer-events-none">
+                {stats}
+            </div>
+        </Show>
+    }
+}
@@ -0,0 +1,81 @@
+use wasm_bindgen::JsCast;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{HtmlImageElement, Response};
+
+use crate::libs::types::errors::ErrorStr;
+
+pub async fn fetch_text(url: &str) -> Result<String, ErrorStr> {
+    let window = if let Some(window) = web_sys::window() {
+        window
+    } else {
+        let error = format!("No window when fetching '{}'", url);
+        return Err(ErrorStr::new(error));
+    };
+    let response = match JsFuture::from(window.fetch_with_str(url)).await {
+        Ok(response) => response,
+        Err(error) => {
+            let error = format!("Unable to fetch '{}': {:?}", url, error);
+            return Err(ErrorStr::new(error));
+        }
+    };
+    let response = match response.dyn_into::<Response>() {
+        Ok(response) => response,
+        Err(_) => {
+            let error = format!("Fetch of '{}' did not return a Response", url);
+            return Err(ErrorStr::new(error));
+        }
+    };
+    if !response.ok() {
+        let error = format!("Fetch of '{}' failed with status {}", url, response.status());
+        return Err(ErrorStr::new(error));
+    }
+    let text_promise = match response.text() {
+        Ok(promise) => promise,
+        Err(error) => {
+            let error = format!("Unable to read '{}': {:?}", url, error);
+            return Err(ErrorStr::new(error));
+        }
+    };
+    let text = match JsFuture::from(text_promise).await {
+        Ok(text) => text,
+        Err(error) => {
+            let error = format!("Unable to read '{}': {:?}", url, error);
+            return Err(ErrorStr::new(error));
+        }
+    };
+    if let Some(text) = text.as_string() {
+        Ok(text)
+    } else {
+        let error = format!("'{}' is not text", url);
+        Err(ErrorStr::new(error))
+    }
+}
+
+// resolves once the image has decoded, so it can be uploaded straight away
+pub async fn load_image(url: &str) -> Result<HtmlImageElement, ErrorStr> {
+    let image = match HtmlImageElement::new() {
+        Ok(image) => image,
+        Err(error) => {
+            let error = format!("Unable to create image for '{}': {:?}", url, error);
+            return Err(ErrorStr::new(error));
+        }
+    };
+    let promise_image = image.clone();
+    let promise = js_sys::Promise::new(&mut |resolve, reject| {
+        promise_image.set_onload(Some(&resolve));
+        promise_image.set_onerror(Some(&reject));
+    });
+    image.set_cross_origin(Some("anonymous"));
+    image.set_src(url);
+    let result = JsFuture::from(promise).await;
+    image.set_onload(None);
+    image.set_onerror(None);
+    match result {
+        Ok(_) => Ok(image),
+        Err(_) => {
+            let error = format!("Unable to load image '{}'", url);
+            Err(ErrorStr::new(error))
+        }
+    }
+}
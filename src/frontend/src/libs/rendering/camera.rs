@@ -0,0 +1,106 @@
+use glam::{Mat4, Vec2, Vec3};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    // world units are pixels at zoom 1, y up
+    Orthographic { zoom: f32 },
+    // fov_y in radians
+    Perspective { fov_y: f32, near: f32, far: f32 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camera {
+    pub position: Vec3,
+    pub target: Vec3,
+    pub up: Vec3,
+    pub projection: Projection,
+    viewport: Vec2,
+}
+
+impl Camera {
+    pub fn orthographic(center: Vec2, zoom: f32, viewport: Vec2) -> Self {
+        Self {
+            position: center.extend(1.0),
+            target: center.extend(0.0),
+            up: Vec3::Y,
+            projection: Projection::Orthographic { zoom },
+            viewport,
+        }
+    }
+
+    pub fn perspective(position: Vec3, target: Vec3, fov_y: f32, viewport: Vec2) -> Self {
+        Self {
+            position,
+            target,
+            up: Vec3::Y,
+            projection: Projection::Perspective {
+                fov_y,
+                near: 0.1,
+                far: 1000.0,
+            },
+            viewport,
+        }
+    }
+
+    // should be called from a resize task
+    pub fn set_viewport(&mut self, width: f32, height: f32) {
+        self.viewport = Vec2::new(width.max(1.0), height.max(1.0));
+    }
+
+    #[inline]
+    pub fn viewport(&self) -> Vec2 {
+        self.viewport
+    }
+
+    // moves the camera in the view plane, keeping the view direction
+    pub fn pan(&mut self, offset: Vec2) {
+        let offset = offset.extend(0.0);
+        self.position += offset;
+        self.target += offset;
+    }
+
+    pub fn view(&self) -> Mat4 {
+        Mat4::look_at_rh(self.position, self.target, self.up)
+    }
+
+    pub fn projection(&self) -> Mat4 {
+        match self.projection {
+            Projection::Orthographic { zoom } => {
+                let half = self.viewport * 0.5 / zoom.max(f32::EPSILON);
+                Mat4::orthographic_rh_gl(-half.x, half.x, -half.y, half.y, -1000.0, 1000.0)
+            }
+            Projection::Perspective { fov_y, near, far } => {
+                let aspect = self.viewport.x / self.viewport.y;
+                Mat4::perspective_rh_gl(fov_y, aspect, near, far)
+            }
+        }
+    }
+
+    pub fn view_projection(&self) -> Mat4 {
+        self.projection() * self.view()
+    }
+
+    // the world space rectangle (min, max) visible on the z = 0 plane
+    pub fn visible_rect(&self) -> (Vec2, Vec2) {
+        let top_left = self.screen_to_world(Vec2::ZERO);
+        let bottom_right = self.screen_to_world(self.viewport);
+        (top_left.min(bottom_right), top_left.max(bottom_right))
+    }
+
+    // converts a canvas pixel position (origin top left) to world space on the z = 0 plane
+    pub fn screen_to_world(&self, screen: Vec2) -> Vec2 {
+        let ndc = Vec2::new(
+            screen.x / self.viewport.x * 2.0 - 1.0,
+            1.0 - screen.y / self.viewport.y * 2.0,
+        );
+        let inverse = self.view_projection().inverse();
+        let near = inverse.project_point3(ndc.extend(-1.0));
+        let far = inverse.project_point3(ndc.extend(1.0));
+        let direction = far - near;
+        if direction.z.abs() < f32::EPSILON {
+            return near.truncate();
+        }
+        let t = -near.z / direction.z;
+        (near + direction * t).truncate()
+    }
+}
@@ -1,10 +1,13 @@
 use crate::define_init_task;
 use crate::define_task;
+use crate::libs::ecs::game_loop::GameLoop;
+use crate::libs::profiler::{FrameProfiler, ProfilerHandle};
+use crate::libs::rendering::debug_draw::DebugDraw;
 use crate::libs::types::errors::ErrorStr;
 use crate::libs::types::shared::*;
+use crate::{log_debug, log_error, log_info, log_trace};
 
 use leptos::html::Canvas;
-use leptos::logging;
 use leptos::prelude::*;
 use leptos_use::{use_raf_fn, use_resize_observer, UseRafFnCallbackArgs};
 
@@ -15,6 +18,8 @@ use std::cell::{Ref, RefMut};
 use std::fmt;
 use std::rc::Rc;
 
+const LOG_TARGET: &str = "rendering::canvas";
+
 pub trait InitTaskFnTrait = FnMut(&WebGlCanvas) -> bool + 'static;
 define_init_task!(InitTask, InitTaskFn, InitTaskFnTrait, (canvas: &WebGlCanvas), bool);
 
@@ -25,14 +30,38 @@ define_task!(ResizeTask, ResizeTaskFn, ResizeTaskFnTrait, (canvas: &WebGlCanvas,
 pub trait RafTaskFnTrait = FnMut(&WebGlCanvas, RafTime) -> Result<(), ErrorStr> + 'static;
 define_task!(RafTask, RafTaskFn, RafTaskFnTrait, (canvas: &WebGlCanvas, timestamp: RafTime), Result<(), ErrorStr>);
 
+#[derive(Debug, Clone, Copy)]
 pub struct RafTime {
     delta: f64,
     timestamp: f64,
+    alpha: f64,
 }
 
 impl RafTime {
-    fn new(delta: f64, timestamp: f64) -> Self {
-        Self { delta, timestamp }
+    fn new(delta: f64, timestamp: f64, alpha: f64) -> Self {
+        Self {
+            delta,
+            timestamp,
+            alpha,
+        }
+    }
+
+    // milliseconds since the last frame
+    #[inline]
+    pub fn delta(&self) -> f64 {
+        self.delta
+    }
+
+    // milliseconds, same clock as performance.now()
+    #[inline]
+    pub fn timestamp(&self) -> f64 {
+        self.timestamp
+    }
+
+    // position between the previous and the current simulation tick, 1 without a game loop
+    #[inline]
+    pub fn alpha(&self) -> f64 {
+        self.alpha
     }
 }
 
@@ -53,11 +82,18 @@ pub struct WebGlCanvas<'a> {
     // tracking
     name: Rc<&'a str>,
 
+    // debugging
+    debug_draw: SharedRefCell<DebugDraw>,
+    profiler: SharedRefCell<FrameProfiler<'a>>,
+    profiler_handle: ProfilerHandle,
+
     // tasks
     init_tasks: SharedRefCell<Vec<InitTask<'a>>>,
     resize_tasks: SharedRefCell<Vec<ResizeTask<'a>>>,
     // passes the time from last frame
     raf_tasks: SharedRefCell<Vec<RafTask<'a>>>,
+    // fixed rate simulation, stepped before the raf tasks
+    game_loop: SharedRefCell<Option<GameLoop<'a>>>,
 }
 
 impl<'a> fmt::Display for WebGlCanvas<'a> {
@@ -73,6 +109,8 @@ impl<'a> WebGlCanvas<'a> {
         let (init_width, init_set_width) = signal(0u32);
         let (init_height, init_set_height) = signal(0u32);
         let (init_initialised, init_set_initialised) = signal(false);
+        let init_profiler = FrameProfiler::new();
+        let init_profiler_handle = init_profiler.handle();
         Self {
             // data
             context: shared_ref_cell(init_context),
@@ -89,12 +127,18 @@ impl<'a> WebGlCanvas<'a> {
             // tracking
             name: Rc::new(name),
 
+            // debugging
+            debug_draw: shared_ref_cell(DebugDraw::new()),
+            profiler: shared_ref_cell(init_profiler),
+            profiler_handle: init_profiler_handle,
+
             // tasks
             // all tasks should already be given a copy of WebGlCanvas in a closure, to reduce copy
             // operations
             init_tasks: shared_ref_cell(Vec::<InitTask>::new()),
             resize_tasks: shared_ref_cell(Vec::<ResizeTask>::new()),
             raf_tasks: shared_ref_cell(Vec::<RafTask>::new()),
+            game_loop: shared_ref_cell(None),
         }
     }
 
@@ -106,6 +150,15 @@ impl<'a> WebGlCanvas<'a> {
         self.resize_tasks.borrow_mut().push(task);
     }
 
+    // replaces any previous game loop
+    pub fn set_game_loop(&self, game_loop: GameLoop<'a>) {
+        *self.game_loop.borrow_mut() = Some(game_loop);
+    }
+
+    pub fn game_loop(&self) -> RefMut<'_, Option<GameLoop<'a>>> {
+        self.game_loop.borrow_mut()
+    }
+
     pub fn add_raf_task(&self, task: RafTask<'a>) {
         self.raf_tasks.borrow_mut().push(task);
     }
@@ -126,10 +179,21 @@ impl<'a> WebGlCanvas<'a> {
         self.canvas_ref.borrow_mut()
     }
 
+    // queue for lines, shapes and labels drawn on top of everything this frame
+    pub fn debug_draw(&self) -> RefMut<'_, DebugDraw> {
+        self.debug_draw.borrow_mut()
+    }
+
+    // frame stats for the ProfilerOverlay component
+    #[inline]
+    pub fn profiler(&self) -> ProfilerHandle {
+        self.profiler_handle
+    }
+
     // this must be called inside the canvas component's setup code!
     // should never be called outside of a WebGlCanvasComponent (or derivative of that)
     pub fn setup(&self) {
-        logging::log!("Setting up WebGlCanvas: {}", self);
+        log_info!(LOG_TARGET, "Setting up WebGlCanvas: {}", self);
         let canvas_ref = NodeRef::<Canvas>::new();
         self.set_canvas_ref(Some(canvas_ref));
     }
@@ -139,19 +203,19 @@ impl<'a> WebGlCanvas<'a> {
     pub fn init(&self) {
         if !self.initialised.get() {
             self.set_initialised.set(true);
-            logging::log!("Initialising WebGlCanvas: {}", self);
+            log_info!(LOG_TARGET, "Initialising WebGlCanvas: {}", self);
             let canvas_ref_opt = (*self.canvas_ref.borrow());
             let canvas_ref = if let Some(canvas_ref) = canvas_ref_opt {
                 canvas_ref
             } else {
-                logging::error!("Canvas NodeRef is None, in {:?}", self);
+                log_error!(LOG_TARGET, "Canvas NodeRef is None, in {:?}", self);
                 self.set_initialised.set(false);
                 return;
             };
             let canvas = if let Some(canvas) = canvas_ref.get() {
                 canvas
             } else {
-                logging::error!("HtmlCanvasElement is None, in {:?}", self);
+                log_error!(LOG_TARGET, "HtmlCanvasElement is None, in {:?}", self);
                 self.set_initialised.set(false);
                 return;
             };
@@ -161,7 +225,8 @@ impl<'a> WebGlCanvas<'a> {
             let context_opt = match canvas_element.get_context("webgl2") {
                 Ok(context_opt) => context_opt,
                 Err(error) => {
-                    logging::error!(
+                    log_error!(
+                        LOG_TARGET,
                         "Error when getting canvas webgl2 context. Error: {:?}, in {:?}",
                         error,
                         self
@@ -173,16 +238,18 @@ impl<'a> WebGlCanvas<'a> {
             let context_obj = if let Some(context) = context_opt {
                 context
             } else {
-                logging::error!("Canvas webgl2 context is None, in {:?}", self);
+                log_error!(LOG_TARGET, "Canvas webgl2 context is None, in {:?}", self);
                 self.set_initialised.set(false);
                 return;
             };
             let context = match context_obj.dyn_into::<WebGl2RenderingContext>() {
                 Ok(context) => context,
                 Err(_) => {
-                    logging::error!(
-                                        "Could not dyn_into canvas webgl2 context into webgl2 rendering context, in {:?}", self
-                                    );
+                    log_error!(
+                        LOG_TARGET,
+                        "Could not dyn_into canvas webgl2 context into webgl2 rendering context, in {:?}",
+                        self
+                    );
                     self.set_context(None);
                     self.set_initialised.set(false);
                     return;
@@ -200,7 +267,11 @@ impl<'a> WebGlCanvas<'a> {
 
     pub fn run_resize_tasks(&self, entries: Vec<ResizeObserverEntry>) {
         if !self.initialised.get() {
-            logging::error!("Called run_resize_tasks when uninitialised, in {:?}", self);
+            log_error!(
+                LOG_TARGET,
+                "Called run_resize_tasks when uninitialised, in {:?}",
+                self
+            );
             return;
         }
         let mut tasks = self.resize_tasks.borrow_mut();
@@ -208,10 +279,16 @@ impl<'a> WebGlCanvas<'a> {
             let result = task.execute(self, &entries[0]);
             match result {
                 Ok(_) => {
-                    logging::log!("Resize task \"{}\" successfully executed in {}", task, self);
+                    log_debug!(
+                        LOG_TARGET,
+                        "Resize task \"{}\" successfully executed in {}",
+                        task,
+                        self
+                    );
                 }
                 Err(error) => {
-                    logging::error!(
+                    log_error!(
+                        LOG_TARGET,
                         "Resize task error: {}, for task {} in {:?}",
                         error,
                         task,
@@ -225,21 +302,100 @@ impl<'a> WebGlCanvas<'a> {
     pub fn run_raf_tasks(&self, timestamp: UseRafFnCallbackArgs) {
         if !self.initialised.get() {
             // this is log, as it is intended, and used for synchronisation
-            // logging::log!("Called run_raf_tasks when uninitialised, in {:?}", self);
+            // log_trace!(LOG_TARGET, "Called run_raf_tasks when uninitialised, in {:?}", self);
             return;
         }
+        // cloned so tasks can still borrow the context themselves
+        let gl = self.context.borrow().clone();
+        let mut profiler = self.profiler.borrow_mut();
+        profiler.begin_frame(gl.as_ref());
+        let alpha = self.advance_game_loop(&mut profiler, gl.as_ref(), timestamp.delta);
+        let time = RafTime::new(timestamp.delta, timestamp.timestamp, alpha);
         let mut tasks = self.raf_tasks.borrow_mut();
         for task in tasks.iter_mut() {
-            let result = task.execute(self, RafTime::new(timestamp.delta, timestamp.timestamp));
+            profiler.begin_task(gl.as_ref(), task.name());
+            let result = task.execute(self, time);
+            profiler.end_task(gl.as_ref());
             match result {
                 Ok(_) => {
-                    logging::log!("RAF task \"{}\" successfully executed in {}", task, self);
+                    // every frame, so only visible with rendering::canvas=trace
+                    log_trace!(
+                        LOG_TARGET,
+                        "RAF task \"{}\" successfully executed in {}",
+                        task,
+                        self
+                    );
                 }
                 Err(error) => {
-                    logging::error!("RAF task error: {}, for task {} in {:?}", error, task, self);
+                    log_error!(
+                        LOG_TARGET,
+                        "RAF task error: {}, for task {} in {:?}",
+                        error,
+                        task,
+                        self
+                    );
                 }
             }
         }
+        drop(tasks);
+        profiler.begin_task(gl.as_ref(), "Debug draw");
+        self.flush_debug_draw();
+        profiler.end_task(gl.as_ref());
+        profiler.end_frame();
+    }
+
+    fn advance_game_loop(
+        &self,
+        profiler: &mut FrameProfiler<'a>,
+        gl: Option<&WebGl2RenderingContext>,
+        delta: f64,
+    ) -> f64 {
+        let mut game_loop = self.game_loop.borrow_mut();
+        let game_loop = if let Some(game_loop) = game_loop.as_mut() {
+            game_loop
+        } else {
+            return 1.0;
+        };
+        profiler.begin_task(gl, "Simulation");
+        let result = game_loop.advance(delta);
+        profiler.end_task(gl);
+        match result {
+            Ok(alpha) => alpha,
+            Err(error) => {
+                // the world is left mid tick, so stop rather than simulate on from it
+                log_error!(
+                    LOG_TARGET,
+                    "Simulation error: {}, pausing game loop in {:?}",
+                    error,
+                    self
+                );
+                game_loop.set_paused(true);
+                1.0
+            }
+        }
+    }
+
+    // drawn after every raf task so debug primitives end up on top
+    fn flush_debug_draw(&self) {
+        let mut debug_draw = self.debug_draw.borrow_mut();
+        if !debug_draw.enabled() {
+            return;
+        }
+        let context = self.context.borrow();
+        let gl = if let Some(gl) = context.as_ref() {
+            gl
+        } else {
+            debug_draw.clear();
+            return;
+        };
+        let canvas = self
+            .canvas_ref
+            .borrow()
+            .and_then(|canvas_ref| canvas_ref.get_untracked());
+        if let Err(error) = debug_draw.flush(gl, canvas.as_ref()) {
+            log_error!(LOG_TARGET, "Debug draw error: {}, in {:?}", error, self);
+            debug_draw.set_enabled(false);
+        }
     }
 
     fn set_canvas_ref(&self, canvas_ref: Option<NodeRef<Canvas>>) {
@@ -269,9 +425,14 @@ pub fn WebGlCanvasComponent(web_gl_canvas: WebGlCanvas<'static>) -> impl IntoVie
         });
         use_raf_fn(move |timestamp| web_gl_canvas_raf.run_raf_tasks(timestamp));
 
-        view! { <canvas node_ref=canvas_ref>"Your browser does not support the canvas element."</canvas> }.into_any()
+        // fills its parent, the resize tasks then match the drawing buffer to it
+        view! { <canvas node_ref=canvas_ref class="block w-full h-full">"Your browser does not support the canvas element."</canvas> }.into_any()
     } else {
-        logging::error!("web_gl_canvas.canvas_ref is None, in {:?}", web_gl_canvas);
+        log_error!(
+            LOG_TARGET,
+            "web_gl_canvas.canvas_ref is None, in {:?}",
+            web_gl_canvas
+        );
         let error_msg = format!(
             "Error: web_gl_canvas.canvas_ref is None, in {:?}",
             web_gl_canvas,
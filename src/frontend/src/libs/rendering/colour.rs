@@ -0,0 +1,48 @@
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Colour {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+impl Colour {
+    pub const WHITE: Colour = Colour::rgb(1.0, 1.0, 1.0);
+    pub const BLACK: Colour = Colour::rgb(0.0, 0.0, 0.0);
+    pub const RED: Colour = Colour::rgb(1.0, 0.0, 0.0);
+    pub const GREEN: Colour = Colour::rgb(0.0, 1.0, 0.0);
+    pub const BLUE: Colour = Colour::rgb(0.0, 0.0, 1.0);
+    pub const YELLOW: Colour = Colour::rgb(1.0, 1.0, 0.0);
+    pub const CYAN: Colour = Colour::rgb(0.0, 1.0, 1.0);
+    pub const MAGENTA: Colour = Colour::rgb(1.0, 0.0, 1.0);
+    pub const TRANSPARENT: Colour = Colour::rgba(0.0, 0.0, 0.0, 0.0);
+
+    pub const fn rgba(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Self { r, g, b, a }
+    }
+
+    pub const fn rgb(r: f32, g: f32, b: f32) -> Self {
+        Self::rgba(r, g, b, 1.0)
+    }
+
+    // 0xRRGGBBAA
+    pub fn from_hex(hex: u32) -> Self {
+        let channel = |shift: u32| ((hex >> shift) & 0xFF) as f32 / 255.0;
+        Self::rgba(channel(24), channel(16), channel(8), channel(0))
+    }
+
+    pub fn with_alpha(self, a: f32) -> Self {
+        Self { a, ..self }
+    }
+
+    #[inline]
+    pub fn to_array(self) -> [f32; 4] {
+        [self.r, self.g, self.b, self.a]
+    }
+}
+
+impl From<[f32; 4]> for Colour {
+    fn from(value: [f32; 4]) -> Self {
+        Self::rgba(value[0], value[1], value[2], value[3])
+    }
+}
@@ -0,0 +1,451 @@
+use glam::{Mat4, Vec2, Vec3, Vec4Swizzles};
+use wasm_bindgen::JsCast;
+use web_sys::{
+    CanvasRenderingContext2d, HtmlCanvasElement, WebGl2RenderingContext, WebGlProgram,
+    WebGlUniformLocation,
+};
+
+use crate::libs::profiler::counters::{record_draw, record_state_change};
+use crate::libs::rendering::colour::Colour;
+use crate::libs::rendering::gl::buffer::{Buffer, BufferTarget, BufferUsage};
+use crate::libs::rendering::gl::program::{link_program_sources, uniform_location};
+use crate::libs::rendering::gl::vao::VertexArray;
+use crate::libs::rendering::shaders::debug_line;
+use crate::libs::types::errors::ErrorStr;
+
+// on in debug builds, release needs the debug-draw feature. when off, enabled() is a constant
+// false, so every call below returns straight away and its body is optimised out. callers never
+// need their own cfg
+pub const DEBUG_DRAW_ENABLED: bool = cfg!(any(debug_assertions, feature = "debug-draw"));
+
+// position (3), colour (4)
+const VERTEX_FLOATS: usize = 7;
+const VERTEX_STRIDE: i32 = (VERTEX_FLOATS * 4) as i32;
+const CIRCLE_SEGMENTS: usize = 32;
+const LABEL_FONT: &str = "12px monospace";
+
+#[derive(Debug, Clone)]
+struct DebugLabel {
+    position: Vec3,
+    text: String,
+    colour: Colour,
+}
+
+// per canvas queue of debug primitives. anything holding the canvas can push to it during a
+// frame, it is drawn after every raf task and then cleared
+#[derive(Debug, Default)]
+pub struct DebugDraw {
+    lines: Vec<f32>,
+    labels: Vec<DebugLabel>,
+    view_projection: Mat4,
+    enabled: bool,
+    renderer: Option<DebugDrawRenderer>,
+    overlay: Option<LabelOverlay>,
+}
+
+impl DebugDraw {
+    pub fn new() -> Self {
+        Self {
+            view_projection: Mat4::IDENTITY,
+            enabled: DEBUG_DRAW_ENABLED,
+            ..Default::default()
+        }
+    }
+
+    // runtime toggle, e.g. from a key binding. has no effect when the feature is off
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = DEBUG_DRAW_ENABLED && enabled;
+        if !self.enabled {
+            self.clear();
+            // flush stops drawing, so the last frame's labels would stay up
+            if let Some(overlay) = self.overlay.as_ref() {
+                overlay.clear();
+            }
+        }
+    }
+
+    #[inline]
+    pub fn enabled(&self) -> bool {
+        DEBUG_DRAW_ENABLED && self.enabled
+    }
+
+    // world to clip transform used for this frame's primitives, usually camera.view_projection()
+    pub fn set_view_projection(&mut self, view_projection: Mat4) {
+        self.view_projection = view_projection;
+    }
+
+    pub fn clear(&mut self) {
+        self.lines.clear();
+        self.labels.clear();
+    }
+
+    #[inline]
+    pub fn line(&mut self, a: Vec3, b: Vec3, colour: Colour) {
+        if !self.enabled() {
+            return;
+        }
+        let c = colour.to_array();
+        self.lines
+            .extend_from_slice(&[a.x, a.y, a.z, c[0], c[1], c[2], c[3]]);
+        self.lines
+            .extend_from_slice(&[b.x, b.y, b.z, c[0], c[1], c[2], c[3]]);
+    }
+
+    pub fn line_2d(&mut self, a: Vec2, b: Vec2, colour: Colour) {
+        self.line(a.extend(0.0), b.extend(0.0), colour);
+    }
+
+    pub fn arrow(&mut self, from: Vec3, to: Vec3, colour: Colour) {
+        if !self.enabled() {
+            return;
+        }
+        self.line(from, to, colour);
+        let direction = to - from;
+        let length = direction.length();
+        if length <= f32::EPSILON {
+            return;
+        }
+        let direction = direction / length;
+        // the head lies in the plane facing the 2d camera unless the arrow points along z
+        let side = if direction.cross(Vec3::Z).length_squared() > 1e-6 {
+            direction.cross(Vec3::Z).normalize()
+        } else {
+            Vec3::X
+        };
+        let head = length.min(16.0) * 0.25;
+        let back = to - direction * head;
+        self.line(to, back + side * head * 0.5, colour);
+        self.line(to, back - side * head * 0.5, colour);
+    }
+
+    // red x, green y, blue z
+    pub fn axes(&mut self, transform: Mat4, size: f32) {
+        if !self.enabled() {
+            return;
+        }
+        let origin = transform.transform_point3(Vec3::ZERO);
+        self.arrow(origin, transform.transform_point3(Vec3::X * size), Colour::RED);
+        self.arrow(origin, transform.transform_point3(Vec3::Y * size), Colour::GREEN);
+        self.arrow(origin, transform.transform_point3(Vec3::Z * size), Colour::BLUE);
+    }
+
+    pub fn wire_box(&mut self, min: Vec3, max: Vec3, colour: Colour) {
+        let center = (min + max) * 0.5;
+        let transform = Mat4::from_translation(center);
+        self.wire_box_transformed(transform, (max - min) * 0.5, colour);
+    }
+
+    // an oriented box, e.g. an entity's world transform and its half extents
+    pub fn wire_box_transformed(&mut self, transform: Mat4, half_extents: Vec3, colour: Colour) {
+        if !self.enabled() {
+            return;
+        }
+        let corner = |i: usize| -> Vec3 {
+            let sign = Vec3::new(
+                if i & 1 == 0 { -1.0 } else { 1.0 },
+                if i & 2 == 0 { -1.0 } else { 1.0 },
+                if i & 4 == 0 { -1.0 } else { 1.0 },
+            );
+            transform.transform_point3(sign * half_extents)
+        };
+        const EDGES: [(usize, usize); 12] = [
+            (0, 1),
+            (2, 3),
+            (4, 5),
+            (6, 7),
+            (0, 2),
+            (1, 3),
+            (4, 6),
+            (5, 7),
+            (0, 4),
+            (1, 5),
+            (2, 6),
+            (3, 7),
+        ];
+        for (a, b) in EDGES {
+            self.line(corner(a), corner(b), colour);
+        }
+    }
+
+    // square grid on the z = 0 plane
+    pub fn grid(&mut self, center: Vec2, cell: f32, cells: u32, colour: Colour) {
+        if !self.enabled() || cell <= 0.0 {
+            return;
+        }
+        let half = cell * cells as f32 * 0.5;
+        for i in 0..=cells {
+            let offset = -half + cell * i as f32;
+            self.line_2d(
+                center + Vec2::new(offset, -half),
+                center + Vec2::new(offset, half),
+                colour,
+            );
+            self.line_2d(
+                center + Vec2::new(-half, offset),
+                center + Vec2::new(half, offset),
+                colour,
+            );
+        }
+    }
+
+    pub fn cross(&mut self, point: Vec3, size: f32, colour: Colour) {
+        let half = size * 0.5;
+        self.line(point - Vec3::X * half, point + Vec3::X * half, colour);
+        self.line(point - Vec3::Y * half, point + Vec3::Y * half, colour);
+    }
+
+    // collision shapes, all on the z = 0 plane
+
+    pub fn circle(&mut self, center: Vec2, radius: f32, colour: Colour) {
+        if !self.enabled() {
+            return;
+        }
+        let points: Vec<Vec2> = (0..CIRCLE_SEGMENTS)
+            .map(|i| {
+                let angle = std::f32::consts::TAU * i as f32 / CIRCLE_SEGMENTS as f32;
+                center + Vec2::from_angle(angle) * radius
+            })
+            .collect();
+        self.polygon(&points, colour);
+    }
+
+    pub fn rect(&mut self, min: Vec2, max: Vec2, colour: Colour) {
+        self.polygon(
+            &[min, Vec2::new(max.x, min.y), max, Vec2::new(min.x, max.y)],
+            colour,
+        );
+    }
+
+    pub fn polygon(&mut self, points: &[Vec2], colour: Colour) {
+        if !self.enabled() || points.len() < 2 {
+            return;
+        }
+        for i in 0..points.len() {
+            self.line_2d(points[i], points[(i + 1) % points.len()], colour);
+        }
+    }
+
+    pub fn capsule(&mut self, a: Vec2, b: Vec2, radius: f32, colour: Colour) {
+        if !self.enabled() {
+            return;
+        }
+        let direction = (b - a).normalize_or(Vec2::Y);
+        let normal = direction.perp() * radius;
+        self.line_2d(a + normal, b + normal, colour);
+        self.line_2d(a - normal, b - normal, colour);
+        let half = CIRCLE_SEGMENTS / 2;
+        for (center, start) in [(b, normal), (a, -normal)] {
+            let start_angle = start.to_angle();
+            for i in 0..half {
+                let angle_a = start_angle - std::f32::consts::PI * i as f32 / half as f32;
+                let angle_b = start_angle - std::f32::consts::PI * (i + 1) as f32 / half as f32;
+                self.line_2d(
+                    center + Vec2::from_angle(angle_a) * radius,
+                    center + Vec2::from_angle(angle_b) * radius,
+                    colour,
+                );
+            }
+        }
+    }
+
+    // drawn in screen space on an overlay, anchored to a world position
+    pub fn text<S>(&mut self, position: Vec3, text: S, colour: Colour)
+    where
+        S: Into<String>,
+    {
+        if !self.enabled() {
+            return;
+        }
+        self.labels.push(DebugLabel {
+            position,
+            text: text.into(),
+            colour,
+        });
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.lines.len() / VERTEX_FLOATS / 2
+    }
+
+    // draws and clears the queue. called by the canvas after its raf tasks
+    pub fn flush(
+        &mut self,
+        gl: &WebGl2RenderingContext,
+        canvas: Option<&HtmlCanvasElement>,
+    ) -> Result<(), ErrorStr> {
+        if !self.enabled() {
+            return Ok(());
+        }
+        if !self.lines.is_empty() {
+            if self.renderer.is_none() {
+                self.renderer = Some(DebugDrawRenderer::new(gl)?);
+            }
+            if let Some(renderer) = self.renderer.as_mut() {
+                renderer.draw(gl, self.view_projection, &self.lines);
+            }
+        }
+        if let Some(canvas) = canvas {
+            if self.overlay.is_none() && !self.labels.is_empty() {
+                self.overlay = Some(LabelOverlay::new()?);
+            }
+            if let Some(overlay) = self.overlay.as_ref() {
+                overlay.draw(canvas, self.view_projection, &self.labels);
+            }
+        }
+        self.clear();
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct DebugDrawRenderer {
+    program: WebGlProgram,
+    view_projection: WebGlUniformLocation,
+    vao: VertexArray,
+    vertices: Buffer,
+}
+
+impl DebugDrawRenderer {
+    fn new(gl: &WebGl2RenderingContext) -> Result<Self, ErrorStr> {
+        let name = "Debug draw";
+        let program = link_program_sources(
+            gl,
+            debug_line::VERT_SHADER,
+            debug_line::FRAG_SHADER,
+            &[],
+            name,
+        )?;
+        let view_projection = uniform_location(gl, &program, "uViewProjection", name)?;
+        let vao = VertexArray::new(gl, "Debug draw lines")?;
+        vao.bind(gl);
+        let vertices = Buffer::new(
+            gl,
+            BufferTarget::Array,
+            BufferUsage::StreamDraw,
+            "Debug draw vertices",
+        )?;
+        vertices.bind(gl);
+        vao.attrib_f32(gl, 0, 3, VERTEX_STRIDE, 0, 0);
+        vao.attrib_f32(gl, 1, 4, VERTEX_STRIDE, 12, 0);
+        vao.unbind(gl);
+        vertices.unbind(gl);
+        Ok(Self {
+            program,
+            view_projection,
+            vao,
+            vertices,
+        })
+    }
+
+    fn draw(&mut self, gl: &WebGl2RenderingContext, view_projection: Mat4, lines: &[f32]) {
+        gl.use_program(Some(&self.program));
+        record_state_change();
+        gl.uniform_matrix4fv_with_f32_array(
+            Some(&self.view_projection),
+            false,
+            &view_projection.to_cols_array(),
+        );
+        self.vao.bind(gl);
+        self.vertices.upload_f32(gl, lines);
+        // always on top of the scene
+        gl.disable(WebGl2RenderingContext::DEPTH_TEST);
+        gl.enable(WebGl2RenderingContext::BLEND);
+        gl.blend_func(
+            WebGl2RenderingContext::SRC_ALPHA,
+            WebGl2RenderingContext::ONE_MINUS_SRC_ALPHA,
+        );
+        gl.draw_arrays(
+            WebGl2RenderingContext::LINES,
+            0,
+            (lines.len() / VERTEX_FLOATS) as i32,
+        );
+        record_draw(
+            WebGl2RenderingContext::LINES,
+            (lines.len() / VERTEX_FLOATS) as i32,
+            1,
+        );
+        gl.disable(WebGl2RenderingContext::BLEND);
+        self.vao.unbind(gl);
+    }
+}
+
+// a 2d canvas laid over the GL canvas for text, which GL can't draw on its own
+#[derive(Debug)]
+struct LabelOverlay {
+    canvas: HtmlCanvasElement,
+    context: CanvasRenderingContext2d,
+}
+
+impl LabelOverlay {
+    fn new() -> Result<Self, ErrorStr> {
+        let document = if let Some(document) = web_sys::window().and_then(|w| w.document()) {
+            document
+        } else {
+            return Err(ErrorStr::new("No document for the debug label overlay"));
+        };
+        let canvas = match document
+            .create_element("canvas")
+            .map(|element| element.dyn_into::<HtmlCanvasElement>())
+        {
+            Ok(Ok(canvas)) => canvas,
+            _ => return Err(ErrorStr::new("Unable to create the debug label overlay")),
+        };
+        let style = canvas.style();
+        let _ = style.set_property("position", "fixed");
+        let _ = style.set_property("pointer-events", "none");
+        let _ = style.set_property("z-index", "1000");
+        let context = match canvas.get_context("2d") {
+            Ok(Some(context)) => match context.dyn_into::<CanvasRenderingContext2d>() {
+                Ok(context) => context,
+                Err(_) => return Err(ErrorStr::new("Debug label overlay context is not 2d")),
+            },
+            _ => return Err(ErrorStr::new("Unable to get the debug label overlay context")),
+        };
+        if let Some(Err(error)) = document.body().map(|body| body.append_child(&canvas)) {
+            let error = format!("Unable to attach the debug label overlay: {:?}", error);
+            return Err(ErrorStr::new(error));
+        }
+        Ok(Self { canvas, context })
+    }
+
+    fn clear(&self) {
+        let (width, height) = (self.canvas.width(), self.canvas.height());
+        self.context.clear_rect(0.0, 0.0, width as f64, height as f64);
+    }
+
+    fn draw(&self, target: &HtmlCanvasElement, view_projection: Mat4, labels: &[DebugLabel]) {
+        let rect = target.get_bounding_client_rect();
+        let style = self.canvas.style();
+        let _ = style.set_property("left", &format!("{}px", rect.left()));
+        let _ = style.set_property("top", &format!("{}px", rect.top()));
+        let _ = style.set_property("width", &format!("{}px", rect.width()));
+        let _ = style.set_property("height", &format!("{}px", rect.height()));
+        let width = rect.width().max(1.0) as u32;
+        let height = rect.height().max(1.0) as u32;
+        if self.canvas.width() != width || self.canvas.height() != height {
+            self.canvas.set_width(width);
+            self.canvas.set_height(height);
+        }
+        let context = &self.context;
+        context.clear_rect(0.0, 0.0, width as f64, height as f64);
+        context.set_font(LABEL_FONT);
+        for label in labels {
+            let clip = view_projection * label.position.extend(1.0);
+            if clip.w <= 0.0 {
+                continue;
+            }
+            let ndc = clip.xyz() / clip.w;
+            let x = (ndc.x as f64 * 0.5 + 0.5) * width as f64;
+            let y = (0.5 - ndc.y as f64 * 0.5) * height as f64;
+            let c = label.colour;
+            context.set_fill_style_str(&format!(
+                "rgba({}, {}, {}, {})",
+                (c.r * 255.0) as u8,
+                (c.g * 255.0) as u8,
+                (c.b * 255.0) as u8,
+                c.a
+            ));
+            let _ = context.fill_text(&label.text, x, y);
+        }
+    }
+}
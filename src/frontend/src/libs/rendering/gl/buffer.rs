@@ -0,0 +1,155 @@
+use web_sys::{WebGl2RenderingContext, WebGlBuffer};
+
+use crate::libs::types::errors::ErrorStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BufferTarget {
+    Array,
+    ElementArray,
+    TransformFeedback,
+    PixelPack,
+}
+
+impl BufferTarget {
+    pub fn gl_enum(&self) -> u32 {
+        match self {
+            BufferTarget::Array => WebGl2RenderingContext::ARRAY_BUFFER,
+            BufferTarget::ElementArray => WebGl2RenderingContext::ELEMENT_ARRAY_BUFFER,
+            BufferTarget::TransformFeedback => WebGl2RenderingContext::TRANSFORM_FEEDBACK_BUFFER,
+            BufferTarget::PixelPack => WebGl2RenderingContext::PIXEL_PACK_BUFFER,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BufferUsage {
+    StaticDraw,
+    DynamicDraw,
+    StreamDraw,
+    DynamicCopy,
+    StreamRead,
+}
+
+impl BufferUsage {
+    pub fn gl_enum(&self) -> u32 {
+        match self {
+            BufferUsage::StaticDraw => WebGl2RenderingContext::STATIC_DRAW,
+            BufferUsage::DynamicDraw => WebGl2RenderingContext::DYNAMIC_DRAW,
+            BufferUsage::StreamDraw => WebGl2RenderingContext::STREAM_DRAW,
+            BufferUsage::DynamicCopy => WebGl2RenderingContext::DYNAMIC_COPY,
+            BufferUsage::StreamRead => WebGl2RenderingContext::STREAM_READ,
+        }
+    }
+}
+
+// owned GL buffer. keeps track of its allocated size so uploads only reallocate when they grow
+#[derive(Debug)]
+pub struct Buffer {
+    buffer: WebGlBuffer,
+    target: BufferTarget,
+    usage: BufferUsage,
+    size: i32,
+    name: String,
+}
+
+impl Buffer {
+    pub fn new<S>(
+        gl: &WebGl2RenderingContext,
+        target: BufferTarget,
+        usage: BufferUsage,
+        name: S,
+    ) -> Result<Self, ErrorStr>
+    where
+        S: Into<String>,
+    {
+        let name = name.into();
+        let buffer = if let Some(buffer) = gl.create_buffer() {
+            buffer
+        } else {
+            let error = format!("Unable to create buffer '{}'", name);
+            return Err(ErrorStr::new(error));
+        };
+        Ok(Self {
+            buffer,
+            target,
+            usage,
+            size: 0,
+            name,
+        })
+    }
+
+    pub fn bind(&self, gl: &WebGl2RenderingContext) {
+        gl.bind_buffer(self.target.gl_enum(), Some(&self.buffer));
+    }
+
+    pub fn unbind(&self, gl: &WebGl2RenderingContext) {
+        gl.bind_buffer(self.target.gl_enum(), None);
+    }
+
+    // allocates uninitialised storage, discarding the old contents
+    pub fn allocate(&mut self, gl: &WebGl2RenderingContext, size: i32) {
+        self.bind(gl);
+        gl.buffer_data_with_i32(self.target.gl_enum(), size, self.usage.gl_enum());
+        self.size = size;
+    }
+
+    pub fn upload_f32(&mut self, gl: &WebGl2RenderingContext, data: &[f32]) {
+        self.bind(gl);
+        // the view must not outlive this call, any allocation can invalidate wasm memory
+        unsafe {
+            let view = js_sys::Float32Array::view(data);
+            self.upload_view(gl, &view, (data.len() * 4) as i32);
+        }
+    }
+
+    pub fn upload_u16(&mut self, gl: &WebGl2RenderingContext, data: &[u16]) {
+        self.bind(gl);
+        unsafe {
+            let view = js_sys::Uint16Array::view(data);
+            self.upload_view(gl, &view, (data.len() * 2) as i32);
+        }
+    }
+
+    pub fn upload_u32(&mut self, gl: &WebGl2RenderingContext, data: &[u32]) {
+        self.bind(gl);
+        unsafe {
+            let view = js_sys::Uint32Array::view(data);
+            self.upload_view(gl, &view, (data.len() * 4) as i32);
+        }
+    }
+
+    fn upload_view(&mut self, gl: &WebGl2RenderingContext, view: &js_sys::Object, size: i32) {
+        if size > self.size {
+            gl.buffer_data_with_array_buffer_view(self.target.gl_enum(), view, self.usage.gl_enum());
+            self.size = size;
+        } else {
+            gl.buffer_sub_data_with_i32_and_array_buffer_view(self.target.gl_enum(), 0, view);
+        }
+    }
+
+    // copies the start of the buffer back to the cpu. with a pixel pack buffer this must only
+    // happen once its fence has signalled, or the call stalls
+    pub fn read(&self, gl: &WebGl2RenderingContext, data: &mut [u8]) {
+        self.bind(gl);
+        gl.get_buffer_sub_data_with_i32_and_u8_array(self.target.gl_enum(), 0, data);
+    }
+
+    pub fn delete(&self, gl: &WebGl2RenderingContext) {
+        gl.delete_buffer(Some(&self.buffer));
+    }
+
+    #[inline]
+    pub fn buffer(&self) -> &WebGlBuffer {
+        &self.buffer
+    }
+
+    #[inline]
+    pub fn size(&self) -> i32 {
+        self.size
+    }
+
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
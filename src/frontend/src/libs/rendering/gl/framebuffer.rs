@@ -0,0 +1,174 @@
+use web_sys::{WebGl2RenderingContext, WebGlFramebuffer, WebGlRenderbuffer};
+
+use crate::libs::profiler::counters::record_state_change;
+use crate::libs::rendering::gl::texture::{Texture, TextureFilter, TextureFormat};
+use crate::libs::types::errors::ErrorStr;
+
+// off-screen render target: colour textures, plus an optional depth renderbuffer
+#[derive(Debug)]
+pub struct Framebuffer {
+    framebuffer: WebGlFramebuffer,
+    colour: Vec<Texture>,
+    formats: Vec<TextureFormat>,
+    depth: Option<WebGlRenderbuffer>,
+    width: u32,
+    height: u32,
+    name: String,
+}
+
+impl Framebuffer {
+    pub fn new<S>(
+        gl: &WebGl2RenderingContext,
+        width: u32,
+        height: u32,
+        formats: &[TextureFormat],
+        depth: bool,
+        name: S,
+    ) -> Result<Self, ErrorStr>
+    where
+        S: Into<String>,
+    {
+        let name = name.into();
+        let framebuffer = if let Some(framebuffer) = gl.create_framebuffer() {
+            framebuffer
+        } else {
+            let error = format!("Unable to create framebuffer '{}'", name);
+            return Err(ErrorStr::new(error));
+        };
+        let mut target = Self {
+            framebuffer,
+            colour: Vec::new(),
+            formats: formats.to_vec(),
+            depth: None,
+            width,
+            height,
+            name,
+        };
+        if let Err(error) = target.attach(gl, depth) {
+            target.delete(gl);
+            return Err(error);
+        }
+        Ok(target)
+    }
+
+    fn attach(&mut self, gl: &WebGl2RenderingContext, depth: bool) -> Result<(), ErrorStr> {
+        let target = WebGl2RenderingContext::FRAMEBUFFER;
+        gl.bind_framebuffer(target, Some(&self.framebuffer));
+        let attachments = js_sys::Array::new();
+        for (i, format) in self.formats.iter().enumerate() {
+            let texture = Texture::allocate(
+                gl,
+                self.width,
+                self.height,
+                *format,
+                TextureFilter::Linear,
+                format!("{} colour {}", self.name, i),
+            )?;
+            let attachment = WebGl2RenderingContext::COLOR_ATTACHMENT0 + i as u32;
+            gl.framebuffer_texture_2d(
+                target,
+                attachment,
+                WebGl2RenderingContext::TEXTURE_2D,
+                Some(texture.texture()),
+                0,
+            );
+            attachments.push(&attachment.into());
+            self.colour.push(texture);
+        }
+        gl.draw_buffers(&attachments);
+        if depth {
+            let renderbuffer = if let Some(renderbuffer) = gl.create_renderbuffer() {
+                renderbuffer
+            } else {
+                gl.bind_framebuffer(target, None);
+                let error = format!("Unable to create depth buffer for '{}'", self.name);
+                return Err(ErrorStr::new(error));
+            };
+            gl.bind_renderbuffer(WebGl2RenderingContext::RENDERBUFFER, Some(&renderbuffer));
+            gl.renderbuffer_storage(
+                WebGl2RenderingContext::RENDERBUFFER,
+                WebGl2RenderingContext::DEPTH_COMPONENT24,
+                self.width.max(1) as i32,
+                self.height.max(1) as i32,
+            );
+            gl.framebuffer_renderbuffer(
+                target,
+                WebGl2RenderingContext::DEPTH_ATTACHMENT,
+                WebGl2RenderingContext::RENDERBUFFER,
+                Some(&renderbuffer),
+            );
+            gl.bind_renderbuffer(WebGl2RenderingContext::RENDERBUFFER, None);
+            self.depth = Some(renderbuffer);
+        }
+        let status = gl.check_framebuffer_status(target);
+        gl.bind_framebuffer(target, None);
+        if status != WebGl2RenderingContext::FRAMEBUFFER_COMPLETE {
+            let error = format!("Framebuffer '{}' is incomplete: 0x{:x}", self.name, status);
+            return Err(ErrorStr::new(error));
+        }
+        Ok(())
+    }
+
+    // recreates the attachments, the old contents are lost
+    pub fn resize(
+        &mut self,
+        gl: &WebGl2RenderingContext,
+        width: u32,
+        height: u32,
+    ) -> Result<(), ErrorStr> {
+        if width == self.width && height == self.height {
+            return Ok(());
+        }
+        self.delete_attachments(gl);
+        self.width = width;
+        self.height = height;
+        let depth = self.depth.is_some();
+        self.depth = None;
+        self.attach(gl, depth)
+    }
+
+    // binds for drawing and sets the viewport to cover the whole target
+    pub fn bind(&self, gl: &WebGl2RenderingContext) {
+        gl.bind_framebuffer(WebGl2RenderingContext::FRAMEBUFFER, Some(&self.framebuffer));
+        record_state_change();
+        gl.viewport(0, 0, self.width as i32, self.height as i32);
+    }
+
+    pub fn unbind(&self, gl: &WebGl2RenderingContext) {
+        gl.bind_framebuffer(WebGl2RenderingContext::FRAMEBUFFER, None);
+    }
+
+    pub fn delete(&mut self, gl: &WebGl2RenderingContext) {
+        self.delete_attachments(gl);
+        gl.delete_framebuffer(Some(&self.framebuffer));
+    }
+
+    fn delete_attachments(&mut self, gl: &WebGl2RenderingContext) {
+        for texture in self.colour.drain(..) {
+            texture.delete(gl);
+        }
+        if let Some(depth) = self.depth.as_ref() {
+            gl.delete_renderbuffer(Some(depth));
+        }
+    }
+
+    #[inline]
+    pub fn colour(&self, index: usize) -> Option<&Texture> {
+        self.colour.get(index)
+    }
+
+    #[inline]
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    #[inline]
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
@@ -1,5 +1,6 @@
 pub mod buffer;
+pub mod framebuffer;
 pub mod program;
 pub mod shader;
+pub mod texture;
 pub mod vao;
-
@@ -1,13 +1,16 @@
-use leptos::logging;
-use web_sys::{WebGl2RenderingContext, WebGlProgram};
+use wasm_bindgen::JsValue;
+use web_sys::{WebGl2RenderingContext, WebGlProgram, WebGlUniformLocation};
 
 use crate::libs::{
     rendering::{
         canvas::WebGlCanvas,
-        gl::shader::{Shader, ShaderType},
+        gl::shader::{Shader, ShaderType, compile_shader_source},
     },
     types::errors::ErrorStr,
 };
+use crate::log_error;
+
+const LOG_TARGET: &str = "rendering::gl";
 
 struct Program<'a> {
     canvas: &'a WebGlCanvas<'a>,
@@ -28,7 +31,7 @@ impl<'a> Program<'a> {
         let program = match program {
             Ok(program) => program,
             Err(error) => {
-                logging::error!("{}", error);
+                log_error!(LOG_TARGET, "{}", error);
                 return Self {
                     canvas,
                     vertex_shader,
@@ -180,3 +183,105 @@ fn link_program<'a>(
         Err(ErrorStr::new(error))
     }
 }
+
+// compiles and links a program straight from shader sources. feedback_varyings must be set before
+// linking, so transform feedback programs have to go through here
+pub fn link_program_sources(
+    gl: &WebGl2RenderingContext,
+    vertex_source: &str,
+    fragment_source: &str,
+    feedback_varyings: &[&str],
+    name: &str,
+) -> Result<WebGlProgram, ErrorStr> {
+    let vertex_shader = compile_shader_source(
+        gl,
+        ShaderType::VertexShader,
+        vertex_source,
+        &format!("{} vertex", name),
+    )?;
+    let fragment_shader = match compile_shader_source(
+        gl,
+        ShaderType::FragmentShader,
+        fragment_source,
+        &format!("{} fragment", name),
+    ) {
+        Ok(shader) => shader,
+        Err(error) => {
+            gl.delete_shader(Some(&vertex_shader));
+            return Err(error);
+        }
+    };
+
+    let program = if let Some(program) = gl.create_program() {
+        program
+    } else {
+        gl.delete_shader(Some(&vertex_shader));
+        gl.delete_shader(Some(&fragment_shader));
+        let error = format!("Unable to create program '{}'", name);
+        return Err(ErrorStr::new(error));
+    };
+    gl.attach_shader(&program, &vertex_shader);
+    gl.attach_shader(&program, &fragment_shader);
+    if !feedback_varyings.is_empty() {
+        let varyings = js_sys::Array::new();
+        for varying in feedback_varyings {
+            varyings.push(&JsValue::from_str(varying));
+        }
+        gl.transform_feedback_varyings(
+            &program,
+            &varyings,
+            WebGl2RenderingContext::INTERLEAVED_ATTRIBS,
+        );
+    }
+    gl.link_program(&program);
+    // the program keeps the compiled code, the shader objects are no longer needed
+    gl.delete_shader(Some(&vertex_shader));
+    gl.delete_shader(Some(&fragment_shader));
+
+    let link_status = gl
+        .get_program_parameter(&program, WebGl2RenderingContext::LINK_STATUS)
+        .as_bool()
+        .unwrap_or(false);
+    if link_status {
+        Ok(program)
+    } else {
+        let linker_error = gl.get_program_info_log(&program).unwrap_or_default();
+        gl.delete_program(Some(&program));
+        let error = format!("Linker error for program '{}': {}", name, linker_error);
+        Err(ErrorStr::new(error))
+    }
+}
+
+pub fn uniform_location(
+    gl: &WebGl2RenderingContext,
+    program: &WebGlProgram,
+    uniform: &str,
+    name: &str,
+) -> Result<WebGlUniformLocation, ErrorStr> {
+    if let Some(location) = gl.get_uniform_location(program, uniform) {
+        Ok(location)
+    } else {
+        let error = format!(
+            "Uniform '{}' does not exist (or was optimised out) in program '{}'",
+            uniform, name
+        );
+        Err(ErrorStr::new(error))
+    }
+}
+
+pub fn attrib_location(
+    gl: &WebGl2RenderingContext,
+    program: &WebGlProgram,
+    attrib: &str,
+    name: &str,
+) -> Result<u32, ErrorStr> {
+    let location = gl.get_attrib_location(program, attrib);
+    if location < 0 {
+        let error = format!(
+            "Attribute '{}' does not exist (or was optimised out) in program '{}'",
+            attrib, name
+        );
+        return Err(ErrorStr::new(error));
+    }
+    Ok(location as u32)
+}
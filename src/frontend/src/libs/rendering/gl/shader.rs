@@ -1,7 +1,9 @@
-use leptos::logging;
 use web_sys::{WebGl2RenderingContext, WebGlProgram, WebGlShader};
 
 use crate::libs::{rendering::canvas::WebGlCanvas, types::errors::ErrorStr};
+use crate::log_error;
+
+const LOG_TARGET: &str = "rendering::gl";
 
 #[derive(Debug, Clone, Copy)]
 pub enum ShaderType {
@@ -29,7 +31,7 @@ impl<'a> Shader<'a> {
         let shader = match shader {
             Ok(shader) => shader,
             Err(error) => {
-                logging::error!("{}", error);
+                log_error!(LOG_TARGET, "{}", error);
                 return Self {
                     program_source,
                     name,
@@ -121,3 +123,38 @@ fn compile_shader<'a>(
     }
     Ok(shader)
 }
+
+// compiles a shader straight from a GL context, for renderers that own their GL objects and
+// outlive the borrow of the canvas passed into their tasks
+pub fn compile_shader_source(
+    gl: &WebGl2RenderingContext,
+    shader_type: ShaderType,
+    program_source: &str,
+    name: &str,
+) -> Result<WebGlShader, ErrorStr> {
+    let shader_type: u32 = {
+        match shader_type {
+            ShaderType::VertexShader => WebGl2RenderingContext::VERTEX_SHADER,
+            ShaderType::FragmentShader => WebGl2RenderingContext::FRAGMENT_SHADER,
+        }
+    };
+    let shader = if let Some(shader) = gl.create_shader(shader_type) {
+        shader
+    } else {
+        let error = format!("Unable to create shader object for shader '{}'", name);
+        return Err(ErrorStr::new(error));
+    };
+    gl.shader_source(&shader, program_source);
+    gl.compile_shader(&shader);
+    let compile_status = gl
+        .get_shader_parameter(&shader, WebGl2RenderingContext::COMPILE_STATUS)
+        .as_bool()
+        .unwrap_or(false);
+    if !compile_status {
+        let compile_error = gl.get_shader_info_log(&shader).unwrap_or_default();
+        gl.delete_shader(Some(&shader));
+        let error = format!("Compilation error for shader '{}': {}", name, compile_error);
+        return Err(ErrorStr::new(error));
+    }
+    Ok(shader)
+}
@@ -0,0 +1,220 @@
+use web_sys::{HtmlImageElement, WebGl2RenderingContext, WebGlTexture};
+
+use crate::libs::profiler::counters::record_state_change;
+use crate::libs::types::errors::ErrorStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureFilter {
+    // pixel art, tiles
+    Nearest,
+    Linear,
+}
+
+impl TextureFilter {
+    pub fn gl_enum(&self) -> i32 {
+        match self {
+            TextureFilter::Nearest => WebGl2RenderingContext::NEAREST as i32,
+            TextureFilter::Linear => WebGl2RenderingContext::LINEAR as i32,
+        }
+    }
+}
+
+// storage formats for textures that are rendered into
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureFormat {
+    Rgba8,
+    // needs EXT_color_buffer_float to be renderable
+    Rgba16F,
+    R32Ui,
+}
+
+impl TextureFormat {
+    pub fn internal_format(&self) -> u32 {
+        match self {
+            TextureFormat::Rgba8 => WebGl2RenderingContext::RGBA8,
+            TextureFormat::Rgba16F => WebGl2RenderingContext::RGBA16F,
+            TextureFormat::R32Ui => WebGl2RenderingContext::R32UI,
+        }
+    }
+
+    #[inline]
+    pub fn is_integer(&self) -> bool {
+        matches!(self, TextureFormat::R32Ui)
+    }
+}
+
+#[derive(Debug)]
+pub struct Texture {
+    texture: WebGlTexture,
+    width: u32,
+    height: u32,
+    name: String,
+}
+
+impl Texture {
+    pub fn from_image<S>(
+        gl: &WebGl2RenderingContext,
+        image: &HtmlImageElement,
+        filter: TextureFilter,
+        name: S,
+    ) -> Result<Self, ErrorStr>
+    where
+        S: Into<String>,
+    {
+        let name = name.into();
+        let texture = create_texture(gl, filter, &name)?;
+        if let Err(error) = gl.tex_image_2d_with_u32_and_u32_and_html_image_element(
+            WebGl2RenderingContext::TEXTURE_2D,
+            0,
+            WebGl2RenderingContext::RGBA as i32,
+            WebGl2RenderingContext::RGBA,
+            WebGl2RenderingContext::UNSIGNED_BYTE,
+            image,
+        ) {
+            gl.delete_texture(Some(&texture));
+            let error = format!("Unable to upload texture '{}': {:?}", name, error);
+            return Err(ErrorStr::new(error));
+        }
+        gl.bind_texture(WebGl2RenderingContext::TEXTURE_2D, None);
+        Ok(Self {
+            texture,
+            width: image.natural_width(),
+            height: image.natural_height(),
+            name,
+        })
+    }
+
+    // RGBA8 texture from raw pixels, e.g. generated atlases
+    pub fn from_rgba<S>(
+        gl: &WebGl2RenderingContext,
+        width: u32,
+        height: u32,
+        pixels: &[u8],
+        filter: TextureFilter,
+        name: S,
+    ) -> Result<Self, ErrorStr>
+    where
+        S: Into<String>,
+    {
+        let name = name.into();
+        let texture = create_texture(gl, filter, &name)?;
+        if let Err(error) = gl
+            .tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
+                WebGl2RenderingContext::TEXTURE_2D,
+                0,
+                WebGl2RenderingContext::RGBA as i32,
+                width as i32,
+                height as i32,
+                0,
+                WebGl2RenderingContext::RGBA,
+                WebGl2RenderingContext::UNSIGNED_BYTE,
+                Some(pixels),
+            )
+        {
+            gl.delete_texture(Some(&texture));
+            let error = format!("Unable to upload texture '{}': {:?}", name, error);
+            return Err(ErrorStr::new(error));
+        }
+        gl.bind_texture(WebGl2RenderingContext::TEXTURE_2D, None);
+        Ok(Self {
+            texture,
+            width,
+            height,
+            name,
+        })
+    }
+
+    // immutable storage with undefined contents, for render targets
+    pub fn allocate<S>(
+        gl: &WebGl2RenderingContext,
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+        filter: TextureFilter,
+        name: S,
+    ) -> Result<Self, ErrorStr>
+    where
+        S: Into<String>,
+    {
+        let name = name.into();
+        // integer textures can't be filtered
+        let filter = if format.is_integer() {
+            TextureFilter::Nearest
+        } else {
+            filter
+        };
+        let texture = create_texture(gl, filter, &name)?;
+        gl.tex_storage_2d(
+            WebGl2RenderingContext::TEXTURE_2D,
+            1,
+            format.internal_format(),
+            width.max(1) as i32,
+            height.max(1) as i32,
+        );
+        gl.bind_texture(WebGl2RenderingContext::TEXTURE_2D, None);
+        Ok(Self {
+            texture,
+            width,
+            height,
+            name,
+        })
+    }
+
+    pub fn bind(&self, gl: &WebGl2RenderingContext, unit: u32) {
+        gl.active_texture(WebGl2RenderingContext::TEXTURE0 + unit);
+        gl.bind_texture(WebGl2RenderingContext::TEXTURE_2D, Some(&self.texture));
+        record_state_change();
+    }
+
+    pub fn delete(&self, gl: &WebGl2RenderingContext) {
+        gl.delete_texture(Some(&self.texture));
+    }
+
+    #[inline]
+    pub fn texture(&self) -> &WebGlTexture {
+        &self.texture
+    }
+
+    #[inline]
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    #[inline]
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+fn create_texture(
+    gl: &WebGl2RenderingContext,
+    filter: TextureFilter,
+    name: &str,
+) -> Result<WebGlTexture, ErrorStr> {
+    let texture = if let Some(texture) = gl.create_texture() {
+        texture
+    } else {
+        let error = format!("Unable to create texture '{}'", name);
+        return Err(ErrorStr::new(error));
+    };
+    gl.bind_texture(WebGl2RenderingContext::TEXTURE_2D, Some(&texture));
+    let target = WebGl2RenderingContext::TEXTURE_2D;
+    gl.tex_parameteri(target, WebGl2RenderingContext::TEXTURE_MIN_FILTER, filter.gl_enum());
+    gl.tex_parameteri(target, WebGl2RenderingContext::TEXTURE_MAG_FILTER, filter.gl_enum());
+    gl.tex_parameteri(
+        target,
+        WebGl2RenderingContext::TEXTURE_WRAP_S,
+        WebGl2RenderingContext::CLAMP_TO_EDGE as i32,
+    );
+    gl.tex_parameteri(
+        target,
+        WebGl2RenderingContext::TEXTURE_WRAP_T,
+        WebGl2RenderingContext::CLAMP_TO_EDGE as i32,
+    );
+    Ok(texture)
+}
@@ -0,0 +1,110 @@
+use web_sys::{WebGl2RenderingContext, WebGlVertexArrayObject};
+
+use crate::libs::profiler::counters::record_state_change;
+use crate::libs::types::errors::ErrorStr;
+
+#[derive(Debug)]
+pub struct VertexArray {
+    vao: WebGlVertexArrayObject,
+    name: String,
+}
+
+impl VertexArray {
+    pub fn new<S>(gl: &WebGl2RenderingContext, name: S) -> Result<Self, ErrorStr>
+    where
+        S: Into<String>,
+    {
+        let name = name.into();
+        let vao = if let Some(vao) = gl.create_vertex_array() {
+            vao
+        } else {
+            let error = format!("Unable to create vertex array '{}'", name);
+            return Err(ErrorStr::new(error));
+        };
+        Ok(Self { vao, name })
+    }
+
+    pub fn bind(&self, gl: &WebGl2RenderingContext) {
+        gl.bind_vertex_array(Some(&self.vao));
+        record_state_change();
+    }
+
+    pub fn unbind(&self, gl: &WebGl2RenderingContext) {
+        gl.bind_vertex_array(None);
+    }
+
+    // describes a float attribute sourced from the currently bound ARRAY_BUFFER.
+    // the vertex array must be bound. divisor 0 is per vertex, 1 is per instance
+    pub fn attrib_f32(
+        &self,
+        gl: &WebGl2RenderingContext,
+        location: u32,
+        components: i32,
+        stride: i32,
+        offset: i32,
+        divisor: u32,
+    ) {
+        gl.enable_vertex_attrib_array(location);
+        gl.vertex_attrib_pointer_with_i32(
+            location,
+            components,
+            WebGl2RenderingContext::FLOAT,
+            false,
+            stride,
+            offset,
+        );
+        gl.vertex_attrib_divisor(location, divisor);
+    }
+
+    // same as attrib_f32, but for normalised unsigned bytes (packed colours)
+    pub fn attrib_u8_normalised(
+        &self,
+        gl: &WebGl2RenderingContext,
+        location: u32,
+        components: i32,
+        stride: i32,
+        offset: i32,
+        divisor: u32,
+    ) {
+        gl.enable_vertex_attrib_array(location);
+        gl.vertex_attrib_pointer_with_i32(
+            location,
+            components,
+            WebGl2RenderingContext::UNSIGNED_BYTE,
+            true,
+            stride,
+            offset,
+        );
+        gl.vertex_attrib_divisor(location, divisor);
+    }
+
+    // integer attribute (uint/ivec in the shader), no conversion to float
+    pub fn attrib_u32(
+        &self,
+        gl: &WebGl2RenderingContext,
+        location: u32,
+        components: i32,
+        stride: i32,
+        offset: i32,
+        divisor: u32,
+    ) {
+        gl.enable_vertex_attrib_array(location);
+        gl.vertex_attrib_i_pointer_with_i32(
+            location,
+            components,
+            WebGl2RenderingContext::UNSIGNED_INT,
+            stride,
+            offset,
+        );
+        gl.vertex_attrib_divisor(location, divisor);
+    }
+
+    pub fn delete(&self, gl: &WebGl2RenderingContext) {
+        gl.delete_vertex_array(Some(&self.vao));
+    }
+
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
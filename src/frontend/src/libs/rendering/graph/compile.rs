@@ -0,0 +1,199 @@
+use std::collections::BTreeSet;
+
+use crate::libs::rendering::graph::{PassNode, ResourceKind, ResourceNode, TargetDesc};
+use crate::libs::types::errors::ErrorStr;
+
+// result of ordering, culling and aliasing a graph. pure data, no gl objects
+#[derive(Debug, Clone, Default)]
+pub struct CompiledGraph {
+    // pass indices in execution order, culled passes excluded
+    pub order: Vec<usize>,
+    pub culled: Vec<bool>,
+    // physical slot per resource, None for the backbuffer and unused resources
+    pub slots: Vec<Option<usize>>,
+    pub slot_descs: Vec<TargetDesc>,
+}
+
+impl CompiledGraph {
+    #[inline]
+    pub fn is_culled(&self, pass: usize) -> bool {
+        self.culled.get(pass).copied().unwrap_or(true)
+    }
+}
+
+// passes each depend on: the writers of what they read, and the previous writer of what they
+// write. a read only waits for writers declared before the reader, unless there are none, which
+// keeps read-modify-write chains acyclic while still allowing passes to be declared in any order
+fn dependencies(passes: &[PassNode]) -> Vec<BTreeSet<usize>> {
+    let mut before = vec![BTreeSet::new(); passes.len()];
+    for (index, pass) in passes.iter().enumerate() {
+        for resource in pass.reads.iter() {
+            let writers: Vec<usize> = passes
+                .iter()
+                .enumerate()
+                .filter(|(other, node)| *other != index && node.writes.contains(resource))
+                .map(|(other, _)| other)
+                .collect();
+            let earlier: Vec<usize> = writers.iter().copied().filter(|w| *w < index).collect();
+            let chosen = if earlier.is_empty() { writers } else { earlier };
+            before[index].extend(chosen);
+        }
+        for resource in pass.writes.iter() {
+            let previous = passes[..index]
+                .iter()
+                .rposition(|node| node.writes.contains(resource));
+            if let Some(previous) = previous {
+                before[index].insert(previous);
+            }
+        }
+    }
+    before
+}
+
+pub fn compile(
+    resources: &[ResourceNode],
+    passes: &[PassNode],
+) -> Result<CompiledGraph, ErrorStr> {
+    for pass in passes.iter() {
+        for resource in pass.reads.iter().chain(pass.writes.iter()) {
+            if resource.0 >= resources.len() {
+                let error = format!("Pass '{}' uses an unknown resource", pass.name);
+                return Err(ErrorStr::new(error));
+            }
+        }
+        for resource in pass.reads.iter() {
+            let node = &resources[resource.0];
+            let written = passes.iter().any(|other| other.writes.contains(resource));
+            if matches!(node.kind, ResourceKind::Transient(_)) && !written {
+                let error = format!(
+                    "Pass '{}' reads '{}', which no pass writes",
+                    pass.name, node.name
+                );
+                return Err(ErrorStr::new(error));
+            }
+        }
+    }
+    let before = dependencies(passes);
+
+    // anything that ends up on screen, in a persistent target, or is marked as having side
+    // effects is a root. everything else only survives if a root needs it
+    let mut alive = vec![false; passes.len()];
+    let mut stack: Vec<usize> = passes
+        .iter()
+        .enumerate()
+        .filter(|(_, pass)| {
+            pass.side_effects
+                || pass.writes.iter().any(|resource| {
+                    matches!(
+                        resources[resource.0].kind,
+                        ResourceKind::Backbuffer | ResourceKind::Persistent(_)
+                    )
+                })
+        })
+        .map(|(index, _)| index)
+        .collect();
+    while let Some(pass) = stack.pop() {
+        if alive[pass] {
+            continue;
+        }
+        alive[pass] = true;
+        stack.extend(before[pass].iter().copied());
+    }
+
+    // kahn's algorithm, ties broken by declaration order so the result is stable
+    let mut remaining: Vec<usize> = before
+        .iter()
+        .enumerate()
+        .map(|(pass, deps)| deps.iter().filter(|dep| alive[**dep]).count())
+        .collect();
+    let mut ready: BTreeSet<usize> = (0..passes.len())
+        .filter(|pass| alive[*pass] && remaining[*pass] == 0)
+        .collect();
+    let mut order = Vec::new();
+    while let Some(pass) = ready.pop_first() {
+        order.push(pass);
+        for (next, deps) in before.iter().enumerate() {
+            if alive[next] && deps.contains(&pass) {
+                remaining[next] -= 1;
+                if remaining[next] == 0 {
+                    ready.insert(next);
+                }
+            }
+        }
+    }
+    let alive_count = alive.iter().filter(|alive| **alive).count();
+    if order.len() != alive_count {
+        let stuck: Vec<&str> = (0..passes.len())
+            .filter(|pass| alive[*pass] && !order.contains(pass))
+            .map(|pass| passes[pass].name.as_str())
+            .collect();
+        let error = format!("Render graph has a cycle between: {}", stuck.join(", "));
+        return Err(ErrorStr::new(error));
+    }
+
+    let (slots, slot_descs) = alias(resources, passes, &order);
+    Ok(CompiledGraph {
+        order,
+        culled: alive.iter().map(|alive| !alive).collect(),
+        slots,
+        slot_descs,
+    })
+}
+
+// transient targets with the same description whose lifetimes don't overlap share a slot.
+// persistent targets always get their own
+fn alias(
+    resources: &[ResourceNode],
+    passes: &[PassNode],
+    order: &[usize],
+) -> (Vec<Option<usize>>, Vec<TargetDesc>) {
+    let mut lifetimes: Vec<Option<(usize, usize)>> = vec![None; resources.len()];
+    for (step, pass) in order.iter().enumerate() {
+        let node = &passes[*pass];
+        for resource in node.reads.iter().chain(node.writes.iter()) {
+            let lifetime = &mut lifetimes[resource.0];
+            *lifetime = Some(match lifetime {
+                Some((first, _)) => (*first, step),
+                None => (step, step),
+            });
+        }
+    }
+    let mut slots = vec![None; resources.len()];
+    let mut slot_descs: Vec<TargetDesc> = Vec::new();
+    // last step each slot is used in
+    let mut slot_last: Vec<usize> = Vec::new();
+
+    let mut transients: Vec<(usize, TargetDesc, usize, usize)> = resources
+        .iter()
+        .enumerate()
+        .filter_map(|(index, resource)| match (&resource.kind, lifetimes[index]) {
+            (ResourceKind::Transient(desc), Some((first, last))) => {
+                Some((index, *desc, first, last))
+            }
+            _ => None,
+        })
+        .collect();
+    transients.sort_by_key(|(index, _, first, _)| (*first, *index));
+    for (index, desc, first, last) in transients {
+        let reusable = (0..slot_descs.len()).find(|slot| {
+            slot_descs[*slot] == desc && slot_last[*slot] < first
+        });
+        let slot = match reusable {
+            Some(slot) => slot,
+            None => {
+                slot_descs.push(desc);
+                slot_last.push(last);
+                slot_descs.len() - 1
+            }
+        };
+        slot_last[slot] = last;
+        slots[index] = Some(slot);
+    }
+    for (index, resource) in resources.iter().enumerate() {
+        if let ResourceKind::Persistent(desc) = resource.kind {
+            slot_descs.push(desc);
+            slots[index] = Some(slot_descs.len() - 1);
+        }
+    }
+    (slots, slot_descs)
+}
@@ -0,0 +1,75 @@
+use std::fmt::Write;
+
+use crate::libs::rendering::graph::compile::CompiledGraph;
+use crate::libs::rendering::graph::{PassNode, ResourceKind, ResourceNode, TargetSize};
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn describe(kind: &ResourceKind) -> String {
+    let desc = match kind {
+        ResourceKind::Backbuffer => return "canvas".to_string(),
+        ResourceKind::Transient(desc) | ResourceKind::Persistent(desc) => desc,
+    };
+    let size = match desc.size {
+        TargetSize::Viewport { scale } => format!("{}x viewport", scale),
+        TargetSize::Fixed { width, height } => format!("{}x{}", width, height),
+    };
+    let depth = if desc.depth { " + depth" } else { "" };
+    format!("{} {:?}{}", size, desc.format, depth)
+}
+
+// passes are boxes numbered by execution order, resources are ellipses labelled with their
+// physical slot. culled passes and unused resources are greyed out
+pub fn to_dot(
+    resources: &[ResourceNode],
+    passes: &[PassNode],
+    compiled: Option<&CompiledGraph>,
+) -> String {
+    let mut dot = String::from("digraph render_graph {\n    rankdir=LR;\n");
+    for (index, resource) in resources.iter().enumerate() {
+        let slot = compiled.and_then(|compiled| compiled.slots.get(index).copied().flatten());
+        let slot = match (resource.kind, slot) {
+            (ResourceKind::Backbuffer, _) => String::new(),
+            (_, Some(slot)) => format!("\\nslot {}", slot),
+            (_, None) => "\\nunused".to_string(),
+        };
+        let style = match resource.kind {
+            ResourceKind::Backbuffer => "style=bold",
+            ResourceKind::Persistent(_) => "style=dashed",
+            ResourceKind::Transient(_) => "style=solid",
+        };
+        let _ = writeln!(
+            dot,
+            "    r{} [shape=ellipse, {}, label=\"{}\\n{}{}\"];",
+            index,
+            style,
+            escape(&resource.name),
+            describe(&resource.kind),
+            slot
+        );
+    }
+    for (index, pass) in passes.iter().enumerate() {
+        let culled = compiled.is_some_and(|compiled| compiled.is_culled(index));
+        let step = compiled.and_then(|compiled| compiled.order.iter().position(|p| *p == index));
+        let label = match step {
+            Some(step) => format!("{}. {}", step + 1, escape(&pass.name)),
+            None => escape(&pass.name),
+        };
+        let style = if culled {
+            ", style=dashed, color=grey, fontcolor=grey"
+        } else {
+            ""
+        };
+        let _ = writeln!(dot, "    p{} [shape=box{}, label=\"{}\"];", index, style, label);
+        for resource in pass.reads.iter() {
+            let _ = writeln!(dot, "    r{} -> p{};", resource.0, index);
+        }
+        for resource in pass.writes.iter() {
+            let _ = writeln!(dot, "    p{} -> r{};", index, resource.0);
+        }
+    }
+    dot.push_str("}\n");
+    dot
+}
@@ -0,0 +1,414 @@
+pub mod compile;
+pub mod dot;
+
+use glam::UVec2;
+use web_sys::WebGl2RenderingContext;
+
+use crate::libs::rendering::canvas::{RafTask, RafTime, WebGlCanvas};
+use crate::libs::rendering::gl::framebuffer::Framebuffer;
+use crate::libs::rendering::gl::texture::{Texture, TextureFormat};
+use crate::libs::rendering::graph::compile::{CompiledGraph, compile};
+use crate::libs::types::errors::ErrorStr;
+
+pub trait PassFnTrait = FnMut(&mut PassContext) -> Result<(), ErrorStr> + 'static;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ResourceId(usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PassId(usize);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TargetSize {
+    // a fraction of the drawing buffer, 0.5 for half resolution bloom etc.
+    Viewport { scale: f32 },
+    Fixed { width: u32, height: u32 },
+}
+
+impl TargetSize {
+    pub fn resolve(&self, viewport: UVec2) -> UVec2 {
+        match self {
+            TargetSize::Viewport { scale } => (viewport.as_vec2() * *scale)
+                .round()
+                .as_uvec2()
+                .max(UVec2::ONE),
+            TargetSize::Fixed { width, height } => UVec2::new(*width, *height).max(UVec2::ONE),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TargetDesc {
+    pub size: TargetSize,
+    pub format: TextureFormat,
+    pub depth: bool,
+}
+
+impl TargetDesc {
+    pub fn viewport(format: TextureFormat) -> Self {
+        Self::scaled(1.0, format)
+    }
+
+    pub fn scaled(scale: f32, format: TextureFormat) -> Self {
+        Self {
+            size: TargetSize::Viewport { scale },
+            format,
+            depth: false,
+        }
+    }
+
+    pub fn fixed(width: u32, height: u32, format: TextureFormat) -> Self {
+        Self {
+            size: TargetSize::Fixed { width, height },
+            format,
+            depth: false,
+        }
+    }
+
+    pub fn with_depth(mut self) -> Self {
+        self.depth = true;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResourceKind {
+    // the canvas' default framebuffer
+    Backbuffer,
+    // only lives for the frame, can share memory with other transients
+    Transient(TargetDesc),
+    // keeps its contents between frames (history buffers), never aliased
+    Persistent(TargetDesc),
+}
+
+#[derive(Debug, Clone)]
+pub struct ResourceNode {
+    pub name: String,
+    pub kind: ResourceKind,
+}
+
+pub struct PassNode {
+    pub name: String,
+    pub reads: Vec<ResourceId>,
+    pub writes: Vec<ResourceId>,
+    // kept even when nothing reads its outputs, e.g. readbacks
+    pub side_effects: bool,
+    execute: Box<dyn PassFnTrait>,
+}
+
+impl std::fmt::Debug for PassNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("PassNode")
+            .field("name", &self.name)
+            .field("reads", &self.reads)
+            .field("writes", &self.writes)
+            .field("side_effects", &self.side_effects)
+            .field("execute", &"<FnMut Closure>")
+            .finish()
+    }
+}
+
+// what a pass sees while it runs. its first written target is already bound
+pub struct PassContext<'g> {
+    gl: &'g WebGl2RenderingContext,
+    viewport: UVec2,
+    pass: &'g str,
+    reads: &'g [ResourceId],
+    writes: &'g [ResourceId],
+    resources: &'g [ResourceNode],
+    slots: &'g [Option<usize>],
+    targets: &'g [Option<(TargetDesc, Framebuffer)>],
+}
+
+impl<'g> PassContext<'g> {
+    #[inline]
+    pub fn gl(&self) -> &WebGl2RenderingContext {
+        self.gl
+    }
+
+    // drawing buffer size
+    #[inline]
+    pub fn viewport(&self) -> UVec2 {
+        self.viewport
+    }
+
+    fn framebuffer(&self, resource: ResourceId) -> Option<&'g Framebuffer> {
+        let slot = (*self.slots.get(resource.0)?)?;
+        self.targets
+            .get(slot)
+            .and_then(|target| target.as_ref())
+            .map(|(_, framebuffer)| framebuffer)
+    }
+
+    // colour texture of a target this pass declared as a read
+    pub fn texture(&self, resource: ResourceId) -> Result<&'g Texture, ErrorStr> {
+        if !self.reads.contains(&resource) {
+            let error = format!(
+                "Pass '{}' samples '{}' without declaring the read",
+                self.pass, self.resources[resource.0].name
+            );
+            return Err(ErrorStr::new(error));
+        }
+        match self.framebuffer(resource).and_then(|target| target.colour(0)) {
+            Some(texture) => Ok(texture),
+            None => {
+                let error = format!(
+                    "'{}' has no texture in pass '{}'",
+                    self.resources[resource.0].name, self.pass
+                );
+                Err(ErrorStr::new(error))
+            }
+        }
+    }
+
+    // switches to another declared output, for passes that write several targets
+    pub fn bind_target(&self, resource: ResourceId) -> Result<(), ErrorStr> {
+        if !self.writes.contains(&resource) {
+            let error = format!(
+                "Pass '{}' binds '{}' without declaring the write",
+                self.pass, self.resources[resource.0].name
+            );
+            return Err(ErrorStr::new(error));
+        }
+        bind_resource(
+            self.gl,
+            self.viewport,
+            &self.resources[resource.0],
+            self.framebuffer(resource),
+        )
+    }
+}
+
+fn bind_resource(
+    gl: &WebGl2RenderingContext,
+    viewport: UVec2,
+    resource: &ResourceNode,
+    framebuffer: Option<&Framebuffer>,
+) -> Result<(), ErrorStr> {
+    match (resource.kind, framebuffer) {
+        (ResourceKind::Backbuffer, _) => {
+            gl.bind_framebuffer(WebGl2RenderingContext::FRAMEBUFFER, None);
+            gl.viewport(0, 0, viewport.x as i32, viewport.y as i32);
+            Ok(())
+        }
+        (_, Some(framebuffer)) => {
+            framebuffer.bind(gl);
+            Ok(())
+        }
+        (_, None) => {
+            let error = format!("'{}' has not been allocated", resource.name);
+            Err(ErrorStr::new(error))
+        }
+    }
+}
+
+// passes declare the targets they read and write. the graph orders them, culls passes whose
+// outputs nobody uses, and backs transient targets with as few framebuffers as it can
+#[derive(Debug)]
+pub struct RenderGraph {
+    resources: Vec<ResourceNode>,
+    passes: Vec<PassNode>,
+    compiled: Option<CompiledGraph>,
+    targets: Vec<Option<(TargetDesc, Framebuffer)>>,
+    viewport: UVec2,
+}
+
+impl Default for RenderGraph {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RenderGraph {
+    pub fn new() -> Self {
+        Self {
+            resources: vec![ResourceNode {
+                name: "backbuffer".to_string(),
+                kind: ResourceKind::Backbuffer,
+            }],
+            passes: Vec::new(),
+            compiled: None,
+            targets: Vec::new(),
+            viewport: UVec2::ZERO,
+        }
+    }
+
+    #[inline]
+    pub fn backbuffer(&self) -> ResourceId {
+        ResourceId(0)
+    }
+
+    pub fn create_target<S>(&mut self, name: S, desc: TargetDesc) -> ResourceId
+    where
+        S: Into<String>,
+    {
+        self.add_resource(name.into(), ResourceKind::Transient(desc))
+    }
+
+    pub fn create_persistent_target<S>(&mut self, name: S, desc: TargetDesc) -> ResourceId
+    where
+        S: Into<String>,
+    {
+        self.add_resource(name.into(), ResourceKind::Persistent(desc))
+    }
+
+    fn add_resource(&mut self, name: String, kind: ResourceKind) -> ResourceId {
+        self.resources.push(ResourceNode { name, kind });
+        self.compiled = None;
+        ResourceId(self.resources.len() - 1)
+    }
+
+    pub fn add_pass<S, F>(
+        &mut self,
+        name: S,
+        reads: &[ResourceId],
+        writes: &[ResourceId],
+        execute: F,
+    ) -> PassId
+    where
+        S: Into<String>,
+        F: PassFnTrait,
+    {
+        self.passes.push(PassNode {
+            name: name.into(),
+            reads: reads.to_vec(),
+            writes: writes.to_vec(),
+            side_effects: false,
+            execute: Box::new(execute),
+        });
+        self.compiled = None;
+        PassId(self.passes.len() - 1)
+    }
+
+    // never cull this pass
+    pub fn keep_pass(&mut self, pass: PassId) {
+        if let Some(node) = self.passes.get_mut(pass.0) {
+            node.side_effects = true;
+            self.compiled = None;
+        }
+    }
+
+    // orders, culls and aliases. called by execute when the graph changed, call it directly to
+    // surface errors early
+    pub fn compile(&mut self) -> Result<&CompiledGraph, ErrorStr> {
+        if self.compiled.is_none() {
+            self.compiled = Some(compile(&self.resources, &self.passes)?);
+        }
+        match self.compiled.as_ref() {
+            Some(compiled) => Ok(compiled),
+            None => Err(ErrorStr::new("Render graph failed to compile")),
+        }
+    }
+
+    pub fn execute(&mut self, gl: &WebGl2RenderingContext) -> Result<(), ErrorStr> {
+        self.compile()?;
+        let viewport = UVec2::new(
+            gl.drawing_buffer_width().max(1) as u32,
+            gl.drawing_buffer_height().max(1) as u32,
+        );
+        self.allocate(gl, viewport)?;
+        let compiled = if let Some(compiled) = self.compiled.as_ref() {
+            compiled
+        } else {
+            return Err(ErrorStr::new("Render graph failed to compile"));
+        };
+        let mut result = Ok(());
+        for pass_index in compiled.order.iter() {
+            let pass = &mut self.passes[*pass_index];
+            let context_slots = &compiled.slots;
+            if let Some(first) = pass.writes.first() {
+                let framebuffer = context_slots[first.0]
+                    .and_then(|slot| self.targets.get(slot))
+                    .and_then(|target| target.as_ref())
+                    .map(|(_, framebuffer)| framebuffer);
+                bind_resource(gl, viewport, &self.resources[first.0], framebuffer)?;
+            }
+            let mut context = PassContext {
+                gl,
+                viewport,
+                pass: &pass.name,
+                reads: &pass.reads,
+                writes: &pass.writes,
+                resources: &self.resources,
+                slots: context_slots,
+                targets: &self.targets,
+            };
+            if let Err(error) = (pass.execute)(&mut context) {
+                let error = format!("Render pass '{}' failed: {}", pass.name, error);
+                result = Err(ErrorStr::new(error));
+                break;
+            }
+        }
+        gl.bind_framebuffer(WebGl2RenderingContext::FRAMEBUFFER, None);
+        gl.viewport(0, 0, viewport.x as i32, viewport.y as i32);
+        result
+    }
+
+    // creates, resizes or replaces framebuffers to match the compiled slots
+    fn allocate(&mut self, gl: &WebGl2RenderingContext, viewport: UVec2) -> Result<(), ErrorStr> {
+        let slot_descs = match self.compiled.as_ref() {
+            Some(compiled) => compiled.slot_descs.clone(),
+            None => return Ok(()),
+        };
+        while self.targets.len() > slot_descs.len() {
+            if let Some(Some((_, mut framebuffer))) = self.targets.pop() {
+                framebuffer.delete(gl);
+            }
+        }
+        self.targets.resize_with(slot_descs.len(), || None);
+        for (slot, desc) in slot_descs.iter().enumerate() {
+            let size = desc.size.resolve(viewport);
+            let target = &mut self.targets[slot];
+            let matches = target.as_ref().is_some_and(|(existing, _)| {
+                existing.format == desc.format && existing.depth == desc.depth
+            });
+            if matches {
+                if let Some((existing, framebuffer)) = target.as_mut() {
+                    framebuffer.resize(gl, size.x, size.y)?;
+                    *existing = *desc;
+                }
+                continue;
+            }
+            if let Some((_, mut framebuffer)) = target.take() {
+                framebuffer.delete(gl);
+            }
+            let framebuffer = Framebuffer::new(
+                gl,
+                size.x,
+                size.y,
+                &[desc.format],
+                desc.depth,
+                format!("Render graph slot {}", slot),
+            )?;
+            *target = Some((*desc, framebuffer));
+        }
+        self.viewport = viewport;
+        Ok(())
+    }
+
+    // graphviz text of the passes, resources and what got culled or aliased
+    pub fn dump_dot(&mut self) -> String {
+        let compiled = self.compile().ok().cloned();
+        dot::to_dot(&self.resources, &self.passes, compiled.as_ref())
+    }
+
+    pub fn delete(&mut self, gl: &WebGl2RenderingContext) {
+        for (_, mut framebuffer) in self.targets.drain(..).flatten() {
+            framebuffer.delete(gl);
+        }
+    }
+
+    // runs the whole graph as one raf task, in place of hand ordered tasks
+    pub fn into_raf_task<'a>(mut self, name: &'a str) -> RafTask<'a> {
+        RafTask::new(
+            move |canvas: &WebGlCanvas, _time: RafTime| {
+                let context = canvas.get_context();
+                match context.as_ref() {
+                    Some(gl) => self.execute(gl),
+                    None => Err(ErrorStr::new("Render graph has no GL context")),
+                }
+            },
+            name,
+        )
+    }
+}
@@ -1,3 +1,12 @@
+pub mod assets;
+pub mod camera;
 pub mod canvas;
+pub mod colour;
+pub mod debug_draw;
 pub mod gl;
+pub mod graph;
+pub mod particles;
+pub mod picking;
 pub mod shaders;
+pub mod shapes;
+pub mod tilemap;
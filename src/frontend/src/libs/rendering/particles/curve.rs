@@ -0,0 +1,95 @@
+use serde::{Deserialize, Serialize};
+
+// number of samples a curve is baked to before being uploaded as a uniform array.
+// must match CURVE_SAMPLES in the particle shaders
+pub const CURVE_SAMPLES: usize = 16;
+
+pub trait Lerp: Copy {
+    fn lerp(self, other: Self, t: f32) -> Self;
+}
+
+impl Lerp for f32 {
+    fn lerp(self, other: Self, t: f32) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl<const N: usize> Lerp for [f32; N] {
+    fn lerp(self, other: Self, t: f32) -> Self {
+        let mut out = self;
+        for (value, other) in out.iter_mut().zip(other) {
+            *value = value.lerp(other, t);
+        }
+        out
+    }
+}
+
+// piecewise linear curve over normalised particle age (0 = spawned, 1 = dead).
+// serialised as a list of [t, value] keys, sorted by t on load
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Curve<T> {
+    keys: Vec<(f32, T)>,
+}
+
+impl<T: Lerp> Curve<T> {
+    pub fn new(mut keys: Vec<(f32, T)>) -> Self {
+        keys.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Self { keys }
+    }
+
+    pub fn constant(value: T) -> Self {
+        Self {
+            keys: vec![(0.0, value)],
+        }
+    }
+
+    // keys read from a file are not guaranteed to be sorted
+    pub fn sort(&mut self) {
+        self.keys.sort_by(|a, b| a.0.total_cmp(&b.0));
+    }
+
+    pub fn sample(&self, t: f32) -> Option<T> {
+        let first = self.keys.first()?;
+        let last = self.keys.last()?;
+        if t <= first.0 {
+            return Some(first.1);
+        }
+        if t >= last.0 {
+            return Some(last.1);
+        }
+        for window in self.keys.windows(2) {
+            let (t0, v0) = window[0];
+            let (t1, v1) = window[1];
+            if t <= t1 {
+                let span = t1 - t0;
+                let k = if span > 0.0 { (t - t0) / span } else { 1.0 };
+                return Some(v0.lerp(v1, k));
+            }
+        }
+        Some(last.1)
+    }
+
+    // evenly spaced samples over [0, 1], for uploading to the GPU
+    pub fn bake(&self, fallback: T) -> [T; CURVE_SAMPLES] {
+        let mut samples = [fallback; CURVE_SAMPLES];
+        for (i, sample) in samples.iter_mut().enumerate() {
+            let t = i as f32 / (CURVE_SAMPLES - 1) as f32;
+            *sample = self.sample(t).unwrap_or(fallback);
+        }
+        samples
+    }
+
+    #[inline]
+    pub fn keys(&self) -> &[(f32, T)] {
+        &self.keys
+    }
+}
+
+// samples a baked curve the same way the shaders do
+pub fn sample_baked<T: Lerp>(baked: &[T; CURVE_SAMPLES], t: f32) -> T {
+    let f = t.clamp(0.0, 1.0) * (CURVE_SAMPLES - 1) as f32;
+    let i = f.floor() as usize;
+    let j = (i + 1).min(CURVE_SAMPLES - 1);
+    baked[i].lerp(baked[j], f - i as f32)
+}
@@ -0,0 +1,142 @@
+use serde::{Deserialize, Serialize};
+
+use crate::libs::rendering::assets::fetch_text;
+use crate::libs::rendering::particles::curve::Curve;
+use crate::libs::types::errors::ErrorStr;
+
+// a named set of emitters, loaded from JSON so effects can be tweaked without a rebuild.
+// see effects/*.json for examples
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ParticleEffect {
+    pub name: String,
+    pub emitters: Vec<EmitterDesc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EmitterShape {
+    Point,
+    Circle { radius: f32 },
+    Box { half_extents: [f32; 3] },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EmitterDesc {
+    pub name: String,
+    // upper bound on live particles, this is the size of the particle buffers
+    pub max_particles: u32,
+    // particles per second
+    pub spawn_rate: f32,
+    // particles spawned at once when the emitter starts
+    pub burst: u32,
+    // seconds the emitter spawns for, None loops forever
+    pub duration: Option<f32>,
+    // [min, max] seconds
+    pub lifetime: [f32; 2],
+    pub shape: EmitterShape,
+    // [min, max] units per second
+    pub speed: [f32; 2],
+    pub direction: [f32; 3],
+    // half angle of the spawn cone in radians, pi spawns in every direction
+    pub spread: f32,
+    // keeps particles on the z = 0 plane, for the 2d views
+    pub planar: bool,
+    pub gravity: [f32; 3],
+    // fraction of velocity lost per second
+    pub drag: f32,
+    pub additive: bool,
+    pub colour_over_life: Curve<[f32; 4]>,
+    pub size_over_life: Curve<f32>,
+    // multiplier on velocity over the particle's life
+    pub speed_over_life: Curve<f32>,
+}
+
+impl Default for EmitterDesc {
+    fn default() -> Self {
+        Self {
+            name: String::from("emitter"),
+            max_particles: 256,
+            spawn_rate: 32.0,
+            burst: 0,
+            duration: None,
+            lifetime: [1.0, 1.0],
+            shape: EmitterShape::Point,
+            speed: [50.0, 50.0],
+            direction: [0.0, 1.0, 0.0],
+            spread: 0.3,
+            planar: true,
+            gravity: [0.0, 0.0, 0.0],
+            drag: 0.0,
+            additive: false,
+            colour_over_life: Curve::constant([1.0, 1.0, 1.0, 1.0]),
+            size_over_life: Curve::constant(4.0),
+            speed_over_life: Curve::constant(1.0),
+        }
+    }
+}
+
+impl EmitterDesc {
+    // longest a single particle can live, used to decide when a finished emitter can be dropped
+    pub fn max_lifetime(&self) -> f32 {
+        self.lifetime[0].max(self.lifetime[1])
+    }
+
+    fn validate(&mut self, effect: &str) -> Result<(), ErrorStr> {
+        if self.max_particles == 0 {
+            let error = format!(
+                "Emitter '{}' in effect '{}' has max_particles = 0",
+                self.name, effect
+            );
+            return Err(ErrorStr::new(error));
+        }
+        if self.lifetime[0] <= 0.0 || self.lifetime[1] <= 0.0 {
+            let error = format!(
+                "Emitter '{}' in effect '{}' has a non-positive lifetime",
+                self.name, effect
+            );
+            return Err(ErrorStr::new(error));
+        }
+        if self.lifetime[0] > self.lifetime[1] {
+            self.lifetime.swap(0, 1);
+        }
+        if self.speed[0] > self.speed[1] {
+            self.speed.swap(0, 1);
+        }
+        self.colour_over_life.sort();
+        self.size_over_life.sort();
+        self.speed_over_life.sort();
+        Ok(())
+    }
+}
+
+impl ParticleEffect {
+    pub fn from_json(json: &str) -> Result<Self, ErrorStr> {
+        let mut effect: ParticleEffect = match serde_json::from_str(json) {
+            Ok(effect) => effect,
+            Err(error) => {
+                let error = format!("Unable to parse particle effect: {}", error);
+                return Err(ErrorStr::new(error));
+            }
+        };
+        for emitter in effect.emitters.iter_mut() {
+            emitter.validate(&effect.name)?;
+        }
+        Ok(effect)
+    }
+
+    pub fn to_json(&self) -> Result<String, ErrorStr> {
+        serde_json::to_string_pretty(self).map_err(|error| {
+            ErrorStr::new(format!(
+                "Unable to serialise particle effect '{}': {}",
+                self.name, error
+            ))
+        })
+    }
+}
+
+// fetches and parses an effect, e.g. load_effect("effects/explosion.json")
+pub async fn load_effect(url: &str) -> Result<ParticleEffect, ErrorStr> {
+    let json = fetch_text(url).await?;
+    ParticleEffect::from_json(&json)
+}
@@ -0,0 +1,507 @@
+use glam::Vec3;
+use web_sys::{WebGl2RenderingContext, WebGlTransformFeedback};
+
+use crate::libs::profiler::counters::record_draw;
+use crate::libs::rendering::gl::buffer::{Buffer, BufferTarget, BufferUsage};
+use crate::libs::rendering::gl::vao::VertexArray;
+use crate::libs::rendering::particles::curve::{CURVE_SAMPLES, sample_baked};
+use crate::libs::rendering::particles::effect::{EmitterDesc, EmitterShape};
+use crate::libs::rendering::particles::{ParticleBackend, RenderProgram, UpdateProgram};
+use crate::libs::types::errors::ErrorStr;
+use crate::libs::types::rng::Rng;
+
+// interleaved particle layout, shared by the transform feedback buffers and the CPU instance
+// buffer: position (3), velocity (3), age, lifetime, seed
+pub const PARTICLE_FLOATS: usize = 9;
+pub const PARTICLE_STRIDE: i32 = (PARTICLE_FLOATS * 4) as i32;
+const POSITION_OFFSET: i32 = 0;
+const VELOCITY_OFFSET: i32 = 12;
+const AGE_OFFSET: i32 = 24;
+const LIFETIME_OFFSET: i32 = 28;
+const SEED_OFFSET: i32 = 32;
+// spreads this wide spawn in every direction, same cutoff as the update shader
+const FULL_SPREAD: f32 = std::f32::consts::PI * 0.999;
+
+// curves baked into the arrays uploaded as uniforms
+#[derive(Debug, Clone)]
+pub struct BakedCurves {
+    pub colour: [[f32; 4]; CURVE_SAMPLES],
+    pub size: [f32; CURVE_SAMPLES],
+    pub speed: [f32; CURVE_SAMPLES],
+}
+
+impl BakedCurves {
+    pub fn new(desc: &EmitterDesc) -> Self {
+        Self {
+            colour: desc.colour_over_life.bake([1.0, 1.0, 1.0, 1.0]),
+            size: desc.size_over_life.bake(1.0),
+            speed: desc.speed_over_life.bake(1.0),
+        }
+    }
+
+    pub fn colour_flat(&self) -> [f32; CURVE_SAMPLES * 4] {
+        let mut flat = [0.0; CURVE_SAMPLES * 4];
+        for (i, colour) in self.colour.iter().enumerate() {
+            flat[i * 4..i * 4 + 4].copy_from_slice(colour);
+        }
+        flat
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Particle {
+    position: Vec3,
+    velocity: Vec3,
+    age: f32,
+    lifetime: f32,
+    seed: f32,
+}
+
+impl Particle {
+    fn dead(seed: f32) -> Self {
+        Self {
+            age: 1.0,
+            lifetime: 0.0,
+            seed,
+            ..Default::default()
+        }
+    }
+
+    #[inline]
+    fn is_dead(&self) -> bool {
+        self.age >= self.lifetime
+    }
+
+    fn write(&self, out: &mut [f32]) {
+        out[0..3].copy_from_slice(&self.position.to_array());
+        out[3..6].copy_from_slice(&self.velocity.to_array());
+        out[6] = self.age;
+        out[7] = self.lifetime;
+        out[8] = self.seed;
+    }
+}
+
+// particles updated by the vertex shader into a second buffer with transform feedback,
+// then the buffers swap
+#[derive(Debug)]
+struct GpuParticles {
+    buffers: [Buffer; 2],
+    update_vaos: [VertexArray; 2],
+    render_vaos: [VertexArray; 2],
+    feedback: WebGlTransformFeedback,
+    current: usize,
+}
+
+// particles simulated in rust and uploaded each frame as instance data
+#[derive(Debug)]
+struct CpuParticles {
+    particles: Vec<Particle>,
+    rng: Rng,
+    instances: Buffer,
+    render_vao: VertexArray,
+    scratch: Vec<f32>,
+}
+
+#[derive(Debug)]
+enum EmitterParticles {
+    Gpu(GpuParticles),
+    Cpu(CpuParticles),
+}
+
+#[derive(Debug)]
+pub struct Emitter {
+    desc: EmitterDesc,
+    baked: BakedCurves,
+    pub origin: Vec3,
+    elapsed: f32,
+    spawn_accumulator: f32,
+    spawn_cursor: u32,
+    burst_pending: bool,
+    // when the emitter stopped spawning, in seconds since it started
+    spawn_end: Option<f32>,
+    particles: EmitterParticles,
+}
+
+impl Emitter {
+    pub fn new(
+        gl: &WebGl2RenderingContext,
+        desc: &EmitterDesc,
+        origin: Vec3,
+        backend: ParticleBackend,
+        quad: &Buffer,
+        render: &RenderProgram,
+        seed: u64,
+    ) -> Result<Self, ErrorStr> {
+        let max = desc.max_particles as usize;
+        let initial: Vec<Particle> = (0..max)
+            .map(|i| Particle::dead(i as f32 * 0.618_034))
+            .collect();
+        let mut initial_data = vec![0.0f32; max * PARTICLE_FLOATS];
+        for (particle, out) in initial
+            .iter()
+            .zip(initial_data.chunks_exact_mut(PARTICLE_FLOATS))
+        {
+            particle.write(out);
+        }
+
+        let particles = match backend {
+            ParticleBackend::TransformFeedback => {
+                let buffers = [
+                    particle_buffer(gl, &desc.name, 0, &initial_data)?,
+                    particle_buffer(gl, &desc.name, 1, &initial_data)?,
+                ];
+                let update_vaos = [
+                    update_vao(gl, &desc.name, &buffers[0])?,
+                    update_vao(gl, &desc.name, &buffers[1])?,
+                ];
+                let render_vaos = [
+                    render_vao(gl, &desc.name, quad, &buffers[0], render)?,
+                    render_vao(gl, &desc.name, quad, &buffers[1], render)?,
+                ];
+                let feedback = if let Some(feedback) = gl.create_transform_feedback() {
+                    feedback
+                } else {
+                    let error = format!(
+                        "Unable to create transform feedback for emitter '{}'",
+                        desc.name
+                    );
+                    return Err(ErrorStr::new(error));
+                };
+                EmitterParticles::Gpu(GpuParticles {
+                    buffers,
+                    update_vaos,
+                    render_vaos,
+                    feedback,
+                    current: 0,
+                })
+            }
+            ParticleBackend::CpuInstanced => {
+                let mut instances = Buffer::new(
+                    gl,
+                    BufferTarget::Array,
+                    BufferUsage::StreamDraw,
+                    format!("{} instances", desc.name),
+                )?;
+                instances.upload_f32(gl, &initial_data);
+                let render_vao = render_vao(gl, &desc.name, quad, &instances, render)?;
+                EmitterParticles::Cpu(CpuParticles {
+                    particles: initial,
+                    rng: Rng::new(seed),
+                    instances,
+                    render_vao,
+                    scratch: initial_data,
+                })
+            }
+        };
+
+        Ok(Self {
+            desc: desc.clone(),
+            baked: BakedCurves::new(desc),
+            origin,
+            elapsed: 0.0,
+            spawn_accumulator: 0.0,
+            spawn_cursor: 0,
+            burst_pending: desc.burst > 0,
+            spawn_end: None,
+            particles,
+        })
+    }
+
+    pub fn stop(&mut self) {
+        if self.spawn_end.is_none() {
+            self.spawn_end = Some(self.elapsed);
+        }
+    }
+
+    // true once the emitter stopped spawning and its last particle has died
+    pub fn finished(&self) -> bool {
+        if let Some(spawn_end) = self.spawn_end {
+            self.elapsed > spawn_end + self.desc.max_lifetime()
+        } else {
+            false
+        }
+    }
+
+    #[inline]
+    pub fn desc(&self) -> &EmitterDesc {
+        &self.desc
+    }
+
+    fn take_spawn_count(&mut self, delta: f32) -> u32 {
+        if self.desc.duration.is_some_and(|duration| self.elapsed > duration) {
+            self.stop();
+        }
+        let mut count = 0u32;
+        if self.burst_pending {
+            count += self.desc.burst;
+            self.burst_pending = false;
+        }
+        if self.spawn_end.is_none() {
+            self.spawn_accumulator += self.desc.spawn_rate * delta;
+            let whole = self.spawn_accumulator.floor();
+            self.spawn_accumulator -= whole;
+            count = count.saturating_add(whole as u32);
+        }
+        count.min(self.desc.max_particles)
+    }
+
+    pub fn update(
+        &mut self,
+        gl: &WebGl2RenderingContext,
+        update: Option<&UpdateProgram>,
+        delta: f32,
+        time: f32,
+    ) -> Result<(), ErrorStr> {
+        self.elapsed += delta;
+        let spawn_start = self.spawn_cursor;
+        let spawn_count = self.take_spawn_count(delta);
+        self.spawn_cursor = (self.spawn_cursor + spawn_count) % self.desc.max_particles;
+
+        match &mut self.particles {
+            EmitterParticles::Gpu(gpu) => {
+                let update = if let Some(update) = update {
+                    update
+                } else {
+                    let error = format!(
+                        "Emitter '{}' uses transform feedback but there is no update program",
+                        self.desc.name
+                    );
+                    return Err(ErrorStr::new(error));
+                };
+                update.set_uniforms(
+                    gl,
+                    &self.desc,
+                    &self.baked,
+                    self.origin,
+                    delta,
+                    time,
+                    spawn_start,
+                    spawn_count,
+                );
+                let source = gpu.current;
+                let destination = 1 - source;
+                gpu.update_vaos[source].bind(gl);
+                gl.bind_transform_feedback(
+                    WebGl2RenderingContext::TRANSFORM_FEEDBACK,
+                    Some(&gpu.feedback),
+                );
+                gl.bind_buffer_base(
+                    WebGl2RenderingContext::TRANSFORM_FEEDBACK_BUFFER,
+                    0,
+                    Some(gpu.buffers[destination].buffer()),
+                );
+                gl.enable(WebGl2RenderingContext::RASTERIZER_DISCARD);
+                gl.begin_transform_feedback(WebGl2RenderingContext::POINTS);
+                gl.draw_arrays(
+                    WebGl2RenderingContext::POINTS,
+                    0,
+                    self.desc.max_particles as i32,
+                );
+                record_draw(
+                    WebGl2RenderingContext::POINTS,
+                    self.desc.max_particles as i32,
+                    1,
+                );
+                gl.end_transform_feedback();
+                gl.disable(WebGl2RenderingContext::RASTERIZER_DISCARD);
+                gl.bind_buffer_base(WebGl2RenderingContext::TRANSFORM_FEEDBACK_BUFFER, 0, None);
+                gl.bind_transform_feedback(WebGl2RenderingContext::TRANSFORM_FEEDBACK, None);
+                gpu.update_vaos[source].unbind(gl);
+                gpu.current = destination;
+            }
+            EmitterParticles::Cpu(cpu) => {
+                simulate_cpu(
+                    cpu,
+                    &self.desc,
+                    &self.baked,
+                    self.origin,
+                    delta,
+                    spawn_start,
+                    spawn_count,
+                );
+                for (particle, out) in cpu
+                    .particles
+                    .iter()
+                    .zip(cpu.scratch.chunks_exact_mut(PARTICLE_FLOATS))
+                {
+                    particle.write(out);
+                }
+                cpu.instances.upload_f32(gl, &cpu.scratch);
+            }
+        }
+        Ok(())
+    }
+
+    // the render program must be in use, with the camera uniforms set
+    pub fn draw(&self, gl: &WebGl2RenderingContext, render: &RenderProgram) {
+        render.set_curves(gl, &self.baked);
+        if self.desc.additive {
+            gl.blend_func(WebGl2RenderingContext::SRC_ALPHA, WebGl2RenderingContext::ONE);
+        } else {
+            gl.blend_func(
+                WebGl2RenderingContext::SRC_ALPHA,
+                WebGl2RenderingContext::ONE_MINUS_SRC_ALPHA,
+            );
+        }
+        let vao = match &self.particles {
+            EmitterParticles::Gpu(gpu) => &gpu.render_vaos[gpu.current],
+            EmitterParticles::Cpu(cpu) => &cpu.render_vao,
+        };
+        vao.bind(gl);
+        gl.draw_arrays_instanced(
+            WebGl2RenderingContext::TRIANGLE_STRIP,
+            0,
+            4,
+            self.desc.max_particles as i32,
+        );
+        record_draw(
+            WebGl2RenderingContext::TRIANGLE_STRIP,
+            4,
+            self.desc.max_particles as i32,
+        );
+        vao.unbind(gl);
+    }
+
+    pub fn delete(&self, gl: &WebGl2RenderingContext) {
+        match &self.particles {
+            EmitterParticles::Gpu(gpu) => {
+                for buffer in gpu.buffers.iter() {
+                    buffer.delete(gl);
+                }
+                for vao in gpu.update_vaos.iter().chain(gpu.render_vaos.iter()) {
+                    vao.delete(gl);
+                }
+                gl.delete_transform_feedback(Some(&gpu.feedback));
+            }
+            EmitterParticles::Cpu(cpu) => {
+                cpu.instances.delete(gl);
+                cpu.render_vao.delete(gl);
+            }
+        }
+    }
+}
+
+// mirrors particle_update/vert.glsl
+fn simulate_cpu(
+    cpu: &mut CpuParticles,
+    desc: &EmitterDesc,
+    baked: &BakedCurves,
+    origin: Vec3,
+    delta: f32,
+    spawn_start: u32,
+    spawn_count: u32,
+) {
+    let max = desc.max_particles;
+    let plane_mask = if desc.planar {
+        Vec3::new(1.0, 1.0, 0.0)
+    } else {
+        Vec3::ONE
+    };
+    let gravity = Vec3::from_array(desc.gravity);
+    let base_direction = Vec3::from_array(desc.direction);
+    let rng = &mut cpu.rng;
+
+    for (i, particle) in cpu.particles.iter_mut().enumerate() {
+        let offset = (i as u32 + max - spawn_start) % max;
+        if particle.is_dead() {
+            if offset < spawn_count {
+                let spawn_offset = match desc.shape {
+                    EmitterShape::Point => Vec3::ZERO,
+                    EmitterShape::Circle { radius } => {
+                        let direction = (random_unit(rng) * plane_mask).normalize_or(Vec3::X);
+                        direction * radius * rng.next_f32().sqrt()
+                    }
+                    EmitterShape::Box { half_extents } => {
+                        let r = Vec3::new(
+                            rng.range_f32(-1.0, 1.0),
+                            rng.range_f32(-1.0, 1.0),
+                            rng.range_f32(-1.0, 1.0),
+                        );
+                        r * Vec3::from_array(half_extents) * plane_mask
+                    }
+                };
+                let direction = if desc.spread >= FULL_SPREAD {
+                    random_unit(rng)
+                } else {
+                    base_direction + random_unit(rng) * desc.spread.min(1.5).tan()
+                };
+                let direction = (direction * plane_mask).normalize_or(Vec3::X);
+                *particle = Particle {
+                    position: origin + spawn_offset,
+                    velocity: direction * rng.range_f32(desc.speed[0], desc.speed[1]),
+                    age: 0.0,
+                    lifetime: rng.range_f32(desc.lifetime[0], desc.lifetime[1]),
+                    seed: rng.next_f32(),
+                };
+            }
+            continue;
+        }
+        let t = particle.age / particle.lifetime;
+        let velocity = (particle.velocity + gravity * delta) * (1.0 - desc.drag * delta).max(0.0);
+        particle.position += velocity * sample_baked(&baked.speed, t) * delta;
+        particle.velocity = velocity;
+        particle.age += delta;
+    }
+}
+
+fn random_unit(rng: &mut Rng) -> Vec3 {
+    let z = rng.range_f32(-1.0, 1.0);
+    let a = rng.range_f32(0.0, std::f32::consts::TAU);
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    Vec3::new(r * a.cos(), r * a.sin(), z)
+}
+
+fn particle_buffer(
+    gl: &WebGl2RenderingContext,
+    name: &str,
+    index: usize,
+    initial_data: &[f32],
+) -> Result<Buffer, ErrorStr> {
+    let mut buffer = Buffer::new(
+        gl,
+        BufferTarget::Array,
+        BufferUsage::DynamicCopy,
+        format!("{} particles {}", name, index),
+    )?;
+    buffer.upload_f32(gl, initial_data);
+    buffer.unbind(gl);
+    Ok(buffer)
+}
+
+fn update_vao(
+    gl: &WebGl2RenderingContext,
+    name: &str,
+    particles: &Buffer,
+) -> Result<VertexArray, ErrorStr> {
+    let vao = VertexArray::new(gl, format!("{} update", name))?;
+    vao.bind(gl);
+    particles.bind(gl);
+    vao.attrib_f32(gl, 0, 3, PARTICLE_STRIDE, POSITION_OFFSET, 0);
+    vao.attrib_f32(gl, 1, 3, PARTICLE_STRIDE, VELOCITY_OFFSET, 0);
+    vao.attrib_f32(gl, 2, 1, PARTICLE_STRIDE, AGE_OFFSET, 0);
+    vao.attrib_f32(gl, 3, 1, PARTICLE_STRIDE, LIFETIME_OFFSET, 0);
+    vao.attrib_f32(gl, 4, 1, PARTICLE_STRIDE, SEED_OFFSET, 0);
+    vao.unbind(gl);
+    particles.unbind(gl);
+    Ok(vao)
+}
+
+fn render_vao(
+    gl: &WebGl2RenderingContext,
+    name: &str,
+    quad: &Buffer,
+    particles: &Buffer,
+    render: &RenderProgram,
+) -> Result<VertexArray, ErrorStr> {
+    let vao = VertexArray::new(gl, format!("{} render", name))?;
+    vao.bind(gl);
+    quad.bind(gl);
+    vao.attrib_f32(gl, render.corner, 2, 8, 0, 0);
+    particles.bind(gl);
+    vao.attrib_f32(gl, render.position, 3, PARTICLE_STRIDE, POSITION_OFFSET, 1);
+    vao.attrib_f32(gl, render.age, 1, PARTICLE_STRIDE, AGE_OFFSET, 1);
+    vao.attrib_f32(gl, render.lifetime, 1, PARTICLE_STRIDE, LIFETIME_OFFSET, 1);
+    vao.unbind(gl);
+    particles.unbind(gl);
+    Ok(vao)
+}
@@ -0,0 +1,349 @@
+pub mod curve;
+pub mod effect;
+pub mod emitter;
+
+use glam::Vec3;
+use web_sys::{WebGl2RenderingContext, WebGlProgram, WebGlUniformLocation};
+
+use crate::libs::profiler::counters::record_state_change;
+use crate::libs::rendering::camera::Camera;
+use crate::libs::rendering::gl::buffer::{Buffer, BufferTarget, BufferUsage};
+use crate::libs::rendering::gl::program::{attrib_location, link_program_sources, uniform_location};
+use crate::libs::rendering::particles::effect::{EmitterDesc, EmitterShape, ParticleEffect};
+use crate::libs::rendering::particles::emitter::{BakedCurves, Emitter};
+use crate::libs::rendering::shaders::{particle_render, particle_update};
+use crate::libs::types::errors::ErrorStr;
+use crate::libs::types::rng::splitmix64;
+use crate::log_warn;
+
+const LOG_TARGET: &str = "rendering::particles";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParticleBackend {
+    // simulated in the vertex shader, nothing is read back
+    TransformFeedback,
+    // simulated on the CPU and uploaded as instance data every frame
+    CpuInstanced,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EffectHandle(u32);
+
+#[derive(Debug)]
+pub struct UpdateProgram {
+    program: WebGlProgram,
+    delta: WebGlUniformLocation,
+    time: WebGlUniformLocation,
+    origin: WebGlUniformLocation,
+    shape: WebGlUniformLocation,
+    shape_size: WebGlUniformLocation,
+    direction: WebGlUniformLocation,
+    spread: WebGlUniformLocation,
+    speed: WebGlUniformLocation,
+    lifetime: WebGlUniformLocation,
+    gravity: WebGlUniformLocation,
+    drag: WebGlUniformLocation,
+    planar: WebGlUniformLocation,
+    speed_over_life: WebGlUniformLocation,
+    spawn_start: WebGlUniformLocation,
+    spawn_count: WebGlUniformLocation,
+    max_particles: WebGlUniformLocation,
+}
+
+impl UpdateProgram {
+    fn new(gl: &WebGl2RenderingContext) -> Result<Self, ErrorStr> {
+        let name = "Particle update";
+        let program = link_program_sources(
+            gl,
+            particle_update::VERT_SHADER,
+            particle_update::FRAG_SHADER,
+            &particle_update::FEEDBACK_VARYINGS,
+            name,
+        )?;
+        Ok(Self {
+            delta: uniform_location(gl, &program, "uDelta", name)?,
+            time: uniform_location(gl, &program, "uTime", name)?,
+            origin: uniform_location(gl, &program, "uOrigin", name)?,
+            shape: uniform_location(gl, &program, "uShape", name)?,
+            shape_size: uniform_location(gl, &program, "uShapeSize", name)?,
+            direction: uniform_location(gl, &program, "uDirection", name)?,
+            spread: uniform_location(gl, &program, "uSpread", name)?,
+            speed: uniform_location(gl, &program, "uSpeed", name)?,
+            lifetime: uniform_location(gl, &program, "uLifetime", name)?,
+            gravity: uniform_location(gl, &program, "uGravity", name)?,
+            drag: uniform_location(gl, &program, "uDrag", name)?,
+            planar: uniform_location(gl, &program, "uPlanar", name)?,
+            speed_over_life: uniform_location(gl, &program, "uSpeedOverLife", name)?,
+            spawn_start: uniform_location(gl, &program, "uSpawnStart", name)?,
+            spawn_count: uniform_location(gl, &program, "uSpawnCount", name)?,
+            max_particles: uniform_location(gl, &program, "uMaxParticles", name)?,
+            program,
+        })
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn set_uniforms(
+        &self,
+        gl: &WebGl2RenderingContext,
+        desc: &EmitterDesc,
+        baked: &BakedCurves,
+        origin: Vec3,
+        delta: f32,
+        time: f32,
+        spawn_start: u32,
+        spawn_count: u32,
+    ) {
+        gl.use_program(Some(&self.program));
+        record_state_change();
+        gl.uniform1f(Some(&self.delta), delta);
+        gl.uniform1f(Some(&self.time), time);
+        gl.uniform3f(Some(&self.origin), origin.x, origin.y, origin.z);
+        let (shape, shape_size) = match desc.shape {
+            EmitterShape::Point => (0, [0.0; 3]),
+            EmitterShape::Circle { radius } => (1, [radius, radius, radius]),
+            EmitterShape::Box { half_extents } => (2, half_extents),
+        };
+        gl.uniform1i(Some(&self.shape), shape);
+        gl.uniform3fv_with_f32_array(Some(&self.shape_size), &shape_size);
+        gl.uniform3fv_with_f32_array(Some(&self.direction), &desc.direction);
+        gl.uniform1f(Some(&self.spread), desc.spread);
+        gl.uniform2fv_with_f32_array(Some(&self.speed), &desc.speed);
+        gl.uniform2fv_with_f32_array(Some(&self.lifetime), &desc.lifetime);
+        gl.uniform3fv_with_f32_array(Some(&self.gravity), &desc.gravity);
+        gl.uniform1f(Some(&self.drag), desc.drag);
+        gl.uniform1f(Some(&self.planar), if desc.planar { 1.0 } else { 0.0 });
+        gl.uniform1fv_with_f32_array(Some(&self.speed_over_life), &baked.speed);
+        gl.uniform1i(Some(&self.spawn_start), spawn_start as i32);
+        gl.uniform1i(Some(&self.spawn_count), spawn_count as i32);
+        gl.uniform1i(Some(&self.max_particles), desc.max_particles as i32);
+    }
+}
+
+#[derive(Debug)]
+pub struct RenderProgram {
+    program: WebGlProgram,
+    pub corner: u32,
+    pub position: u32,
+    pub age: u32,
+    pub lifetime: u32,
+    view_projection: WebGlUniformLocation,
+    camera_right: WebGlUniformLocation,
+    camera_up: WebGlUniformLocation,
+    colour_over_life: WebGlUniformLocation,
+    size_over_life: WebGlUniformLocation,
+}
+
+impl RenderProgram {
+    fn new(gl: &WebGl2RenderingContext) -> Result<Self, ErrorStr> {
+        let name = "Particle render";
+        let program = link_program_sources(
+            gl,
+            particle_render::VERT_SHADER,
+            particle_render::FRAG_SHADER,
+            &[],
+            name,
+        )?;
+        Ok(Self {
+            corner: attrib_location(gl, &program, "aCorner", name)?,
+            position: attrib_location(gl, &program, "aPosition", name)?,
+            age: attrib_location(gl, &program, "aAge", name)?,
+            lifetime: attrib_location(gl, &program, "aLifetime", name)?,
+            view_projection: uniform_location(gl, &program, "uViewProjection", name)?,
+            camera_right: uniform_location(gl, &program, "uCameraRight", name)?,
+            camera_up: uniform_location(gl, &program, "uCameraUp", name)?,
+            colour_over_life: uniform_location(gl, &program, "uColourOverLife", name)?,
+            size_over_life: uniform_location(gl, &program, "uSizeOverLife", name)?,
+            program,
+        })
+    }
+
+    fn set_camera(&self, gl: &WebGl2RenderingContext, camera: &Camera) {
+        gl.use_program(Some(&self.program));
+        record_state_change();
+        let view = camera.view();
+        let right = view.row(0).truncate();
+        let up = view.row(1).truncate();
+        gl.uniform_matrix4fv_with_f32_array(
+            Some(&self.view_projection),
+            false,
+            &camera.view_projection().to_cols_array(),
+        );
+        gl.uniform3f(Some(&self.camera_right), right.x, right.y, right.z);
+        gl.uniform3f(Some(&self.camera_up), up.x, up.y, up.z);
+    }
+
+    pub fn set_curves(&self, gl: &WebGl2RenderingContext, baked: &BakedCurves) {
+        gl.uniform4fv_with_f32_array(Some(&self.colour_over_life), &baked.colour_flat());
+        gl.uniform1fv_with_f32_array(Some(&self.size_over_life), &baked.size);
+    }
+}
+
+#[derive(Debug)]
+struct ActiveEffect {
+    handle: EffectHandle,
+    name: String,
+    emitters: Vec<Emitter>,
+}
+
+// owns every live effect on one canvas. build it in an init task, then call update and draw from
+// a raf task
+#[derive(Debug)]
+pub struct ParticleSystem {
+    backend: ParticleBackend,
+    update: Option<UpdateProgram>,
+    render: RenderProgram,
+    quad: Buffer,
+    effects: Vec<ActiveEffect>,
+    next_handle: u32,
+    time: f32,
+}
+
+impl ParticleSystem {
+    // falls back to the CPU path if the transform feedback program can't be built
+    pub fn new(
+        gl: &WebGl2RenderingContext,
+        preferred: ParticleBackend,
+    ) -> Result<Self, ErrorStr> {
+        let render = RenderProgram::new(gl)?;
+        let (backend, update) = match preferred {
+            ParticleBackend::TransformFeedback => match UpdateProgram::new(gl) {
+                Ok(update) => (ParticleBackend::TransformFeedback, Some(update)),
+                Err(error) => {
+                    log_warn!(
+                        LOG_TARGET,
+                        "Transform feedback particles unavailable, falling back to CPU: {}",
+                        error
+                    );
+                    (ParticleBackend::CpuInstanced, None)
+                }
+            },
+            ParticleBackend::CpuInstanced => (ParticleBackend::CpuInstanced, None),
+        };
+        let mut quad = Buffer::new(
+            gl,
+            BufferTarget::Array,
+            BufferUsage::StaticDraw,
+            "Particle quad",
+        )?;
+        let corners: [f32; 8] = [-0.5, -0.5, 0.5, -0.5, -0.5, 0.5, 0.5, 0.5];
+        quad.upload_f32(gl, &corners);
+        quad.unbind(gl);
+        Ok(Self {
+            backend,
+            update,
+            render,
+            quad,
+            effects: Vec::new(),
+            next_handle: 0,
+            time: 0.0,
+        })
+    }
+
+    pub fn spawn(
+        &mut self,
+        gl: &WebGl2RenderingContext,
+        effect: &ParticleEffect,
+        origin: Vec3,
+    ) -> Result<EffectHandle, ErrorStr> {
+        let handle = EffectHandle(self.next_handle);
+        self.next_handle = self.next_handle.wrapping_add(1);
+        let mut emitters = Vec::with_capacity(effect.emitters.len());
+        for (i, desc) in effect.emitters.iter().enumerate() {
+            let seed = splitmix64(((handle.0 as u64) << 16) | i as u64);
+            match Emitter::new(
+                gl,
+                desc,
+                origin,
+                self.backend,
+                &self.quad,
+                &self.render,
+                seed,
+            ) {
+                Ok(emitter) => emitters.push(emitter),
+                Err(error) => {
+                    for emitter in emitters.iter() {
+                        emitter.delete(gl);
+                    }
+                    return Err(error);
+                }
+            }
+        }
+        self.effects.push(ActiveEffect {
+            handle,
+            name: effect.name.clone(),
+            emitters,
+        });
+        Ok(handle)
+    }
+
+    // moves a running effect, e.g. a trail following a projectile
+    pub fn set_origin(&mut self, handle: EffectHandle, origin: Vec3) {
+        if let Some(effect) = self.effects.iter_mut().find(|e| e.handle == handle) {
+            for emitter in effect.emitters.iter_mut() {
+                emitter.origin = origin;
+            }
+        }
+    }
+
+    // stops spawning, live particles finish their lifetime before the effect is dropped
+    pub fn stop(&mut self, handle: EffectHandle) {
+        if let Some(effect) = self.effects.iter_mut().find(|e| e.handle == handle) {
+            for emitter in effect.emitters.iter_mut() {
+                emitter.stop();
+            }
+        }
+    }
+
+    pub fn is_alive(&self, handle: EffectHandle) -> bool {
+        self.effects.iter().any(|e| e.handle == handle)
+    }
+
+    // delta in seconds
+    pub fn update(&mut self, gl: &WebGl2RenderingContext, delta: f32) -> Result<(), ErrorStr> {
+        self.time += delta;
+        let mut result = Ok(());
+        for effect in self.effects.iter_mut() {
+            for emitter in effect.emitters.iter_mut() {
+                if let Err(error) = emitter.update(gl, self.update.as_ref(), delta, self.time) {
+                    let error = format!("In particle effect '{}': {}", effect.name, error);
+                    result = Err(ErrorStr::new(error));
+                }
+            }
+        }
+        self.effects.retain(|effect| {
+            let finished = effect.emitters.iter().all(|emitter| emitter.finished());
+            if finished {
+                for emitter in effect.emitters.iter() {
+                    emitter.delete(gl);
+                }
+            }
+            !finished
+        });
+        result
+    }
+
+    pub fn draw(&self, gl: &WebGl2RenderingContext, camera: &Camera) {
+        if self.effects.is_empty() {
+            return;
+        }
+        self.render.set_camera(gl, camera);
+        gl.enable(WebGl2RenderingContext::BLEND);
+        gl.depth_mask(false);
+        for effect in self.effects.iter() {
+            for emitter in effect.emitters.iter() {
+                emitter.draw(gl, &self.render);
+            }
+        }
+        gl.depth_mask(true);
+        gl.disable(WebGl2RenderingContext::BLEND);
+    }
+
+    #[inline]
+    pub fn backend(&self) -> ParticleBackend {
+        self.backend
+    }
+
+    pub fn live_effects(&self) -> usize {
+        self.effects.len()
+    }
+}
@@ -0,0 +1,455 @@
+use glam::{UVec2, Vec2, Vec3};
+use web_sys::{WebGl2RenderingContext, WebGlProgram, WebGlSync, WebGlUniformLocation};
+
+use crate::libs::profiler::counters::{record_draw, record_state_change};
+use crate::libs::rendering::camera::Camera;
+use crate::libs::rendering::colour::Colour;
+use crate::libs::rendering::gl::buffer::{Buffer, BufferTarget, BufferUsage};
+use crate::libs::rendering::gl::framebuffer::Framebuffer;
+use crate::libs::rendering::gl::program::{link_program_sources, uniform_location};
+use crate::libs::rendering::gl::texture::TextureFormat;
+use crate::libs::rendering::gl::vao::VertexArray;
+use crate::libs::rendering::shaders::picking as picking_shader;
+use crate::libs::rendering::shapes::tessellate::{Mesh, fill_circle, fill_polygon};
+use crate::libs::types::entity::EntityId;
+use crate::libs::types::errors::ErrorStr;
+
+// position (3 floats as bits), entity id (1)
+const VERTEX_WORDS: usize = 4;
+const VERTEX_STRIDE: i32 = (VERTEX_WORDS * 4) as i32;
+// integer targets are always readable as RGBA_INTEGER, a single channel read is optional
+const PIXEL_BYTES: usize = 16;
+const CIRCLE_TOLERANCE: f32 = 0.5;
+
+// geometry for the id pass, in the same world space the camera sees. later shapes win on equal
+// depth, so for 2d content push them in draw order
+#[derive(Debug, Clone, Default)]
+pub struct PickBatch {
+    vertices: Vec<u32>,
+    indices: Vec<u32>,
+    scratch: Mesh,
+}
+
+impl PickBatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+        self.indices.clear();
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    // any tessellated shape, e.g. the same ShapeBatch mesh that was drawn for a unit
+    pub fn add_mesh(&mut self, mesh: &Mesh, z: f32, id: EntityId) {
+        let base = (self.vertices.len() / VERTEX_WORDS) as u32;
+        for vertex in mesh.vertices.iter() {
+            self.push_vertex(vertex.position.extend(z), id);
+        }
+        self.indices.extend(mesh.indices.iter().map(|index| base + index));
+    }
+
+    // 3d triangles, three positions each
+    pub fn add_triangles(&mut self, positions: &[Vec3], id: EntityId) {
+        let base = (self.vertices.len() / VERTEX_WORDS) as u32;
+        let count = positions.len() - positions.len() % 3;
+        for position in positions[..count].iter() {
+            self.push_vertex(*position, id);
+        }
+        self.indices.extend(base..base + count as u32);
+    }
+
+    // tiles and sprites
+    pub fn add_rect(&mut self, min: Vec2, max: Vec2, id: EntityId) {
+        let base = (self.vertices.len() / VERTEX_WORDS) as u32;
+        for corner in [min, Vec2::new(max.x, min.y), max, Vec2::new(min.x, max.y)] {
+            self.push_vertex(corner.extend(0.0), id);
+        }
+        self.indices
+            .extend_from_slice(&[base, base + 1, base + 2, base, base + 2, base + 3]);
+    }
+
+    pub fn add_circle(&mut self, center: Vec2, radius: f32, id: EntityId) {
+        let mut mesh = std::mem::take(&mut self.scratch);
+        mesh.clear();
+        fill_circle(&mut mesh, center, radius, Colour::WHITE, CIRCLE_TOLERANCE);
+        self.add_mesh(&mesh, 0.0, id);
+        self.scratch = mesh;
+    }
+
+    pub fn add_polygon(&mut self, points: &[Vec2], id: EntityId) {
+        let mut mesh = std::mem::take(&mut self.scratch);
+        mesh.clear();
+        fill_polygon(&mut mesh, points, Colour::WHITE);
+        self.add_mesh(&mesh, 0.0, id);
+        self.scratch = mesh;
+    }
+
+    fn push_vertex(&mut self, position: Vec3, id: EntityId) {
+        self.vertices.extend_from_slice(&[
+            position.x.to_bits(),
+            position.y.to_bits(),
+            position.z.to_bits(),
+            id.raw(),
+        ]);
+    }
+}
+
+// ids read back from a region of the target
+#[derive(Debug, Clone)]
+pub struct PickResult {
+    origin: UVec2,
+    size: UVec2,
+    ids: Vec<u32>,
+}
+
+impl PickResult {
+    fn from_pixels(origin: UVec2, size: UVec2, pixels: &[u8]) -> Self {
+        let ids = pixels
+            .chunks_exact(PIXEL_BYTES)
+            .map(|pixel| u32::from_le_bytes([pixel[0], pixel[1], pixel[2], pixel[3]]))
+            .collect();
+        Self { origin, size, ids }
+    }
+
+    // the entity under the centre of the region, the pointer for single pixel picks
+    pub fn entity(&self) -> Option<EntityId> {
+        let center = self.size / 2;
+        let index = (center.y * self.size.x + center.x) as usize;
+        self.ids.get(index).and_then(|raw| EntityId::new(*raw))
+    }
+
+    // every distinct entity in the region, in first seen order
+    pub fn entities(&self) -> Vec<EntityId> {
+        let mut entities: Vec<EntityId> = Vec::new();
+        for id in self.ids.iter().filter_map(|raw| EntityId::new(*raw)) {
+            if !entities.contains(&id) {
+                entities.push(id);
+            }
+        }
+        entities
+    }
+
+    // bottom left corner, in target pixels
+    #[inline]
+    pub fn origin(&self) -> UVec2 {
+        self.origin
+    }
+
+    #[inline]
+    pub fn size(&self) -> UVec2 {
+        self.size
+    }
+}
+
+#[derive(Debug)]
+struct PendingReadback {
+    sync: WebGlSync,
+    origin: UVec2,
+    size: UVec2,
+}
+
+// renders entity ids into an R32UI target on demand and reads them back, either straight away
+// (stalls until the gpu catches up) or through a pixel pack buffer and a fence
+#[derive(Debug)]
+pub struct Picker {
+    program: WebGlProgram,
+    view_projection: WebGlUniformLocation,
+    vao: VertexArray,
+    vertices: Buffer,
+    indices: Buffer,
+    readback: Buffer,
+    target: Option<Framebuffer>,
+    pending: Option<PendingReadback>,
+}
+
+impl Picker {
+    pub fn new(gl: &WebGl2RenderingContext) -> Result<Self, ErrorStr> {
+        let name = "Picking";
+        let program = link_program_sources(
+            gl,
+            picking_shader::VERT_SHADER,
+            picking_shader::FRAG_SHADER,
+            &[],
+            name,
+        )?;
+        let view_projection = uniform_location(gl, &program, "uViewProjection", name)?;
+        let vao = VertexArray::new(gl, "Picking geometry")?;
+        vao.bind(gl);
+        let vertices = Buffer::new(
+            gl,
+            BufferTarget::Array,
+            BufferUsage::StreamDraw,
+            "Picking vertices",
+        )?;
+        vertices.bind(gl);
+        vao.attrib_f32(gl, 0, 3, VERTEX_STRIDE, 0, 0);
+        vao.attrib_u32(gl, 1, 1, VERTEX_STRIDE, 12, 0);
+        let indices = Buffer::new(
+            gl,
+            BufferTarget::ElementArray,
+            BufferUsage::StreamDraw,
+            "Picking indices",
+        )?;
+        indices.bind(gl);
+        vao.unbind(gl);
+        vertices.unbind(gl);
+        let readback = Buffer::new(
+            gl,
+            BufferTarget::PixelPack,
+            BufferUsage::StreamRead,
+            "Picking readback",
+        )?;
+        Ok(Self {
+            program,
+            view_projection,
+            vao,
+            vertices,
+            indices,
+            readback,
+            target: None,
+            pending: None,
+        })
+    }
+
+    // blocking pick of the pixel under a screen position (top left origin, camera viewport
+    // pixels, the same space as Camera::screen_to_world)
+    pub fn pick(
+        &mut self,
+        gl: &WebGl2RenderingContext,
+        batch: &PickBatch,
+        camera: &Camera,
+        screen: Vec2,
+    ) -> Result<Option<EntityId>, ErrorStr> {
+        let result = self.pick_region(gl, batch, camera, screen, UVec2::ONE)?;
+        Ok(result.and_then(|result| result.entity()))
+    }
+
+    // blocking pick of a size pixel region centred on a screen position. None when the region
+    // lies outside the viewport
+    pub fn pick_region(
+        &mut self,
+        gl: &WebGl2RenderingContext,
+        batch: &PickBatch,
+        camera: &Camera,
+        screen: Vec2,
+        size: UVec2,
+    ) -> Result<Option<PickResult>, ErrorStr> {
+        let (origin, size) = if let Some(region) = self.render(gl, batch, camera, screen, size)? {
+            region
+        } else {
+            return Ok(None);
+        };
+        let mut pixels = vec![0u8; (size.x * size.y) as usize * PIXEL_BYTES];
+        let result = gl.read_pixels_with_opt_u8_array(
+            origin.x as i32,
+            origin.y as i32,
+            size.x as i32,
+            size.y as i32,
+            WebGl2RenderingContext::RGBA_INTEGER,
+            WebGl2RenderingContext::UNSIGNED_INT,
+            Some(&mut pixels),
+        );
+        self.unbind_target(gl, camera);
+        if let Err(error) = result {
+            let error = format!("Unable to read picking pixels: {:?}", error);
+            return Err(ErrorStr::new(error));
+        }
+        Ok(Some(PickResult::from_pixels(origin, size, &pixels)))
+    }
+
+    // non blocking version of pick_region. the result is collected with poll, usually a frame or
+    // two later. a new request replaces one still in flight
+    pub fn request_pick(
+        &mut self,
+        gl: &WebGl2RenderingContext,
+        batch: &PickBatch,
+        camera: &Camera,
+        screen: Vec2,
+        size: UVec2,
+    ) -> Result<(), ErrorStr> {
+        self.cancel(gl);
+        let (origin, size) = if let Some(region) = self.render(gl, batch, camera, screen, size)? {
+            region
+        } else {
+            return Ok(());
+        };
+        let bytes = (size.x * size.y) as usize * PIXEL_BYTES;
+        if self.readback.size() < bytes as i32 {
+            self.readback.allocate(gl, bytes as i32);
+        }
+        self.readback.bind(gl);
+        let result = gl.read_pixels_with_i32(
+            origin.x as i32,
+            origin.y as i32,
+            size.x as i32,
+            size.y as i32,
+            WebGl2RenderingContext::RGBA_INTEGER,
+            WebGl2RenderingContext::UNSIGNED_INT,
+            0,
+        );
+        self.readback.unbind(gl);
+        self.unbind_target(gl, camera);
+        if let Err(error) = result {
+            let error = format!("Unable to start picking readback: {:?}", error);
+            return Err(ErrorStr::new(error));
+        }
+        let sync = if let Some(sync) =
+            gl.fence_sync(WebGl2RenderingContext::SYNC_GPU_COMMANDS_COMPLETE, 0)
+        {
+            sync
+        } else {
+            return Err(ErrorStr::new("Unable to create picking fence"));
+        };
+        // make sure the fence is actually submitted, otherwise it may never signal
+        gl.flush();
+        self.pending = Some(PendingReadback { sync, origin, size });
+        Ok(())
+    }
+
+    // Some once the pending request's fence has signalled, None while waiting or when nothing was
+    // requested
+    pub fn poll(&mut self, gl: &WebGl2RenderingContext) -> Option<PickResult> {
+        let pending = self.pending.as_ref()?;
+        let status = gl.client_wait_sync_with_u32(&pending.sync, 0, 0);
+        match status {
+            WebGl2RenderingContext::ALREADY_SIGNALED
+            | WebGl2RenderingContext::CONDITION_SATISFIED => {}
+            WebGl2RenderingContext::WAIT_FAILED => {
+                self.cancel(gl);
+                return None;
+            }
+            _ => return None,
+        }
+        let pending = self.pending.take()?;
+        gl.delete_sync(Some(&pending.sync));
+        let mut pixels = vec![0u8; (pending.size.x * pending.size.y) as usize * PIXEL_BYTES];
+        self.readback.read(gl, &mut pixels);
+        self.readback.unbind(gl);
+        Some(PickResult::from_pixels(
+            pending.origin,
+            pending.size,
+            &pixels,
+        ))
+    }
+
+    #[inline]
+    pub fn is_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    pub fn cancel(&mut self, gl: &WebGl2RenderingContext) {
+        if let Some(pending) = self.pending.take() {
+            gl.delete_sync(Some(&pending.sync));
+        }
+    }
+
+    pub fn delete(&mut self, gl: &WebGl2RenderingContext) {
+        self.cancel(gl);
+        if let Some(mut target) = self.target.take() {
+            target.delete(gl);
+        }
+        self.vertices.delete(gl);
+        self.indices.delete(gl);
+        self.readback.delete(gl);
+        self.vao.delete(gl);
+        gl.delete_program(Some(&self.program));
+    }
+
+    // draws the id pass, scissored to the requested region. leaves the target bound for reading
+    fn render(
+        &mut self,
+        gl: &WebGl2RenderingContext,
+        batch: &PickBatch,
+        camera: &Camera,
+        screen: Vec2,
+        size: UVec2,
+    ) -> Result<Option<(UVec2, UVec2)>, ErrorStr> {
+        let viewport = camera.viewport().round().as_uvec2().max(UVec2::ONE);
+        let region = if let Some(region) = screen_region(screen, size, viewport) {
+            region
+        } else {
+            return Ok(None);
+        };
+        let target = match self.target.as_mut() {
+            Some(target) => {
+                target.resize(gl, viewport.x, viewport.y)?;
+                target
+            }
+            None => self.target.insert(Framebuffer::new(
+                gl,
+                viewport.x,
+                viewport.y,
+                &[TextureFormat::R32Ui],
+                true,
+                "Picking target",
+            )?),
+        };
+        target.bind(gl);
+        let (origin, size) = region;
+        gl.enable(WebGl2RenderingContext::SCISSOR_TEST);
+        gl.scissor(
+            origin.x as i32,
+            origin.y as i32,
+            size.x as i32,
+            size.y as i32,
+        );
+        gl.clear_bufferuiv_with_u32_array(WebGl2RenderingContext::COLOR, 0, &[0, 0, 0, 0]);
+        gl.clear_bufferfv_with_f32_array(WebGl2RenderingContext::DEPTH, 0, &[1.0]);
+        if !batch.is_empty() {
+            gl.enable(WebGl2RenderingContext::DEPTH_TEST);
+            gl.depth_func(WebGl2RenderingContext::LEQUAL);
+            gl.use_program(Some(&self.program));
+            record_state_change();
+            gl.uniform_matrix4fv_with_f32_array(
+                Some(&self.view_projection),
+                false,
+                &camera.view_projection().to_cols_array(),
+            );
+            self.vao.bind(gl);
+            self.vertices.upload_u32(gl, &batch.vertices);
+            self.indices.upload_u32(gl, &batch.indices);
+            gl.draw_elements_with_i32(
+                WebGl2RenderingContext::TRIANGLES,
+                batch.indices.len() as i32,
+                WebGl2RenderingContext::UNSIGNED_INT,
+                0,
+            );
+            record_draw(
+                WebGl2RenderingContext::TRIANGLES,
+                batch.indices.len() as i32,
+                1,
+            );
+            self.vao.unbind(gl);
+            gl.disable(WebGl2RenderingContext::DEPTH_TEST);
+        }
+        gl.disable(WebGl2RenderingContext::SCISSOR_TEST);
+        Ok(Some(region))
+    }
+
+    fn unbind_target(&self, gl: &WebGl2RenderingContext, camera: &Camera) {
+        if let Some(target) = self.target.as_ref() {
+            target.unbind(gl);
+        }
+        let viewport = camera.viewport();
+        gl.viewport(0, 0, viewport.x as i32, viewport.y as i32);
+    }
+}
+
+// converts a top left origin screen position and size into a bottom left origin pixel region,
+// clamped to the target
+fn screen_region(screen: Vec2, size: UVec2, viewport: UVec2) -> Option<(UVec2, UVec2)> {
+    let size = size.max(UVec2::ONE);
+    let pixel = Vec2::new(screen.x.floor(), viewport.y as f32 - 1.0 - screen.y.floor());
+    let min = (pixel - (size / 2).as_vec2()).max(Vec2::ZERO);
+    let max = (pixel + (size - size / 2).as_vec2()).min(viewport.as_vec2());
+    if max.x <= min.x || max.y <= min.y {
+        return None;
+    }
+    Some((min.as_uvec2(), (max - min).as_uvec2()))
+}
@@ -1 +1,7 @@
+pub mod debug_line;
+pub mod particle_render;
+pub mod particle_update;
+pub mod picking;
+pub mod shape;
 pub mod simple;
+pub mod tilemap;
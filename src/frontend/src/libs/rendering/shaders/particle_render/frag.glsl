@@ -0,0 +1,12 @@
+#version 300 es
+precision mediump float;
+
+in vec4 vColour;
+in vec2 vUv;
+out vec4 FragColor;
+
+void main() {
+    float d = length(vUv - 0.5) * 2.0;
+    float falloff = smoothstep(1.0, 0.5, d);
+    FragColor = vec4(vColour.rgb, vColour.a * falloff);
+}
@@ -0,0 +1,33 @@
+#version 300 es
+precision highp float;
+
+#define CURVE_SAMPLES 16
+
+layout(location = 0) in vec2 aCorner;
+layout(location = 1) in vec3 aPosition;
+layout(location = 2) in float aAge;
+layout(location = 3) in float aLifetime;
+
+uniform mat4 uViewProjection;
+uniform vec3 uCameraRight;
+uniform vec3 uCameraUp;
+uniform vec4 uColourOverLife[CURVE_SAMPLES];
+uniform float uSizeOverLife[CURVE_SAMPLES];
+
+out vec4 vColour;
+out vec2 vUv;
+
+void main() {
+    float t = aLifetime > 0.0 ? clamp(aAge / aLifetime, 0.0, 1.0) : 1.0;
+    float f = t * float(CURVE_SAMPLES - 1);
+    int i = int(floor(f));
+    int j = min(i + 1, CURVE_SAMPLES - 1);
+    float k = f - float(i);
+
+    // dead particles collapse to a zero area quad
+    float size = aAge < aLifetime ? mix(uSizeOverLife[i], uSizeOverLife[j], k) : 0.0;
+    vec3 world = aPosition + (uCameraRight * aCorner.x + uCameraUp * aCorner.y) * size;
+    gl_Position = uViewProjection * vec4(world, 1.0);
+    vColour = mix(uColourOverLife[i], uColourOverLife[j], k);
+    vUv = aCorner + 0.5;
+}
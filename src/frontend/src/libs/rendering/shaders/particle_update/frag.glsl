@@ -0,0 +1,9 @@
+#version 300 es
+precision mediump float;
+
+// never runs, the update pass draws with RASTERIZER_DISCARD enabled
+out vec4 FragColor;
+
+void main() {
+    FragColor = vec4(0.0);
+}
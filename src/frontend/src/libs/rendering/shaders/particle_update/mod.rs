@@ -0,0 +1,5 @@
+pub const VERT_SHADER: &str = include_str!("vert.glsl");
+pub const FRAG_SHADER: &str = include_str!("frag.glsl");
+
+// order matches the interleaved particle layout in rendering::particles
+pub const FEEDBACK_VARYINGS: [&str; 5] = ["vPosition", "vVelocity", "vAge", "vLifetime", "vSeed"];
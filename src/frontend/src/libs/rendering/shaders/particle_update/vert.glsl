@@ -0,0 +1,99 @@
+#version 300 es
+precision highp float;
+
+#define CURVE_SAMPLES 16
+
+layout(location = 0) in vec3 aPosition;
+layout(location = 1) in vec3 aVelocity;
+layout(location = 2) in float aAge;
+layout(location = 3) in float aLifetime;
+layout(location = 4) in float aSeed;
+
+uniform float uDelta;
+uniform float uTime;
+uniform vec3 uOrigin;
+// 0 point, 1 circle, 2 box
+uniform int uShape;
+uniform vec3 uShapeSize;
+uniform vec3 uDirection;
+uniform float uSpread;
+uniform vec2 uSpeed;
+uniform vec2 uLifetime;
+uniform vec3 uGravity;
+uniform float uDrag;
+uniform float uPlanar;
+uniform float uSpeedOverLife[CURVE_SAMPLES];
+// slots [uSpawnStart, uSpawnStart + uSpawnCount) (wrapping) may respawn this frame
+uniform int uSpawnStart;
+uniform int uSpawnCount;
+uniform int uMaxParticles;
+
+out vec3 vPosition;
+out vec3 vVelocity;
+out float vAge;
+out float vLifetime;
+out float vSeed;
+
+float hash(float n) {
+    return fract(sin(n) * 43758.5453123);
+}
+
+vec3 randomUnit(float seed) {
+    float z = hash(seed) * 2.0 - 1.0;
+    float a = hash(seed + 1.7) * 6.2831853;
+    float r = sqrt(max(0.0, 1.0 - z * z));
+    return vec3(r * cos(a), r * sin(a), z);
+}
+
+float sampleCurve(float t) {
+    float f = clamp(t, 0.0, 1.0) * float(CURVE_SAMPLES - 1);
+    int i = int(floor(f));
+    int j = min(i + 1, CURVE_SAMPLES - 1);
+    return mix(uSpeedOverLife[i], uSpeedOverLife[j], f - float(i));
+}
+
+void main() {
+    vec3 planeMask = vec3(1.0, 1.0, 1.0 - uPlanar);
+    int offset = (gl_VertexID - uSpawnStart + uMaxParticles) % uMaxParticles;
+    bool dead = aAge >= aLifetime;
+
+    if (dead && offset < uSpawnCount) {
+        float seed = hash(float(gl_VertexID) * 12.9898 + uTime * 78.233 + aSeed);
+        vec3 spawnOffset = vec3(0.0);
+        if (uShape == 1) {
+            vec3 direction = normalize(randomUnit(seed + 3.1) * planeMask + vec3(1e-5, 0.0, 0.0));
+            spawnOffset = direction * uShapeSize.x * sqrt(hash(seed + 5.3));
+        } else if (uShape == 2) {
+            vec3 r = vec3(hash(seed + 7.1), hash(seed + 8.3), hash(seed + 9.7)) * 2.0 - 1.0;
+            spawnOffset = r * uShapeSize * planeMask;
+        }
+        vec3 jitter = randomUnit(seed + 11.9) * tan(min(uSpread, 1.5));
+        vec3 direction = uSpread >= 3.138 ? randomUnit(seed + 11.9) : uDirection + jitter;
+        direction = normalize(direction * planeMask + vec3(1e-5, 0.0, 0.0));
+        float speed = mix(uSpeed.x, uSpeed.y, hash(seed + 13.1));
+
+        vPosition = uOrigin + spawnOffset;
+        vVelocity = direction * speed;
+        vAge = 0.0;
+        vLifetime = mix(uLifetime.x, uLifetime.y, hash(seed + 17.3));
+        vSeed = seed;
+        return;
+    }
+
+    if (dead) {
+        vPosition = aPosition;
+        vVelocity = aVelocity;
+        vAge = aAge;
+        vLifetime = aLifetime;
+        vSeed = aSeed;
+        return;
+    }
+
+    float t = aAge / aLifetime;
+    vec3 velocity = (aVelocity + uGravity * uDelta) * max(0.0, 1.0 - uDrag * uDelta);
+    vPosition = aPosition + velocity * sampleCurve(t) * uDelta;
+    vVelocity = velocity;
+    vAge = aAge + uDelta;
+    vLifetime = aLifetime;
+    vSeed = aSeed;
+}
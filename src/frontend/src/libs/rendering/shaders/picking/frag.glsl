@@ -0,0 +1,11 @@
+#version 300 es
+precision highp float;
+precision highp int;
+
+flat in uint vEntityId;
+
+layout(location = 0) out uint FragId;
+
+void main() {
+    FragId = vEntityId;
+}
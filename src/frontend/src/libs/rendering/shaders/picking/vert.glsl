@@ -0,0 +1,14 @@
+#version 300 es
+precision highp float;
+
+layout(location = 0) in vec3 aPosition;
+layout(location = 1) in uint aEntityId;
+
+uniform mat4 uViewProjection;
+
+flat out uint vEntityId;
+
+void main() {
+    vEntityId = aEntityId;
+    gl_Position = uViewProjection * vec4(aPosition, 1.0);
+}
@@ -0,0 +1,16 @@
+#version 300 es
+precision mediump float;
+
+uniform sampler2D uTileset;
+uniform float uOpacity;
+
+in vec2 vUv;
+out vec4 FragColor;
+
+void main() {
+    vec4 colour = texture(uTileset, vUv);
+    if (colour.a <= 0.0) {
+        discard;
+    }
+    FragColor = vec4(colour.rgb, colour.a * uOpacity);
+}
@@ -0,0 +1,2 @@
+pub const VERT_SHADER: &str = include_str!("vert.glsl");
+pub const FRAG_SHADER: &str = include_str!("frag.glsl");
@@ -0,0 +1,30 @@
+#version 300 es
+precision highp float;
+
+#define MAX_ANIMATIONS 32
+
+layout(location = 0) in vec2 aPosition;
+layout(location = 1) in vec2 aCorner;
+layout(location = 2) in float aTile;
+layout(location = 3) in float aAnimation;
+
+uniform mat4 uViewProjection;
+// parallax shift of the layer
+uniform vec2 uLayerOffset;
+uniform float uColumns;
+uniform vec2 uTileSize;
+uniform float uMargin;
+uniform float uSpacing;
+uniform vec2 uImageSize;
+uniform float uAnimationTiles[MAX_ANIMATIONS];
+
+out vec2 vUv;
+
+void main() {
+    float tile = aAnimation >= 0.0 ? uAnimationTiles[int(aAnimation)] : aTile;
+    float column = mod(tile, uColumns);
+    float row = floor(tile / uColumns);
+    vec2 origin = vec2(uMargin) + vec2(column, row) * (uTileSize + vec2(uSpacing));
+    vUv = (origin + aCorner * uTileSize) / uImageSize;
+    gl_Position = uViewProjection * vec4(aPosition + uLayerOffset, 0.0, 1.0);
+}
@@ -0,0 +1,223 @@
+pub mod path;
+pub mod tessellate;
+
+use glam::{Mat4, Vec2};
+use web_sys::{WebGl2RenderingContext, WebGlProgram, WebGlUniformLocation};
+
+use crate::libs::profiler::counters::{record_draw, record_state_change};
+use crate::libs::rendering::colour::Colour;
+use crate::libs::rendering::gl::buffer::{Buffer, BufferTarget, BufferUsage};
+use crate::libs::rendering::gl::program::{link_program_sources, uniform_location};
+use crate::libs::rendering::gl::vao::VertexArray;
+use crate::libs::rendering::shaders::shape as shape_shader;
+use crate::libs::rendering::shapes::path::Path;
+use crate::libs::rendering::shapes::tessellate::*;
+use crate::libs::types::errors::ErrorStr;
+
+// position (2), colour (4)
+const VERTEX_FLOATS: usize = 6;
+const VERTEX_STRIDE: i32 = (VERTEX_FLOATS * 4) as i32;
+
+// immediate mode shape api. everything drawn in a frame is tessellated into one mesh and drawn
+// with a single draw call by ShapeRenderer
+#[derive(Debug, Clone)]
+pub struct ShapeBatch {
+    mesh: Mesh,
+    tolerance: f32,
+}
+
+impl Default for ShapeBatch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ShapeBatch {
+    pub fn new() -> Self {
+        Self {
+            mesh: Mesh::default(),
+            tolerance: 0.25,
+        }
+    }
+
+    // max distance between a curve and its tessellation, in world units. lower it when zoomed in
+    pub fn set_tolerance(&mut self, tolerance: f32) {
+        self.tolerance = tolerance.max(0.01);
+    }
+
+    pub fn clear(&mut self) {
+        self.mesh.clear();
+    }
+
+    pub fn draw_line(&mut self, a: Vec2, b: Vec2, width: f32, colour: Colour) {
+        let style = StrokeStyle::new(width, colour);
+        stroke_polyline(&mut self.mesh, &[a, b], false, &style, self.tolerance);
+    }
+
+    pub fn draw_polyline(&mut self, points: &[Vec2], closed: bool, style: &StrokeStyle) {
+        stroke_polyline(&mut self.mesh, points, closed, style, self.tolerance);
+    }
+
+    pub fn draw_circle(&mut self, center: Vec2, radius: f32, colour: Colour) {
+        fill_circle(&mut self.mesh, center, radius, colour, self.tolerance);
+    }
+
+    pub fn stroke_circle(&mut self, center: Vec2, radius: f32, style: &StrokeStyle) {
+        let segments = arc_segments(radius, std::f32::consts::TAU, self.tolerance).max(3);
+        let points: Vec<Vec2> = (0..segments)
+            .map(|i| {
+                let angle = std::f32::consts::TAU * i as f32 / segments as f32;
+                center + Vec2::from_angle(angle) * radius
+            })
+            .collect();
+        stroke_polyline(&mut self.mesh, &points, true, style, self.tolerance);
+    }
+
+    pub fn fill_rect(&mut self, min: Vec2, max: Vec2, colour: Colour) {
+        fill_convex(
+            &mut self.mesh,
+            &[min, Vec2::new(max.x, min.y), max, Vec2::new(min.x, max.y)],
+            colour,
+        );
+    }
+
+    pub fn fill_rounded_rect(&mut self, min: Vec2, max: Vec2, radius: f32, colour: Colour) {
+        let path = Path::rounded_rect(min, max, radius).with_tolerance(self.tolerance);
+        for sub_path in path.sub_paths() {
+            fill_convex(&mut self.mesh, &sub_path.points, colour);
+        }
+    }
+
+    pub fn stroke_rounded_rect(&mut self, min: Vec2, max: Vec2, radius: f32, style: &StrokeStyle) {
+        let path = Path::rounded_rect(min, max, radius).with_tolerance(self.tolerance);
+        stroke_path(&mut self.mesh, &path, style);
+    }
+
+    // concave outlines are fine, they go through ear clipping
+    pub fn fill_polygon(&mut self, points: &[Vec2], colour: Colour) {
+        fill_polygon(&mut self.mesh, points, colour);
+    }
+
+    pub fn fill_path(&mut self, path: &Path, colour: Colour) {
+        fill_path(&mut self.mesh, path, colour);
+    }
+
+    pub fn stroke_path(&mut self, path: &Path, style: &StrokeStyle) {
+        stroke_path(&mut self.mesh, path, style);
+    }
+
+    #[inline]
+    pub fn mesh(&self) -> &Mesh {
+        &self.mesh
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.mesh.indices.is_empty()
+    }
+}
+
+#[derive(Debug)]
+pub struct ShapeRenderer {
+    program: WebGlProgram,
+    view_projection: WebGlUniformLocation,
+    vao: VertexArray,
+    vertices: Buffer,
+    indices: Buffer,
+    scratch: Vec<f32>,
+}
+
+impl ShapeRenderer {
+    pub fn new(gl: &WebGl2RenderingContext) -> Result<Self, ErrorStr> {
+        let name = "Shape";
+        let program = link_program_sources(
+            gl,
+            shape_shader::VERT_SHADER,
+            shape_shader::FRAG_SHADER,
+            &[],
+            name,
+        )?;
+        let view_projection = uniform_location(gl, &program, "uViewProjection", name)?;
+        let vao = VertexArray::new(gl, "Shape batch")?;
+        vao.bind(gl);
+        let vertices = Buffer::new(
+            gl,
+            BufferTarget::Array,
+            BufferUsage::DynamicDraw,
+            "Shape batch vertices",
+        )?;
+        vertices.bind(gl);
+        vao.attrib_f32(gl, 0, 2, VERTEX_STRIDE, 0, 0);
+        vao.attrib_f32(gl, 1, 4, VERTEX_STRIDE, 8, 0);
+        let indices = Buffer::new(
+            gl,
+            BufferTarget::ElementArray,
+            BufferUsage::DynamicDraw,
+            "Shape batch indices",
+        )?;
+        indices.bind(gl);
+        vao.unbind(gl);
+        vertices.unbind(gl);
+        Ok(Self {
+            program,
+            view_projection,
+            vao,
+            vertices,
+            indices,
+            scratch: Vec::new(),
+        })
+    }
+
+    // uploads the whole batch and draws it in one call
+    pub fn draw(&mut self, gl: &WebGl2RenderingContext, view_projection: Mat4, batch: &ShapeBatch) {
+        if batch.is_empty() {
+            return;
+        }
+        let mesh = batch.mesh();
+        self.scratch.clear();
+        self.scratch.reserve(mesh.vertices.len() * VERTEX_FLOATS);
+        for vertex in mesh.vertices.iter() {
+            self.scratch.extend_from_slice(&[
+                vertex.position.x,
+                vertex.position.y,
+                vertex.colour.r,
+                vertex.colour.g,
+                vertex.colour.b,
+                vertex.colour.a,
+            ]);
+        }
+
+        gl.use_program(Some(&self.program));
+
+        record_state_change();
+        gl.uniform_matrix4fv_with_f32_array(
+            Some(&self.view_projection),
+            false,
+            &view_projection.to_cols_array(),
+        );
+        self.vao.bind(gl);
+        self.vertices.upload_f32(gl, &self.scratch);
+        self.indices.upload_u32(gl, &mesh.indices);
+        gl.enable(WebGl2RenderingContext::BLEND);
+        gl.blend_func(
+            WebGl2RenderingContext::SRC_ALPHA,
+            WebGl2RenderingContext::ONE_MINUS_SRC_ALPHA,
+        );
+        gl.draw_elements_with_i32(
+            WebGl2RenderingContext::TRIANGLES,
+            mesh.indices.len() as i32,
+            WebGl2RenderingContext::UNSIGNED_INT,
+            0,
+        );
+        record_draw(WebGl2RenderingContext::TRIANGLES, mesh.indices.len() as i32, 1);
+        gl.disable(WebGl2RenderingContext::BLEND);
+        self.vao.unbind(gl);
+    }
+
+    pub fn delete(&self, gl: &WebGl2RenderingContext) {
+        self.vao.delete(gl);
+        self.vertices.delete(gl);
+        self.indices.delete(gl);
+        gl.delete_program(Some(&self.program));
+    }
+}
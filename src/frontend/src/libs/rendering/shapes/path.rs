@@ -0,0 +1,153 @@
+use glam::Vec2;
+
+use crate::libs::rendering::shapes::tessellate::arc_segments;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SubPath {
+    pub points: Vec<Vec2>,
+    pub closed: bool,
+}
+
+// a set of polylines, curves are flattened as they're added
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Path {
+    sub_paths: Vec<SubPath>,
+    // allowed distance between a curve and its flattened polyline
+    tolerance: f32,
+}
+
+impl Path {
+    pub fn new() -> Self {
+        Self {
+            sub_paths: Vec::new(),
+            tolerance: 0.25,
+        }
+    }
+
+    pub fn from_points(points: &[Vec2], closed: bool) -> Self {
+        let mut path = Self::new();
+        path.sub_paths.push(SubPath {
+            points: points.to_vec(),
+            closed,
+        });
+        path
+    }
+
+    pub fn with_tolerance(mut self, tolerance: f32) -> Self {
+        self.tolerance = tolerance.max(0.01);
+        self
+    }
+
+    fn current(&mut self) -> &mut SubPath {
+        if self.sub_paths.last().is_none_or(|sub_path| sub_path.closed) {
+            self.sub_paths.push(SubPath::default());
+        }
+        self.sub_paths.last_mut().unwrap()
+    }
+
+    fn last_point(&self) -> Vec2 {
+        self.sub_paths
+            .last()
+            .and_then(|sub_path| sub_path.points.last())
+            .copied()
+            .unwrap_or(Vec2::ZERO)
+    }
+
+    pub fn move_to(&mut self, point: Vec2) -> &mut Self {
+        self.sub_paths.push(SubPath {
+            points: vec![point],
+            closed: false,
+        });
+        self
+    }
+
+    pub fn line_to(&mut self, point: Vec2) -> &mut Self {
+        self.current().points.push(point);
+        self
+    }
+
+    pub fn quad_to(&mut self, control: Vec2, to: Vec2) -> &mut Self {
+        let from = self.last_point();
+        let length = from.distance(control) + control.distance(to);
+        let steps = ((length / (self.tolerance * 16.0)).sqrt().ceil() as usize).clamp(1, 64);
+        let current = self.current();
+        for i in 1..=steps {
+            let t = i as f32 / steps as f32;
+            let a = from.lerp(control, t);
+            let b = control.lerp(to, t);
+            current.points.push(a.lerp(b, t));
+        }
+        self
+    }
+
+    pub fn cubic_to(&mut self, control_a: Vec2, control_b: Vec2, to: Vec2) -> &mut Self {
+        let from = self.last_point();
+        let length = from.distance(control_a) + control_a.distance(control_b) + control_b.distance(to);
+        let steps = ((length / (self.tolerance * 16.0)).sqrt().ceil() as usize).clamp(1, 64);
+        let current = self.current();
+        for i in 1..=steps {
+            let t = i as f32 / steps as f32;
+            let u = 1.0 - t;
+            current.points.push(
+                from * (u * u * u)
+                    + control_a * (3.0 * u * u * t)
+                    + control_b * (3.0 * u * t * t)
+                    + to * (t * t * t),
+            );
+        }
+        self
+    }
+
+    // angles in radians, counter clockwise
+    pub fn arc(&mut self, center: Vec2, radius: f32, start: f32, end: f32) -> &mut Self {
+        let sweep = end - start;
+        let steps = arc_segments(radius, sweep.abs(), self.tolerance);
+        let current = self.current();
+        for i in 0..=steps {
+            let angle = start + sweep * i as f32 / steps as f32;
+            current
+                .points
+                .push(center + Vec2::from_angle(angle) * radius);
+        }
+        self
+    }
+
+    pub fn close(&mut self) -> &mut Self {
+        if let Some(sub_path) = self.sub_paths.last_mut() {
+            sub_path.closed = true;
+        }
+        self
+    }
+
+    pub fn rounded_rect(min: Vec2, max: Vec2, radius: f32) -> Self {
+        let mut path = Self::new();
+        let size = max - min;
+        let radius = radius.clamp(0.0, size.x.min(size.y) * 0.5);
+        if radius <= 0.0 {
+            path.move_to(min)
+                .line_to(Vec2::new(max.x, min.y))
+                .line_to(max)
+                .line_to(Vec2::new(min.x, max.y))
+                .close();
+            return path;
+        }
+        use std::f32::consts::{FRAC_PI_2, PI};
+        path.move_to(Vec2::new(min.x + radius, min.y));
+        path.arc(Vec2::new(max.x - radius, min.y + radius), radius, -FRAC_PI_2, 0.0);
+        path.arc(Vec2::new(max.x - radius, max.y - radius), radius, 0.0, FRAC_PI_2);
+        path.arc(Vec2::new(min.x + radius, max.y - radius), radius, FRAC_PI_2, PI);
+        path.arc(Vec2::new(min.x + radius, min.y + radius), radius, PI, PI + FRAC_PI_2);
+        path.close();
+        path
+    }
+
+    #[inline]
+    pub fn sub_paths(&self) -> &[SubPath] {
+        &self.sub_paths
+    }
+
+    #[inline]
+    pub fn tolerance(&self) -> f32 {
+        self.tolerance
+    }
+}
@@ -0,0 +1,688 @@
+// turns shapes into indexed triangles. nothing in here touches GL, so it can run anywhere
+use glam::Vec2;
+
+use crate::libs::rendering::colour::Colour;
+use crate::libs::rendering::shapes::path::{Path, SubPath};
+
+// points closer than this are merged before tessellating
+const EPSILON: f32 = 1e-4;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LineJoin {
+    // falls back to bevel when the miter would be longer than limit * half width
+    Miter { limit: f32 },
+    Bevel,
+    Round,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineCap {
+    Butt,
+    Square,
+    Round,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StrokeStyle {
+    pub width: f32,
+    pub colour: Colour,
+    pub join: LineJoin,
+    pub cap: LineCap,
+}
+
+impl StrokeStyle {
+    pub fn new(width: f32, colour: Colour) -> Self {
+        Self {
+            width,
+            colour,
+            join: LineJoin::Miter { limit: 4.0 },
+            cap: LineCap::Butt,
+        }
+    }
+
+    pub fn with_join(self, join: LineJoin) -> Self {
+        Self { join, ..self }
+    }
+
+    pub fn with_cap(self, cap: LineCap) -> Self {
+        Self { cap, ..self }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShapeVertex {
+    pub position: Vec2,
+    pub colour: Colour,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Mesh {
+    pub vertices: Vec<ShapeVertex>,
+    pub indices: Vec<u32>,
+}
+
+impl Mesh {
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+        self.indices.clear();
+    }
+
+    #[inline]
+    pub fn vertex(&mut self, position: Vec2, colour: Colour) -> u32 {
+        self.vertices.push(ShapeVertex { position, colour });
+        (self.vertices.len() - 1) as u32
+    }
+
+    #[inline]
+    pub fn triangle(&mut self, a: u32, b: u32, c: u32) {
+        self.indices.extend_from_slice(&[a, b, c]);
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+}
+
+// segments needed for an arc so the chords stay within tolerance of the true curve
+pub fn arc_segments(radius: f32, angle: f32, tolerance: f32) -> usize {
+    if radius <= tolerance {
+        return 1.max((angle / std::f32::consts::FRAC_PI_2).ceil() as usize);
+    }
+    let step = 2.0 * (1.0 - tolerance / radius).clamp(-1.0, 1.0).acos();
+    ((angle / step).ceil() as usize).clamp(1, 256)
+}
+
+// positive for counter clockwise winding (y up)
+pub fn signed_area(points: &[Vec2]) -> f32 {
+    let mut area = 0.0;
+    for i in 0..points.len() {
+        let a = points[i];
+        let b = points[(i + 1) % points.len()];
+        area += a.perp_dot(b);
+    }
+    area * 0.5
+}
+
+fn dedup(points: &[Vec2], closed: bool) -> Vec<Vec2> {
+    let mut out: Vec<Vec2> = Vec::with_capacity(points.len());
+    for point in points {
+        if out
+            .last()
+            .is_none_or(|last| last.distance_squared(*point) > EPSILON * EPSILON)
+        {
+            out.push(*point);
+        }
+    }
+    if closed && out.len() > 1 && out[0].distance_squared(out[out.len() - 1]) <= EPSILON * EPSILON
+    {
+        out.pop();
+    }
+    out
+}
+
+pub fn fill_circle(mesh: &mut Mesh, center: Vec2, radius: f32, colour: Colour, tolerance: f32) {
+    let segments = arc_segments(radius, std::f32::consts::TAU, tolerance).max(3);
+    let hub = mesh.vertex(center, colour);
+    let first = mesh.vertices.len() as u32;
+    for i in 0..segments {
+        let angle = std::f32::consts::TAU * i as f32 / segments as f32;
+        mesh.vertex(center + Vec2::from_angle(angle) * radius, colour);
+    }
+    for i in 0..segments as u32 {
+        mesh.triangle(hub, first + i, first + (i + 1) % segments as u32);
+    }
+}
+
+// triangle fan, only valid for convex outlines
+pub fn fill_convex(mesh: &mut Mesh, points: &[Vec2], colour: Colour) {
+    let points = dedup(points, true);
+    if points.len() < 3 {
+        return;
+    }
+    let first = mesh.vertices.len() as u32;
+    for point in points.iter() {
+        mesh.vertex(*point, colour);
+    }
+    for i in 1..points.len() as u32 - 1 {
+        mesh.triangle(first, first + i, first + i + 1);
+    }
+}
+
+fn point_in_triangle(p: Vec2, a: Vec2, b: Vec2, c: Vec2) -> bool {
+    // inclusive, so points on an edge block the ear too
+    let d1 = (b - a).perp_dot(p - a);
+    let d2 = (c - b).perp_dot(p - b);
+    let d3 = (a - c).perp_dot(p - c);
+    d1 >= -EPSILON && d2 >= -EPSILON && d3 >= -EPSILON
+}
+
+// ear clipping. handles concave simple polygons in either winding. self intersecting input
+// won't fail, but the leftover part is fanned and may overlap
+pub fn fill_polygon(mesh: &mut Mesh, points: &[Vec2], colour: Colour) {
+    let mut points = dedup(points, true);
+    if points.len() < 3 {
+        return;
+    }
+    let area = signed_area(&points);
+    // every point on one line, there's nothing to fill
+    if area.abs() <= EPSILON {
+        return;
+    }
+    if area < 0.0 {
+        points.reverse();
+    }
+    let first = mesh.vertices.len() as u32;
+    for point in points.iter() {
+        mesh.vertex(*point, colour);
+    }
+
+    let mut remaining: Vec<usize> = (0..points.len()).collect();
+    let mut misses = 0;
+    let mut i = 0;
+    while remaining.len() > 3 {
+        let n = remaining.len();
+        let prev = remaining[(i + n - 1) % n];
+        let current = remaining[i % n];
+        let next = remaining[(i + 1) % n];
+        let (a, b, c) = (points[prev], points[current], points[next]);
+        let cross = (b - a).perp_dot(c - b);
+
+        // collinear vertices add nothing, drop them
+        if cross.abs() <= EPSILON {
+            remaining.remove(i % n);
+            misses = 0;
+            continue;
+        }
+        let is_ear = cross > 0.0
+            && !remaining.iter().any(|&other| {
+                other != prev
+                    && other != current
+                    && other != next
+                    && point_in_triangle(points[other], a, b, c)
+            });
+        if is_ear {
+            mesh.triangle(first + prev as u32, first + current as u32, first + next as u32);
+            remaining.remove(i % n);
+            misses = 0;
+        } else {
+            i = (i + 1) % n;
+            misses += 1;
+            if misses > n {
+                // no ear left, the outline intersects itself
+                break;
+            }
+        }
+    }
+    for k in 1..remaining.len().saturating_sub(1) {
+        mesh.triangle(
+            first + remaining[0] as u32,
+            first + remaining[k] as u32,
+            first + remaining[k + 1] as u32,
+        );
+    }
+}
+
+// fan of triangles around center, sweeping from `from` to `to` (offsets from center) the short way
+fn round_fan(mesh: &mut Mesh, center: Vec2, from: Vec2, to: Vec2, colour: Colour, tolerance: f32) {
+    let radius = from.length();
+    let start = from.to_angle();
+    let mut sweep = to.to_angle() - start;
+    if sweep > std::f32::consts::PI {
+        sweep -= std::f32::consts::TAU;
+    } else if sweep < -std::f32::consts::PI {
+        sweep += std::f32::consts::TAU;
+    }
+    let segments = arc_segments(radius, sweep.abs(), tolerance);
+    let hub = mesh.vertex(center, colour);
+    let mut previous = mesh.vertex(center + from, colour);
+    for i in 1..=segments {
+        let angle = start + sweep * i as f32 / segments as f32;
+        let next = mesh.vertex(center + Vec2::from_angle(angle) * radius, colour);
+        mesh.triangle(hub, previous, next);
+        previous = next;
+    }
+}
+
+fn cap(
+    mesh: &mut Mesh,
+    point: Vec2,
+    direction: Vec2,
+    half_width: f32,
+    style: &StrokeStyle,
+    tolerance: f32,
+) {
+    // direction points away from the line
+    if style.cap != LineCap::Round {
+        return;
+    }
+    let normal = direction.perp() * half_width;
+    let radius = half_width;
+    let segments = arc_segments(radius, std::f32::consts::PI, tolerance);
+    let hub = mesh.vertex(point, style.colour);
+    let mut previous = mesh.vertex(point + normal, style.colour);
+    let start = normal.to_angle();
+    for i in 1..=segments {
+        // sweeps from the left edge, round the front, to the right edge
+        let angle = start - std::f32::consts::PI * i as f32 / segments as f32;
+        let next = mesh.vertex(point + Vec2::from_angle(angle) * radius, style.colour);
+        mesh.triangle(hub, previous, next);
+        previous = next;
+    }
+}
+
+fn join(
+    mesh: &mut Mesh,
+    point: Vec2,
+    incoming: Vec2,
+    outgoing: Vec2,
+    half_width: f32,
+    style: &StrokeStyle,
+    tolerance: f32,
+) {
+    let turn = incoming.perp_dot(outgoing);
+    if turn.abs() <= EPSILON && incoming.dot(outgoing) > 0.0 {
+        return;
+    }
+    // the gap opens on the outside of the turn
+    let side = if turn > 0.0 { -1.0 } else { 1.0 };
+    let from = incoming.perp() * half_width * side;
+    let to = outgoing.perp() * half_width * side;
+    let colour = style.colour;
+    match style.join {
+        LineJoin::Round => round_fan(mesh, point, from, to, colour, tolerance),
+        LineJoin::Bevel => {
+            let hub = mesh.vertex(point, colour);
+            let a = mesh.vertex(point + from, colour);
+            let b = mesh.vertex(point + to, colour);
+            mesh.triangle(hub, a, b);
+        }
+        LineJoin::Miter { limit } => {
+            let bisector = (from + to).normalize_or_zero();
+            let cos_half = bisector.dot(from / half_width);
+            let hub = mesh.vertex(point, colour);
+            let a = mesh.vertex(point + from, colour);
+            let b = mesh.vertex(point + to, colour);
+            if cos_half > EPSILON && 1.0 / cos_half <= limit {
+                let miter = mesh.vertex(point + bisector * (half_width / cos_half), colour);
+                mesh.triangle(hub, a, miter);
+                mesh.triangle(hub, miter, b);
+            } else {
+                mesh.triangle(hub, a, b);
+            }
+        }
+    }
+}
+
+// strokes a polyline. each segment is a quad, joins fill the wedge on the outside of each turn
+pub fn stroke_polyline(
+    mesh: &mut Mesh,
+    points: &[Vec2],
+    closed: bool,
+    style: &StrokeStyle,
+    tolerance: f32,
+) {
+    let points = dedup(points, closed);
+    let half_width = style.width * 0.5;
+    if half_width <= 0.0 {
+        return;
+    }
+    if points.len() == 1 {
+        // a zero length line still shows its caps
+        if style.cap == LineCap::Round {
+            fill_circle(mesh, points[0], half_width, style.colour, tolerance);
+        } else if style.cap == LineCap::Square {
+            let h = Vec2::splat(half_width);
+            fill_convex(
+                mesh,
+                &[
+                    points[0] - h,
+                    points[0] + Vec2::new(h.x, -h.y),
+                    points[0] + h,
+                    points[0] + Vec2::new(-h.x, h.y),
+                ],
+                style.colour,
+            );
+        }
+        return;
+    }
+    if points.len() < 2 {
+        return;
+    }
+    let segment_count = if closed { points.len() } else { points.len() - 1 };
+    let direction = |i: usize| -> Vec2 {
+        let a = points[i % points.len()];
+        let b = points[(i + 1) % points.len()];
+        (b - a).normalize_or_zero()
+    };
+
+    for i in 0..segment_count {
+        let mut a = points[i];
+        let mut b = points[(i + 1) % points.len()];
+        let d = direction(i);
+        if !closed && style.cap == LineCap::Square {
+            if i == 0 {
+                a -= d * half_width;
+            }
+            if i == segment_count - 1 {
+                b += d * half_width;
+            }
+        }
+        let n = d.perp() * half_width;
+        let v0 = mesh.vertex(a + n, style.colour);
+        let v1 = mesh.vertex(a - n, style.colour);
+        let v2 = mesh.vertex(b + n, style.colour);
+        let v3 = mesh.vertex(b - n, style.colour);
+        mesh.triangle(v0, v1, v2);
+        mesh.triangle(v2, v1, v3);
+    }
+
+    let joints = if closed {
+        0..points.len()
+    } else {
+        1..points.len() - 1
+    };
+    for i in joints {
+        let incoming = direction((i + points.len() - 1) % points.len());
+        let outgoing = direction(i);
+        join(
+            mesh,
+            points[i],
+            incoming,
+            outgoing,
+            half_width,
+            style,
+            tolerance,
+        );
+    }
+
+    if !closed {
+        let start_direction = direction(0);
+        let end_direction = direction(points.len() - 2);
+        cap(
+            mesh,
+            points[0],
+            -start_direction,
+            half_width,
+            style,
+            tolerance,
+        );
+        cap(
+            mesh,
+            points[points.len() - 1],
+            end_direction,
+            half_width,
+            style,
+            tolerance,
+        );
+    }
+}
+
+pub fn stroke_path(mesh: &mut Mesh, path: &Path, style: &StrokeStyle) {
+    for SubPath { points, closed } in path.sub_paths() {
+        stroke_polyline(mesh, points, *closed, style, path.tolerance());
+    }
+}
+
+// every sub path is filled on its own, holes are not cut out
+pub fn fill_path(mesh: &mut Mesh, path: &Path, colour: Colour) {
+    for sub_path in path.sub_paths() {
+        fill_polygon(mesh, &sub_path.points, colour);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOLERANCE: f32 = 0.25;
+
+    fn triangles(mesh: &Mesh) -> Vec<[Vec2; 3]> {
+        mesh.indices
+            .chunks(3)
+            .map(|triangle| {
+                [
+                    mesh.vertices[triangle[0] as usize].position,
+                    mesh.vertices[triangle[1] as usize].position,
+                    mesh.vertices[triangle[2] as usize].position,
+                ]
+            })
+            .collect()
+    }
+
+    fn filled(points: &[Vec2]) -> Mesh {
+        let mut mesh = Mesh::default();
+        fill_polygon(&mut mesh, points, Colour::WHITE);
+        mesh
+    }
+
+    fn stroked(points: &[Vec2], closed: bool, style: &StrokeStyle) -> Mesh {
+        let mut mesh = Mesh::default();
+        stroke_polyline(&mut mesh, points, closed, style, TOLERANCE);
+        mesh
+    }
+
+    // ear clipping should cover the outline exactly, with every triangle wound counter clockwise
+    fn assert_covers(mesh: &Mesh, points: &[Vec2]) {
+        let triangles = triangles(mesh);
+        for triangle in triangles.iter() {
+            assert!(signed_area(triangle) > 0.0, "{:?}", triangle);
+        }
+        let area: f32 = triangles.iter().map(|triangle| signed_area(triangle)).sum();
+        assert!((area - signed_area(points).abs()).abs() < 1e-3, "{}", area);
+    }
+
+    fn l_shape() -> Vec<Vec2> {
+        vec![
+            Vec2::new(0.0, 0.0),
+            Vec2::new(2.0, 0.0),
+            Vec2::new(2.0, 1.0),
+            Vec2::new(1.0, 1.0),
+            Vec2::new(1.0, 2.0),
+            Vec2::new(0.0, 2.0),
+        ]
+    }
+
+    #[test]
+    fn fills_concave_polygon() {
+        let points = l_shape();
+        let mesh = filled(&points);
+        assert_eq!(mesh.vertices.len(), 6);
+        assert_eq!(mesh.triangle_count(), 4);
+        assert_covers(&mesh, &points);
+    }
+
+    #[test]
+    fn fills_clockwise_concave_polygon() {
+        let mut points = l_shape();
+        points.reverse();
+        let mesh = filled(&points);
+        assert_eq!(mesh.triangle_count(), 4);
+        assert_covers(&mesh, &points);
+    }
+
+    #[test]
+    fn fills_star() {
+        let points: Vec<Vec2> = (0..10)
+            .map(|i| {
+                let radius = if i % 2 == 0 { 2.0 } else { 0.8 };
+                Vec2::from_angle(std::f32::consts::TAU * i as f32 / 10.0) * radius
+            })
+            .collect();
+        let mesh = filled(&points);
+        assert_eq!(mesh.triangle_count(), 8);
+        assert_covers(&mesh, &points);
+    }
+
+    #[test]
+    fn drops_collinear_points() {
+        let points = [
+            Vec2::new(0.0, 0.0),
+            Vec2::new(0.5, 0.0),
+            Vec2::new(1.0, 0.0),
+            Vec2::new(1.0, 0.5),
+            Vec2::new(1.0, 1.0),
+            Vec2::new(0.5, 1.0),
+            Vec2::new(0.0, 1.0),
+            Vec2::new(0.0, 0.5),
+        ];
+        let mesh = filled(&points);
+        assert_covers(&mesh, &points);
+    }
+
+    #[test]
+    fn merges_duplicate_points() {
+        let points = [
+            Vec2::new(0.0, 0.0),
+            Vec2::new(0.0, 0.0),
+            Vec2::new(1.0, 0.0),
+            Vec2::new(1.0, 1.0),
+            Vec2::new(1.0, 1.0),
+            Vec2::new(0.0, 1.0),
+            Vec2::new(0.0, 0.0),
+        ];
+        let mesh = filled(&points);
+        assert_eq!(mesh.vertices.len(), 4);
+        assert_eq!(mesh.triangle_count(), 2);
+        assert_covers(&mesh, &points);
+    }
+
+    #[test]
+    fn skips_degenerate_fills() {
+        let line = [Vec2::ZERO, Vec2::new(1.0, 0.0), Vec2::new(2.0, 0.0)];
+        assert_eq!(filled(&line), Mesh::default());
+        let longer_line = [
+            Vec2::ZERO,
+            Vec2::new(1.0, 1.0),
+            Vec2::new(2.0, 2.0),
+            Vec2::new(3.0, 3.0),
+        ];
+        assert_eq!(filled(&longer_line), Mesh::default());
+        let point = [Vec2::ONE, Vec2::ONE, Vec2::ONE];
+        assert_eq!(filled(&point), Mesh::default());
+
+        let mut mesh = Mesh::default();
+        fill_convex(&mut mesh, &[Vec2::ZERO, Vec2::ZERO, Vec2::X], Colour::WHITE);
+        assert_eq!(mesh, Mesh::default());
+    }
+
+    // one quad per segment
+    const SEGMENT_VERTICES: usize = 4;
+    const SEGMENT_TRIANGLES: usize = 2;
+
+    fn corner() -> [Vec2; 3] {
+        [Vec2::ZERO, Vec2::new(10.0, 0.0), Vec2::new(10.0, 10.0)]
+    }
+
+    #[test]
+    fn straight_lines_need_no_join() {
+        let points = [Vec2::ZERO, Vec2::new(10.0, 0.0), Vec2::new(20.0, 0.0)];
+        let mesh = stroked(&points, false, &StrokeStyle::new(2.0, Colour::WHITE));
+        assert_eq!(mesh.vertices.len(), 2 * SEGMENT_VERTICES);
+        assert_eq!(mesh.triangle_count(), 2 * SEGMENT_TRIANGLES);
+    }
+
+    #[test]
+    fn bevel_join() {
+        let style = StrokeStyle::new(2.0, Colour::WHITE).with_join(LineJoin::Bevel);
+        let mesh = stroked(&corner(), false, &style);
+        assert_eq!(mesh.vertices.len(), 2 * SEGMENT_VERTICES + 3);
+        assert_eq!(mesh.triangle_count(), 2 * SEGMENT_TRIANGLES + 1);
+    }
+
+    #[test]
+    fn miter_join() {
+        let style = StrokeStyle::new(2.0, Colour::WHITE);
+        let mesh = stroked(&corner(), false, &style);
+        assert_eq!(mesh.vertices.len(), 2 * SEGMENT_VERTICES + 4);
+        assert_eq!(mesh.triangle_count(), 2 * SEGMENT_TRIANGLES + 2);
+        // a left turn, so the tip sticks out to the bottom right
+        let tip = Vec2::new(11.0, -1.0);
+        assert!(
+            mesh.vertices
+                .iter()
+                .any(|vertex| vertex.position.distance(tip) < 1e-4)
+        );
+    }
+
+    #[test]
+    fn miter_over_limit_bevels() {
+        // a right angle's miter is sqrt(2) half widths long
+        let style = StrokeStyle::new(2.0, Colour::WHITE).with_join(LineJoin::Miter { limit: 1.2 });
+        let mesh = stroked(&corner(), false, &style);
+        assert_eq!(mesh.vertices.len(), 2 * SEGMENT_VERTICES + 3);
+        assert_eq!(mesh.triangle_count(), 2 * SEGMENT_TRIANGLES + 1);
+    }
+
+    #[test]
+    fn round_join() {
+        let style = StrokeStyle::new(8.0, Colour::WHITE).with_join(LineJoin::Round);
+        let mesh = stroked(&corner(), false, &style);
+        let segments = arc_segments(4.0, std::f32::consts::FRAC_PI_2, TOLERANCE);
+        assert!(segments > 1);
+        assert_eq!(mesh.vertices.len(), 2 * SEGMENT_VERTICES + segments + 2);
+        assert_eq!(mesh.triangle_count(), 2 * SEGMENT_TRIANGLES + segments);
+    }
+
+    #[test]
+    fn closed_outline_joins_every_corner() {
+        let square = [
+            Vec2::ZERO,
+            Vec2::new(10.0, 0.0),
+            Vec2::new(10.0, 10.0),
+            Vec2::new(0.0, 10.0),
+        ];
+        let style = StrokeStyle::new(2.0, Colour::WHITE).with_join(LineJoin::Bevel);
+        let mesh = stroked(&square, true, &style);
+        assert_eq!(mesh.vertices.len(), 4 * SEGMENT_VERTICES + 4 * 3);
+        assert_eq!(mesh.triangle_count(), 4 * SEGMENT_TRIANGLES + 4);
+    }
+
+    #[test]
+    fn caps() {
+        let line = [Vec2::ZERO, Vec2::new(10.0, 0.0)];
+        let butt = stroked(&line, false, &StrokeStyle::new(2.0, Colour::WHITE));
+        assert_eq!(butt.vertices.len(), SEGMENT_VERTICES);
+
+        // same quad, pushed out half a width at both ends
+        let style = StrokeStyle::new(2.0, Colour::WHITE).with_cap(LineCap::Square);
+        let square = stroked(&line, false, &style);
+        assert_eq!(square.vertices.len(), SEGMENT_VERTICES);
+        let xs = square.vertices.iter().map(|vertex| vertex.position.x);
+        let min_x = xs.clone().fold(f32::MAX, f32::min);
+        let max_x = xs.fold(f32::MIN, f32::max);
+        assert_eq!((min_x, max_x), (-1.0, 11.0));
+
+        let style = StrokeStyle::new(8.0, Colour::WHITE).with_cap(LineCap::Round);
+        let round = stroked(&line, false, &style);
+        let segments = arc_segments(4.0, std::f32::consts::PI, TOLERANCE);
+        assert_eq!(round.vertices.len(), SEGMENT_VERTICES + 2 * (segments + 2));
+        assert_eq!(round.triangle_count(), SEGMENT_TRIANGLES + 2 * segments);
+    }
+
+    #[test]
+    fn zero_length_lines() {
+        let point = [Vec2::ONE, Vec2::ONE];
+        let butt = stroked(&point, false, &StrokeStyle::new(2.0, Colour::WHITE));
+        assert_eq!(butt, Mesh::default());
+
+        let style = StrokeStyle::new(2.0, Colour::WHITE).with_cap(LineCap::Square);
+        let square = stroked(&point, false, &style);
+        assert_eq!(square.triangle_count(), 2);
+
+        let style = StrokeStyle::new(2.0, Colour::WHITE).with_cap(LineCap::Round);
+        let round = stroked(&point, false, &style);
+        assert!(round.triangle_count() >= 3);
+
+        let zero_width = stroked(&corner(), false, &StrokeStyle::new(0.0, Colour::WHITE));
+        assert_eq!(zero_width, Mesh::default());
+    }
+
+    #[test]
+    fn arc_segments_stay_in_range() {
+        assert_eq!(arc_segments(0.1, std::f32::consts::TAU, TOLERANCE), 4);
+        assert!(arc_segments(1.0e6, std::f32::consts::TAU, TOLERANCE) <= 256);
+        let coarse = arc_segments(10.0, std::f32::consts::PI, 1.0);
+        let fine = arc_segments(10.0, std::f32::consts::PI, 0.1);
+        assert!(fine > coarse);
+    }
+}
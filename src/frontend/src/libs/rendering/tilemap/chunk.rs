@@ -0,0 +1,196 @@
+use glam::Vec2;
+
+use crate::libs::rendering::tilemap::tiled::{AnimationFrame, TileLayer, TileMap, Tileset};
+use crate::log_warn;
+
+const LOG_TARGET: &str = "rendering::tilemap";
+
+// tiles per chunk side. a chunk is built once and culled as a whole
+pub const CHUNK_TILES: u32 = 16;
+// position (2), corner (2), tile, animation slot
+pub const VERTEX_FLOATS: usize = 6;
+pub const VERTEX_STRIDE: i32 = (VERTEX_FLOATS * 4) as i32;
+// animated tiles per tileset, must match MAX_ANIMATIONS in the tilemap shader
+pub const MAX_ANIMATIONS: usize = 32;
+
+#[derive(Debug, Clone)]
+struct AnimatedTile {
+    local_id: u32,
+    frames: Vec<AnimationFrame>,
+    total_ms: u32,
+}
+
+// the animated tiles of one tileset. geometry only stores the slot, the current frame of every
+// slot is uploaded as a uniform array, so animating never touches the chunk buffers
+#[derive(Debug, Clone, Default)]
+pub struct AnimationTable {
+    slots: Vec<AnimatedTile>,
+}
+
+impl AnimationTable {
+    pub fn new(tileset: &Tileset) -> Self {
+        let mut slots = Vec::new();
+        for (local_id, tile) in tileset.tiles.iter() {
+            if tile.animation.is_empty() {
+                continue;
+            }
+            if slots.len() == MAX_ANIMATIONS {
+                log_warn!(
+                    LOG_TARGET,
+                    "Tileset '{}' has more than {} animated tiles, the rest are static",
+                    tileset.name,
+                    MAX_ANIMATIONS
+                );
+                break;
+            }
+            slots.push(AnimatedTile {
+                local_id: *local_id,
+                total_ms: tile.animation.iter().map(|f| f.duration_ms).sum(),
+                frames: tile.animation.clone(),
+            });
+        }
+        Self { slots }
+    }
+
+    pub fn slot(&self, local_id: u32) -> Option<usize> {
+        self.slots.iter().position(|slot| slot.local_id == local_id)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    // writes the current tile of every slot into out
+    pub fn current_tiles(&self, time_ms: f64, out: &mut [f32; MAX_ANIMATIONS]) {
+        for (slot, out) in self.slots.iter().zip(out.iter_mut()) {
+            if slot.total_ms == 0 {
+                *out = slot.local_id as f32;
+                continue;
+            }
+            let mut t = (time_ms as u64 % slot.total_ms as u64) as u32;
+            for frame in slot.frames.iter() {
+                if t < frame.duration_ms {
+                    *out = frame.tile_id as f32;
+                    break;
+                }
+                t -= frame.duration_ms;
+            }
+        }
+    }
+}
+
+// geometry of one chunk for one tileset
+#[derive(Debug, Clone, Default)]
+pub struct ChunkGeometry {
+    pub tileset: usize,
+    pub vertices: Vec<f32>,
+    pub indices: Vec<u16>,
+}
+
+pub fn chunk_count(layer: &TileLayer) -> (u32, u32) {
+    (
+        layer.width.div_ceil(CHUNK_TILES),
+        layer.height.div_ceil(CHUNK_TILES),
+    )
+}
+
+// world space bounds (min, max) of a chunk, before parallax. tiles taller or wider than the grid
+// overhang their cell, so the bounds grow by the largest tileset
+pub fn chunk_bounds(map: &TileMap, layer: &TileLayer, chunk_x: u32, chunk_y: u32) -> (Vec2, Vec2) {
+    let tile = Vec2::new(map.tile_width as f32, map.tile_height as f32);
+    let overhang = map
+        .tilesets
+        .iter()
+        .map(|t| Vec2::new(t.tile_width as f32, t.tile_height as f32) - tile)
+        .fold(Vec2::ZERO, Vec2::max);
+    let top_left = Vec2::new(
+        (chunk_x * CHUNK_TILES) as f32 * tile.x,
+        (chunk_y * CHUNK_TILES) as f32 * tile.y,
+    ) + layer.offset;
+    let bottom_right = top_left + tile * CHUNK_TILES as f32;
+    // tiled space is y down
+    let min = TileMap::tiled_to_world(Vec2::new(top_left.x, bottom_right.y));
+    let max = TileMap::tiled_to_world(Vec2::new(bottom_right.x, top_left.y - overhang.y))
+        + Vec2::new(overhang.x, 0.0);
+    (min, max)
+}
+
+pub fn build_chunk(
+    map: &TileMap,
+    layer: &TileLayer,
+    animations: &[AnimationTable],
+    chunk_x: u32,
+    chunk_y: u32,
+) -> Vec<ChunkGeometry> {
+    let mut geometry: Vec<ChunkGeometry> = Vec::new();
+    let x_end = ((chunk_x + 1) * CHUNK_TILES).min(layer.width);
+    let y_end = ((chunk_y + 1) * CHUNK_TILES).min(layer.height);
+    for y in chunk_y * CHUNK_TILES..y_end {
+        for x in chunk_x * CHUNK_TILES..x_end {
+            let gid = layer.tile(x, y);
+            let (tileset_index, local_id) = if let Some(found) = map.tileset_for(gid) {
+                found
+            } else {
+                continue;
+            };
+            let tileset = &map.tilesets[tileset_index];
+            let slot = animations
+                .get(tileset_index)
+                .and_then(|table| table.slot(local_id))
+                .map(|slot| slot as f32)
+                .unwrap_or(-1.0);
+
+            let index = if let Some(index) = geometry.iter().position(|g| g.tileset == tileset_index)
+            {
+                index
+            } else {
+                geometry.push(ChunkGeometry {
+                    tileset: tileset_index,
+                    ..Default::default()
+                });
+                geometry.len() - 1
+            };
+            let out = &mut geometry[index];
+
+            // tiles are anchored to the bottom left of their cell, like in Tiled
+            let size = Vec2::new(tileset.tile_width as f32, tileset.tile_height as f32);
+            let left = (x * map.tile_width) as f32 + layer.offset.x;
+            let top = ((y + 1) * map.tile_height) as f32 - size.y + layer.offset.y;
+
+            let base = (out.vertices.len() / VERTEX_FLOATS) as u16;
+            for corner in [
+                Vec2::new(0.0, 0.0),
+                Vec2::new(1.0, 0.0),
+                Vec2::new(0.0, 1.0),
+                Vec2::new(1.0, 1.0),
+            ] {
+                let position = TileMap::tiled_to_world(Vec2::new(
+                    left + corner.x * size.x,
+                    top + corner.y * size.y,
+                ));
+                // Tiled applies the diagonal flip first, then horizontal and vertical
+                let mut uv = corner;
+                if gid.flipped_diagonally() {
+                    uv = Vec2::new(uv.y, uv.x);
+                }
+                if gid.flipped_horizontally() {
+                    uv.x = 1.0 - uv.x;
+                }
+                if gid.flipped_vertically() {
+                    uv.y = 1.0 - uv.y;
+                }
+                out.vertices.extend_from_slice(&[
+                    position.x,
+                    position.y,
+                    uv.x,
+                    uv.y,
+                    local_id as f32,
+                    slot,
+                ]);
+            }
+            out.indices
+                .extend_from_slice(&[base, base + 2, base + 1, base + 1, base + 2, base + 3]);
+        }
+    }
+    geometry
+}
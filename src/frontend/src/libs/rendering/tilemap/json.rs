@@ -0,0 +1,425 @@
+// Tiled JSON (.tmj / .tsj) import
+use std::collections::BTreeMap;
+
+use glam::Vec2;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::libs::rendering::tilemap::tiled::*;
+use crate::libs::types::errors::ErrorStr;
+
+fn default_one() -> f32 {
+    1.0
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Deserialize)]
+struct RawProperty {
+    name: String,
+    #[serde(rename = "type", default)]
+    kind: String,
+    value: Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawMap {
+    width: u32,
+    height: u32,
+    tilewidth: u32,
+    tileheight: u32,
+    #[serde(default)]
+    orientation: String,
+    #[serde(default)]
+    infinite: bool,
+    #[serde(default)]
+    layers: Vec<RawLayer>,
+    #[serde(default)]
+    tilesets: Vec<RawTileset>,
+    #[serde(default)]
+    properties: Vec<RawProperty>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawLayer {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    id: u32,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    width: u32,
+    #[serde(default)]
+    height: u32,
+    data: Option<Value>,
+    encoding: Option<String>,
+    compression: Option<String>,
+    #[serde(default)]
+    objects: Vec<RawObject>,
+    #[serde(default)]
+    layers: Vec<RawLayer>,
+    #[serde(default = "default_one")]
+    opacity: f32,
+    #[serde(default = "default_true")]
+    visible: bool,
+    #[serde(default)]
+    offsetx: f32,
+    #[serde(default)]
+    offsety: f32,
+    #[serde(default = "default_one")]
+    parallaxx: f32,
+    #[serde(default = "default_one")]
+    parallaxy: f32,
+    #[serde(default)]
+    properties: Vec<RawProperty>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawPoint {
+    x: f32,
+    y: f32,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawObject {
+    #[serde(default)]
+    id: u32,
+    #[serde(default)]
+    name: String,
+    // "type" before Tiled 1.9, "class" after
+    #[serde(rename = "type", default)]
+    kind: String,
+    #[serde(default)]
+    class: String,
+    #[serde(default)]
+    x: f32,
+    #[serde(default)]
+    y: f32,
+    #[serde(default)]
+    width: f32,
+    #[serde(default)]
+    height: f32,
+    #[serde(default)]
+    rotation: f32,
+    gid: Option<u32>,
+    #[serde(default)]
+    ellipse: bool,
+    #[serde(default)]
+    point: bool,
+    polygon: Option<Vec<RawPoint>>,
+    polyline: Option<Vec<RawPoint>>,
+    #[serde(default = "default_true")]
+    visible: bool,
+    #[serde(default)]
+    properties: Vec<RawProperty>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawFrame {
+    tileid: u32,
+    duration: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawTile {
+    id: u32,
+    #[serde(rename = "type", default)]
+    kind: String,
+    #[serde(default)]
+    class: String,
+    #[serde(default)]
+    animation: Vec<RawFrame>,
+    #[serde(default)]
+    properties: Vec<RawProperty>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawTileset {
+    #[serde(default)]
+    firstgid: u32,
+    source: Option<String>,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    tilewidth: u32,
+    #[serde(default)]
+    tileheight: u32,
+    #[serde(default)]
+    tilecount: u32,
+    #[serde(default)]
+    columns: u32,
+    #[serde(default)]
+    spacing: u32,
+    #[serde(default)]
+    margin: u32,
+    image: Option<String>,
+    #[serde(default)]
+    imagewidth: u32,
+    #[serde(default)]
+    imageheight: u32,
+    #[serde(default)]
+    tiles: Vec<RawTile>,
+    #[serde(default)]
+    properties: Vec<RawProperty>,
+}
+
+pub fn parse_map(json: &str) -> Result<TileMap, ErrorStr> {
+    let raw: RawMap = match serde_json::from_str(json) {
+        Ok(raw) => raw,
+        Err(error) => {
+            let error = format!("Unable to parse Tiled map: {}", error);
+            return Err(ErrorStr::new(error));
+        }
+    };
+    if !raw.orientation.is_empty() && raw.orientation != "orthogonal" {
+        let error = format!(
+            "Only orthogonal maps are supported, map is {}",
+            raw.orientation
+        );
+        return Err(ErrorStr::new(error));
+    }
+    if raw.infinite {
+        return Err(ErrorStr::new(
+            "Infinite maps are not supported, disable 'Infinite' in the map properties",
+        ));
+    }
+
+    let mut tilesets = Vec::new();
+    let mut external_tilesets = Vec::new();
+    for raw_tileset in raw.tilesets {
+        if let Some(source) = raw_tileset.source {
+            external_tilesets.push(ExternalTileset {
+                first_gid: raw_tileset.firstgid,
+                source,
+            });
+        } else {
+            let first_gid = raw_tileset.firstgid;
+            tilesets.push(convert_tileset(raw_tileset, first_gid)?);
+        }
+    }
+    tilesets.sort_by_key(|tileset: &Tileset| tileset.first_gid);
+
+    let mut layers = Vec::new();
+    for raw_layer in raw.layers {
+        convert_layer(raw_layer, GroupState::default(), &mut layers)?;
+    }
+
+    Ok(TileMap {
+        width: raw.width,
+        height: raw.height,
+        tile_width: raw.tilewidth,
+        tile_height: raw.tileheight,
+        layers,
+        tilesets,
+        external_tilesets,
+        properties: convert_properties(raw.properties)?,
+    })
+}
+
+// an external .tsj tileset, first_gid comes from the map that references it
+pub fn parse_tileset(json: &str, first_gid: u32) -> Result<Tileset, ErrorStr> {
+    let raw: RawTileset = match serde_json::from_str(json) {
+        Ok(raw) => raw,
+        Err(error) => {
+            let error = format!("Unable to parse Tiled tileset: {}", error);
+            return Err(ErrorStr::new(error));
+        }
+    };
+    convert_tileset(raw, first_gid)
+}
+
+fn convert_properties(raw: Vec<RawProperty>) -> Result<Properties, ErrorStr> {
+    let mut properties = BTreeMap::new();
+    for property in raw {
+        let value = match &property.value {
+            Value::String(value) => PropertyValue::parse(&property.kind, value)?,
+            Value::Bool(value) => PropertyValue::Bool(*value),
+            Value::Number(number) => {
+                if property.kind == "float" {
+                    PropertyValue::Float(number.as_f64().unwrap_or_default())
+                } else if property.kind == "object" {
+                    PropertyValue::Object(number.as_u64().unwrap_or_default() as u32)
+                } else if let Some(value) = number.as_i64() {
+                    PropertyValue::Int(value)
+                } else {
+                    PropertyValue::Float(number.as_f64().unwrap_or_default())
+                }
+            }
+            other => {
+                let error = format!(
+                    "Unsupported value {} for property '{}'",
+                    other, property.name
+                );
+                return Err(ErrorStr::new(error));
+            }
+        };
+        properties.insert(property.name, value);
+    }
+    Ok(properties)
+}
+
+fn convert_tileset(raw: RawTileset, first_gid: u32) -> Result<Tileset, ErrorStr> {
+    let image = if let Some(image) = raw.image {
+        image
+    } else {
+        let error = format!(
+            "Tileset '{}' is an image collection, only single image tilesets are supported",
+            raw.name
+        );
+        return Err(ErrorStr::new(error));
+    };
+    let mut tiles = BTreeMap::new();
+    for tile in raw.tiles {
+        let class = if tile.class.is_empty() {
+            tile.kind
+        } else {
+            tile.class
+        };
+        tiles.insert(
+            tile.id,
+            TileData {
+                class,
+                animation: tile
+                    .animation
+                    .iter()
+                    .map(|frame| AnimationFrame {
+                        tile_id: frame.tileid,
+                        duration_ms: frame.duration,
+                    })
+                    .collect(),
+                properties: convert_properties(tile.properties)?,
+            },
+        );
+    }
+    Ok(Tileset {
+        first_gid,
+        name: raw.name,
+        tile_width: raw.tilewidth,
+        tile_height: raw.tileheight,
+        tile_count: raw.tilecount,
+        columns: raw.columns,
+        spacing: raw.spacing,
+        margin: raw.margin,
+        image,
+        image_width: raw.imagewidth,
+        image_height: raw.imageheight,
+        tiles,
+        properties: convert_properties(raw.properties)?,
+    })
+}
+
+fn convert_layer(raw: RawLayer, group: GroupState, out: &mut Vec<Layer>) -> Result<(), ErrorStr> {
+    let state = group.child(
+        Vec2::new(raw.offsetx, raw.offsety),
+        Vec2::new(raw.parallaxx, raw.parallaxy),
+        raw.opacity,
+        raw.visible,
+    );
+    match raw.kind.as_str() {
+        "tilelayer" => {
+            let expected = (raw.width * raw.height) as usize;
+            let data = match &raw.data {
+                Some(Value::Array(values)) => {
+                    let mut data = Vec::with_capacity(values.len());
+                    for value in values {
+                        data.push(Gid(value.as_u64().unwrap_or_default() as u32));
+                    }
+                    if data.len() != expected {
+                        let error = format!(
+                            "Layer '{}' has {} tiles, expected {}",
+                            raw.name,
+                            data.len(),
+                            expected
+                        );
+                        return Err(ErrorStr::new(error));
+                    }
+                    data
+                }
+                Some(Value::String(encoded)) => decode_layer_data(
+                    encoded,
+                    raw.encoding.as_deref(),
+                    raw.compression.as_deref(),
+                    expected,
+                    &raw.name,
+                )?,
+                _ => {
+                    let error = format!("Tile layer '{}' has no data", raw.name);
+                    return Err(ErrorStr::new(error));
+                }
+            };
+            out.push(Layer::Tiles(TileLayer {
+                id: raw.id,
+                name: raw.name,
+                width: raw.width,
+                height: raw.height,
+                data,
+                offset: state.offset,
+                parallax: state.parallax,
+                opacity: state.opacity,
+                visible: state.visible,
+                properties: convert_properties(raw.properties)?,
+            }));
+        }
+        "objectgroup" => {
+            let mut objects = Vec::with_capacity(raw.objects.len());
+            for object in raw.objects {
+                objects.push(convert_object(object)?);
+            }
+            out.push(Layer::Objects(ObjectLayer {
+                id: raw.id,
+                name: raw.name,
+                objects,
+                offset: state.offset,
+                parallax: state.parallax,
+                opacity: state.opacity,
+                visible: state.visible,
+                properties: convert_properties(raw.properties)?,
+            }));
+        }
+        "group" => {
+            for child in raw.layers {
+                convert_layer(child, state, out)?;
+            }
+        }
+        // image layers are skipped, nothing in the game uses them
+        _ => {}
+    }
+    Ok(())
+}
+
+fn convert_object(raw: RawObject) -> Result<MapObject, ErrorStr> {
+    let points = |points: Vec<RawPoint>| -> Vec<Vec2> {
+        points.iter().map(|p| Vec2::new(p.x, p.y)).collect()
+    };
+    let shape = if let Some(gid) = raw.gid {
+        ObjectShape::Tile(Gid(gid))
+    } else if let Some(polygon) = raw.polygon {
+        ObjectShape::Polygon(points(polygon))
+    } else if let Some(polyline) = raw.polyline {
+        ObjectShape::Polyline(points(polyline))
+    } else if raw.ellipse {
+        ObjectShape::Ellipse
+    } else if raw.point {
+        ObjectShape::Point
+    } else {
+        ObjectShape::Rectangle
+    };
+    Ok(MapObject {
+        id: raw.id,
+        name: raw.name,
+        class: if raw.class.is_empty() {
+            raw.kind
+        } else {
+            raw.class
+        },
+        position: Vec2::new(raw.x, raw.y),
+        size: Vec2::new(raw.width, raw.height),
+        rotation: raw.rotation,
+        shape,
+        visible: raw.visible,
+        properties: convert_properties(raw.properties)?,
+    })
+}
@@ -0,0 +1,362 @@
+pub mod chunk;
+pub mod json;
+pub mod tiled;
+pub mod tmx;
+
+use glam::Vec2;
+use web_sys::{HtmlImageElement, WebGl2RenderingContext, WebGlProgram, WebGlUniformLocation};
+
+use crate::libs::profiler::counters::{record_draw, record_state_change};
+use crate::libs::rendering::assets::{fetch_text, load_image};
+use crate::libs::rendering::camera::Camera;
+use crate::libs::rendering::gl::buffer::{Buffer, BufferTarget, BufferUsage};
+use crate::libs::rendering::gl::program::{link_program_sources, uniform_location};
+use crate::libs::rendering::gl::texture::{Texture, TextureFilter};
+use crate::libs::rendering::gl::vao::VertexArray;
+use crate::libs::rendering::shaders::tilemap as tilemap_shader;
+use crate::libs::rendering::tilemap::chunk::*;
+use crate::libs::rendering::tilemap::tiled::{Layer, TileMap, resolve_relative};
+use crate::libs::types::errors::ErrorStr;
+
+// a parsed map with the image of every tileset, in tileset order
+#[derive(Debug)]
+pub struct LoadedTileMap {
+    pub map: TileMap,
+    pub images: Vec<HtmlImageElement>,
+}
+
+fn is_xml(url: &str) -> bool {
+    url.ends_with(".tmx") || url.ends_with(".tsx") || url.ends_with(".xml")
+}
+
+// loads a .tmj/.json or .tmx map, its external tilesets and their images. image paths in the
+// returned map are resolved against the map url
+pub async fn load_tile_map(url: &str) -> Result<LoadedTileMap, ErrorStr> {
+    let text = fetch_text(url).await?;
+    let mut map = if is_xml(url) {
+        tmx::parse_map(&text)?
+    } else {
+        json::parse_map(&text)?
+    };
+    for tileset in map.tilesets.iter_mut() {
+        tileset.image = resolve_relative(url, &tileset.image);
+    }
+    for external in map.external_tilesets.clone() {
+        let source = resolve_relative(url, &external.source);
+        let text = fetch_text(&source).await?;
+        let mut tileset = if is_xml(&source) {
+            tmx::parse_tileset(&text, external.first_gid)?
+        } else {
+            json::parse_tileset(&text, external.first_gid)?
+        };
+        tileset.image = resolve_relative(&source, &tileset.image);
+        map.add_tileset(tileset);
+    }
+    let mut images = Vec::with_capacity(map.tilesets.len());
+    for tileset in map.tilesets.iter() {
+        images.push(load_image(&tileset.image).await?);
+    }
+    Ok(LoadedTileMap { map, images })
+}
+
+#[derive(Debug)]
+struct TilemapProgram {
+    program: WebGlProgram,
+    view_projection: WebGlUniformLocation,
+    layer_offset: WebGlUniformLocation,
+    columns: WebGlUniformLocation,
+    tile_size: WebGlUniformLocation,
+    margin: WebGlUniformLocation,
+    spacing: WebGlUniformLocation,
+    image_size: WebGlUniformLocation,
+    animation_tiles: WebGlUniformLocation,
+    tileset: WebGlUniformLocation,
+    opacity: WebGlUniformLocation,
+}
+
+impl TilemapProgram {
+    fn new(gl: &WebGl2RenderingContext) -> Result<Self, ErrorStr> {
+        let name = "Tilemap";
+        let program = link_program_sources(
+            gl,
+            tilemap_shader::VERT_SHADER,
+            tilemap_shader::FRAG_SHADER,
+            &[],
+            name,
+        )?;
+        Ok(Self {
+            view_projection: uniform_location(gl, &program, "uViewProjection", name)?,
+            layer_offset: uniform_location(gl, &program, "uLayerOffset", name)?,
+            columns: uniform_location(gl, &program, "uColumns", name)?,
+            tile_size: uniform_location(gl, &program, "uTileSize", name)?,
+            margin: uniform_location(gl, &program, "uMargin", name)?,
+            spacing: uniform_location(gl, &program, "uSpacing", name)?,
+            image_size: uniform_location(gl, &program, "uImageSize", name)?,
+            animation_tiles: uniform_location(gl, &program, "uAnimationTiles", name)?,
+            tileset: uniform_location(gl, &program, "uTileset", name)?,
+            opacity: uniform_location(gl, &program, "uOpacity", name)?,
+            program,
+        })
+    }
+}
+
+#[derive(Debug)]
+struct TilesetGpu {
+    texture: Texture,
+    columns: f32,
+    tile_size: Vec2,
+    margin: f32,
+    spacing: f32,
+    image_size: Vec2,
+    animations: AnimationTable,
+    current_tiles: [f32; MAX_ANIMATIONS],
+}
+
+#[derive(Debug)]
+struct ChunkMesh {
+    tileset: usize,
+    vao: VertexArray,
+    vertices: Buffer,
+    indices: Buffer,
+    index_count: i32,
+    min: Vec2,
+    max: Vec2,
+}
+
+#[derive(Debug)]
+struct LayerGpu {
+    name: String,
+    parallax: Vec2,
+    opacity: f32,
+    visible: bool,
+    chunks: Vec<ChunkMesh>,
+}
+
+// static chunk geometry for every tile layer of a map. object layers are left to the game
+#[derive(Debug)]
+pub struct TileMapRenderer {
+    program: TilemapProgram,
+    tilesets: Vec<TilesetGpu>,
+    layers: Vec<LayerGpu>,
+    time_ms: f64,
+    drawn_chunks: usize,
+}
+
+impl TileMapRenderer {
+    pub fn new(gl: &WebGl2RenderingContext, loaded: &LoadedTileMap) -> Result<Self, ErrorStr> {
+        let map = &loaded.map;
+        if !map.external_tilesets.is_empty() {
+            return Err(ErrorStr::new(
+                "Tile map still has unresolved external tilesets, load it with load_tile_map",
+            ));
+        }
+        let program = TilemapProgram::new(gl)?;
+
+        let mut tilesets = Vec::with_capacity(map.tilesets.len());
+        for (tileset, image) in map.tilesets.iter().zip(loaded.images.iter()) {
+            let texture = Texture::from_image(gl, image, TextureFilter::Nearest, &tileset.name)?;
+            let image_size = Vec2::new(texture.width() as f32, texture.height() as f32);
+            tilesets.push(TilesetGpu {
+                columns: tileset.columns.max(1) as f32,
+                tile_size: Vec2::new(tileset.tile_width as f32, tileset.tile_height as f32),
+                margin: tileset.margin as f32,
+                spacing: tileset.spacing as f32,
+                image_size,
+                animations: AnimationTable::new(tileset),
+                current_tiles: [0.0; MAX_ANIMATIONS],
+                texture,
+            });
+        }
+        if tilesets.len() != map.tilesets.len() {
+            let error = format!(
+                "Tile map has {} tilesets but {} images",
+                map.tilesets.len(),
+                loaded.images.len()
+            );
+            return Err(ErrorStr::new(error));
+        }
+        let animations: Vec<AnimationTable> =
+            tilesets.iter().map(|t| t.animations.clone()).collect();
+
+        let mut layers = Vec::new();
+        for layer in map.layers.iter() {
+            let layer = if let Layer::Tiles(layer) = layer {
+                layer
+            } else {
+                continue;
+            };
+            let (chunks_x, chunks_y) = chunk_count(layer);
+            let mut chunks = Vec::new();
+            for chunk_y in 0..chunks_y {
+                for chunk_x in 0..chunks_x {
+                    let (min, max) = chunk_bounds(map, layer, chunk_x, chunk_y);
+                    for geometry in build_chunk(map, layer, &animations, chunk_x, chunk_y) {
+                        let name = format!("{} chunk {},{}", layer.name, chunk_x, chunk_y);
+                        chunks.push(upload_chunk(gl, &geometry, &name, min, max)?);
+                    }
+                }
+            }
+            layers.push(LayerGpu {
+                name: layer.name.clone(),
+                parallax: layer.parallax,
+                opacity: layer.opacity,
+                visible: layer.visible,
+                chunks,
+            });
+        }
+
+        Ok(Self {
+            program,
+            tilesets,
+            layers,
+            time_ms: 0.0,
+            drawn_chunks: 0,
+        })
+    }
+
+    // advances tile animations, delta in milliseconds
+    pub fn update(&mut self, delta: f64) {
+        self.time_ms += delta;
+        for tileset in self.tilesets.iter_mut() {
+            if !tileset.animations.is_empty() {
+                tileset
+                    .animations
+                    .current_tiles(self.time_ms, &mut tileset.current_tiles);
+            }
+        }
+    }
+
+    pub fn set_layer_visible(&mut self, name: &str, visible: bool) {
+        for layer in self.layers.iter_mut().filter(|layer| layer.name == name) {
+            layer.visible = visible;
+        }
+    }
+
+    pub fn draw(&mut self, gl: &WebGl2RenderingContext, camera: &Camera) {
+        let program = &self.program;
+        gl.use_program(Some(&program.program));
+        record_state_change();
+        gl.uniform_matrix4fv_with_f32_array(
+            Some(&program.view_projection),
+            false,
+            &camera.view_projection().to_cols_array(),
+        );
+        gl.uniform1i(Some(&program.tileset), 0);
+        gl.enable(WebGl2RenderingContext::BLEND);
+        gl.blend_func(
+            WebGl2RenderingContext::SRC_ALPHA,
+            WebGl2RenderingContext::ONE_MINUS_SRC_ALPHA,
+        );
+
+        let (view_min, view_max) = camera.visible_rect();
+        let camera_center = camera.target.truncate();
+        let mut bound_tileset = None;
+        self.drawn_chunks = 0;
+        for layer in self.layers.iter().filter(|layer| layer.visible) {
+            // Tiled parallax: a factor of 0 stays fixed to the screen, 1 moves with the world
+            let offset = camera_center * (Vec2::ONE - layer.parallax);
+            gl.uniform2f(Some(&program.layer_offset), offset.x, offset.y);
+            gl.uniform1f(Some(&program.opacity), layer.opacity);
+            for chunk in layer.chunks.iter() {
+                let min = chunk.min + offset;
+                let max = chunk.max + offset;
+                if max.x < view_min.x || min.x > view_max.x || max.y < view_min.y || min.y > view_max.y
+                {
+                    continue;
+                }
+                if bound_tileset != Some(chunk.tileset) {
+                    let tileset = &self.tilesets[chunk.tileset];
+                    tileset.texture.bind(gl, 0);
+                    gl.uniform1f(Some(&program.columns), tileset.columns);
+                    gl.uniform2f(
+                        Some(&program.tile_size),
+                        tileset.tile_size.x,
+                        tileset.tile_size.y,
+                    );
+                    gl.uniform1f(Some(&program.margin), tileset.margin);
+                    gl.uniform1f(Some(&program.spacing), tileset.spacing);
+                    gl.uniform2f(
+                        Some(&program.image_size),
+                        tileset.image_size.x,
+                        tileset.image_size.y,
+                    );
+                    gl.uniform1fv_with_f32_array(
+                        Some(&program.animation_tiles),
+                        &tileset.current_tiles,
+                    );
+                    bound_tileset = Some(chunk.tileset);
+                }
+                chunk.vao.bind(gl);
+                gl.draw_elements_with_i32(
+                    WebGl2RenderingContext::TRIANGLES,
+                    chunk.index_count,
+                    WebGl2RenderingContext::UNSIGNED_SHORT,
+                    0,
+                );
+                record_draw(WebGl2RenderingContext::TRIANGLES, chunk.index_count, 1);
+                self.drawn_chunks += 1;
+            }
+        }
+        gl.bind_vertex_array(None);
+        gl.disable(WebGl2RenderingContext::BLEND);
+    }
+
+    // chunks that passed culling in the last draw
+    #[inline]
+    pub fn drawn_chunks(&self) -> usize {
+        self.drawn_chunks
+    }
+
+    pub fn delete(&self, gl: &WebGl2RenderingContext) {
+        for tileset in self.tilesets.iter() {
+            tileset.texture.delete(gl);
+        }
+        for chunk in self.layers.iter().flat_map(|layer| layer.chunks.iter()) {
+            chunk.vao.delete(gl);
+            chunk.vertices.delete(gl);
+            chunk.indices.delete(gl);
+        }
+        gl.delete_program(Some(&self.program.program));
+    }
+}
+
+fn upload_chunk(
+    gl: &WebGl2RenderingContext,
+    geometry: &ChunkGeometry,
+    name: &str,
+    min: Vec2,
+    max: Vec2,
+) -> Result<ChunkMesh, ErrorStr> {
+    let vao = VertexArray::new(gl, name)?;
+    vao.bind(gl);
+    let mut vertices = Buffer::new(
+        gl,
+        BufferTarget::Array,
+        BufferUsage::StaticDraw,
+        format!("{} vertices", name),
+    )?;
+    vertices.upload_f32(gl, &geometry.vertices);
+    vao.attrib_f32(gl, 0, 2, VERTEX_STRIDE, 0, 0);
+    vao.attrib_f32(gl, 1, 2, VERTEX_STRIDE, 8, 0);
+    vao.attrib_f32(gl, 2, 1, VERTEX_STRIDE, 16, 0);
+    vao.attrib_f32(gl, 3, 1, VERTEX_STRIDE, 20, 0);
+    // the element buffer binding is part of the vertex array state
+    let mut indices = Buffer::new(
+        gl,
+        BufferTarget::ElementArray,
+        BufferUsage::StaticDraw,
+        format!("{} indices", name),
+    )?;
+    indices.upload_u16(gl, &geometry.indices);
+    vao.unbind(gl);
+    vertices.unbind(gl);
+    Ok(ChunkMesh {
+        tileset: geometry.tileset,
+        vao,
+        vertices,
+        indices,
+        index_count: geometry.indices.len() as i32,
+        min,
+        max,
+    })
+}
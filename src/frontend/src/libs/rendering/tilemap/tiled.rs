@@ -0,0 +1,463 @@
+use std::collections::BTreeMap;
+
+use glam::Vec2;
+
+use crate::libs::types::errors::ErrorStr;
+
+// Tiled stores flips in the top bits of each global tile id
+pub const FLIPPED_HORIZONTALLY: u32 = 0x8000_0000;
+pub const FLIPPED_VERTICALLY: u32 = 0x4000_0000;
+pub const FLIPPED_DIAGONALLY: u32 = 0x2000_0000;
+const FLIP_MASK: u32 = FLIPPED_HORIZONTALLY | FLIPPED_VERTICALLY | FLIPPED_DIAGONALLY;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PropertyValue {
+    String(String),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    // #AARRGGBB, as written by Tiled
+    Colour(String),
+    File(String),
+    // id of an object on the map
+    Object(u32),
+}
+
+pub type Properties = BTreeMap<String, PropertyValue>;
+
+impl PropertyValue {
+    // Tiled writes every value as a string in TMX and as typed JSON in .tmj, both go through here
+    pub fn parse(kind: &str, value: &str) -> Result<Self, ErrorStr> {
+        let parsed = match kind {
+            "" | "string" => Some(PropertyValue::String(value.to_string())),
+            "int" => value.parse().ok().map(PropertyValue::Int),
+            "float" => value.parse().ok().map(PropertyValue::Float),
+            "bool" => value.parse().ok().map(PropertyValue::Bool),
+            "color" => Some(PropertyValue::Colour(value.to_string())),
+            "file" => Some(PropertyValue::File(value.to_string())),
+            "object" => value.parse().ok().map(PropertyValue::Object),
+            _ => None,
+        };
+        parsed.ok_or_else(|| {
+            ErrorStr::new(format!(
+                "Unable to parse property value '{}' of type '{}'",
+                value, kind
+            ))
+        })
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            PropertyValue::String(value)
+            | PropertyValue::Colour(value)
+            | PropertyValue::File(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            PropertyValue::Int(value) => Some(*value as f64),
+            PropertyValue::Float(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            PropertyValue::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Gid(pub u32);
+
+impl Gid {
+    pub const EMPTY: Gid = Gid(0);
+
+    #[inline]
+    pub fn id(&self) -> u32 {
+        self.0 & !FLIP_MASK
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.id() == 0
+    }
+
+    #[inline]
+    pub fn flipped_horizontally(&self) -> bool {
+        self.0 & FLIPPED_HORIZONTALLY != 0
+    }
+
+    #[inline]
+    pub fn flipped_vertically(&self) -> bool {
+        self.0 & FLIPPED_VERTICALLY != 0
+    }
+
+    #[inline]
+    pub fn flipped_diagonally(&self) -> bool {
+        self.0 & FLIPPED_DIAGONALLY != 0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AnimationFrame {
+    pub tile_id: u32,
+    pub duration_ms: u32,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TileData {
+    pub class: String,
+    pub animation: Vec<AnimationFrame>,
+    pub properties: Properties,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tileset {
+    pub first_gid: u32,
+    pub name: String,
+    pub tile_width: u32,
+    pub tile_height: u32,
+    pub tile_count: u32,
+    pub columns: u32,
+    pub spacing: u32,
+    pub margin: u32,
+    // relative to the file the tileset was defined in
+    pub image: String,
+    pub image_width: u32,
+    pub image_height: u32,
+    // only tiles with animations, properties or a class are listed
+    pub tiles: BTreeMap<u32, TileData>,
+    pub properties: Properties,
+}
+
+impl Tileset {
+    #[inline]
+    pub fn contains(&self, gid: u32) -> bool {
+        gid >= self.first_gid && gid < self.first_gid + self.tile_count
+    }
+
+    // pixel rectangle (min, max) of a tile in the tileset image
+    pub fn tile_rect(&self, local_id: u32) -> (Vec2, Vec2) {
+        let columns = self.columns.max(1);
+        let column = local_id % columns;
+        let row = local_id / columns;
+        let min = Vec2::new(
+            (self.margin + column * (self.tile_width + self.spacing)) as f32,
+            (self.margin + row * (self.tile_height + self.spacing)) as f32,
+        );
+        (
+            min,
+            min + Vec2::new(self.tile_width as f32, self.tile_height as f32),
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TileLayer {
+    pub id: u32,
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    // row major, width * height entries
+    pub data: Vec<Gid>,
+    pub offset: Vec2,
+    pub parallax: Vec2,
+    pub opacity: f32,
+    pub visible: bool,
+    pub properties: Properties,
+}
+
+impl TileLayer {
+    pub fn tile(&self, x: u32, y: u32) -> Gid {
+        if x >= self.width || y >= self.height {
+            return Gid::EMPTY;
+        }
+        self.data
+            .get((y * self.width + x) as usize)
+            .copied()
+            .unwrap_or(Gid::EMPTY)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ObjectShape {
+    Rectangle,
+    Ellipse,
+    Point,
+    // points are relative to the object position
+    Polygon(Vec<Vec2>),
+    Polyline(Vec<Vec2>),
+    // a tile object, drawn with the given tile
+    Tile(Gid),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MapObject {
+    pub id: u32,
+    pub name: String,
+    pub class: String,
+    // in Tiled pixel coordinates, origin top left, y down
+    pub position: Vec2,
+    pub size: Vec2,
+    // degrees clockwise
+    pub rotation: f32,
+    pub shape: ObjectShape,
+    pub visible: bool,
+    pub properties: Properties,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectLayer {
+    pub id: u32,
+    pub name: String,
+    pub objects: Vec<MapObject>,
+    pub offset: Vec2,
+    pub parallax: Vec2,
+    pub opacity: f32,
+    pub visible: bool,
+    pub properties: Properties,
+}
+
+// group layers are flattened on import, their offsets, parallax and opacity folded into children
+#[derive(Debug, Clone, PartialEq)]
+pub enum Layer {
+    Tiles(TileLayer),
+    Objects(ObjectLayer),
+}
+
+impl Layer {
+    pub fn name(&self) -> &str {
+        match self {
+            Layer::Tiles(layer) => &layer.name,
+            Layer::Objects(layer) => &layer.name,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExternalTileset {
+    pub first_gid: u32,
+    // relative to the map file
+    pub source: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TileMap {
+    pub width: u32,
+    pub height: u32,
+    pub tile_width: u32,
+    pub tile_height: u32,
+    pub layers: Vec<Layer>,
+    // sorted by first_gid
+    pub tilesets: Vec<Tileset>,
+    // tilesets stored in their own .tsx/.tsj file, resolved by the loader
+    pub external_tilesets: Vec<ExternalTileset>,
+    pub properties: Properties,
+}
+
+impl TileMap {
+    // returns (index into tilesets, local tile id)
+    pub fn tileset_for(&self, gid: Gid) -> Option<(usize, u32)> {
+        let id = gid.id();
+        if id == 0 {
+            return None;
+        }
+        let index = self
+            .tilesets
+            .iter()
+            .rposition(|tileset| tileset.first_gid <= id)?;
+        let tileset = &self.tilesets[index];
+        if tileset.contains(id) {
+            Some((index, id - tileset.first_gid))
+        } else {
+            None
+        }
+    }
+
+    pub fn add_tileset(&mut self, tileset: Tileset) {
+        self.external_tilesets
+            .retain(|external| external.first_gid != tileset.first_gid);
+        let index = self
+            .tilesets
+            .partition_point(|existing| existing.first_gid < tileset.first_gid);
+        self.tilesets.insert(index, tileset);
+    }
+
+    pub fn layer(&self, name: &str) -> Option<&Layer> {
+        self.layers.iter().find(|layer| layer.name() == name)
+    }
+
+    // Tiled is y down with the origin top left, the renderer is y up with the map's top left
+    // corner at the world origin
+    #[inline]
+    pub fn tiled_to_world(position: Vec2) -> Vec2 {
+        Vec2::new(position.x, -position.y)
+    }
+
+    pub fn tile_to_world(&self, x: u32, y: u32) -> Vec2 {
+        Self::tiled_to_world(Vec2::new(
+            (x * self.tile_width) as f32,
+            (y * self.tile_height) as f32,
+        ))
+    }
+
+    pub fn pixel_size(&self) -> Vec2 {
+        Vec2::new(
+            (self.width * self.tile_width) as f32,
+            (self.height * self.tile_height) as f32,
+        )
+    }
+}
+
+// accumulated state of the group layers above a layer
+#[derive(Debug, Clone, Copy)]
+pub struct GroupState {
+    pub offset: Vec2,
+    pub parallax: Vec2,
+    pub opacity: f32,
+    pub visible: bool,
+}
+
+impl Default for GroupState {
+    fn default() -> Self {
+        Self {
+            offset: Vec2::ZERO,
+            parallax: Vec2::ONE,
+            opacity: 1.0,
+            visible: true,
+        }
+    }
+}
+
+impl GroupState {
+    pub fn child(&self, offset: Vec2, parallax: Vec2, opacity: f32, visible: bool) -> Self {
+        Self {
+            offset: self.offset + offset,
+            parallax: self.parallax * parallax,
+            opacity: self.opacity * opacity,
+            visible: self.visible && visible,
+        }
+    }
+}
+
+// tile data as either csv or uncompressed base64. compressed layers need a
+// decompressor we don't ship, so Tiled has to export them uncompressed
+pub fn decode_layer_data(
+    data: &str,
+    encoding: Option<&str>,
+    compression: Option<&str>,
+    expected: usize,
+    layer: &str,
+) -> Result<Vec<Gid>, ErrorStr> {
+    if let Some(compression) = compression.filter(|c| !c.is_empty()) {
+        let error = format!(
+            "Layer '{}' uses {} compression, export it as CSV or uncompressed base64",
+            layer, compression
+        );
+        return Err(ErrorStr::new(error));
+    }
+    let gids = match encoding {
+        Some("csv") | None => {
+            let mut gids = Vec::with_capacity(expected);
+            for value in data.split(',').map(str::trim).filter(|v| !v.is_empty()) {
+                match value.parse::<u32>() {
+                    Ok(gid) => gids.push(Gid(gid)),
+                    Err(_) => {
+                        let error =
+                            format!("Invalid tile '{}' in csv data of layer '{}'", value, layer);
+                        return Err(ErrorStr::new(error));
+                    }
+                }
+            }
+            gids
+        }
+        Some("base64") => {
+            let bytes = decode_base64(data.trim())?;
+            bytes
+                .chunks_exact(4)
+                .map(|b| Gid(u32::from_le_bytes([b[0], b[1], b[2], b[3]])))
+                .collect()
+        }
+        Some(encoding) => {
+            let error = format!("Layer '{}' has unknown encoding '{}'", layer, encoding);
+            return Err(ErrorStr::new(error));
+        }
+    };
+    if gids.len() != expected {
+        let error = format!(
+            "Layer '{}' has {} tiles, expected {}",
+            layer,
+            gids.len(),
+            expected
+        );
+        return Err(ErrorStr::new(error));
+    }
+    Ok(gids)
+}
+
+pub fn decode_base64(input: &str) -> Result<Vec<u8>, ErrorStr> {
+    fn value(c: u8) -> Option<u32> {
+        match c {
+            b'A'..=b'Z' => Some((c - b'A') as u32),
+            b'a'..=b'z' => Some((c - b'a') as u32 + 26),
+            b'0'..=b'9' => Some((c - b'0') as u32 + 52),
+            b'+' => Some(62),
+            b'/' => Some(63),
+            _ => None,
+        }
+    }
+    let mut out = Vec::with_capacity(input.len() / 4 * 3);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for c in input.bytes() {
+        if c == b'=' {
+            break;
+        }
+        if c.is_ascii_whitespace() {
+            continue;
+        }
+        let v = if let Some(v) = value(c) {
+            v
+        } else {
+            let error = format!("Invalid base64 character '{}'", c as char);
+            return Err(ErrorStr::new(error));
+        };
+        buffer = (buffer << 6) | v;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    Ok(out)
+}
+
+// resolves a path relative to the file it was referenced from, e.g. a tileset image
+pub fn resolve_relative(base: &str, path: &str) -> String {
+    if path.starts_with('/') || path.contains("://") {
+        return path.to_string();
+    }
+    let mut parts: Vec<&str> = base.split('/').collect();
+    // drop the file name
+    parts.pop();
+    for part in path.split('/') {
+        match part {
+            "." | "" => {}
+            ".." => {
+                if parts.last().is_some_and(|last| !last.is_empty() && *last != "..") {
+                    parts.pop();
+                } else {
+                    parts.push("..");
+                }
+            }
+            part => parts.push(part),
+        }
+    }
+    parts.join("/")
+}
@@ -0,0 +1,323 @@
+// Tiled XML (.tmx / .tsx) import
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use glam::Vec2;
+use roxmltree::{Document, Node};
+
+use crate::libs::rendering::tilemap::tiled::*;
+use crate::libs::types::errors::ErrorStr;
+
+fn attribute<T: FromStr>(node: &Node, name: &str, default: T) -> Result<T, ErrorStr> {
+    match node.attribute(name) {
+        Some(value) => value.parse().map_err(|_| {
+            ErrorStr::new(format!(
+                "Invalid value '{}' for attribute '{}' on <{}>",
+                value,
+                name,
+                node.tag_name().name()
+            ))
+        }),
+        None => Ok(default),
+    }
+}
+
+fn required<T: FromStr>(node: &Node, name: &str) -> Result<T, ErrorStr> {
+    if let Some(value) = node.attribute(name) {
+        value.parse().map_err(|_| {
+            ErrorStr::new(format!(
+                "Invalid value '{}' for attribute '{}' on <{}>",
+                value,
+                name,
+                node.tag_name().name()
+            ))
+        })
+    } else {
+        let error = format!(
+            "Missing attribute '{}' on <{}>",
+            name,
+            node.tag_name().name()
+        );
+        Err(ErrorStr::new(error))
+    }
+}
+
+fn child<'a, 'input>(node: &Node<'a, 'input>, tag: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|c| c.has_tag_name(tag))
+}
+
+fn parse_document(xml: &str) -> Result<Document<'_>, ErrorStr> {
+    Document::parse(xml).map_err(|error| ErrorStr::new(format!("Unable to parse TMX: {}", error)))
+}
+
+pub fn parse_map(xml: &str) -> Result<TileMap, ErrorStr> {
+    let document = parse_document(xml)?;
+    let map = document.root_element();
+    if !map.has_tag_name("map") {
+        return Err(ErrorStr::new("TMX root element is not <map>"));
+    }
+    let orientation = map.attribute("orientation").unwrap_or("orthogonal");
+    if orientation != "orthogonal" {
+        let error = format!("Only orthogonal maps are supported, map is {}", orientation);
+        return Err(ErrorStr::new(error));
+    }
+    if attribute(&map, "infinite", 0u32)? != 0 {
+        return Err(ErrorStr::new(
+            "Infinite maps are not supported, disable 'Infinite' in the map properties",
+        ));
+    }
+
+    let mut tilesets = Vec::new();
+    let mut external_tilesets = Vec::new();
+    let mut layers = Vec::new();
+    for node in map.children().filter(Node::is_element) {
+        match node.tag_name().name() {
+            "tileset" => {
+                let first_gid = required(&node, "firstgid")?;
+                if let Some(source) = node.attribute("source") {
+                    external_tilesets.push(ExternalTileset {
+                        first_gid,
+                        source: source.to_string(),
+                    });
+                } else {
+                    tilesets.push(convert_tileset(&node, first_gid)?);
+                }
+            }
+            _ => convert_layer(&node, GroupState::default(), &mut layers)?,
+        }
+    }
+    tilesets.sort_by_key(|tileset: &Tileset| tileset.first_gid);
+
+    Ok(TileMap {
+        width: required(&map, "width")?,
+        height: required(&map, "height")?,
+        tile_width: required(&map, "tilewidth")?,
+        tile_height: required(&map, "tileheight")?,
+        layers,
+        tilesets,
+        external_tilesets,
+        properties: convert_properties(&map)?,
+    })
+}
+
+// an external .tsx tileset, first_gid comes from the map that references it
+pub fn parse_tileset(xml: &str, first_gid: u32) -> Result<Tileset, ErrorStr> {
+    let document = parse_document(xml)?;
+    let tileset = document.root_element();
+    if !tileset.has_tag_name("tileset") {
+        return Err(ErrorStr::new("TSX root element is not <tileset>"));
+    }
+    convert_tileset(&tileset, first_gid)
+}
+
+fn convert_properties(node: &Node) -> Result<Properties, ErrorStr> {
+    let mut properties = BTreeMap::new();
+    if let Some(list) = child(node, "properties") {
+        for property in list.children().filter(|c| c.has_tag_name("property")) {
+            let name: String = required(&property, "name")?;
+            let kind = property.attribute("type").unwrap_or("string");
+            // multi-line strings are stored as the element text instead of the value attribute
+            let value = property
+                .attribute("value")
+                .or_else(|| property.text())
+                .unwrap_or_default();
+            properties.insert(name, PropertyValue::parse(kind, value)?);
+        }
+    }
+    Ok(properties)
+}
+
+fn convert_tileset(node: &Node, first_gid: u32) -> Result<Tileset, ErrorStr> {
+    let name: String = attribute(node, "name", String::new())?;
+    let image = if let Some(image) = child(node, "image") {
+        image
+    } else {
+        let error = format!(
+            "Tileset '{}' is an image collection, only single image tilesets are supported",
+            name
+        );
+        return Err(ErrorStr::new(error));
+    };
+    let mut tiles = BTreeMap::new();
+    for tile in node.children().filter(|c| c.has_tag_name("tile")) {
+        let id = required(&tile, "id")?;
+        let class = tile
+            .attribute("class")
+            .or_else(|| tile.attribute("type"))
+            .unwrap_or_default()
+            .to_string();
+        let mut animation = Vec::new();
+        if let Some(frames) = child(&tile, "animation") {
+            for frame in frames.children().filter(|c| c.has_tag_name("frame")) {
+                animation.push(AnimationFrame {
+                    tile_id: required(&frame, "tileid")?,
+                    duration_ms: required(&frame, "duration")?,
+                });
+            }
+        }
+        tiles.insert(
+            id,
+            TileData {
+                class,
+                animation,
+                properties: convert_properties(&tile)?,
+            },
+        );
+    }
+    Ok(Tileset {
+        first_gid,
+        name,
+        tile_width: required(node, "tilewidth")?,
+        tile_height: required(node, "tileheight")?,
+        tile_count: attribute(node, "tilecount", 0)?,
+        columns: attribute(node, "columns", 0)?,
+        spacing: attribute(node, "spacing", 0)?,
+        margin: attribute(node, "margin", 0)?,
+        image: required(&image, "source")?,
+        image_width: attribute(&image, "width", 0)?,
+        image_height: attribute(&image, "height", 0)?,
+        tiles,
+        properties: convert_properties(node)?,
+    })
+}
+
+fn convert_layer(node: &Node, group: GroupState, out: &mut Vec<Layer>) -> Result<(), ErrorStr> {
+    let state = group.child(
+        Vec2::new(attribute(node, "offsetx", 0.0)?, attribute(node, "offsety", 0.0)?),
+        Vec2::new(
+            attribute(node, "parallaxx", 1.0)?,
+            attribute(node, "parallaxy", 1.0)?,
+        ),
+        attribute(node, "opacity", 1.0)?,
+        attribute(node, "visible", 1u32)? != 0,
+    );
+    let id = attribute(node, "id", 0)?;
+    let name: String = attribute(node, "name", String::new())?;
+    match node.tag_name().name() {
+        "layer" => {
+            let width: u32 = required(node, "width")?;
+            let height: u32 = required(node, "height")?;
+            let data = if let Some(data) = child(node, "data") {
+                data
+            } else {
+                let error = format!("Tile layer '{}' has no data", name);
+                return Err(ErrorStr::new(error));
+            };
+            let expected = (width * height) as usize;
+            let gids = match data.attribute("encoding") {
+                // plain XML, one <tile gid=".."/> per tile
+                None => {
+                    let mut gids = Vec::with_capacity(expected);
+                    for tile in data.children().filter(|c| c.has_tag_name("tile")) {
+                        gids.push(Gid(attribute(&tile, "gid", 0)?));
+                    }
+                    if gids.len() != expected {
+                        let error = format!(
+                            "Layer '{}' has {} tiles, expected {}",
+                            name,
+                            gids.len(),
+                            expected
+                        );
+                        return Err(ErrorStr::new(error));
+                    }
+                    gids
+                }
+                encoding => decode_layer_data(
+                    data.text().unwrap_or_default(),
+                    encoding,
+                    data.attribute("compression"),
+                    expected,
+                    &name,
+                )?,
+            };
+            out.push(Layer::Tiles(TileLayer {
+                id,
+                name,
+                width,
+                height,
+                data: gids,
+                offset: state.offset,
+                parallax: state.parallax,
+                opacity: state.opacity,
+                visible: state.visible,
+                properties: convert_properties(node)?,
+            }));
+        }
+        "objectgroup" => {
+            let mut objects = Vec::new();
+            for object in node.children().filter(|c| c.has_tag_name("object")) {
+                objects.push(convert_object(&object)?);
+            }
+            out.push(Layer::Objects(ObjectLayer {
+                id,
+                name,
+                objects,
+                offset: state.offset,
+                parallax: state.parallax,
+                opacity: state.opacity,
+                visible: state.visible,
+                properties: convert_properties(node)?,
+            }));
+        }
+        "group" => {
+            for child in node.children().filter(Node::is_element) {
+                convert_layer(&child, state, out)?;
+            }
+        }
+        // image layers and the map's own <properties> land here
+        _ => {}
+    }
+    Ok(())
+}
+
+fn parse_points(points: &str) -> Result<Vec<Vec2>, ErrorStr> {
+    let mut out = Vec::new();
+    for pair in points.split_whitespace() {
+        let mut coordinates = pair.split(',').map(str::parse::<f32>);
+        match (coordinates.next(), coordinates.next()) {
+            (Some(Ok(x)), Some(Ok(y))) => out.push(Vec2::new(x, y)),
+            _ => {
+                let error = format!("Invalid point '{}'", pair);
+                return Err(ErrorStr::new(error));
+            }
+        }
+    }
+    Ok(out)
+}
+
+fn convert_object(node: &Node) -> Result<MapObject, ErrorStr> {
+    let shape = if let Some(gid) = node.attribute("gid") {
+        let gid = gid
+            .parse()
+            .map_err(|_| ErrorStr::new(format!("Invalid object gid '{}'", gid)))?;
+        ObjectShape::Tile(Gid(gid))
+    } else if let Some(polygon) = child(node, "polygon") {
+        ObjectShape::Polygon(parse_points(polygon.attribute("points").unwrap_or_default())?)
+    } else if let Some(polyline) = child(node, "polyline") {
+        ObjectShape::Polyline(parse_points(polyline.attribute("points").unwrap_or_default())?)
+    } else if child(node, "ellipse").is_some() {
+        ObjectShape::Ellipse
+    } else if child(node, "point").is_some() {
+        ObjectShape::Point
+    } else {
+        ObjectShape::Rectangle
+    };
+    Ok(MapObject {
+        id: attribute(node, "id", 0)?,
+        name: attribute(node, "name", String::new())?,
+        class: node
+            .attribute("class")
+            .or_else(|| node.attribute("type"))
+            .unwrap_or_default()
+            .to_string(),
+        position: Vec2::new(attribute(node, "x", 0.0)?, attribute(node, "y", 0.0)?),
+        size: Vec2::new(
+            attribute(node, "width", 0.0)?,
+            attribute(node, "height", 0.0)?,
+        ),
+        rotation: attribute(node, "rotation", 0.0)?,
+        shape,
+        visible: attribute(node, "visible", 1u32)? != 0,
+        properties: convert_properties(node)?,
+    })
+}
@@ -0,0 +1,150 @@
+use crate::libs::input::bindings::MAX_AXES;
+use crate::libs::input::snapshot::InputSnapshot;
+use crate::libs::replay::{MAX_PLAYERS, Replay};
+use crate::libs::types::bytes::{ByteReader, ByteWriter};
+use crate::libs::types::errors::ErrorStr;
+
+const MAGIC: &[u8; 4] = b"RPLY";
+const VERSION: u8 = 1;
+
+// layout:
+//   "RPLY", version u8, seed u64, tick_hz u16, players u8, tick count varint
+//   per tick: varint mask of players whose input changed, then for each of them
+//     actions varint, u8 mask of non zero axes, each such axis zigzag,
+//     u8 pointer flag, pointer x and y zigzag
+//   final hash flag u8, final hash u64
+// unchanged ticks cost a single byte
+pub fn encode(replay: &Replay) -> Vec<u8> {
+    let mut writer = ByteWriter::with_capacity(32 + replay.ticks.len() * 2);
+    writer.write_bytes(MAGIC);
+    writer.write_u8(VERSION);
+    writer.write_u64(replay.seed);
+    writer.write_u16(replay.tick_hz);
+    writer.write_u8(replay.players);
+    writer.write_varint(replay.ticks.len() as u64);
+    let mut previous = vec![InputSnapshot::default(); replay.players as usize];
+    for inputs in replay.ticks.iter() {
+        let mut changed = 0u64;
+        for (player, input) in inputs.iter().enumerate() {
+            if !same_input(input, &previous[player]) {
+                changed |= 1 << player;
+            }
+        }
+        writer.write_varint(changed);
+        for (player, input) in inputs.iter().enumerate() {
+            if changed & (1 << player) != 0 {
+                write_input(&mut writer, input);
+                previous[player] = *input;
+            }
+        }
+    }
+    match replay.final_hash {
+        Some(hash) => {
+            writer.write_u8(1);
+            writer.write_u64(hash);
+        }
+        None => writer.write_u8(0),
+    }
+    writer.into_bytes()
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Replay, ErrorStr> {
+    let mut reader = ByteReader::new(bytes);
+    if reader.read_bytes(MAGIC.len())? != MAGIC {
+        return Err(ErrorStr::new("Not a replay file"));
+    }
+    let version = reader.read_u8()?;
+    if version != VERSION {
+        let error = format!("Unsupported replay version {}", version);
+        return Err(ErrorStr::new(error));
+    }
+    let seed = reader.read_u64()?;
+    let tick_hz = reader.read_u16()?;
+    let players = reader.read_u8()?;
+    if players as usize > MAX_PLAYERS {
+        return Err(ErrorStr::new(format!("Replay has {} players", players)));
+    }
+    let tick_count = reader.read_varint()? as usize;
+    // every tick takes at least one byte, so this also bounds the allocation
+    if tick_count > reader.remaining() {
+        return Err(ErrorStr::new("Replay tick count exceeds its size"));
+    }
+    let mut ticks = Vec::with_capacity(tick_count);
+    let mut current = vec![InputSnapshot::default(); players as usize];
+    for tick in 0..tick_count {
+        let changed = reader.read_varint()?;
+        if changed >> players != 0 {
+            let error = format!("Replay tick {} changes a missing player", tick);
+            return Err(ErrorStr::new(error));
+        }
+        for (player, input) in current.iter_mut().enumerate() {
+            if changed & (1 << player) != 0 {
+                *input = read_input(&mut reader)?;
+            }
+            input.tick = tick as u64;
+        }
+        ticks.push(current.clone());
+    }
+    let final_hash = match reader.read_u8()? {
+        0 => None,
+        _ => Some(reader.read_u64()?),
+    };
+    Ok(Replay {
+        seed,
+        tick_hz,
+        players,
+        ticks,
+        final_hash,
+    })
+}
+
+// ticks differ between every snapshot, only the contents count
+fn same_input(a: &InputSnapshot, b: &InputSnapshot) -> bool {
+    a.actions == b.actions && a.axes == b.axes && a.pointer == b.pointer
+}
+
+fn write_input(writer: &mut ByteWriter, input: &InputSnapshot) {
+    writer.write_varint(input.actions);
+    let mut axes = 0u8;
+    for (index, axis) in input.axes.iter().enumerate() {
+        if *axis != 0 {
+            axes |= 1 << index;
+        }
+    }
+    writer.write_u8(axes);
+    for axis in input.axes.iter().filter(|axis| **axis != 0) {
+        writer.write_zigzag(*axis as i64);
+    }
+    match input.pointer {
+        Some([x, y]) => {
+            writer.write_u8(1);
+            writer.write_zigzag(x as i64);
+            writer.write_zigzag(y as i64);
+        }
+        None => writer.write_u8(0),
+    }
+}
+
+fn read_input(reader: &mut ByteReader) -> Result<InputSnapshot, ErrorStr> {
+    let mut input = InputSnapshot {
+        actions: reader.read_varint()?,
+        ..InputSnapshot::default()
+    };
+    let axes = reader.read_u8()?;
+    for index in 0..MAX_AXES {
+        if axes & (1 << index) != 0 {
+            input.axes[index] = read_i16(reader)?;
+        }
+    }
+    if reader.read_u8()? != 0 {
+        input.pointer = Some([read_i16(reader)?, read_i16(reader)?]);
+    }
+    Ok(input)
+}
+
+fn read_i16(reader: &mut ByteReader) -> Result<i16, ErrorStr> {
+    match i16::try_from(reader.read_zigzag()?) {
+        Ok(value) => Ok(value),
+        Err(_) => Err(ErrorStr::new("Replay value out of range")),
+    }
+}
@@ -0,0 +1,459 @@
+pub mod format;
+
+use crate::libs::ecs::World;
+use crate::libs::ecs::hash::StateHashFnTrait;
+use crate::libs::ecs::schedule::{Schedule, System};
+use crate::libs::input::snapshot::{InputFrame, InputSnapshot, PlayerInputs};
+use crate::libs::types::download::download_bytes;
+use crate::libs::types::errors::ErrorStr;
+use crate::libs::types::rng::Rng;
+use crate::libs::types::shared::SharedRefCell;
+use crate::{log_info, log_warn};
+
+const LOG_TARGET: &str = "replay";
+// one bit per player in the per tick change mask
+pub const MAX_PLAYERS: usize = 32;
+
+// every player's input for every tick of a match, plus what's needed to start the simulation
+// the same way. the simulation has to be a pure function of these
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Replay {
+    // the world's Rng resource is seeded with this
+    pub seed: u64,
+    // informational, the simulation only sees ticks
+    pub tick_hz: u16,
+    pub players: u8,
+    // ticks[t][p], t counts from 0 at the start of the recording
+    ticks: Vec<Vec<InputSnapshot>>,
+    // state hash after the last tick, for desync and regression checks
+    pub final_hash: Option<u64>,
+}
+
+impl Replay {
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.ticks.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.ticks.is_empty()
+    }
+
+    pub fn inputs(&self, tick: usize) -> Option<&[InputSnapshot]> {
+        self.ticks.get(tick).map(|inputs| inputs.as_slice())
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        format::encode(self)
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, ErrorStr> {
+        format::decode(bytes)
+    }
+
+    pub fn download(&self) -> Result<(), ErrorStr> {
+        let file_name = format!("replay-{}.rply", js_sys::Date::now() as u64);
+        download_bytes(&self.encode(), "application/octet-stream", &file_name)
+    }
+
+    // seeds a fresh world the way the recording started
+    pub fn prepare(&self, world: &mut World) {
+        world.insert_resource(Rng::new(self.seed));
+    }
+
+    // inserts the inputs for replay tick `tick` as PlayerInputs and, for player 0, InputFrame
+    pub fn apply(&self, world: &mut World, tick: usize) -> Result<(), ErrorStr> {
+        let current = if let Some(current) = self.ticks.get(tick) {
+            current
+        } else {
+            let error = format!("Replay has no tick {}, it has {}", tick, self.ticks.len());
+            return Err(ErrorStr::new(error));
+        };
+        let previous = tick.checked_sub(1).and_then(|tick| self.ticks.get(tick));
+        let frames: Vec<InputFrame> = current
+            .iter()
+            .enumerate()
+            .map(|(player, input)| InputFrame {
+                current: *input,
+                previous: previous
+                    .and_then(|previous| previous.get(player).copied())
+                    .unwrap_or_default(),
+            })
+            .collect();
+        if let Some(frame) = frames.first() {
+            world.insert_resource(*frame);
+        }
+        world.insert_resource(PlayerInputs { frames });
+        Ok(())
+    }
+
+    // runs the whole match headless on a fresh world and returns the final state hash. the
+    // schedule must not contain the live input or recording systems
+    pub fn run<F>(
+        &self,
+        world: &mut World,
+        schedule: &mut Schedule,
+        hash: F,
+    ) -> Result<u64, ErrorStr>
+    where
+        F: StateHashFnTrait,
+    {
+        self.prepare(world);
+        for tick in 0..self.ticks.len() {
+            self.apply(world, tick)?;
+            schedule.run(world)?;
+        }
+        world.state_hash(hash)
+    }
+
+    // run and compare against the recorded hash
+    pub fn verify<F>(
+        &self,
+        world: &mut World,
+        schedule: &mut Schedule,
+        hash: F,
+    ) -> Result<(), ErrorStr>
+    where
+        F: StateHashFnTrait,
+    {
+        let expected = if let Some(expected) = self.final_hash {
+            expected
+        } else {
+            return Err(ErrorStr::new("Replay has no final hash to verify against"));
+        };
+        let actual = self.run(world, schedule, hash)?;
+        if actual != expected {
+            let error = format!(
+                "Replay desynced: expected state {:016x}, got {:016x}",
+                expected, actual
+            );
+            return Err(ErrorStr::new(error));
+        }
+        Ok(())
+    }
+
+    // feeds the inputs tick by tick in place of the input system, for watching a replay at
+    // normal speed. the world must have been prepared at tick start_tick
+    pub fn into_system<'a>(self, start_tick: u64, name: &'a str) -> System<'a> {
+        let mut finished = false;
+        System::new(
+            move |world: &mut World| {
+                let tick = world.tick().saturating_sub(start_tick) as usize;
+                if tick < self.ticks.len() {
+                    return self.apply(world, tick);
+                }
+                if !finished {
+                    finished = true;
+                    log_info!(
+                        LOG_TARGET,
+                        "Replay finished after {} ticks",
+                        self.ticks.len()
+                    );
+                }
+                // hold the last input rather than snapping back to nothing pressed
+                Ok(())
+            },
+            name,
+        )
+    }
+}
+
+// collects inputs as the match is played
+#[derive(Debug, Clone)]
+pub struct Recorder {
+    replay: Replay,
+    start_tick: u64,
+}
+
+impl Recorder {
+    // seeds the world's Rng so the recording can be reproduced
+    pub fn start(
+        world: &mut World,
+        seed: u64,
+        players: u8,
+        tick_hz: u16,
+    ) -> Result<Self, ErrorStr> {
+        if players as usize > MAX_PLAYERS {
+            let error = format!("Replays are limited to {} players", MAX_PLAYERS);
+            return Err(ErrorStr::new(error));
+        }
+        world.insert_resource(Rng::new(seed));
+        Ok(Self {
+            replay: Replay {
+                seed,
+                tick_hz,
+                players,
+                ..Replay::default()
+            },
+            start_tick: world.tick(),
+        })
+    }
+
+    // confirmed input only, never predicted input. ticks that are skipped copy the previous
+    // tick, as a missing input would be held by the simulation
+    pub fn record(&mut self, tick: u64, player: u8, input: InputSnapshot) -> Result<(), ErrorStr> {
+        if player >= self.replay.players {
+            let error = format!("Player {} is outside the recording", player);
+            return Err(ErrorStr::new(error));
+        }
+        let index = if let Some(index) = tick.checked_sub(self.start_tick) {
+            index as usize
+        } else {
+            let error = format!("Tick {} is before the recording started", tick);
+            return Err(ErrorStr::new(error));
+        };
+        while self.replay.ticks.len() <= index {
+            let next = match self.replay.ticks.last() {
+                Some(last) => last.clone(),
+                None => vec![InputSnapshot::default(); self.replay.players as usize],
+            };
+            self.replay.ticks.push(next);
+        }
+        self.replay.ticks[index][player as usize] = InputSnapshot {
+            tick: index as u64,
+            ..input
+        };
+        Ok(())
+    }
+
+    // the inputs the world sees this tick, from PlayerInputs or else the local InputFrame
+    pub fn record_world(&mut self, world: &World) -> Result<(), ErrorStr> {
+        let tick = world.tick();
+        if let Some(inputs) = world.resource::<PlayerInputs>() {
+            for (player, frame) in inputs.frames.iter().enumerate() {
+                self.record(tick, player as u8, frame.current)?;
+            }
+            return Ok(());
+        }
+        match world.resource::<InputFrame>() {
+            Some(frame) => self.record(tick, 0, frame.current),
+            None => {
+                log_warn!(LOG_TARGET, "No input to record on tick {}", tick);
+                Ok(())
+            }
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.replay.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.replay.is_empty()
+    }
+
+    // call after the last recorded tick has been simulated
+    pub fn finish<F>(mut self, world: &World, hash: F) -> Result<Replay, ErrorStr>
+    where
+        F: StateHashFnTrait,
+    {
+        self.replay.final_hash = Some(world.state_hash(hash)?);
+        log_info!(
+            LOG_TARGET,
+            "Recorded {} ticks for {} players",
+            self.replay.len(),
+            self.replay.players
+        );
+        Ok(self.replay)
+    }
+
+    // add after the input system and before the simulation systems
+    pub fn into_system<'a>(recorder: SharedRefCell<Recorder>, name: &'a str) -> System<'a> {
+        System::new(
+            move |world: &mut World| recorder.borrow_mut().record_world(world),
+            name,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::hash::Hasher;
+
+    use super::*;
+    use crate::libs::ecs::hash::StateHasher;
+    use crate::libs::input::bindings::{ActionId, AxisId};
+    use crate::libs::types::shared::shared_ref_cell;
+
+    // recorded with write_fixture, the hash pins the simulation below. if a change to the ecs
+    // or the replay format moves it, that change breaks every saved replay
+    const FIXTURE: &[u8] = include_bytes!("fixtures/walk.rply");
+    const FIXTURE_HASH: u64 = 0xd23f_94f1_1ca8_0601;
+    const FIXTURE_TICKS: u64 = 120;
+    const SEED: u64 = 0x5eed;
+    const PLAYERS: u8 = 2;
+    const MOVE: ActionId = ActionId(0);
+    const STEER: AxisId = AxisId(0);
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct Position([f32; 2]);
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct Player(usize);
+
+    fn setup() -> World {
+        let mut world = World::new();
+        for player in 0..PLAYERS as usize {
+            let entity = world.spawn().unwrap();
+            world.insert(entity, Player(player)).unwrap();
+            world.insert(entity, Position([0.0, 0.0])).unwrap();
+        }
+        world
+    }
+
+    // moves each player by their input plus a little randomness, so both the inputs and the
+    // rng have to come back exactly
+    fn movement(world: &mut World) -> Result<(), ErrorStr> {
+        let inputs = if let Some(inputs) = world.resource::<PlayerInputs>() {
+            inputs.clone()
+        } else {
+            return Err(ErrorStr::new("No player inputs"));
+        };
+        let jitter = if let Some(rng) = world.resource_mut::<Rng>() {
+            rng.range_f32(-0.5, 0.5)
+        } else {
+            return Err(ErrorStr::new("No rng"));
+        };
+        world.each::<(&Player, &mut Position), _>(|_, (player, position)| {
+            let frame = if let Some(frame) = inputs.player(player.0) {
+                frame
+            } else {
+                return;
+            };
+            if frame.pressed(MOVE) {
+                position.0[0] += 2.0;
+            }
+            position.0[0] += frame.axis(STEER) * 3.0 + jitter;
+            if let Some([_, y]) = frame.current.pointer {
+                position.0[1] += (y as f32 - position.0[1]) * 0.1;
+            }
+        })
+    }
+
+    fn simulation<'a>() -> Schedule<'a> {
+        Schedule::new().with_system(movement, "Move")
+    }
+
+    fn hash(world: &World, hasher: &mut StateHasher) -> Result<(), ErrorStr> {
+        if let Some(rng) = world.resource::<Rng>() {
+            hasher.write_u64(rng.state());
+        }
+        world.each::<&Position, _>(|entity, position| {
+            hasher.write_u32(entity.raw());
+            hasher.write_f32(position.0[0]);
+            hasher.write_f32(position.0[1]);
+        })
+    }
+
+    // what the players did on each tick
+    fn scripted(tick: u64) -> PlayerInputs {
+        let mut walker = InputSnapshot::default();
+        walker.set_action(MOVE, (tick / 10).is_multiple_of(2));
+        if (40..80).contains(&tick) {
+            walker.pointer = Some([tick as i16 * 3, 100 - tick as i16]);
+        }
+        let mut steerer = InputSnapshot::default();
+        steerer.set_axis(STEER, (tick as f32 - 60.0) / 60.0);
+        PlayerInputs {
+            frames: [walker, steerer]
+                .into_iter()
+                .map(|current| InputFrame {
+                    current: InputSnapshot { tick, ..current },
+                    previous: InputSnapshot::default(),
+                })
+                .collect(),
+        }
+    }
+
+    fn record() -> Replay {
+        let mut world = setup();
+        let recorder = Recorder::start(&mut world, SEED, PLAYERS, 60).unwrap();
+        let recorder = shared_ref_cell(recorder);
+        let mut schedule = Schedule::new().with_system(
+            |world: &mut World| {
+                world.insert_resource(scripted(world.tick()));
+                Ok(())
+            },
+            "Script",
+        );
+        schedule.add_system(Recorder::into_system(recorder.clone(), "Record"));
+        let mut schedule = schedule.with_system(movement, "Move");
+        for _ in 0..FIXTURE_TICKS {
+            schedule.run(&mut world).unwrap();
+        }
+        let recorder = recorder.borrow().clone();
+        recorder.finish(&world, hash).unwrap()
+    }
+
+    #[test]
+    #[ignore = "regenerates the fixture, run by hand after an intended format change"]
+    fn write_fixture() {
+        let path = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/src/libs/replay/fixtures/walk.rply"
+        );
+        std::fs::write(path, record().encode()).unwrap();
+    }
+
+    #[test]
+    fn replays_fixture_to_the_same_state() {
+        let replay = Replay::decode(FIXTURE).unwrap();
+        assert_eq!(replay.len() as u64, FIXTURE_TICKS);
+        assert_eq!(replay.players, PLAYERS);
+        assert_eq!(replay.final_hash, Some(FIXTURE_HASH));
+        let actual = replay.run(&mut setup(), &mut simulation(), hash).unwrap();
+        assert_eq!(actual, FIXTURE_HASH);
+        replay
+            .verify(&mut setup(), &mut simulation(), hash)
+            .unwrap();
+    }
+
+    #[test]
+    fn records_what_the_fixture_holds() {
+        let replay = record();
+        assert_eq!(replay.final_hash, Some(FIXTURE_HASH));
+        assert_eq!(replay.encode(), FIXTURE);
+    }
+
+    #[test]
+    fn round_trips_through_the_format() {
+        let replay = record();
+        assert_eq!(Replay::decode(&replay.encode()).unwrap(), replay);
+    }
+
+    #[test]
+    fn detects_a_desync() {
+        let mut replay = Replay::decode(FIXTURE).unwrap();
+        replay.ticks[50][1].set_axis(STEER, 1.0);
+        assert!(
+            replay
+                .verify(&mut setup(), &mut simulation(), hash)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn rejects_truncated_files() {
+        for len in 0..FIXTURE.len() {
+            assert!(Replay::decode(&FIXTURE[..len]).is_err(), "{} bytes", len);
+        }
+    }
+
+    #[test]
+    fn holds_skipped_ticks() {
+        let mut world = World::new();
+        let mut recorder = Recorder::start(&mut world, SEED, 1, 60).unwrap();
+        let mut input = InputSnapshot::default();
+        input.set_action(MOVE, true);
+        recorder.record(0, 0, input).unwrap();
+        recorder.record(3, 0, InputSnapshot::default()).unwrap();
+        assert!(recorder.record(0, 1, input).is_err());
+        let replay = recorder.finish(&world, hash).unwrap();
+        assert_eq!(replay.len(), 4);
+        assert!(replay.inputs(2).unwrap()[0].pressed(MOVE));
+        assert!(!replay.inputs(3).unwrap()[0].pressed(MOVE));
+    }
+}
@@ -0,0 +1,391 @@
+pub mod bounds;
+pub mod queue;
+pub mod transform;
+
+use glam::{Mat4, Vec3};
+use web_sys::WebGl2RenderingContext;
+
+use crate::libs::rendering::camera::Camera;
+use crate::libs::rendering::canvas::{RafTask, RafTime, WebGlCanvas};
+use crate::libs::scene::bounds::{Aabb, Frustum};
+use crate::libs::scene::queue::{RenderItem, RenderQueue, Renderable, SceneDrawer, view_depth};
+use crate::libs::scene::transform::Transform;
+use crate::libs::types::errors::ErrorStr;
+use crate::libs::types::shared::SharedRefCell;
+
+// index into the node arena plus a generation, so ids of removed nodes don't alias new ones
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodeId {
+    index: u32,
+    generation: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct Node {
+    pub name: String,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    local: Transform,
+    world: Mat4,
+    // local changed since the last update, children inherit it during propagation
+    dirty: bool,
+    visible: bool,
+    renderable: Option<Renderable>,
+    // local space, None means never culled
+    bounds: Option<Aabb>,
+    world_bounds: Option<Aabb>,
+}
+
+impl Node {
+    fn new(name: String) -> Self {
+        Self {
+            name,
+            parent: None,
+            children: Vec::new(),
+            local: Transform::IDENTITY,
+            world: Mat4::IDENTITY,
+            dirty: true,
+            visible: true,
+            renderable: None,
+            bounds: None,
+            world_bounds: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Slot {
+    generation: u32,
+    node: Option<Node>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Scene {
+    slots: Vec<Slot>,
+    free: Vec<u32>,
+    roots: Vec<NodeId>,
+    queue: RenderQueue,
+}
+
+impl Scene {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn create_node<S>(&mut self, name: S) -> NodeId
+    where
+        S: Into<String>,
+    {
+        let node = Node::new(name.into());
+        let id = match self.free.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+                slot.node = Some(node);
+                NodeId {
+                    index,
+                    generation: slot.generation,
+                }
+            }
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    node: Some(node),
+                });
+                NodeId {
+                    index: (self.slots.len() - 1) as u32,
+                    generation: 0,
+                }
+            }
+        };
+        self.roots.push(id);
+        id
+    }
+
+    pub fn create_child<S>(&mut self, parent: NodeId, name: S) -> Result<NodeId, ErrorStr>
+    where
+        S: Into<String>,
+    {
+        let child = self.create_node(name);
+        if let Err(error) = self.set_parent(child, Some(parent)) {
+            self.remove_node(child);
+            return Err(error);
+        }
+        Ok(child)
+    }
+
+    pub fn node(&self, id: NodeId) -> Option<&Node> {
+        self.slots
+            .get(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.node.as_ref())
+    }
+
+    fn node_mut(&mut self, id: NodeId) -> Option<&mut Node> {
+        self.slots
+            .get_mut(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.node.as_mut())
+    }
+
+    fn node_or_error(&mut self, id: NodeId) -> Result<&mut Node, ErrorStr> {
+        match self.node_mut(id) {
+            Some(node) => Ok(node),
+            None => Err(ErrorStr::new(format!("Scene node {:?} does not exist", id))),
+        }
+    }
+
+    #[inline]
+    pub fn contains(&self, id: NodeId) -> bool {
+        self.node(id).is_some()
+    }
+
+    // removes the node and its whole subtree
+    pub fn remove_node(&mut self, id: NodeId) {
+        let parent = if let Some(node) = self.node(id) {
+            node.parent
+        } else {
+            return;
+        };
+        self.detach(id, parent);
+        let mut stack = vec![id];
+        while let Some(current) = stack.pop() {
+            let slot = &mut self.slots[current.index as usize];
+            if let Some(node) = slot.node.take() {
+                stack.extend(node.children);
+                slot.generation = slot.generation.wrapping_add(1);
+                self.free.push(current.index);
+            }
+        }
+    }
+
+    fn detach(&mut self, id: NodeId, parent: Option<NodeId>) {
+        match parent.and_then(|parent| self.node_mut(parent)) {
+            Some(parent) => parent.children.retain(|child| *child != id),
+            None => self.roots.retain(|root| *root != id),
+        }
+    }
+
+    // None makes the node a root. the world transform is recomputed on the next update
+    pub fn set_parent(&mut self, child: NodeId, parent: Option<NodeId>) -> Result<(), ErrorStr> {
+        let old_parent = self.node_or_error(child)?.parent;
+        if let Some(parent) = parent {
+            if !self.contains(parent) {
+                let error = format!("Scene node {:?} does not exist", parent);
+                return Err(ErrorStr::new(error));
+            }
+            // walking up from the new parent must not reach the child
+            let mut ancestor = Some(parent);
+            while let Some(current) = ancestor {
+                if current == child {
+                    return Err(ErrorStr::new("Scene node can't be parented to its descendant"));
+                }
+                ancestor = self.node(current).and_then(|node| node.parent);
+            }
+        }
+        self.detach(child, old_parent);
+        match parent {
+            Some(parent) => self.node_or_error(parent)?.children.push(child),
+            None => self.roots.push(child),
+        }
+        let node = self.node_or_error(child)?;
+        node.parent = parent;
+        node.dirty = true;
+        Ok(())
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.node(id).and_then(|node| node.parent)
+    }
+
+    pub fn children(&self, id: NodeId) -> &[NodeId] {
+        self.node(id)
+            .map(|node| node.children.as_slice())
+            .unwrap_or(&[])
+    }
+
+    #[inline]
+    pub fn roots(&self) -> &[NodeId] {
+        &self.roots
+    }
+
+    pub fn local(&self, id: NodeId) -> Option<Transform> {
+        self.node(id).map(|node| node.local)
+    }
+
+    pub fn set_local(&mut self, id: NodeId, local: Transform) {
+        if let Some(node) = self.node_mut(id) {
+            node.local = local;
+            node.dirty = true;
+        }
+    }
+
+    // edit in place, marks the node dirty
+    pub fn update_local<F>(&mut self, id: NodeId, update: F)
+    where
+        F: FnOnce(&mut Transform),
+    {
+        if let Some(node) = self.node_mut(id) {
+            update(&mut node.local);
+            node.dirty = true;
+        }
+    }
+
+    // as of the last update
+    pub fn world(&self, id: NodeId) -> Option<Mat4> {
+        self.node(id).map(|node| node.world)
+    }
+
+    pub fn world_bounds(&self, id: NodeId) -> Option<Aabb> {
+        self.node(id).and_then(|node| node.world_bounds)
+    }
+
+    pub fn set_renderable(&mut self, id: NodeId, renderable: Option<Renderable>) {
+        if let Some(node) = self.node_mut(id) {
+            node.renderable = renderable;
+            // sprites get bounds from their size unless some were set explicitly
+            if let (None, Some(Renderable::Sprite { size, .. })) =
+                (node.bounds.as_ref(), node.renderable.as_ref())
+            {
+                node.bounds = Some(Aabb::from_center(Vec3::ZERO, (*size * 0.5).extend(0.0)));
+            }
+            node.dirty = true;
+        }
+    }
+
+    pub fn set_bounds(&mut self, id: NodeId, bounds: Option<Aabb>) {
+        if let Some(node) = self.node_mut(id) {
+            node.bounds = bounds;
+            node.dirty = true;
+        }
+    }
+
+    // hiding a node hides its subtree
+    pub fn set_visible(&mut self, id: NodeId, visible: bool) {
+        if let Some(node) = self.node_mut(id) {
+            node.visible = visible;
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len() - self.free.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // recomputes world transforms below dirty nodes. untouched subtrees are skipped entirely
+    pub fn update_transforms(&mut self) {
+        let mut stack: Vec<(NodeId, Mat4, bool)> = self
+            .roots
+            .iter()
+            .map(|root| (*root, Mat4::IDENTITY, false))
+            .collect();
+        while let Some((id, parent_world, parent_dirty)) = stack.pop() {
+            let node = if let Some(node) = self.node_mut(id) {
+                node
+            } else {
+                continue;
+            };
+            let dirty = parent_dirty || node.dirty;
+            if dirty {
+                node.world = parent_world * node.local.matrix();
+                node.world_bounds = node.bounds.map(|bounds| bounds.transformed(node.world));
+                node.dirty = false;
+            }
+            let world = node.world;
+            stack.extend(node.children.iter().map(|child| (*child, world, dirty)));
+        }
+    }
+
+    // updates transforms, then culls and sorts everything visible from the camera
+    pub fn build_queue(&mut self, camera: &Camera) -> &RenderQueue {
+        self.update_transforms();
+        let mut queue = std::mem::take(&mut self.queue);
+        queue.clear();
+        let frustum = Frustum::from_view_projection(camera.view_projection());
+        let view = camera.view();
+        let mut stack: Vec<NodeId> = self.roots.clone();
+        while let Some(id) = stack.pop() {
+            let node = if let Some(node) = self.node(id) {
+                node
+            } else {
+                continue;
+            };
+            if !node.visible {
+                continue;
+            }
+            stack.extend(node.children.iter().copied());
+            let renderable = if let Some(renderable) = node.renderable.as_ref() {
+                renderable
+            } else {
+                continue;
+            };
+            let outside = node
+                .world_bounds
+                .as_ref()
+                .is_some_and(|bounds| !frustum.intersects_aabb(bounds));
+            if outside {
+                queue.culled += 1;
+                continue;
+            }
+            let center = node
+                .world_bounds
+                .map(|bounds| bounds.center())
+                .unwrap_or_else(|| node.world.w_axis.truncate());
+            queue.push(RenderItem {
+                node: id,
+                world: node.world,
+                renderable: renderable.clone(),
+                depth: view_depth(view, center),
+            });
+        }
+        queue.sort();
+        self.queue = queue;
+        &self.queue
+    }
+
+    #[inline]
+    pub fn queue(&self) -> &RenderQueue {
+        &self.queue
+    }
+
+    pub fn draw<D>(
+        &mut self,
+        gl: &WebGl2RenderingContext,
+        camera: &Camera,
+        drawer: &mut D,
+    ) -> Result<(), ErrorStr>
+    where
+        D: SceneDrawer,
+    {
+        self.build_queue(camera);
+        self.queue.draw(gl, camera, drawer)
+    }
+
+    // draws the scene from the shared camera every frame. the scene and camera stay shared so
+    // other tasks (game logic, input) can keep editing them
+    pub fn into_raf_task<'a, D>(
+        scene: SharedRefCell<Scene>,
+        camera: SharedRefCell<Camera>,
+        mut drawer: D,
+        name: &'a str,
+    ) -> RafTask<'a>
+    where
+        D: SceneDrawer + 'static,
+    {
+        RafTask::new(
+            move |canvas: &WebGlCanvas, _time: RafTime| {
+                let context = canvas.get_context();
+                let gl = if let Some(gl) = context.as_ref() {
+                    gl
+                } else {
+                    return Err(ErrorStr::new("Scene has no GL context"));
+                };
+                let camera = camera.borrow();
+                scene.borrow_mut().draw(gl, &camera, &mut drawer)
+            },
+            name,
+        )
+    }
+}
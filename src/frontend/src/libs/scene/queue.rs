@@ -0,0 +1,178 @@
+use glam::{Mat4, Vec2, Vec3};
+use web_sys::WebGl2RenderingContext;
+
+use crate::libs::rendering::camera::Camera;
+use crate::libs::rendering::colour::Colour;
+use crate::libs::scene::NodeId;
+use crate::libs::types::errors::ErrorStr;
+
+// handles into whatever mesh / material / texture storage the drawer owns
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MeshId(pub u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MaterialId(pub u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TextureId(pub u32);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Renderable {
+    Mesh {
+        mesh: MeshId,
+        material: MaterialId,
+        transparent: bool,
+    },
+    // a quad centred on the node, uv as (min, max)
+    Sprite {
+        texture: TextureId,
+        size: Vec2,
+        uv: (Vec2, Vec2),
+        tint: Colour,
+    },
+    Text {
+        text: String,
+        size: f32,
+        colour: Colour,
+    },
+}
+
+impl Renderable {
+    pub fn sprite(texture: TextureId, size: Vec2) -> Self {
+        Renderable::Sprite {
+            texture,
+            size,
+            uv: (Vec2::ZERO, Vec2::ONE),
+            tint: Colour::WHITE,
+        }
+    }
+
+    pub fn is_transparent(&self) -> bool {
+        match self {
+            Renderable::Mesh { transparent, .. } => *transparent,
+            Renderable::Sprite { tint, .. } => tint.a < 1.0,
+            // glyph edges are always blended
+            Renderable::Text { .. } => true,
+        }
+    }
+
+    // used to batch opaque draws that sit at the same depth
+    fn sort_key(&self) -> u64 {
+        match self {
+            Renderable::Mesh { mesh, material, .. } => {
+                ((material.0 as u64) << 32) | mesh.0 as u64
+            }
+            Renderable::Sprite { texture, .. } => (1 << 63) | texture.0 as u64,
+            Renderable::Text { .. } => u64::MAX,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RenderItem {
+    pub node: NodeId,
+    pub world: Mat4,
+    pub renderable: Renderable,
+    // distance along the camera's view direction, bigger is further away
+    pub depth: f32,
+}
+
+// visible renderables for one camera. opaque front to back so early z rejects overdraw,
+// transparent back to front so blending composites correctly
+#[derive(Debug, Clone, Default)]
+pub struct RenderQueue {
+    pub opaque: Vec<RenderItem>,
+    pub transparent: Vec<RenderItem>,
+    // nodes rejected by the frustum this frame
+    pub culled: usize,
+}
+
+impl RenderQueue {
+    pub fn clear(&mut self) {
+        self.opaque.clear();
+        self.transparent.clear();
+        self.culled = 0;
+    }
+
+    pub(crate) fn push(&mut self, item: RenderItem) {
+        if item.renderable.is_transparent() {
+            self.transparent.push(item);
+        } else {
+            self.opaque.push(item);
+        }
+    }
+
+    pub(crate) fn sort(&mut self) {
+        self.opaque.sort_by(|a, b| {
+            a.depth
+                .total_cmp(&b.depth)
+                .then_with(|| a.renderable.sort_key().cmp(&b.renderable.sort_key()))
+        });
+        self.transparent.sort_by(|a, b| b.depth.total_cmp(&a.depth));
+    }
+
+    pub fn len(&self) -> usize {
+        self.opaque.len() + self.transparent.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // opaque items first, then transparent ones, in queue order
+    pub fn items(&self) -> impl Iterator<Item = &RenderItem> {
+        self.opaque.iter().chain(self.transparent.iter())
+    }
+
+    pub fn draw<D>(
+        &self,
+        gl: &WebGl2RenderingContext,
+        camera: &Camera,
+        drawer: &mut D,
+    ) -> Result<(), ErrorStr>
+    where
+        D: SceneDrawer,
+    {
+        drawer.begin(gl, camera)?;
+        for item in self.opaque.iter() {
+            drawer.draw(gl, camera, item)?;
+        }
+        drawer.begin_transparent(gl, camera)?;
+        for item in self.transparent.iter() {
+            drawer.draw(gl, camera, item)?;
+        }
+        drawer.end(gl, camera)
+    }
+}
+
+// turns queue items into draw calls. the scene only knows handles, the drawer owns the gpu side
+pub trait SceneDrawer {
+    fn begin(&mut self, _gl: &WebGl2RenderingContext, _camera: &Camera) -> Result<(), ErrorStr> {
+        Ok(())
+    }
+
+    // called between the opaque and transparent items, e.g. to enable blending
+    fn begin_transparent(
+        &mut self,
+        _gl: &WebGl2RenderingContext,
+        _camera: &Camera,
+    ) -> Result<(), ErrorStr> {
+        Ok(())
+    }
+
+    fn draw(
+        &mut self,
+        gl: &WebGl2RenderingContext,
+        camera: &Camera,
+        item: &RenderItem,
+    ) -> Result<(), ErrorStr>;
+
+    fn end(&mut self, _gl: &WebGl2RenderingContext, _camera: &Camera) -> Result<(), ErrorStr> {
+        Ok(())
+    }
+}
+
+// view space depth of a world position, positive in front of the camera
+pub(crate) fn view_depth(view: Mat4, position: Vec3) -> f32 {
+    -view.transform_point3(position).z
+}
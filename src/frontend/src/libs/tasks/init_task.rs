@@ -62,18 +62,20 @@ impl<'a> $struct_name<'a> {
 
     pub fn execute_if_uninitialised(&mut self, $($arg_name: $arg_type),*) -> bool {
         if !self.initialised.get() {
-            leptos::logging::log!("Executing {} '{}'", stringify!($struct_name), self.name);
+            $crate::log_debug!("tasks", "Executing {} '{}'", stringify!($struct_name), self.name);
             *self.initialised.get_mut() = (self.task)($($arg_name),*);
             *self.attempts.get_mut() += 1;
             if self.initialised.get() {
-                leptos::logging::log!(
+                $crate::log_info!(
+                    "tasks",
                     "{} '{}' initialised successfully. Attempts: {}",
                     stringify!($struct_name),
                     self.name,
                     self.attempts.get()
                 );
             } else {
-                leptos::logging::error!(
+                $crate::log_error!(
+                    "tasks",
                     "{} '{}' initialisation failed. Attempts: {}",
                     stringify!($struct_name),
                     self.name,
@@ -55,7 +55,7 @@ impl<'a> $struct_name<'a> {
     #[inline]
     pub fn execute(&mut self, $($arg_name: $arg_type),*) -> $return_type {
         // Note: Using stringify!($struct_name) provides the struct name at compile time
-        leptos::logging::log!("Executing {} '{}'", stringify!($struct_name), self.name);
+        $crate::log_trace!("tasks", "Executing {} '{}'", stringify!($struct_name), self.name);
         (self.task)($($arg_name),*)
     }
 
@@ -0,0 +1,306 @@
+use crate::libs::types::errors::ErrorStr;
+
+// little endian fixed width values plus LEB128 varints, for compact binary formats
+#[derive(Debug, Clone, Default)]
+pub struct ByteWriter {
+    bytes: Vec<u8>,
+}
+
+impl ByteWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            bytes: Vec::with_capacity(capacity),
+        }
+    }
+
+    #[inline]
+    pub fn write_u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    #[inline]
+    pub fn write_u16(&mut self, value: u16) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    #[inline]
+    pub fn write_u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    #[inline]
+    pub fn write_u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    #[inline]
+    pub fn write_f32(&mut self, value: f32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.bytes.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        self.bytes.push(value as u8);
+    }
+
+    // small negative numbers stay small
+    #[inline]
+    pub fn write_zigzag(&mut self, value: i64) {
+        self.write_varint(((value << 1) ^ (value >> 63)) as u64);
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    // up to 8 booleans in one byte, the first in the lowest bit
+    pub fn write_flags(&mut self, flags: &[bool]) {
+        debug_assert!(flags.len() <= 8);
+        let byte = flags
+            .iter()
+            .enumerate()
+            .fold(0u8, |byte, (bit, flag)| byte | ((*flag as u8) << bit));
+        self.bytes.push(byte);
+    }
+
+    // length prefixed
+    pub fn write_str(&mut self, value: &str) {
+        self.write_varint(value.len() as u64);
+        self.bytes.extend_from_slice(value.as_bytes());
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ByteReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, position: 0 }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], ErrorStr> {
+        // lengths come off the wire, so they may be anything
+        let end = match self.position.checked_add(len) {
+            Some(end) if len <= self.remaining() => end,
+            _ => {
+                let error = format!(
+                    "Unexpected end of data, wanted {} bytes at {} of {}",
+                    len,
+                    self.position,
+                    self.bytes.len()
+                );
+                return Err(ErrorStr::new(error));
+            }
+        };
+        let bytes = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn take_array<const N: usize>(&mut self) -> Result<[u8; N], ErrorStr> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+
+    #[inline]
+    pub fn read_u8(&mut self) -> Result<u8, ErrorStr> {
+        Ok(self.take(1)?[0])
+    }
+
+    #[inline]
+    pub fn read_u16(&mut self) -> Result<u16, ErrorStr> {
+        Ok(u16::from_le_bytes(self.take_array()?))
+    }
+
+    #[inline]
+    pub fn read_u32(&mut self) -> Result<u32, ErrorStr> {
+        Ok(u32::from_le_bytes(self.take_array()?))
+    }
+
+    #[inline]
+    pub fn read_u64(&mut self) -> Result<u64, ErrorStr> {
+        Ok(u64::from_le_bytes(self.take_array()?))
+    }
+
+    #[inline]
+    pub fn read_f32(&mut self) -> Result<f32, ErrorStr> {
+        Ok(f32::from_le_bytes(self.take_array()?))
+    }
+
+    pub fn read_varint(&mut self) -> Result<u64, ErrorStr> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.read_u8()?;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(ErrorStr::new("Varint is longer than 64 bits"))
+    }
+
+    // a varint that has to fit a narrower type, e.g. read_varint_as::<u32>()
+    pub fn read_varint_as<T>(&mut self) -> Result<T, ErrorStr>
+    where
+        T: TryFrom<u64>,
+    {
+        let value = self.read_varint()?;
+        match T::try_from(value) {
+            Ok(value) => Ok(value),
+            Err(_) => Err(ErrorStr::new(format!("Varint {} out of range", value))),
+        }
+    }
+
+    #[inline]
+    pub fn read_zigzag(&mut self) -> Result<i64, ErrorStr> {
+        let value = self.read_varint()?;
+        Ok((value >> 1) as i64 ^ -((value & 1) as i64))
+    }
+
+    // bits above N have to be clear, so a newer writer's extra flags aren't silently dropped
+    pub fn read_flags<const N: usize>(&mut self) -> Result<[bool; N], ErrorStr> {
+        let byte = self.read_u8()?;
+        if N < 8 && byte >> N != 0 {
+            return Err(ErrorStr::new(format!("Unknown flags in {:#010b}", byte)));
+        }
+        let mut flags = [false; N];
+        for (bit, flag) in flags.iter_mut().enumerate() {
+            *flag = byte & (1 << bit) != 0;
+        }
+        Ok(flags)
+    }
+
+    pub fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], ErrorStr> {
+        self.take(len)
+    }
+
+    pub fn read_str(&mut self) -> Result<&'a str, ErrorStr> {
+        let len = match usize::try_from(self.read_varint()?) {
+            Ok(len) => len,
+            Err(_) => return Err(ErrorStr::new("String length out of range")),
+        };
+        match std::str::from_utf8(self.take(len)?) {
+            Ok(value) => Ok(value),
+            Err(error) => Err(ErrorStr::new(format!("Invalid string: {}", error))),
+        }
+    }
+
+    #[inline]
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    #[inline]
+    pub fn remaining(&self) -> usize {
+        self.bytes.len() - self.position
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.remaining() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_values() {
+        let mut writer = ByteWriter::new();
+        writer.write_u8(7);
+        writer.write_u16(0xbeef);
+        writer.write_u32(0xdead_beef);
+        writer.write_u64(u64::MAX - 1);
+        writer.write_f32(-1.5);
+        writer.write_varint(300);
+        writer.write_zigzag(-3);
+        writer.write_flags(&[true, false, true]);
+        writer.write_str("héllo");
+        let bytes = writer.into_bytes();
+        let mut reader = ByteReader::new(&bytes);
+        assert_eq!(reader.read_u8().unwrap(), 7);
+        assert_eq!(reader.read_u16().unwrap(), 0xbeef);
+        assert_eq!(reader.read_u32().unwrap(), 0xdead_beef);
+        assert_eq!(reader.read_u64().unwrap(), u64::MAX - 1);
+        assert_eq!(reader.read_f32().unwrap(), -1.5);
+        assert_eq!(reader.read_varint().unwrap(), 300);
+        assert_eq!(reader.read_zigzag().unwrap(), -3);
+        assert_eq!(reader.read_flags::<3>().unwrap(), [true, false, true]);
+        assert_eq!(reader.read_str().unwrap(), "héllo");
+        assert!(reader.is_empty());
+    }
+
+    #[test]
+    fn rejects_reads_past_the_end() {
+        let mut reader = ByteReader::new(&[1, 2, 3]);
+        assert!(reader.read_u32().is_err());
+        // a failed read consumes nothing
+        assert_eq!(reader.position(), 0);
+        assert_eq!(reader.read_bytes(3).unwrap(), &[1, 2, 3]);
+        assert!(reader.read_u8().is_err());
+    }
+
+    #[test]
+    fn rejects_huge_lengths() {
+        let mut reader = ByteReader::new(&[0]);
+        reader.read_u8().unwrap();
+        assert!(reader.read_bytes(usize::MAX).is_err());
+
+        // a length prefix near u64::MAX mustn't wrap around
+        let mut writer = ByteWriter::new();
+        writer.write_varint(u64::MAX);
+        writer.write_bytes(b"abc");
+        let bytes = writer.into_bytes();
+        assert!(ByteReader::new(&bytes).read_str().is_err());
+    }
+
+    #[test]
+    fn rejects_overlong_varints() {
+        let bytes = [0xff; 11];
+        assert!(ByteReader::new(&bytes).read_varint().is_err());
+    }
+
+    #[test]
+    fn narrows_varints() {
+        let mut writer = ByteWriter::new();
+        writer.write_varint(u32::MAX as u64);
+        writer.write_varint(u32::MAX as u64 + 1);
+        let bytes = writer.into_bytes();
+        let mut reader = ByteReader::new(&bytes);
+        assert_eq!(reader.read_varint_as::<u32>().unwrap(), u32::MAX);
+        assert!(reader.read_varint_as::<u32>().is_err());
+        assert!(ByteReader::new(&bytes).read_varint_as::<u16>().is_err());
+    }
+
+    #[test]
+    fn rejects_unknown_flags() {
+        assert!(ByteReader::new(&[0b1000]).read_flags::<3>().is_err());
+        assert!(ByteReader::new(&[0xff]).read_flags::<8>().is_ok());
+    }
+}
@@ -0,0 +1,45 @@
+use wasm_bindgen::JsCast;
+use web_sys::{Blob, BlobPropertyBag, HtmlAnchorElement, Url};
+
+use crate::libs::types::errors::ErrorStr;
+
+// saves bytes as a file through a temporary download link
+pub fn download_bytes(bytes: &[u8], mime: &str, file_name: &str) -> Result<(), ErrorStr> {
+    let document = if let Some(document) = web_sys::window().and_then(|w| w.document()) {
+        document
+    } else {
+        return Err(ErrorStr::new("No document to download from"));
+    };
+    let parts = js_sys::Array::of1(&js_sys::Uint8Array::from(bytes));
+    let options = BlobPropertyBag::new();
+    options.set_type(mime);
+    let blob = match Blob::new_with_u8_array_sequence_and_options(&parts, &options) {
+        Ok(blob) => blob,
+        Err(error) => {
+            let error = format!("Unable to create blob for {}: {:?}", file_name, error);
+            return Err(ErrorStr::new(error));
+        }
+    };
+    let url = match Url::create_object_url_with_blob(&blob) {
+        Ok(url) => url,
+        Err(error) => {
+            let error = format!("Unable to create url for {}: {:?}", file_name, error);
+            return Err(ErrorStr::new(error));
+        }
+    };
+    let anchor = match document
+        .create_element("a")
+        .map(|element| element.dyn_into::<HtmlAnchorElement>())
+    {
+        Ok(Ok(anchor)) => anchor,
+        _ => {
+            let _ = Url::revoke_object_url(&url);
+            return Err(ErrorStr::new("Unable to create download link"));
+        }
+    };
+    anchor.set_href(&url);
+    anchor.set_download(file_name);
+    anchor.click();
+    let _ = Url::revoke_object_url(&url);
+    Ok(())
+}
@@ -0,0 +1,53 @@
+use std::fmt;
+use std::num::NonZeroU32;
+
+// low bits hold index + 1, high bits a wrapping generation so a despawned id never matches the
+// entity that reuses its slot. packed into a u32 so it still fits the picking target
+const INDEX_BITS: u32 = 20;
+const INDEX_MASK: u32 = (1 << INDEX_BITS) - 1;
+const GENERATION_MASK: u32 = u32::MAX >> INDEX_BITS;
+pub const MAX_ENTITIES: u32 = INDEX_MASK;
+
+// stable id for anything that can be selected or referenced across systems. 0 is reserved for
+// "nothing", which is also what the picking target is cleared to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct EntityId(NonZeroU32);
+
+impl EntityId {
+    #[inline]
+    pub fn new(raw: u32) -> Option<Self> {
+        if raw & INDEX_MASK == 0 {
+            return None;
+        }
+        NonZeroU32::new(raw).map(Self)
+    }
+
+    // index must be below MAX_ENTITIES, the generation is truncated
+    #[inline]
+    pub const fn from_parts(index: u32, generation: u32) -> Self {
+        debug_assert!(index < MAX_ENTITIES);
+        let raw = ((generation & GENERATION_MASK) << INDEX_BITS) | (index + 1);
+        Self(NonZeroU32::new(raw).unwrap())
+    }
+
+    #[inline]
+    pub fn raw(&self) -> u32 {
+        self.0.get()
+    }
+
+    #[inline]
+    pub fn index(&self) -> u32 {
+        (self.0.get() & INDEX_MASK) - 1
+    }
+
+    #[inline]
+    pub fn generation(&self) -> u32 {
+        self.0.get() >> INDEX_BITS
+    }
+}
+
+impl fmt::Display for EntityId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{}v{}", self.index(), self.generation())
+    }
+}
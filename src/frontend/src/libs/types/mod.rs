@@ -1,2 +1,6 @@
+pub mod bytes;
+pub mod download;
+pub mod entity;
 pub mod errors;
+pub mod rng;
 pub mod shared;
@@ -8,5 +8,6 @@ use app::body::Body;
 
 fn main() {
     console_error_panic_hook::set_once();
+    libs::logging::init();
     leptos::mount::mount_to_body(Body);
 }
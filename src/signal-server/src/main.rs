@@ -0,0 +1,274 @@
+mod protocol;
+mod rooms;
+
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use axum::Router;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Json, Response};
+use axum::routing::get;
+use tokio::sync::mpsc;
+
+use crate::rooms::{RoomSummary, Rooms};
+
+const DEFAULT_ADDR: &str = "127.0.0.1:8787";
+const DEFAULT_ROOM_SIZE: usize = 8;
+// signals are SDP and candidates, nothing legitimate comes close
+const MAX_MESSAGE_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Clone)]
+struct AppState {
+    rooms: Arc<Mutex<Rooms>>,
+}
+
+impl AppState {
+    // a panic while holding the lock leaves plain data behind, keep serving
+    fn rooms(&self) -> MutexGuard<'_, Rooms> {
+        match self.rooms.lock() {
+            Ok(rooms) => rooms,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Config {
+    addr: SocketAddr,
+    room_size: usize,
+}
+
+fn usage() -> String {
+    format!(
+        "usage: signal-server [--addr {}] [--room-size {}]",
+        DEFAULT_ADDR, DEFAULT_ROOM_SIZE
+    )
+}
+
+fn parse_args() -> Result<Config, String> {
+    let mut addr = DEFAULT_ADDR.to_string();
+    let mut room_size = DEFAULT_ROOM_SIZE.to_string();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let value = match arg.as_str() {
+            "--addr" => &mut addr,
+            "--room-size" => &mut room_size,
+            _ => return Err(usage()),
+        };
+        *value = if let Some(next) = args.next() {
+            next
+        } else {
+            return Err(usage());
+        };
+    }
+    let addr = match addr.parse() {
+        Ok(addr) => addr,
+        Err(error) => return Err(format!("Bad address {}: {}", addr, error)),
+    };
+    let room_size = match room_size.parse::<usize>() {
+        Ok(room_size) if room_size > 0 => room_size,
+        _ => return Err(format!("Bad room size {}", room_size)),
+    };
+    Ok(Config { addr, room_size })
+}
+
+fn app(room_size: usize) -> Router {
+    let state = AppState {
+        rooms: Arc::new(Mutex::new(Rooms::new(room_size))),
+    };
+    Router::new()
+        .route("/ws", get(websocket))
+        .route("/rooms", get(list_rooms))
+        .route("/rooms/{name}", get(room))
+        .route("/health", get(|| async { "ok" }))
+        .with_state(state)
+}
+
+#[tokio::main]
+async fn main() {
+    let config = match parse_args() {
+        Ok(config) => config,
+        Err(error) => {
+            eprintln!("{}", error);
+            std::process::exit(2);
+        }
+    };
+    let listener = match tokio::net::TcpListener::bind(config.addr).await {
+        Ok(listener) => listener,
+        Err(error) => {
+            eprintln!("Unable to listen on {}: {}", config.addr, error);
+            std::process::exit(1);
+        }
+    };
+    println!("Signaling on ws://{}/ws", config.addr);
+    let shutdown = async {
+        let _ = tokio::signal::ctrl_c().await;
+    };
+    if let Err(error) = axum::serve(listener, app(config.room_size))
+        .with_graceful_shutdown(shutdown)
+        .await
+    {
+        eprintln!("Server stopped: {}", error);
+        std::process::exit(1);
+    }
+}
+
+async fn list_rooms(State(state): State<AppState>) -> Json<Vec<RoomSummary>> {
+    Json(state.rooms().list())
+}
+
+async fn room(State(state): State<AppState>, Path(name): Path<String>) -> Response {
+    match state.rooms().room(&name) {
+        Some(room) => Json(room).into_response(),
+        None => (StatusCode::NOT_FOUND, format!("No room {}", name)).into_response(),
+    }
+}
+
+async fn websocket(State(state): State<AppState>, upgrade: WebSocketUpgrade) -> Response {
+    upgrade
+        .max_message_size(MAX_MESSAGE_SIZE)
+        .on_upgrade(move |socket| connection(socket, state))
+}
+
+// one task per client. reads go straight to Rooms, which answers through the outbox
+async fn connection(mut socket: WebSocket, state: AppState) {
+    let (outbox, mut inbox) = mpsc::unbounded_channel::<String>();
+    let client = state.rooms().connect(outbox);
+    loop {
+        tokio::select! {
+            incoming = socket.recv() => match incoming {
+                Some(Ok(Message::Text(text))) => state.rooms().handle(client, text.as_str()),
+                // the protocol is JSON text, pings are answered by axum
+                Some(Ok(Message::Binary(_) | Message::Ping(_) | Message::Pong(_))) => {}
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+            },
+            outgoing = inbox.recv() => match outgoing {
+                Some(text) => {
+                    if socket.send(Message::Text(text.into())).await.is_err() {
+                        break;
+                    }
+                }
+                None => break,
+            },
+        }
+    }
+    state.rooms().disconnect(client);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures_util::{SinkExt, StreamExt};
+    use serde_json::{Value, json};
+    use tokio::net::TcpStream;
+    use tokio_tungstenite::tungstenite::Message as WsMessage;
+    use tokio_tungstenite::{MaybeTlsStream, WebSocketStream, connect_async};
+
+    use super::*;
+
+    type Client = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+    // long enough for a slow CI box, short enough that a missing message fails the test
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    // the real server on a port the OS picks
+    async fn serve(room_size: usize) -> SocketAddr {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app(room_size)).await });
+        addr
+    }
+
+    async fn connect(addr: SocketAddr) -> Client {
+        let (client, _) = connect_async(format!("ws://{}/ws", addr)).await.unwrap();
+        client
+    }
+
+    async fn send(client: &mut Client, message: Value) {
+        let text = message.to_string();
+        client.send(WsMessage::Text(text.into())).await.unwrap();
+    }
+
+    async fn recv(client: &mut Client) -> Value {
+        loop {
+            let message = tokio::time::timeout(TIMEOUT, client.next())
+                .await
+                .expect("no message in time")
+                .expect("connection closed")
+                .unwrap();
+            if let WsMessage::Text(text) = message {
+                return serde_json::from_str(text.as_str()).unwrap();
+            }
+        }
+    }
+
+    async fn join(client: &mut Client, room: &str, name: &str) -> Value {
+        let join = json!({"v": 1, "type": "join", "room": room, "name": name});
+        send(client, join).await;
+        recv(client).await
+    }
+
+    #[tokio::test]
+    async fn joins_two_clients_and_relays_signals() {
+        let addr = serve(8).await;
+        let mut ada = connect(addr).await;
+        let mut bob = connect(addr).await;
+
+        let joined = join(&mut ada, "lobby", "Ada").await;
+        assert_eq!(
+            joined,
+            json!({"v": 1, "type": "joined", "room": "lobby", "id": 1, "mode": "rollback", "peers": []})
+        );
+        let joined = join(&mut bob, "lobby", "Bob").await;
+        assert_eq!(joined["id"], 2);
+        assert_eq!(joined["peers"], json!([{"id": 1, "name": "Ada"}]));
+        assert_eq!(
+            recv(&mut ada).await,
+            json!({"v": 1, "type": "peer_joined", "peer": {"id": 2, "name": "Bob"}})
+        );
+
+        send(
+            &mut bob,
+            json!({"v": 1, "type": "offer", "to": 1, "sdp": "offer"}),
+        )
+        .await;
+        assert_eq!(
+            recv(&mut ada).await,
+            json!({"v": 1, "type": "offer", "from": 2, "sdp": "offer"})
+        );
+        send(
+            &mut ada,
+            json!({"v": 1, "type": "answer", "to": 2, "sdp": "answer"}),
+        )
+        .await;
+        assert_eq!(
+            recv(&mut bob).await,
+            json!({"v": 1, "type": "answer", "from": 1, "sdp": "answer"})
+        );
+
+        // closing the socket counts as leaving
+        bob.close(None).await.unwrap();
+        assert_eq!(
+            recv(&mut ada).await,
+            json!({"v": 1, "type": "peer_left", "id": 2})
+        );
+    }
+
+    #[tokio::test]
+    async fn turns_away_full_rooms_and_long_names() {
+        let addr = serve(1).await;
+        let mut ada = connect(addr).await;
+        let mut bob = connect(addr).await;
+        assert_eq!(join(&mut ada, "lobby", "Ada").await["type"], "joined");
+        assert_eq!(join(&mut bob, "lobby", "Bob").await["code"], "room_full");
+
+        let name = "n".repeat(protocol::MAX_NAME_LEN + 1);
+        assert_eq!(join(&mut bob, "other", &name).await["code"], "bad_message");
+        let room = "r".repeat(protocol::MAX_ROOM_LEN + 1);
+        assert_eq!(join(&mut bob, &room, "Bob").await["code"], "bad_message");
+        assert_eq!(join(&mut bob, "other", "Bob").await["type"], "joined");
+    }
+}
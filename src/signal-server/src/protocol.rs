@@ -0,0 +1,273 @@
+use serde::{Deserialize, Serialize};
+
+// has to match PROTOCOL_VERSION in the frontend's libs::net::signaling::protocol
+pub const PROTOCOL_VERSION: u32 = 1;
+// in bytes, the same limits as the frontend's
+pub const MAX_ROOM_LEN: usize = 64;
+pub const MAX_NAME_LEN: usize = 32;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Envelope<T> {
+    v: u32,
+    #[serde(flatten)]
+    message: T,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct PeerId(pub u32);
+
+// chosen by whoever creates a room, the server only passes it on
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncMode {
+    #[default]
+    Rollback,
+    Authoritative,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PeerInfo {
+    pub id: PeerId,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Signal {
+    Offer {
+        sdp: String,
+    },
+    Answer {
+        sdp: String,
+    },
+    Candidate {
+        candidate: String,
+        sdp_mid: Option<String>,
+        sdp_m_line_index: Option<u16>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    Join {
+        room: String,
+        name: String,
+        #[serde(default)]
+        mode: SyncMode,
+    },
+    Offer {
+        to: PeerId,
+        sdp: String,
+    },
+    Answer {
+        to: PeerId,
+        sdp: String,
+    },
+    Candidate {
+        to: PeerId,
+        candidate: String,
+        sdp_mid: Option<String>,
+        sdp_m_line_index: Option<u16>,
+    },
+    Leave,
+}
+
+impl ClientMessage {
+    // the recipient and what to relay to it, if this is a signal
+    pub fn into_signal(self) -> Result<(PeerId, Signal), Self> {
+        match self {
+            Self::Offer { to, sdp } => Ok((to, Signal::Offer { sdp })),
+            Self::Answer { to, sdp } => Ok((to, Signal::Answer { sdp })),
+            Self::Candidate {
+                to,
+                candidate,
+                sdp_mid,
+                sdp_m_line_index,
+            } => Ok((
+                to,
+                Signal::Candidate {
+                    candidate,
+                    sdp_mid,
+                    sdp_m_line_index,
+                },
+            )),
+            message => Err(message),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    Version,
+    RoomFull,
+    UnknownPeer,
+    BadMessage,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    Joined {
+        room: String,
+        id: PeerId,
+        #[serde(default)]
+        mode: SyncMode,
+        peers: Vec<PeerInfo>,
+    },
+    PeerJoined {
+        peer: PeerInfo,
+    },
+    PeerLeft {
+        id: PeerId,
+    },
+    Offer {
+        from: PeerId,
+        sdp: String,
+    },
+    Answer {
+        from: PeerId,
+        sdp: String,
+    },
+    Candidate {
+        from: PeerId,
+        candidate: String,
+        sdp_mid: Option<String>,
+        sdp_m_line_index: Option<u16>,
+    },
+    Error {
+        code: ErrorCode,
+        message: String,
+    },
+}
+
+impl ServerMessage {
+    pub fn signal(from: PeerId, signal: Signal) -> Self {
+        match signal {
+            Signal::Offer { sdp } => Self::Offer { from, sdp },
+            Signal::Answer { sdp } => Self::Answer { from, sdp },
+            Signal::Candidate {
+                candidate,
+                sdp_mid,
+                sdp_m_line_index,
+            } => Self::Candidate {
+                from,
+                candidate,
+                sdp_mid,
+                sdp_m_line_index,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Version {
+    v: u32,
+}
+
+pub fn version(text: &str) -> Option<u32> {
+    serde_json::from_str::<Version>(text)
+        .ok()
+        .map(|version| version.v)
+}
+
+pub fn encode(message: &ServerMessage) -> String {
+    let envelope = Envelope {
+        v: PROTOCOL_VERSION,
+        message,
+    };
+    // plain data, serialising can't fail
+    serde_json::to_string(&envelope).unwrap_or_default()
+}
+
+pub fn decode(text: &str) -> Result<ClientMessage, String> {
+    match version(text) {
+        Some(PROTOCOL_VERSION) => {}
+        Some(version) => {
+            return Err(format!(
+                "Protocol version {} is not supported, the server speaks {}",
+                version, PROTOCOL_VERSION
+            ));
+        }
+        None => return Err("Message has no protocol version".to_string()),
+    }
+    match serde_json::from_str::<Envelope<ClientMessage>>(text) {
+        Ok(envelope) => Ok(envelope.message),
+        Err(error) => Err(format!("Bad message: {}", error)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use serde::de::DeserializeOwned;
+    use serde_json::Value;
+
+    use super::*;
+
+    // the frontend checks the same files against its own types, so the two can't drift apart
+    const CLIENT_FIXTURES: &str = include_str!("../../fixtures/signaling/client.json");
+    const SERVER_FIXTURES: &str = include_str!("../../fixtures/signaling/server.json");
+    const CLIENT_TYPES: [&str; 5] = ["join", "offer", "answer", "candidate", "leave"];
+    const SERVER_TYPES: [&str; 7] = [
+        "joined",
+        "peer_joined",
+        "peer_left",
+        "offer",
+        "answer",
+        "candidate",
+        "error",
+    ];
+
+    fn fixtures(text: &str) -> Vec<Value> {
+        serde_json::from_str(text).unwrap()
+    }
+
+    fn types(fixtures: &[Value]) -> BTreeSet<&str> {
+        fixtures
+            .iter()
+            .map(|fixture| fixture["type"].as_str().unwrap())
+            .collect()
+    }
+
+    // has to come back out exactly as it went in
+    fn round_trip<T>(fixture: &Value)
+    where
+        T: Serialize + DeserializeOwned,
+    {
+        let envelope: Envelope<T> = serde_json::from_value(fixture.clone()).unwrap();
+        assert_eq!(&serde_json::to_value(&envelope).unwrap(), fixture);
+    }
+
+    #[test]
+    fn reads_client_fixtures() {
+        let fixtures = fixtures(CLIENT_FIXTURES);
+        assert_eq!(types(&fixtures), BTreeSet::from(CLIENT_TYPES));
+        for fixture in fixtures.iter() {
+            decode(&fixture.to_string()).unwrap();
+            round_trip::<ClientMessage>(fixture);
+        }
+    }
+
+    #[test]
+    fn writes_server_fixtures() {
+        let fixtures = fixtures(SERVER_FIXTURES);
+        assert_eq!(types(&fixtures), BTreeSet::from(SERVER_TYPES));
+        for fixture in fixtures.iter() {
+            let envelope: Envelope<ServerMessage> =
+                serde_json::from_value(fixture.clone()).unwrap();
+            let encoded: Value = serde_json::from_str(&encode(&envelope.message)).unwrap();
+            assert_eq!(&encoded, fixture);
+        }
+    }
+
+    #[test]
+    fn rejects_other_versions() {
+        assert!(decode(r#"{"v": 2, "type": "leave"}"#).is_err());
+        assert!(decode(r#"{"type": "leave"}"#).is_err());
+        assert!(decode(r#"{"v": 1, "type": "shout"}"#).is_err());
+    }
+}
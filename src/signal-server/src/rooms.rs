@@ -0,0 +1,245 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::protocol::{
+    self, ClientMessage, ErrorCode, MAX_NAME_LEN, MAX_ROOM_LEN, PROTOCOL_VERSION, PeerId, PeerInfo,
+    ServerMessage, Signal, SyncMode,
+};
+
+pub type ClientId = u64;
+
+#[derive(Debug)]
+struct Client {
+    // encoded messages, written to the socket by the connection's task
+    outbox: UnboundedSender<String>,
+    member: Option<Member>,
+}
+
+#[derive(Debug, Clone)]
+struct Member {
+    room: String,
+    info: PeerInfo,
+    // the room's, copied to everyone in it
+    mode: SyncMode,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RoomSummary {
+    pub name: String,
+    pub mode: SyncMode,
+    pub peers: Vec<PeerInfo>,
+    pub capacity: usize,
+}
+
+// every connected client and the rooms they're in. rooms exist while someone is in them
+#[derive(Debug)]
+pub struct Rooms {
+    room_size: usize,
+    next_client: ClientId,
+    // unique across rooms, never reused while the server runs
+    next_peer: u32,
+    clients: BTreeMap<ClientId, Client>,
+}
+
+impl Rooms {
+    pub fn new(room_size: usize) -> Self {
+        Self {
+            room_size,
+            next_client: 0,
+            next_peer: 0,
+            clients: BTreeMap::new(),
+        }
+    }
+
+    pub fn connect(&mut self, outbox: UnboundedSender<String>) -> ClientId {
+        self.next_client += 1;
+        let client = Client {
+            outbox,
+            member: None,
+        };
+        self.clients.insert(self.next_client, client);
+        self.next_client
+    }
+
+    pub fn disconnect(&mut self, client: ClientId) {
+        self.leave(client);
+        self.clients.remove(&client);
+    }
+
+    pub fn handle(&mut self, client: ClientId, text: &str) {
+        if protocol::version(text).is_some_and(|version| version != PROTOCOL_VERSION) {
+            let message = format!("Server speaks protocol version {}", PROTOCOL_VERSION);
+            self.error(client, ErrorCode::Version, message);
+            return;
+        }
+        let message = match protocol::decode(text) {
+            Ok(message) => message,
+            Err(error) => {
+                self.error(client, ErrorCode::BadMessage, error);
+                return;
+            }
+        };
+        let message = match message.into_signal() {
+            Ok((to, signal)) => {
+                self.relay(client, to, signal);
+                return;
+            }
+            Err(message) => message,
+        };
+        match message {
+            ClientMessage::Join { room, name, mode } => self.join(client, room, name, mode),
+            ClientMessage::Leave => self.leave(client),
+            // taken by into_signal above
+            ClientMessage::Offer { .. }
+            | ClientMessage::Answer { .. }
+            | ClientMessage::Candidate { .. } => {}
+        }
+    }
+
+    // rooms with at least one peer, by name
+    pub fn list(&self) -> Vec<RoomSummary> {
+        let mut rooms: BTreeMap<&str, RoomSummary> = BTreeMap::new();
+        for member in self.clients.values().filter_map(|c| c.member.as_ref()) {
+            let room = rooms.entry(&member.room).or_insert_with(|| RoomSummary {
+                name: member.room.clone(),
+                mode: member.mode,
+                peers: Vec::new(),
+                capacity: self.room_size,
+            });
+            room.peers.push(member.info.clone());
+        }
+        for room in rooms.values_mut() {
+            room.peers.sort_by_key(|peer| peer.id);
+        }
+        rooms.into_values().collect()
+    }
+
+    pub fn room(&self, name: &str) -> Option<RoomSummary> {
+        self.list().into_iter().find(|room| room.name == name)
+    }
+
+    fn push(&self, client: ClientId, message: &ServerMessage) {
+        if let Some(client) = self.clients.get(&client) {
+            // the receiver is gone when the connection is closing, disconnect follows
+            let _ = client.outbox.send(protocol::encode(message));
+        }
+    }
+
+    fn error<S>(&self, client: ClientId, code: ErrorCode, message: S)
+    where
+        S: Into<String>,
+    {
+        let message = ServerMessage::Error {
+            code,
+            message: message.into(),
+        };
+        self.push(client, &message);
+    }
+
+    // clients in the room other than `except`
+    fn members(&self, room: &str, except: ClientId) -> Vec<(ClientId, Member)> {
+        self.clients
+            .iter()
+            .filter(|(id, _)| **id != except)
+            .filter_map(|(id, client)| match client.member.as_ref() {
+                Some(member) if member.room == room => Some((*id, member.clone())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn join(&mut self, client: ClientId, room: String, name: String, mode: SyncMode) {
+        if self
+            .clients
+            .get(&client)
+            .is_some_and(|c| c.member.is_some())
+        {
+            self.error(client, ErrorCode::BadMessage, "Already in a room");
+            return;
+        }
+        if room.is_empty() {
+            self.error(client, ErrorCode::BadMessage, "Room names can't be empty");
+            return;
+        }
+        // both end up in every other member's messages and in /rooms
+        if room.len() > MAX_ROOM_LEN || name.len() > MAX_NAME_LEN {
+            let message = format!(
+                "Room names are limited to {} bytes and peer names to {}",
+                MAX_ROOM_LEN, MAX_NAME_LEN
+            );
+            self.error(client, ErrorCode::BadMessage, message);
+            return;
+        }
+        let others = self.members(&room, client);
+        if others.len() >= self.room_size {
+            let message = format!("Room {} is full", room);
+            self.error(client, ErrorCode::RoomFull, message);
+            return;
+        }
+        self.next_peer += 1;
+        let info = PeerInfo {
+            id: PeerId(self.next_peer),
+            name,
+        };
+        // whoever created the room decided
+        let mode = others.first().map_or(mode, |(_, other)| other.mode);
+        for (other, _) in others.iter() {
+            let message = ServerMessage::PeerJoined { peer: info.clone() };
+            self.push(*other, &message);
+        }
+        if let Some(joined) = self.clients.get_mut(&client) {
+            joined.member = Some(Member {
+                room: room.clone(),
+                info: info.clone(),
+                mode,
+            });
+        }
+        let mut peers: Vec<PeerInfo> = others.into_iter().map(|(_, other)| other.info).collect();
+        peers.sort_by_key(|peer| peer.id);
+        println!("{} ({}) joined room {}", info.id.0, info.name, room);
+        let message = ServerMessage::Joined {
+            room,
+            id: info.id,
+            mode,
+            peers,
+        };
+        self.push(client, &message);
+    }
+
+    fn relay(&self, client: ClientId, to: PeerId, signal: Signal) {
+        let (room, from) = match self.clients.get(&client).and_then(|c| c.member.as_ref()) {
+            Some(member) => (member.room.as_str(), member.info.id),
+            None => {
+                self.error(client, ErrorCode::BadMessage, "Join a room first");
+                return;
+            }
+        };
+        let target = self
+            .members(room, client)
+            .into_iter()
+            .find(|(_, other)| other.info.id == to);
+        match target {
+            Some((target, _)) => self.push(target, &ServerMessage::signal(from, signal)),
+            None => {
+                let message = format!("No peer {} in room {}", to.0, room);
+                self.error(client, ErrorCode::UnknownPeer, message);
+            }
+        }
+    }
+
+    fn leave(&mut self, client: ClientId) {
+        let member = self
+            .clients
+            .get_mut(&client)
+            .and_then(|client| client.member.take());
+        if let Some(member) = member {
+            println!("{} left room {}", member.info.id.0, member.room);
+            for (other, _) in self.members(&member.room, client) {
+                let message = ServerMessage::PeerLeft { id: member.info.id };
+                self.push(other, &message);
+            }
+        }
+    }
+}